@@ -0,0 +1,105 @@
+//! Embeds build provenance for `onsm --version-json` (see `util::version`):
+//! git commit and describe (or "unknown" outside a git checkout), build time,
+//! rustc version and enabled cargo features.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let git_dir = Path::new(&manifest_dir).join(".git");
+    // only our own checkout: a crates.io tarball unpacked inside some other
+    // repository must not pick up that repository's commit
+    let in_git = git_dir.exists();
+    if in_git {
+        for f in ["HEAD", "index", "refs/heads", "refs/tags", "packed-refs"] {
+            let p = git_dir.join(f);
+            if p.exists() {
+                println!("cargo:rerun-if-changed={}", p.display());
+            }
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let git = |args: &[&str]| {
+        in_git
+            .then(|| run("git", args, &manifest_dir))
+            .flatten()
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+    set("ONSM_GIT_COMMIT", &git(&["rev-parse", "HEAD"]));
+    set(
+        "ONSM_GIT_DESCRIBE",
+        &git(&["describe", "--tags", "--always", "--dirty"]),
+    );
+
+    // reproducible builds set SOURCE_DATE_EPOCH
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    set("ONSM_BUILD_TIMESTAMP", &rfc3339(secs));
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    set(
+        "ONSM_RUSTC_VERSION",
+        &run(&rustc, &["--version"], &manifest_dir).unwrap_or_else(|| "unknown".to_string()),
+    );
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| {
+            k.strip_prefix("CARGO_FEATURE_")
+                .map(|f| f.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    set("ONSM_FEATURES", &features.join(","));
+}
+
+fn set(key: &str, value: &str) {
+    println!("cargo:rustc-env={key}={value}");
+}
+
+/// First line of a command's stdout, if it ran and succeeded.
+fn run(bin: &str, args: &[&str], dir: &str) -> Option<String> {
+    let out = Command::new(bin)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let s = String::from_utf8_lossy(&out.stdout);
+    let line = s.lines().next()?.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+/// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ` (UTC).
+fn rfc3339(secs: u64) -> String {
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // civil-from-days (Howard Hinnant)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!(
+        "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,386 @@
+//! How far each nuclear end of a locus could be from where the alignments put
+//! it: `nuc_start_ci` / `nuc_end_ci` in pairs.tsv (± bp), and the intervals
+//! `--bed-bounds` exports for masking.
+//!
+//! Three signals go in, each in bp at one end:
+//!   * reciprocal disagreement: the mito→nuclear record's end against that of
+//!     its reciprocal nuclear→mito hit (reciprocal pairing only);
+//!   * alternative alignments: the ends of merged, collapsed or clustered
+//!     alignments that cover the same stretch as the one the locus end came
+//!     from (overlapping it by at least `ALTERNATIVE_MIN_OVERLAP` of the
+//!     shorter), rather than continuing it as the next fragment does;
+//!   * with `classify --boundary-clips`, the dispersion of read clip positions
+//!     around the end (median absolute deviation), from the reads→nuclear BAM.
+//!
+//! They are taken as independent errors: the root of the sum of their
+//! squares, rounded up. A locus carries its estimate through merging and
+//! collapsing, so a signal seen early is not lost.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::io::bam::{self, Window};
+use crate::io::bed::{self, BedRecord};
+use crate::model::PairedLocus;
+use crate::summary::Tier;
+
+/// Alignments overlapping by this fraction of the shorter are alternatives
+/// for the same stretch; less, and one continues the other.
+pub const ALTERNATIVE_MIN_OVERLAP: f32 = 0.5;
+/// `--boundary-clips`: reads clipped within this many bp of an end…
+pub const CLIP_WINDOW_BP: u32 = 250;
+/// …by at least this many bases…
+pub const MIN_CLIP_BP: u32 = 20;
+/// …and at least this many of them, or the clips say nothing.
+pub const MIN_CLIP_READS: usize = 3;
+
+/// `sqrt(a² + b² + …)`, rounded up.
+pub fn combine(terms: &[u32]) -> u32 {
+    let sq: f64 = terms.iter().map(|&t| f64::from(t).powi(2)).sum();
+    sq.sqrt().ceil() as u32
+}
+
+/// Nuclear start and end disagreement between a mito→nuclear locus and the
+/// locus of its reciprocal nuclear→mito hit.
+pub fn reciprocal(fwd: &PairedLocus, rev: &PairedLocus) -> (u32, u32) {
+    (
+        fwd.nuc_start.abs_diff(rev.nuc_start),
+        fwd.nuc_end.abs_diff(rev.nuc_end),
+    )
+}
+
+/// Whether `a` and `b` cover the same nuclear stretch.
+fn alternatives(a: &PairedLocus, b: &PairedLocus) -> bool {
+    let shared = a
+        .nuc_end
+        .min(b.nuc_end)
+        .saturating_sub(a.nuc_start.max(b.nuc_start));
+    let shorter = (a.nuc_end - a.nuc_start)
+        .min(b.nuc_end - b.nuc_start)
+        .max(1);
+    shared as f32 >= ALTERNATIVE_MIN_OVERLAP * shorter as f32
+}
+
+/// CI at one end: the largest CI and end offset among the alternatives of
+/// `anchor`, the member whose end the locus took.
+fn end_ci(
+    anchor: &PairedLocus,
+    members: &[&PairedLocus],
+    end: impl Fn(&PairedLocus) -> u32,
+    ci: impl Fn(&PairedLocus) -> u32,
+) -> u32 {
+    let (mut spread, mut own) = (0, ci(anchor));
+    for m in members.iter().filter(|m| alternatives(anchor, m)) {
+        spread = spread.max(end(m).abs_diff(end(anchor)));
+        own = own.max(ci(m));
+    }
+    combine(&[own, spread])
+}
+
+/// `(nuc_start_ci, nuc_end_ci)` of a locus with the coordinates of `anchor`,
+/// made of `members` (`anchor` among them), e.g. the loci collapsed into it.
+pub fn from_members(anchor: &PairedLocus, members: &[&PairedLocus]) -> (u32, u32) {
+    (
+        end_ci(anchor, members, |l| l.nuc_start, |l| l.nuc_start_ci),
+        end_ci(anchor, members, |l| l.nuc_end, |l| l.nuc_end_ci),
+    )
+}
+
+/// `(nuc_start_ci, nuc_end_ci)` of the hull of `members` (merged fragments,
+/// a cluster): each end is anchored on the member that reaches furthest.
+pub fn from_hull(members: &[&PairedLocus]) -> (u32, u32) {
+    let Some(first) = members.iter().min_by_key(|l| l.nuc_start) else {
+        return (0, 0);
+    };
+    let last = members.iter().max_by_key(|l| l.nuc_end).unwrap();
+    (
+        end_ci(first, members, |l| l.nuc_start, |l| l.nuc_start_ci),
+        end_ci(last, members, |l| l.nuc_end, |l| l.nuc_end_ci),
+    )
+}
+
+/// Median absolute deviation of clip positions from their median; `None`
+/// below `MIN_CLIP_READS` of them.
+pub fn clip_dispersion(mut positions: Vec<u32>) -> Option<u32> {
+    if positions.len() < MIN_CLIP_READS {
+        return None;
+    }
+    let median = |v: &mut Vec<u32>| {
+        v.sort_unstable();
+        v[(v.len() - 1) / 2]
+    };
+    let m = median(&mut positions);
+    let mut dev: Vec<u32> = positions.iter().map(|&p| p.abs_diff(m)).collect();
+    Some(median(&mut dev))
+}
+
+/// `--boundary-clips`: add the clip dispersion around each nuclear end,
+/// from `bam` (reads→nuclear; a CRAM decoded against `reference`), to the
+/// pairs' CIs.
+pub fn add_clip_dispersion(
+    pairs: &mut [PairedLocus],
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+) -> Result<()> {
+    let mut n_piled = 0;
+    for p in pairs.iter_mut() {
+        for (at, ci) in [
+            (p.nuc_start, &mut p.nuc_start_ci),
+            (p.nuc_end, &mut p.nuc_end_ci),
+        ] {
+            let w = Window {
+                start: at as i32 - CLIP_WINDOW_BP as i32,
+                end: at as i32 + CLIP_WINDOW_BP as i32,
+            };
+            let clips =
+                bam::clip_positions(samtools, bam, reference, &p.nuc_contig, w, MIN_CLIP_BP)?;
+            if let Some(d) = clip_dispersion(clips) {
+                *ci = combine(&[*ci, d]);
+                n_piled += 1;
+            }
+        }
+    }
+    log::info!(
+        "--boundary-clips: {n_piled} of {} locus ends have at least {MIN_CLIP_READS} reads clipped within {CLIP_WINDOW_BP} bp",
+        2 * pairs.len()
+    );
+    Ok(())
+}
+
+/// Which interval of a locus `--bed-bounds` exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum BedBounds {
+    /// Each end moved out by its CI: conservative, for masking.
+    Outer,
+    /// Each end moved in by its CI: only what is surely inserted.
+    Inner,
+    /// The aligned coordinates.
+    Point,
+}
+
+impl BedBounds {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BedBounds::Outer => "outer",
+            BedBounds::Inner => "inner",
+            BedBounds::Point => "point",
+        }
+    }
+
+    /// `pairs_nuc.<bounds>.bed`
+    pub fn file_name(self) -> String {
+        format!("pairs_nuc.{}.bed", self.as_str())
+    }
+
+    /// The nuclear interval of `l`; `Outer` stops at `contig_len` when known.
+    /// `None` for an `Inner` interval the CIs leave nothing of.
+    pub fn interval(self, l: &PairedLocus, contig_len: Option<u64>) -> Option<(u32, u32)> {
+        match self {
+            BedBounds::Point => Some((l.nuc_start, l.nuc_end)),
+            BedBounds::Outer => {
+                let end = l.nuc_end.saturating_add(l.nuc_end_ci);
+                let end = contig_len.map_or(end, |len| end.min(len.min(u32::MAX as u64) as u32));
+                Some((l.nuc_start.saturating_sub(l.nuc_start_ci), end))
+            }
+            BedBounds::Inner => {
+                let (s, e) = (
+                    l.nuc_start.saturating_add(l.nuc_start_ci),
+                    l.nuc_end.saturating_sub(l.nuc_end_ci),
+                );
+                (s < e).then_some((s, e))
+            }
+        }
+    }
+}
+
+/// The pairs written to the `--bed-bounds` files, collected as they are
+/// scored.
+#[derive(Debug, Default)]
+pub struct BoundsBedBuilder {
+    bounds: Vec<BedBounds>,
+    tier: Tier,
+    loci: Vec<PairedLocus>,
+}
+
+impl BoundsBedBuilder {
+    pub fn new(bounds: &[BedBounds]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        Self {
+            bounds,
+            tier: Tier::All,
+            loci: Vec::new(),
+        }
+    }
+
+    /// Only write the pairs of `tier` (`--bed-tier`).
+    pub fn with_tier(mut self, tier: Tier) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    pub fn add(&mut self, l: &PairedLocus, high_confidence: bool) {
+        if !self.bounds.is_empty() && self.tier.includes(high_confidence) {
+            self.loci.push(l.clone());
+        }
+    }
+
+    /// One BED per bounds in `out_dir`, named `pair_id`, score = identity ×
+    /// 1000 as in `onsm pair --bed`.
+    pub fn write(&self, out_dir: &Path, nuc_lens: &BTreeMap<String, u64>) -> Result<()> {
+        for &b in &self.bounds {
+            let mut n_empty = 0;
+            let records: Vec<BedRecord> = self
+                .loci
+                .iter()
+                .filter_map(|l| {
+                    let len = nuc_lens.get(&l.nuc_contig).copied();
+                    let Some((start, end)) = b.interval(l, len) else {
+                        n_empty += 1;
+                        return None;
+                    };
+                    Some(BedRecord {
+                        name: Some(l.pair_id.clone()),
+                        score: Some((1000.0 * l.aln_ident).round() as f64),
+                        strand: Some(l.strand.as_char()),
+                        ..BedRecord::new(&l.nuc_contig, start, end)
+                    })
+                })
+                .collect();
+            if n_empty > 0 {
+                log::info!(
+                    "{}: {n_empty} loci left out, shorter than their CIs",
+                    b.file_name()
+                );
+            }
+            bed::write_bed(&out_dir.join(b.file_name()), &records)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+    use tempfile::TempDir;
+
+    fn locus(start: u32, end: u32, ci: (u32, u32)) -> PairedLocus {
+        PairedLocus {
+            pair_id: "P000001".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: start,
+            nuc_end: end,
+            mito_contig: "mt".into(),
+            mito_start: 0,
+            mito_end: end - start,
+            aln_len: end - start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: ci.0,
+            nuc_end_ci: ci.1,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn signals_combine_as_independent_errors() {
+        assert_eq!(combine(&[]), 0);
+        assert_eq!(combine(&[0, 7]), 7);
+        assert_eq!(combine(&[30, 40]), 50);
+        // rounded up: sqrt(2) bp is 2 bp
+        assert_eq!(combine(&[1, 1]), 2);
+        let fwd = locus(1000, 2000, (0, 0));
+        assert_eq!(reciprocal(&fwd, &locus(1012, 1995, (0, 0))), (12, 5));
+    }
+
+    #[test]
+    fn alternative_alignments_spread_the_ends() {
+        // two placements of the same block, 30 bp apart at the start and 40
+        // at the end, the second with a reciprocal disagreement of 9 bp
+        let a = locus(1000, 2000, (0, 0));
+        let b = locus(1030, 2040, (9, 0));
+        assert_eq!(from_hull(&[&a, &b]), (combine(&[9, 30]), 40));
+        assert_eq!(from_members(&a, &[&a, &b]), (combine(&[9, 30]), 40));
+
+        // consecutive fragments overlapping by 100 of 1000 bp continue each
+        // other: the hull's ends are each fragment's own
+        let c = locus(1900, 2900, (0, 6));
+        assert_eq!(from_hull(&[&a, &c]), (0, 6));
+        // …and a third fragment that is an alternative of `c` only
+        let d = locus(1950, 2880, (0, 0));
+        assert_eq!(from_hull(&[&a, &c, &d]), (0, combine(&[6, 20])));
+        assert_eq!(from_hull(&[]), (0, 0));
+        assert_eq!(from_hull(&[&b]), (9, 0));
+    }
+
+    #[test]
+    fn clip_dispersion_needs_a_pileup() {
+        assert_eq!(clip_dispersion(vec![1000, 1010]), None);
+        assert_eq!(clip_dispersion(vec![1000, 1000, 1000]), Some(0));
+        // median 1008, deviations 8 4 0 2 92 → 4
+        assert_eq!(clip_dispersion(vec![1000, 1004, 1008, 1010, 1100]), Some(4));
+    }
+
+    #[test]
+    fn bed_bounds_intervals() {
+        let l = locus(1000, 2000, (30, 50));
+        assert_eq!(BedBounds::Point.interval(&l, None), Some((1000, 2000)));
+        assert_eq!(BedBounds::Outer.interval(&l, None), Some((970, 2050)));
+        assert_eq!(BedBounds::Outer.interval(&l, Some(2020)), Some((970, 2020)));
+        assert_eq!(BedBounds::Inner.interval(&l, None), Some((1030, 1950)));
+        // at the contig start, and a locus shorter than its CIs
+        let l = locus(10, 60, (30, 30));
+        assert_eq!(BedBounds::Outer.interval(&l, None), Some((0, 90)));
+        assert_eq!(BedBounds::Inner.interval(&l, None), None);
+    }
+
+    #[test]
+    fn bed_files_per_bounds() {
+        let td = TempDir::new().unwrap();
+        let mut b = BoundsBedBuilder::new(&[BedBounds::Inner, BedBounds::Outer, BedBounds::Inner]);
+        let mut short = locus(10, 60, (30, 30));
+        short.pair_id = "P000002".into();
+        b.add(&locus(1000, 2000, (30, 50)), true);
+        b.add(&short, false);
+        let lens = BTreeMap::from([("chr1".to_string(), 2020)]);
+        b.write(td.path(), &lens).unwrap();
+        let read = |f: BedBounds| fs_err::read_to_string(td.path().join(f.file_name())).unwrap();
+        assert_eq!(
+            read(BedBounds::Outer),
+            "chr1\t970\t2020\tP000001\t950\t+\nchr1\t0\t90\tP000002\t950\t+\n"
+        );
+        assert_eq!(
+            read(BedBounds::Inner),
+            "chr1\t1030\t1950\tP000001\t950\t+\n"
+        );
+        assert!(!td.path().join(BedBounds::Point.file_name()).exists());
+
+        // the high tier leaves out the pairs below the high-confidence threshold
+        let mut high = BoundsBedBuilder::new(&[BedBounds::Point]).with_tier(Tier::High);
+        high.add(&locus(1000, 2000, (30, 50)), true);
+        high.add(&short, false);
+        high.write(td.path(), &lens).unwrap();
+        assert_eq!(
+            read(BedBounds::Point),
+            "chr1\t1000\t2000\tP000001\t950\t+\n"
+        );
+
+        // nothing asked for, nothing kept
+        let mut none = BoundsBedBuilder::new(&[]);
+        none.add(&short, true);
+        assert!(none.loci.is_empty());
+    }
+}
@@ -0,0 +1,512 @@
+//! Run bundles: one file holding everything needed to look at a run and
+//! rescore it, without the read BAMs.
+//!
+//! A bundle (`.onsm` by convention) is a gzipped tar (`io::tar`) of the run
+//! directory's own files (manifest, pairs.tsv, classification.tsv,
+//! results.json, coverage.json, the summary, warnings.json and the rest of
+//! the top level but index artifacts, see `indexes`),
+//! `tmp/evidence_cache.jsonl` when the run kept it, and with `--sequences`
+//! `loci.fa`, each pair's nuclear and mito sequence cut from the assemblies. `bundle_manifest.json`, the last member, lists every other
+//! member with its size and FNV-1a checksum. Unpacking checks them all, so a
+//! truncated or edited bundle is refused rather than rescored.
+//!
+//! `reuse --from`, `explain --from` and `stats --from` take a bundle wherever
+//! they take a run directory: it is unpacked to a temporary directory
+//! (`RunDir`) for the command's lifetime. `import-bundle` unpacks it for good.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::indexes;
+use crate::io::fasta;
+use crate::io::tar::{TarReader, TarWriter};
+use crate::io::tmpfiles;
+use crate::io::window_cache::{fnv1a, FNV_OFFSET};
+use crate::model::RunManifest;
+use crate::scoring;
+use crate::util::runlock::LOCK_FILE;
+use crate::util::version::VersionInfo;
+
+pub const BUNDLE_MANIFEST_FILE: &str = "bundle_manifest.json";
+/// Locus sequences (`--sequences`).
+pub const LOCI_FASTA: &str = "loci.fa";
+/// Layout version of the bundle; readers refuse newer ones.
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// One member of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleMember {
+    /// Relative to the run directory, `/`-separated.
+    pub path: String,
+    pub bytes: u64,
+    /// `fnv1a64:<hex>`, as `--post-summary` reports them.
+    pub checksum: String,
+}
+
+/// `bundle_manifest.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: u32,
+    /// The onsm that packed it.
+    pub build_info: VersionInfo,
+    /// The run directory it was packed from.
+    pub run_dir: PathBuf,
+    pub members: Vec<BundleMember>,
+}
+
+impl BundleManifest {
+    /// Bytes of all members, unpacked.
+    pub fn total_bytes(&self) -> u64 {
+        self.members.iter().map(|m| m.bytes).sum()
+    }
+}
+
+/// FNV-1a over everything read through it.
+struct Hashing<R> {
+    inner: R,
+    hash: u64,
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hash = fnv1a(&buf[..n], self.hash);
+        Ok(n)
+    }
+}
+
+fn checksum_str(hash: u64) -> String {
+    format!("fnv1a64:{hash:016x}")
+}
+
+/// The files of `run` a bundle holds, relative to it: the top level except
+/// the run lock and the manifest of a bundle it was unpacked from, and the
+/// evidence cache in `tmp/` (or a compressed copy).
+pub fn member_paths(run: &Path) -> Result<Vec<String>> {
+    if !run.join("run_manifest.json").exists() {
+        bail!(
+            "{} has no run_manifest.json (is this an onsm run directory?)",
+            run.display()
+        );
+    }
+    let mut names = Vec::new();
+    for e in fs_err::read_dir(run)? {
+        let e = e?;
+        let name = e.file_name().to_string_lossy().into_owned();
+        // an imported run's bundle manifest is rewritten, not carried along;
+        // index artifacts are rebuilt from the assemblies
+        if e.file_type()?.is_file()
+            && name != LOCK_FILE
+            && name != BUNDLE_MANIFEST_FILE
+            && !indexes::is_artifact(&name)
+        {
+            names.push(name);
+        }
+    }
+    names.sort();
+    let tmp = run.join("tmp");
+    for suffix in ["", ".gz", ".zst"] {
+        let name = format!("{}{suffix}", tmpfiles::EVIDENCE_CACHE);
+        if tmp.join(&name).is_file() {
+            names.push(format!("tmp/{name}"));
+            break;
+        }
+    }
+    Ok(names)
+}
+
+/// Write the run's pairs' nuclear and mito sequences (coordinates as in pairs.tsv)
+/// as FASTA, reading each assembly once.
+pub fn write_loci_fasta(run: &Path, out: &mut impl Write) -> Result<()> {
+    let m = RunManifest::load_from(run)?;
+    let rows = scoring::read_run_pairs(run)
+        .with_context(|| format!("--sequences: read the pairs of {}", run.display()))?;
+    // (fasta, contig) → [(row, side, start, end)]
+    type Slices = Vec<(usize, usize, u32, u32)>;
+    let mut wanted: HashMap<(&Path, &str), Slices> = HashMap::new();
+    for (i, r) in rows.iter().enumerate() {
+        let l = &r.locus;
+        for (side, fa, contig, start, end) in [
+            (
+                0,
+                m.nuclear.as_path(),
+                &l.nuc_contig,
+                l.nuc_start,
+                l.nuc_end,
+            ),
+            (
+                1,
+                m.mito.as_path(),
+                &l.mito_contig,
+                l.mito_start,
+                l.mito_end,
+            ),
+        ] {
+            wanted
+                .entry((fa, contig))
+                .or_default()
+                .push((i, side, start, end));
+        }
+    }
+    let mut seqs: HashMap<(usize, usize), Vec<u8>> = HashMap::new();
+    for fa in [m.nuclear.as_path(), m.mito.as_path()] {
+        if !fa.exists() {
+            bail!(
+                "--sequences needs the run's assemblies, and {} is not there",
+                fa.display()
+            );
+        }
+        let mut rdr = needletail::parse_fastx_file(fa)
+            .with_context(|| format!("open fasta {}", fa.display()))?;
+        while let Some(rec) = rdr.next() {
+            let rec = rec.with_context(|| format!("read fasta {}", fa.display()))?;
+            let id = String::from_utf8_lossy(rec.id());
+            let Some(loci) = wanted.get(&(fa, id.as_ref())) else {
+                continue;
+            };
+            let seq = rec.seq();
+            for &(i, side, start, end) in loci {
+                let s = seq.get(start as usize..end as usize).ok_or_else(|| {
+                    anyhow!(
+                        "{id}:{start}-{end} runs past the end of {id} ({} bp) in {}",
+                        seq.len(),
+                        fa.display()
+                    )
+                })?;
+                seqs.insert((i, side), s.to_vec());
+            }
+        }
+    }
+    for (i, r) in rows.iter().enumerate() {
+        let l = &r.locus;
+        for (side, tag, contig, start, end) in [
+            (0, "nuc", &l.nuc_contig, l.nuc_start, l.nuc_end),
+            (1, "mito", &l.mito_contig, l.mito_start, l.mito_end),
+        ] {
+            let seq = seqs.get(&(i, side)).ok_or_else(|| {
+                anyhow!("no contig {contig} in the assemblies of {}", run.display())
+            })?;
+            let name = format!("{}_{tag} {contig}:{start}-{end}", l.pair_id);
+            fasta::write_fasta_record(out, &name, seq)?;
+        }
+    }
+    Ok(())
+}
+
+fn mtime(p: &Path) -> u64 {
+    fs_err::metadata(p)
+        .ok()
+        .and_then(|md| md.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Pack `run` into the bundle `out`, with `loci.fa` if `sequences`.
+pub fn export(run: &Path, out: &Path, sequences: bool) -> Result<BundleManifest> {
+    let mut names = member_paths(run)?;
+    // a bundle written into the run directory is not part of itself
+    if let Ok(out_abs) = out.canonicalize() {
+        names.retain(|n| run.join(n).canonicalize().ok().as_ref() != Some(&out_abs));
+    }
+    let loci = if sequences {
+        let mut f = tempfile::tempfile()?;
+        let mut w = BufWriter::new(&mut f);
+        write_loci_fasta(run, &mut w)?;
+        w.flush()?;
+        drop(w);
+        io::Seek::rewind(&mut f)?;
+        Some(f)
+    } else {
+        None
+    };
+
+    let gz = flate2::write::GzEncoder::new(
+        BufWriter::new(fs_err::File::create(out)?),
+        flate2::Compression::default(),
+    );
+    let mut tar = TarWriter::new(gz);
+    let mut members = Vec::with_capacity(names.len() + 2);
+    let mut add = |tar: &mut TarWriter<_>, name: &str, f: File, t: u64| -> Result<()> {
+        let bytes = f.metadata()?.len();
+        let mut data = Hashing {
+            inner: BufReader::new(f),
+            hash: FNV_OFFSET,
+        };
+        tar.append(name, bytes, t, &mut data)
+            .with_context(|| format!("bundle {name}"))?;
+        members.push(BundleMember {
+            path: name.to_string(),
+            bytes,
+            checksum: checksum_str(data.hash),
+        });
+        Ok(())
+    };
+    for name in &names {
+        let p = run.join(name);
+        add(&mut tar, name, File::open(&p)?, mtime(&p))?;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    if let Some(f) = loci {
+        add(&mut tar, LOCI_FASTA, f, now)?;
+    }
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        build_info: VersionInfo::build(),
+        run_dir: run.to_path_buf(),
+        members,
+    };
+    let json = serde_json::to_vec_pretty(&manifest)?;
+    tar.append(BUNDLE_MANIFEST_FILE, json.len() as u64, now, &mut &json[..])?;
+    tar.finish()?.finish()?.into_inner()?.sync_all()?;
+    Ok(manifest)
+}
+
+/// Unpack the bundle into `dir` (created), checking every member against the
+/// bundle manifest, which is written there too.
+pub fn unpack(bundle: &Path, dir: &Path) -> Result<BundleManifest> {
+    let f = fs_err::File::open(bundle)?;
+    let gz = flate2::read::MultiGzDecoder::new(BufReader::new(f));
+    let mut tar = TarReader::new(gz);
+    let not_a_bundle =
+        |e: io::Error| anyhow!(e).context(format!("{} is not an onsm bundle", bundle.display()));
+    fs_err::create_dir_all(dir)?;
+    // path → (bytes, checksum) as unpacked
+    let mut found: BTreeMap<String, (u64, String)> = BTreeMap::new();
+    let mut manifest = None;
+    while let Some(e) = tar.next_entry().map_err(not_a_bundle)? {
+        if e.name == BUNDLE_MANIFEST_FILE {
+            let mut json = Vec::new();
+            tar.read_to_end(&mut json).map_err(not_a_bundle)?;
+            fs_err::write(dir.join(&e.name), &json)?;
+            manifest = Some(
+                serde_json::from_slice::<BundleManifest>(&json)
+                    .with_context(|| format!("{BUNDLE_MANIFEST_FILE} in {}", bundle.display()))?,
+            );
+            continue;
+        }
+        let p = dir.join(&e.name);
+        if let Some(parent) = p.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        let mut data = Hashing {
+            inner: &mut tar,
+            hash: FNV_OFFSET,
+        };
+        let mut out = BufWriter::new(fs_err::File::create(&p)?);
+        let bytes = io::copy(&mut data, &mut out).map_err(not_a_bundle)?;
+        out.flush()?;
+        found.insert(e.name, (bytes, checksum_str(data.hash)));
+    }
+    let Some(manifest) = manifest else {
+        bail!(
+            "{} has no {BUNDLE_MANIFEST_FILE}: not an onsm bundle, or cut short",
+            bundle.display()
+        );
+    };
+    if manifest.format > BUNDLE_FORMAT {
+        bail!(
+            "{} is a version {} bundle; this onsm ({}) reads up to version {BUNDLE_FORMAT}",
+            bundle.display(),
+            manifest.format,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    let mut bad = Vec::new();
+    for m in &manifest.members {
+        match found.remove(&m.path) {
+            None => bad.push(format!("{} is missing", m.path)),
+            Some((bytes, sum)) if (bytes, &sum) != (m.bytes, &m.checksum) => {
+                bad.push(format!("{} does not match its checksum", m.path))
+            }
+            Some(_) => {}
+        }
+    }
+    bad.extend(found.into_keys().map(|p| format!("{p} is not listed")));
+    if !bad.is_empty() {
+        bail!(
+            "{} is damaged or was edited: {}",
+            bundle.display(),
+            bad.join(", ")
+        );
+    }
+    Ok(manifest)
+}
+
+/// A run directory, or a bundle unpacked into a temporary one that is
+/// removed when this is dropped.
+#[derive(Debug)]
+pub struct RunDir {
+    dir: PathBuf,
+    unpacked: Option<tempfile::TempDir>,
+}
+
+impl RunDir {
+    /// `path` itself if it is a directory, else the bundle it names, unpacked.
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self {
+                dir: path.to_path_buf(),
+                unpacked: None,
+            });
+        }
+        let tmp = tempfile::Builder::new().prefix("onsm-bundle-").tempdir()?;
+        let m = unpack(path, tmp.path())?;
+        log::info!(
+            "BUNDLE: {} unpacked ({} files, run {})",
+            path.display(),
+            m.members.len(),
+            m.run_dir.display()
+        );
+        Ok(Self {
+            dir: tmp.path().to_path_buf(),
+            unpacked: Some(tmp),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether this came from a bundle.
+    pub fn is_bundle(&self) -> bool {
+        self.unpacked.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A run directory with a few files and an evidence cache.
+    fn run_dir(dir: &Path) -> PathBuf {
+        let run = dir.join("run");
+        let mito = dir.join("mito.fa");
+        let nuc = dir.join("nuc.fa");
+        fs_err::write(&mito, ">mt\nACGTACGTAC\n").unwrap();
+        fs_err::write(&nuc, ">chr1\nTTTTTACGTAAAAA\n").unwrap();
+        let m = RunManifest::new(&mito, &nuc, &[], "hifi", 1, 0.8, 100, 0, 500, 250);
+        RunManifest::save_to(&run, &m).unwrap();
+        fs_err::write(run.join("summary.tsv"), "metric\tvalue\n").unwrap();
+        fs_err::write(run.join(LOCK_FILE), "pid 1\n").unwrap();
+        fs_err::create_dir_all(run.join("tmp")).unwrap();
+        fs_err::write(run.join("tmp").join(tmpfiles::EVIDENCE_CACHE), "{}\n").unwrap();
+        fs_err::write(run.join("tmp").join(tmpfiles::PAF_MITO_TO_NUC), "").unwrap();
+        fs_err::create_dir_all(run.join("reuse_1")).unwrap();
+        run
+    }
+
+    #[test]
+    fn bundles_round_trip_with_checksums() {
+        let dir = TempDir::new().unwrap();
+        let run = run_dir(dir.path());
+        assert_eq!(
+            member_paths(&run).unwrap(),
+            [
+                "run_manifest.json",
+                "summary.tsv",
+                "tmp/evidence_cache.jsonl"
+            ]
+        );
+        let bundle = dir.path().join("run.onsm");
+        let m = export(&run, &bundle, false).unwrap();
+        assert_eq!(m.members.len(), 3);
+        assert_eq!(m.members[1].bytes, 13);
+        assert!(m.members[1].checksum.starts_with("fnv1a64:"));
+        let on_disk: u64 = member_paths(&run)
+            .unwrap()
+            .iter()
+            .map(|p| fs_err::metadata(run.join(p)).unwrap().len())
+            .sum();
+        assert_eq!(m.total_bytes(), on_disk);
+
+        let out = dir.path().join("out");
+        assert_eq!(unpack(&bundle, &out).unwrap(), m);
+        for p in member_paths(&run).unwrap() {
+            assert_eq!(
+                fs_err::read(out.join(&p)).unwrap(),
+                fs_err::read(run.join(&p)).unwrap(),
+                "{p}"
+            );
+        }
+        assert!(out.join(BUNDLE_MANIFEST_FILE).exists());
+        assert!(!out.join(LOCK_FILE).exists());
+
+        let opened = RunDir::open(&bundle).unwrap();
+        assert!(opened.is_bundle());
+        assert!(opened.path().join("summary.tsv").exists());
+        let unpacked = opened.path().to_path_buf();
+        drop(opened);
+        assert!(!unpacked.exists());
+        let plain = RunDir::open(&run).unwrap();
+        assert!(!plain.is_bundle() && plain.path() == run);
+
+        // not a run directory; not a bundle
+        assert!(export(dir.path(), &dir.path().join("x.onsm"), false).is_err());
+        let e = unpack(&run.join("summary.tsv"), &dir.path().join("y")).unwrap_err();
+        assert!(format!("{e:#}").contains("not an onsm bundle"), "{e:#}");
+    }
+
+    #[test]
+    fn edited_bundles_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let run = run_dir(dir.path());
+        let bundle = dir.path().join("run.onsm");
+        export(&run, &bundle, false).unwrap();
+
+        // repack with one member changed, the manifest as it was
+        let gz = flate2::read::MultiGzDecoder::new(fs_err::File::open(&bundle).unwrap());
+        let mut tar = TarReader::new(gz);
+        let mut files = Vec::new();
+        while let Some(e) = tar.next_entry().unwrap() {
+            let mut data = Vec::new();
+            tar.read_to_end(&mut data).unwrap();
+            if e.name == "summary.tsv" {
+                data = b"metric\tvalue\nx\t1\n".to_vec();
+            }
+            files.push((e.name, data));
+        }
+        let repack = |files: &[(String, Vec<u8>)], to: &Path| {
+            let gz = flate2::write::GzEncoder::new(
+                fs_err::File::create(to).unwrap(),
+                flate2::Compression::fast(),
+            );
+            let mut w = TarWriter::new(gz);
+            for (name, data) in files {
+                w.append(name, data.len() as u64, 0, &mut &data[..])
+                    .unwrap();
+            }
+            w.finish().unwrap().finish().unwrap();
+        };
+        let edited = dir.path().join("edited.onsm");
+        repack(&files, &edited);
+        let e = unpack(&edited, &dir.path().join("a")).unwrap_err();
+        assert!(
+            e.to_string()
+                .contains("summary.tsv does not match its checksum"),
+            "{e}"
+        );
+        // a member dropped; the manifest dropped
+        repack(&files[1..], &edited);
+        let e = unpack(&edited, &dir.path().join("b")).unwrap_err();
+        assert!(
+            e.to_string().contains("run_manifest.json is missing"),
+            "{e}"
+        );
+        repack(&files[..files.len() - 1], &edited);
+        let e = unpack(&edited, &dir.path().join("c")).unwrap_err();
+        assert!(e.to_string().contains("no bundle_manifest.json"), "{e}");
+
+        // cut short
+        let bytes = fs_err::read(&bundle).unwrap();
+        fs_err::write(&edited, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(unpack(&edited, &dir.path().join("d")).is_err());
+    }
+}
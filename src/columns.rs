@@ -0,0 +1,261 @@
+//! Column selection for pairs.tsv (`--columns` / `--columns-preset`).
+//!
+//! The valid names are the columns of the full table
+//! (`scoring::PAIRS_TSV_HEADER`); `pair_id` is always written, as the first
+//! column. Readers in this crate locate columns by header name, and fall back
+//! to results.json when a narrowed pairs.tsv lacks one they need
+//! (`scoring::read_run_pairs`).
+//!
+//! `--cost-columns` appends `cost_ms` and `n_subprocess_calls`, what
+//! collecting each pair's evidence took, after the selected columns.
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::model::EvidenceCost;
+use crate::scoring::{self, ScoredPair};
+
+/// Named column sets for `--columns-preset`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColumnsPreset {
+    /// Locus coordinates and strand.
+    Minimal,
+    /// Coordinates, alignment, evidence ratios and scores.
+    Standard,
+    /// Every column.
+    #[default]
+    Full,
+}
+
+const MINIMAL: &[&str] = &[
+    "pair_id",
+    "nuc_contig",
+    "nuc_start",
+    "nuc_end",
+    "mito_contig",
+    "mito_start",
+    "mito_end",
+    "strand",
+];
+
+const STANDARD: &[&str] = &[
+    "pair_id",
+    "nuc_contig",
+    "nuc_start",
+    "nuc_end",
+    "mito_contig",
+    "mito_start",
+    "mito_end",
+    "strand",
+    "aln_len",
+    "aln_ident",
+    "rnuc",
+    "rmito",
+    "s_nuc",
+    "s_mito",
+    "score_numt",
+    "score_nimt",
+];
+
+/// Every pairs.tsv column, in the order of the full table.
+pub fn pairs_column_names() -> impl Iterator<Item = &'static str> {
+    scoring::PAIRS_TSV_HEADER.split('\t')
+}
+
+/// Appended by `--cost-columns`.
+pub const COST_COLUMNS: &str = "cost_ms\tn_subprocess_calls";
+
+/// Which pairs.tsv columns to write, as indices into the full row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairsColumns {
+    idx: Vec<usize>,
+    cost: bool,
+}
+
+impl Default for PairsColumns {
+    fn default() -> Self {
+        Self::preset(ColumnsPreset::Full)
+    }
+}
+
+impl PairsColumns {
+    pub fn preset(preset: ColumnsPreset) -> Self {
+        match preset {
+            ColumnsPreset::Minimal => Self::named(MINIMAL),
+            ColumnsPreset::Standard => Self::named(STANDARD),
+            ColumnsPreset::Full => Self::indices((0..pairs_column_names().count()).collect()),
+        }
+    }
+
+    fn indices(idx: Vec<usize>) -> Self {
+        Self { idx, cost: false }
+    }
+
+    fn named(names: &[&str]) -> Self {
+        Self::parse(names).expect("preset columns are pairs.tsv columns")
+    }
+
+    /// `--columns`: names in output order. Unknown or repeated names are an
+    /// error; `pair_id` is moved (or added) to the front.
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        let all: Vec<&str> = pairs_column_names().collect();
+        let mut idx = vec![0];
+        for name in names.iter().map(|n| n.as_ref().trim()) {
+            let i = all.iter().position(|c| *c == name).ok_or_else(|| {
+                anyhow!(
+                    "--columns: unknown pairs.tsv column '{name}' (valid: {})",
+                    all.join(",")
+                )
+            })?;
+            if i == 0 {
+                continue;
+            }
+            if idx.contains(&i) {
+                return Err(anyhow!("--columns: '{name}' is listed twice"));
+            }
+            idx.push(i);
+        }
+        Ok(Self::indices(idx))
+    }
+
+    /// `--columns` if given, else the preset.
+    pub fn from_args(columns: &[String], preset: ColumnsPreset) -> Result<Self> {
+        if columns.is_empty() {
+            Ok(Self::preset(preset))
+        } else {
+            Self::parse(columns)
+        }
+    }
+
+    /// Also write the evidence cost columns (`--cost-columns`).
+    pub fn with_cost(mut self, yes: bool) -> Self {
+        self.cost = yes;
+        self
+    }
+
+    pub fn cost(&self) -> bool {
+        self.cost
+    }
+
+    /// Only the columns among `names` (`--output-compat`), in the same order.
+    pub fn retain(self, names: &[&str]) -> Self {
+        let all: Vec<&str> = pairs_column_names().collect();
+        Self {
+            idx: self
+                .idx
+                .into_iter()
+                .filter(|&i| names.contains(&all[i]))
+                .collect(),
+            ..self
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.idx.iter().copied().eq(0..pairs_column_names().count())
+    }
+
+    /// The header line (no trailing newline).
+    pub fn header(&self) -> String {
+        let all: Vec<&str> = pairs_column_names().collect();
+        let mut h = self
+            .idx
+            .iter()
+            .map(|&i| all[i])
+            .collect::<Vec<_>>()
+            .join("\t");
+        if self.cost {
+            h = format!("{h}\t{COST_COLUMNS}");
+        }
+        h
+    }
+
+    /// One row (no trailing newline).
+    pub fn row(&self, sp: &ScoredPair) -> String {
+        let full = scoring::pairs_tsv_row(sp);
+        let row = if self.is_full() {
+            full
+        } else {
+            let fields: Vec<&str> = full.split('\t').collect();
+            self.idx
+                .iter()
+                .map(|&i| fields[i])
+                .collect::<Vec<_>>()
+                .join("\t")
+        };
+        if !self.cost {
+            return row;
+        }
+        match sp.evidence_cost {
+            Some(EvidenceCost {
+                cost_ms,
+                n_subprocess_calls,
+            }) => format!("{row}\t{cost_ms:.3}\t{n_subprocess_calls}"),
+            // evidence not collected in this run (rescored from results.json)
+            None => format!("{row}\t.\t."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_of(cols: &PairsColumns) -> String {
+        cols.header().replace('\t', ",")
+    }
+
+    #[test]
+    fn presets() {
+        let full = PairsColumns::default();
+        assert_eq!(full.header(), scoring::PAIRS_TSV_HEADER);
+        assert!(full.is_full());
+        assert_eq!(
+            header_of(&PairsColumns::preset(ColumnsPreset::Minimal)),
+            "pair_id,nuc_contig,nuc_start,nuc_end,mito_contig,mito_start,mito_end,strand"
+        );
+        let standard = PairsColumns::preset(ColumnsPreset::Standard);
+        assert_eq!(standard.idx.len(), STANDARD.len());
+        assert!(!standard.is_full());
+        // --columns wins over the preset
+        let cols = PairsColumns::from_args(&["rnuc".to_string()], ColumnsPreset::Minimal);
+        assert_eq!(header_of(&cols.unwrap()), "pair_id,rnuc");
+    }
+
+    #[test]
+    fn pair_id_is_always_first() {
+        let cols = PairsColumns::parse(&["aln_ident", "pair_id", "nuc_contig"]).unwrap();
+        assert_eq!(header_of(&cols), "pair_id,aln_ident,nuc_contig");
+        let cols = PairsColumns::parse(&["score_nimt", "score_numt"]).unwrap();
+        assert_eq!(header_of(&cols), "pair_id,score_nimt,score_numt");
+        assert_eq!(
+            header_of(&PairsColumns::parse::<&str>(&[]).unwrap()),
+            "pair_id"
+        );
+    }
+
+    #[test]
+    fn cost_columns_follow_the_selection() {
+        let cols = PairsColumns::parse(&["rnuc"]).unwrap().with_cost(true);
+        assert_eq!(header_of(&cols), "pair_id,rnuc,cost_ms,n_subprocess_calls");
+        let full = PairsColumns::default().with_cost(true);
+        assert_eq!(
+            full.header(),
+            format!("{}\t{COST_COLUMNS}", scoring::PAIRS_TSV_HEADER)
+        );
+    }
+
+    #[test]
+    fn unknown_or_repeated_names_are_errors() {
+        let e = PairsColumns::parse(&["pair_id", "rnuk"])
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("unknown pairs.tsv column 'rnuk'"), "{e}");
+        assert!(e.contains("valid: pair_id,nuc_contig,"), "{e}");
+        assert!(
+            e.contains(",low_mapq_nuc,low_mapq_mito,junction_clips_start,junction_clips_end)"),
+            "{e}"
+        );
+        let e = PairsColumns::parse(&["rnuc", "rmito", "rnuc"]).unwrap_err();
+        assert!(e.to_string().contains("'rnuc' is listed twice"), "{e}");
+    }
+}
@@ -0,0 +1,352 @@
+//! `--output-compat`: write the tables as an earlier onsm release did, for
+//! pipelines that parse them by position or reject unknown fields.
+//!
+//! Each level is the list of pairs.tsv and classification.tsv columns and
+//! summary.tsv / summary.json metrics of that release's outputs. The writers
+//! leave out everything after it; what a level does not list, the current
+//! outputs added. A flag whose output is only in a newer schema is refused
+//! rather than dropped silently. So is a call: one the release did not have
+//! is written as Ambiguous, and the reason codes are the one code of the call
+//! written, as that release gave. Other files are written as usual: the JSON
+//! artifacts only gained fields, and new files (BEDs, bedGraphs) are new.
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::columns::{ColumnsPreset, PairsColumns};
+use crate::scoring::{self, Call};
+
+/// The output schema to write (`--output-compat`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum OutputCompat {
+    /// The outputs of onsm 0.1: 15 pairs.tsv columns, 4 classification.tsv
+    /// columns, 13 summary metrics.
+    #[value(name = "0.1")]
+    #[serde(rename = "0.1")]
+    V0_1,
+    /// Every column and metric of this build.
+    #[default]
+    #[serde(rename = "latest")]
+    Latest,
+}
+
+/// The columns and metrics of one released output schema.
+struct OutputSchema {
+    pairs: &'static [&'static str],
+    classification: &'static [&'static str],
+    summary: &'static [&'static str],
+    /// The calls of its classification.tsv.
+    calls: &'static [Call],
+}
+
+const V0_1: OutputSchema = OutputSchema {
+    pairs: &[
+        "pair_id",
+        "nuc_contig",
+        "nuc_start",
+        "nuc_end",
+        "mito_contig",
+        "mito_start",
+        "mito_end",
+        "aln_len",
+        "aln_ident",
+        "rnuc",
+        "rmito",
+        "s_nuc",
+        "s_mito",
+        "score_numt",
+        "score_nimt",
+    ],
+    classification: &["pair_id", "call", "confidence", "reason_codes"],
+    summary: &[
+        "n_pairs",
+        "n_numt",
+        "n_nimt",
+        "nuclear_bp_total",
+        "nuclear_bp_numt",
+        "nuclear_pct_numt",
+        "mito_bp_total",
+        "mito_bp_nimt",
+        "mito_pct_nimt",
+        "mito_bp_covered_by_numt_homologs",
+        "mito_pct_covered_by_numt_homologs",
+        "nuc_bp_covered_by_nimt_homologs",
+        "nuc_pct_covered_by_nimt_homologs",
+    ],
+    calls: &[Call::NUMT, Call::NIMT, Call::Ambiguous],
+};
+
+impl OutputCompat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputCompat::V0_1 => "0.1",
+            OutputCompat::Latest => "latest",
+        }
+    }
+
+    fn schema(self) -> Option<&'static OutputSchema> {
+        match self {
+            OutputCompat::V0_1 => Some(&V0_1),
+            OutputCompat::Latest => None,
+        }
+    }
+
+    /// `--columns` / `--columns-preset` within this schema. A preset is cut
+    /// down to the columns the schema has; a listed column it lacks is an error.
+    pub fn pairs_columns(self, columns: &[String], preset: ColumnsPreset) -> Result<PairsColumns> {
+        let cols = PairsColumns::from_args(columns, preset)?;
+        let Some(schema) = self.schema() else {
+            return Ok(cols);
+        };
+        if let Some(c) = columns
+            .iter()
+            .map(|c| c.trim())
+            .find(|c| !schema.pairs.contains(c))
+        {
+            return Err(anyhow!(
+                "--columns {c} conflicts with --output-compat {}: its pairs.tsv has no such column (use --output-compat latest)",
+                self.as_str()
+            ));
+        }
+        Ok(cols.retain(schema.pairs))
+    }
+
+    /// Refuse the `(flag, given)` options whose output this schema cannot hold.
+    pub fn check_options(self, options: &[(&str, bool)]) -> Result<()> {
+        if self.schema().is_none() {
+            return Ok(());
+        }
+        match options.iter().find(|(_, given)| *given) {
+            Some((flag, _)) => Err(anyhow!(
+                "{flag} conflicts with --output-compat {}: its output is not in that schema (use --output-compat latest)",
+                self.as_str()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The first classification.tsv columns to write.
+    fn classification_len(self) -> usize {
+        self.schema().map_or(usize::MAX, |s| s.classification.len())
+    }
+
+    pub fn classification_header(self) -> String {
+        first_fields(
+            scoring::CLASSIFICATION_TSV_HEADER,
+            self.classification_len(),
+        )
+    }
+
+    pub fn classification_row(self, sp: &scoring::ScoredPair) -> String {
+        let row = scoring::classification_tsv_row(sp);
+        let Some(schema) = self.schema() else {
+            return row;
+        };
+        let call = match sp.score.call {
+            call if schema.calls.contains(&call) => call,
+            _ => Call::Ambiguous,
+        };
+        let (call, reason) = call.as_str_and_reason();
+        let fields: Vec<&str> = schema
+            .classification
+            .iter()
+            .zip(row.split('\t'))
+            .map(|(&col, field)| match col {
+                "call" => call,
+                "reason_codes" => reason,
+                _ => field,
+            })
+            .collect();
+        fields.join("\t")
+    }
+
+    /// classification.tsv as a whole.
+    pub fn classification_tsv(self, scored: &[scoring::ScoredPair]) -> String {
+        let mut t = self.classification_header();
+        for sp in scored {
+            t.push('\n');
+            t.push_str(&self.classification_row(sp));
+        }
+        t.push('\n');
+        t
+    }
+
+    /// summary.tsv without the metrics the schema lacks.
+    pub fn summary_tsv(self, tsv: String) -> String {
+        let Some(schema) = self.schema() else {
+            return tsv;
+        };
+        let mut lines = tsv.lines();
+        let mut t = lines.next().map(|h| format!("{h}\n")).unwrap_or_default();
+        for line in lines {
+            let metric = line.split('\t').next().unwrap_or_default();
+            if schema.summary.contains(&metric) {
+                t.push_str(line);
+                t.push('\n');
+            }
+        }
+        t
+    }
+
+    /// summary.json without the fields the schema lacks.
+    pub fn summary_json(self, mut json: serde_json::Value) -> serde_json::Value {
+        if let (Some(schema), Some(obj)) = (self.schema(), json.as_object_mut()) {
+            obj.retain(|k, _| schema.summary.contains(&k.as_str()));
+        }
+        json
+    }
+}
+
+/// The first `n` tab-separated fields of `line`.
+fn first_fields(line: &str, n: usize) -> String {
+    line.split('\t').take(n).collect::<Vec<_>>().join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_within_the_schema() {
+        let v0_1 = OutputCompat::V0_1;
+        // the full table is the whole 0.1 table, in its order
+        let full = v0_1.pairs_columns(&[], ColumnsPreset::Full).unwrap();
+        assert_eq!(full.header(), V0_1.pairs.join("\t"));
+        // presets lose what 0.1 did not have
+        let minimal = v0_1.pairs_columns(&[], ColumnsPreset::Minimal).unwrap();
+        assert_eq!(
+            minimal.header(),
+            "pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end"
+        );
+        let listed = ["rnuc".to_string(), "aln_ident".to_string()];
+        let cols = v0_1.pairs_columns(&listed, ColumnsPreset::Full).unwrap();
+        assert_eq!(cols.header(), "pair_id\trnuc\taln_ident");
+        // asking for a newer column is a conflict, not a silent drop
+        let e = v0_1
+            .pairs_columns(
+                &["rnuc".to_string(), "strand".to_string()],
+                ColumnsPreset::Full,
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("--columns strand conflicts with --output-compat 0.1"),
+            "{e}"
+        );
+        // latest is the table as it is
+        let latest = OutputCompat::Latest
+            .pairs_columns(&[], ColumnsPreset::Full)
+            .unwrap();
+        assert_eq!(latest, PairsColumns::default());
+    }
+
+    #[test]
+    fn options_needing_a_newer_schema_are_refused() {
+        let opts = [("--summary-units", false), ("--mito-contig-meta", true)];
+        let e = OutputCompat::V0_1.check_options(&opts).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("--mito-contig-meta conflicts with --output-compat 0.1"),
+            "{e}"
+        );
+        assert!(OutputCompat::V0_1.check_options(&opts[..1]).is_ok());
+        assert!(OutputCompat::Latest.check_options(&opts).is_ok());
+    }
+
+    fn scored(
+        id: &str,
+        span: (f32, f32),
+        clamped_window: Option<(u32, u32)>,
+    ) -> scoring::ScoredPair {
+        let p = crate::model::PairedLocus {
+            pair_id: id.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: 1_000,
+            nuc_end: 2_000,
+            mito_contig: "mt".into(),
+            mito_start: 100,
+            mito_end: 1_100,
+            aln_len: 1_000,
+            aln_ident: 0.95,
+            strand: crate::model::orientation::Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: crate::io::paf::IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let ev = crate::model::PairEvidence {
+            depth: (20.0, 200.0),
+            span,
+            read_len: (15_000.0, 15_000.0),
+            clamped_window,
+            ..Default::default()
+        };
+        scoring::score_pair(
+            &p,
+            &ev,
+            Default::default(),
+            Default::default(),
+            scoring::DepthBaselines {
+                nuclear_median: 20.0,
+                mito_median: 200.0,
+            },
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn classification_keeps_to_the_calls_of_the_schema() {
+        let scored = [
+            scored("P1", (0.9, 0.0), None),
+            // a flag 0.1 did not have
+            scored("P2", (0.9, 0.0), Some((200, 500))),
+            // a call 0.1 did not have
+            scored("P3", (0.9, 0.9), None),
+            scored("P4", (0.0, 0.0), None),
+        ];
+        let calls = |t: String| -> Vec<String> {
+            t.lines()
+                .skip(1)
+                .map(|l| l.split('\t').take(4).collect::<Vec<_>>().join(" "))
+                .collect()
+        };
+        assert_eq!(
+            calls(OutputCompat::Latest.classification_tsv(&scored)),
+            [
+                "P1 Likely_NUMT 0.9000 score_difference",
+                "P2 Likely_NUMT 0.9000 score_difference,short_window",
+                "P3 Shared_Support 0.0000 both_sides_supported",
+                "P4 Ambiguous 0.0000 delta_below_threshold",
+            ]
+        );
+        assert_eq!(
+            OutputCompat::V0_1.classification_tsv(&scored),
+            "pair_id\tcall\tconfidence\treason_codes\n\
+             P1\tLikely_NUMT\t0.9000\tscore_difference\n\
+             P2\tLikely_NUMT\t0.9000\tscore_difference\n\
+             P3\tAmbiguous\t0.0000\tdelta_below_threshold\n\
+             P4\tAmbiguous\t0.0000\tdelta_below_threshold\n"
+        );
+    }
+
+    #[test]
+    fn summary_metrics_kept_in_order() {
+        let tsv = "metric\tvalue\nn_pairs\t3\nn_shared\t1\nnuclear_bp_total\t100\ncaveats\tx\n";
+        assert_eq!(
+            OutputCompat::V0_1.summary_tsv(tsv.to_string()),
+            "metric\tvalue\nn_pairs\t3\nnuclear_bp_total\t100\n"
+        );
+        assert_eq!(OutputCompat::Latest.summary_tsv(tsv.to_string()), tsv);
+        let json = serde_json::json!({"n_pairs": 3, "n_shared": 1, "numt_identity": {}});
+        assert_eq!(
+            OutputCompat::V0_1.summary_json(json),
+            serde_json::json!({"n_pairs": 3})
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Control region (D-loop) hints for the mito assembly (`--mito-control-region`).
+//!
+//! The control region evolves fast and is full of tandem repeats, so an
+//! alignment's identity says little about which way a transfer went there.
+//! Without a full annotation users usually still know its coordinates:
+//! `contig:start-end`, 0-based half-open as in pairs.tsv, with `start > end`
+//! for a region running across the origin of a circular contig. Each pair's
+//! mito interval is then inside one (`Full`), partly in one (`Partial`) or
+//! clear of them; pairs.tsv gets `in_control_region`, classification.tsv a
+//! reason code, summary.tsv the calls inside, and `--control-region-identity-weight`
+//! can weaken the identity term of the pairs inside.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::mito_meta::MitoContigMeta;
+use crate::util::region;
+
+/// `--mito-control-region` as given, before the contig length is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlRegionSpec {
+    pub contig: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// `contig:start-end` as in `util::region` (always 0-based here), with
+/// `start > end` allowed for the origin wrap.
+pub fn parse_spec(s: &str) -> Result<ControlRegionSpec, String> {
+    let r = region::parse(s)?;
+    if r.start == r.end {
+        return Err(format!("{s:?} is empty (start = end)"));
+    }
+    Ok(ControlRegionSpec {
+        contig: r.contig,
+        start: r.start,
+        end: r.end,
+    })
+}
+
+/// How a mito interval lies relative to the control regions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ControlRegionOverlap {
+    #[default]
+    None,
+    /// Some bases inside a control region, some outside.
+    Partial,
+    /// Entirely inside one control region.
+    Full,
+}
+
+/// One control region, with its contig's length for the origin wrap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ControlRegion {
+    pub contig: String,
+    pub start: u32,
+    /// Below `start` when the region wraps the origin.
+    pub end: u32,
+    pub contig_len: u32,
+}
+
+impl ControlRegion {
+    pub fn wraps(&self) -> bool {
+        self.start > self.end
+    }
+
+    /// The region as linear half-open pieces: one, or two across the origin.
+    fn pieces(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (a, b) = if self.wraps() {
+            ((self.start, self.contig_len), (0, self.end))
+        } else {
+            ((self.start, self.end), (0, 0))
+        };
+        [a, b].into_iter().filter(|(s, e)| s < e)
+    }
+
+    /// Bases of `[start, end)` on the region's contig inside the region.
+    fn overlap_bp(&self, start: u32, end: u32) -> u32 {
+        self.pieces()
+            .map(|(s, e)| end.min(e).saturating_sub(start.max(s)))
+            .sum()
+    }
+}
+
+/// The run's control regions (stored in `run_manifest.json`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct ControlRegions(Vec<ControlRegion>);
+
+impl ControlRegions {
+    /// Check `specs` against the mito assembly: the contig must exist, the
+    /// coordinates lie on it, and only circular contigs (per `meta`) wrap.
+    pub fn resolve(
+        specs: &[ControlRegionSpec],
+        mito_lens: &HashMap<String, u64>,
+        meta: &MitoContigMeta,
+    ) -> Result<Self> {
+        let mut regions = Vec::with_capacity(specs.len());
+        for s in specs {
+            let at = format!("--mito-control-region {}:{}-{}", s.contig, s.start, s.end);
+            let len = *mito_lens.get(&s.contig).ok_or_else(|| {
+                anyhow!("{at}: {} is not a contig of the mito assembly", s.contig)
+            })?;
+            if s.start.max(s.end) as u64 > len {
+                return Err(anyhow!("{at}: past the end of {} ({len} bp)", s.contig));
+            }
+            if s.start > s.end && !meta.circular(&s.contig) {
+                return Err(anyhow!(
+                    "{at}: start > end wraps the origin, but {} is linear (--mito-contig-meta)",
+                    s.contig
+                ));
+            }
+            regions.push(ControlRegion {
+                contig: s.contig.clone(),
+                start: s.start,
+                end: s.end,
+                contig_len: len as u32,
+            });
+        }
+        Ok(Self(regions))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Where the mito interval `[start, end)` of `contig` lies: `Full` if one
+    /// region holds all of it, `Partial` if any region holds some.
+    pub fn overlap(&self, contig: &str, start: u32, end: u32) -> ControlRegionOverlap {
+        let len = end.saturating_sub(start);
+        let mut best = ControlRegionOverlap::None;
+        for r in self.0.iter().filter(|r| r.contig == contig) {
+            match r.overlap_bp(start, end) {
+                0 => {}
+                bp if bp >= len => return ControlRegionOverlap::Full,
+                _ => best = ControlRegionOverlap::Partial,
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ControlRegionOverlap::{Full, None as Outside, Partial};
+
+    fn regions(specs: &[&str]) -> Result<ControlRegions> {
+        let specs: Vec<_> = specs.iter().map(|s| parse_spec(s).unwrap()).collect();
+        let lens = HashMap::from([("mt".to_string(), 16_569), ("lin".to_string(), 5_000)]);
+        let meta = MitoContigMeta::parse("lin\tplasmid\tno\n").unwrap();
+        ControlRegions::resolve(&specs, &lens, &meta)
+    }
+
+    #[test]
+    fn spec_syntax() {
+        assert_eq!(
+            parse_spec("mt:16,024-576"),
+            Ok(ControlRegionSpec {
+                contig: "mt".into(),
+                start: 16_024,
+                end: 576
+            })
+        );
+        assert_eq!(parse_spec("chrM:v2:0-100").unwrap().contig, "chrM:v2");
+        for bad in ["mt", "mt:100", "mt:a-b", ":1-2", "mt:-5-10", "mt:5-5"] {
+            assert!(parse_spec(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn resolve_checks_the_assembly() {
+        assert!(regions(&["mt:16024-576", "lin:0-800"]).is_ok());
+        for (bad, why) in [
+            ("chrM:0-100", "not a contig"),
+            ("mt:16000-17000", "past the end"),
+            ("lin:4500-200", "linear"),
+        ] {
+            let e = regions(&[bad]).unwrap_err().to_string();
+            assert!(e.contains(why), "{bad}: {e}");
+        }
+    }
+
+    #[test]
+    fn full_and_partial_containment() {
+        let r = regions(&["mt:100-1100", "lin:0-800"]).unwrap();
+        assert_eq!(r.overlap("mt", 200, 900), Full);
+        assert_eq!(r.overlap("mt", 100, 1100), Full);
+        assert_eq!(r.overlap("mt", 50, 900), Partial);
+        assert_eq!(r.overlap("mt", 1000, 2000), Partial);
+        assert_eq!(r.overlap("mt", 1100, 2000), Outside);
+        // same coordinates, another contig
+        assert_eq!(r.overlap("other", 200, 900), Outside);
+        assert_eq!(r.overlap("lin", 0, 800), Full);
+        assert_eq!(ControlRegions::default().overlap("mt", 0, 10), Outside);
+    }
+
+    #[test]
+    fn regions_across_the_origin() {
+        // the human D-loop: 16024 to the end, then 0-576
+        let r = regions(&["mt:16024-576"]).unwrap();
+        assert!(r.0[0].wraps());
+        assert_eq!(r.overlap("mt", 16_100, 16_569), Full);
+        assert_eq!(r.overlap("mt", 0, 500), Full);
+        assert_eq!(r.overlap("mt", 500, 1_000), Partial);
+        assert_eq!(r.overlap("mt", 15_000, 16_100), Partial);
+        assert_eq!(r.overlap("mt", 600, 16_000), Outside);
+        // a linear mito interval spanning the whole contig only partly overlaps
+        assert_eq!(r.overlap("mt", 0, 16_569), Partial);
+    }
+}
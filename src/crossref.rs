@@ -0,0 +1,372 @@
+//! Links between calls that share a region (`related_pairs` in pairs.tsv,
+//! `cross_call_conflicts.tsv`).
+//!
+//! One stretch of sequence can be the locus of one call and the counterpart of
+//! another: a mito region called as a Likely_NIMT insertion may also be the
+//! source of Likely_NUMT copies elsewhere. The locus of a Likely_NUMT pair is
+//! its nuclear interval and that of a Likely_NIMT pair its mito interval; the
+//! other interval is its counterpart. Both intervals of an undecided pair
+//! (Ambiguous, Shared_Support) count as counterparts. Two pairs are related
+//! when the locus of one overlaps a counterpart of the other on the same contig
+//! by at least `RELATED_MIN_OVERLAP` of the shorter interval. Overlapping loci
+//! are left to collapsing, and overlapping counterparts (several NUMTs of one
+//! mito region) are no conflict.
+//!
+//! `related_pairs` lists every pair related either way; the conflicts table
+//! only those where the two calls are opposite, one row per locus and the
+//! counterpart it overlaps.
+
+use anyhow::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::model::orientation::{side_interval, Side};
+use crate::model::PairedLocus;
+use crate::scoring::Call;
+use crate::tracking::IntervalIndex;
+
+/// Shared bp over the shorter interval for a locus and a counterpart to be related.
+pub const RELATED_MIN_OVERLAP: f32 = 0.5;
+
+pub const CROSS_CALL_CONFLICTS_FILE: &str = "cross_call_conflicts.tsv";
+pub const CROSS_CALL_CONFLICTS_HEADER: &str =
+    "pair_id\tcall\tside\tcontig\tstart\tend\trelated_pair_id\trelated_call\trelated_start\trelated_end\toverlap";
+
+/// The genome a call places the insertion on; `None` for undecided calls.
+pub fn locus_side(call: Call) -> Option<Side> {
+    match call {
+        Call::NUMT => Some(Side::Nuclear),
+        Call::NIMT => Some(Side::Mito),
+        Call::Ambiguous | Call::SharedSupport => None,
+    }
+}
+
+/// The contig of one side of a pair.
+fn side_contig(p: &PairedLocus, side: Side) -> &str {
+    match side {
+        Side::Nuclear => &p.nuc_contig,
+        Side::Mito => &p.mito_contig,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Locus {
+    pair_id: String,
+    call: Call,
+    side: Side,
+    contig: String,
+    start: u32,
+    end: u32,
+}
+
+/// A locus overlapping a counterpart of an opposite call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub pair_id: String,
+    pub call: Call,
+    /// The genome of the locus.
+    pub side: Side,
+    pub contig: String,
+    pub start: u32,
+    pub end: u32,
+    pub related_pair_id: String,
+    pub related_call: Call,
+    /// The related pair's counterpart interval on `contig`.
+    pub related_start: u32,
+    pub related_end: u32,
+    /// Shared bp over the shorter of the two intervals.
+    pub overlap: f32,
+}
+
+/// First pass: feed every pair, then `index`.
+///
+/// Only the loci of decided calls are kept, so memory grows with the NUMT and
+/// NIMT calls, as in `regions::RegionBuilder`.
+#[derive(Debug, Default)]
+pub struct CrossRefBuilder {
+    loci: Vec<Locus>,
+}
+
+impl CrossRefBuilder {
+    pub fn add(&mut self, p: &PairedLocus, call: Call) {
+        let Some(side) = locus_side(call) else {
+            return;
+        };
+        let (start, end) = side_interval(p, side);
+        self.loci.push(Locus {
+            pair_id: p.pair_id.clone(),
+            call,
+            side,
+            contig: side_contig(p, side).to_string(),
+            start,
+            end,
+        });
+    }
+
+    pub fn index(self) -> CrossRefIndex {
+        let of_side = |side: Side| {
+            let at: Vec<usize> = (0..self.loci.len())
+                .filter(|&i| self.loci[i].side == side)
+                .collect();
+            let index = IntervalIndex::of_intervals(at.iter().map(|&i| {
+                let l = &self.loci[i];
+                (l.contig.as_str(), l.start, l.end)
+            }));
+            (index, at)
+        };
+        CrossRefIndex {
+            nuclear: of_side(Side::Nuclear),
+            mito: of_side(Side::Mito),
+            loci: self.loci,
+            related: HashMap::new(),
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+/// Second pass: feed every pair again, then `finish`.
+#[derive(Debug)]
+pub struct CrossRefIndex {
+    loci: Vec<Locus>,
+    /// Each side's loci, and their positions in `loci`.
+    nuclear: (IntervalIndex, Vec<usize>),
+    mito: (IntervalIndex, Vec<usize>),
+    related: HashMap<String, BTreeSet<String>>,
+    conflicts: Vec<Conflict>,
+}
+
+impl CrossRefIndex {
+    /// Relate the counterparts of `p` to the loci they overlap.
+    pub fn add(&mut self, p: &PairedLocus, call: Call) {
+        let own = locus_side(call);
+        for side in [Side::Nuclear, Side::Mito] {
+            if own == Some(side) {
+                continue;
+            }
+            let contig = side_contig(p, side);
+            let (start, end) = side_interval(p, side);
+            let (index, at) = match side {
+                Side::Nuclear => &self.nuclear,
+                Side::Mito => &self.mito,
+            };
+            for i in index.overlapping(contig, start, end) {
+                let l = &self.loci[at[i]];
+                if l.pair_id == p.pair_id {
+                    continue;
+                }
+                let shared = l.end.min(end) - l.start.max(start);
+                let shorter = (l.end - l.start).min(end - start).max(1);
+                let overlap = shared as f32 / shorter as f32;
+                if overlap < RELATED_MIN_OVERLAP {
+                    continue;
+                }
+                for (a, b) in [(&l.pair_id, &p.pair_id), (&p.pair_id, &l.pair_id)] {
+                    self.related.entry(a.clone()).or_default().insert(b.clone());
+                }
+                if own == Some(side.other()) {
+                    self.conflicts.push(Conflict {
+                        pair_id: l.pair_id.clone(),
+                        call: l.call,
+                        side,
+                        contig: l.contig.clone(),
+                        start: l.start,
+                        end: l.end,
+                        related_pair_id: p.pair_id.clone(),
+                        related_call: call,
+                        related_start: start,
+                        related_end: end,
+                        overlap,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> CrossRefs {
+        self.conflicts.sort_by(|a, b| {
+            (&a.pair_id, &a.related_pair_id).cmp(&(&b.pair_id, &b.related_pair_id))
+        });
+        CrossRefs {
+            related: self
+                .related
+                .into_iter()
+                .map(|(id, ids)| (id, ids.into_iter().collect()))
+                .collect(),
+            conflicts: self.conflicts,
+        }
+    }
+}
+
+/// The related pairs of every pair and the cross-call conflicts among them.
+#[derive(Debug, Default)]
+pub struct CrossRefs {
+    related: HashMap<String, Vec<String>>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl CrossRefs {
+    /// Both passes over pairs held in memory.
+    pub fn of_pairs<'a>(pairs: impl Iterator<Item = (&'a PairedLocus, Call)> + Clone) -> Self {
+        let mut b = CrossRefBuilder::default();
+        for (p, call) in pairs.clone() {
+            b.add(p, call);
+        }
+        let mut index = b.index();
+        for (p, call) in pairs {
+            index.add(p, call);
+        }
+        index.finish()
+    }
+
+    /// IDs of the pairs related to `pair_id`, in ID order.
+    pub fn related(&self, pair_id: &str) -> Vec<String> {
+        self.related.get(pair_id).cloned().unwrap_or_default()
+    }
+
+    pub fn conflicts_tsv(&self) -> String {
+        let mut s = format!("{CROSS_CALL_CONFLICTS_HEADER}\n");
+        for c in &self.conflicts {
+            let _ = writeln!(
+                s,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}",
+                c.pair_id,
+                c.call.as_str(),
+                c.side.as_str(),
+                c.contig,
+                c.start,
+                c.end,
+                c.related_pair_id,
+                c.related_call.as_str(),
+                c.related_start,
+                c.related_end,
+                c.overlap
+            );
+        }
+        s
+    }
+
+    /// Write `cross_call_conflicts.tsv` (the header alone when there are none).
+    pub fn write(&self, out_dir: &Path) -> Result<()> {
+        fs_err::write(
+            out_dir.join(CROSS_CALL_CONFLICTS_FILE),
+            self.conflicts_tsv(),
+        )?;
+        if !self.conflicts.is_empty() {
+            log::info!(
+                "{} locus/counterpart overlaps between opposite calls; see {CROSS_CALL_CONFLICTS_FILE}",
+                self.conflicts.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+
+    fn pair(id: &str, nuc: (&str, u32, u32), mito: (u32, u32)) -> PairedLocus {
+        PairedLocus {
+            pair_id: id.to_string(),
+            nuc_contig: nuc.0.to_string(),
+            nuc_start: nuc.1,
+            nuc_end: nuc.2,
+            mito_contig: "mt".to_string(),
+            mito_start: mito.0,
+            mito_end: mito.1,
+            aln_len: nuc.2 - nuc.1,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn nimt_locus_that_numts_copy_is_a_conflict() {
+        let pairs = [
+            // a NIMT at mt:1000-2000, and two NUMTs copied from that stretch
+            (
+                pair("P1", ("chr1", 50_000, 51_000), (1000, 2000)),
+                Call::NIMT,
+            ),
+            (pair("P2", ("chr2", 0, 800), (1100, 1900)), Call::NUMT),
+            (pair("P3", ("chr3", 0, 600), (1700, 2300)), Call::NUMT),
+            // an undecided pair of the same mito stretch: related, no conflict
+            (pair("P4", ("chr4", 0, 900), (1000, 1900)), Call::Ambiguous),
+            // a NUMT copied from elsewhere on the mito, and one sharing too
+            // little of the NIMT locus
+            (pair("P5", ("chr5", 0, 1000), (5000, 6000)), Call::NUMT),
+            (pair("P6", ("chr6", 0, 1000), (1800, 2800)), Call::NUMT),
+        ];
+        let refs = CrossRefs::of_pairs(pairs.iter().map(|(p, c)| (p, *c)));
+        assert_eq!(refs.related("P1"), ["P2", "P3", "P4"]);
+        assert_eq!(refs.related("P2"), ["P1"]);
+        assert_eq!(refs.related("P4"), ["P1"]);
+        assert!(refs.related("P5").is_empty() && refs.related("P6").is_empty());
+        // NUMTs of the same mito stretch are not related to each other
+        assert!(!refs.related("P2").contains(&"P3".to_string()));
+
+        let rows: Vec<_> = refs
+            .conflicts
+            .iter()
+            .map(|c| (c.pair_id.as_str(), c.related_pair_id.as_str(), c.side))
+            .collect();
+        assert_eq!(rows, [("P1", "P2", Side::Mito), ("P1", "P3", Side::Mito)]);
+        // 300 of P3's 600 bp counterpart lie in the locus
+        assert!((refs.conflicts[1].overlap - 0.5).abs() < 1e-6);
+        let tsv = refs.conflicts_tsv();
+        assert!(tsv.starts_with(CROSS_CALL_CONFLICTS_HEADER));
+        assert!(tsv.contains(
+            "\nP1\tLikely_NIMT\tmito\tmt\t1000\t2000\tP2\tLikely_NUMT\t1100\t1900\t1.0000\n"
+        ));
+    }
+
+    #[test]
+    fn numt_locus_can_be_the_counterpart_of_a_nimt() {
+        let pairs = [
+            // nuclear chr1:10000-11000 is a NUMT, and the source of a NIMT
+            (pair("P1", ("chr1", 10_000, 11_000), (0, 1000)), Call::NUMT),
+            (
+                pair("P2", ("chr1", 10_200, 10_900), (9000, 9700)),
+                Call::NIMT,
+            ),
+            // overlapping NUMT loci are for collapsing, not cross-referencing
+            (
+                pair("P3", ("chr1", 10_000, 10_800), (4000, 4800)),
+                Call::NUMT,
+            ),
+            // same coordinates on another contig
+            (
+                pair("P4", ("chr2", 10_000, 11_000), (12_000, 13_000)),
+                Call::Ambiguous,
+            ),
+        ];
+        let refs = CrossRefs::of_pairs(pairs.iter().map(|(p, c)| (p, *c)));
+        assert_eq!(refs.related("P1"), ["P2"]);
+        assert_eq!(refs.related("P2"), ["P1", "P3"]);
+        assert!(refs.related("P4").is_empty());
+        let rows: Vec<_> = refs
+            .conflicts
+            .iter()
+            .map(|c| (c.pair_id.as_str(), c.related_pair_id.as_str(), c.side))
+            .collect();
+        assert_eq!(
+            rows,
+            [("P1", "P2", Side::Nuclear), ("P3", "P2", Side::Nuclear)]
+        );
+
+        // nothing decided, nothing related
+        let undecided = pairs.iter().map(|(p, _)| (p, Call::SharedSupport));
+        let refs = CrossRefs::of_pairs(undecided);
+        assert!(refs.related("P1").is_empty() && refs.conflicts.is_empty());
+    }
+}
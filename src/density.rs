@@ -0,0 +1,218 @@
+//! Windowed density tracks (bedGraph) of called bp along each genome:
+//! Likely_NUMT bp per nuclear window and Likely_NIMT bp per mito window.
+//!
+//! Windows tile each contig from 0 in steps of `window` bp; the last window is
+//! truncated at the contig end. Coordinates are 0-based half-open, as bedGraph
+//! expects. Values are bp of the (merged) called intervals inside each window.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::io::fasta::LengthCache;
+use crate::summary::SummaryBuilder;
+use crate::util::warnings;
+
+pub const DEFAULT_DENSITY_WINDOW: u32 = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DensityOptions {
+    pub window: u32,
+    /// Write windows with no called bp as 0 (else they are omitted).
+    pub keep_empty: bool,
+}
+
+impl Default for DensityOptions {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_DENSITY_WINDOW,
+            keep_empty: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedGraphRow {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    pub value: u64,
+}
+
+/// Tile every contig (sorted by name) and sum the overlap of `merged` intervals
+/// (sorted, disjoint per contig) with each window. Intervals on contigs missing
+/// from `contig_lens` are ignored; intervals past a contig end are clipped.
+pub fn window_density(
+    contig_lens: &HashMap<String, u64>,
+    merged: &HashMap<String, Vec<(u32, u32)>>,
+    opts: DensityOptions,
+) -> Vec<BedGraphRow> {
+    let w = opts.window.max(1) as u64;
+    let mut merged_contigs: Vec<&String> = merged.keys().collect();
+    merged_contigs.sort();
+    for chrom in merged_contigs {
+        let ivs = &merged[chrom];
+        match contig_lens.get(chrom) {
+            None if !ivs.is_empty() => warnings::warn(
+                warnings::INTERVAL_CLIPPED,
+                format!(
+                    "density: {} interval(s) on {chrom}, which has no known length, ignored",
+                    ivs.len()
+                ),
+            ),
+            Some(&len) if ivs.last().is_some_and(|iv| iv.1 as u64 > len) => warnings::warn(
+                warnings::INTERVAL_CLIPPED,
+                format!("density: intervals on {chrom} run past its end ({len} bp); clipped"),
+            ),
+            _ => {}
+        }
+    }
+    let mut contigs: Vec<(&String, &u64)> = contig_lens.iter().collect();
+    contigs.sort();
+
+    let mut rows = Vec::new();
+    for (chrom, &len) in contigs {
+        let ivs = merged.get(chrom).map(Vec::as_slice).unwrap_or(&[]);
+        let mut k = 0usize;
+        let mut start = 0u64;
+        while start < len {
+            let end = (start + w).min(len);
+            // skip intervals wholly before this window
+            while k < ivs.len() && (ivs[k].1 as u64) <= start {
+                k += 1;
+            }
+            let mut bp = 0u64;
+            let mut j = k;
+            while j < ivs.len() && (ivs[j].0 as u64) < end {
+                let (s, e) = (ivs[j].0 as u64, ivs[j].1 as u64);
+                bp += e.min(end).saturating_sub(s.max(start));
+                j += 1;
+            }
+            if bp > 0 || opts.keep_empty {
+                rows.push(BedGraphRow {
+                    chrom: chrom.clone(),
+                    start,
+                    end,
+                    value: bp,
+                });
+            }
+            start = end;
+        }
+    }
+    rows
+}
+
+/// bedGraph text with a `track` header line.
+pub fn bedgraph_string(name: &str, description: &str, rows: &[BedGraphRow]) -> String {
+    let mut s = format!("track type=bedGraph name=\"{name}\" description=\"{description}\"\n");
+    for r in rows {
+        let _ = writeln!(s, "{}\t{}\t{}\t{}", r.chrom, r.start, r.end, r.value);
+    }
+    s
+}
+
+/// Write `numt_density.bedgraph` (nuclear) and `nimt_density.bedgraph` (mito).
+pub fn write_density_tracks(
+    out_dir: &Path,
+    mito_fa: &Path,
+    nuc_fa: &Path,
+    lengths: &LengthCache,
+    calls: &SummaryBuilder,
+    opts: DensityOptions,
+) -> Result<()> {
+    let nuc_rows = window_density(
+        &*lengths.lengths(nuc_fa)?,
+        &calls.numt_nuclear_intervals(),
+        opts,
+    );
+    let mito_rows = window_density(
+        &*lengths.lengths(mito_fa)?,
+        &calls.nimt_mito_intervals(),
+        opts,
+    );
+    fs_err::write(
+        out_dir.join("numt_density.bedgraph"),
+        bedgraph_string(
+            "onsm_numt_density",
+            &format!("Likely_NUMT bp per {} bp window", opts.window),
+            &nuc_rows,
+        ),
+    )?;
+    fs_err::write(
+        out_dir.join("nimt_density.bedgraph"),
+        bedgraph_string(
+            "onsm_nimt_density",
+            &format!("Likely_NIMT bp per {} bp window", opts.window),
+            &mito_rows,
+        ),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lens(v: &[(&str, u64)]) -> HashMap<String, u64> {
+        v.iter().map(|(c, l)| (c.to_string(), *l)).collect()
+    }
+
+    fn ivs(v: &[(&str, u32, u32)]) -> HashMap<String, Vec<(u32, u32)>> {
+        let mut m: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        for (c, s, e) in v {
+            m.entry(c.to_string()).or_default().push((*s, *e));
+        }
+        m
+    }
+
+    fn opts(window: u32, keep_empty: bool) -> DensityOptions {
+        DensityOptions { window, keep_empty }
+    }
+
+    #[test]
+    fn tiles_and_truncates_last_window() {
+        let rows = window_density(&lens(&[("chr1", 250)]), &HashMap::new(), opts(100, true));
+        let spans: Vec<(u64, u64, u64)> = rows.iter().map(|r| (r.start, r.end, r.value)).collect();
+        assert_eq!(spans, vec![(0, 100, 0), (100, 200, 0), (200, 250, 0)]);
+    }
+
+    #[test]
+    fn bp_split_across_windows_and_clipped() {
+        // [90,130) straddles windows 0 and 1; [240,400) runs past the contig end
+        let rows = window_density(
+            &lens(&[("chr1", 250)]),
+            &ivs(&[("chr1", 90, 130), ("chr1", 150, 160), ("chr1", 240, 400)]),
+            opts(100, false),
+        );
+        let spans: Vec<(u64, u64, u64)> = rows.iter().map(|r| (r.start, r.end, r.value)).collect();
+        assert_eq!(spans, vec![(0, 100, 10), (100, 200, 40), (200, 250, 10)]);
+    }
+
+    #[test]
+    fn empty_windows_omitted_or_zero() {
+        let l = lens(&[("a", 300), ("b", 100)]);
+        let i = ivs(&[("a", 210, 220), ("zzz", 0, 10)]);
+        let omit = window_density(&l, &i, opts(100, false));
+        assert_eq!(omit.len(), 1);
+        assert_eq!((omit[0].chrom.as_str(), omit[0].start), ("a", 200));
+        let zero = window_density(&l, &i, opts(100, true));
+        assert_eq!(zero.len(), 4);
+        assert_eq!(zero[3].chrom, "b");
+    }
+
+    #[test]
+    fn bedgraph_has_track_header() {
+        let rows = vec![BedGraphRow {
+            chrom: "chr1".into(),
+            start: 0,
+            end: 100,
+            value: 7,
+        }];
+        let s = bedgraph_string("n", "d", &rows);
+        assert_eq!(
+            s,
+            "track type=bedGraph name=\"n\" description=\"d\"\nchr1\t0\t100\t7\n"
+        );
+    }
+}
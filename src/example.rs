@@ -0,0 +1,316 @@
+//! A miniature dataset with planted NUMTs, generated deterministically, for
+//! trying onsm without real data (`onsm example`).
+//!
+//! The mito is 16 kb of random sequence; three short stretches of it (one
+//! reverse-complemented, each ~1% diverged) are copied into two random
+//! nuclear contigs. HiFi-like reads (8–14 kb, 0.1% substitutions, either
+//! strand) are sampled from both assemblies. The NUMTs are shorter than the
+//! 500 bp span window and the nuclear depth is above the mito depth, so
+//! nuclear reads span the nuclear loci while the mito windows are mostly
+//! covered by reads that only clip onto them: the span evidence points at the
+//! nuclear side. Everything derives from a fixed seed, so a given onsm version
+//! always writes the same files.
+
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+
+use crate::io::fasta::write_fasta;
+use crate::model::orientation::Strand;
+use crate::self_exclusion::complement;
+
+pub const MITO_FILE: &str = "mito.fa";
+pub const NUCLEAR_FILE: &str = "nuclear.fa";
+pub const READS_FILE: &str = "reads.fq.gz";
+pub const EXPECTED_FILE: &str = "expected_numts.tsv";
+pub const RUN_SCRIPT: &str = "run.sh";
+
+pub const EXPECTED_HEADER: &str =
+    "numt\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\tstrand\texpected_call";
+
+const SEED: u64 = 0x6f6e_736d;
+const MITO_CONTIG: &str = "chrM";
+const MITO_LEN: usize = 16_000;
+const NUCLEAR_CONTIGS: &[(&str, usize)] = &[("chr1", 120_000), ("chr2", 120_000)];
+/// (nuclear contig, nuclear start, mito start, length, strand)
+const PLANTED: &[(&str, usize, usize, usize, Strand)] = &[
+    ("chr1", 30_000, 2_000, 400, Strand::Forward),
+    ("chr1", 80_000, 7_500, 450, Strand::Reverse),
+    ("chr2", 60_000, 12_000, 380, Strand::Forward),
+];
+/// Substitution rate of a NUMT relative to its mito source.
+const NUMT_DIVERGENCE: f64 = 0.01;
+const READ_ERROR_RATE: f64 = 0.001;
+const READ_LEN: (usize, usize) = (8_000, 14_000);
+const NUCLEAR_DEPTH: usize = 40;
+const MITO_DEPTH: usize = 15;
+
+/// splitmix64: small, fast and the same on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `lo..hi`.
+    fn range(&mut self, lo: usize, hi: usize) -> usize {
+        lo + (self.next_u64() % (hi - lo) as u64) as usize
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 <= p
+    }
+
+    fn base(&mut self) -> u8 {
+        b"ACGT"[(self.next_u64() & 3) as usize]
+    }
+
+    fn sequence(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.base()).collect()
+    }
+
+    /// Substitute each base with probability `rate` (always for a different base).
+    fn mutate(&mut self, seq: &mut [u8], rate: f64) {
+        for b in seq.iter_mut() {
+            if self.chance(rate) {
+                let old = *b;
+                while *b == old {
+                    *b = self.base();
+                }
+            }
+        }
+    }
+}
+
+fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| complement(b)).collect()
+}
+
+/// One NUMT copied into the nuclear assembly (0-based, half-open).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlantedNumt {
+    pub name: String,
+    pub nuc_contig: String,
+    pub nuc_start: u32,
+    pub nuc_end: u32,
+    pub mito_contig: String,
+    pub mito_start: u32,
+    pub mito_end: u32,
+    pub strand: Strand,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExampleDataset {
+    pub mito: (String, Vec<u8>),
+    pub nuclear: Vec<(String, Vec<u8>)>,
+    pub planted: Vec<PlantedNumt>,
+    seed: u64,
+}
+
+impl ExampleDataset {
+    /// The bundled example: always the same sequences and loci.
+    pub fn generate() -> Self {
+        let mut rng = Rng(SEED);
+        let mito = rng.sequence(MITO_LEN);
+        let mut nuclear: Vec<(String, Vec<u8>)> = NUCLEAR_CONTIGS
+            .iter()
+            .map(|&(name, len)| (name.to_string(), rng.sequence(len)))
+            .collect();
+        let mut planted = Vec::new();
+        for (i, &(contig, nuc_start, mito_start, len, strand)) in PLANTED.iter().enumerate() {
+            let src = &mito[mito_start..mito_start + len];
+            let mut copy = match strand {
+                Strand::Forward => src.to_vec(),
+                Strand::Reverse => revcomp(src),
+            };
+            rng.mutate(&mut copy, NUMT_DIVERGENCE);
+            let seq = &mut nuclear.iter_mut().find(|(n, _)| n == contig).unwrap().1;
+            seq[nuc_start..nuc_start + len].copy_from_slice(&copy);
+            planted.push(PlantedNumt {
+                name: format!("NUMT{}", i + 1),
+                nuc_contig: contig.to_string(),
+                nuc_start: nuc_start as u32,
+                nuc_end: (nuc_start + len) as u32,
+                mito_contig: MITO_CONTIG.to_string(),
+                mito_start: mito_start as u32,
+                mito_end: (mito_start + len) as u32,
+                strand,
+            });
+        }
+        Self {
+            mito: (MITO_CONTIG.to_string(), mito),
+            nuclear,
+            planted,
+            seed: rng.next_u64(),
+        }
+    }
+
+    /// Simulated reads as (name, sequence), nuclear first.
+    pub fn reads(&self) -> Vec<(String, Vec<u8>)> {
+        let mut rng = Rng(self.seed);
+        let mut out = Vec::new();
+        let sources = self
+            .nuclear
+            .iter()
+            .map(|c| ("nuc", c, NUCLEAR_DEPTH))
+            .chain(std::iter::once(("mito", &self.mito, MITO_DEPTH)));
+        for (kind, (contig, seq), depth) in sources {
+            let mean_len = (READ_LEN.0 + READ_LEN.1) / 2;
+            let n = (seq.len() * depth).div_ceil(mean_len);
+            for _ in 0..n {
+                let len = rng.range(READ_LEN.0, READ_LEN.1).min(seq.len());
+                let start = rng.range(0, seq.len() - len + 1);
+                let mut read = seq[start..start + len].to_vec();
+                if rng.chance(0.5) {
+                    read = revcomp(&read);
+                }
+                rng.mutate(&mut read, READ_ERROR_RATE);
+                let name = format!("{kind}_{}_{}_{start}", out.len() + 1, contig);
+                out.push((name, read));
+            }
+        }
+        out
+    }
+
+    /// Write the FASTAs, gzipped FASTQ, expected loci and `run.sh` into `dir`.
+    pub fn write_to(&self, dir: &Path) -> Result<()> {
+        fs_err::create_dir_all(dir)?;
+        write_fasta(&dir.join(MITO_FILE), std::slice::from_ref(&self.mito))?;
+        write_fasta(&dir.join(NUCLEAR_FILE), &self.nuclear)?;
+
+        let f = fs_err::File::create(dir.join(READS_FILE))?;
+        let mut gz = GzEncoder::new(std::io::BufWriter::new(f), flate2::Compression::default());
+        for (name, seq) in self.reads() {
+            writeln!(gz, "@{name}")?;
+            gz.write_all(&seq)?;
+            writeln!(gz, "\n+")?;
+            gz.write_all(&vec![b'I'; seq.len()])?;
+            writeln!(gz)?;
+        }
+        gz.finish()?.flush()?;
+
+        fs_err::write(dir.join(EXPECTED_FILE), self.expected_tsv())?;
+        let script = dir.join(RUN_SCRIPT);
+        fs_err::write(&script, run_script())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs_err::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    pub fn expected_tsv(&self) -> String {
+        let mut s = format!("{EXPECTED_HEADER}\n");
+        for p in &self.planted {
+            let _ = writeln!(
+                s,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\tLikely_NUMT",
+                p.name,
+                p.nuc_contig,
+                p.nuc_start,
+                p.nuc_end,
+                p.mito_contig,
+                p.mito_start,
+                p.mito_end,
+                p.strand.as_char()
+            );
+        }
+        s
+    }
+}
+
+/// The `onsm classify` command line for the example, relative to its directory.
+pub fn classify_args() -> Vec<&'static str> {
+    vec![
+        "classify",
+        "--mito",
+        MITO_FILE,
+        "--nuclear",
+        NUCLEAR_FILE,
+        "--reads",
+        READS_FILE,
+        "--platform",
+        "hifi",
+        "--out",
+        "results",
+    ]
+}
+
+fn run_script() -> String {
+    format!(
+        "#!/bin/sh\n\
+         # Written by `onsm example`. Needs onsm, minimap2 and samtools on PATH.\n\
+         # Compare results/classification.tsv with {EXPECTED_FILE}.\n\
+         set -e\n\
+         cd \"$(dirname \"$0\")\"\n\
+         onsm {}\n",
+        classify_args().join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(a: &[u8], b: &[u8]) -> f64 {
+        let same = a.iter().zip(b).filter(|(x, y)| x == y).count();
+        same as f64 / a.len() as f64
+    }
+
+    #[test]
+    fn planted_numts_are_diverged_mito_copies() {
+        let d = ExampleDataset::generate();
+        assert_eq!(d.planted.len(), PLANTED.len());
+        for p in &d.planted {
+            let nuc = &d
+                .nuclear
+                .iter()
+                .find(|(n, _)| *n == p.nuc_contig)
+                .unwrap()
+                .1;
+            let copy = &nuc[p.nuc_start as usize..p.nuc_end as usize];
+            let src = &d.mito.1[p.mito_start as usize..p.mito_end as usize];
+            let src = match p.strand {
+                Strand::Forward => src.to_vec(),
+                Strand::Reverse => revcomp(src),
+            };
+            let id = identity(copy, &src);
+            assert!((0.97..1.0).contains(&id), "{}: identity {id}", p.name);
+            // unrelated random sequence is ~25% identical
+            let flank = &nuc[p.nuc_end as usize..p.nuc_end as usize + src.len()];
+            assert!(identity(flank, &src) < 0.4, "{}", p.name);
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic_and_covers_both_assemblies() {
+        let (a, b) = (ExampleDataset::generate(), ExampleDataset::generate());
+        assert_eq!(a.nuclear, b.nuclear);
+        let (ra, rb) = (a.reads(), b.reads());
+        assert_eq!(ra, rb);
+
+        let bases = |kind: &str| -> usize {
+            ra.iter()
+                .filter(|(n, _)| n.starts_with(kind))
+                .map(|(_, s)| s.len())
+                .sum()
+        };
+        let nuc_len: usize = NUCLEAR_CONTIGS.iter().map(|c| c.1).sum();
+        let nuc_depth = bases("nuc_") as f64 / nuc_len as f64;
+        let mito_depth = bases("mito_") as f64 / MITO_LEN as f64;
+        assert!(
+            (nuc_depth - NUCLEAR_DEPTH as f64).abs() < 2.0,
+            "{nuc_depth}"
+        );
+        assert!((mito_depth - MITO_DEPTH as f64).abs() < 2.0, "{mito_depth}");
+        assert!(ra.iter().all(|(_, s)| s.len() >= READ_LEN.0.min(MITO_LEN)));
+    }
+}
@@ -0,0 +1,450 @@
+//! Index artifacts a classify run can reuse instead of rebuilding or
+//! rescanning: minimap2 indexes of the assemblies as `-x asm10` targets
+//! (`mito.mmi`, `nuclear.mmi`), their faidx indexes (`mito.fai`,
+//! `nuclear.fai`) and both assemblies' `contig_lengths.json`.
+//!
+//! They live in `--index-dir`, by default the run's `--out`. Each has a
+//! sidecar, `<name>.source.json`, with the size and FNV-1a checksum of every
+//! FASTA it was built from, and is used only while the FASTAs given still
+//! match. One without a sidecar, or a stale one, is ignored, and replaced
+//! when classify builds that kind (`contig_lengths.json` always, the
+//! others with `--build-indexes`).
+//!
+//! `is_artifact` is how the `--out` overwrite guard and bundles tell these
+//! files from a run's outputs.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::io::fasta::{FastaCompression, LengthCache};
+use crate::io::window_cache::{fnv1a, FNV_OFFSET};
+use crate::model::orientation::Side;
+use crate::util::mapping;
+use crate::util::shutdown::CancelToken;
+
+pub const CONTIG_LENGTHS_FILE: &str = "contig_lengths.json";
+/// Appended to an artifact's name for its sidecar.
+pub const SIDECAR_SUFFIX: &str = ".source.json";
+
+/// `mito.<ext>` or `nuclear.<ext>`.
+pub fn file_name(side: Side, ext: &str) -> String {
+    match side {
+        Side::Mito => format!("mito.{ext}"),
+        Side::Nuclear => format!("nuclear.{ext}"),
+    }
+}
+
+/// Whether a file called `name` is an index artifact or a sidecar of one.
+pub fn is_artifact(name: &str) -> bool {
+    let name = name.strip_suffix(SIDECAR_SUFFIX).unwrap_or(name);
+    name == CONTIG_LENGTHS_FILE
+        || [Side::Mito, Side::Nuclear].into_iter().any(|side| {
+            ["mmi", "fai"]
+                .iter()
+                .any(|ext| name == file_name(side, ext))
+        })
+}
+
+/// A FASTA an artifact was built from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStamp {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// `fnv1a64:<hex>` of the file's bytes.
+    pub checksum: String,
+}
+
+impl SourceStamp {
+    /// Reads all of `p`.
+    pub fn of(p: &Path) -> Result<Self> {
+        let mut f = fs_err::File::open(p)?;
+        let mut buf = vec![0u8; 1 << 20];
+        let (mut hash, mut bytes) = (FNV_OFFSET, 0u64);
+        loop {
+            let n = f
+                .read(&mut buf)
+                .with_context(|| format!("read {}", p.display()))?;
+            if n == 0 {
+                break;
+            }
+            hash = fnv1a(&buf[..n], hash);
+            bytes += n as u64;
+        }
+        Ok(Self {
+            path: fs_err::canonicalize(p).unwrap_or_else(|_| p.to_path_buf()),
+            bytes,
+            checksum: format!("fnv1a64:{hash:016x}"),
+        })
+    }
+
+    /// Same contents, wherever the file now is.
+    fn matches(&self, other: &Self) -> bool {
+        (self.bytes, &self.checksum) == (other.bytes, &other.checksum)
+    }
+}
+
+/// `<name>.source.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sidecar {
+    sources: Vec<SourceStamp>,
+}
+
+/// `contig_lengths.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContigLengthsFile {
+    pub mito: BTreeMap<String, u64>,
+    pub nuclear: BTreeMap<String, u64>,
+}
+
+/// The artifacts of one index directory, checked against the run's FASTAs.
+#[derive(Debug)]
+pub struct IndexDir {
+    dir: PathBuf,
+    /// Each FASTA is checksummed at most once.
+    stamps: Mutex<HashMap<PathBuf, SourceStamp>>,
+}
+
+impl IndexDir {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            stamps: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn sidecar_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}{SIDECAR_SUFFIX}"))
+    }
+
+    fn stamp_of(&self, fasta: &Path) -> Result<SourceStamp> {
+        let mut stamps = self.stamps.lock().unwrap();
+        if let Some(s) = stamps.get(fasta) {
+            return Ok(s.clone());
+        }
+        let s = SourceStamp::of(fasta)?;
+        stamps.insert(fasta.to_path_buf(), s.clone());
+        Ok(s)
+    }
+
+    /// Whether `name` exists with a sidecar matching `sources`; says why not
+    /// in the log when it exists.
+    fn current(&self, name: &str, sources: &[&Path]) -> Result<bool> {
+        let artifact = self.path(name);
+        if !artifact.is_file() {
+            return Ok(false);
+        }
+        let sidecar = self.sidecar_path(name);
+        let recorded: Sidecar = match fs_err::read_to_string(&sidecar)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+        {
+            Some(s) => s,
+            None => {
+                log::info!(
+                    "{}: no readable {}, so not reusing it",
+                    artifact.display(),
+                    sidecar.display()
+                );
+                return Ok(false);
+            }
+        };
+        if recorded.sources.len() != sources.len() {
+            log::info!(
+                "{}: {} is not for these inputs",
+                artifact.display(),
+                sidecar.display()
+            );
+            return Ok(false);
+        }
+        for (was, fasta) in recorded.sources.iter().zip(sources) {
+            // a size change is enough, without reading the FASTA
+            let stale =
+                fs_err::metadata(fasta)?.len() != was.bytes || !self.stamp_of(fasta)?.matches(was);
+            if stale {
+                log::info!(
+                    "{} is stale: built from a different {} than {}",
+                    artifact.display(),
+                    was.path.display(),
+                    fasta.display()
+                );
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Record that `name` was just built from `sources`.
+    fn stamp(&self, name: &str, sources: &[&Path]) -> Result<()> {
+        let sidecar = Sidecar {
+            sources: sources
+                .iter()
+                .map(|p| self.stamp_of(p))
+                .collect::<Result<_>>()?,
+        };
+        let f = fs_err::File::create(self.sidecar_path(name))?;
+        serde_json::to_writer_pretty(f, &sidecar)?;
+        Ok(())
+    }
+
+    /// `side`'s minimap2 index, if it is current for `fasta`.
+    pub fn mmi(&self, side: Side, fasta: &Path) -> Result<Option<PathBuf>> {
+        let name = file_name(side, "mmi");
+        Ok(self.current(&name, &[fasta])?.then(|| self.path(&name)))
+    }
+
+    /// `side`'s minimap2 index, built from `fasta` unless it is current.
+    pub fn build_mmi(
+        &self,
+        mm2: &Path,
+        side: Side,
+        fasta: &Path,
+        threads: usize,
+        cancel: &CancelToken,
+    ) -> Result<PathBuf> {
+        if let Some(p) = self.mmi(side, fasta)? {
+            log::info!("reusing {}", p.display());
+            return Ok(p);
+        }
+        let name = file_name(side, "mmi");
+        fs_err::create_dir_all(&self.dir)?;
+        mapping::build_asm_index(mm2, fasta, &self.path(&name), threads, cancel)?;
+        self.stamp(&name, &[fasta])?;
+        Ok(self.path(&name))
+    }
+
+    /// Build `side`'s `.fai` from `fasta` with samtools unless it is current;
+    /// nothing for a compressed FASTA.
+    pub fn build_fai(&self, sam: &Path, side: Side, fasta: &Path) -> Result<()> {
+        let name = file_name(side, "fai");
+        if self.current(&name, &[fasta])? {
+            return Ok(());
+        }
+        if FastaCompression::detect(fasta)? != FastaCompression::None {
+            log::info!("{} is compressed; not building {name}", fasta.display());
+            return Ok(());
+        }
+        fs_err::create_dir_all(&self.dir)?;
+        mapping::faidx(sam, fasta, &self.path(&name))?;
+        self.stamp(&name, &[fasta])
+    }
+
+    /// Seed `lengths` with both assemblies' contig lengths from a current
+    /// `contig_lengths.json`, else from each one's current `.fai`, so the
+    /// FASTAs need not be read for them. Returns how many were seeded.
+    pub fn seed_lengths(
+        &self,
+        lengths: &LengthCache,
+        mito: &Path,
+        nuclear: &Path,
+    ) -> Result<usize> {
+        if self.current(CONTIG_LENGTHS_FILE, &[mito, nuclear])? {
+            let f = fs_err::File::open(self.path(CONTIG_LENGTHS_FILE))?;
+            let c: ContigLengthsFile = serde_json::from_reader(std::io::BufReader::new(f))
+                .with_context(|| format!("parse {}", self.path(CONTIG_LENGTHS_FILE).display()))?;
+            lengths.seed(mito, c.mito.into_iter().collect())?;
+            lengths.seed(nuclear, c.nuclear.into_iter().collect())?;
+            log::info!(
+                "contig lengths from {}",
+                self.path(CONTIG_LENGTHS_FILE).display()
+            );
+            return Ok(2);
+        }
+        let mut n = 0;
+        for (side, fasta) in [(Side::Mito, mito), (Side::Nuclear, nuclear)] {
+            let name = file_name(side, "fai");
+            if self.current(&name, &[fasta])? {
+                lengths.seed(fasta, fai_lengths(&self.path(&name))?)?;
+                log::info!("contig lengths of {} from {name}", fasta.display());
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
+    /// Write `contig_lengths.json` from `lengths` unless it is current.
+    pub fn save_contig_lengths(
+        &self,
+        lengths: &LengthCache,
+        mito: &Path,
+        nuclear: &Path,
+    ) -> Result<()> {
+        if self.current(CONTIG_LENGTHS_FILE, &[mito, nuclear])? {
+            return Ok(());
+        }
+        let c = ContigLengthsFile {
+            mito: lengths
+                .lengths(mito)?
+                .iter()
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            nuclear: lengths
+                .lengths(nuclear)?
+                .iter()
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+        };
+        fs_err::create_dir_all(&self.dir)?;
+        let f = fs_err::File::create(self.path(CONTIG_LENGTHS_FILE))?;
+        serde_json::to_writer_pretty(f, &c)?;
+        self.stamp(CONTIG_LENGTHS_FILE, &[mito, nuclear])
+    }
+}
+
+/// Contig name → length, from the first two columns of a `.fai`.
+fn fai_lengths(fai: &Path) -> Result<HashMap<String, u64>> {
+    let txt = fs_err::read_to_string(fai)?;
+    txt.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut f = line.split('\t');
+            match (f.next(), f.next().and_then(|v| v.parse::<u64>().ok())) {
+                (Some(name), Some(len)) if !name.is_empty() => Ok((name.to_string(), len)),
+                _ => anyhow::bail!("{}:{}: not a .fai line", fai.display(), i + 1),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fastas(dir: &Path) -> (PathBuf, PathBuf) {
+        let (mito, nuc) = (dir.join("mt.fa"), dir.join("nuc.fa"));
+        fs_err::write(&mito, ">mt\nACGTACGT\n").unwrap();
+        fs_err::write(&nuc, ">chr1\nCCCC\n>chr2\nGG\n").unwrap();
+        (mito, nuc)
+    }
+
+    #[test]
+    fn artifacts_recognised_by_name() {
+        for name in [
+            "mito.mmi",
+            "nuclear.mmi",
+            "mito.fai",
+            "nuclear.fai.source.json",
+            "contig_lengths.json",
+            "contig_lengths.json.source.json",
+        ] {
+            assert!(is_artifact(name), "{name}");
+        }
+        for name in ["run_manifest.json", "pairs.tsv", "mito.fa", "other.mmi"] {
+            assert!(!is_artifact(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn contig_lengths_reused_until_a_fasta_changes() {
+        let td = TempDir::new().unwrap();
+        let (mito, nuc) = fastas(td.path());
+        let idx = IndexDir::new(&td.path().join("idx"));
+
+        let lengths = LengthCache::default();
+        assert_eq!(idx.seed_lengths(&lengths, &mito, &nuc).unwrap(), 0);
+        idx.save_contig_lengths(&lengths, &mito, &nuc).unwrap();
+        assert_eq!(lengths.scans(), 2);
+
+        // a later run reads neither FASTA for its lengths
+        let lengths = LengthCache::default();
+        let idx = IndexDir::new(&td.path().join("idx"));
+        assert_eq!(idx.seed_lengths(&lengths, &mito, &nuc).unwrap(), 2);
+        assert_eq!(lengths.lengths(&nuc).unwrap()["chr2"], 2);
+        assert_eq!(lengths.scans(), 0);
+
+        // same size, other bases: only the checksum tells
+        fs_err::write(&nuc, ">chr1\nCCCC\n>chr2\nGA\n").unwrap();
+        let idx = IndexDir::new(&td.path().join("idx"));
+        assert_eq!(
+            idx.seed_lengths(&LengthCache::default(), &mito, &nuc)
+                .unwrap(),
+            0
+        );
+        // and an artifact without its sidecar is not trusted
+        fs_err::remove_file(idx.sidecar_path(CONTIG_LENGTHS_FILE)).unwrap();
+        fs_err::write(&nuc, ">chr1\nCCCC\n>chr2\nGG\n").unwrap();
+        assert_eq!(
+            idx.seed_lengths(&LengthCache::default(), &mito, &nuc)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn fai_lengths_seed_without_contig_lengths() {
+        let td = TempDir::new().unwrap();
+        let (mito, nuc) = fastas(td.path());
+        let idx = IndexDir::new(td.path());
+        fs_err::write(
+            idx.path("nuclear.fai"),
+            "chr1\t4\t6\t4\t5\nchr2\t2\t17\t2\t3\n",
+        )
+        .unwrap();
+        idx.stamp("nuclear.fai", &[&nuc]).unwrap();
+        let lengths = LengthCache::default();
+        assert_eq!(idx.seed_lengths(&lengths, &mito, &nuc).unwrap(), 1);
+        assert_eq!(lengths.lengths(&nuc).unwrap()["chr1"], 4);
+        assert_eq!(lengths.scans(), 0);
+        assert_eq!(lengths.lengths(&mito).unwrap()["mt"], 8);
+        assert_eq!(lengths.scans(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mmi_built_once_and_rebuilt_when_stale() {
+        use std::os::unix::fs::PermissionsExt;
+        let td = TempDir::new().unwrap();
+        let (mito, nuc) = fastas(td.path());
+        // Mock minimap2: record each invocation, write the -d target.
+        let mock = td.path().join("minimap2");
+        let log = td.path().join("calls.txt");
+        fs_err::write(
+            &mock,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> '{}'\nwhile [ $# -gt 1 ]; do [ \"$1\" = -d ] && echo mmi > \"$2\"; shift; done\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&mock, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let calls = || {
+            fs_err::read_to_string(&log)
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+
+        let out = td.path().join("out");
+        let build = |side, fa: &Path| {
+            IndexDir::new(&out)
+                .build_mmi(&mock, side, fa, 2, &CancelToken::default())
+                .unwrap()
+        };
+        assert_eq!(build(Side::Nuclear, &nuc), out.join("nuclear.mmi"));
+        assert_eq!(calls(), 1);
+        assert!(fs_err::read_to_string(&log)
+            .unwrap()
+            .contains("-x asm10 -t 2 -d "));
+        assert_eq!(build(Side::Nuclear, &nuc), out.join("nuclear.mmi"));
+        assert_eq!(calls(), 1, "a current index is not rebuilt");
+        assert_eq!(
+            IndexDir::new(&out).mmi(Side::Nuclear, &nuc).unwrap(),
+            Some(out.join("nuclear.mmi"))
+        );
+        // an index of the nuclear FASTA is not the mito's
+        assert_eq!(IndexDir::new(&out).mmi(Side::Mito, &mito).unwrap(), None);
+
+        fs_err::write(&nuc, ">chr1\nCCCCCC\n").unwrap();
+        assert_eq!(IndexDir::new(&out).mmi(Side::Nuclear, &nuc).unwrap(), None);
+        build(Side::Nuclear, &nuc);
+        assert_eq!(calls(), 2);
+    }
+}
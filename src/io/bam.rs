@@ -1,47 +1,170 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::path::Path;
-use std::process::Command;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdout, Command, Stdio};
 
-use crate::model::{CoverageSummary, PairedLocus, SpanSummary};
+use crate::io::bam_index::{self, IndexedBams};
+use crate::io::window_cache::{WindowCache, WindowKey};
+use crate::mito_meta::MitoContigMeta;
+use crate::model::{
+    self, CoverageSummary, EvidenceParams, PairEvidence, PairedLocus, SpanReads, SpanSummary,
+};
+use crate::summary::ContigLengths;
+use crate::util::cigar::{Cigar, Op};
+use crate::util::mapping::SamtoolsFeature;
+use crate::util::warnings;
 
-/// Half-open window on reference in 0-based coordinates [start, end).
+/// samtools invocations the evidence stage needs; check with
+/// `SamtoolsCaps::require` before starting the coverage loop.
+pub const EVIDENCE_FEATURES: &[SamtoolsFeature] =
+    &[SamtoolsFeature::DepthRegion, SamtoolsFeature::ViewRegion];
+/// …and with supplementary alignments kept out of depth, the default.
+const EVIDENCE_FEATURES_EXCL: &[SamtoolsFeature] = &[
+    SamtoolsFeature::DepthRegion,
+    SamtoolsFeature::DepthExclFlags,
+    SamtoolsFeature::ViewRegion,
+];
+
+/// The features the evidence stage needs with `--depth-include-supplementary`
+/// set to `depth_supplementary`.
+pub fn evidence_features(depth_supplementary: bool) -> &'static [SamtoolsFeature] {
+    if depth_supplementary {
+        EVIDENCE_FEATURES
+    } else {
+        EVIDENCE_FEATURES_EXCL
+    }
+}
+
+/// How the evidence windows are read from the reads→assembly BAMs
+/// (`--bam-backend`). Both give the same depths and span records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BamBackend {
+    /// `samtools depth` and `samtools view` per window.
+    #[default]
+    Samtools,
+    /// Read the BAM through its `.bai` index in process (`io::bam_index`):
+    /// no process per window, and no samtools for the evidence stage.
+    Native,
+}
+
+/// `samtools depth -G` without supplementary alignments. `-G` replaces the
+/// default exclusions (UNMAP, SECONDARY, QCFAIL, DUP), so they are repeated
+/// next to SUPPLEMENTARY (0x800).
+const DEPTH_EXCL_FLAGS: &str = "0xf04";
+/// The same as flag bits, and samtools depth's default exclusions (no `-G`),
+/// for the native backend.
+const DEPTH_EXCL: u16 = 0xf04;
+const DEPTH_DEFAULT_EXCL: u16 = 0x704;
+const FLAG_SECONDARY: u16 = 0x100;
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+/// Half-open window on reference in 0-based coordinates [start, end); the
+/// start may lie before the contig (clamped when the BAM is read). Built by
+/// `Window::around`, which refuses windows no region could express, and cut
+/// at the contig end by `Window::clamp_to`.
 #[derive(Debug, Clone, Copy)]
 pub struct Window {
     pub start: i32,
     pub end: i32,
 }
 
+/// Why a pair's evidence windows could not be built. The pair is then scored
+/// Ambiguous with the `invalid_window` reason code rather than from a
+/// made-up region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum WindowError {
+    /// A locus whose end is before its start.
+    #[error("the locus ends before it starts")]
+    Inverted,
+    /// A locus, or a window (flank 0), without a base.
+    #[error("zero-width locus or window")]
+    ZeroWidth,
+    /// Coordinates past what a BAM region can address (2^31 − 1).
+    #[error("coordinates beyond the largest BAM position")]
+    OutOfRange,
+    /// A window that starts at or past the end of its contig.
+    #[error("the window lies past the contig end")]
+    PastContigEnd,
+}
+
+/// The midpoint of the locus [start, end), without overflowing.
+pub fn locus_mid(start: u32, end: u32) -> std::result::Result<u64, WindowError> {
+    match end.cmp(&start) {
+        std::cmp::Ordering::Less => Err(WindowError::Inverted),
+        std::cmp::Ordering::Equal => Err(WindowError::ZeroWidth),
+        std::cmp::Ordering::Greater => Ok((u64::from(start) + u64::from(end)) / 2),
+    }
+}
+
+impl Window {
+    /// ±`half` around `mid`.
+    pub fn around(mid: u64, half: u32) -> std::result::Result<Self, WindowError> {
+        if half == 0 {
+            return Err(WindowError::ZeroWidth);
+        }
+        let (mid, half) = (
+            i64::try_from(mid).map_err(|_| WindowError::OutOfRange)?,
+            i64::from(half),
+        );
+        match (i32::try_from(mid - half), i32::try_from(mid + half)) {
+            (Ok(start), Ok(end)) => Ok(Self { start, end }),
+            _ => Err(WindowError::OutOfRange),
+        }
+    }
+
+    /// The window cut at the end of a contig of `len` bp, so that no read
+    /// has to reach past the contig to span it; unchanged when the length is
+    /// unknown. The start is clamped when the BAM is read (`region_bounds`).
+    pub fn clamp_to(self, len: Option<u64>) -> std::result::Result<Self, WindowError> {
+        let Some(len) = len else {
+            return Ok(self);
+        };
+        let end = i64::from(self.end).min(i64::try_from(len).unwrap_or(i64::MAX)) as i32;
+        if end <= self.start.max(0) {
+            return Err(WindowError::PastContigEnd);
+        }
+        Ok(Self { end, ..self })
+    }
+
+    /// Bases of the window on its contig: without the part before its start.
+    pub fn width(self) -> u32 {
+        let (beg, end) = region_bounds(self);
+        end - beg
+    }
+}
+
 fn region_str(rname: &str, w: Window) -> String {
+    let (beg, end) = region_bounds(w);
     // samtools uses 1-based inclusive coordinates
-    let s1 = (w.start.max(0) + 1) as usize;
-    let e1 = w.end.max(w.start + 1) as usize;
-    format!("{rname}:{s1}-{e1}")
+    format!("{rname}:{}-{end}", beg + 1)
 }
 
-fn parse_cigar_ref_consumed(cigar: &str) -> Option<u32> {
-    // Sum of ref-consuming ops: M, =, X, D, N
-    let mut num = 0u64;
-    let mut acc = 0u64;
-    for ch in cigar.bytes() {
-        match ch {
-            b'0'..=b'9' => {
-                num = num * 10 + (ch - b'0') as u64;
-            }
-            b'M' | b'=' | b'X' | b'D' | b'N' => {
-                acc = acc.saturating_add(num);
-                num = 0;
-            }
-            b'I' | b'S' | b'H' | b'P' => {
-                num = 0;
-            }
-            _ => return None, // malformed
-        }
+/// The region of `region_str` as 0-based half-open bounds: the window
+/// clamped at the contig start, which `Window::around` leaves at least a base.
+fn region_bounds(w: Window) -> (u32, u32) {
+    debug_assert!(w.end > w.start.max(0), "degenerate window {w:?}");
+    (w.start.max(0) as u32, w.end.max(0) as u32)
+}
+
+/// `samtools <sub>`, decoding a CRAM against `reference` (`--reference`)
+/// when there is one; BAMs need none.
+fn samtools_cmd(samtools: &Path, sub: &str, reference: Option<&Path>) -> Command {
+    let mut cmd = Command::new(samtools);
+    cmd.arg(sub);
+    if let Some(fa) = reference {
+        cmd.arg("--reference").arg(fa);
     }
-    Some(acc.min(u32::MAX as u64) as u32)
+    cmd
 }
 
-fn median_f32(mut v: Vec<f32>) -> f32 {
+pub(crate) fn median_f32(mut v: Vec<f32>) -> f32 {
     if v.is_empty() {
         return 0.0;
     }
@@ -54,12 +177,65 @@ fn median_f32(mut v: Vec<f32>) -> f32 {
     }
 }
 
-/// Compute local median depth in a region using `samtools depth`.
-fn local_median_depth(samtools: &Path, bam: &Path, rname: &str, w: Window) -> Result<f32> {
+/// The (nuclear, mito) baselines depths are normalized by, and the medians of
+/// the pairs' local medians (`nuc_locals`, `mito_locals`). The baselines are
+/// the `genome` medians (`WindowFetch::genome_median_depths`); where those
+/// are missing or 0, the loci's, with a warning: candidates are unusual
+/// regions, so their median is a biased one. Both are logged.
+pub fn depth_baselines(
+    genome: Option<(f32, f32)>,
+    nuc_locals: Vec<f32>,
+    mito_locals: Vec<f32>,
+) -> ((f64, f64), (f64, f64)) {
+    let loci = (
+        median_f32(nuc_locals) as f64,
+        median_f32(mito_locals) as f64,
+    );
+    let pick = |side: &str, genome: Option<f32>, loci: f64| match genome {
+        Some(d) if d > 0.0 => d as f64,
+        _ => {
+            warnings::warn(
+                warnings::DEPTH_BASELINE,
+                format!(
+                    "no genome-wide {side} depth; normalizing by the median of the candidate \
+                     loci ({loci:.1}×), which their own depth biases"
+                ),
+            );
+            loci
+        }
+    };
+    let baselines = (
+        pick("nuclear", genome.map(|g| g.0), loci.0),
+        pick("mito", genome.map(|g| g.1), loci.1),
+    );
+    log::info!(
+        "depth baselines: nuclear {:.1}×, mito {:.1}× (median of the candidate loci: {:.1}×, {:.1}×)",
+        baselines.0,
+        baselines.1,
+        loci.0,
+        loci.1
+    );
+    (baselines, loci)
+}
+
+/// Compute local median depth in a region using `samtools depth`; a long
+/// read split into primary and supplementary alignments counts once unless
+/// `supplementary`. `reference` is a CRAM's FASTA.
+pub(crate) fn local_median_depth(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    rname: &str,
+    w: Window,
+    supplementary: bool,
+) -> Result<f32> {
     let region = region_str(rname, w);
-    let out = Command::new(samtools)
-        .args(["depth", "-r"])
-        .arg(&region)
+    let mut cmd = samtools_cmd(samtools, "depth", reference);
+    cmd.arg("-r").arg(&region);
+    if !supplementary {
+        cmd.args(["-G", DEPTH_EXCL_FLAGS]);
+    }
+    let out = cmd
         .arg(bam)
         .output()
         .with_context(|| format!("spawn samtools depth for {region}"))?;
@@ -82,141 +258,1422 @@ fn local_median_depth(samtools: &Path, bam: &Path, rname: &str, w: Window) -> Re
     Ok(median_f32(depths))
 }
 
-/// Fraction of alignments that span the entire [w.start, w.end) window on rname.
-/// Uses `samtools view` (SAM text), MAPQ ≥ 20.
-fn span_fraction(samtools: &Path, bam: &Path, rname: &str, w: Window) -> Result<f32> {
-    const MIN_MAPQ: u8 = 20;
+/// A depth window of one pair, for `batch_median_depths`.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthWindow<'a> {
+    pub pair_id: &'a str,
+    pub rname: &'a str,
+    pub w: Window,
+}
+
+/// `local_median_depth` of many windows from one `samtools depth -b` run: the
+/// windows go to a temporary BED, and the positions samtools reports are
+/// handed back to every window that covers them, so overlapping windows each
+/// get theirs. Windows on contigs the BAM lacks, or without coverage, get 0.
+pub(crate) fn batch_median_depths(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    windows: &[DepthWindow],
+    supplementary: bool,
+) -> Result<HashMap<String, f32>> {
+    let depths = batch_window_depths(samtools, bam, reference, windows, supplementary)?;
+    Ok(windows
+        .iter()
+        .zip(depths)
+        .map(|(dw, d)| (dw.pair_id.to_string(), median_f32(d)))
+        .collect())
+}
+
+/// The depth of every covered position of each window, from one
+/// `samtools depth -b` run, as `batch_median_depths` takes their medians of.
+fn batch_window_depths(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    windows: &[DepthWindow],
+    supplementary: bool,
+) -> Result<Vec<Vec<f32>>> {
+    let mut bed = tempfile::Builder::new()
+        .prefix("onsm-depth-")
+        .suffix(".bed")
+        .tempfile()?;
+    // per contig: (start, end, window index), by start
+    let mut by_contig: HashMap<&str, Vec<(u32, u32, usize)>> = HashMap::new();
+    {
+        let mut out = BufWriter::new(bed.as_file_mut());
+        for (i, dw) in windows.iter().enumerate() {
+            let (beg, end) = region_bounds(dw.w);
+            writeln!(out, "{}\t{beg}\t{end}", dw.rname)?;
+            by_contig.entry(dw.rname).or_default().push((beg, end, i));
+        }
+        out.flush()?;
+    }
+    by_contig.values_mut().for_each(|v| v.sort_unstable());
+
+    let mut cmd = samtools_cmd(samtools, "depth", reference);
+    cmd.arg("-b").arg(bed.path());
+    if !supplementary {
+        cmd.args(["-G", DEPTH_EXCL_FLAGS]);
+    }
+    // a warning per window on a missing contig could fill a stderr pipe
+    let mut stderr = tempfile::tempfile()?;
+    let mut child = cmd
+        .arg(bam)
+        .stdout(Stdio::piped())
+        .stderr(stderr.try_clone()?)
+        .spawn()
+        .with_context(|| format!("spawn samtools depth for {} windows", windows.len()))?;
+    let stdout = BufReader::with_capacity(1 << 16, child.stdout.take().expect("stdout is piped"));
+
+    // positions come in order along each contig: sweep its windows, keeping
+    // those that have started and not ended
+    let mut depths = vec![Vec::new(); windows.len()];
+    let mut contig = String::new();
+    let mut sorted: &[(u32, u32, usize)] = &[];
+    let (mut next, mut last) = (0, None);
+    let mut open: Vec<(u32, usize)> = Vec::new();
+    for line in stdout.lines() {
+        let line = line.context("read samtools depth output")?;
+        // depth output: chrom  pos  depth
+        let mut it = line.split('\t');
+        let (Some(chrom), Some(Ok(pos)), Some(Ok(d))) = (
+            it.next(),
+            it.next().map(str::parse::<u32>),
+            it.next().map(str::parse::<u32>),
+        ) else {
+            continue;
+        };
+        if chrom != contig {
+            contig = chrom.to_string();
+            sorted = by_contig.get(chrom).map_or(&[], |v| v.as_slice());
+            (next, last) = (0, None);
+            open.clear();
+        }
+        match last {
+            // each position once, however many BED lines cover it
+            Some(l) if pos == l => continue,
+            // out of order (never from samtools): start the sweep over
+            Some(l) if pos < l => {
+                next = 0;
+                open.clear();
+            }
+            _ => {}
+        }
+        last = Some(pos);
+        let x = pos.saturating_sub(1);
+        while let Some(&(_, end, i)) = sorted.get(next).filter(|w| w.0 <= x) {
+            open.push((end, i));
+            next += 1;
+        }
+        open.retain(|&(end, _)| end > x);
+        for &(_, i) in &open {
+            depths[i].push(d as f32);
+        }
+    }
+    if !child.wait()?.success() {
+        let mut err = String::new();
+        stderr.seek(SeekFrom::Start(0))?;
+        stderr.read_to_string(&mut err)?;
+        return Err(anyhow::anyhow!("samtools depth failed: {}", err.trim()));
+    }
+    Ok(depths)
+}
+
+/// `local_median_depth` read from the indexed BAM: the aligned (M/=/X) bases
+/// of the alignments samtools depth keeps, counted per position of the
+/// region; positions no alignment covers are left out, as samtools does.
+fn native_median_depth(
+    bams: &IndexedBams,
+    bam: &Path,
+    rname: &str,
+    w: Window,
+    supplementary: bool,
+) -> Result<f32> {
+    Ok(median_f32(native_depths(
+        bams,
+        bam,
+        rname,
+        w,
+        supplementary,
+    )?))
+}
+
+/// The depth of every covered position of the window, as
+/// `native_median_depth` takes the median of.
+fn native_depths(
+    bams: &IndexedBams,
+    bam: &Path,
+    rname: &str,
+    w: Window,
+    supplementary: bool,
+) -> Result<Vec<f32>> {
+    let (beg, end) = region_bounds(w);
+    let excl = if supplementary {
+        DEPTH_DEFAULT_EXCL
+    } else {
+        DEPTH_EXCL
+    };
+    let mut depth = vec![0u32; (end - beg.min(end)) as usize];
+    bams.fetch(bam, rname, beg, end, |line| {
+        let Some(f) = parse_sam_fields(line) else {
+            return;
+        };
+        let Ok(c) = Cigar::parse(f.cigar) else {
+            return;
+        };
+        if f.flag & excl != 0 || f.rname != rname.as_bytes() {
+            return;
+        }
+        let at = i64::from(f.pos) - 1;
+        for b in c.iter_ref_blocks() {
+            let from = (at + b.ref_offset as i64).max(i64::from(beg));
+            let to = (at + (b.ref_offset + u64::from(b.len)) as i64).min(i64::from(end));
+            for x in from..to {
+                depth[(x - i64::from(beg)) as usize] += 1;
+            }
+        }
+    })?;
+    Ok(depth
+        .into_iter()
+        .filter(|&d| d > 0)
+        .map(|d| d as f32)
+        .collect())
+}
+
+/// Bases of a BAM the genome-wide depth is sampled over: references adding up
+/// to no more (a mito) are read whole, a longer genome in evenly spaced
+/// windows of `GENOME_SAMPLE_WINDOW`.
+pub const GENOME_SAMPLE_BP: u64 = 2_000_000;
+const GENOME_SAMPLE_WINDOW: u32 = 10_000;
+
+/// The windows of `refs` (name, length) the genome-wide depth is sampled
+/// from (see `GENOME_SAMPLE_BP`), in reference order.
+fn genome_sample(refs: &[(String, u32)]) -> Vec<(&str, Window)> {
+    let total: u64 = refs.iter().map(|(_, len)| u64::from(*len)).sum();
+    fn window(rname: &str, beg: u64, end: u64) -> Option<(&str, Window)> {
+        let (start, end) = (i32::try_from(beg).ok()?, i32::try_from(end).ok()?);
+        (end > start).then_some((rname, Window { start, end }))
+    }
+    if total <= GENOME_SAMPLE_BP {
+        return refs
+            .iter()
+            .filter_map(|(rname, len)| window(rname, 0, u64::from(*len)))
+            .collect();
+    }
+    let win = u64::from(GENOME_SAMPLE_WINDOW);
+    let stride = total / (GENOME_SAMPLE_BP / win);
+    // each window in the middle of its stride of the concatenated references
+    let (mut offset, mut next) = (0, (stride - win) / 2);
+    let mut out = Vec::new();
+    for (rname, len) in refs {
+        let len = u64::from(*len);
+        while next < offset + len {
+            let beg = next - offset;
+            out.extend(window(rname, beg, (beg + win).min(len)));
+            next += stride;
+        }
+        offset += len;
+    }
+    out
+}
+
+/// The references of `bam` and their lengths, from `samtools idxstats`, or
+/// for a CRAM from the `@SQ` lines of its header: a `.crai` holds no counts,
+/// so idxstats would decode every record.
+fn samtools_references(samtools: &Path, bam: &Path) -> Result<Vec<(String, u32)>> {
+    let cram = bam_index::is_cram(bam);
+    let mut cmd = Command::new(samtools);
+    match cram {
+        true => cmd.args(["view", "-H"]),
+        false => cmd.arg("idxstats"),
+    };
+    let out = cmd
+        .arg(bam)
+        .output()
+        .with_context(|| format!("spawn samtools for the references of {}", bam.display()))?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(anyhow::anyhow!(
+            "samtools {} failed: {}",
+            if cram { "view -H" } else { "idxstats" },
+            err.trim()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    if cram {
+        return Ok(stdout
+            .lines()
+            .filter(|line| line.starts_with("@SQ\t"))
+            .filter_map(|line| {
+                let tag = |t: &str| line.split('\t').find_map(|f| f.strip_prefix(t));
+                Some((tag("SN:")?.to_string(), tag("LN:")?.parse().ok()?))
+            })
+            .collect());
+    }
+    // idxstats output: name  length  mapped  unmapped, then `*` for the unplaced
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let mut it = line.split('\t');
+            let (name, len) = (it.next()?, it.next()?.parse().ok()?);
+            (name != "*").then(|| (name.to_string(), len))
+        })
+        .collect())
+}
+
+/// Run `samtools view BAM REGION` and hand its SAM output to `read`;
+/// `reference` is a CRAM's FASTA.
+fn samtools_view_with<T>(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    rname: &str,
+    w: Window,
+    read: impl FnOnce(BufReader<ChildStdout>) -> std::io::Result<T>,
+) -> Result<T> {
     let region = region_str(rname, w);
-    let out = Command::new(samtools)
-        .args(["view"])
+    let mut child = samtools_cmd(samtools, "view", reference)
         .arg(bam)
         .arg(&region)
-        .output()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
         .with_context(|| format!("spawn samtools view for {region}"))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let res = read(BufReader::with_capacity(1 << 16, stdout))
+        .with_context(|| format!("read samtools view output for {region}"))?;
+    let out = child.wait_with_output()?;
     if !out.status.success() {
         let err = String::from_utf8_lossy(&out.stderr);
         return Err(anyhow::anyhow!("samtools view failed: {}", err.trim()));
     }
-    let s1 = w.start.max(0) + 1; // window start 1-based
-    let e1 = w.end.max(w.start + 1); // window end 1-based inclusive-ish
+    Ok(res)
+}
 
-    let mut total = 0f32;
-    let mut spans = 0f32;
+/// Stream the records of `samtools view BAM REGION` that count as span
+/// evidence (see `sam_records`) into `each`.
+fn samtools_view(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    rname: &str,
+    w: Window,
+    each: impl FnMut(WindowRecord),
+) -> Result<()> {
+    samtools_view_with(samtools, bam, reference, rname, w, |sam| {
+        sam_records(sam, rname, each)
+    })
+}
 
-    for line in String::from_utf8_lossy(&out.stdout).lines() {
-        if line.is_empty() || line.starts_with('@') {
-            continue;
+/// Stream the records of the indexed BAM's region that count as span
+/// evidence into `each`: `samtools_view` without samtools.
+fn native_view(
+    bams: &IndexedBams,
+    bam: &Path,
+    rname: &str,
+    w: Window,
+    mut each: impl FnMut(WindowRecord),
+) -> Result<()> {
+    let (beg, end) = region_bounds(w);
+    bams.fetch(bam, rname, beg, end, |line| {
+        if let Some(r) = window_record(line, rname) {
+            each(r)
+        }
+    })
+}
+
+/// How evidence windows are fetched: with samtools, or from the indexed BAMs
+/// with `native`, through the window cache when there is one (`--no-cache`
+/// turns it off).
+#[derive(Debug, Clone)]
+pub struct WindowFetch<'a> {
+    pub samtools: &'a Path,
+    pub cache: Option<&'a WindowCache>,
+    /// `--bam-backend native`: the BAMs read in process.
+    pub native: Option<&'a IndexedBams>,
+    /// `--depth-include-supplementary`; span windows always keep them.
+    pub depth_supplementary: bool,
+    /// `--span-mapq`: lowest MAPQ of the reads counted for spans.
+    pub min_span_mapq: u8,
+    /// `--junction-min-clip` (0: junction reads not counted).
+    pub junction_min_clip: u32,
+    /// Depths fetched for many pairs at once (`batch_depths`).
+    pub batched: Option<&'a BatchDepths>,
+    /// (CRAM, the FASTA its reads were compressed against) for samtools to
+    /// decode them with; BAMs need none.
+    pub cram_references: &'a [(PathBuf, PathBuf)],
+    /// Lengths of the contigs the reads were mapped to, to cut windows at
+    /// their ends (`pair_windows`); without, only the start is clamped.
+    pub contig_lengths: Option<&'a ContigLengths>,
+    /// samtools runs so far (windows found in the cache need none).
+    calls: Cell<u64>,
+}
+
+impl<'a> WindowFetch<'a> {
+    /// Depth without supplementary alignments, the default.
+    pub fn new(samtools: &'a Path, cache: Option<&'a WindowCache>) -> Self {
+        Self {
+            samtools,
+            cache,
+            native: None,
+            depth_supplementary: false,
+            min_span_mapq: model::MIN_MAPQ,
+            junction_min_clip: 0,
+            batched: None,
+            cram_references: &[],
+            contig_lengths: None,
+            calls: Cell::new(0),
+        }
+    }
+
+    /// Read the windows from the indexed BAMs instead of with samtools.
+    pub fn with_native(mut self, bams: Option<&'a IndexedBams>) -> Self {
+        self.native = bams;
+        self
+    }
+
+    pub fn with_depth_supplementary(mut self, yes: bool) -> Self {
+        self.depth_supplementary = yes;
+        self
+    }
+
+    pub fn with_min_span_mapq(mut self, q: u8) -> Self {
+        self.min_span_mapq = q;
+        self
+    }
+
+    pub fn with_junction_min_clip(mut self, bp: u32) -> Self {
+        self.junction_min_clip = bp;
+        self
+    }
+
+    /// Take the depths of the pairs in `batched` from it.
+    pub fn with_batched(mut self, batched: Option<&'a BatchDepths>) -> Self {
+        self.batched = batched;
+        self
+    }
+
+    pub fn with_cram_references(mut self, refs: &'a [(PathBuf, PathBuf)]) -> Self {
+        self.cram_references = refs;
+        self
+    }
+
+    pub fn with_contig_lengths(mut self, lengths: Option<&'a ContigLengths>) -> Self {
+        self.contig_lengths = lengths;
+        self
+    }
+
+    /// The FASTA to decode `bam` with, when it is a CRAM given one; without,
+    /// samtools looks for the reference its header names.
+    fn reference(&self, bam: &Path) -> Option<&'a Path> {
+        if !bam_index::is_cram(bam) {
+            return None;
         }
-        let mut cols = line.split('\t');
-        let _qname = cols.next();
-        let flag = cols.next().and_then(|s| s.parse::<u16>().ok()).unwrap_or(0);
-        let rname_sam = cols.next().unwrap_or("*");
-        let pos = cols.next().and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
-        let mapq = cols.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-        let cigar = cols.next().unwrap_or("*");
+        self.cram_references
+            .iter()
+            .find(|(cram, _)| cram == bam)
+            .map(|(_, fa)| fa.as_path())
+    }
+
+    /// How many times samtools has been run through this fetch (never with
+    /// `native`).
+    pub fn subprocess_calls(&self) -> u64 {
+        self.calls.get()
+    }
+
+    fn count_call(&self) {
+        self.calls.set(self.calls.get() + 1);
+    }
+
+    fn key(
+        cache: &WindowCache,
+        kind: &'static str,
+        bam: &Path,
+        rname: &str,
+        w: Window,
+        filters: String,
+    ) -> Result<WindowKey> {
+        Ok(WindowKey {
+            kind,
+            bam_stamp: cache.stamp(bam)?,
+            contig: rname.to_string(),
+            start: w.start,
+            end: w.end,
+            filters,
+        })
+    }
+
+    fn depth_key(
+        &self,
+        cache: &WindowCache,
+        bam: &Path,
+        rname: &str,
+        w: Window,
+    ) -> Result<WindowKey> {
+        // windows cached before the option were fetched with them
+        let filters = if self.depth_supplementary {
+            String::new()
+        } else {
+            format!("excl={DEPTH_EXCL_FLAGS}")
+        };
+        Self::key(cache, "depth", bam, rname, w, filters)
+    }
+
+    /// Local median depth in the window (`samtools depth`), or `batched`, the
+    /// one `batch_depths` fetched for it.
+    fn median_depth(
+        &self,
+        bam: &Path,
+        rname: &str,
+        w: Window,
+        batched: Option<f32>,
+    ) -> Result<f32> {
+        let fetch = || match (batched, self.native) {
+            (Some(d), _) => Ok(d),
+            (None, Some(bams)) => {
+                native_median_depth(bams, bam, rname, w, self.depth_supplementary)
+            }
+            (None, None) => {
+                self.count_call();
+                local_median_depth(
+                    self.samtools,
+                    bam,
+                    self.reference(bam),
+                    rname,
+                    w,
+                    self.depth_supplementary,
+                )
+            }
+        };
+        match self.cache {
+            None => fetch(),
+            Some(c) => c.get_or_fetch(&self.depth_key(c, bam, rname, w)?, fetch),
+        }
+    }
+
+    /// The depths of the pairs' windows (`pair_windows`) from one
+    /// `samtools depth -b` per BAM rather than one run per window, for
+    /// `with_batched`. Windows already in the cache are left out; with
+    /// `native` nothing is fetched, as windows cost no process there.
+    pub fn batch_depths(
+        &self,
+        bam_reads_to_nuc: &Path,
+        bam_reads_to_mito: &Path,
+        pairs: &[PairedLocus],
+        flank: u32,
+    ) -> Result<BatchDepths> {
+        if self.native.is_some() {
+            return Ok(BatchDepths::default());
+        }
+        let (mut nuc, mut mito) = (Vec::new(), Vec::new());
+        for p in pairs {
+            // `pair_evidence` flags these
+            let Ok((n_w, m_w)) = pair_windows(p, flank, self.contig_lengths) else {
+                continue;
+            };
+            for (bam, rname, w, to) in [
+                (bam_reads_to_nuc, &p.nuc_contig, n_w, &mut nuc),
+                (bam_reads_to_mito, &p.mito_contig, m_w, &mut mito),
+            ] {
+                if let Some(c) = self.cache {
+                    if c.contains::<f32>(&self.depth_key(c, bam, rname, w)?) {
+                        continue;
+                    }
+                }
+                to.push(DepthWindow {
+                    pair_id: &p.pair_id,
+                    rname,
+                    w,
+                });
+            }
+        }
+        let batch = |bam: &Path, windows: &[DepthWindow]| {
+            if windows.is_empty() {
+                return Ok(HashMap::new());
+            }
+            self.count_call();
+            batch_median_depths(
+                self.samtools,
+                bam,
+                self.reference(bam),
+                windows,
+                self.depth_supplementary,
+            )
+        };
+        Ok(BatchDepths {
+            nuc: batch(bam_reads_to_nuc, &nuc)?,
+            mito: batch(bam_reads_to_mito, &mito)?,
+        })
+    }
+
+    /// Median depth of `bam` over `genome_sample` of its references: a
+    /// baseline of the genome as a whole, which the candidate loci, unusual
+    /// regions by construction, are not a fair sample of. Positions without
+    /// coverage are left out, as in the pairs' windows; 0 when none is
+    /// covered. One `samtools idxstats` and one `samtools depth -b` run, or
+    /// none once cached.
+    pub fn genome_median_depth(&self, bam: &Path) -> Result<f32> {
+        let fetch = || {
+            let refs = match self.native {
+                Some(bams) => bams.references(bam)?,
+                None => {
+                    self.count_call();
+                    samtools_references(self.samtools, bam)?
+                }
+            };
+            let sample = genome_sample(&refs);
+            let depths = match self.native {
+                Some(bams) => {
+                    let mut depths = Vec::new();
+                    for &(rname, w) in &sample {
+                        depths.extend(native_depths(
+                            bams,
+                            bam,
+                            rname,
+                            w,
+                            self.depth_supplementary,
+                        )?);
+                    }
+                    depths
+                }
+                None if sample.is_empty() => Vec::new(),
+                None => {
+                    let windows: Vec<DepthWindow> = sample
+                        .iter()
+                        .map(|&(rname, w)| DepthWindow {
+                            pair_id: "",
+                            rname,
+                            w,
+                        })
+                        .collect();
+                    self.count_call();
+                    batch_window_depths(
+                        self.samtools,
+                        bam,
+                        self.reference(bam),
+                        &windows,
+                        self.depth_supplementary,
+                    )?
+                    .concat()
+                }
+            };
+            Ok(median_f32(depths))
+        };
+        match self.cache {
+            None => fetch(),
+            Some(c) => {
+                let excl = match self.depth_supplementary {
+                    true => String::new(),
+                    false => format!("excl={DEPTH_EXCL_FLAGS},"),
+                };
+                let filters = format!("{excl}sample={GENOME_SAMPLE_BP}/{GENOME_SAMPLE_WINDOW}");
+                let whole = Window { start: 0, end: 0 };
+                let key = Self::key(c, "genome_depth", bam, "", whole, filters)?;
+                c.get_or_fetch(&key, fetch)
+            }
+        }
+    }
+
+    /// `genome_median_depth` of the nuclear and the mito BAM.
+    pub fn genome_median_depths(
+        &self,
+        bam_reads_to_nuc: &Path,
+        bam_reads_to_mito: &Path,
+    ) -> Result<(f32, f32)> {
+        Ok((
+            self.genome_median_depth(bam_reads_to_nuc)?,
+            self.genome_median_depth(bam_reads_to_mito)?,
+        ))
+    }
+
+    /// Fraction of alignments that span the entire [w.start, w.end) window on
+    /// rname, and the median reference-consumed length of those alignments.
+    /// Uncached, the `samtools view` output is streamed, so memory is one line
+    /// buffer plus at most `max_reads` sampled alignments, however deep the
+    /// window; the cache stores every evidence record of the window (about 20
+    /// bytes each), and sampling them gives the same result.
+    fn span_fraction(
+        &self,
+        bam: &Path,
+        rname: &str,
+        w: Window,
+        max_reads: u32,
+    ) -> Result<SpanStats> {
+        let mut acc = SpanAccumulator::new(rname, w, max_reads, self.min_span_mapq);
+        self.window_records(bam, rname, w, &mut |r| acc.offer(r))?;
+        let stats = acc.finish();
+        if stats.reads.subsampled() {
+            log::debug!(
+                "{}: {} MAPQ≥{} reads, span fraction from {} sampled",
+                region_str(rname, w),
+                stats.reads.total,
+                self.min_span_mapq,
+                stats.reads.sampled
+            );
+        }
+        Ok(stats)
+    }
+
+    /// Junction reads around `at` on rname: alignments passing `--span-mapq`
+    /// whose first aligned base follows a clip, or whose last one precedes a
+    /// clip, of at least `--junction-min-clip` bases within
+    /// ±`JUNCTION_WINDOW_BP` of it. Secondary alignments are left out.
+    fn junction_clips(&self, bam: &Path, rname: &str, at: u32) -> Result<u32> {
+        let mut c = JunctionCounter::new(at, self.junction_min_clip, self.min_span_mapq)?;
+        self.window_records(bam, rname, c.w, &mut |r| c.offer(r))?;
+        Ok(c.n)
+    }
+
+    /// The evidence records of window `w` on rname, in the order samtools
+    /// view lists them, through the cache when there is one.
+    fn window_records(
+        &self,
+        bam: &Path,
+        rname: &str,
+        w: Window,
+        each: &mut dyn FnMut(WindowRecord),
+    ) -> Result<()> {
+        let view = |each: &mut dyn FnMut(WindowRecord)| match self.native {
+            Some(bams) => native_view(bams, bam, rname, w, each),
+            None => {
+                self.count_call();
+                samtools_view(self.samtools, bam, self.reference(bam), rname, w, each)
+            }
+        };
+        match self.cache {
+            None => view(each)?,
+            Some(c) => {
+                // every MAPQ is stored, so one entry serves any --span-mapq
+                let key = Self::key(c, "view", bam, rname, w, "mapped".to_string())?;
+                let records: Vec<WindowRecord> = c.get_or_fetch(&key, || {
+                    let mut v = Vec::new();
+                    view(&mut |r| v.push(r))?;
+                    Ok(v)
+                })?;
+                records.into_iter().for_each(each);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Local median depths by pair ID, from `WindowFetch::batch_depths`.
+#[derive(Debug, Clone, Default)]
+pub struct BatchDepths {
+    pub nuc: HashMap<String, f32>,
+    pub mito: HashMap<String, f32>,
+}
+
+/// SplitMix64: small, fast and good enough to pick reservoir slots.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Seeded from the region, so a window samples the same reads on every
+    /// run whatever order the pairs are processed in.
+    fn for_region(rname: &str, w: Window) -> Self {
+        // FNV-1a
+        let mut h = 0xcbf2_9ce4_8422_2325u64;
+        for b in region_str(rname, w).bytes() {
+            h = (h ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+        Self(h)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n` (multiply-shift; the bias is below n / 2^64).
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// A uniform random sample of at most `cap` items of a stream (Algorithm R):
+/// the first `cap` are kept, and the n-th after them replaces a random slot
+/// with probability cap / n.
+struct Reservoir<T> {
+    cap: usize,
+    seen: u64,
+    items: Vec<T>,
+    rng: SplitMix64,
+}
+
+impl<T> Reservoir<T> {
+    fn new(cap: usize, rng: SplitMix64) -> Self {
+        Self {
+            cap,
+            seen: 0,
+            items: Vec::new(),
+            rng,
+        }
+    }
+
+    fn offer(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.cap {
+            self.items.push(item);
+        } else {
+            let j = self.rng.below(self.seen);
+            if let Some(slot) = self.items.get_mut(j as usize) {
+                *slot = item;
+            }
+        }
+    }
+}
+
+/// What `span_stats` measured in one window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpanStats {
+    fraction: f32,
+    read_len: f32,
+    /// Median read divergence (NM / alignment columns) over the reads with
+    /// an NM tag; `None` when none had one.
+    read_div: Option<f32>,
+    reads: SpanReads,
+}
+
+/// The SAM columns `span_stats` needs, borrowed from the line.
+#[derive(Debug, PartialEq, Eq)]
+struct SamFields<'a> {
+    flag: u16,
+    rname: &'a [u8],
+    pos: i32,
+    mapq: u8,
+    cigar: &'a [u8],
+    /// The NM tag, if the line has one.
+    nm: Option<u32>,
+}
+
+fn parse_ascii<T: std::str::FromStr>(b: &[u8]) -> Option<T> {
+    std::str::from_utf8(b).ok()?.parse().ok()
+}
+
+/// FLAG, RNAME, POS, MAPQ and CIGAR (columns 2–6) of one SAM line, and the
+/// NM tag from the optional fields; SEQ and QUAL are skipped over, never
+/// parsed. `None` for headers and truncated or non-numeric lines.
+fn parse_sam_fields(line: &[u8]) -> Option<SamFields<'_>> {
+    if line.first() == Some(&b'@') {
+        return None;
+    }
+    let mut cols = line.splitn(7, |&b| b == b'\t');
+    let _qname = cols.next()?;
+    let flag = parse_ascii(cols.next()?)?;
+    let rname = cols.next()?;
+    let pos = parse_ascii(cols.next()?)?;
+    let mapq = parse_ascii(cols.next()?)?;
+    let cigar = cols.next()?;
+    // CIGAR must be followed by RNEXT…; a line cut inside it is truncated
+    let rest = cols.next()?;
+    Some(SamFields {
+        flag,
+        rname,
+        pos,
+        mapq,
+        cigar,
+        nm: nm_tag(rest),
+    })
+}
+
+/// The NM tag among the optional fields, which follow RNEXT, PNEXT, TLEN,
+/// SEQ and QUAL in `rest` (the line from RNEXT on) in any order.
+fn nm_tag(rest: &[u8]) -> Option<u32> {
+    rest.split(|&b| b == b'\t')
+        .skip(5)
+        .find_map(|t| t.strip_prefix(b"NM:i:"))
+        .and_then(parse_ascii)
+}
+
+/// Reference bases a CIGAR covers, and its alignment columns (see
+/// `aligned_columns`); `None` past `u32`.
+fn cigar_lens(c: &Cigar) -> Option<(u32, u32)> {
+    Some((
+        u32::try_from(c.ref_consumed()).ok()?,
+        u32::try_from(aligned_columns(c)).ok()?,
+    ))
+}
+
+/// Alignment columns of a CIGAR: M/=/X, I and D bases, what NM counts
+/// edits over.
+fn aligned_columns(c: &Cigar) -> u64 {
+    c.ops()
+        .iter()
+        .filter(|(op, _)| matches!(op, Op::Match | Op::Equal | Op::Diff | Op::Ins | Op::Del))
+        .map(|&(_, n)| u64::from(n))
+        .sum()
+}
+
+/// One alignment of a window that counts as span evidence, reduced to what
+/// the evidence needs; what the window cache stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WindowRecord {
+    flag: u16,
+    /// 1-based, as in SAM.
+    pos: i32,
+    mapq: u8,
+    /// Reference bases consumed by the CIGAR.
+    ref_len: u32,
+    /// NM tag, and the alignment columns it counts edits over.
+    nm: Option<u32>,
+    aligned: u32,
+    /// Leading and trailing clips, soft and hard.
+    clips: (u32, u32),
+}
+
+impl WindowRecord {
+    /// NM / alignment columns; `None` without an NM tag.
+    fn divergence(&self) -> Option<f32> {
+        self.nm.map(|nm| nm as f32 / self.aligned.max(1) as f32)
+    }
+}
+
+/// Span fraction and median length over a window's records with MAPQ ≥
+/// `min_mapq`, in stream order; the others are only counted. Past
+/// `max_reads` records (0: no cap), both are estimated from a uniform sample
+/// of `max_reads` of them. Supplementary alignments are records like
+/// any other here: the piece of a long read on either side of a junction is
+/// what the window is looking for.
+struct SpanAccumulator {
+    s1: i32,
+    e1: i32,
+    min_mapq: u8,
+    low_mapq: u32,
+    /// Primary and supplementary records offered, sampled or not.
+    primary: u32,
+    supplementary: u32,
+    // (ref-consumed length, spans the window, divergence)
+    sample: Reservoir<(u32, bool, Option<f32>)>,
+}
+
+impl SpanAccumulator {
+    fn new(rname: &str, w: Window, max_reads: u32, min_mapq: u8) -> Self {
+        let cap = if max_reads == 0 {
+            usize::MAX
+        } else {
+            max_reads as usize
+        };
+        Self {
+            s1: w.start.max(0) + 1,     // window start 1-based
+            e1: w.end.max(w.start + 1), // window end 1-based inclusive-ish
+            min_mapq,
+            low_mapq: 0,
+            primary: 0,
+            supplementary: 0,
+            sample: Reservoir::new(cap, SplitMix64::for_region(rname, w)),
+        }
+    }
+
+    fn offer(&mut self, r: WindowRecord) {
+        if r.mapq < self.min_mapq {
+            self.low_mapq = self.low_mapq.saturating_add(1);
+            return;
+        }
+        if r.flag & FLAG_SUPPLEMENTARY != 0 {
+            self.supplementary = self.supplementary.saturating_add(1);
+        } else if r.flag & FLAG_SECONDARY == 0 {
+            self.primary = self.primary.saturating_add(1);
+        }
+        let rec_end = r.pos.saturating_add(r.ref_len as i32 - 1); // inclusive on reference
+        self.sample.offer((
+            r.ref_len,
+            r.pos <= self.s1 && rec_end >= self.e1,
+            r.divergence(),
+        ));
+    }
+
+    fn finish(self) -> SpanStats {
+        let sample = self.sample;
+        let n = sample.items.len();
+        let spans = sample.items.iter().filter(|(_, spans, _)| *spans).count();
+        let divs: Vec<f32> = sample.items.iter().filter_map(|(_, _, d)| *d).collect();
+        let without_nm = (n - divs.len()) as u32;
+        SpanStats {
+            fraction: if n == 0 { 0.0 } else { spans as f32 / n as f32 },
+            read_len: median_f32(sample.items.iter().map(|(l, _, _)| *l as f32).collect()),
+            read_div: (!divs.is_empty()).then(|| median_f32(divs)),
+            reads: SpanReads {
+                total: sample.seen.min(u32::MAX as u64) as u32,
+                sampled: n as u32,
+                without_nm,
+                primary: self.primary,
+                supplementary: self.supplementary,
+                low_mapq: self.low_mapq,
+            },
+        }
+    }
+}
+
+/// Junction reads around one position, counted over its window's records
+/// (see `WindowFetch::junction_clips`).
+struct JunctionCounter {
+    w: Window,
+    min_clip: u32,
+    min_mapq: u8,
+    n: u32,
+}
+
+impl JunctionCounter {
+    fn new(at: u32, min_clip: u32, min_mapq: u8) -> std::result::Result<Self, WindowError> {
+        Ok(Self {
+            w: Window::around(u64::from(at), model::JUNCTION_WINDOW_BP)?,
+            min_clip,
+            min_mapq,
+            n: 0,
+        })
+    }
+
+    fn offer(&mut self, r: WindowRecord) {
+        if r.flag & FLAG_SECONDARY != 0 || r.mapq < self.min_mapq {
+            return;
+        }
+        let in_window = |x: i64| x >= i64::from(self.w.start) && x < i64::from(self.w.end);
+        // 0-based first and last aligned bases
+        let first = i64::from(r.pos) - 1;
+        let last = first + i64::from(r.ref_len) - 1;
+        let (lead, trail) = r.clips;
+        if (lead >= self.min_clip && in_window(first))
+            || (trail >= self.min_clip && in_window(last))
+        {
+            self.n = self.n.saturating_add(1);
+        }
+    }
+}
+
+/// The mapped alignments on `rname` with a reference length, in the order of
+/// the SAM text.
+fn sam_records(
+    mut sam: impl BufRead,
+    rname: &str,
+    mut each: impl FnMut(WindowRecord),
+) -> std::io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if sam.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if let Some(r) = window_record(line.strip_suffix(b"\n").unwrap_or(&line), rname) {
+            each(r);
+        }
+    }
+    Ok(())
+}
+
+/// One SAM line as span evidence, if it is a mapped alignment on `rname`
+/// with a reference length; MAPQ is left to `SpanAccumulator`.
+fn window_record(line: &[u8], rname: &str) -> Option<WindowRecord> {
+    let f = parse_sam_fields(line)?;
+
+    // filter
+    if (f.flag & 0x4) != 0 {
+        return None; // unmapped
+    }
+    if f.rname != rname.as_bytes() {
+        return None;
+    }
+
+    let c = Cigar::parse(f.cigar).ok()?;
+    let (ref_len, aligned) = match cigar_lens(&c) {
+        Some((x, aligned)) if x > 0 => (x, aligned),
+        _ => return None,
+    };
+    let clip = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+    Some(WindowRecord {
+        flag: f.flag,
+        pos: f.pos,
+        mapq: f.mapq,
+        ref_len,
+        nm: f.nm,
+        aligned,
+        clips: (clip(c.leading_clip()), clip(c.trailing_clip())),
+    })
+}
+
+/// Reference positions (0-based) in `w` where MAPQ ≥ 20 alignments on
+/// `rname` are clipped, soft or hard, by at least `min_clip` bases: the first
+/// aligned base for a leading clip, one past the last for a trailing one.
+/// Secondary alignments are left out; supplementary ones are the clipped
+/// pieces a junction leaves, so they count. `reference` is a CRAM's FASTA.
+pub fn clip_positions(
+    samtools: &Path,
+    bam: &Path,
+    reference: Option<&Path>,
+    rname: &str,
+    w: Window,
+    min_clip: u32,
+) -> Result<Vec<u32>> {
+    samtools_view_with(samtools, bam, reference, rname, w, |sam| {
+        sam_clip_positions(sam, rname, w, min_clip)
+    })
+}
 
-        // filter
-        if (flag & 0x4) != 0 {
-            continue; // unmapped
+/// `clip_positions` over SAM text.
+fn sam_clip_positions(
+    mut sam: impl BufRead,
+    rname: &str,
+    w: Window,
+    min_clip: u32,
+) -> std::io::Result<Vec<u32>> {
+    let mut out = Vec::new();
+    let mut line = Vec::new();
+    let in_window = |x: i64| x >= i64::from(w.start) && x < i64::from(w.end);
+    loop {
+        line.clear();
+        if sam.read_until(b'\n', &mut line)? == 0 {
+            break;
         }
-        if mapq < MIN_MAPQ {
+        let Some(f) = parse_sam_fields(line.strip_suffix(b"\n").unwrap_or(&line)) else {
+            continue;
+        };
+        if f.flag & (0x4 | FLAG_SECONDARY) != 0
+            || f.mapq < model::MIN_MAPQ
+            || f.rname != rname.as_bytes()
+        {
             continue;
         }
-        if rname_sam != rname {
+        let Ok(c) = Cigar::parse(f.cigar) else {
             continue;
+        };
+        let start = i64::from(f.pos) - 1;
+        let end = start + c.ref_consumed() as i64;
+        for (clip, at) in [(c.leading_clip(), start), (c.trailing_clip(), end)] {
+            if clip >= u64::from(min_clip) && in_window(at) {
+                out.push(at as u32);
+            }
         }
+    }
+    Ok(out)
+}
 
-        let ref_len = match parse_cigar_ref_consumed(cigar) {
-            Some(x) if x > 0 => x as i32,
-            _ => continue,
-        };
-        let rec_start = pos; // POS is 1-based
-        let rec_end = pos + ref_len - 1; // inclusive on reference
+/// Edit distance to the reference (the NM tag) summed over a window's
+/// primary alignments; what `self_assembly` samples read identity from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MismatchTally {
+    /// Alignments with an NM tag.
+    pub n_reads: u64,
+    /// Their alignment columns: M/=/X, I and D bases.
+    pub aligned_bp: u64,
+    /// Sum of their NM tags.
+    pub edits: u64,
+}
 
-        total += 1.0;
-        if rec_start <= s1 && rec_end >= e1 {
-            spans += 1.0;
+impl MismatchTally {
+    /// Edits per alignment column; `None` without any aligned bases.
+    pub fn rate(&self) -> Option<f64> {
+        (self.aligned_bp > 0).then(|| self.edits as f64 / self.aligned_bp as f64)
+    }
+}
+
+/// Tally the NM tags of the primary alignments on `rname` that pass the
+/// evidence MAPQ filter. Alignments without an NM tag are skipped.
+fn tally_mismatches(mut sam: impl BufRead, rname: &str) -> std::io::Result<MismatchTally> {
+    let mut tally = MismatchTally::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if sam.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let Some(f) = parse_sam_fields(line.strip_suffix(b"\n").unwrap_or(&line)) else {
+            continue;
+        };
+        // unmapped, secondary and supplementary alignments
+        if (f.flag & 0x904) != 0 || f.mapq < model::MIN_MAPQ || f.rname != rname.as_bytes() {
+            continue;
         }
+        let (Ok(cigar), Some(nm)) = (Cigar::parse(f.cigar), f.nm) else {
+            continue;
+        };
+        tally.n_reads += 1;
+        tally.aligned_bp += aligned_columns(&cigar);
+        tally.edits += u64::from(nm);
+    }
+    Ok(tally)
+}
+
+/// NM tally of the alignments in a window (`samtools view`).
+pub fn window_mismatches(
+    samtools: &Path,
+    bam: &Path,
+    rname: &str,
+    w: Window,
+) -> Result<MismatchTally> {
+    samtools_view_with(samtools, bam, None, rname, w, |sam| {
+        tally_mismatches(sam, rname)
+    })
+}
+
+/// The nuclear and mito windows of a pair: ±`half` around the alignment
+/// midpoints (`flank` for depth, `win` for spanning reads), cut at the
+/// contig ends `lengths` knows of.
+pub fn pair_windows(
+    p: &PairedLocus,
+    half: u32,
+    lengths: Option<&ContigLengths>,
+) -> std::result::Result<(Window, Window), WindowError> {
+    let len = |by: fn(&ContigLengths) -> &std::collections::BTreeMap<String, u64>, contig: &str| {
+        lengths.and_then(|l| by(l).get(contig).copied())
+    };
+    Ok((
+        Window::around(locus_mid(p.nuc_start, p.nuc_end)?, half)?
+            .clamp_to(len(|l| &l.nuclear, &p.nuc_contig))?,
+        Window::around(locus_mid(p.mito_start, p.mito_end)?, half)?
+            .clamp_to(len(|l| &l.mito, &p.mito_contig))?,
+    ))
+}
+
+/// Evidence for a pair whose windows could not be built: no reads, flagged
+/// `invalid_window` for scoring. Any other error of `r` is passed on.
+pub fn flag_invalid_window(p: &PairedLocus, r: Result<PairEvidence>) -> Result<PairEvidence> {
+    let e = match r {
+        Err(e) => e,
+        ok => return ok,
+    };
+    let Some(&invalid) = e.downcast_ref::<WindowError>() else {
+        return Err(e);
+    };
+    warnings::warn(
+        warnings::INVALID_WINDOW,
+        format!(
+            "{}: no evidence windows for {}:{}-{} / {}:{}-{} ({invalid}); called Ambiguous",
+            p.pair_id,
+            p.nuc_contig,
+            p.nuc_start,
+            p.nuc_end,
+            p.mito_contig,
+            p.mito_start,
+            p.mito_end
+        ),
+    );
+    Ok(PairEvidence {
+        invalid_window: Some(invalid),
+        ..PairEvidence::default()
+    })
+}
+
+/// Evidence for one pair: local median depths, spanning-read fractions and
+/// median read lengths around the locus midpoints (span windows with more
+/// than `max_reads` reads are subsampled; 0 = no cap). `mito_circular` is the
+/// topology of the mito contig, for the window-clamp warning. Windows are cut
+/// at the contig ends (`WindowFetch::contig_lengths`), and a pair whose span
+/// windows were records their widths (`PairEvidence::clamped_window`). A
+/// pair whose windows cannot be built fails with its `WindowError`, before
+/// any read is looked at; `flag_invalid_window` turns that into a flagged
+/// pair.
+#[allow(clippy::too_many_arguments)]
+pub fn pair_evidence(
+    bam_reads_to_nuc: &Path,
+    bam_reads_to_mito: &Path,
+    p: &PairedLocus,
+    flank: u32,
+    win: u32,
+    max_reads: u32,
+    mito_circular: bool,
+    fetch: &WindowFetch,
+) -> Result<PairEvidence> {
+    let (n_w, m_w) = pair_windows(p, flank, fetch.contig_lengths)?;
+    // Spanning windows: tighten to ±win around mid (must fully cover)
+    let (n_s, m_s) = pair_windows(p, win, fetch.contig_lengths)?;
+
+    // Local depths
+    let batched = |by: fn(&BatchDepths) -> &HashMap<String, f32>| {
+        fetch.batched.and_then(|b| by(b).get(&p.pair_id).copied())
+    };
+    let d_n = fetch.median_depth(bam_reads_to_nuc, &p.nuc_contig, n_w, batched(|b| &b.nuc))?;
+    let d_m = fetch.median_depth(bam_reads_to_mito, &p.mito_contig, m_w, batched(|b| &b.mito))?;
+
+    let full = 2 * win;
+    for (side, contig, w, circular) in [
+        ("nuclear", &p.nuc_contig, n_s, false),
+        ("mito", &p.mito_contig, m_s, mito_circular),
+    ] {
+        // the BAM is linear, so reads across the origin of a circular
+        // contig are split in two and none spans the window
+        let at = match (w.start < 0, w.width() < full, circular) {
+            (true, _, true) => "cut at the origin of the circular contig",
+            (true, _, false) => "clamped at the contig start",
+            (false, true, true) => "cut at the end of the circular contig",
+            (false, true, false) => "clamped at the contig end",
+            (false, false, _) => continue,
+        };
+        warnings::warn(
+            warnings::WINDOW_CLAMPED,
+            format!(
+                "{}: {side} span window {}..{} on {contig} {at} ({} bp)",
+                p.pair_id,
+                w.start,
+                w.end,
+                w.width()
+            ),
+        );
+    }
+    let clamped_window =
+        (n_s.width() < full || m_s.width() < full).then(|| (n_s.width(), m_s.width()));
+    let nuc = fetch.span_fraction(bam_reads_to_nuc, &p.nuc_contig, n_s, max_reads)?;
+    let mito = fetch.span_fraction(bam_reads_to_mito, &p.mito_contig, m_s, max_reads)?;
+    let (l_n, l_m) = (nuc.read_len, mito.read_len);
+    // read_len is the median over the reads passing --span-mapq, so 0 means
+    // there were none; one warning per pair, so `--strict` can compare the
+    // count to the pairs
+    let sparse: Vec<&str> = [("nuclear", l_n), ("mito", l_m)]
+        .into_iter()
+        .filter(|&(_, l)| l == 0.0)
+        .map(|(side, _)| side)
+        .collect();
+    if !sparse.is_empty() {
+        warnings::warn(
+            warnings::SPARSE_EVIDENCE,
+            format!(
+                "{}: no MAPQ≥{} reads in the {} span window(s) ({}, {})",
+                p.pair_id,
+                fetch.min_span_mapq,
+                sparse.join(" and "),
+                p.nuc_contig,
+                p.mito_contig
+            ),
+        );
     }
+    let junction_clips = if fetch.junction_min_clip == 0 {
+        (0, 0)
+    } else {
+        (
+            fetch.junction_clips(bam_reads_to_nuc, &p.nuc_contig, p.nuc_start)?,
+            fetch.junction_clips(bam_reads_to_nuc, &p.nuc_contig, p.nuc_end)?,
+        )
+    };
 
-    Ok(if total == 0.0 { 0.0 } else { spans / total })
+    Ok(PairEvidence {
+        depth: (d_n, d_m),
+        span: (nuc.fraction, mito.fraction),
+        read_len: (l_n, l_m),
+        read_div: (nuc.read_div, mito.read_div),
+        span_reads: Some((nuc.reads, mito.reads)),
+        junction_clips,
+        clamped_window,
+        invalid_window: None,
+    })
 }
 
 /// Compute (coverage, spans) for all pairs using small windows around each locus.
-/// Global medians are computed as the median of per-pair local medians (robust & fast).
+/// The baselines are the genome-wide medians (`depth_baselines`).
+#[allow(clippy::too_many_arguments)]
 pub fn compute_coverage_and_spans_with_tools(
     bam_reads_to_nuc: &Path,
     bam_reads_to_mito: &Path,
     pairs: &[PairedLocus],
     flank: u32,
     win: u32,
-    samtools: &Path,
+    max_reads: u32,
+    mito_meta: &MitoContigMeta,
+    fetch: &WindowFetch,
 ) -> Result<(CoverageSummary, SpanSummary)> {
-    log::info!(
-        "BAM: computing coverage & spans for {} pairs (flank={} bp) using samtools={}",
-        pairs.len(),
-        flank,
-        samtools.display()
-    );
+    match fetch.native {
+        Some(_) => log::info!(
+            "BAM: computing coverage & spans for {} pairs (flank={} bp) from the indexed BAMs",
+            pairs.len(),
+            flank
+        ),
+        None => log::info!(
+            "BAM: computing coverage & spans for {} pairs (flank={} bp) using samtools={}",
+            pairs.len(),
+            flank,
+            fetch.samtools.display()
+        ),
+    }
 
     let mut per_pair_depth: HashMap<String, (f32, f32)> = HashMap::new();
     let mut per_pair_span: HashMap<String, (f32, f32)> = HashMap::new();
+    let mut per_pair_read_len: HashMap<String, (f32, f32)> = HashMap::new();
+    let mut per_pair_read_div: HashMap<String, (Option<f32>, Option<f32>)> = HashMap::new();
+    let mut per_pair_reads: HashMap<String, (SpanReads, SpanReads)> = HashMap::new();
+    let mut per_pair_invalid: HashMap<String, WindowError> = HashMap::new();
+    let mut per_pair_junction: HashMap<String, (u32, u32)> = HashMap::new();
+    let mut per_pair_clamped: HashMap<String, (u32, u32)> = HashMap::new();
 
     let mut nuc_locals = Vec::new();
     let mut mito_locals = Vec::new();
 
-    let flank_i = flank as i32;
-    let win_i = win as i32;
-
+    let batch = fetch.batch_depths(bam_reads_to_nuc, bam_reads_to_mito, pairs, flank)?;
+    let batched = fetch.clone().with_batched(Some(&batch));
     for (i, p) in pairs.iter().enumerate() {
         if (i + 1) % 50 == 0 || i == 0 {
             log::info!("BAM: {}/{} …", i + 1, pairs.len());
         }
 
-        // Center windows at the alignment midpoints
-        let n_mid = ((p.nuc_start + p.nuc_end) / 2) as i32;
-        let m_mid = ((p.mito_start + p.mito_end) / 2) as i32;
-        let n_w = Window {
-            start: n_mid - flank_i,
-            end: n_mid + flank_i,
-        };
-        let m_w = Window {
-            start: m_mid - flank_i,
-            end: m_mid + flank_i,
-        };
-
-        // Local depths
-        let d_n = local_median_depth(samtools, bam_reads_to_nuc, &p.nuc_contig, n_w)?;
-        let d_m = local_median_depth(samtools, bam_reads_to_mito, &p.mito_contig, m_w)?;
-        per_pair_depth.insert(p.pair_id.clone(), (d_n, d_m));
-        nuc_locals.push(d_n);
-        mito_locals.push(d_m);
-
-        // Spanning windows: tighten to ±win around mid (must fully cover)
-        let n_s = Window {
-            start: n_mid - win_i,
-            end: n_mid + win_i,
-        };
-        let m_s = Window {
-            start: m_mid - win_i,
-            end: m_mid + win_i,
-        };
-        let s_n = span_fraction(samtools, bam_reads_to_nuc, &p.nuc_contig, n_s)?;
-        let s_m = span_fraction(samtools, bam_reads_to_mito, &p.mito_contig, m_s)?;
-        per_pair_span.insert(p.pair_id.clone(), (s_n, s_m));
+        let ev = flag_invalid_window(
+            p,
+            pair_evidence(
+                bam_reads_to_nuc,
+                bam_reads_to_mito,
+                p,
+                flank,
+                win,
+                max_reads,
+                mito_meta.circular(&p.mito_contig),
+                &batched,
+            ),
+        )?;
+        per_pair_depth.insert(p.pair_id.clone(), ev.depth);
+        // a flagged pair's zero depths are no sample of the genome's
+        match ev.invalid_window {
+            Some(invalid) => {
+                per_pair_invalid.insert(p.pair_id.clone(), invalid);
+            }
+            None => {
+                nuc_locals.push(ev.depth.0);
+                mito_locals.push(ev.depth.1);
+            }
+        }
+        per_pair_span.insert(p.pair_id.clone(), ev.span);
+        per_pair_read_len.insert(p.pair_id.clone(), ev.read_len);
+        per_pair_read_div.insert(p.pair_id.clone(), ev.read_div);
+        if let Some(reads) = ev.span_reads {
+            per_pair_reads.insert(p.pair_id.clone(), reads);
+        }
+        if ev.junction_clips != (0, 0) {
+            per_pair_junction.insert(p.pair_id.clone(), ev.junction_clips);
+        }
+        if let Some(widths) = ev.clamped_window {
+            per_pair_clamped.insert(p.pair_id.clone(), widths);
+        }
     }
 
-    let nuclear_median = super::bam::median_f32(nuc_locals) as f64;
-    let mito_median = super::bam::median_f32(mito_locals) as f64;
+    let genome = batched.genome_median_depths(bam_reads_to_nuc, bam_reads_to_mito)?;
+    fetch.calls.set(batched.calls.get());
+    let params = Some(
+        EvidenceParams::new(flank, win)
+            .with_depth_supplementary(fetch.depth_supplementary)
+            .with_min_mapq(fetch.min_span_mapq)
+            .with_junction_min_clip(fetch.junction_min_clip),
+    );
+    let ((nuclear_median, mito_median), loci) =
+        depth_baselines(Some(genome), nuc_locals, mito_locals);
 
     Ok((
         CoverageSummary {
             nuclear_median,
             mito_median,
+            loci_medians: Some(loci),
             per_pair: per_pair_depth,
+            params,
         },
         SpanSummary {
             per_pair: per_pair_span,
+            read_len: per_pair_read_len,
+            read_div: per_pair_read_div,
+            reads: per_pair_reads,
+            clamped_window: per_pair_clamped,
+            invalid_window: per_pair_invalid,
+            junction_clips: per_pair_junction,
+            params,
+            cost: HashMap::new(),
+            stage_cost: None,
         },
     ))
 }
@@ -225,14 +1682,664 @@ pub fn compute_coverage_and_spans_with_tools(
 mod tests {
     use super::*;
 
+    /// `WindowFetch::span_fraction` over SAM text.
+    fn span_stats(
+        sam: impl BufRead,
+        rname: &str,
+        w: Window,
+        max_reads: u32,
+    ) -> std::io::Result<SpanStats> {
+        span_stats_mapq(sam, rname, w, max_reads, model::MIN_MAPQ)
+    }
+
+    /// Reference bases a SAM CIGAR covers and its alignment columns; `None`
+    /// when it is `*` or malformed.
+    fn parse_cigar_lens(cigar: &[u8]) -> Option<(u32, u32)> {
+        cigar_lens(&Cigar::parse(cigar).ok()?)
+    }
+
+    /// …with `--span-mapq min_mapq`.
+    fn span_stats_mapq(
+        sam: impl BufRead,
+        rname: &str,
+        w: Window,
+        max_reads: u32,
+        min_mapq: u8,
+    ) -> std::io::Result<SpanStats> {
+        let mut acc = SpanAccumulator::new(rname, w, max_reads, min_mapq);
+        sam_records(sam, rname, |r| acc.offer(r))?;
+        Ok(acc.finish())
+    }
+
     #[test]
     fn cigar_ref_len_parses_basic() {
-        assert_eq!(parse_cigar_ref_consumed("100M"), Some(100));
-        assert_eq!(parse_cigar_ref_consumed("10S90M"), Some(90));
-        assert_eq!(parse_cigar_ref_consumed("50M10I40M"), Some(90));
-        assert_eq!(parse_cigar_ref_consumed("50M5D45M"), Some(100));
-        assert_eq!(parse_cigar_ref_consumed("50M100N50M"), Some(200)); // spliced
-        assert_eq!(parse_cigar_ref_consumed("*"), None);
+        let parse_cigar_ref_consumed = |c: &[u8]| parse_cigar_lens(c).map(|(r, _)| r);
+        assert_eq!(parse_cigar_ref_consumed(b"100M"), Some(100));
+        assert_eq!(parse_cigar_ref_consumed(b"10S90M"), Some(90));
+        assert_eq!(parse_cigar_ref_consumed(b"50M10I40M"), Some(90));
+        assert_eq!(parse_cigar_ref_consumed(b"50M5D45M"), Some(100));
+        assert_eq!(parse_cigar_ref_consumed(b"50M100N50M"), Some(200)); // spliced
+        assert_eq!(parse_cigar_ref_consumed(b"*"), None);
+        assert_eq!(parse_cigar_ref_consumed(b"99999999999M"), None);
+        assert_eq!(parse_cigar_ref_consumed(b"4294967295M1D"), None);
+        // alignment columns: clips and splices are not columns, I and D are
+        assert_eq!(parse_cigar_lens(b"10S50M10I40M5D2H"), Some((95, 105)));
+        assert_eq!(parse_cigar_lens(b"50M100N50M"), Some((200, 100)));
+        assert_eq!(parse_cigar_lens(b"20=1X20="), Some((41, 41)));
+    }
+
+    /// (fraction, median length) over every read.
+    fn span_stats_all(sam: &[u8], rname: &str, w: Window) -> (f32, f32) {
+        let s = span_stats(sam, rname, w, 0).unwrap();
+        assert!(!s.reads.subsampled());
+        (s.fraction, s.read_len)
+    }
+
+    #[test]
+    fn span_stats_over_cigar_mix() {
+        // window [1000, 1500) → 1-based 1001..=1500
+        let w = Window {
+            start: 1000,
+            end: 1500,
+        };
+        let sam = "\
+@HD\tVN:1.6
+r1\t0\tchr1\t1\t60\t5000M\t*\t0\t0\t*\t*
+r2\t0\tchr1\t1200\t60\t100S300M50I\t*\t0\t0\t*\t*
+r3\t16\tchr1\t900\t60\t200M10D400M\t*\t0\t0\t*\t*
+r4\t0\tchr1\t1\t5\t10000M\t*\t0\t0\t*\t*
+r5\t4\tchr1\t1\t60\t10000M\t*\t0\t0\t*\t*
+r6\t0\tchr2\t1\t60\t10000M\t*\t0\t0\t*\t*
+r7\t0\tchr1\t1300\t60\t2000N100M\t*\t0\t0\t*\t*
+";
+        // kept: r1 (5000, spans), r2 (300), r3 (610, spans), r7 (2100)
+        let (frac, len) = span_stats_all(sam.as_bytes(), "chr1", w);
+        assert_eq!(frac, 0.5);
+        assert_eq!(len, (610.0 + 2100.0) / 2.0);
+
+        // short fragments only: nothing spans, median is their length
+        let short = "r\t0\tchr1\t1100\t60\t20S250M\t*\t0\t0\t*\t*\n\
+                     s\t0\tchr1\t1200\t60\t250M\t*\t0\t0\t*\t*\n\
+                     t\t0\tchr1\t1250\t60\t150M5I100M\t*\t0\t0\t*\t*\n";
+        assert_eq!(span_stats_all(short.as_bytes(), "chr1", w), (0.0, 250.0));
+        assert_eq!(span_stats_all(b"", "chr1", w), (0.0, 0.0));
+    }
+
+    #[test]
+    fn clip_positions_at_either_end() {
+        let w = Window {
+            start: 1000,
+            end: 2000,
+        };
+        let sam = "\
+@HD\tVN:1.6
+a\t0\tchr1\t1101\t60\t50S500M\t*\t0\t0\t*\t*
+b\t2048\tchr1\t1001\t60\t400M300H\t*\t0\t0\t*\t*
+c\t0\tchr1\t1101\t60\t10S500M\t*\t0\t0\t*\t*
+d\t256\tchr1\t1101\t60\t50S500M\t*\t0\t0\t*\t*
+e\t0\tchr1\t1101\t5\t50S500M\t*\t0\t0\t*\t*
+f\t0\tchr1\t1951\t60\t100M50S\t*\t0\t0\t*\t*
+g\t0\tchr1\t901\t60\t50S1000M\t*\t0\t0\t*\t*
+";
+        // a: leading at 1100; b: supplementary, trailing at 1400; c: clip
+        // too short; d/e: secondary, low MAPQ; f: 2050 and g: 900 outside
+        let got = sam_clip_positions(sam.as_bytes(), "chr1", w, 20).unwrap();
+        assert_eq!(got, [1100, 1400]);
+        let got = sam_clip_positions(sam.as_bytes(), "chr1", w, 10).unwrap();
+        assert_eq!(got, [1100, 1400, 1100]);
+    }
+
+    #[test]
+    fn read_divergence_from_nm_wherever_the_tag_is() {
+        let w = Window {
+            start: 1000,
+            end: 1500,
+        };
+        // NM first, after other tags, last, absent; and an NM-looking QUAL
+        let sam = "\
+r1\t0\tchr1\t1\t60\t2000M\t*\t0\t0\t*\t*\tNM:i:20\ttp:A:P
+r2\t0\tchr1\t1\t60\t990M10D\t*\t0\t0\t*\t*\ttp:A:P\tNM:i:30\tcs:Z::990
+r3\t0\tchr1\t1\t60\t1000M\t*\t0\t0\t*\t*\ts1:i:900\tde:f:0.05\tNM:i:50
+r4\t0\tchr1\t1\t60\t1000M\t*\t0\t0\t*\t*\ttp:A:P
+r5\t0\tchr1\t1\t60\t4M\t*\t0\t0\tACGT\tNM:i:9
+";
+        let s = span_stats(sam.as_bytes(), "chr1", w, 0).unwrap();
+        // 0.01, 0.03 and 0.05; r4 and r5 have no NM
+        assert!(
+            (s.read_div.unwrap() - 0.03).abs() < 1e-6,
+            "{:?}",
+            s.read_div
+        );
+        assert_eq!((s.reads.sampled, s.reads.without_nm), (5, 2));
+        // all without: unknown, not zero
+        let s = span_stats(
+            &b"r4\t0\tchr1\t1\t60\t1000M\t*\t0\t0\t*\t*\n"[..],
+            "chr1",
+            w,
+            0,
+        )
+        .unwrap();
+        assert_eq!((s.read_div, s.reads.without_nm), (None, 1));
+    }
+
+    #[test]
+    fn supplementary_alignments_are_span_evidence_and_counted() {
+        let w = Window {
+            start: 1000,
+            end: 1500,
+        };
+        // r1 is one read split at a junction inside the window: its primary
+        // alignment ends there and its supplementary one starts there
+        let sam = "\
+r1\t0\tchr1\t600\t60\t5000S650M\t*\t0\t0\t*\t*
+r1\t2048\tchr1\t1250\t60\t650H800M\t*\t0\t0\t*\t*
+r2\t16\tchr1\t1\t60\t3000M\t*\t0\t0\t*\t*
+r3\t2064\tchr1\t900\t60\t800M\t*\t0\t0\t*\t*
+r4\t256\tchr1\t900\t60\t800M\t*\t0\t0\t*\t*
+r5\t2052\tchr1\t900\t60\t800M\t*\t0\t0\t*\t*
+";
+        let s = span_stats(sam.as_bytes(), "chr1", w, 0).unwrap();
+        // r2, r3 and r4 span; both halves of r1 are records, r5 is unmapped
+        assert_eq!(s.fraction, 3.0 / 5.0);
+        assert_eq!(
+            (s.reads.total, s.reads.primary, s.reads.supplementary),
+            (5, 2, 2)
+        );
+        // counted over every record, not just the sampled ones
+        let s = span_stats(sam.as_bytes(), "chr1", w, 1).unwrap();
+        assert_eq!(
+            (s.reads.sampled, s.reads.primary, s.reads.supplementary),
+            (1, 2, 2)
+        );
+    }
+
+    #[test]
+    fn junction_reads_end_clipped_at_a_locus_end() {
+        // a locus at 1000..3000 (0-based); reads are 1-based
+        let sam = "\
+s1\t0\tchr1\t1001\t60\t300S2000M\t*\t0\t0\t*\t*
+s2\t2048\tchr1\t1011\t60\t500H800M\t*\t0\t0\t*\t*
+s3\t16\tchr1\t981\t60\t150S900M\t*\t0\t0\t*\t*
+e1\t0\tchr1\t2001\t60\t1000M400S\t*\t0\t0\t*\t*
+e2\t0\tchr1\t1991\t60\t1000M120H\t*\t0\t0\t*\t*
+a1\t0\tchr1\t1101\t60\t300S900M\t*\t0\t0\t*\t*
+a2\t0\tchr1\t1001\t60\t50S900M\t*\t0\t0\t*\t*
+a3\t0\tchr1\t501\t60\t1000M\t*\t0\t0\t*\t*
+a4\t256\tchr1\t1001\t60\t300S900M\t*\t0\t0\t*\t*
+a5\t0\tchr1\t1001\t3\t300S900M\t*\t0\t0\t*\t*
+a6\t0\tchr2\t1001\t60\t300S900M\t*\t0\t0\t*\t*
+";
+        let count = |at, min_clip, min_mapq| {
+            let mut c = JunctionCounter::new(at, min_clip, min_mapq).unwrap();
+            sam_records(sam.as_bytes(), "chr1", |r| c.offer(r)).unwrap();
+            c.n
+        };
+        // s1–s3 start clipped at 1000, 1010 and 980; e1 and e2 end clipped at
+        // 3000 and 2990. a1 is clipped 100 bp away, a2 too little, a3 not at
+        // all; a4 is secondary, a5 below MAPQ 20, a6 on another contig
+        assert_eq!(count(1000, 100, 20), 3);
+        assert_eq!(count(3000, 100, 20), 2);
+        assert_eq!(count(1100, 100, 20), 1);
+        // a longer minimum clip drops e2's 120 bp…
+        assert_eq!(count(3000, 200, 20), 1);
+        // …a shorter one takes a2 in, and MAPQ 0 a5
+        assert_eq!(count(1000, 50, 20), 4);
+        assert_eq!(count(1000, 100, 0), 4);
+    }
+
+    #[test]
+    fn span_mapq_cutoff_decides_which_reads_count() {
+        let w = Window {
+            start: 1000,
+            end: 1500,
+        };
+        // inside a recent NUMT the reads that cross the junction map as well
+        // to the mito copy and get MAPQ 0; the unique flank reads do not span
+        let sam = "\
+u1\t0\tchr1\t600\t60\t500M\t*\t0\t0\t*\t*
+u2\t0\tchr1\t1300\t60\t500M\t*\t0\t0\t*\t*
+m1\t0\tchr1\t900\t0\t800M\t*\t0\t0\t*\t*
+m2\t16\tchr1\t800\t0\t1000M\t*\t0\t0\t*\t*
+m3\t0\tchr1\t950\t7\t700M\t*\t0\t0\t*\t*
+x1\t4\tchr1\t900\t0\t800M\t*\t0\t0\t*\t*
+";
+        let strict = span_stats(sam.as_bytes(), "chr1", w, 0).unwrap();
+        assert_eq!(strict.fraction, 0.0);
+        // the unmapped x1 is no read at any cutoff
+        assert_eq!((strict.reads.total, strict.reads.low_mapq), (2, 3));
+        let loose = span_stats_mapq(sam.as_bytes(), "chr1", w, 0, 0).unwrap();
+        assert_eq!(loose.fraction, 3.0 / 5.0);
+        assert_eq!((loose.reads.total, loose.reads.low_mapq), (5, 0));
+        let between = span_stats_mapq(sam.as_bytes(), "chr1", w, 0, 5).unwrap();
+        assert_eq!(between.fraction, 1.0 / 3.0);
+        assert_eq!((between.reads.total, between.reads.low_mapq), (3, 2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn depth_leaves_out_supplementary_alignments_unless_asked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // stand-in samtools depth: two reads' worth of depth with
+        // supplementary alignments, one without
+        let dir = tempfile::TempDir::new().unwrap();
+        let samtools = dir.path().join("samtools");
+        std::fs::write(
+            &samtools,
+            r#"#!/bin/sh
+echo "$@" >> "$(dirname "$0")/calls.log"
+case "$*" in
+*"-G 0xf04"*) d=1 ;;
+*) d=2 ;;
+esac
+printf 'c\t1\t%d\nc\t2\t%d\n' "$d" "$d"
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let bam = dir.path().join("r.bam");
+        std::fs::write(&bam, "bam").unwrap();
+        let w = Window { start: 0, end: 2 };
+        assert_eq!(
+            local_median_depth(&samtools, &bam, None, "c", w, false).unwrap(),
+            1.0
+        );
+        assert_eq!(
+            local_median_depth(&samtools, &bam, None, "c", w, true).unwrap(),
+            2.0
+        );
+        let calls = std::fs::read_to_string(dir.path().join("calls.log")).unwrap();
+        let shown = bam.display();
+        assert_eq!(
+            calls.lines().collect::<Vec<_>>(),
+            [
+                format!("depth -r c:1-2 -G 0xf04 {shown}"),
+                format!("depth -r c:1-2 {shown}")
+            ]
+        );
+
+        // the window cache keeps the two apart
+        let cache = WindowCache::open(&dir.path().join("cache"), 100).unwrap();
+        let fetch = WindowFetch::new(&samtools, Some(&cache));
+        assert_eq!(fetch.median_depth(&bam, "c", w, None).unwrap(), 1.0);
+        let with = fetch.clone().with_depth_supplementary(true);
+        assert_eq!(with.median_depth(&bam, "c", w, None).unwrap(), 2.0);
+        assert_eq!(fetch.median_depth(&bam, "c", w, None).unwrap(), 1.0);
+        assert_eq!(cache.stats().hits, 1);
+        // a cache hit runs no samtools
+        assert_eq!(fetch.subprocess_calls(), 1);
+    }
+
+    #[test]
+    fn crams_are_decoded_against_their_reference() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // stand-in samtools: a header of one 2 bp contig, depth 3 on it, no
+        // alignments in view
+        let dir = tempfile::TempDir::new().unwrap();
+        let samtools = dir.path().join("samtools");
+        std::fs::write(
+            &samtools,
+            r#"#!/bin/sh
+echo "$@" >> "$(dirname "$0")/calls.log"
+case "$1 $2" in
+"view -H") printf '@HD\tVN:1.6\n@SQ\tSN:c\tLN:2\n' ;;
+depth*) printf 'c\t1\t3\nc\t2\t3\n' ;;
+esac
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (bam, cram, fa) = (
+            dir.path().join("r.bam"),
+            dir.path().join("r.cram"),
+            dir.path().join("ref.fa"),
+        );
+        let refs = [(cram.clone(), fa.clone())];
+        let fetch = WindowFetch::new(&samtools, None).with_cram_references(&refs);
+        let w = Window { start: 0, end: 2 };
+        assert_eq!(fetch.median_depth(&cram, "c", w, None).unwrap(), 3.0);
+        assert_eq!(fetch.span_fraction(&cram, "c", w, 0).unwrap().fraction, 0.0);
+        assert_eq!(fetch.genome_median_depth(&cram).unwrap(), 3.0);
+        // a BAM is read as it is, whatever the references say
+        fetch.median_depth(&bam, "c", w, None).unwrap();
+
+        let calls = std::fs::read_to_string(dir.path().join("calls.log")).unwrap();
+        let calls: Vec<&str> = calls.lines().collect();
+        let (cram, fa, bam) = (cram.display(), fa.display(), bam.display());
+        assert_eq!(
+            calls[0],
+            format!("depth --reference {fa} -r c:1-2 -G 0xf04 {cram}")
+        );
+        assert_eq!(calls[1], format!("view --reference {fa} {cram} c:1-2"));
+        // the header names the references; a .crai has no idxstats
+        assert_eq!(calls[2], format!("view -H {cram}"));
+        assert!(
+            calls[3].starts_with(&format!("depth --reference {fa} -b ")),
+            "{}",
+            calls[3]
+        );
+        assert_eq!(calls[4], format!("depth -r c:1-2 -G 0xf04 {bam}"));
+        assert_eq!(calls.len(), 5);
+    }
+
+    /// `DepthWindow`s named w0, w1, … after their index.
+    fn depth_windows_of<'a>(
+        ids: &'a [String],
+        windows: &[(&'a str, i32, i32)],
+    ) -> Vec<DepthWindow<'a>> {
+        ids.iter()
+            .zip(windows)
+            .map(|(id, &(rname, start, end))| DepthWindow {
+                pair_id: id,
+                rname,
+                w: Window { start, end },
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn batched_depths_match_one_call_per_window() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // stand-in samtools depth: depth varies along the contigs; each
+        // position of the region (-r) or of the BED windows (-b) once, in
+        // order, as samtools reports them; chrZ is not in the BAM
+        let dir = tempfile::TempDir::new().unwrap();
+        let samtools = dir.path().join("samtools");
+        std::fs::write(
+            &samtools,
+            r#"#!/bin/sh
+echo "$1 $2" >> "$(dirname "$0")/calls.log"
+if [ "$2" = -b ]; then
+  cp "$3" "$(dirname "$0")/windows.bed"
+  sort -k1,1 -k2,2n "$3"
+else
+  r="$3"; s="${r##*:}"; printf '%s\t%d\t%d\n' "${r%:*}" $(( ${s%-*} - 1 )) "${s#*-}"
+fi | awk -F '\t' '$1 == "chrZ" { next } $1 != c { c = $1; last = 0 }
+  { for (x = ($2 > last ? $2 : last) + 1; x <= $3; x++) printf "%s\t%d\t%d\n", c, x, (x / 97 + x % 5) % 37 + 1
+    if ($3 > last) last = $3 }'
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let bam = dir.path().join("r.bam");
+        std::fs::write(&bam, "bam").unwrap();
+
+        // overlapping, nested, clamped at the contig start, on another contig
+        // and on one the BAM lacks
+        let windows: &[(&str, i32, i32)] = &[
+            ("c", 0, 1_000),
+            ("d", 100, 300),
+            ("c", 500, 1_500),
+            ("c", 600, 700),
+            ("c", 1_400, 1_600),
+            ("c", -50, 50),
+            ("chrZ", 0, 100),
+            ("c", 500, 1_500),
+        ];
+        let ids: Vec<String> = (0..windows.len()).map(|i| format!("w{i}")).collect();
+        let dws = depth_windows_of(&ids, windows);
+        let got = batch_median_depths(&samtools, &bam, None, &dws, false).unwrap();
+        assert_eq!(got.len(), windows.len());
+        for dw in &dws {
+            let want = local_median_depth(&samtools, &bam, None, dw.rname, dw.w, false).unwrap();
+            assert_eq!(got[dw.pair_id], want, "{dw:?}");
+        }
+        assert_eq!(got["w6"], 0.0);
+        assert!(got["w0"] != got["w2"]);
+
+        let calls = std::fs::read_to_string(dir.path().join("calls.log")).unwrap();
+        assert_eq!(calls.lines().next(), Some("depth -b"));
+        let bed = std::fs::read_to_string(dir.path().join("windows.bed")).unwrap();
+        assert_eq!(bed.lines().nth(5), Some("c\t0\t50"));
+        assert_eq!(bed.lines().count(), windows.len());
+
+        // a failing samtools is an error, with what it said
+        std::fs::write(
+            &samtools,
+            "#!/bin/sh\necho 'r.bam: truncated' >&2\nexit 1\n",
+        )
+        .unwrap();
+        let e = batch_median_depths(&samtools, &bam, None, &dws, false).unwrap_err();
+        assert!(format!("{e:#}").contains("r.bam: truncated"), "{e:#}");
+    }
+
+    #[test]
+    fn sam_fields_parse_bytes_only_as_needed() {
+        let f = parse_sam_fields(b"q1\t16\tchrM\t1001\t60\t5S95M\t*\t0\t0\tACGT\tIIII\tNM:i:0")
+            .unwrap();
+        assert_eq!(
+            f,
+            SamFields {
+                flag: 16,
+                rname: b"chrM",
+                pos: 1001,
+                mapq: 60,
+                cigar: b"5S95M",
+                nm: Some(0)
+            }
+        );
+        // long QNAMEs and non-UTF-8 bytes after the CIGAR don't matter
+        let qname = "m64011_190830_220126/".repeat(500);
+        let mut line = format!("{qname}\t0\tchr1\t7\t3\t10M\t*\t0\t0\t").into_bytes();
+        line.extend_from_slice(&[0xff, 0xfe, b'\t', 0x80]);
+        let f = parse_sam_fields(&line).unwrap();
+        assert_eq!((f.pos, f.mapq, f.cigar), (7, 3, &b"10M"[..]));
+        // empty fields are kept apart (two tabs in a row), not merged
+        assert!(parse_sam_fields(b"q\t0\t\t1\t60\t10M\t*").is_some_and(|f| f.rname.is_empty()));
+
+        // headers, truncated and garbled lines
+        assert_eq!(parse_sam_fields(b"@SQ\tSN:chr1\tLN:100"), None);
+        assert_eq!(parse_sam_fields(b""), None);
+        assert_eq!(parse_sam_fields(b"q\t0\tchr1\t100\t60"), None);
+        assert_eq!(parse_sam_fields(b"q\t0\tchr1\t100\t60\t10M"), None);
+        assert_eq!(parse_sam_fields(b"q\t0\tchr1\t1x0\t60\t10M\t*"), None);
+        assert_eq!(parse_sam_fields(b"q\t0\tchr1\t100\t999\t10M\t*"), None);
+    }
+
+    #[test]
+    fn mismatch_tally_sums_nm_over_primary_alignments() {
+        let sam = b"@SQ\tSN:chrM\tLN:16569\n\
+q1\t0\tchrM\t101\t60\t100S1000M\t*\t0\t0\t*\t*\tNM:i:1\ttp:A:P\n\
+q2\t16\tchrM\t201\t60\t500M10I490M5D\t*\t0\t0\t*\t*\trq:f:0.999\tNM:i:15\n\
+q3\t0\tchrM\t301\t60\t1000M\t*\t0\t0\t*\t*\n\
+q4\t256\tchrM\t301\t60\t1000M\t*\t0\t0\t*\t*\tNM:i:400\n\
+q5\t2048\tchrM\t301\t60\t1000M\t*\t0\t0\t*\t*\tNM:i:400\n\
+q6\t0\tchrM\t301\t0\t1000M\t*\t0\t0\t*\t*\tNM:i:400\n\
+q7\t0\tchr1\t301\t60\t1000M\t*\t0\t0\t*\t*\tNM:i:400\n\
+q8\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*\n";
+        // q3 has no NM tag; q4–q8 are secondary, supplementary, MAPQ 0,
+        // another contig, unmapped
+        let t = tally_mismatches(&sam[..], "chrM").unwrap();
+        assert_eq!(
+            t,
+            MismatchTally {
+                n_reads: 2,
+                aligned_bp: 1000 + 1005,
+                edits: 16
+            }
+        );
+        assert!((t.rate().unwrap() - 16.0 / 2005.0).abs() < 1e-12);
+        assert_eq!(MismatchTally::default().rate(), None);
+        // an NM-like string in QUAL is not the tag
+        assert_eq!(nm_tag(b"*\t0\t0\tACGT\tNM:i:9"), None);
+        assert_eq!(nm_tag(b"*\t0\t0\tACGT\tNM:i:9\tNM:i:2"), Some(2));
+    }
+
+    #[test]
+    fn span_stats_skips_truncated_lines() {
+        let w = Window {
+            start: 1000,
+            end: 1500,
+        };
+        // last line has no trailing newline; the middle one is cut short
+        let sam = b"@HD\tVN:1.6\n\
+                    a\t0\tchr1\t900\t60\t700M\t*\t0\t0\t*\t*\n\
+                    b\t0\tchr1\t950\t6\n\
+                    c\t0\tchr1\t1200\t60\t100M\t*\t0\t0\t*\t*";
+        assert_eq!(span_stats_all(sam, "chr1", w), (0.5, 400.0));
+    }
+
+    /// `n` alignments sorted by position, as `samtools view` gives them: the
+    /// first `n_spanning` cover 1..=5000, the rest are 100 bp inside 1101..1500.
+    fn deep_window(n: usize, n_spanning: usize) -> Vec<u8> {
+        let mut sam = Vec::new();
+        for i in 0..n {
+            let (pos, cigar) = if i < n_spanning {
+                (1, "5000M")
+            } else {
+                (1_101 + (i % 300), "100M")
+            };
+            sam.extend_from_slice(
+                format!("r{i}\t0\tchrM\t{pos}\t60\t{cigar}\t*\t0\t0\t*\t*\n").as_bytes(),
+            );
+        }
+        sam
+    }
+
+    #[test]
+    fn deep_windows_are_subsampled_reproducibly() {
+        let w = Window {
+            start: 1_000,
+            end: 1_500,
+        };
+        let sam = deep_window(10_000, 3_000);
+        let s = span_stats(&sam[..], "chrM", w, 500).unwrap();
+        assert_eq!(
+            s.reads,
+            SpanReads {
+                total: 10_000,
+                sampled: 500,
+                without_nm: 500,
+                primary: 10_000,
+                supplementary: 0,
+                low_mapq: 0,
+            }
+        );
+        assert!(s.reads.subsampled());
+        // no NM tags, so no divergence
+        assert_eq!(s.read_div, None);
+        // the same window samples the same reads
+        assert_eq!(span_stats(&sam[..], "chrM", w, 500).unwrap(), s);
+        // the read length comes from the sample too
+        assert_eq!(s.read_len, 100.0);
+
+        // at or under the cap every read counts, as with no cap
+        let exact = span_stats(&sam[..], "chrM", w, 10_000).unwrap();
+        assert_eq!(exact, span_stats(&sam[..], "chrM", w, 0).unwrap());
+        assert_eq!((exact.fraction, exact.reads.sampled), (0.3, 10_000));
+    }
+
+    #[test]
+    fn subsampled_span_fractions_are_unbiased() {
+        // spanning reads come first, so keeping the first 500 would say 1.0
+        const N: usize = 10_000;
+        const CAP: u32 = 500;
+        let sam = deep_window(N, 3_000);
+        let trials = 40;
+        let mut sum = 0.0;
+        for k in 0..trials {
+            // another window, so another seed; the same reads span it
+            let w = Window {
+                start: 1_000 + k,
+                end: 1_500 + k,
+            };
+            let s = span_stats(&sam[..], "chrM", w, CAP).unwrap();
+            assert_eq!(s.reads.sampled, CAP);
+            // sd of one estimate ≈ sqrt(0.3 · 0.7 / 500) ≈ 0.02
+            assert!((s.fraction - 0.3).abs() < 0.1, "window {k}: {}", s.fraction);
+            sum += s.fraction as f64;
+        }
+        // sd of the mean ≈ 0.02 / sqrt(40) ≈ 0.003
+        let mean = sum / trials as f64;
+        assert!((mean - 0.3).abs() < 0.015, "mean {mean}");
+    }
+
+    #[test]
+    fn reservoir_keeps_items_uniformly() {
+        // every item of a 100-item stream should be kept ~10% of the time
+        let mut kept = [0u32; 100];
+        let rounds = 2_000;
+        for r in 0..rounds {
+            let mut res = Reservoir::new(10, SplitMix64(r));
+            (0..100).for_each(|i| res.offer(i));
+            assert_eq!((res.seen, res.items.len()), (100, 10));
+            res.items.iter().for_each(|&i| kept[i] += 1);
+        }
+        // expected 200 per item, sd ≈ 13.4
+        for (i, &k) in kept.iter().enumerate() {
+            assert!((140..=260).contains(&k), "item {i} kept {k}×");
+        }
+    }
+
+    /// The previous implementation, kept as the baseline for the benchmark.
+    fn span_stats_lossy(sam: &[u8], rname: &str, w: Window) -> (f32, f32) {
+        let text = String::from_utf8_lossy(sam);
+        let (s1, e1) = (w.start.max(0) + 1, w.end.max(w.start + 1));
+        let (mut total, mut spans, mut lens) = (0f32, 0f32, Vec::new());
+        for line in text.lines() {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if line.starts_with('@') || cols.len() < 6 || cols[2] != rname {
+                continue;
+            }
+            let flag: u16 = cols[1].parse().unwrap_or(0);
+            let pos: i32 = cols[3].parse().unwrap_or(0);
+            let mapq: u8 = cols[4].parse().unwrap_or(0);
+            let Some(len) = parse_cigar_lens(cols[5].as_bytes()).map(|(r, _)| r) else {
+                continue;
+            };
+            if flag & 0x4 != 0 || mapq < 20 || len == 0 {
+                continue;
+            }
+            total += 1.0;
+            lens.push(len as f32);
+            if pos <= s1 && pos + len as i32 > e1 {
+                spans += 1.0;
+            }
+        }
+        (
+            if total == 0.0 { 0.0 } else { spans / total },
+            median_f32(lens),
+        )
+    }
+
+    /// Micro-benchmark over a deep mito window; run alone:
+    /// `cargo test --release span_stats_throughput -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn span_stats_throughput() {
+        const N: usize = 50_000;
+        let w = Window {
+            start: 8000,
+            end: 8500,
+        };
+        let seq = "ACGT".repeat(500);
+        let qual = "I".repeat(seq.len());
+        let mut sam = Vec::new();
+        for i in 0..N {
+            let pos = 1 + (i * 37) % 8000;
+            sam.extend_from_slice(
+                format!(
+                    "m64011_190830_220126/{i}/ccs\t0\tchrM\t{pos}\t60\t200S11800M\t*\t0\t0\t{seq}\t{qual}\tNM:i:0\trq:f:0.999\n"
+                )
+                .as_bytes(),
+            );
+        }
+        let t = std::time::Instant::now();
+        let old = span_stats_lossy(&sam, "chrM", w);
+        let t_old = t.elapsed();
+        let t = std::time::Instant::now();
+        let new = span_stats_all(&sam, "chrM", w);
+        let t_new = t.elapsed();
+        assert_eq!(old, new);
+        eprintln!(
+            "{N} alignments, {} MB of SAM: lossy String + split {t_old:?}, byte fields {t_new:?} ({:.1}×)",
+            sam.len() >> 20,
+            t_old.as_secs_f64() / t_new.as_secs_f64()
+        );
+        assert!(t_new < t_old);
     }
 
     #[test]
@@ -243,6 +2350,566 @@ mod tests {
         assert_eq!(median_f32(vec![1.0, 3.0, 2.0]), 2.0);
     }
 
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/evidence.bam")
+    }
+
+    /// The fixture's windows: across 16 kb bins, clamped at either end of a
+    /// contig, and on a contig without reads.
+    const FIXTURE_WINDOWS: &[(&str, i32, i32)] = &[
+        ("chr1", 1_000, 2_000),
+        ("chr1", 15_500, 16_600),
+        ("chr1", 31_900, 33_000),
+        ("chr1", -300, 200),
+        ("chr1", 59_500, 60_500),
+        ("chrM", 7_800, 8_800),
+        ("chrM", 16_000, 17_000),
+        ("chr2", 1_000, 2_000),
+    ];
+
+    /// The lines of evidence.sam that `samtools view` would give for `w`.
+    fn fixture_sam(rname: &str, w: Window) -> Vec<u8> {
+        let (beg, end) = region_bounds(w);
+        let sam = fs_err::read_to_string(fixture().with_extension("sam")).unwrap();
+        let mut out = Vec::new();
+        for l in sam.lines().filter(|l| !l.starts_with('@')) {
+            let f = parse_sam_fields(l.as_bytes()).unwrap();
+            let start = i64::from(f.pos) - 1;
+            let len = Cigar::parse(f.cigar).map_or(0, |c| c.ref_consumed());
+            if f.rname == rname.as_bytes()
+                && start < i64::from(end)
+                && start + len.max(1) as i64 > i64::from(beg)
+            {
+                out.extend(l.as_bytes());
+                out.push(b'\n');
+            }
+        }
+        out
+    }
+
+    /// samtools depth over evidence.sam, one position at a time.
+    fn fixture_depth(rname: &str, w: Window, supplementary: bool) -> f32 {
+        let (beg, end) = region_bounds(w);
+        let excl = if supplementary { 0x704 } else { 0xf04 };
+        let sam = fixture_sam(rname, w);
+        let reads: Vec<(i64, Cigar)> = sam
+            .split(|&b| b == b'\n')
+            .filter_map(parse_sam_fields)
+            .filter(|f| f.flag & excl == 0)
+            .filter_map(|f| Some((i64::from(f.pos) - 1, Cigar::parse(f.cigar).ok()?)))
+            .collect();
+        let depths = (beg..end)
+            .map(|x| {
+                let x = i64::from(x);
+                reads
+                    .iter()
+                    .filter(|(start, c)| {
+                        c.iter_ref_blocks().any(|b| {
+                            let b0 = start + b.ref_offset as i64;
+                            (b0..b0 + i64::from(b.len)).contains(&x)
+                        })
+                    })
+                    .count() as f32
+            })
+            .filter(|&d| d > 0.0)
+            .collect();
+        median_f32(depths)
+    }
+
+    #[test]
+    fn native_backend_matches_the_sam_records() {
+        let bams = IndexedBams::default();
+        let bam = fixture();
+        let no_samtools = Path::new("/nonexistent/samtools");
+        let fetch = WindowFetch::new(no_samtools, None).with_native(Some(&bams));
+        for &(rname, start, end) in FIXTURE_WINDOWS {
+            let w = Window { start, end };
+            for supplementary in [false, true] {
+                let got = native_median_depth(&bams, &bam, rname, w, supplementary).unwrap();
+                let want = fixture_depth(rname, w, supplementary);
+                assert_eq!(got, want, "{rname}:{start}-{end} {supplementary}");
+            }
+            for max_reads in [0, 7] {
+                let got = fetch.span_fraction(&bam, rname, w, max_reads).unwrap();
+                let want = span_stats(&fixture_sam(rname, w)[..], rname, w, max_reads).unwrap();
+                assert_eq!(got, want, "{rname}:{start}-{end} {max_reads}");
+                if rname != "chr2" {
+                    assert!(got.reads.total > 0, "{rname}:{start}-{end}");
+                }
+            }
+        }
+        assert!(
+            fixture_depth(
+                "chr1",
+                Window {
+                    start: 1_000,
+                    end: 2_000
+                },
+                false
+            ) > 0.0
+        );
+        assert_eq!(fetch.subprocess_calls(), 0);
+
+        // through the window cache, a second fetch reads nothing
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = WindowCache::open(&dir.path().join("cache"), 100).unwrap();
+        let cached = WindowFetch {
+            cache: Some(&cache),
+            ..fetch.clone()
+        };
+        let w = Window {
+            start: 15_500,
+            end: 16_600,
+        };
+        let first = cached.span_fraction(&bam, "chr1", w, 0).unwrap();
+        assert_eq!(cached.span_fraction(&bam, "chr1", w, 0).unwrap(), first);
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    /// The native backend against samtools itself, where it is installed.
+    #[test]
+    fn native_backend_matches_samtools() {
+        let Ok(samtools) = which::which("samtools") else {
+            eprintln!("samtools not on PATH; skipping");
+            return;
+        };
+        let bams = IndexedBams::default();
+        let bam = fixture();
+        let with_samtools = WindowFetch::new(&samtools, None);
+        let native = with_samtools.clone().with_native(Some(&bams));
+        for &(rname, start, end) in FIXTURE_WINDOWS {
+            let w = Window { start, end };
+            for supplementary in [false, true] {
+                let got = native_median_depth(&bams, &bam, rname, w, supplementary).unwrap();
+                let want =
+                    local_median_depth(&samtools, &bam, None, rname, w, supplementary).unwrap();
+                assert!(
+                    (got - want).abs() < 1e-3,
+                    "{rname}:{start}-{end}: {got} vs {want}"
+                );
+            }
+            let got = native.span_fraction(&bam, rname, w, 0).unwrap();
+            let want = with_samtools.span_fraction(&bam, rname, w, 0).unwrap();
+            assert!(
+                (got.fraction - want.fraction).abs() < 1e-6,
+                "{rname}:{start}-{end}"
+            );
+            assert_eq!(
+                (got.read_len, got.reads),
+                (want.read_len, want.reads),
+                "{rname}:{start}-{end}"
+            );
+        }
+    }
+
+    /// One `samtools depth -b` call against the fixture, where samtools is
+    /// installed.
+    #[test]
+    fn batched_depths_match_the_fixture() {
+        let Ok(samtools) = which::which("samtools") else {
+            eprintln!("samtools not on PATH; skipping");
+            return;
+        };
+        let windows: Vec<(&str, i32, i32)> = FIXTURE_WINDOWS
+            .iter()
+            .copied()
+            .chain([
+                ("chr1", 1_500, 2_500),
+                ("chr1", 1_800, 1_900),
+                ("chrZ", 0, 100),
+            ])
+            .collect();
+        let ids: Vec<String> = (0..windows.len()).map(|i| format!("w{i}")).collect();
+        let dws = depth_windows_of(&ids, &windows);
+        for supplementary in [false, true] {
+            let got =
+                batch_median_depths(&samtools, &fixture(), None, &dws, supplementary).unwrap();
+            for dw in &dws {
+                let want = match dw.rname {
+                    "chrZ" => 0.0,
+                    rname => fixture_depth(rname, dw.w, supplementary),
+                };
+                assert_eq!(got[dw.pair_id], want, "{dw:?} {supplementary}");
+            }
+        }
+    }
+
+    fn locus(nuc: (u32, u32), mito: (u32, u32)) -> PairedLocus {
+        PairedLocus {
+            pair_id: "P000001".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: nuc.0,
+            nuc_end: nuc.1,
+            mito_contig: "mt".into(),
+            mito_start: mito.0,
+            mito_end: mito.1,
+            aln_len: 100,
+            aln_ident: 0.99,
+            strand: crate::model::orientation::Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: crate::io::paf::IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn degenerate_windows_are_refused() {
+        let windows = |start, end, half| {
+            pair_windows(&locus((start, end), (100, 200)), half, None)
+                .map(|(n, _)| (n.start, n.end))
+        };
+        assert_eq!(windows(1_000, 2_000, 250), Ok((1_250, 1_750)));
+        // clamped at the contig start, not refused
+        assert_eq!(windows(0, 100, 250), Ok((-200, 300)));
+        assert_eq!(
+            region_str(
+                "chr1",
+                Window {
+                    start: -200,
+                    end: 300
+                }
+            ),
+            "chr1:1-300"
+        );
+
+        assert_eq!(windows(2_000, 1_000, 250), Err(WindowError::Inverted));
+        assert_eq!(windows(1_000, 1_000, 250), Err(WindowError::ZeroWidth));
+        assert_eq!(windows(1_000, 2_000, 0), Err(WindowError::ZeroWidth));
+        // the midpoint of the largest coordinates no longer overflows…
+        assert_eq!(
+            locus_mid(u32::MAX - 1, u32::MAX),
+            Ok(u64::from(u32::MAX) - 1)
+        );
+        // …and a window past what a region can hold is refused
+        assert_eq!(
+            windows(u32::MAX - 1, u32::MAX, 250),
+            Err(WindowError::OutOfRange)
+        );
+        let last = i32::MAX as u32;
+        assert_eq!(
+            windows(last - 600, last - 500, 250),
+            Ok((last as i32 - 800, last as i32 - 300))
+        );
+        assert_eq!(
+            windows(last - 200, last - 100, 250),
+            Err(WindowError::OutOfRange)
+        );
+        // the mito side too
+        let p = locus((1_000, 2_000), (100, 50));
+        assert_eq!(
+            pair_windows(&p, 250, None).unwrap_err(),
+            WindowError::Inverted
+        );
+    }
+
+    #[test]
+    fn windows_are_clamped_at_contig_ends() {
+        let (nuc, mito) = depth_fixtures();
+        let lengths = ContigLengths {
+            mito: [("chrM".to_string(), 16_569)].into(),
+            nuclear: [("chr2".to_string(), 40_000)].into(),
+        };
+        // centred 100 bp from the end of chr2
+        let p = PairedLocus {
+            nuc_contig: "chr2".into(),
+            mito_contig: "chrM".into(),
+            ..locus((39_880, 39_920), (4_000, 5_000))
+        };
+        let (n, m) = pair_windows(&p, model::WIN_BP, Some(&lengths)).unwrap();
+        assert_eq!((n.start, n.end, n.width()), (39_650, 40_000, 350));
+        assert_eq!((m.start, m.end, m.width()), (4_250, 4_750, 500));
+
+        let bams = IndexedBams::default();
+        let fetch =
+            WindowFetch::new(Path::new("/nonexistent/samtools"), None).with_native(Some(&bams));
+        let evidence = |fetch: &WindowFetch| {
+            pair_evidence(
+                &nuc,
+                &mito,
+                &p,
+                model::FLANK_BP,
+                model::WIN_BP,
+                0,
+                false,
+                fetch,
+            )
+            .unwrap()
+        };
+        // no read reaches past the contig end, so none spanned the full window
+        let unclamped = evidence(&fetch);
+        assert_eq!((unclamped.span.0, unclamped.clamped_window), (0.0, None));
+        let clamped = evidence(&fetch.clone().with_contig_lengths(Some(&lengths)));
+        assert!(clamped.span.0 > 0.0, "{:?}", clamped.span);
+        assert_eq!(clamped.clamped_window, Some((350, 500)));
+
+        let past = Window {
+            start: 40_100,
+            end: 40_600,
+        };
+        let end = |len| past.clamp_to(len).map(|w| w.end);
+        assert_eq!(end(Some(40_000)), Err(WindowError::PastContigEnd));
+        assert_eq!(end(None), Ok(40_600));
+        let p = PairedLocus {
+            nuc_contig: "chr2".into(),
+            ..locus((40_300, 40_400), (100, 200))
+        };
+        assert_eq!(
+            pair_windows(&p, model::WIN_BP, Some(&lengths)).unwrap_err(),
+            WindowError::PastContigEnd
+        );
+    }
+
+    #[test]
+    fn invalid_windows_become_flagged_evidence() {
+        let p = locus((10, 10), (100, 200));
+        let r = pair_windows(&p, 250, None).map(|_| PairEvidence::default());
+        let ev = flag_invalid_window(&p, r.map_err(anyhow::Error::from)).unwrap();
+        assert_eq!(ev.invalid_window, Some(WindowError::ZeroWidth));
+        assert_eq!((ev.depth, ev.span), ((0.0, 0.0), (0.0, 0.0)));
+        // under context, too
+        let r: Result<PairEvidence> =
+            Err(anyhow::Error::from(WindowError::OutOfRange).context("P000001"));
+        let ev = flag_invalid_window(&p, r).unwrap();
+        assert_eq!(ev.invalid_window, Some(WindowError::OutOfRange));
+        // anything else still fails the run
+        let r: Result<PairEvidence> = Err(anyhow::anyhow!("samtools depth failed"));
+        assert!(flag_invalid_window(&p, r).is_err());
+        let ok = PairEvidence {
+            depth: (1.0, 2.0),
+            ..PairEvidence::default()
+        };
+        assert_eq!(flag_invalid_window(&p, Ok(ok)).unwrap(), ok);
+    }
+
+    #[test]
+    fn genome_sample_reads_short_references_whole_and_spaces_long_ones() {
+        let refs = |lens: &[u32]| -> Vec<(String, u32)> {
+            lens.iter()
+                .enumerate()
+                .map(|(i, &l)| (format!("c{i}"), l))
+                .collect()
+        };
+        let bounds = |s: Vec<(&str, Window)>| -> Vec<(String, i32, i32)> {
+            s.into_iter()
+                .map(|(r, w)| (r.to_string(), w.start, w.end))
+                .collect()
+        };
+        let mito = refs(&[16_569, 0]);
+        assert_eq!(bounds(genome_sample(&mito)), [("c0".into(), 0, 16_569)]);
+
+        // 5 Mb in three contigs: 200 windows, one per 25 kb, the one across
+        // a contig end cut there
+        let nuc = refs(&[2_000_000, 1_000_000 + 7_500 + 5_000, 1_987_500]);
+        let sample = bounds(genome_sample(&nuc));
+        assert_eq!(sample.len(), 200);
+        assert_eq!(sample[0], ("c0".into(), 7_500, 17_500));
+        assert_eq!(sample[80], ("c1".into(), 7_500, 17_500));
+        assert_eq!(sample[120], ("c1".into(), 1_007_500, 1_012_500));
+        assert_eq!(sample[121], ("c2".into(), 20_000, 30_000));
+        let covered: i64 = sample.iter().map(|(_, s, e)| i64::from(e - s)).sum();
+        assert_eq!(covered, 2_000_000 - 5_000);
+    }
+
+    fn depth_fixtures() -> (std::path::PathBuf, std::path::PathBuf) {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        (data.join("depth_nuc.bam"), data.join("depth_mito.bam"))
+    }
+
+    /// Pairs at the depth fixtures' loci of three times the depth.
+    fn hot_pairs() -> Vec<PairedLocus> {
+        [
+            ("chr1", 21_000, 23_000),
+            ("chr1", 71_500, 72_500),
+            ("chr2", 11_000, 12_000),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (rname, start, end))| PairedLocus {
+            pair_id: format!("P{:06}", i + 1),
+            nuc_contig: rname.into(),
+            mito_contig: "chrM".into(),
+            ..locus(
+                (start, end),
+                (4_000 + 500 * i as u32, 5_000 + 500 * i as u32),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn baselines_are_the_genome_wide_depth_not_the_loci_depth() {
+        let (nuc, mito) = depth_fixtures();
+        let pairs = hot_pairs();
+        let bams = IndexedBams::default();
+        let fetch =
+            WindowFetch::new(Path::new("/nonexistent/samtools"), None).with_native(Some(&bams));
+        assert_eq!(
+            fetch.genome_median_depths(&nuc, &mito).unwrap(),
+            (10.0, 100.0)
+        );
+
+        let (coverage, _) = compute_coverage_and_spans_with_tools(
+            &nuc,
+            &mito,
+            &pairs,
+            model::FLANK_BP,
+            model::WIN_BP,
+            0,
+            &MitoContigMeta::default(),
+            &fetch,
+        )
+        .unwrap();
+        for p in &pairs {
+            assert_eq!(
+                coverage.per_pair[&p.pair_id],
+                (30.0, 300.0),
+                "{}",
+                p.pair_id
+            );
+        }
+        // the candidates' median would have made every pair look average
+        assert_eq!(coverage.loci_medians, Some((30.0, 300.0)));
+        assert_eq!(
+            (coverage.nuclear_median, coverage.mito_median),
+            (10.0, 100.0)
+        );
+
+        // without a genome-wide figure, the loci's is all there is
+        let ((n, m), loci) = depth_baselines(None, vec![30.0, 20.0, 30.0], vec![300.0]);
+        assert_eq!(((n, m), loci), ((30.0, 300.0), (30.0, 300.0)));
+        let ((n, m), _) = depth_baselines(Some((0.0, 90.0)), vec![30.0], vec![300.0]);
+        assert_eq!((n, m), (30.0, 90.0));
+
+        // a second run takes the genome-wide depths from the window cache
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = WindowCache::open(&dir.path().join("cache"), 100).unwrap();
+        let cached = fetch.clone();
+        let cached = WindowFetch {
+            cache: Some(&cache),
+            ..cached
+        };
+        let first = cached.genome_median_depths(&nuc, &mito).unwrap();
+        assert_eq!(cached.genome_median_depths(&nuc, &mito).unwrap(), first);
+        assert_eq!(cache.stats().hits, 2);
+    }
+
+    /// The same through samtools, where it is installed.
+    #[test]
+    fn samtools_baselines_match_the_native_ones() {
+        let Ok(samtools) = which::which("samtools") else {
+            eprintln!("samtools not on PATH; skipping");
+            return;
+        };
+        let (nuc, mito) = depth_fixtures();
+        let fetch = WindowFetch::new(&samtools, None);
+        assert_eq!(
+            fetch.genome_median_depths(&nuc, &mito).unwrap(),
+            (10.0, 100.0)
+        );
+        // idxstats and depth per BAM
+        assert_eq!(fetch.subprocess_calls(), 4);
+        let (coverage, _) = compute_coverage_and_spans_with_tools(
+            &nuc,
+            &mito,
+            &hot_pairs(),
+            model::FLANK_BP,
+            model::WIN_BP,
+            0,
+            &MitoContigMeta::default(),
+            &fetch,
+        )
+        .unwrap();
+        assert_eq!(coverage.loci_medians, Some((30.0, 300.0)));
+        assert_eq!(
+            (coverage.nuclear_median, coverage.mito_median),
+            (10.0, 100.0)
+        );
+    }
+
+    /// The depth fixtures converted to CRAM, where samtools is installed and
+    /// can write CRAM: the same coverage and spans as from the BAMs.
+    #[test]
+    fn crams_give_the_coverage_of_their_bams() {
+        let Ok(samtools) = which::which("samtools") else {
+            eprintln!("samtools not on PATH; skipping");
+            return;
+        };
+        let dir = tempfile::TempDir::new().unwrap();
+        let (nuc, mito) = depth_fixtures();
+        let bams = IndexedBams::default();
+        let mut refs = Vec::new();
+        for bam in [&nuc, &mito] {
+            // a reference of the BAM's contigs: a CRAM stores how the reads
+            // differ from whatever it is
+            let fa = dir
+                .path()
+                .join(bam.with_extension("fa").file_name().unwrap());
+            let mut fasta = String::new();
+            for (name, len) in bams.references(bam).unwrap() {
+                fasta.push_str(&format!(">{name}\n{}\n", "A".repeat(len as usize)));
+            }
+            fs_err::write(&fa, fasta).unwrap();
+            let cram = dir
+                .path()
+                .join(bam.with_extension("cram").file_name().unwrap());
+            let converted = Command::new(&samtools)
+                .args(["view", "-C", "--reference"])
+                .arg(&fa)
+                .arg("-o")
+                .arg(&cram)
+                .arg(bam)
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+                && Command::new(&samtools)
+                    .arg("index")
+                    .arg(&cram)
+                    .status()
+                    .is_ok_and(|s| s.success());
+            if !converted {
+                eprintln!("samtools cannot write CRAM; skipping");
+                return;
+            }
+            bam_index::require_index(&cram).unwrap();
+            refs.push((cram, fa));
+        }
+
+        let coverage_of = |nuc: &Path, mito: &Path, fetch: &WindowFetch| {
+            compute_coverage_and_spans_with_tools(
+                nuc,
+                mito,
+                &hot_pairs(),
+                model::FLANK_BP,
+                model::WIN_BP,
+                0,
+                &MitoContigMeta::default(),
+                fetch,
+            )
+            .unwrap()
+        };
+        let fetch = WindowFetch::new(&samtools, None);
+        let (want, want_spans) = coverage_of(&nuc, &mito, &fetch);
+        let fetch = fetch.with_cram_references(&refs);
+        let (got, got_spans) = coverage_of(&refs[0].0, &refs[1].0, &fetch);
+        assert_eq!(got.per_pair, want.per_pair);
+        assert_eq!(
+            (got.nuclear_median, got.mito_median, got.loci_medians),
+            (want.nuclear_median, want.mito_median, want.loci_medians)
+        );
+        assert_eq!(got_spans.per_pair, want_spans.per_pair);
+        assert_eq!(got_spans.read_len, want_spans.read_len);
+        assert_eq!(got_spans.reads, want_spans.reads);
+
+        // the native backend reads BAMs only
+        let native = fetch.with_native(Some(&bams));
+        let e = native.genome_median_depth(&refs[0].0).unwrap_err();
+        assert!(format!("{e:#}").contains("--bam-backend samtools"), "{e:#}");
+    }
+
     #[test]
     fn region_format_ok() {
         let r = region_str("chr1", Window { start: 0, end: 10 });
@@ -0,0 +1,443 @@
+//! Indexed BAM windows read in process, for `--bam-backend native`.
+//!
+//! A BAM is a series of BGZF blocks, each a gzip member of at most 64 KiB of
+//! BAM data; a virtual offset (compressed block start << 16 | offset in the
+//! block) points at any record; `bgzf::BgzfReader` reads them. The `.bai` index gives, per reference, bins
+//! of chunks (virtual offset ranges) covering the records that fall in each
+//! bin, and a linear index of the first record offset per 16 kb window. A
+//! region query reads the chunks of the bins that overlap it, past the linear
+//! index's lower bound, and keeps the records that overlap the region, as
+//! `samtools view BAM REGION` does. Records are decoded by `sam`'s BAM reader
+//! into the same SAM lines the samtools path parses.
+//!
+//! CRAMs, which need their reference to decode, are left to samtools; only
+//! their `.crai` is looked for here.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::io::bgzf::BgzfReader;
+use crate::io::sam;
+use crate::util::cigar::Cigar;
+
+/// htslib's pseudo-bin of per-reference metadata, not chunks of records.
+const META_BIN: u32 = 37450;
+/// Linear index window: 16 kb.
+const LINEAR_SHIFT: u32 = 14;
+
+/// A virtual offset range of records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chunk {
+    beg: u64,
+    end: u64,
+}
+
+/// One reference's part of a `.bai`.
+#[derive(Debug, Default)]
+struct RefIndex {
+    bins: HashMap<u32, Vec<Chunk>>,
+    /// Lowest virtual offset of a record overlapping each 16 kb window.
+    linear: Vec<u64>,
+}
+
+/// The bins that can hold records overlapping [beg, end) (SAM spec 5.3).
+fn reg2bins(beg: u32, end: u32) -> Vec<u32> {
+    let end = end.max(beg + 1) - 1;
+    let mut bins = vec![0];
+    for (offset, shift) in [(1, 26), (9, 23), (73, 20), (585, 17), (4681, 14)] {
+        bins.extend(offset + (beg >> shift)..=offset + (end >> shift));
+    }
+    bins
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_bai(mut r: impl Read) -> io::Result<Vec<RefIndex>> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != b"BAI\x01" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a BAI index",
+        ));
+    }
+    let n_ref = read_u32(&mut r)?;
+    let mut refs = Vec::with_capacity(n_ref as usize);
+    for _ in 0..n_ref {
+        let mut x = RefIndex::default();
+        for _ in 0..read_u32(&mut r)? {
+            let bin = read_u32(&mut r)?;
+            let n_chunk = read_u32(&mut r)?;
+            let mut chunks = Vec::with_capacity(n_chunk.min(1 << 16) as usize);
+            for _ in 0..n_chunk {
+                let (beg, end) = (read_u64(&mut r)?, read_u64(&mut r)?);
+                chunks.push(Chunk { beg, end });
+            }
+            if bin != META_BIN {
+                x.bins.insert(bin, chunks);
+            }
+        }
+        let n_intv = read_u32(&mut r)?;
+        x.linear = (0..n_intv)
+            .map(|_| read_u64(&mut r))
+            .collect::<io::Result<_>>()?;
+        refs.push(x);
+    }
+    Ok(refs)
+}
+
+/// Whether `path` names a CRAM rather than a BAM, by its extension.
+pub fn is_cram(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("cram"))
+}
+
+/// The extension of the index samtools writes for `aln`.
+fn index_ext(aln: &Path) -> &'static str {
+    if is_cram(aln) {
+        "crai"
+    } else {
+        "bai"
+    }
+}
+
+/// The index of `aln`: `BAM.bai` (`CRAM.crai`) as samtools writes it, else
+/// `aln` with its extension replaced by `.bai` (`.crai`).
+fn index_path(aln: &Path) -> Option<PathBuf> {
+    let ext = index_ext(aln);
+    let mut appended = aln.as_os_str().to_owned();
+    appended.push(format!(".{ext}"));
+    [PathBuf::from(appended), aln.with_extension(ext)]
+        .into_iter()
+        .find(|p| p.is_file())
+}
+
+/// The index of the BAM or CRAM `aln`, which region queries need, or an
+/// error naming the one it lacks.
+pub fn require_index(aln: &Path) -> Result<PathBuf> {
+    index_path(aln).ok_or_else(|| {
+        anyhow!(
+            "{}: no .{} index next to it (`samtools index` writes one)",
+            aln.display(),
+            index_ext(aln)
+        )
+    })
+}
+
+/// A coordinate-sorted BAM and its `.bai`, open for region queries.
+pub struct IndexedBam {
+    bgzf: BgzfReader<BufReader<File>>,
+    refs: Vec<String>,
+    lens: Vec<u32>,
+    index: Vec<RefIndex>,
+}
+
+impl std::fmt::Debug for IndexedBam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IndexedBam")
+            .field("refs", &self.refs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IndexedBam {
+    pub fn open(bam: &Path) -> Result<Self> {
+        if is_cram(bam) {
+            bail!(
+                "{}: CRAMs are read with samtools (--bam-backend samtools), not natively",
+                bam.display()
+            );
+        }
+        let bai = require_index(bam)?;
+        let index = read_bai(BufReader::new(
+            File::open(&bai).with_context(|| format!("open {}", bai.display()))?,
+        ))
+        .with_context(|| format!("read {}", bai.display()))?;
+        let file = File::open(bam).with_context(|| format!("open {}", bam.display()))?;
+        let mut bgzf = BgzfReader::new(BufReader::new(file));
+        let (refs, lens): (Vec<String>, Vec<u32>) = sam::read_header(&mut bgzf)
+            .with_context(|| format!("read {}", bam.display()))?
+            .into_iter()
+            .unzip();
+        if refs.len() != index.len() {
+            bail!(
+                "{}: {} references, but {} lists {}",
+                bam.display(),
+                refs.len(),
+                bai.display(),
+                index.len()
+            );
+        }
+        Ok(Self {
+            bgzf,
+            refs,
+            lens,
+            index,
+        })
+    }
+
+    /// The references of the header and their lengths.
+    pub fn references(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.refs
+            .iter()
+            .map(String::as_str)
+            .zip(self.lens.iter().copied())
+    }
+
+    /// The chunks to read for [beg, end) on reference `tid`: merged, in file
+    /// order, and none ending before the linear index's lower bound.
+    fn chunks(&self, tid: usize, beg: u32, end: u32) -> Vec<Chunk> {
+        let x = &self.index[tid];
+        let min_offset = x
+            .linear
+            .get((beg >> LINEAR_SHIFT) as usize)
+            .copied()
+            .unwrap_or(0);
+        let mut chunks: Vec<Chunk> = reg2bins(beg, end)
+            .iter()
+            .filter_map(|b| x.bins.get(b))
+            .flatten()
+            .filter(|c| c.end > min_offset)
+            .copied()
+            .collect();
+        chunks.sort_by_key(|c| c.beg);
+        let mut merged: Vec<Chunk> = Vec::with_capacity(chunks.len());
+        for c in chunks {
+            match merged.last_mut() {
+                Some(last) if c.beg <= last.end => last.end = last.end.max(c.end),
+                _ => merged.push(c),
+            }
+        }
+        merged
+    }
+
+    /// Hand each record overlapping [beg, end) (0-based) on `rname` to
+    /// `each`, in file order, as the SAM line `sam` renders it. An unknown
+    /// reference has no records, as with samtools.
+    pub fn fetch(
+        &mut self,
+        rname: &str,
+        beg: u32,
+        end: u32,
+        mut each: impl FnMut(&[u8]),
+    ) -> Result<()> {
+        let Some(tid) = self.refs.iter().position(|r| r == rname) else {
+            return Ok(());
+        };
+        if beg >= end {
+            return Ok(());
+        }
+        for c in self.chunks(tid, beg, end) {
+            self.bgzf.seek(c.beg)?;
+            while self.bgzf.virtual_offset() < c.end {
+                let Some(b) = sam::read_record(&mut self.bgzf)? else {
+                    break;
+                };
+                let field = |at: usize| i32::from_le_bytes(b[at..at + 4].try_into().unwrap());
+                let (ref_id, pos) = (field(0), field(4));
+                // sorted: past the region, nothing later overlaps it
+                if ref_id != tid as i32 || pos >= end as i32 {
+                    return Ok(());
+                }
+                let line = sam::record_line(&self.refs, &b)?;
+                let cigar = line.split('\t').nth(5).unwrap_or("*");
+                let ref_len = Cigar::parse(cigar.as_bytes())
+                    .map(|c| c.ref_consumed())
+                    .unwrap_or(0);
+                if i64::from(pos) + ref_len.max(1) as i64 > i64::from(beg) {
+                    each(line.as_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The BAMs a run reads natively, each opened (and its index loaded) once.
+#[derive(Debug, Default)]
+pub struct IndexedBams {
+    open: RefCell<HashMap<PathBuf, IndexedBam>>,
+}
+
+impl IndexedBams {
+    /// `f` on `bam`, opening it the first time.
+    fn with<T>(&self, bam: &Path, f: impl FnOnce(&mut IndexedBam) -> Result<T>) -> Result<T> {
+        let mut open = self.open.borrow_mut();
+        if !open.contains_key(bam) {
+            let b = IndexedBam::open(bam)?;
+            open.insert(bam.to_path_buf(), b);
+        }
+        f(open.get_mut(bam).expect("opened above"))
+    }
+
+    /// `IndexedBam::fetch` on `bam`, opening it the first time.
+    pub fn fetch(
+        &self,
+        bam: &Path,
+        rname: &str,
+        beg: u32,
+        end: u32,
+        each: impl FnMut(&[u8]),
+    ) -> Result<()> {
+        self.with(bam, |b| {
+            b.fetch(rname, beg, end, each).with_context(|| {
+                format!("read {}:{}-{} from {}", rname, beg + 1, end, bam.display())
+            })
+        })
+    }
+
+    /// `IndexedBam::references` of `bam`, opening it the first time.
+    pub fn references(&self, bam: &Path) -> Result<Vec<(String, u32)>> {
+        self.with(bam, |b| {
+            Ok(b.references().map(|(n, l)| (n.to_string(), l)).collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/evidence.bam")
+    }
+
+    /// The records of evidence.sam on `rname` overlapping [beg, end).
+    fn sam_overlapping(rname: &str, beg: u32, end: u32) -> Vec<String> {
+        let sam = fs_err::read_to_string(fixture().with_extension("sam")).unwrap();
+        sam.lines()
+            .filter(|l| !l.starts_with('@'))
+            .filter(|l| {
+                let f: Vec<&str> = l.split('\t').collect();
+                let pos = f[3].parse::<i64>().unwrap() - 1;
+                let len = Cigar::parse(f[5].as_bytes()).map_or(0, |c| c.ref_consumed());
+                f[2] == rname && pos < i64::from(end) && pos + len.max(1) as i64 > i64::from(beg)
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reg2bins_covers_every_level() {
+        assert_eq!(reg2bins(0, 1), [0, 1, 9, 73, 585, 4681]);
+        // [16000, 17000) crosses a 16 kb bin
+        assert_eq!(reg2bins(16_000, 17_000), [0, 1, 9, 73, 585, 4681, 4682]);
+    }
+
+    #[test]
+    fn region_queries_return_the_overlapping_records_in_order() {
+        let mut bam = IndexedBam::open(&fixture()).unwrap();
+        for (rname, beg, end) in [
+            ("chr1", 1_000, 1_001),
+            ("chr1", 15_900, 16_500),
+            ("chr1", 32_767, 32_768),
+            ("chr1", 40_000, 48_000),
+            ("chr1", 59_000, 70_000),
+            ("chr1", 0, 60_000),
+            ("chrM", 8_000, 8_500),
+            ("chrM", 16_000, 16_569),
+            ("chr2", 0, 5_000),
+        ] {
+            let mut got = Vec::new();
+            bam.fetch(rname, beg, end, |l| {
+                got.push(String::from_utf8(l.to_vec()).unwrap())
+            })
+            .unwrap();
+            assert_eq!(got, sam_overlapping(rname, beg, end), "{rname}:{beg}-{end}");
+            if rname != "chr2" {
+                assert!(!got.is_empty(), "{rname}:{beg}-{end}");
+            }
+        }
+        // the CG read's CIGAR is its tag's
+        let mut cg = Vec::new();
+        bam.fetch("chr1", 15_500, 15_501, |l| {
+            if l.starts_with(b"chr1_cg\t") {
+                cg.push(String::from_utf8(l.to_vec()).unwrap())
+            }
+        })
+        .unwrap();
+        let want: Vec<String> = sam_overlapping("chr1", 15_500, 15_501)
+            .into_iter()
+            .filter(|l| l.starts_with("chr1_cg\t"))
+            .collect();
+        assert_eq!((cg.len(), &cg), (1, &want));
+        assert!(!cg[0].contains("S"), "{}", cg[0]);
+        // unknown references and empty regions have no records
+        let mut n = 0;
+        bam.fetch("chrX", 0, 100, |_| n += 1).unwrap();
+        bam.fetch("chr1", 100, 100, |_| n += 1).unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn missing_or_broken_indexes_are_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let bam = dir.path().join("r.bam");
+        fs_err::copy(fixture(), &bam).unwrap();
+        let e = IndexedBam::open(&bam).unwrap_err();
+        assert!(format!("{e:#}").contains("no .bai index"), "{e:#}");
+        // `r.bai` is found too
+        fs_err::write(dir.path().join("r.bai"), b"BAI\x01\x01\0\0\0").unwrap();
+        assert!(IndexedBam::open(&bam).is_err());
+        fs_err::copy(
+            fixture().with_extension("bam.bai"),
+            dir.path().join("r.bai"),
+        )
+        .unwrap();
+        IndexedBam::open(&bam).unwrap();
+    }
+
+    #[test]
+    fn crams_need_a_crai_and_samtools() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cram = dir.path().join("r.cram");
+        fs_err::write(&cram, b"CRAM").unwrap();
+        let e = require_index(&cram).unwrap_err();
+        assert!(format!("{e:#}").contains("no .crai index"), "{e:#}");
+        // a .bai is no CRAM index
+        fs_err::write(dir.path().join("r.cram.bai"), b"").unwrap();
+        assert!(require_index(&cram).is_err());
+        fs_err::write(dir.path().join("r.crai"), b"").unwrap();
+        assert_eq!(require_index(&cram).unwrap(), dir.path().join("r.crai"));
+        fs_err::write(dir.path().join("r.cram.crai"), b"").unwrap();
+        assert_eq!(
+            require_index(&cram).unwrap(),
+            dir.path().join("r.cram.crai")
+        );
+        let e = IndexedBam::open(&cram).unwrap_err();
+        assert!(format!("{e:#}").contains("--bam-backend samtools"), "{e:#}");
+    }
+
+    #[test]
+    fn corrupt_blocks_are_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let bam = dir.path().join("r.bam");
+        let mut bytes = fs_err::read(fixture()).unwrap();
+        fs_err::copy(
+            fixture().with_extension("bam.bai"),
+            dir.path().join("r.bam.bai"),
+        )
+        .unwrap();
+        // flip a byte in the middle of the file's data
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+        fs_err::write(&bam, &bytes).unwrap();
+        let bams = IndexedBams::default();
+        let e = bams.fetch(&bam, "chr1", 0, 60_000, |_| {}).unwrap_err();
+        assert!(format!("{e:#}").contains("r.bam"), "{e:#}");
+    }
+}
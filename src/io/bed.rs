@@ -0,0 +1,328 @@
+//! BED reading/writing shared by every feature that consumes or emits intervals.
+//!
+//! Coordinates are 0-based half-open [start, end), exactly as in the file.
+//! The reader tolerates the usual real-world quirks:
+//!   * `browser` / `track` / `#` header lines,
+//!   * tab *or* whitespace separation (tabs win when present, so names may hold spaces),
+//!   * CRLF line endings and trailing whitespace,
+//!   * 3–6+ columns (extra columns are kept verbatim),
+//!   * float scores and `.` placeholders,
+//!   * gzip/bgzip compression (detected from magic bytes).
+//!
+//! Malformed lines are skipped with a warning, or rejected with an error in strict mode.
+
+use anyhow::{anyhow, Context, Result};
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::io::runfiles;
+use crate::util::warnings;
+
+/// One BED interval (0-based half-open).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedRecord {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub name: Option<String>,
+    pub score: Option<f64>,
+    pub strand: Option<char>,
+    /// Columns 7+ (thickStart, itemRgb, …), untouched.
+    pub extra: Vec<String>,
+}
+
+impl BedRecord {
+    /// Minimal 3-column record.
+    pub fn new(chrom: &str, start: u32, end: u32) -> Self {
+        Self {
+            chrom: chrom.to_string(),
+            start,
+            end,
+            name: None,
+            score: None,
+            strand: None,
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Render as a BED line (no trailing newline), using only as many columns as needed.
+    pub fn to_line(&self) -> String {
+        let mut s = format!("{}\t{}\t{}", self.chrom, self.start, self.end);
+        let has_extra = !self.extra.is_empty();
+        let has_strand = self.strand.is_some() || has_extra;
+        let has_score = self.score.is_some() || has_strand;
+        let has_name = self.name.is_some() || has_score;
+
+        if has_name {
+            let _ = write!(s, "\t{}", self.name.as_deref().unwrap_or("."));
+        }
+        if has_score {
+            match self.score {
+                Some(x) if x.fract() == 0.0 && x.abs() < 1e15 => {
+                    let _ = write!(s, "\t{}", x as i64);
+                }
+                Some(x) => {
+                    let _ = write!(s, "\t{x}");
+                }
+                None => s.push_str("\t."),
+            }
+        }
+        if has_strand {
+            let _ = write!(s, "\t{}", self.strand.unwrap_or('.'));
+        }
+        for e in &self.extra {
+            let _ = write!(s, "\t{e}");
+        }
+        s
+    }
+}
+
+/// Reader behaviour knobs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BedReadOptions {
+    /// Error on the first malformed line instead of skipping it.
+    pub strict: bool,
+}
+
+/// Read a BED file leniently (malformed lines are skipped with a warning).
+pub fn read_bed(path: &Path) -> Result<Vec<BedRecord>> {
+    read_bed_with(path, BedReadOptions::default())
+}
+
+/// Read a BED file (plain or gzip) with explicit options.
+pub fn read_bed_with(path: &Path, opts: BedReadOptions) -> Result<Vec<BedRecord>> {
+    let rdr = runfiles::open_maybe_compressed(path)?;
+    parse_bed_reader(rdr, &path.display().to_string(), opts)
+}
+
+/// Parse BED from any buffered reader; `source` is only used in messages.
+pub fn parse_bed_reader<R: BufRead>(
+    rdr: R,
+    source: &str,
+    opts: BedReadOptions,
+) -> Result<Vec<BedRecord>> {
+    let mut out = Vec::new();
+    let mut n_malformed = 0usize;
+
+    for (i, line) in rdr.lines().enumerate() {
+        let lineno = i + 1;
+        let line = line.with_context(|| format!("read BED line {lineno} in {source}"))?;
+        let trimmed = line.trim_end();
+        if is_header_or_blank(trimmed) {
+            continue;
+        }
+        match parse_bed_line(trimmed) {
+            Ok(rec) => out.push(rec),
+            Err(e) => {
+                if opts.strict {
+                    return Err(anyhow!("{source}:{lineno}: malformed BED line: {e}"));
+                }
+                n_malformed += 1;
+                warnings::warn(
+                    warnings::MALFORMED_BED_LINE,
+                    format!("{source}:{lineno}: skipping malformed BED line ({e}): {trimmed}"),
+                );
+            }
+        }
+    }
+
+    if n_malformed > 0 {
+        warnings::warn(
+            warnings::MALFORMED_BED_FILE,
+            format!("{source}: skipped {n_malformed} malformed BED line(s)"),
+        );
+    }
+    Ok(out)
+}
+
+/// Write records as BED (one line each, columns as needed).
+pub fn write_bed(path: &Path, records: &[BedRecord]) -> Result<()> {
+    fs_err::write(path, bed_string(records))?;
+    Ok(())
+}
+
+/// Render records as BED text.
+pub fn bed_string(records: &[BedRecord]) -> String {
+    let mut s = String::new();
+    for r in records {
+        s.push_str(&r.to_line());
+        s.push('\n');
+    }
+    s
+}
+
+/* ------------------------- internal helpers ------------------------- */
+
+fn is_header_or_blank(line: &str) -> bool {
+    let t = line.trim_start();
+    t.is_empty() || t.starts_with('#') || t.starts_with("browser") || t.starts_with("track")
+}
+
+fn parse_bed_line(line: &str) -> Result<BedRecord> {
+    let cols: Vec<&str> = if line.contains('\t') {
+        line.split('\t').map(str::trim).collect()
+    } else {
+        line.split_whitespace().collect()
+    };
+    if cols.len() < 3 {
+        return Err(anyhow!("expected ≥3 columns, found {}", cols.len()));
+    }
+
+    let chrom = cols[0];
+    if chrom.is_empty() {
+        return Err(anyhow!("empty chrom"));
+    }
+    let start: u32 = cols[1]
+        .parse()
+        .map_err(|_| anyhow!("bad start '{}'", cols[1]))?;
+    let end: u32 = cols[2]
+        .parse()
+        .map_err(|_| anyhow!("bad end '{}'", cols[2]))?;
+    if end < start {
+        return Err(anyhow!("end {end} < start {start}"));
+    }
+
+    let mut rec = BedRecord::new(chrom, start, end);
+    rec.name = cols
+        .get(3)
+        .filter(|s| !s.is_empty() && **s != ".")
+        .map(|s| s.to_string());
+    rec.score = match cols.get(4) {
+        None => None,
+        Some(s) if s.is_empty() || *s == "." => None,
+        Some(s) => Some(s.parse::<f64>().map_err(|_| anyhow!("bad score '{s}'"))?),
+    };
+    rec.strand = match cols.get(5).copied() {
+        None | Some("") | Some(".") => None,
+        Some("+") => Some('+'),
+        Some("-") => Some('-'),
+        Some(s) => return Err(anyhow!("bad strand '{s}'")),
+    };
+    rec.extra = cols.iter().skip(6).map(|s| s.to_string()).collect();
+    Ok(rec)
+}
+
+/* ------------------------------ tests ------------------------------ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn parse(s: &str) -> Vec<BedRecord> {
+        parse_bed_reader(s.as_bytes(), "test", BedReadOptions::default()).unwrap()
+    }
+
+    fn parse_strict(s: &str) -> Result<Vec<BedRecord>> {
+        parse_bed_reader(s.as_bytes(), "test", BedReadOptions { strict: true })
+    }
+
+    #[test]
+    fn three_to_six_columns() {
+        let v = parse("chr1\t0\t10\nchr1\t5\t20\tfoo\nchr2\t1\t2\tbar\t7\t-\n");
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0], BedRecord::new("chr1", 0, 10));
+        assert_eq!(v[1].name.as_deref(), Some("foo"));
+        assert_eq!(v[1].score, None);
+        assert_eq!(v[2].score, Some(7.0));
+        assert_eq!(v[2].strand, Some('-'));
+        assert_eq!(v[0].len(), 10);
+    }
+
+    #[test]
+    fn headers_comments_blank_lines_skipped() {
+        let txt = "browser position chr1:1-100\ntrack name=x\n# comment\n\n   \nchr1\t1\t2\n";
+        let v = parse(txt);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].chrom, "chr1");
+    }
+
+    #[test]
+    fn crlf_and_trailing_whitespace() {
+        let v = parse("chr1\t1\t5\tn1 \r\nchr2 3 9   \r\n");
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0].name.as_deref(), Some("n1"));
+        assert_eq!((v[1].start, v[1].end), (3, 9));
+    }
+
+    #[test]
+    fn tabs_preserve_spaces_in_names() {
+        let v = parse("chr1\t1\t5\tmy feature\t0\t+\n");
+        assert_eq!(v[0].name.as_deref(), Some("my feature"));
+        assert_eq!(v[0].strand, Some('+'));
+    }
+
+    #[test]
+    fn float_scores_and_placeholders() {
+        let v = parse("c\t0\t1\t.\t0.75\t.\nc\t0\t1\tx\t.\t+\n");
+        assert_eq!(v[0].name, None);
+        assert_eq!(v[0].score, Some(0.75));
+        assert_eq!(v[0].strand, None);
+        assert_eq!(v[1].score, None);
+    }
+
+    #[test]
+    fn extra_columns_kept() {
+        let v = parse("c\t0\t10\tx\t0\t+\t0\t10\t255,0,0\n");
+        assert_eq!(v[0].extra, vec!["0", "10", "255,0,0"]);
+    }
+
+    #[test]
+    fn malformed_lines_skipped_or_rejected() {
+        let txt = "c\t0\t10\nc\t5\nc\tx\t10\nc\t10\t5\nc\t0\t1\tn\t0\t*\nc\t0\t1\tn\tabc\n";
+        let v = parse(txt);
+        assert_eq!(v.len(), 1, "only the first line is valid");
+
+        let e = parse_strict(txt).unwrap_err().to_string();
+        assert!(e.contains("test:2"), "error names the line: {e}");
+    }
+
+    #[test]
+    fn zero_length_allowed() {
+        let v = parse_strict("c\t5\t5\n").unwrap();
+        assert!(v[0].is_empty());
+    }
+
+    #[test]
+    fn gzip_detected() {
+        let mut f = NamedTempFile::new().unwrap();
+        {
+            let mut gz = flate2::write::GzEncoder::new(&mut f, flate2::Compression::default());
+            gz.write_all(b"chr1\t10\t20\tgz\n").unwrap();
+            gz.finish().unwrap();
+        }
+        let v = read_bed(f.path()).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].name.as_deref(), Some("gz"));
+    }
+
+    #[test]
+    fn write_read_roundtrip() {
+        let mut r1 = BedRecord::new("chr1", 0, 100);
+        r1.name = Some("P000001".into());
+        r1.score = Some(0.5);
+        r1.strand = Some('-');
+        let mut r2 = BedRecord::new("chr2", 7, 9);
+        r2.strand = Some('+');
+        let r3 = BedRecord::new("chr3", 1, 2);
+        let recs = vec![r1, r2, r3];
+
+        let f = NamedTempFile::new().unwrap();
+        write_bed(f.path(), &recs).unwrap();
+        let back = read_bed_with(f.path(), BedReadOptions { strict: true }).unwrap();
+        assert_eq!(back, recs);
+
+        assert_eq!(recs[1].to_line(), "chr2\t7\t9\t.\t.\t+");
+        assert_eq!(recs[2].to_line(), "chr3\t1\t2");
+    }
+}
@@ -0,0 +1,346 @@
+//! Just enough of BGZF (blocked gzip, as `bgzip` and samtools write): writing
+//! it, with the `.gzi` index `samtools faidx` would make, and reading it from
+//! a virtual offset (BAM indexes) or an uncompressed offset through a `.gzi`
+//! (FASTA).
+//!
+//! A BGZF file is a series of gzip members of at most 64 KiB each, so any
+//! gzip reader (`MultiGzDecoder`, minimap2) reads it as one stream, and a
+//! reader can start at any member. The `.gzi` lists where each member after
+//! the first starts, compressed and uncompressed, as little-endian u64s after
+//! their count.
+
+use anyhow::{bail, Context, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Uncompressed bytes per block, as `bgzip` uses: the deflated block then
+/// always fits the 64 KiB a block may take.
+const BLOCK_DATA: usize = 0xff00;
+
+/// One BGZF block is at most this much data, compressed or not.
+const MAX_BLOCK: usize = 1 << 16;
+/// The empty block that marks the end of a BGZF file.
+const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0x1b, 0, 0x03, 0, 0,
+    0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Whether `head`, the first bytes of a file, start a BGZF block: a gzip
+/// member with the `BC` extra subfield. Plain gzip is not BGZF.
+pub fn is_bgzf(head: &[u8]) -> bool {
+    head.len() >= 14
+        && head.starts_with(&[0x1f, 0x8b, 0x08])
+        && head[3] & 0x04 != 0
+        && &head[12..14] == b"BC"
+}
+
+/// Writes BGZF, keeping the offsets of its blocks for the `.gzi`.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    /// (compressed, uncompressed) start of every block after the first.
+    index: Vec<(u64, u64)>,
+    compressed: u64,
+    uncompressed: u64,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(BLOCK_DATA),
+            index: Vec::new(),
+            compressed: 0,
+            uncompressed: 0,
+        }
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        if self.compressed > 0 {
+            self.index.push((self.compressed, self.uncompressed));
+        }
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(&self.buf)?;
+        let cdata = deflate.finish()?;
+        let mut crc = Crc::new();
+        crc.update(&self.buf);
+        // header (18) + cdata + CRC32 and ISIZE (8)
+        let block_len = 18 + cdata.len() + 8;
+        let bsize = u16::try_from(block_len - 1)
+            .map_err(|_| io::Error::other("bgzf: block does not fit 64 KiB"))?;
+        let mut header = [
+            0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0, 0,
+        ];
+        header[16..18].copy_from_slice(&bsize.to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&cdata)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner
+            .write_all(&(self.buf.len() as u32).to_le_bytes())?;
+        self.compressed += block_len as u64;
+        self.uncompressed += self.buf.len() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write the last block and the end-of-file marker; the `.gzi` entries.
+    pub fn finish(mut self) -> io::Result<Vec<(u64, u64)>> {
+        self.write_block()?;
+        self.inner.write_all(&EOF_BLOCK)?;
+        self.inner.flush()?;
+        Ok(self.index)
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(BLOCK_DATA - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == BLOCK_DATA {
+            self.write_block()?;
+        }
+        Ok(n)
+    }
+
+    /// Flushes whole blocks only; a partial one waits for more data or
+    /// `finish`, so blocks stay full.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads BGZF, checking each block's CRC, and seeks to virtual offsets (as
+/// BAM indexes give them) or, through `read_at`, to `.gzi` offsets.
+pub struct BgzfReader<R> {
+    inner: R,
+    /// Decompressed data of the current block, and the read position in it.
+    block: Vec<u8>,
+    at: usize,
+    /// Compressed offsets of the current block and the next one.
+    block_start: u64,
+    next_block: u64,
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            block: Vec::new(),
+            at: 0,
+            block_start: 0,
+            next_block: 0,
+        }
+    }
+
+    /// Go to a virtual offset: compressed block start << 16 | offset in
+    /// the block.
+    pub fn seek(&mut self, voffset: u64) -> io::Result<()> {
+        let (coffset, uoffset) = (voffset >> 16, (voffset & 0xffff) as usize);
+        if coffset != self.block_start || self.block.is_empty() {
+            self.inner.seek(SeekFrom::Start(coffset))?;
+            self.next_block = coffset;
+            self.load_block()?;
+        }
+        if uoffset > self.block.len() {
+            return Err(invalid("virtual offset past the end of its block"));
+        }
+        self.at = uoffset;
+        Ok(())
+    }
+
+    /// Where the next byte would be read from; the end of a block is the
+    /// start of the next, as the index records it.
+    pub fn virtual_offset(&self) -> u64 {
+        if self.at == self.block.len() {
+            self.next_block << 16
+        } else {
+            self.block_start << 16 | self.at as u64
+        }
+    }
+
+    /// Read the block at `next_block`; false at the end of the file.
+    fn load_block(&mut self) -> io::Result<bool> {
+        self.block_start = self.next_block;
+        self.block.clear();
+        self.at = 0;
+        let mut header = [0; 12];
+        match self.inner.read_exact(&mut header[..1]) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            r => r?,
+        }
+        self.inner.read_exact(&mut header[1..])?;
+        if header[..4] != [31, 139, 8, 4] {
+            return Err(invalid("not a BGZF block"));
+        }
+        let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let mut extra = vec![0; xlen];
+        self.inner.read_exact(&mut extra)?;
+        // the BC subfield holds the block size - 1
+        let mut bsize = None;
+        let mut sub = &extra[..];
+        while sub.len() >= 4 {
+            let len = u16::from_le_bytes([sub[2], sub[3]]) as usize;
+            if &sub[..2] == b"BC" && len == 2 && sub.len() >= 6 {
+                bsize = Some(u16::from_le_bytes([sub[4], sub[5]]) as usize + 1);
+            }
+            sub = sub.get(4 + len..).unwrap_or_default();
+        }
+        let bsize = bsize.ok_or_else(|| invalid("BGZF block without its size"))?;
+        let rest = bsize
+            .checked_sub(12 + xlen + 8)
+            .ok_or_else(|| invalid("BGZF block size too small"))?;
+        let mut cdata = vec![0; rest + 8];
+        self.inner.read_exact(&mut cdata)?;
+        let tail = &cdata[rest..];
+        let crc = u32::from_le_bytes(tail[..4].try_into().unwrap());
+        let isize = u32::from_le_bytes(tail[4..].try_into().unwrap()) as usize;
+        if isize > MAX_BLOCK {
+            return Err(invalid("BGZF block larger than 64 KiB"));
+        }
+        self.block.reserve(isize);
+        DeflateDecoder::new(&cdata[..rest]).read_to_end(&mut self.block)?;
+        let mut check = flate2::Crc::new();
+        check.update(&self.block);
+        if self.block.len() != isize || check.sum() != crc {
+            return Err(invalid("BGZF block fails its CRC"));
+        }
+        self.next_block += bsize as u64;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // the empty block that ends a BGZF file, or one written mid-file
+        while self.at == self.block.len() {
+            if !self.load_block()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.block.len() - self.at);
+        buf[..n].copy_from_slice(&self.block[self.at..self.at + n]);
+        self.at += n;
+        Ok(n)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("BGZF: {msg}"))
+}
+
+/// Write a `.gzi` as `samtools faidx`/`bgzip -i` do.
+pub fn write_gzi(path: &Path, index: &[(u64, u64)]) -> Result<()> {
+    let mut bytes = Vec::with_capacity(8 + 16 * index.len());
+    bytes.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    for (c, u) in index {
+        bytes.extend_from_slice(&c.to_le_bytes());
+        bytes.extend_from_slice(&u.to_le_bytes());
+    }
+    fs_err::write(path, bytes)?;
+    Ok(())
+}
+
+/// Read a `.gzi`.
+pub fn read_gzi(path: &Path) -> Result<Vec<(u64, u64)>> {
+    let bytes = fs_err::read(path)?;
+    let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+    if bytes.len() < 8 {
+        bail!("{}: not a .gzi index (too short)", path.display());
+    }
+    let n = u64_at(0) as usize;
+    if bytes.len() != 8 + 16 * n {
+        bail!(
+            "{}: not a .gzi index ({n} entries, {} bytes)",
+            path.display(),
+            bytes.len()
+        );
+    }
+    Ok((0..n)
+        .map(|i| (u64_at(8 + 16 * i), u64_at(16 + 16 * i)))
+        .collect())
+}
+
+/// `len` uncompressed bytes of the BGZF file `path` from uncompressed
+/// `offset` on (fewer at its end), decompressing from the block holding
+/// `offset`, as `index` (its `.gzi`) places it.
+pub fn read_at(path: &Path, index: &[(u64, u64)], offset: u64, len: usize) -> Result<Vec<u8>> {
+    let (block_c, block_u) = index
+        .iter()
+        .take_while(|(_, u)| *u <= offset)
+        .last()
+        .copied()
+        .unwrap_or((0, 0));
+    let ctx = || format!("read {} at {offset}", path.display());
+    let mut gz = BgzfReader::new(BufReader::new(fs_err::File::open(path)?));
+    gz.seek(block_c << 16).with_context(ctx)?;
+    io::copy(&mut (&mut gz).take(offset - block_u), &mut io::sink()).with_context(ctx)?;
+    let mut out = Vec::with_capacity(len);
+    gz.take(len as u64)
+        .read_to_end(&mut out)
+        .with_context(ctx)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn blocks_read_as_one_gzip_stream_and_from_any_offset() {
+        let td = TempDir::new().unwrap();
+        let p = td.path().join("x.gz");
+        // three full blocks and a partial one, not very compressible
+        let data: Vec<u8> = (0..3 * BLOCK_DATA + 1000)
+            .map(|i| b"ACGT"[(i * 7 + i / 13) % 4])
+            .collect();
+        let mut w = BgzfWriter::new(fs_err::File::create(&p).unwrap());
+        for chunk in data.chunks(10_000) {
+            w.write_all(chunk).unwrap();
+        }
+        let index = w.finish().unwrap();
+        assert_eq!(
+            index.iter().map(|&(_, u)| u).collect::<Vec<_>>(),
+            [1, 2, 3].map(|i| (i * BLOCK_DATA) as u64)
+        );
+
+        let bytes = fs_err::read(&p).unwrap();
+        assert!(is_bgzf(&bytes));
+        assert!(bytes.ends_with(&EOF_BLOCK));
+        let mut all = Vec::new();
+        flate2::read::MultiGzDecoder::new(&bytes[..])
+            .read_to_end(&mut all)
+            .unwrap();
+        assert_eq!(all, data);
+
+        let gzi = td.path().join("x.gz.gzi");
+        write_gzi(&gzi, &index).unwrap();
+        let index = read_gzi(&gzi).unwrap();
+        for (offset, len) in [(0, 10), (BLOCK_DATA - 5, 10), (2 * BLOCK_DATA + 17, 70_000)] {
+            let got = read_at(&p, &index, offset as u64, len).unwrap();
+            let end = (offset + len).min(data.len());
+            assert_eq!(got, &data[offset..end], "{offset}");
+        }
+
+        // a damaged block fails its CRC rather than giving other bases
+        let mut bad = bytes.clone();
+        let at = index[0].0 as usize + 100;
+        bad[at] ^= 0xff;
+        fs_err::write(&p, &bad).unwrap();
+        assert!(read_at(&p, &index, BLOCK_DATA as u64 + 5, 10).is_err());
+        assert!(read_at(&p, &index, 5, 10).is_ok());
+
+        // plain gzip is not BGZF
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b">c\nACGT\n").unwrap();
+        assert!(!is_bgzf(&gz.finish().unwrap()));
+        fs_err::write(&gzi, [1u8, 0, 0]).unwrap();
+        assert!(read_gzi(&gzi).is_err());
+    }
+}
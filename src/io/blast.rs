@@ -0,0 +1,139 @@
+//! BLAST tabular alignments (`-outfmt 6`) in place of minimap2 PAFs.
+//!
+//! Either the default twelve columns (qseqid sseqid pident length mismatch
+//! gapopen qstart qend sstart send evalue bitscore) or the ten of
+//! `-outfmt "6 qseqid sseqid pident length qstart qend sstart send evalue
+//! bitscore"`. Coordinates are 1-based and inclusive; a hit on the minus
+//! strand of the subject has sstart > send. Records come out as `PafRecord`s
+//! with 0-based half-open coordinates, start < end and the strand kept, and
+//! identity pident/100.
+
+use crate::io::paf::{IdentityKind, PafRecord};
+
+/// Columns of the default `-outfmt 6`…
+const DEFAULT_COLUMNS: usize = 12;
+/// …and of the ten-column custom format without mismatch and gapopen.
+const SHORT_COLUMNS: usize = 10;
+
+/// MAPQ of a BLAST record; BLAST has none, and 255 is PAF's "missing".
+const NO_MAPQ: u8 = 255;
+
+/// 1-based inclusive `a`..`b`, either way round, as 0-based half-open and
+/// whether it ran backwards.
+fn interval(a: u32, b: u32) -> Result<(u32, u32, bool), String> {
+    if a == 0 || b == 0 {
+        return Err("coordinates are 1-based, got 0".into());
+    }
+    Ok((a.min(b) - 1, a.max(b), a > b))
+}
+
+/// One outfmt 6 line as a `PafRecord`. Comment lines (`# ...`, as
+/// `-outfmt 7` writes) are `Ok(None)`.
+pub fn parse_line(line: &str) -> Result<Option<PafRecord>, String> {
+    if line.starts_with('#') {
+        return Ok(None);
+    }
+    let cols: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+    let coords = match cols.len() {
+        DEFAULT_COLUMNS => 6,
+        SHORT_COLUMNS => 4,
+        n => {
+            return Err(format!(
+                "expected {DEFAULT_COLUMNS} (or {SHORT_COLUMNS}) tab-separated columns, got {n}"
+            ))
+        }
+    };
+    let num = |i: usize, what: &str| {
+        cols[i]
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("{what} is not a number: {:?}", cols[i]))
+    };
+    let pident: f32 = cols[2]
+        .trim()
+        .parse()
+        .ok()
+        .filter(|p| (0.0..=100.0).contains(p))
+        .ok_or_else(|| format!("pident is not a percentage: {:?}", cols[2]))?;
+    let alnlen = num(3, "length")?;
+    let (qstart, qend, q_rev) = interval(num(coords, "qstart")?, num(coords + 1, "qend")?)?;
+    let (tstart, tend, t_rev) = interval(num(coords + 2, "sstart")?, num(coords + 3, "send")?)?;
+    if cols[0].is_empty() || cols[1].is_empty() {
+        return Err("empty qseqid or sseqid".into());
+    }
+    let identity = pident / 100.0;
+    Ok(Some(PafRecord {
+        qname: cols[0].to_string(),
+        qstart,
+        qend,
+        tname: cols[1].to_string(),
+        tstart,
+        tend,
+        matches: (identity * alnlen as f32).round() as u32,
+        alnlen,
+        mapq: NO_MAPQ,
+        identity,
+        identity_kind: IdentityKind::Blast,
+        blast_identity: identity,
+        gap_compressed_identity: None,
+        strand: if q_rev != t_rev { '-' } else { '+' },
+        tp: None,
+        parent: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> PafRecord {
+        parse_line(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn plus_and_minus_strand_hits() {
+        let r = parse("chrM\tchr1\t98.50\t200\t3\t0\t101\t300\t5001\t5200\t1e-90\t350\n");
+        assert_eq!(
+            (r.qname.as_str(), r.qstart, r.qend, r.tname.as_str()),
+            ("chrM", 100, 300, "chr1")
+        );
+        assert_eq!((r.tstart, r.tend, r.strand), (5000, 5200, '+'));
+        assert_eq!((r.alnlen, r.matches, r.mapq, r.tp), (200, 197, 255, None));
+        assert!((r.identity - 0.985).abs() < 1e-6);
+
+        // sstart > send: the subject runs backwards
+        let r = parse("chrM\tchr1\t100.00\t200\t0\t0\t101\t300\t5200\t5001\t1e-90\t350");
+        assert_eq!((r.tstart, r.tend, r.strand), (5000, 5200, '-'));
+        assert_eq!((r.qstart, r.qend), (100, 300));
+        // a reversed query (tblastx and friends) flips the strand too
+        let r = parse("chrM\tchr1\t100.00\t200\t0\t0\t300\t101\t5200\t5001\t1e-90\t350");
+        assert_eq!((r.qstart, r.qend, r.strand), (100, 300, '+'));
+        // a single base
+        let r = parse("chrM\tchr1\t100.00\t1\t0\t0\t7\t7\t9\t9\t1\t2");
+        assert_eq!((r.qstart, r.qend, r.tstart, r.tend), (6, 7, 8, 9));
+    }
+
+    #[test]
+    fn ten_column_format() {
+        let r = parse("chrM\tchr1\t90.0\t1000\t1\t1000\t20000\t19001\t0.0\t1500");
+        assert_eq!((r.qstart, r.qend), (0, 1000));
+        assert_eq!((r.tstart, r.tend, r.strand), (19000, 20000, '-'));
+        assert_eq!(r.matches, 900);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert!(parse_line("# BLASTN 2.15.0+").unwrap().is_none());
+        for bad in [
+            "",
+            "chrM\tchr1\t98.5",
+            "chrM\tchr1\tnan\t200\t3\t0\t101\t300\t5001\t5200\t1e-90\t350",
+            "chrM\tchr1\t101\t200\t3\t0\t101\t300\t5001\t5200\t1e-90\t350",
+            "chrM\tchr1\t98.5\t200\t3\t0\t0\t300\t5001\t5200\t1e-90\t350",
+            "chrM\tchr1\t98.5\t200\t3\t0\t101\t300\t-5\t5200\t1e-90\t350",
+            "\tchr1\t98.5\t200\t3\t0\t101\t300\t5001\t5200\t1e-90\t350",
+        ] {
+            assert!(parse_line(bad).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -1,7 +1,109 @@
+//! FASTA reading and writing. Every reader takes plain, gzip or bgzip
+//! input alike (needletail detects it from the magic bytes), and minimap2 is
+//! handed the path as is. Only `subsequence` seeks, through a `.fai` (and
+//! for bgzip a `.gzi`) when the FASTA has one; without, it reads the FASTA
+//! through.
+
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use needletail::parse_fastx_file;
 use std::collections::HashMap;
-use std::path::Path;
+use std::ffi::OsString;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::io::bgzf::{self, BgzfWriter};
+use crate::util::warnings;
+
+/// Compression of a FASTA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FastaCompression {
+    #[default]
+    None,
+    /// One gzip stream; read through, never seeked.
+    Gzip,
+    /// Blocked gzip (`bgzip`), which a `.gzi` index makes seekable.
+    Bgzip,
+}
+
+impl FastaCompression {
+    /// The compression to write `path` with: bgzip for `.gz`/`.bgz`, so the
+    /// output can be indexed with `samtools faidx`, else none.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("gz" | "bgz") => FastaCompression::Bgzip,
+            _ => FastaCompression::None,
+        }
+    }
+
+    /// The compression of an existing file, from its first bytes.
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut head = Vec::with_capacity(18);
+        fs_err::File::open(path)?
+            .take(18)
+            .read_to_end(&mut head)
+            .with_context(|| format!("read {}", path.display()))?;
+        Ok(if bgzf::is_bgzf(&head) {
+            FastaCompression::Bgzip
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            FastaCompression::Gzip
+        } else {
+            FastaCompression::None
+        })
+    }
+}
+
+/// A FASTA being written, compressed or not; `finish` ends the stream.
+pub enum FastaWriter {
+    Plain(BufWriter<fs_err::File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<fs_err::File>>),
+    Bgzip(BgzfWriter<BufWriter<fs_err::File>>),
+}
+
+impl FastaWriter {
+    pub fn create(path: &Path, compression: FastaCompression) -> Result<Self> {
+        let f = BufWriter::new(fs_err::File::create(path)?);
+        Ok(match compression {
+            FastaCompression::None => FastaWriter::Plain(f),
+            FastaCompression::Gzip => FastaWriter::Gzip(flate2::write::GzEncoder::new(
+                f,
+                flate2::Compression::default(),
+            )),
+            FastaCompression::Bgzip => FastaWriter::Bgzip(BgzfWriter::new(f)),
+        })
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            FastaWriter::Plain(mut w) => w.flush()?,
+            FastaWriter::Gzip(w) => w.finish()?.flush()?,
+            FastaWriter::Bgzip(w) => {
+                w.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for FastaWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            FastaWriter::Plain(w) => w.write(buf),
+            FastaWriter::Gzip(w) => w.write(buf),
+            FastaWriter::Bgzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            FastaWriter::Plain(w) => w.flush(),
+            FastaWriter::Gzip(w) => w.flush(),
+            FastaWriter::Bgzip(w) => w.flush(),
+        }
+    }
+}
 
 /// Checks file exists, is readable, and looks like FASTA by reading first record.
 pub fn validate_fasta(p: &Path) -> Result<()> {
@@ -32,12 +134,596 @@ pub fn contig_lengths(p: &Path) -> Result<HashMap<String, u64>> {
     Ok(m)
 }
 
+/// `[start, end)` of `contig`, read from the FASTA: through its `.fai` (and
+/// `.gzi`, for bgzip) when it has them, else by reading it through.
+pub fn subsequence(p: &Path, contig: &str, start: u32, end: u32) -> Result<Vec<u8>> {
+    if let Some(fai) = FaiEntry::find(p, contig)? {
+        return fai.subsequence(p, contig, start, end);
+    }
+    let mut rdr = parse_fastx_file(p).with_context(|| format!("open fasta {}", p.display()))?;
+    while let Some(rec) = rdr
+        .next()
+        .transpose()
+        .with_context(|| format!("read fasta {}", p.display()))?
+    {
+        if rec.id() != contig.as_bytes() {
+            continue;
+        }
+        let seq = rec.seq();
+        return seq
+            .get(start as usize..end as usize)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{contig}:{start}-{end} runs past the end of {contig} ({} bp) in {}",
+                    seq.len(),
+                    p.display()
+                )
+            });
+    }
+    Err(anyhow::anyhow!("no contig {contig} in {}", p.display()))
+}
+
+/// `path` with `ext` appended, as samtools names indexes: `x.fa.gz.fai`.
+fn index_path(path: &Path, ext: &str) -> PathBuf {
+    let mut s = OsString::from(path.as_os_str());
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
+}
+
+/// One line of a `.fai` (`samtools faidx`). For bgzip, `offset` counts
+/// uncompressed bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FaiEntry {
+    len: u64,
+    offset: u64,
+    line_bases: u64,
+    line_bytes: u64,
+    /// For bgzip, the `.gzi` entries.
+    gzi: Option<Vec<(u64, u64)>>,
+}
+
+impl FaiEntry {
+    /// `contig`'s entry in the `.fai` next to `fa`; `None` without a usable
+    /// index, and for gzip, which cannot be seeked.
+    fn find(fa: &Path, contig: &str) -> Result<Option<Self>> {
+        let fai = index_path(fa, "fai");
+        let compression = FastaCompression::detect(fa)?;
+        let read_through = |why: &str| {
+            if compression != FastaCompression::None {
+                log::info!("{} {why}; decompressing it through", fa.display());
+            }
+            Ok(None)
+        };
+        if !fai.exists() {
+            return read_through("has no .fai index (samtools faidx makes one)");
+        }
+        let modified = |p: &Path| fs_err::metadata(p).ok().and_then(|m| m.modified().ok());
+        if modified(&fai) < modified(fa) {
+            log::info!(
+                "{} is older than {}; reading the FASTA through instead",
+                fai.display(),
+                fa.display()
+            );
+            return Ok(None);
+        }
+        let gzi = match compression {
+            FastaCompression::None => None,
+            FastaCompression::Bgzip if index_path(fa, "gzi").exists() => {
+                Some(bgzf::read_gzi(&index_path(fa, "gzi"))?)
+            }
+            FastaCompression::Bgzip => return read_through("has no .gzi index"),
+            FastaCompression::Gzip => {
+                return read_through("is gzip, not bgzip, so cannot be seeked")
+            }
+        };
+        let txt = fs_err::read_to_string(&fai)?;
+        for (i, line) in txt.lines().enumerate() {
+            let f: Vec<&str> = line.split('\t').collect();
+            if f.first() != Some(&contig) {
+                continue;
+            }
+            let num = |j: usize| f.get(j).and_then(|v| v.parse::<u64>().ok());
+            let (Some(len), Some(offset), Some(line_bases), Some(line_bytes)) =
+                (num(1), num(2), num(3), num(4))
+            else {
+                anyhow::bail!("{}:{}: not a .fai line", fai.display(), i + 1);
+            };
+            if line_bases == 0 || line_bytes < line_bases {
+                anyhow::bail!("{}:{}: not a .fai line", fai.display(), i + 1);
+            }
+            return Ok(Some(Self {
+                len,
+                offset,
+                line_bases,
+                line_bytes,
+                gzi,
+            }));
+        }
+        Err(anyhow::anyhow!("no contig {contig} in {}", fa.display()))
+    }
+
+    /// File offset of base `pos`.
+    fn byte(&self, pos: u64) -> u64 {
+        self.offset + pos / self.line_bases * self.line_bytes + pos % self.line_bases
+    }
+
+    fn subsequence(&self, fa: &Path, contig: &str, start: u32, end: u32) -> Result<Vec<u8>> {
+        let (start, end) = (u64::from(start), u64::from(end));
+        if end > self.len || start > end {
+            anyhow::bail!(
+                "{contig}:{start}-{end} runs past the end of {contig} ({} bp) in {}",
+                self.len,
+                fa.display()
+            );
+        }
+        if start == end {
+            return Ok(Vec::new());
+        }
+        let (from, to) = (self.byte(start), self.byte(end - 1) + 1);
+        let raw = match &self.gzi {
+            Some(gzi) => bgzf::read_at(fa, gzi, from, (to - from) as usize)?,
+            None => {
+                let mut f = fs_err::File::open(fa)?;
+                f.seek(SeekFrom::Start(from))?;
+                let mut raw = Vec::with_capacity((to - from) as usize);
+                f.take(to - from).read_to_end(&mut raw)?;
+                raw
+            }
+        };
+        let seq: Vec<u8> = raw
+            .into_iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        if seq.len() as u64 != end - start {
+            anyhow::bail!(
+                "{} does not match its .fai (re-run samtools faidx)",
+                fa.display()
+            );
+        }
+        Ok(seq)
+    }
+}
+
+/// Write `(name, sequence)` records, 80 bases to a line; bgzipped for a
+/// `.gz` path (`FastaCompression::for_path`).
+pub fn write_fasta(path: &Path, contigs: &[(String, Vec<u8>)]) -> Result<()> {
+    let mut w = FastaWriter::create(path, FastaCompression::for_path(path))?;
+    for (name, seq) in contigs {
+        write_fasta_record(&mut w, name, seq)?;
+    }
+    w.finish()
+}
+
+/// One record as `write_fasta` writes it.
+pub fn write_fasta_record(w: &mut impl std::io::Write, name: &str, seq: &[u8]) -> Result<()> {
+    writeln!(w, ">{name}")?;
+    for line in seq.chunks(80) {
+        w.write_all(line)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Size and modification time, to notice a FASTA changing under a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(p: &Path) -> Result<Self> {
+        let md = fs_err::metadata(p)?;
+        Ok(Self {
+            len: md.len(),
+            modified: md.modified().ok(),
+        })
+    }
+}
+
+type Lengths = Arc<HashMap<String, u64>>;
+
+/// `contig_lengths` memoized for one run, so each FASTA is scanned at most once
+/// (validation, summary totals and density windows all need the lengths).
+///
+/// Entries are keyed by path and re-checked against the file's size and mtime on
+/// every lookup; a file that changed mid-run is re-read, with a warning.
+#[derive(Debug, Default)]
+pub struct LengthCache {
+    entries: Mutex<HashMap<PathBuf, (FileStamp, Lengths)>>,
+    scans: Mutex<usize>,
+}
+
+impl LengthCache {
+    pub fn lengths(&self, p: &Path) -> Result<Lengths> {
+        let key = fs_err::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let stamp = FileStamp::of(p)?;
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((cached, lens)) = entries.get(&key) {
+            if *cached == stamp {
+                return Ok(Arc::clone(lens));
+            }
+            warnings::warn(
+                warnings::FASTA_CHANGED,
+                format!(
+                    "{} changed during the run (size/mtime differ); re-reading it",
+                    p.display()
+                ),
+            );
+        }
+        let lens = Arc::new(contig_lengths(p)?);
+        *self.scans.lock().unwrap() += 1;
+        entries.insert(key, (stamp, Arc::clone(&lens)));
+        Ok(lens)
+    }
+
+    /// Take `lens` as `p`'s contig lengths (e.g. from an index), without
+    /// reading it.
+    pub fn seed(&self, p: &Path, lens: HashMap<String, u64>) -> Result<()> {
+        let key = fs_err::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let stamp = FileStamp::of(p)?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (stamp, Arc::new(lens)));
+        Ok(())
+    }
+
+    /// `fasta_report` through the cache.
+    pub fn report(&self, p: &Path) -> Result<FastaReport> {
+        Ok(FastaReport::from_lengths(p, &*self.lengths(p)?))
+    }
+
+    /// Sum of contig lengths.
+    pub fn total_bp(&self, p: &Path) -> Result<u64> {
+        Ok(self.lengths(p)?.values().sum())
+    }
+
+    /// How many times a FASTA was actually read.
+    pub fn scans(&self) -> usize {
+        *self.scans.lock().unwrap()
+    }
+}
+
+/// Contig count and size overview of one FASTA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaReport {
+    pub path: String,
+    pub n_contigs: usize,
+    pub total_bp: u64,
+    pub longest_bp: u64,
+}
+
+impl FastaReport {
+    pub fn from_lengths(path: &Path, lens: &HashMap<String, u64>) -> Self {
+        Self {
+            path: path.display().to_string(),
+            n_contigs: lens.len(),
+            total_bp: lens.values().sum(),
+            longest_bp: lens.values().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+/// Read a FASTA once and summarize it.
+pub fn fasta_report(p: &Path) -> Result<FastaReport> {
+    Ok(FastaReport::from_lengths(p, &contig_lengths(p)?))
+}
+
+/// Finds runs of matching bases (N-runs, soft-masked runs) in a sequence fed
+/// to it in chunks of any size, so a run that crosses a chunk boundary is
+/// still reported once.
+#[derive(Debug)]
+pub struct RunScanner {
+    in_run: fn(u8) -> bool,
+    pos: u32,
+    open: Option<u32>,
+    runs: Vec<(u32, u32)>,
+}
+
+impl RunScanner {
+    fn new(in_run: fn(u8) -> bool) -> Self {
+        Self {
+            in_run,
+            pos: 0,
+            open: None,
+            runs: Vec::new(),
+        }
+    }
+
+    /// `N`/`n`: assembly gaps.
+    pub fn n_runs() -> Self {
+        Self::new(|b| b == b'N' || b == b'n')
+    }
+
+    /// Lowercase bases: soft-masked repeats (masked gaps, `n`, included).
+    pub fn soft_masked() -> Self {
+        Self::new(|b| b.is_ascii_lowercase())
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            match ((self.in_run)(b), self.open) {
+                (true, None) => self.open = Some(self.pos),
+                (false, Some(start)) => {
+                    self.runs.push((start, self.pos));
+                    self.open = None;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// 0-based half-open runs, in order.
+    pub fn finish(mut self) -> Vec<(u32, u32)> {
+        if let Some(start) = self.open.take() {
+            self.runs.push((start, self.pos));
+        }
+        self.runs
+    }
+}
+
+/// N bases inside one interval, and its soft-masked (lowercase) bases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GapStats {
+    pub n_bases: u32,
+    pub longest_run: u32,
+    pub masked_bases: u32,
+}
+
+/// Bases of `runs` (sorted, disjoint) within `[start, end)`, and the longest
+/// clipped run.
+fn clipped_runs(runs: &[(u32, u32)], start: u32, end: u32) -> (u32, u32) {
+    let first = runs.partition_point(|&(_, e)| e <= start);
+    let (mut bases, mut longest) = (0, 0);
+    for &(s, e) in runs[first..].iter().take_while(|&&(s, _)| s < end) {
+        let len = e.min(end) - s.max(start);
+        bases += len;
+        longest = longest.max(len);
+    }
+    (bases, longest)
+}
+
+/// N-runs (assembly gaps) and soft-masked runs of every contig in a FASTA,
+/// from a single pass. The sequence is read as is, so lowercase survives.
+///
+/// Only the runs are kept, so per-locus statistics cost a binary search rather
+/// than re-reading sequence, however long the locus.
+#[derive(Debug, Default)]
+pub struct GapIndex {
+    runs: HashMap<String, Vec<(u32, u32)>>,
+    masked: HashMap<String, Vec<(u32, u32)>>,
+}
+
+/// Sequence is handed to the scanner in pieces of this size.
+const GAP_SCAN_CHUNK: usize = 1 << 20;
+
+impl GapIndex {
+    pub fn scan(p: &Path) -> Result<Self> {
+        let (mut runs, mut masked) = (HashMap::new(), HashMap::new());
+        let mut rdr = parse_fastx_file(p).with_context(|| format!("open fasta {}", p.display()))?;
+        while let Some(rec) = rdr
+            .next()
+            .transpose()
+            .with_context(|| format!("read fasta {}", p.display()))?
+        {
+            let mut n_runs = RunScanner::n_runs();
+            let mut soft = RunScanner::soft_masked();
+            for chunk in rec.seq().chunks(GAP_SCAN_CHUNK) {
+                n_runs.feed(chunk);
+                soft.feed(chunk);
+            }
+            let id = String::from_utf8_lossy(rec.id()).to_string();
+            for (found, into) in [(n_runs.finish(), &mut runs), (soft.finish(), &mut masked)] {
+                if !found.is_empty() {
+                    into.insert(id.clone(), found);
+                }
+            }
+        }
+        Ok(Self { runs, masked })
+    }
+
+    pub fn from_runs(runs: HashMap<String, Vec<(u32, u32)>>) -> Self {
+        Self {
+            runs,
+            masked: HashMap::new(),
+        }
+    }
+
+    /// With these soft-masked runs.
+    pub fn with_masked(mut self, masked: HashMap<String, Vec<(u32, u32)>>) -> Self {
+        self.masked = masked;
+        self
+    }
+
+    /// N bases, the longest (clipped) N-run and the soft-masked bases within
+    /// `[start, end)` of `contig`.
+    pub fn stats(&self, contig: &str, start: u32, end: u32) -> GapStats {
+        let clip = |runs: &HashMap<String, Vec<(u32, u32)>>| {
+            runs.get(contig)
+                .map_or((0, 0), |r| clipped_runs(r, start, end))
+        };
+        let (n_bases, longest_run) = clip(&self.runs);
+        GapStats {
+            n_bases,
+            longest_run,
+            masked_bases: clip(&self.masked).0,
+        }
+    }
+
+    /// Total N bases within merged (non-overlapping) intervals per contig.
+    pub fn n_bases_in(&self, intervals: &HashMap<String, Vec<(u32, u32)>>) -> u64 {
+        self.sum_in(intervals, |g| g.n_bases)
+    }
+
+    /// Total soft-masked bases within merged intervals per contig.
+    pub fn masked_bases_in(&self, intervals: &HashMap<String, Vec<(u32, u32)>>) -> u64 {
+        self.sum_in(intervals, |g| g.masked_bases)
+    }
+
+    fn sum_in(
+        &self,
+        intervals: &HashMap<String, Vec<(u32, u32)>>,
+        field: impl Fn(GapStats) -> u32,
+    ) -> u64 {
+        intervals
+            .iter()
+            .flat_map(|(c, v)| v.iter().map(move |&(s, e)| (c, s, e)))
+            .map(|(c, s, e)| field(self.stats(c, s, e)) as u64)
+            .sum()
+    }
+}
+
+/// Mito larger than this multiple of the nuclear assembly is treated as a swap.
+const SWAP_SIZE_RATIO: u64 = 2;
+/// "Hundreds of mito contigs vs one small nuclear contig" thresholds.
+const SWAP_MANY_CONTIGS: usize = 100;
+const SWAP_SMALL_NUCLEAR_BP: u64 = 1_000_000;
+
+/// Preflight heuristic for `--mito`/`--nuclear` given the wrong way round.
+/// Errors with both files' contig counts and sizes; skip with `--no-input-sanity`.
+pub fn check_not_swapped(mito: &FastaReport, nuclear: &FastaReport) -> Result<()> {
+    let bigger = mito.total_bp > SWAP_SIZE_RATIO * nuclear.total_bp;
+    let shape = mito.n_contigs >= SWAP_MANY_CONTIGS
+        && nuclear.n_contigs == 1
+        && nuclear.total_bp < SWAP_SMALL_NUCLEAR_BP;
+    if !(bigger || shape) {
+        return Ok(());
+    }
+    let why = if bigger {
+        format!("the --mito assembly is more than {SWAP_SIZE_RATIO}× larger than the --nuclear one")
+    } else {
+        "the --mito assembly has hundreds of contigs while --nuclear is a single small contig"
+            .to_string()
+    };
+    Err(anyhow::anyhow!(
+        "--mito and --nuclear look swapped: {why}.\n  --mito    {}: {} contigs, {} bp\n  --nuclear {}: {} contigs, {} bp\nSwap the arguments, or pass --no-input-sanity if this is intended.",
+        mito.path,
+        mito.n_contigs,
+        mito.total_bp,
+        nuclear.path,
+        nuclear.n_contigs,
+        nuclear.total_bp
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn subsequence_and_write() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let p = dir.path().join("a.fa");
+        let long: Vec<u8> = b"ACGT".repeat(30);
+        write_fasta(
+            &p,
+            &[("c1".into(), b"AAAAcgtN".to_vec()), ("c2".into(), long)],
+        )
+        .unwrap();
+        let txt = fs_err::read_to_string(&p).unwrap();
+        assert_eq!(
+            txt.lines().map(str::len).collect::<Vec<_>>(),
+            [3, 8, 3, 80, 40]
+        );
+
+        assert_eq!(subsequence(&p, "c1", 3, 7).unwrap(), b"Acgt");
+        assert_eq!(subsequence(&p, "c2", 118, 120).unwrap(), b"GT");
+        let e = subsequence(&p, "c1", 4, 9).unwrap_err().to_string();
+        assert!(e.contains("past the end of c1 (8 bp)"), "{e}");
+        assert!(subsequence(&p, "c3", 0, 1).is_err());
+    }
+
+    /// The `.fai` `samtools faidx` makes of a `write_fasta` file.
+    fn fai_of(contigs: &[(String, Vec<u8>)]) -> String {
+        let (mut fai, mut offset) = (String::new(), 0);
+        for (name, seq) in contigs {
+            offset += name.len() + 2;
+            fai.push_str(&format!("{name}\t{}\t{offset}\t80\t81\n", seq.len()));
+            offset += seq.len() + seq.len().div_ceil(80);
+        }
+        fai
+    }
+
+    #[test]
+    fn indexed_subsequence_plain_and_bgzip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let contigs: Vec<(String, Vec<u8>)> = (1..=3)
+            .map(|c| {
+                let seq = (0..70_000 * c).map(|i| b"ACGTacgtN"[(i * c) % 9]).collect();
+                (format!("chr{c}"), seq)
+            })
+            .collect();
+        let plain = dir.path().join("nuc.fa");
+        let bgz = dir.path().join("nuc.fa.gz");
+        write_fasta(&plain, &contigs).unwrap();
+        write_fasta(&bgz, &contigs).unwrap();
+        assert_eq!(
+            FastaCompression::detect(&plain).unwrap(),
+            FastaCompression::None
+        );
+        assert_eq!(
+            FastaCompression::detect(&bgz).unwrap(),
+            FastaCompression::Bgzip
+        );
+        assert_eq!(
+            contig_lengths(&bgz).unwrap(),
+            contig_lengths(&plain).unwrap()
+        );
+
+        let want = |c: usize, s: usize, e: usize| contigs[c - 1].1[s..e].to_vec();
+        let queries = [(1, 0, 10), (2, 65_270, 65_300), (3, 100_001, 209_999)];
+        let check = |fa: &Path| {
+            for (c, s, e) in queries {
+                let got = subsequence(fa, &format!("chr{c}"), s as u32, e as u32).unwrap();
+                assert_eq!(got, want(c, s, e), "{} chr{c}:{s}-{e}", fa.display());
+            }
+            let e = subsequence(fa, "chr1", 69_990, 70_001).unwrap_err();
+            assert!(
+                e.to_string().contains("past the end of chr1 (70000 bp)"),
+                "{e}"
+            );
+        };
+        // read through, without indexes
+        check(&plain);
+        check(&bgz);
+
+        // through the .fai, and for bgzip its .gzi
+        let fai = fai_of(&contigs);
+        fs_err::write(index_path(&plain, "fai"), &fai).unwrap();
+        fs_err::write(index_path(&bgz, "fai"), &fai).unwrap();
+        let mut w = BgzfWriter::new(Vec::new());
+        for (name, seq) in &contigs {
+            write_fasta_record(&mut w, name, seq).unwrap();
+        }
+        bgzf::write_gzi(&index_path(&bgz, "gzi"), &w.finish().unwrap()).unwrap();
+        for fa in [&plain, &bgz] {
+            assert!(FaiEntry::find(fa, "chr2").unwrap().is_some());
+            check(fa);
+        }
+        assert!(subsequence(&bgz, "chr4", 0, 1).is_err());
+
+        // plain gzip has no random access: read through
+        let gz = dir.path().join("gz.fa.gz");
+        let mut w = FastaWriter::create(&gz, FastaCompression::Gzip).unwrap();
+        for (name, seq) in &contigs {
+            write_fasta_record(&mut w, name, seq).unwrap();
+        }
+        w.finish().unwrap();
+        fs_err::write(index_path(&gz, "fai"), &fai).unwrap();
+        assert_eq!(
+            FastaCompression::detect(&gz).unwrap(),
+            FastaCompression::Gzip
+        );
+        assert!(FaiEntry::find(&gz, "chr2").unwrap().is_none());
+        check(&gz);
+    }
+
     #[test]
     fn lengths_ok() {
         let mut f = NamedTempFile::new().unwrap();
@@ -47,4 +733,177 @@ mod tests {
         assert_eq!(m.get("c2"), Some(&10));
         assert_eq!(m.get("c3"), Some(&1));
     }
+
+    fn report(n_contigs: usize, total_bp: u64) -> FastaReport {
+        FastaReport {
+            path: "x.fa".into(),
+            n_contigs,
+            total_bp,
+            longest_bp: total_bp,
+        }
+    }
+
+    #[test]
+    fn swap_detected() {
+        // Arabidopsis-like nuclear (135 Mb, 5 chr) passed as --mito
+        let e = check_not_swapped(&report(5, 135_000_000), &report(1, 367_808))
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("look swapped"), "{e}");
+        assert!(e.contains("5 contigs, 135000000 bp"), "{e}");
+        assert!(e.contains("--no-input-sanity"), "{e}");
+        // fragmented nuclear assembly, similar size, vs one small contig
+        assert!(check_not_swapped(&report(300, 500_000), &report(1, 400_000)).is_err());
+    }
+
+    #[test]
+    fn legitimate_small_genomes_pass() {
+        // fungal: 12 Mb nuclear in 16 chromosomes, 80 kb mito
+        assert!(check_not_swapped(&report(1, 80_000), &report(16, 12_000_000)).is_ok());
+        // large plant mito (11 Mb, multichromosomal) vs a big nuclear
+        assert!(check_not_swapped(&report(128, 11_000_000), &report(12, 700_000_000)).is_ok());
+    }
+
+    #[test]
+    fn length_cache_hit_miss_and_invalidation() {
+        let mut a = NamedTempFile::new().unwrap();
+        writeln!(a, ">c1\nAAAA\n>c2\nAA").unwrap();
+        let mut b = NamedTempFile::new().unwrap();
+        writeln!(b, ">m\nACGTACGT").unwrap();
+        let cache = LengthCache::default();
+
+        assert_eq!(cache.total_bp(a.path()).unwrap(), 6);
+        assert_eq!(cache.report(a.path()).unwrap().n_contigs, 2);
+        assert_eq!(cache.lengths(a.path()).unwrap()["c1"], 4);
+        assert_eq!(cache.scans(), 1, "hits don't rescan");
+
+        assert_eq!(cache.total_bp(b.path()).unwrap(), 8);
+        assert_eq!(cache.scans(), 2, "another file is a miss");
+
+        // rewritten mid-run: detected and re-read
+        writeln!(a, ">c3\nAAAAAAAAAA").unwrap();
+        a.flush().unwrap();
+        assert_eq!(cache.total_bp(a.path()).unwrap(), 16);
+        assert_eq!(cache.scans(), 3);
+        assert_eq!(cache.total_bp(a.path()).unwrap(), 16);
+        assert_eq!(cache.scans(), 3);
+
+        let missing = cache.lengths(Path::new("/nope/nope.fa")).unwrap_err();
+        assert!(missing.to_string().contains("nope.fa"), "{missing}");
+    }
+
+    #[test]
+    fn n_runs_found_across_chunk_boundaries() {
+        let seq = b"NNACGTnnnnACGTNNN";
+        let expected = vec![(0, 2), (6, 10), (14, 17)];
+        for chunk in 1..=seq.len() {
+            let mut s = RunScanner::n_runs();
+            for c in seq.chunks(chunk) {
+                s.feed(c);
+            }
+            assert_eq!(s.finish(), expected, "chunk size {chunk}");
+        }
+        assert!(RunScanner::n_runs().finish().is_empty());
+
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(f, ">a\nNNACGT\nnnnnACGTNNN\n>b\nACGT").unwrap();
+        let idx = GapIndex::scan(f.path()).unwrap();
+        assert_eq!(idx.runs["a"], expected);
+        assert!(!idx.runs.contains_key("b"));
+    }
+
+    #[test]
+    fn soft_masked_runs_from_mixed_case() {
+        // lowercase n is both a gap and masked
+        let seq = b"ACgtaCGTnnNNacgT";
+        let expected = vec![(2, 5), (8, 10), (12, 15)];
+        for chunk in 1..=seq.len() {
+            let mut s = RunScanner::soft_masked();
+            for c in seq.chunks(chunk) {
+                s.feed(c);
+            }
+            assert_eq!(s.finish(), expected, "chunk size {chunk}");
+        }
+
+        // the scan keeps the case of multi-line records
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(f, ">a\nACgta\nCGTnnNNacgT\n>b\nACGT\n>c\nacgt").unwrap();
+        let idx = GapIndex::scan(f.path()).unwrap();
+        assert_eq!(idx.masked["a"], expected);
+        assert_eq!(idx.runs["a"], [(8, 12)]);
+        assert!(!idx.masked.contains_key("b"));
+        assert_eq!(
+            idx.stats("a", 4, 13),
+            GapStats {
+                n_bases: 4,
+                longest_run: 4,
+                masked_bases: 1 + 2 + 1
+            }
+        );
+        assert_eq!(idx.stats("c", 0, 4).masked_bases, 4);
+        let merged = [
+            ("a".to_string(), vec![(0, 3), (9, 16)]),
+            ("c".to_string(), vec![(1, 3)]),
+        ]
+        .into();
+        assert_eq!(idx.masked_bases_in(&merged), 1 + 1 + 3 + 2);
+    }
+
+    #[test]
+    fn gap_stats_at_locus_start_middle_and_end() {
+        // 100 bp runs at 0..100, 450..550 and 900..1000
+        let idx = GapIndex::from_runs(
+            [("chr1".to_string(), vec![(0, 100), (450, 550), (900, 1000)])].into(),
+        );
+        let at = |s, e| idx.stats("chr1", s, e);
+        // locus starting inside a gap: clipped to its part of the run
+        assert_eq!(
+            at(60, 300),
+            GapStats {
+                n_bases: 40,
+                longest_run: 40,
+                masked_bases: 0
+            }
+        );
+        // gap wholly in the middle
+        assert_eq!(
+            at(300, 700),
+            GapStats {
+                n_bases: 100,
+                longest_run: 100,
+                masked_bases: 0
+            }
+        );
+        // locus ending inside a gap
+        assert_eq!(
+            at(700, 930),
+            GapStats {
+                n_bases: 30,
+                longest_run: 30,
+                masked_bases: 0
+            }
+        );
+        // all three, and bounds touching runs without overlapping them
+        assert_eq!(
+            at(0, 1000),
+            GapStats {
+                n_bases: 300,
+                longest_run: 100,
+                masked_bases: 0
+            }
+        );
+        assert_eq!(at(100, 450), GapStats::default());
+        assert_eq!(idx.stats("chr2", 0, 1000), GapStats::default());
+
+        let merged = [("chr1".to_string(), vec![(50, 500), (950, 2000)])].into();
+        assert_eq!(idx.n_bases_in(&merged), 50 + 50 + 50);
+    }
+
+    #[test]
+    fn report_from_file() {
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(f, ">a\nAAAA\n>b\nCCCCCCCC\n").unwrap();
+        let r = fasta_report(f.path()).unwrap();
+        assert_eq!((r.n_contigs, r.total_bp, r.longest_bp), (2, 12, 8));
+    }
 }
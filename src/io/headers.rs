@@ -0,0 +1,417 @@
+//! FASTA header naming for extracted locus sequences (`--rename-scheme`).
+//!
+//! Schemes:
+//!   * `full`  — `{pair_id}_{side} {contig}:{start}-{end} call={call} ident={aln_ident} …`
+//!   * `short` — `{pair_id}_{side}`
+//!   * `custom:TEMPLATE` — e.g. `custom:{pair_id}|{nuc_contig}:{nuc_start}-{nuc_end}|{call}`
+//!
+//! Template fields: `pair_id`, `side` (`nuc`/`mito`), `contig`, `start`, `end`
+//! (the extracted side), `nuc_contig`, `nuc_start`, `nuc_end`, `mito_contig`,
+//! `mito_start`, `mito_end`, `aln_len`, `aln_ident`, `strand` (`+`/`-`), `call`. `{{`/`}}` are literal braces.
+//!
+//! Every extraction path (`stats --fasta-out`, `mask --masked-fasta`) writes
+//! through `write_renamed_fasta`: `assign_names` rejects schemes producing
+//! duplicate IDs (the header up to the first whitespace), and `write_name_map`
+//! writes the name → metadata sidecar beside the FASTA.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::io::fasta;
+use crate::model::PairedLocus;
+
+/// Which genome a sequence was extracted from.
+pub use crate::model::orientation::Side;
+
+/// Everything a header may refer to for one extracted sequence.
+#[derive(Debug, Clone, Copy)]
+pub struct HeaderFields<'a> {
+    pub locus: &'a PairedLocus,
+    pub call: &'a str,
+    pub side: Side,
+}
+
+impl HeaderFields<'_> {
+    fn side_coords(&self) -> (&str, u32, u32) {
+        let p = self.locus;
+        match self.side {
+            Side::Nuclear => (&p.nuc_contig, p.nuc_start, p.nuc_end),
+            Side::Mito => (&p.mito_contig, p.mito_start, p.mito_end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    PairId,
+    Side,
+    Contig,
+    Start,
+    End,
+    NucContig,
+    NucStart,
+    NucEnd,
+    MitoContig,
+    MitoStart,
+    MitoEnd,
+    AlnLen,
+    AlnIdent,
+    Strand,
+    Call,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("pair_id", Field::PairId),
+    ("side", Field::Side),
+    ("contig", Field::Contig),
+    ("start", Field::Start),
+    ("end", Field::End),
+    ("nuc_contig", Field::NucContig),
+    ("nuc_start", Field::NucStart),
+    ("nuc_end", Field::NucEnd),
+    ("mito_contig", Field::MitoContig),
+    ("mito_start", Field::MitoStart),
+    ("mito_end", Field::MitoEnd),
+    ("aln_len", Field::AlnLen),
+    ("aln_ident", Field::AlnIdent),
+    ("strand", Field::Strand),
+    ("call", Field::Call),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Lit(String),
+    Field(Field),
+}
+
+/// A parsed header template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut lit = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    lit.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    lit.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(anyhow!("unclosed '{{' in template {s:?}")),
+                        }
+                    }
+                    let f = FIELDS
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, f)| *f)
+                        .ok_or_else(|| {
+                            let known: Vec<&str> = FIELDS.iter().map(|(n, _)| *n).collect();
+                            anyhow!(
+                                "unknown field {{{name}}} in template {s:?} (known: {})",
+                                known.join(", ")
+                            )
+                        })?;
+                    if !lit.is_empty() {
+                        parts.push(Part::Lit(std::mem::take(&mut lit)));
+                    }
+                    parts.push(Part::Field(f));
+                }
+                '}' => return Err(anyhow!("unmatched '}}' in template {s:?}")),
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() {
+            parts.push(Part::Lit(lit));
+        }
+        if parts.is_empty() {
+            return Err(anyhow!("empty header template"));
+        }
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, h: &HeaderFields) -> String {
+        let p = h.locus;
+        let (contig, start, end) = h.side_coords();
+        let mut out = String::new();
+        for part in &self.parts {
+            let _ = match part {
+                Part::Lit(s) => write!(out, "{s}"),
+                Part::Field(f) => match f {
+                    Field::PairId => write!(out, "{}", p.pair_id),
+                    Field::Side => write!(out, "{}", h.side.as_str()),
+                    Field::Contig => write!(out, "{contig}"),
+                    Field::Start => write!(out, "{start}"),
+                    Field::End => write!(out, "{end}"),
+                    Field::NucContig => write!(out, "{}", p.nuc_contig),
+                    Field::NucStart => write!(out, "{}", p.nuc_start),
+                    Field::NucEnd => write!(out, "{}", p.nuc_end),
+                    Field::MitoContig => write!(out, "{}", p.mito_contig),
+                    Field::MitoStart => write!(out, "{}", p.mito_start),
+                    Field::MitoEnd => write!(out, "{}", p.mito_end),
+                    Field::AlnLen => write!(out, "{}", p.aln_len),
+                    Field::AlnIdent => write!(out, "{:.4}", p.aln_ident),
+                    Field::Strand => write!(out, "{}", p.strand.as_char()),
+                    Field::Call => write!(out, "{}", h.call),
+                },
+            };
+        }
+        out
+    }
+}
+
+const FULL_TEMPLATE: &str = "{pair_id}_{side} {contig}:{start}-{end} call={call} ident={aln_ident} nuc={nuc_contig}:{nuc_start}-{nuc_end} mito={mito_contig}:{mito_start}-{mito_end}";
+const SHORT_TEMPLATE: &str = "{pair_id}_{side}";
+
+/// `--rename-scheme full|short|custom:TEMPLATE`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RenameScheme {
+    #[default]
+    Full,
+    Short,
+    Custom(Template),
+}
+
+impl FromStr for RenameScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "full" => Ok(RenameScheme::Full),
+            "short" => Ok(RenameScheme::Short),
+            _ => match s.strip_prefix("custom:") {
+                Some(t) => Ok(RenameScheme::Custom(Template::parse(t)?)),
+                None => Err(anyhow!(
+                    "unknown rename scheme {s:?}; use full, short or custom:TEMPLATE"
+                )),
+            },
+        }
+    }
+}
+
+impl RenameScheme {
+    fn template(&self) -> Template {
+        match self {
+            RenameScheme::Full => Template::parse(FULL_TEMPLATE).expect("valid built-in"),
+            RenameScheme::Short => Template::parse(SHORT_TEMPLATE).expect("valid built-in"),
+            RenameScheme::Custom(t) => t.clone(),
+        }
+    }
+}
+
+/// Render a header for every item, erroring if two share an ID (text before the
+/// first whitespace, which is what downstream tools key on).
+pub fn assign_names(scheme: &RenameScheme, items: &[HeaderFields]) -> Result<Vec<String>> {
+    let t = scheme.template();
+    let names: Vec<String> = items.iter().map(|h| t.render(h)).collect();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (i, n) in names.iter().enumerate() {
+        let id = n.split_whitespace().next().unwrap_or("");
+        if id.is_empty() {
+            return Err(anyhow!(
+                "rename scheme produced an empty name for {} ({})",
+                items[i].locus.pair_id,
+                items[i].side.as_str()
+            ));
+        }
+        if let Some(&j) = seen.get(id) {
+            return Err(anyhow!(
+                "rename scheme produces duplicate name {id:?} for {} and {}; add {{pair_id}} or {{side}} to the template",
+                items[j].locus.pair_id,
+                items[i].locus.pair_id
+            ));
+        }
+        seen.insert(id, i);
+    }
+    Ok(names)
+}
+
+/// The name map written beside `fasta`: `<fasta>.names.tsv`.
+pub fn name_map_path(fasta: &Path) -> PathBuf {
+    let mut p = fasta.as_os_str().to_owned();
+    p.push(".names.tsv");
+    PathBuf::from(p)
+}
+
+/// Name `items` by `scheme` and write each one's sequence on its side, cut
+/// from `fasta_in`, to `out` (compressed as its extension says), with the
+/// name map at `name_map_path(out)`.
+pub fn write_renamed_fasta(
+    scheme: &RenameScheme,
+    items: &[HeaderFields],
+    fasta_in: &Path,
+    out: &Path,
+) -> Result<()> {
+    let names = assign_names(scheme, items)?;
+    let mut records = Vec::with_capacity(items.len());
+    for (name, h) in names.iter().zip(items) {
+        let (contig, start, end) = h.side_coords();
+        records.push((
+            name.clone(),
+            fasta::subsequence(fasta_in, contig, start, end)?,
+        ));
+    }
+    fasta::write_fasta(out, &records)?;
+    write_name_map(&name_map_path(out), &names, items)
+}
+
+/// Sidecar TSV mapping each emitted name (ID) back to its full metadata.
+pub fn write_name_map(path: &Path, names: &[String], items: &[HeaderFields]) -> Result<()> {
+    fs_err::write(path, name_map_string(names, items))?;
+    Ok(())
+}
+
+pub fn name_map_string(names: &[String], items: &[HeaderFields]) -> String {
+    let mut s = String::from(
+        "name\tpair_id\tside\tcontig\tstart\tend\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\tcall\n",
+    );
+    for (n, h) in names.iter().zip(items) {
+        let p = h.locus;
+        let (contig, start, end) = h.side_coords();
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{contig}\t{start}\t{end}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}",
+            n.split_whitespace().next().unwrap_or(""),
+            p.pair_id,
+            h.side.as_str(),
+            p.nuc_contig,
+            p.nuc_start,
+            p.nuc_end,
+            p.mito_contig,
+            p.mito_start,
+            p.mito_end,
+            p.aln_len,
+            p.aln_ident,
+            h.call
+        );
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+
+    fn locus(id: &str, nuc_start: u32) -> PairedLocus {
+        PairedLocus {
+            pair_id: id.into(),
+            nuc_contig: "chr2".into(),
+            nuc_start,
+            nuc_end: nuc_start + 500,
+            mito_contig: "mt".into(),
+            mito_start: 100,
+            mito_end: 600,
+            aln_len: 500,
+            aln_ident: 0.97,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    fn fields(p: &PairedLocus, side: Side) -> HeaderFields<'_> {
+        HeaderFields {
+            locus: p,
+            call: "Likely_NUMT",
+            side,
+        }
+    }
+
+    #[test]
+    fn template_parsing_and_errors() {
+        assert!(Template::parse("{pair_id}|{call}").is_ok());
+        assert!(Template::parse("{{x}}").is_ok());
+        let e = Template::parse("{pair_id}|{bogus}")
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("unknown field {bogus}"), "{e}");
+        assert!(Template::parse("{pair_id").is_err());
+        assert!(Template::parse("a}b").is_err());
+        assert!(Template::parse("").is_err());
+    }
+
+    #[test]
+    fn scheme_from_str() {
+        assert_eq!("full".parse::<RenameScheme>().unwrap(), RenameScheme::Full);
+        assert_eq!(
+            "short".parse::<RenameScheme>().unwrap(),
+            RenameScheme::Short
+        );
+        assert!(matches!(
+            "custom:{pair_id}".parse::<RenameScheme>().unwrap(),
+            RenameScheme::Custom(_)
+        ));
+        assert!("medium".parse::<RenameScheme>().is_err());
+    }
+
+    #[test]
+    fn substitution() {
+        let p = locus("P000007", 1000);
+        let s: RenameScheme = "custom:{pair_id}|{nuc_contig}:{nuc_start}-{nuc_end}|{call}|{{x}}"
+            .parse()
+            .unwrap();
+        let n = assign_names(&s, &[fields(&p, Side::Nuclear)]).unwrap();
+        assert_eq!(n[0], "P000007|chr2:1000-1500|Likely_NUMT|{x}");
+
+        let n = assign_names(&RenameScheme::Short, &[fields(&p, Side::Mito)]).unwrap();
+        assert_eq!(n[0], "P000007_mito");
+
+        let n = assign_names(&RenameScheme::Full, &[fields(&p, Side::Mito)]).unwrap();
+        assert_eq!(
+            n[0],
+            "P000007_mito mt:100-600 call=Likely_NUMT ident=0.9700 nuc=chr2:1000-1500 mito=mt:100-600"
+        );
+    }
+
+    #[test]
+    fn duplicates_rejected() {
+        let (a, b) = (locus("P000001", 0), locus("P000002", 0));
+        let items = [fields(&a, Side::Nuclear), fields(&b, Side::Nuclear)];
+        // same nuclear coords → same name
+        let s: RenameScheme = "custom:{nuc_contig}:{nuc_start}-{nuc_end}".parse().unwrap();
+        let e = assign_names(&s, &items).unwrap_err().to_string();
+        assert!(e.contains("duplicate name \"chr2:0-500\""), "{e}");
+        assert!(assign_names(&RenameScheme::Short, &items).is_ok());
+
+        // only the ID (before whitespace) counts
+        let s: RenameScheme = "custom:{call} {pair_id}".parse().unwrap();
+        assert!(assign_names(&s, &items).is_err());
+    }
+
+    #[test]
+    fn sidecar_maps_names_back() {
+        let p = locus("P000003", 10);
+        let items = [fields(&p, Side::Nuclear)];
+        let names = assign_names(&RenameScheme::Full, &items).unwrap();
+        let tsv = name_map_string(&names, &items);
+        let mut lines = tsv.lines();
+        assert!(lines.next().unwrap().starts_with("name\tpair_id\tside\t"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "P000003_nuc\tP000003\tnuc\tchr2\t10\t510\tchr2\t10\t510\tmt\t100\t600\t500\t0.9700\tLikely_NUMT"
+        );
+    }
+}
@@ -1,9 +1,16 @@
 use anyhow::{anyhow, Context, Result};
-use paf::Reader as PafReader;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
 use std::path::Path;
 
-use crate::model::PairedLocus;
+use crate::io::{blast, runfiles, sam};
+use crate::model::{self, PairedLocus, PairingParams};
+use crate::pairing::{PairingStrategy, ReciprocalBest};
+use crate::util::cigar::{Cigar, Op};
+use crate::util::mapping::AsmMapOptions;
+use crate::util::warnings;
 
 /// Thin, crate-internal PAF record (we compute identity here).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,85 +24,788 @@ pub struct PafRecord {
     pub matches: u32,
     pub alnlen: u32,
     pub mapq: u8,
+    /// The identity filtered and paired on: `identity_kind` of the two below.
     pub identity: f32,
+    #[serde(default)]
+    pub identity_kind: IdentityKind,
+    /// Matches over block length (PAF columns 10/11; BLAST's pident).
+    #[serde(default)]
+    pub blast_identity: f32,
+    /// Matches over aligned columns plus gap openings, from the `cg:Z` CIGAR;
+    /// `None` without one.
+    #[serde(default)]
+    pub gap_compressed_identity: Option<f32>,
     pub strand: char,
+    /// `tp:A` alignment type (P primary, S secondary, I/i inversion), if present.
+    #[serde(default)]
+    pub tp: Option<char>,
+    /// The alignment this record is a piece of, if it was split at a long
+    /// indel (see `split_at_indels`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<AlnParent>,
 }
 
-impl From<paf::PafRecord> for PafRecord {
-    fn from(r: paf::PafRecord) -> Self {
-        let matches = r.residue_matches();
-        let alnlen = r.alignment_block_len();
-        let identity = if alnlen > 0 {
-            matches as f32 / alnlen as f32
-        } else {
-            0.0
+/// Query and target interval of an alignment split into pieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlnParent {
+    pub query: (u32, u32),
+    pub target: (u32, u32),
+}
+
+impl PafRecord {
+    /// Make `kind` the identity in effect; without a CIGAR, gap-compressed
+    /// falls back to BLAST identity. Returns whether it had to.
+    pub fn use_identity(&mut self, kind: IdentityKind) -> bool {
+        let (identity, used) = match (kind, self.gap_compressed_identity) {
+            (IdentityKind::GapCompressed, Some(gc)) => (gc, IdentityKind::GapCompressed),
+            _ => (self.blast_identity, IdentityKind::Blast),
         };
+        self.identity = identity;
+        self.identity_kind = used;
+        used != kind
+    }
+}
+
+/// Gap-compressed identity of a `cg:Z` CIGAR with `matches` residue matches:
+/// matches / (matches + mismatches + gap openings), so a 5 kb insertion costs
+/// as much as a 1 bp one. `None` if the CIGAR doesn't parse or has fewer
+/// aligned columns than matches.
+pub(crate) fn gap_compressed_identity(cg: &str, matches: u32) -> Option<f32> {
+    let c = Cigar::parse(cg.as_bytes()).ok()?;
+    let aligned = c.aligned();
+    if aligned < u64::from(matches) {
+        return None;
+    }
+    let denom = aligned + c.gap_opens();
+    Some(if denom == 0 {
+        0.0
+    } else {
+        matches as f32 / denom as f32
+    })
+}
+
+/// The `cg:Z` tag of a PAF line, if it has one.
+fn cg_tag(line: &str) -> Option<&str> {
+    line.split('\t')
+        .skip(12)
+        .find_map(|t| t.strip_prefix("cg:Z:"))
+}
+
+/// `pr` cut into pieces at every insertion or deletion longer than `min_indel`
+/// bp in its CIGAR, so two fragments an alignment bridges over unrelated
+/// sequence become separate records; `pr` itself when there is none, or the
+/// CIGAR does not fit its coordinates.
+///
+/// Each piece spans its first to last aligned column, with the query interval
+/// counted back from `qend` on the `-` strand. A piece's matches are its `=`
+/// columns with an extended CIGAR, else `pr`'s matches shared out by aligned
+/// columns; its block length takes in the shorter indels inside it. Pieces
+/// carry `pr`'s interval as their `parent`.
+pub fn split_at_indels(pr: &PafRecord, cigar: &Cigar, min_indel: u32) -> Vec<PafRecord> {
+    let long = |op: Op, n: u32| matches!(op, Op::Ins | Op::Del) && n > min_indel;
+    if min_indel == 0
+        || !cigar.ops().iter().any(|&(op, n)| long(op, n))
+        || cigar.query_consumed() != u64::from(pr.qend - pr.qstart)
+        || cigar.ref_consumed() != u64::from(pr.tend - pr.tstart)
+    {
+        return vec![pr.clone()];
+    }
+    let exact = cigar
+        .ops()
+        .iter()
+        .any(|&(op, _)| matches!(op, Op::Equal | Op::Diff));
+    let total_aligned = cigar.aligned().max(1);
+
+    #[derive(Default)]
+    struct Piece {
+        q: (u64, u64),
+        t: (u64, u64),
+        aligned: u64,
+        equal: u64,
+        cols: u64,
+        gap_opens: u64,
+    }
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut open = false;
+    // a short indel only joins a piece once an aligned column follows it
+    let mut gap = (0u64, 0u64);
+    let (mut q, mut t) = (0u64, 0u64);
+    for &(op, n) in cigar.ops() {
+        let len = u64::from(n);
+        if long(op, n) || op == Op::Skip {
+            open = false;
+        } else if matches!(op, Op::Match | Op::Equal | Op::Diff) {
+            if !open {
+                pieces.push(Piece {
+                    q: (q, q),
+                    t: (t, t),
+                    ..Default::default()
+                });
+                open = true;
+                gap = (0, 0);
+            }
+            let p = pieces.last_mut().unwrap();
+            p.cols += gap.0 + len;
+            p.gap_opens += gap.1;
+            gap = (0, 0);
+            p.aligned += len;
+            if op == Op::Equal {
+                p.equal += len;
+            }
+            p.q.1 = q + len;
+            p.t.1 = t + len;
+        } else if matches!(op, Op::Ins | Op::Del) && open {
+            gap = (gap.0 + len, gap.1 + 1);
+        }
+        if op.consumes_query() {
+            q += len;
+        }
+        if op.consumes_ref() {
+            t += len;
+        }
+    }
+    let parent = AlnParent {
+        query: (pr.qstart, pr.qend),
+        target: (pr.tstart, pr.tend),
+    };
+    pieces
+        .into_iter()
+        .map(|p| {
+            let matches = if exact {
+                p.equal
+            } else {
+                (u64::from(pr.matches) * p.aligned + total_aligned / 2) / total_aligned
+            };
+            let matches = matches.min(p.aligned) as u32;
+            let alnlen = p.cols as u32;
+            let (qstart, qend) = if pr.strand == '-' {
+                (pr.qend - p.q.1 as u32, pr.qend - p.q.0 as u32)
+            } else {
+                (pr.qstart + p.q.0 as u32, pr.qstart + p.q.1 as u32)
+            };
+            let blast_identity = if alnlen > 0 {
+                matches as f32 / alnlen as f32
+            } else {
+                0.0
+            };
+            let gc_denom = p.aligned + p.gap_opens;
+            PafRecord {
+                qname: pr.qname.clone(),
+                qstart,
+                qend,
+                tname: pr.tname.clone(),
+                tstart: pr.tstart + p.t.0 as u32,
+                tend: pr.tstart + p.t.1 as u32,
+                matches,
+                alnlen,
+                mapq: pr.mapq,
+                identity: blast_identity,
+                identity_kind: IdentityKind::Blast,
+                blast_identity,
+                gap_compressed_identity: Some(if gc_denom > 0 {
+                    matches as f32 / gc_denom as f32
+                } else {
+                    0.0
+                }),
+                strand: pr.strand,
+                tp: pr.tp,
+                parent: Some(parent),
+            }
+        })
+        .collect()
+}
+
+/// One PAF line: the 12 mandatory columns, plus the `tp:A` and `cg:Z` tags
+/// (a mistyped one is ignored, like a missing one); other tags are not read.
+fn parse_paf_line(line: &str) -> Result<PafRecord, String> {
+    let cols: Vec<&str> = line.trim().split('\t').collect();
+    if cols.len() < 12 {
+        return Err(format!(
+            "expected at least 12 tab-separated columns, got {}",
+            cols.len()
+        ));
+    }
+    let num = |i: usize, what: &str| {
+        cols[i]
+            .parse::<u32>()
+            .map_err(|_| format!("{what} is not a number: {:?}", cols[i]))
+    };
+    let qstart = num(2, "query start")?;
+    let qend = num(3, "query end")?;
+    let strand = match cols[4] {
+        "+" => '+',
+        "-" => '-',
+        s => return Err(format!("strand is not + or -: {s:?}")),
+    };
+    let tstart = num(7, "target start")?;
+    let tend = num(8, "target end")?;
+    let matches = num(9, "residue matches")?;
+    let alnlen = num(10, "alignment block length")?;
+    let mapq = cols[11]
+        .parse::<u8>()
+        .map_err(|_| format!("MAPQ is not a number: {:?}", cols[11]))?;
+    // lengths are not kept, but a non-numeric one is still a broken line
+    num(1, "query length")?;
+    num(6, "target length")?;
+    let tag = |prefix: &str| cols[12..].iter().find_map(|t| t.strip_prefix(prefix));
+    let tp = tag("tp:A:").and_then(|t| {
+        let mut c = t.chars();
+        c.next().filter(|_| c.next().is_none())
+    });
+    let gap_compressed_identity = tag("cg:Z:").and_then(|cg| gap_compressed_identity(cg, matches));
+    let identity = if alnlen > 0 {
+        matches as f32 / alnlen as f32
+    } else {
+        0.0
+    };
+    Ok(PafRecord {
+        qname: cols[0].to_string(),
+        qstart,
+        qend,
+        tname: cols[5].to_string(),
+        tstart,
+        tend,
+        matches,
+        alnlen,
+        mapq,
+        identity,
+        identity_kind: IdentityKind::Blast,
+        blast_identity: identity,
+        gap_compressed_identity,
+        strand,
+        tp,
+        parent: None,
+    })
+}
+
+/// Which identity `--min-id` and `aln_ident` use (`--aln-identity`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum IdentityKind {
+    /// Matches over alignment block length; every inserted or deleted base
+    /// counts against it.
+    #[default]
+    Blast,
+    /// Matches over matches, mismatches and gap openings (minimap2's
+    /// `1 - de`), from the `cg:Z` CIGAR. Old, indel-riddled NUMTs keep their
+    /// identity. Records without a CIGAR fall back to `blast`.
+    GapCompressed,
+}
+
+impl IdentityKind {
+    /// As in pairs.tsv `aln_ident_kind` and the JSON artifacts.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IdentityKind::Blast => "blast",
+            IdentityKind::GapCompressed => "gap_compressed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [IdentityKind::Blast, IdentityKind::GapCompressed]
+            .into_iter()
+            .find(|k| k.as_str() == s)
+    }
+}
+
+/// What to do with a PAF line that fails to parse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PafErrorPolicy {
+    /// Abort on the first malformed line.
+    #[default]
+    Strict,
+    /// Count and skip malformed lines (error if they exceed `max_error_frac`).
+    Skip,
+}
+
+/// Format of the assembly alignments (`--aln-format`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum AlnFormat {
+    /// minimap2 PAF.
+    #[default]
+    Paf,
+    /// BLAST tabular, `-outfmt 6` (see `io::blast`).
+    Blast6,
+    /// SAM or BAM, e.g. `minimap2 -a` (see `io::sam`).
+    Sam,
+}
+
+impl AlnFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlnFormat::Paf => "PAF",
+            AlnFormat::Blast6 => "BLAST",
+            AlnFormat::Sam => "SAM",
+        }
+    }
+
+    /// One line as a record; `Ok(None)` for lines that hold none.
+    fn parse_line(self, line: &str) -> Result<Option<PafRecord>, String> {
+        match self {
+            AlnFormat::Paf => parse_paf_line(line).map(Some),
+            AlnFormat::Blast6 => blast::parse_line(line),
+            AlnFormat::Sam => sam::parse_line(line),
+        }
+    }
+}
+
+/// What to do with secondary alignments (`tp:A:S`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecondaryPolicy {
+    /// Pair them like primary alignments.
+    #[default]
+    Keep,
+    /// Filter them out.
+    Drop,
+}
+
+impl SecondaryPolicy {
+    /// `Keep` if `keep`, else `Drop` (`--keep-secondary`).
+    pub fn keeping(keep: bool) -> Self {
+        if keep {
+            SecondaryPolicy::Keep
+        } else {
+            SecondaryPolicy::Drop
+        }
+    }
+}
+
+/// Record filters and parse-error handling for `read_paf_filtered`.
+#[derive(Debug, Clone)]
+pub struct PafFilter {
+    pub min_id: f32,
+    pub min_len: u32,
+    pub min_mapq: u8,
+    pub secondary: SecondaryPolicy,
+    pub error_policy: PafErrorPolicy,
+    pub format: AlnFormat,
+    /// The identity `min_id` applies to, and the records carry on.
+    pub identity: IdentityKind,
+    /// In skip mode, malformed/(total lines) above this is treated as corruption.
+    pub max_error_frac: f64,
+    /// Split PAF records at indels longer than this many bp (0: never; see
+    /// `split_at_indels`), before the filters see them.
+    pub split_indel: u32,
+}
+
+impl PafFilter {
+    pub fn new(min_id: f32, min_len: u32) -> Self {
         Self {
-            qname: r.query_name().to_string(),
-            qstart: r.query_start(),
-            qend: r.query_end(),
-            tname: r.target_name().to_string(),
-            tstart: r.target_start(),
-            tend: r.target_end(),
-            matches,
-            alnlen,
-            mapq: r.mapping_quality(),
-            identity,
-            strand: r.strand(),
+            min_id,
+            min_len,
+            min_mapq: model::MIN_PAF_MAPQ,
+            secondary: SecondaryPolicy::Keep,
+            error_policy: PafErrorPolicy::Strict,
+            format: AlnFormat::Paf,
+            identity: IdentityKind::Blast,
+            max_error_frac: MAX_PAF_ERROR_FRAC,
+            split_indel: 0,
         }
     }
 }
 
-/// Read & filter PAF: keep records with identity ≥ min_id and length ≥ min_len.
-pub fn read_paf(path: &Path, min_id: f32, min_len: u32) -> Result<Vec<PafRecord>> {
-    if !path.exists() {
-        return Err(anyhow!("PAF not found: {}", path.display()));
+/// Per-file accounting of what `read_paf_filtered` read, kept and dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PafFilterStats {
+    pub path: String,
+    pub n_records: u64,
+    pub n_kept: u64,
+    pub n_low_identity: u64,
+    pub n_short: u64,
+    pub n_malformed: u64,
+    /// Parsed records flagged `tp:A:S` (before filtering).
+    pub n_secondary: u64,
+    /// Secondary records filtered out (`SecondaryPolicy::Drop`).
+    #[serde(default)]
+    pub n_secondary_dropped: u64,
+    #[serde(default)]
+    pub n_low_mapq: u64,
+    /// Records without a `cg:Z` CIGAR, filtered on BLAST identity although
+    /// gap-compressed was asked for.
+    #[serde(default)]
+    pub n_identity_fallback: u64,
+    /// Alignments split at a long indel (`split_indel`); from there on each
+    /// piece counts as a record.
+    #[serde(default)]
+    pub n_split: u64,
+}
+
+/// Columns of the rejected-records audit (`PafStream::with_audit`).
+pub const REJECTED_TSV_HEADER: &str =
+    "paf\tline\tqname\tqstart\tqend\ttname\ttstart\ttend\tstrand\tidentity\talnlen\tmapq\treason";
+
+/// The filter that dropped a PAF line, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    Secondary,
+    LowIdentity,
+    Short,
+    LowMapq,
+    Malformed,
+}
+
+impl RejectReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectReason::Secondary => "secondary",
+            RejectReason::LowIdentity => "low_identity",
+            RejectReason::Short => "short",
+            RejectReason::LowMapq => "low_mapq",
+            RejectReason::Malformed => "malformed",
+        }
     }
-    let mut reader =
-        PafReader::from_path(path).with_context(|| format!("open PAF {}", path.display()))?;
-    let mut out = Vec::new();
-    for rec in reader.records() {
-        let r = rec.with_context(|| format!("read PAF record in {}", path.display()))?;
-        let pr: PafRecord = r.into();
-        if pr.identity >= min_id && pr.alnlen >= min_len {
-            out.push(pr);
+}
+
+/// Default ceiling on the malformed-line fraction in skip mode.
+pub const MAX_PAF_ERROR_FRAC: f64 = 0.01;
+
+/// Read & filter PAF (plain, gzip or zstd): keep records with identity ≥ min_id,
+/// length ≥ min_len and MAPQ ≥ min_mapq.
+pub fn read_paf(path: &Path, min_id: f32, min_len: u32, min_mapq: u8) -> Result<Vec<PafRecord>> {
+    let filter = PafFilter {
+        min_mapq,
+        ..PafFilter::new(min_id, min_len)
+    };
+    read_paf_filtered(path, &filter).map(|(v, _)| v)
+}
+
+/// Read & filter PAF with explicit filters, returning per-filter counts.
+pub fn read_paf_filtered(
+    path: &Path,
+    filter: &PafFilter,
+) -> Result<(Vec<PafRecord>, PafFilterStats)> {
+    read_paf_audited(path, filter, None)
+}
+
+/// `read_paf_filtered`, writing the dropped lines to `audit` if given (see
+/// `PafStream::with_audit`).
+pub fn read_paf_audited(
+    path: &Path,
+    filter: &PafFilter,
+    audit: Option<&mut dyn Write>,
+) -> Result<(Vec<PafRecord>, PafFilterStats)> {
+    let mut stream = PafStream::open(path, filter)?;
+    if let Some(w) = audit {
+        stream = stream.with_audit(w);
+    }
+    let out = stream.by_ref().collect::<Result<Vec<_>>>()?;
+    Ok((out, stream.finish()?))
+}
+
+/// The records of a PAF (or BLAST table) that pass `filter`, parsed and
+/// filtered line by line, so only the kept ones need to be held.
+///
+/// Yields an error for an unreadable line or, with `PafErrorPolicy::Strict`,
+/// a malformed one, and ends there. `finish` gives the counts once the file is read, after
+/// the file-level warnings and the corruption check.
+pub struct PafStream<'a> {
+    path: &'a Path,
+    filter: &'a PafFilter,
+    lines: std::iter::Enumerate<Box<dyn Iterator<Item = std::io::Result<String>>>>,
+    stats: PafFilterStats,
+    /// Where each dropped line goes, with its reason (`with_audit`).
+    audit: Option<&'a mut dyn Write>,
+    /// Records of the last line read not yet filtered (several once split),
+    /// with its line number.
+    pending: VecDeque<(usize, PafRecord)>,
+    /// Set by an error, after which nothing more is read.
+    failed: bool,
+}
+
+impl<'a> PafStream<'a> {
+    pub fn open(path: &'a Path, filter: &'a PafFilter) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!(
+                "{} not found: {}",
+                filter.format.name(),
+                path.display()
+            ));
         }
+        let lines: Box<dyn Iterator<Item = _>> = match filter.format {
+            AlnFormat::Sam => sam::lines(path),
+            _ => runfiles::open_maybe_compressed(path).map(|f| Box::new(f.lines()) as _),
+        }
+        .with_context(|| format!("open {} {}", filter.format.name(), path.display()))?;
+        Ok(Self {
+            path,
+            filter,
+            lines: lines.enumerate(),
+            stats: PafFilterStats {
+                path: path.display().to_string(),
+                ..Default::default()
+            },
+            audit: None,
+            pending: VecDeque::new(),
+            failed: false,
+        })
+    }
+
+    /// Also write every dropped line to `w` as a `REJECTED_TSV_HEADER` row
+    /// (the header is the caller's, so several files can share one audit).
+    pub fn with_audit(mut self, w: &'a mut dyn Write) -> Self {
+        self.audit = Some(w);
+        self
+    }
+
+    /// Count `pr` under the filter it fails, if any.
+    fn reject(&mut self, pr: &mut PafRecord) -> Option<RejectReason> {
+        let (filter, stats) = (self.filter, &mut self.stats);
+        stats.n_records += 1;
+        if pr.use_identity(filter.identity) {
+            stats.n_identity_fallback += 1;
+        }
+        if pr.tp == Some('S') {
+            stats.n_secondary += 1;
+        }
+        let reason = if pr.tp == Some('S') && filter.secondary == SecondaryPolicy::Drop {
+            stats.n_secondary_dropped += 1;
+            RejectReason::Secondary
+        } else if pr.identity < filter.min_id {
+            stats.n_low_identity += 1;
+            RejectReason::LowIdentity
+        } else if pr.alnlen < filter.min_len {
+            stats.n_short += 1;
+            RejectReason::Short
+        } else if pr.mapq < filter.min_mapq {
+            stats.n_low_mapq += 1;
+            RejectReason::LowMapq
+        } else {
+            stats.n_kept += 1;
+            return None;
+        };
+        Some(reason)
+    }
+
+    /// One audit row; a malformed line has `.` for its fields.
+    fn write_audit(
+        &mut self,
+        lineno: usize,
+        pr: Option<&PafRecord>,
+        reason: RejectReason,
+    ) -> Result<()> {
+        let Some(w) = self.audit.as_mut() else {
+            return Ok(());
+        };
+        let fields = match pr {
+            Some(r) => format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}",
+                r.qname,
+                r.qstart,
+                r.qend,
+                r.tname,
+                r.tstart,
+                r.tend,
+                r.strand,
+                r.identity,
+                r.alnlen,
+                r.mapq
+            ),
+            None => ["."; 10].join("\t"),
+        };
+        writeln!(
+            w,
+            "{}\t{lineno}\t{fields}\t{}",
+            self.path.display(),
+            reason.as_str()
+        )
+        .context("write rejected PAF records")
+    }
+
+    /// The per-filter counts; the rest of the file is read (and counted) first.
+    pub fn finish(mut self) -> Result<PafFilterStats> {
+        for r in self.by_ref() {
+            r?;
+        }
+        let (path, filter, stats) = (self.path, self.filter, self.stats);
+        if stats.n_identity_fallback > 0 {
+            warnings::warn(
+                warnings::IDENTITY_FALLBACK,
+                format!(
+                    "{}: {} of {} records have no cg:Z CIGAR (was minimap2 run without -c?); \
+                     they are filtered and paired on BLAST identity",
+                    path.display(),
+                    stats.n_identity_fallback,
+                    stats.n_records
+                ),
+            );
+        }
+        if stats.n_malformed > 0 {
+            let total = stats.n_malformed + stats.n_records;
+            let frac = stats.n_malformed as f64 / total as f64;
+            warnings::warn(
+                warnings::MALFORMED_PAF_FILE,
+                format!(
+                    "{}: skipped {} malformed PAF line(s) of {total} ({:.3}%)",
+                    path.display(),
+                    stats.n_malformed,
+                    100.0 * frac
+                ),
+            );
+            if frac > filter.max_error_frac {
+                return Err(anyhow!(
+                    "{}: {} of {total} PAF lines are malformed ({:.3}% > {:.3}% allowed); the file looks corrupted",
+                    path.display(),
+                    stats.n_malformed,
+                    100.0 * frac,
+                    100.0 * filter.max_error_frac
+                ));
+            }
+        }
+        if stats.n_split > 0 {
+            log::info!(
+                "{}: split {} alignments at indels longer than {} bp",
+                path.display(),
+                stats.n_split,
+                filter.split_indel
+            );
+        }
+        log::info!(
+            "{}: kept {} of {} records (identity < {}: {}, length < {}: {}, MAPQ < {}: {}, secondary dropped: {}, malformed: {})",
+            path.display(),
+            stats.n_kept,
+            stats.n_records,
+            filter.min_id,
+            stats.n_low_identity,
+            filter.min_len,
+            stats.n_short,
+            filter.min_mapq,
+            stats.n_low_mapq,
+            stats.n_secondary_dropped,
+            stats.n_malformed
+        );
+        Ok(stats)
+    }
+}
+
+impl Iterator for PafStream<'_> {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let item = self.next_kept();
+        self.failed = matches!(item, Some(Err(_)));
+        item
     }
-    Ok(out)
 }
 
-/// Very simple pairing:
-/// drive by mito→nuclear records, look for best reciprocal nuclear→mito by swapped names.
+impl PafStream<'_> {
+    fn next_kept(&mut self) -> Option<Result<PafRecord>> {
+        loop {
+            if let Some((lineno, mut pr)) = self.pending.pop_front() {
+                match self.reject(&mut pr) {
+                    None => return Some(Ok(pr)),
+                    Some(reason) => {
+                        if let Err(e) = self.write_audit(lineno, Some(&pr), reason) {
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                continue;
+            }
+            let (i, line) = self.lines.next()?;
+            let lineno = i + 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    return Some(Err(anyhow::Error::new(e).context(format!(
+                        "read PAF line {lineno} in {}",
+                        self.path.display()
+                    ))))
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let pr = match self.filter.format.parse_line(&line) {
+                Ok(Some(r)) => r,
+                Ok(None) => continue,
+                Err(e) => {
+                    let kind = self.filter.format.name();
+                    if self.filter.error_policy == PafErrorPolicy::Strict {
+                        return Some(Err(anyhow!(
+                            "malformed {kind} record at {}:{lineno}: {e} (use --paf-error-policy skip to continue past bad lines)",
+                            self.path.display()
+                        )));
+                    }
+                    self.stats.n_malformed += 1;
+                    if let Err(e) = self.write_audit(lineno, None, RejectReason::Malformed) {
+                        return Some(Err(e));
+                    }
+                    warnings::warn(
+                        warnings::MALFORMED_PAF_LINE,
+                        format!(
+                            "{}:{lineno}: skipping malformed {kind} line ({e}): {}",
+                            self.path.display(),
+                            truncate_for_log(&line)
+                        ),
+                    );
+                    continue;
+                }
+            };
+            self.queue(lineno, pr, &line);
+        }
+    }
+
+    /// Queue `pr` for filtering, as pieces if it has a long indel.
+    fn queue(&mut self, lineno: usize, pr: PafRecord, line: &str) {
+        let cigar = match (self.filter.split_indel, self.filter.format) {
+            (0, _) | (_, AlnFormat::Blast6 | AlnFormat::Sam) => None,
+            (_, AlnFormat::Paf) => cg_tag(line).and_then(|cg| Cigar::parse(cg.as_bytes()).ok()),
+        };
+        let pieces = match cigar {
+            Some(c) => split_at_indels(&pr, &c, self.filter.split_indel),
+            None => vec![pr],
+        };
+        if pieces.len() > 1 {
+            self.stats.n_split += 1;
+        }
+        self.pending.extend(pieces.into_iter().map(|p| (lineno, p)));
+    }
+}
+
+/// Write per-direction filter stats to `paf_filter_stats.json`, alongside the
+/// aligner settings that produced the PAFs (to tell aligner-side from filter-side
+/// effects apart, e.g. few secondaries; `null` for PAFs from elsewhere).
+pub fn write_filter_stats(
+    out_path: &Path,
+    m2n: &PafFilterStats,
+    n2m: &PafFilterStats,
+    aligner: Option<&AsmMapOptions>,
+) -> Result<()> {
+    let obj = serde_json::json!({
+        "aligner": aligner,
+        "mito_to_nuc": m2n,
+        "nuc_to_mito": n2m,
+    });
+    serde_json::to_writer_pretty(fs_err::File::create(out_path)?, &obj)?;
+    Ok(())
+}
+
+fn truncate_for_log(line: &str) -> &str {
+    match line.char_indices().nth(200) {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+/// Very simple pairing: drive by mito→nuclear records and take the most
+/// identical nuclear→mito record overlapping each on both genomes.
+/// Thin wrapper over `pairing::ReciprocalBest`; loci are merged and
+/// collapsed as `params` says (`pairing::finalize_loci`). See `pairing` for
+/// other strategies.
 pub fn pair_and_merge(
     m2n: &[PafRecord],
-    n2m: Vec<PafRecord>,
-    _merge_gap: u32,
+    n2m: &[PafRecord],
+    params: &PairingParams,
 ) -> Result<Vec<PairedLocus>> {
-    let mut loci = Vec::new();
-    for (i, rec) in m2n.iter().enumerate() {
-        let best = n2m
-            .iter()
-            .filter(|r| r.qname == rec.tname && r.tname == rec.qname)
-            .max_by(|a, b| a.identity.partial_cmp(&b.identity).unwrap());
-
-        let ident = best
-            .map(|b| b.identity.max(rec.identity))
-            .unwrap_or(rec.identity);
-        let (mito_s, mito_e) = (rec.qstart.min(rec.qend), rec.qstart.max(rec.qend));
-        let (nuc_s, nuc_e) = (rec.tstart.min(rec.tend), rec.tstart.max(rec.tend));
-        loci.push(PairedLocus {
-            pair_id: format!("P{:06}", i + 1),
-            nuc_contig: rec.tname.clone(),
-            nuc_start: nuc_s,
-            nuc_end: nuc_e,
-            mito_contig: rec.qname.clone(),
-            mito_start: mito_s,
-            mito_end: mito_e,
-            aln_len: rec.alnlen,
-            aln_ident: ident,
-        });
-    }
-    Ok(loci)
+    ReciprocalBest.pair(m2n, n2m, params)
 }
 
 #[cfg(test)]
@@ -130,9 +840,562 @@ mod tests {
             );
             w.write_record(&rec).unwrap();
         }
-        let v = read_paf(tmp.path(), 0.90, 50).unwrap();
+        let v = read_paf(tmp.path(), 0.90, 50, 0).unwrap();
         assert_eq!(v.len(), 1);
         assert_eq!(v[0].qname, "mito1");
         assert_eq!(v[0].tname, "chr1");
+        assert_eq!(v[0].tp, Some('P'));
+    }
+
+    #[test]
+    fn blast_records_pass_the_same_filters() {
+        use crate::model::{MIN_ID, MIN_LEN};
+        let tmp = NamedTempFile::new().unwrap();
+        // ten-column BLAST line: pident, length, query 1..=length, sstart, send
+        let line = |id: f32, len: u32, sstart: u32, send: u32| {
+            format!(
+                "chrM\tchr1\t{:.2}\t{len}\t1\t{len}\t{sstart}\t{send}\t0.0\t100\n",
+                100.0 * id
+            )
+        };
+        let lines = [
+            // kept: exactly at both thresholds, on the minus strand
+            line(MIN_ID, MIN_LEN, MIN_LEN + 1000, 1001),
+            // just below MIN_ID
+            line(MIN_ID - 0.01, MIN_LEN, 1, MIN_LEN),
+            // just below MIN_LEN
+            line(1.0, MIN_LEN - 1, 1, MIN_LEN - 1),
+        ];
+        std::fs::write(tmp.path(), lines.concat()).unwrap();
+        let filter = PafFilter {
+            format: AlnFormat::Blast6,
+            ..PafFilter::new(MIN_ID, MIN_LEN)
+        };
+        let (v, stats) = read_paf_filtered(tmp.path(), &filter).unwrap();
+        assert_eq!(
+            (
+                stats.n_records,
+                stats.n_kept,
+                stats.n_low_identity,
+                stats.n_short
+            ),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(
+            (v[0].tstart, v[0].tend, v[0].strand),
+            (1000, MIN_LEN + 1000, '-')
+        );
+        assert_eq!((v[0].qstart, v[0].qend), (0, MIN_LEN));
+    }
+
+    #[test]
+    fn sam_records_pass_the_same_filters() {
+        let tmp = NamedTempFile::new().unwrap();
+        let lines = [
+            "@HD\tVN:1.6\n",
+            "@SQ\tSN:chr1\tLN:9000\n",
+            // kept: 100 aligned, 2 mismatches
+            "chrM\t16\tchr1\t1001\t60\t100M\t*\t0\t0\t*\t*\tNM:i:2\n",
+            // secondary, dropped as in a PAF
+            "chrM\t256\tchr1\t3001\t0\t100M\t*\t0\t0\t*\t*\tNM:i:0\n",
+            // 20 mismatches: below 0.9
+            "chrM\t0\tchr1\t5001\t60\t100M\t*\t0\t0\t*\t*\tNM:i:20\n",
+            "chrM\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*\n",
+        ];
+        std::fs::write(tmp.path(), lines.concat()).unwrap();
+        let filter = PafFilter {
+            format: AlnFormat::Sam,
+            secondary: SecondaryPolicy::Drop,
+            ..PafFilter::new(0.9, 50)
+        };
+        let (v, stats) = read_paf_filtered(tmp.path(), &filter).unwrap();
+        assert_eq!(
+            (
+                stats.n_records,
+                stats.n_kept,
+                stats.n_secondary_dropped,
+                stats.n_low_identity
+            ),
+            (3, 1, 1, 1)
+        );
+        assert_eq!((v[0].tstart, v[0].tend, v[0].strand), (1000, 1100, '-'));
+        assert!((v[0].identity - 0.98).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gap_compressed_identity_forgives_long_indels() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        // a 5 kb insertion: 950 matches over 1000 aligned columns and one gap
+        f.write_all(b"m\t20000\t0\t6000\t+\tc\t9000\t0\t1000\t950\t6000\t60\tcg:Z:500M5000I500M\n")
+            .unwrap();
+        // a 2 kb deletion plus three 1 bp indels: 990 / (1000 + 4)
+        f.write_all(b"m\t20000\t0\t1002\t+\tc\t9000\t0\t3001\t990\t3003\t60\tcg:Z:300M2000D200M1I1D500M1I\n")
+            .unwrap();
+        // no CIGAR, a mistyped one, more matches than aligned columns
+        f.write_all(b"m\t20000\t0\t100\t+\tc\t9000\t0\t100\t95\t100\t60\n")
+            .unwrap();
+        f.write_all(b"m\t20000\t0\t100\t+\tc\t9000\t0\t100\t95\t100\t60\tcg:i:100\n")
+            .unwrap();
+        f.write_all(b"m\t20000\t0\t100\t+\tc\t9000\t0\t100\t95\t100\t60\tcg:Z:90M10I\n")
+            .unwrap();
+
+        let (v, st) = read_paf_filtered(f.path(), &PafFilter::new(0.0, 0)).unwrap();
+        assert!((v[0].blast_identity - 950.0 / 6000.0).abs() < 1e-6);
+        assert!((v[0].gap_compressed_identity.unwrap() - 950.0 / 1001.0).abs() < 1e-6);
+        assert!((v[1].gap_compressed_identity.unwrap() - 990.0 / 1004.0).abs() < 1e-6);
+        assert_eq!(
+            v[2..]
+                .iter()
+                .map(|r| r.gap_compressed_identity)
+                .collect::<Vec<_>>(),
+            [None; 3]
+        );
+        // BLAST identity is the default, and nothing falls back from it
+        assert!(v
+            .iter()
+            .all(|r| r.identity_kind == IdentityKind::Blast && r.identity == r.blast_identity));
+        assert_eq!(st.n_identity_fallback, 0);
+
+        // at --min-id 0.9 only gap-compressed identity keeps the indel-riddled two
+        let (v, st) = read_paf_filtered(f.path(), &PafFilter::new(0.9, 0)).unwrap();
+        assert_eq!((v.len(), st.n_low_identity), (3, 2));
+        let filter = PafFilter {
+            identity: IdentityKind::GapCompressed,
+            ..PafFilter::new(0.9, 0)
+        };
+        let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+        assert_eq!(
+            (v.len(), st.n_low_identity, st.n_identity_fallback),
+            (5, 0, 3)
+        );
+        assert_eq!(
+            v.iter().map(|r| r.identity_kind).collect::<Vec<_>>(),
+            [
+                IdentityKind::GapCompressed,
+                IdentityKind::GapCompressed,
+                IdentityKind::Blast,
+                IdentityKind::Blast,
+                IdentityKind::Blast
+            ]
+        );
+        assert!((v[0].identity - 950.0 / 1001.0).abs() < 1e-6);
+        assert!((v[2].identity - 0.95).abs() < 1e-6);
+    }
+
+    #[test]
+    fn long_indels_split_alignments_into_pieces() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        // two fragments around 500 bp of nuclear-only sequence
+        f.write_all(
+            b"m\t20000\t0\t2000\t+\tc\t90000\t10000\t12500\t1980\t2500\t60\tcg:Z:1000M500D1000M\n",
+        )
+        .unwrap();
+        // …and around 500 bp of mito-only sequence, on the - strand
+        f.write_all(
+            b"m\t20000\t0\t2500\t-\tc\t90000\t0\t2000\t1990\t2500\t60\tcg:Z:1000M500I1000M\n",
+        )
+        .unwrap();
+        // exact matches from =/X; the 5 bp insertion stays inside its piece
+        f.write_all(b"m\t20000\t5000\t6305\t+\tc\t90000\t30000\t31900\t1299\t1905\t60\tcg:Z:400=1X99=5I500=600D300=\n")
+            .unwrap();
+        // no CIGAR: kept whole
+        f.write_all(b"m\t20000\t0\t100\t+\tc\t9000\t0\t100\t95\t100\t60\n")
+            .unwrap();
+
+        let split = |min_indel: u32| {
+            let filter = PafFilter {
+                split_indel: min_indel,
+                ..PafFilter::new(0.0, 0)
+            };
+            read_paf_filtered(f.path(), &filter).unwrap()
+        };
+        let coords = |v: &[PafRecord]| -> Vec<(u32, u32, u32, u32, u32, u32)> {
+            v.iter()
+                .map(|r| (r.qstart, r.qend, r.tstart, r.tend, r.matches, r.alnlen))
+                .collect()
+        };
+
+        let (v, st) = split(0);
+        assert_eq!((v.len(), st.n_split), (4, 0));
+        assert!(v.iter().all(|r| r.parent.is_none()));
+
+        let (v, st) = split(200);
+        assert_eq!((st.n_split, st.n_records, st.n_kept), (3, 7, 7));
+        assert_eq!(
+            coords(&v),
+            [
+                (0, 1000, 10000, 11000, 990, 1000),
+                (1000, 2000, 11500, 12500, 990, 1000),
+                // counted back from qend on the - strand
+                (1500, 2500, 0, 1000, 995, 1000),
+                (0, 1000, 1000, 2000, 995, 1000),
+                (5000, 6005, 30000, 31000, 999, 1005),
+                (6005, 6305, 31600, 31900, 300, 300),
+                (0, 100, 0, 100, 95, 100),
+            ]
+        );
+        assert!((v[0].blast_identity - 0.99).abs() < 1e-6);
+        assert!((v[4].gap_compressed_identity.unwrap() - 999.0 / 1001.0).abs() < 1e-6);
+        assert_eq!(
+            v[0].parent,
+            Some(AlnParent {
+                query: (0, 2000),
+                target: (10000, 12500)
+            })
+        );
+        assert_eq!(v[0].parent, v[1].parent);
+        assert_eq!(v[6].parent, None);
+
+        // only indels longer than the threshold split
+        let (v, st) = split(500);
+        assert_eq!((v.len(), st.n_split), (5, 1));
+        assert_eq!(coords(&v)[2], (5000, 6005, 30000, 31000, 999, 1005));
+
+        // the pieces, not the whole alignments, meet the filters: whole, the
+        // indels drag BLAST identity below 0.98
+        let filter = PafFilter {
+            split_indel: 200,
+            ..PafFilter::new(0.98, 500)
+        };
+        let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+        assert_eq!((v.len(), st.n_short, st.n_low_identity), (5, 1, 1));
+        let whole = read_paf_filtered(f.path(), &PafFilter::new(0.98, 500)).unwrap();
+        assert_eq!(whole.1.n_low_identity, 4);
+    }
+
+    const GOOD: &str = "mito1\t1000\t0\t100\t+\tchr1\t5000\t1000\t1100\t95\t100\t60\n";
+
+    #[test]
+    fn compressed_pafs_filter_alike() {
+        use std::io::Write;
+        let text = format!(
+            "{GOOD}mito1\t1000\t0\t100\t+\tchr2\t5000\t0\t100\t80\t100\t60\n\
+             mito1\t1000\t0\t40\t+\tchr3\t5000\t0\t40\t40\t40\t60\n"
+        );
+        let plain = NamedTempFile::new().unwrap();
+        fs_err::write(plain.path(), &text).unwrap();
+        let gz = NamedTempFile::with_suffix(".paf.gz").unwrap();
+        let mut enc = flate2::write::GzEncoder::new(
+            fs_err::File::create(gz.path()).unwrap(),
+            flate2::Compression::default(),
+        );
+        enc.write_all(text.as_bytes()).unwrap();
+        enc.finish().unwrap();
+        let zst = NamedTempFile::with_suffix(".paf.zst").unwrap();
+        fs_err::write(
+            zst.path(),
+            ruzstd::encoding::compress_to_vec(
+                text.as_bytes(),
+                ruzstd::encoding::CompressionLevel::Fastest,
+            ),
+        )
+        .unwrap();
+
+        let filter = PafFilter::new(0.9, 50);
+        let (want, want_stats) = read_paf_filtered(plain.path(), &filter).unwrap();
+        assert_eq!(want.len(), 1);
+        assert_eq!((want_stats.n_low_identity, want_stats.n_short), (1, 1));
+        for f in [&gz, &zst] {
+            let (got, stats) = read_paf_filtered(f.path(), &filter).unwrap();
+            assert_eq!(got.len(), 1);
+            assert_eq!(got[0].tname, "chr1");
+            assert_eq!(
+                (
+                    stats.n_records,
+                    stats.n_kept,
+                    stats.n_low_identity,
+                    stats.n_short
+                ),
+                (3, 1, 1, 1)
+            );
+        }
+    }
+
+    fn corrupted(n_good: usize, bad_at: &[usize]) -> NamedTempFile {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        for i in 0..n_good {
+            if bad_at.contains(&i) {
+                // truncated transfer: line cut mid-record
+                f.write_all(b"mito1\t1000\t0\t100\t+\tchr1\t50\n").unwrap();
+            }
+            f.write_all(GOOD.as_bytes()).unwrap();
+        }
+        f
+    }
+
+    #[test]
+    fn strict_policy_names_the_line() {
+        let f = corrupted(10, &[3]);
+        let e = read_paf(f.path(), 0.9, 50, 0).unwrap_err().to_string();
+        assert!(e.contains(":4:"), "line number in error: {e}");
+    }
+
+    #[test]
+    fn skip_policy_counts_and_continues() {
+        let f = corrupted(200, &[3]);
+        let filter = PafFilter {
+            error_policy: PafErrorPolicy::Skip,
+            ..PafFilter::new(0.9, 50)
+        };
+        let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+        assert_eq!(v.len(), 200);
+        assert_eq!(st.n_malformed, 1);
+        assert_eq!(st.n_records, 200);
+        assert_eq!(st.n_kept, 200);
+    }
+
+    #[test]
+    fn stream_yields_kept_records_and_counts_the_rest() {
+        let f = corrupted(5, &[2]);
+        let filter = PafFilter {
+            error_policy: PafErrorPolicy::Skip,
+            max_error_frac: 0.5,
+            ..PafFilter::new(0.9, 50)
+        };
+        let mut stream = PafStream::open(f.path(), &filter).unwrap();
+        assert_eq!(stream.next().unwrap().unwrap().tname, "chr1");
+        // stopping early still counts the whole file
+        let stats = stream.finish().unwrap();
+        assert_eq!(
+            (stats.n_records, stats.n_kept, stats.n_malformed),
+            (5, 5, 1)
+        );
+        // a strict stream stops at the bad line
+        let strict = PafFilter::new(0.9, 50);
+        let got: Vec<_> = PafStream::open(f.path(), &strict).unwrap().collect();
+        assert_eq!(got.len(), 3);
+        assert!(got[2].is_err());
+    }
+
+    #[test]
+    fn skip_policy_escalates_above_fraction() {
+        // 2 bad of 22 lines ≈ 9% > 1%
+        let f = corrupted(20, &[1, 5]);
+        let filter = PafFilter {
+            error_policy: PafErrorPolicy::Skip,
+            ..PafFilter::new(0.9, 50)
+        };
+        let e = read_paf_filtered(f.path(), &filter)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("corrupted"), "{e}");
+
+        let lenient = PafFilter {
+            max_error_frac: 0.5,
+            ..filter
+        };
+        assert!(read_paf_filtered(f.path(), &lenient).is_ok());
+    }
+
+    #[test]
+    fn filter_stats_count_each_reason() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(GOOD.as_bytes()).unwrap();
+        // identity 0.5
+        f.write_all(b"m\t1000\t0\t100\t+\tc\t5000\t0\t100\t50\t100\t60\n")
+            .unwrap();
+        // short: 40 bp at 100%
+        f.write_all(b"m\t1000\t0\t40\t+\tc\t5000\t0\t40\t40\t40\t60\n")
+            .unwrap();
+        let (_, st) = read_paf_filtered(f.path(), &PafFilter::new(0.9, 50)).unwrap();
+        assert_eq!(
+            (st.n_records, st.n_kept, st.n_low_identity, st.n_short),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(st.n_secondary, 0);
+    }
+
+    #[test]
+    fn audit_lists_each_dropped_line_with_its_filter() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(GOOD.as_bytes()).unwrap();
+        f.write_all(b"m\t1000\t0\t100\t+\tc\t5000\t0\t100\t50\t100\t60\n")
+            .unwrap();
+        f.write_all(b"m\t1000\t0\t40\t+\tc\t5000\t0\t40\t40\t40\t60\n")
+            .unwrap();
+        f.write_all(b"m\t1000\t0\t100\t-\tc\t5000\t0\t100\t100\t100\t1\n")
+            .unwrap();
+        f.write_all(b"m\t1000\t0\t100\t+\tc\t5000\t0\t100\t100\t100\t60\ttp:A:S\n")
+            .unwrap();
+        f.write_all(b"not a paf line\n").unwrap();
+        let filter = PafFilter {
+            min_mapq: 5,
+            secondary: SecondaryPolicy::Drop,
+            error_policy: PafErrorPolicy::Skip,
+            max_error_frac: 0.5,
+            ..PafFilter::new(0.9, 50)
+        };
+        let mut audit = Vec::new();
+        let (kept, st) = read_paf_audited(f.path(), &filter, Some(&mut audit)).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(
+            (
+                st.n_low_identity,
+                st.n_short,
+                st.n_low_mapq,
+                st.n_secondary_dropped,
+                st.n_malformed
+            ),
+            (1, 1, 1, 1, 1)
+        );
+        let audit = String::from_utf8(audit).unwrap();
+        let rows: Vec<Vec<&str>> = audit.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows
+            .iter()
+            .all(|r| r.len() == REJECTED_TSV_HEADER.split('\t').count()));
+        let path = f.path().display().to_string();
+        assert_eq!(
+            rows[0],
+            [
+                &path,
+                "2",
+                "m",
+                "0",
+                "100",
+                "c",
+                "0",
+                "100",
+                "+",
+                "0.5000",
+                "100",
+                "60",
+                "low_identity"
+            ]
+        );
+        let reasons: Vec<(&str, &str)> = rows.iter().map(|r| (r[1], r[12])).collect();
+        assert_eq!(
+            reasons,
+            [
+                ("2", "low_identity"),
+                ("3", "short"),
+                ("4", "low_mapq"),
+                ("5", "secondary"),
+                ("6", "malformed")
+            ]
+        );
+        assert_eq!(rows[4][2..12], ["."; 10]);
+    }
+
+    #[test]
+    fn secondaries_counted_and_bad_tp_tolerated() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(b"m\t1000\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t0\ttp:A:S\n")
+            .unwrap();
+        // mistyped tp tag must not panic
+        f.write_all(b"m\t1000\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t60\ttp:i:1\n")
+            .unwrap();
+        let (v, st) = read_paf_filtered(f.path(), &PafFilter::new(0.9, 50)).unwrap();
+        assert_eq!(st.n_secondary, 1);
+        assert_eq!(v[1].tp, None);
+
+        // the secondary also has MAPQ 0: dropped as a secondary, counted once
+        let filter = PafFilter {
+            min_mapq: 1,
+            secondary: SecondaryPolicy::Drop,
+            ..PafFilter::new(0.9, 50)
+        };
+        let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(
+            (st.n_secondary, st.n_secondary_dropped, st.n_low_mapq),
+            (1, 1, 0)
+        );
+        let filter = PafFilter {
+            min_mapq: 1,
+            ..PafFilter::new(0.9, 50)
+        };
+        let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+        assert_eq!((v.len(), st.n_low_mapq), (1, 1));
+    }
+
+    #[test]
+    fn paf_line_fields_and_errors() {
+        let r = parse_paf_line(
+            "m\t1000\t5\t105\t-\tc\t5000\t10\t110\t90\t100\t7\tNM:i:10\ttp:A:P\tcg:Z:100M\r",
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                r.qname.as_str(),
+                r.qstart,
+                r.qend,
+                r.strand,
+                r.tname.as_str()
+            ),
+            ("m", 5, 105, '-', "c")
+        );
+        assert_eq!(
+            (r.tstart, r.tend, r.matches, r.alnlen, r.mapq),
+            (10, 110, 90, 100, 7)
+        );
+        assert_eq!((r.identity, r.tp), (0.9, Some('P')));
+        assert_eq!(r.gap_compressed_identity, Some(0.9));
+
+        for (line, what) in [
+            ("m\t1000\t0\t100\t+\tchr1\t50", "12"),
+            ("m\t1000\t0\t100\t*\tc\t5000\t0\t100\t95\t100\t60", "strand"),
+            (
+                "m\tx\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t60",
+                "query length",
+            ),
+            ("m\t1000\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t300", "MAPQ"),
+        ] {
+            let e = parse_paf_line(line).unwrap_err();
+            assert!(e.contains(what), "{line:?}: {e}");
+        }
+    }
+
+    #[test]
+    fn tp_tag_parsed_and_only_secondaries_dropped() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        for tag in ["", "\ttp:A:P", "\ttp:A:S", "\ttp:A:I", "\ttp:A:i"] {
+            writeln!(f, "m\t1000\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t60{tag}").unwrap();
+        }
+        let tps = |secondary| -> (Vec<Option<char>>, u64) {
+            let filter = PafFilter {
+                secondary,
+                ..PafFilter::new(0.9, 50)
+            };
+            let (v, st) = read_paf_filtered(f.path(), &filter).unwrap();
+            (v.iter().map(|r| r.tp).collect(), st.n_secondary_dropped)
+        };
+        // no tag is not a secondary, and inversions are kept
+        assert_eq!(
+            tps(SecondaryPolicy::Drop),
+            (vec![None, Some('P'), Some('I'), Some('i')], 1)
+        );
+        assert_eq!(
+            tps(SecondaryPolicy::Keep),
+            (vec![None, Some('P'), Some('S'), Some('I'), Some('i')], 0)
+        );
+        assert_eq!(SecondaryPolicy::keeping(false), SecondaryPolicy::Drop);
+    }
+
+    #[test]
+    fn read_paf_drops_records_below_min_mapq() {
+        use std::io::Write;
+        let mut f = NamedTempFile::new().unwrap();
+        for mapq in [0, 1, 59, 60] {
+            writeln!(f, "m\t1000\t0\t100\t+\tc\t5000\t0\t100\t95\t100\t{mapq}").unwrap();
+        }
+        let mapqs = |min| -> Vec<u8> {
+            read_paf(f.path(), 0.9, 50, min)
+                .unwrap()
+                .iter()
+                .map(|r| r.mapq)
+                .collect()
+        };
+        assert_eq!(mapqs(0), [0, 1, 59, 60]);
+        assert_eq!(mapqs(1), [1, 59, 60]);
+        assert_eq!(mapqs(60), [60]);
+        assert_eq!(mapqs(61), Vec::<u8>::new());
     }
 }
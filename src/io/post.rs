@@ -0,0 +1,310 @@
+//! `--post-summary URL` (cargo feature `http`): send the run's summary to a
+//! results server when classify/reuse finish.
+//!
+//! The body is one JSON object: `summary` exactly as in `summary.json` (in
+//! the run's `--summary-units`), plus
+//! what identifies the run (`command`, `out_dir`, the `run_manifest.json` it
+//! was made from, FNV-1a checksums of that file and `summary.json`, and the
+//! onsm build). A token in `ONSM_POST_TOKEN` is sent as a bearer token.
+//!
+//! A failed attempt (no connection, timeout, 5xx or 429) is retried once;
+//! any other status is not. Posting never fails the run: a request that still
+//! fails is a `post_summary_failed` warning.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::io::window_cache::{fnv1a, FNV_OFFSET};
+use crate::model::RunManifest;
+use crate::summary::{self, Summary, SummaryUnits};
+use crate::util::version::VersionInfo;
+use crate::util::warnings;
+
+/// Environment variable holding the bearer token, if the server wants one.
+pub const TOKEN_ENV: &str = "ONSM_POST_TOKEN";
+/// Default `--post-timeout`, in seconds.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// clap parser for `--post-summary`: an http(s) URL.
+pub fn parse_url(s: &str) -> Result<String, String> {
+    if s.starts_with("http://") || s.starts_with("https://") {
+        Ok(s.to_string())
+    } else {
+        Err(format!("expected an http:// or https:// URL, got {s:?}"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PostOptions {
+    pub url: String,
+    /// For the whole request, connecting included.
+    pub timeout: Duration,
+    pub token: Option<String>,
+}
+
+impl PostOptions {
+    /// Options for `url`, with the token from `ONSM_POST_TOKEN`.
+    pub fn from_env(url: &str, timeout_secs: u64) -> Self {
+        Self {
+            url: url.to_string(),
+            timeout: Duration::from_secs(timeout_secs),
+            token: std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Payload<'a> {
+    /// `classify` or `reuse`.
+    pub command: &'a str,
+    pub out_dir: PathBuf,
+    /// The onsm that made the run; the manifest has that of the original
+    /// classify run.
+    pub build_info: VersionInfo,
+    pub manifest: &'a RunManifest,
+    /// File name → `fnv1a64:<hex>` of `run_manifest.json` and `summary.json`.
+    pub checksums: BTreeMap<String, String>,
+    /// As in summary.json.
+    pub summary: serde_json::Value,
+}
+
+impl<'a> Payload<'a> {
+    /// `manifest_dir` holds the `run_manifest.json` of `manifest` (for reuse,
+    /// the source run).
+    pub fn new(
+        command: &'a str,
+        out_dir: &Path,
+        manifest: &'a RunManifest,
+        manifest_dir: &Path,
+        summary: &Summary,
+        units: SummaryUnits,
+    ) -> Self {
+        let checksums = [
+            manifest_dir.join("run_manifest.json"),
+            out_dir.join("summary.json"),
+        ]
+        .iter()
+        .filter_map(|p| {
+            let bytes = fs_err::read(p).ok()?;
+            let name = p.file_name()?.to_string_lossy().into_owned();
+            Some((name, format!("fnv1a64:{:016x}", fnv1a(&bytes, FNV_OFFSET))))
+        })
+        .collect();
+        Self {
+            command,
+            out_dir: out_dir.to_path_buf(),
+            build_info: VersionInfo::build(),
+            manifest,
+            checksums,
+            summary: summary::summary_json(summary, units),
+        }
+    }
+}
+
+/// POST `payload` as JSON, retrying once after a transient failure.
+pub fn post(opts: &PostOptions, payload: &Payload) -> Result<()> {
+    let body = serde_json::to_string(payload)?;
+    let agent = ureq::AgentBuilder::new().timeout(opts.timeout).build();
+    // Err((transient, message))
+    let send = || {
+        let mut req = agent
+            .post(&opts.url)
+            .set("Content-Type", "application/json");
+        if let Some(token) = &opts.token {
+            req = req.set("Authorization", &format!("Bearer {token}"));
+        }
+        match req.send_string(&body) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let transient = match &e {
+                    ureq::Error::Status(code, _) => *code >= 500 || *code == 429,
+                    ureq::Error::Transport(_) => true,
+                };
+                Err((transient, e.to_string()))
+            }
+        }
+    };
+    match send() {
+        Err((true, e)) => {
+            log::debug!("POST {} failed ({e}); retrying once", opts.url);
+            thread::sleep(RETRY_DELAY);
+            send()
+        }
+        first => first,
+    }
+    .map_err(|(_, e)| anyhow!(e))
+}
+
+/// End-of-run `--post-summary`: logs the outcome, never fails.
+pub fn post_summary(opts: &PostOptions, payload: &Payload) {
+    match post(opts, payload) {
+        Ok(()) => log::info!("posted the summary to {}", opts.url),
+        Err(e) => warnings::warn(
+            warnings::POST_SUMMARY,
+            format!("could not post the summary to {}: {e}", opts.url),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::SummaryBuilder;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Answers one request per status in `statuses`, sending back each
+    /// request's headers (lowercased) and body.
+    fn mock_server(statuses: &[u16]) -> (String, mpsc::Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/runs", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        let statuses = statuses.to_vec();
+        thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut r = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    r.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    let line = line.to_ascii_lowercase();
+                    if let Some(v) = line.strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0; len];
+                r.read_exact(&mut body).unwrap();
+                tx.send((head, String::from_utf8(body).unwrap())).unwrap();
+                write!(
+                    &stream,
+                    "HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn opts(url: &str, token: Option<&str>) -> PostOptions {
+        PostOptions {
+            url: url.to_string(),
+            timeout: Duration::from_secs(5),
+            token: token.map(str::to_string),
+        }
+    }
+
+    fn manifest() -> RunManifest {
+        RunManifest::new(
+            Path::new("mito.fa"),
+            Path::new("nuc.fa"),
+            &[PathBuf::from("reads.fq")],
+            "hifi",
+            4,
+            0.9,
+            100,
+            50,
+            5_000,
+            500,
+        )
+    }
+
+    #[test]
+    fn payload_carries_summary_and_run_identity() {
+        let td = tempfile::tempdir().unwrap();
+        let m = manifest();
+        RunManifest::save_to(td.path(), &m).unwrap();
+        let summary = SummaryBuilder::default().finish(16_000, 1_000_000);
+        let units = SummaryUnits::Ppm;
+        summary::write_summary_json(&td.path().join("summary.json"), &summary, units).unwrap();
+
+        let (url, rx) = mock_server(&[200]);
+        let payload = Payload::new("classify", td.path(), &m, td.path(), &summary, units);
+        post(&opts(&url, Some("s3cret")), &payload).unwrap();
+        let (head, body) = rx.recv().unwrap();
+        assert!(head.starts_with("post /runs "), "{head}");
+        assert!(head.contains("authorization: bearer s3cret"), "{head}");
+        assert!(head.contains("content-type: application/json"), "{head}");
+
+        let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["command"], "classify");
+        assert_eq!(v["manifest"]["platform"], "hifi");
+        assert_eq!(v["build_info"]["name"], "onsm");
+        // the summary is summary.json verbatim
+        let on_disk: serde_json::Value =
+            serde_json::from_str(&fs_err::read_to_string(td.path().join("summary.json")).unwrap())
+                .unwrap();
+        assert_eq!(v["summary"], on_disk);
+        assert_eq!(v["summary"]["units"], "ppm");
+        let sums = v["checksums"].as_object().unwrap();
+        assert_eq!(
+            sums.keys().collect::<Vec<_>>(),
+            ["run_manifest.json", "summary.json"]
+        );
+        assert!(sums["summary.json"]
+            .as_str()
+            .unwrap()
+            .starts_with("fnv1a64:"));
+    }
+
+    #[test]
+    fn failures_retry_once_and_never_fail_the_run() {
+        let m = manifest();
+        let summary = SummaryBuilder::default().finish(0, 0);
+        let payload = Payload::new(
+            "reuse",
+            Path::new("/nonexistent"),
+            &m,
+            Path::new("/nonexistent"),
+            &summary,
+            SummaryUnits::Percent,
+        );
+        assert!(payload.checksums.is_empty());
+
+        // a 503 is retried, and the retry can succeed
+        let (url, rx) = mock_server(&[503, 200]);
+        post(&opts(&url, None), &payload).unwrap();
+        let (head, _) = rx.recv().unwrap();
+        assert!(!head.contains("authorization"), "{head}");
+        rx.recv().unwrap();
+
+        // two failures give up
+        let (url, rx) = mock_server(&[500, 500]);
+        let e = post(&opts(&url, None), &payload).unwrap_err();
+        assert!(e.to_string().contains("500"), "{e}");
+        assert_eq!(rx.iter().count(), 2);
+
+        // a client error is not retried
+        let (url, rx) = mock_server(&[401]);
+        post(&opts(&url, None), &payload).unwrap_err();
+        assert_eq!(rx.iter().count(), 1);
+
+        // nothing listening: a warning, not an error
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let _runs = crate::util::logging::TEST_RUNS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let td = tempfile::tempdir().unwrap();
+        let run = warnings::RunWarnings::begin(td.path());
+        post_summary(&opts(&format!("http://127.0.0.1:{port}/"), None), &payload);
+        let report = warnings::current_report();
+        assert_eq!(report.warnings[0].category, warnings::POST_SUMMARY);
+        assert_eq!(report.warnings[0].count, 1);
+        drop(run);
+    }
+}
@@ -0,0 +1,337 @@
+//! Read-set statistics from a pass over the `--reads` files before mapping,
+//! to catch read sets that cannot support the spanning evidence.
+//!
+//! Reads that are mostly shorter than the spanning window map fine and give
+//! plausible depths, but almost none of them span a locus; the span fractions
+//! then sit near 0 on both sides and calls rest on depth alone. Such read sets
+//! are rejected, or with `--allow-short-reads` used with the span weight set
+//! to 0 (`span_evidence_disabled`).
+//!
+//! A `--reads` entry may also be a directory or a wildcard pattern
+//! (`expand_inputs`), which `classify` turns into the files it stands for
+//! before anything else looks at them.
+
+use anyhow::{anyhow, Context, Result};
+use needletail::errors::ParseErrorKind;
+use needletail::parse_fastx_file;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::io::runfiles;
+use crate::util::{glob, stats};
+
+/// Files taken from a `--reads` directory.
+pub const READS_DIR_PATTERNS: [&str; 3] = ["*.fastq*", "*.fq*", "*.fasta*"];
+
+/// Reads looked at per file; the statistics describe these.
+pub const READ_STATS_MAX_RECORDS: usize = 200_000;
+
+/// Default `--min-median-read-len` for each `--platform`.
+pub fn default_min_median_len(platform: &str) -> u32 {
+    match platform {
+        "hifi" => 3_000,
+        _ => 1_000,
+    }
+}
+
+/// Length statistics of the reads, over at most `READ_STATS_MAX_RECORDS` per file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ReadStats {
+    pub n_reads: u64,
+    pub total_bp: u64,
+    pub median_len: u64,
+    pub n50: u64,
+    /// Whether a file had more reads than were looked at.
+    pub sampled: bool,
+}
+
+impl ReadStats {
+    pub fn from_lengths(lens: &[u64], sampled: bool) -> Self {
+        let mut v = lens.to_vec();
+        v.sort_unstable();
+        Self {
+            n_reads: v.len() as u64,
+            total_bp: v.iter().sum(),
+            median_len: v.get(v.len().saturating_sub(1) / 2).copied().unwrap_or(0),
+            n50: stats::n50(&v),
+            sampled,
+        }
+    }
+}
+
+/// Read lengths of the first `max_records` reads of each file.
+pub fn scan(files: &[PathBuf], max_records: usize) -> Result<ReadStats> {
+    let mut lens = Vec::new();
+    let mut sampled = false;
+    for p in files {
+        sampled |= scan_file(p, max_records, &mut lens)?;
+    }
+    Ok(ReadStats::from_lengths(&lens, sampled))
+}
+
+/// Append the read lengths of `p`; true if it had more than `max_records`.
+fn scan_file(p: &Path, max_records: usize, lens: &mut Vec<u64>) -> Result<bool> {
+    let mut rdr = match parse_fastx_file(p) {
+        Ok(r) => r,
+        Err(e) if e.kind == ParseErrorKind::EmptyFile => return Ok(false),
+        Err(e) => return Err(e).with_context(|| format!("open reads {}", p.display())),
+    };
+    let mut n = 0;
+    while let Some(rec) = rdr
+        .next()
+        .transpose()
+        .with_context(|| format!("read reads {}", p.display()))?
+    {
+        if n == max_records {
+            return Ok(true);
+        }
+        lens.push(rec.num_bases() as u64);
+        n += 1;
+    }
+    Ok(false)
+}
+
+/// The reads files `entries` stand for, each checked to exist. A directory
+/// gives its files matching `READS_DIR_PATTERNS` (its subdirectories too with
+/// `recursive`), a wildcard pattern the files it matches, and a file itself.
+/// Files come in entry order, each expansion sorted; a file reached twice is
+/// kept the first time. An entry that gives no files is an error.
+pub fn expand_inputs(entries: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for entry in entries {
+        let found = if entry.is_dir() {
+            let found = dir_reads(entry, recursive)?;
+            if found.is_empty() {
+                return Err(anyhow!(
+                    "--reads {}: no {} files in the directory{}",
+                    entry.display(),
+                    READS_DIR_PATTERNS.join("/"),
+                    if recursive {
+                        ""
+                    } else {
+                        " (pass --reads-recursive to look in subdirectories)"
+                    }
+                ));
+            }
+            found
+        } else if !entry.exists() && glob::has_wildcards(&entry.to_string_lossy()) {
+            let found = glob::expand(entry)?;
+            if found.is_empty() {
+                return Err(anyhow!(
+                    "--reads {}: the pattern matches no files",
+                    entry.display()
+                ));
+            }
+            found
+        } else {
+            vec![entry.clone()]
+        };
+        for f in found {
+            runfiles::ensure_exists(&f)?;
+            let key = fs_err::canonicalize(&f)?;
+            if seen.insert(key) {
+                files.push(f);
+            } else {
+                log::debug!("--reads: {} given twice, kept once", f.display());
+            }
+        }
+    }
+    log::info!("--reads: {} file(s)", files.len());
+    for f in &files {
+        log::info!("  {}", f.display());
+    }
+    Ok(files)
+}
+
+/// The reads files in `dir`, sorted, then those of its subdirectories.
+fn dir_reads(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    for entry in fs_err::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // `file_type` does not follow symlinks; `is_dir` and `is_file` do
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive && !name.starts_with('.') {
+                subdirs.push(path);
+            }
+        } else if path.is_file() && READS_DIR_PATTERNS.iter().any(|p| glob::matches(p, &name)) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    subdirs.sort();
+    for sub in subdirs {
+        files.extend(dir_reads(&sub, recursive)?);
+    }
+    Ok(files)
+}
+
+/// Whether the reads can be used, and if so whether span evidence must be
+/// disabled (`allow_short`, median below `min_median_len`).
+pub fn assess(stats: &ReadStats, min_median_len: u32, allow_short: bool) -> Result<bool> {
+    if stats.n_reads == 0 {
+        return Err(anyhow!(
+            "--reads contain no records: nothing to map (check that the files are not empty or truncated)"
+        ));
+    }
+    if stats.median_len >= min_median_len as u64 {
+        return Ok(false);
+    }
+    let what = format!(
+        "median read length {} bp is below {min_median_len} bp ({} reads{})",
+        stats.median_len,
+        stats.n_reads,
+        if stats.sampled { " looked at" } else { "" }
+    );
+    if !allow_short {
+        return Err(anyhow!(
+            "{what}: too few reads would span a locus for the spanning evidence to mean \
+             anything (adapter-trimmed or fragmented reads?). Pass --allow-short-reads to \
+             classify on depth alone, or lower --min-median-read-len"
+        ));
+    }
+    log::warn!("{what}: --allow-short-reads, so span evidence is disabled (span weight 0)");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs_err as fs;
+    use tempfile::TempDir;
+
+    fn fastq(dir: &Path, name: &str, lens: &[usize]) -> PathBuf {
+        let mut s = String::new();
+        for (i, &l) in lens.iter().enumerate() {
+            s.push_str(&format!("@r{i}\n{}\n+\n{}\n", "A".repeat(l), "I".repeat(l)));
+        }
+        let p = dir.join(name);
+        fs::write(&p, s).unwrap();
+        p
+    }
+
+    #[test]
+    fn stats_over_files_and_sampling() {
+        let td = TempDir::new().unwrap();
+        let a = fastq(td.path(), "a.fq", &[100, 300, 200]);
+        let b = td.path().join("b.fa");
+        fs::write(&b, ">x\nACGT\n>y\nACGTACGTAC\n").unwrap();
+        let empty = td.path().join("empty.fq");
+        fs::write(&empty, "").unwrap();
+
+        let s = scan(&[a.clone(), b.clone(), empty.clone()], 10).unwrap();
+        assert_eq!(
+            s,
+            ReadStats {
+                n_reads: 5,
+                total_bp: 614,
+                median_len: 100,
+                n50: 200,
+                sampled: false
+            }
+        );
+        // only the first two reads of each file
+        let s = scan(&[a, b], 2).unwrap();
+        assert_eq!((s.n_reads, s.median_len, s.sampled), (4, 10, true));
+        assert_eq!(scan(&[empty], 10).unwrap().n_reads, 0);
+    }
+
+    #[test]
+    fn directories_and_patterns_expand_in_order() {
+        let td = TempDir::new().unwrap();
+        let d = td.path();
+        fs::create_dir_all(d.join("run/sub")).unwrap();
+        for f in [
+            "run/b.fastq.gz",
+            "run/a.fq",
+            "run/notes.txt",
+            "run/.a.fq.partial",
+            "run/sub/c.fasta",
+            "loose/x.fq",
+            "loose/y.fq",
+        ] {
+            fs::create_dir_all(d.join(f).parent().unwrap()).unwrap();
+            fs::write(d.join(f), "").unwrap();
+        }
+        let rel = |v: Vec<PathBuf>| -> Vec<String> {
+            v.iter()
+                .map(|p| p.strip_prefix(d).unwrap().display().to_string())
+                .collect()
+        };
+
+        // a directory, non-recursive, then recursive
+        assert_eq!(
+            rel(expand_inputs(&[d.join("run")], false).unwrap()),
+            ["run/a.fq", "run/b.fastq.gz"]
+        );
+        assert_eq!(
+            rel(expand_inputs(&[d.join("run")], true).unwrap()),
+            ["run/a.fq", "run/b.fastq.gz", "run/sub/c.fasta"]
+        );
+        // a file, a pattern and a directory together; the repeated a.fq is
+        // kept where it came first
+        let mixed = [
+            d.join("loose/y.fq"),
+            d.join("loose/*.fq"),
+            d.join("run"),
+            d.join("run/../run/a.fq"),
+        ];
+        assert_eq!(
+            rel(expand_inputs(&mixed, false).unwrap()),
+            ["loose/y.fq", "loose/x.fq", "run/a.fq", "run/b.fastq.gz"]
+        );
+    }
+
+    #[test]
+    fn inputs_that_give_no_files() {
+        let td = TempDir::new().unwrap();
+        let d = td.path();
+        fs::create_dir_all(d.join("empty/sub")).unwrap();
+        fs::write(d.join("empty/sub/a.fq"), "").unwrap();
+
+        let e = expand_inputs(&[d.join("empty")], false).unwrap_err();
+        assert!(e.to_string().contains("--reads-recursive"), "{e}");
+        assert_eq!(expand_inputs(&[d.join("empty")], true).unwrap().len(), 1);
+        let e = expand_inputs(&[d.join("*.fq")], false).unwrap_err();
+        assert!(e.to_string().contains("matches no files"), "{e}");
+        let e = expand_inputs(&[d.join("missing.fq")], false).unwrap_err();
+        assert!(e.to_string().contains("input not found"), "{e}");
+    }
+
+    #[test]
+    fn degenerate_read_sets() {
+        let floor = default_min_median_len("hifi");
+        let stats = |lens: &[u64]| ReadStats::from_lengths(lens, false);
+
+        // long reads are fine either way
+        let hifi: Vec<u64> = (0..101).map(|i| 12_000 + 50 * i).collect();
+        assert!(!assess(&stats(&hifi), floor, false).unwrap());
+        assert!(!assess(&stats(&hifi), floor, true).unwrap());
+
+        // 99% adapter-trimmed fragments: a long N50 does not save the median
+        let mut trimmed: Vec<u64> = (0..99).map(|i| 200 + 3 * i).collect();
+        trimmed.push(40_000);
+        let s = stats(&trimmed);
+        assert!(s.n50 >= 40_000 && s.median_len < 500, "{s:?}");
+        let e = assess(&s, floor, false).unwrap_err().to_string();
+        assert!(
+            e.contains("below 3000 bp") && e.contains("--allow-short-reads"),
+            "{e}"
+        );
+        assert!(assess(&s, floor, true).unwrap());
+        // …but a lower --min-median-read-len accepts them
+        assert!(!assess(&s, 300, false).unwrap());
+
+        // no reads at all cannot be allowed
+        for allow in [false, true] {
+            let e = assess(&stats(&[]), floor, allow).unwrap_err();
+            assert!(e.to_string().contains("no records"), "{e}");
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
 
 pub fn ensure_exists(p: &Path) -> Result<()> {
@@ -11,6 +13,88 @@ pub fn ensure_exists(p: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Write `contents` to a temporary file next to `path`, then rename it over
+/// `path`: a reader (or a killed run) sees the old file or the new one, never
+/// half of one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("create a temporary file in {}", dir.display()))?;
+    tmp.write_all(contents)?;
+    tmp.persist(path)
+        .with_context(|| format!("persist {}", path.display()))?;
+    Ok(())
+}
+
+/// Open a text file for buffered reading, transparently decompressing
+/// gzip/bgzip or zstd. Compression is detected from the magic bytes, not the
+/// extension.
+pub fn open_maybe_compressed(p: &Path) -> Result<Box<dyn BufRead>> {
+    let f = fs_err::File::open(p)?;
+    let mut rdr = BufReader::new(f);
+    let magic = rdr
+        .fill_buf()
+        .with_context(|| format!("read {}", p.display()))?;
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(
+            rdr,
+        ))))
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        let frames = ZstdFrames::new(rdr).with_context(|| format!("read {}", p.display()))?;
+        Ok(Box::new(BufReader::new(frames)))
+    } else {
+        Ok(Box::new(rdr))
+    }
+}
+
+/// Read a whole (possibly compressed) text file into a String.
+pub fn read_to_string_maybe_compressed(p: &Path) -> Result<String> {
+    let mut s = String::new();
+    open_maybe_compressed(p)?
+        .read_to_string(&mut s)
+        .with_context(|| format!("read {}", p.display()))?;
+    Ok(s)
+}
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Concatenated zstd frames (as `pzstd` and `cat a.zst b.zst` produce) read
+/// as one stream; a single decoder stops after the first.
+struct ZstdFrames<R: BufRead> {
+    frame: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(source: R) -> io::Result<Self> {
+        let frame = StreamingDecoder::new(source).map_err(io::Error::other)?;
+        Ok(Self { frame: Some(frame) })
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(frame) = self.frame.as_mut() else {
+                return Ok(0);
+            };
+            let n = frame.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            let (mut source, decoder) = self.frame.take().unwrap().into_parts();
+            if !source.fill_buf()?.is_empty() {
+                self.frame = Some(
+                    StreamingDecoder::new_with_decoder(source, decoder)
+                        .map_err(io::Error::other)?,
+                );
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -22,6 +106,33 @@ mod tests {
         ensure_exists(f.path()).unwrap();
     }
 
+    #[test]
+    fn compressed_text_read_transparently() {
+        use std::io::Write;
+        let text = "line one\nline two\n";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(text.as_bytes()).unwrap();
+        let zst = |s: &str| {
+            ruzstd::encoding::compress_to_vec(
+                s.as_bytes(),
+                ruzstd::encoding::CompressionLevel::Fastest,
+            )
+        };
+        // two zstd frames back to back read as one text
+        let mut frames = zst("line one\n");
+        frames.extend(zst("line two\n"));
+        for bytes in [
+            text.as_bytes().to_vec(),
+            gz.finish().unwrap(),
+            zst(text),
+            frames,
+        ] {
+            let mut f = NamedTempFile::new().unwrap();
+            f.write_all(&bytes).unwrap();
+            assert_eq!(read_to_string_maybe_compressed(f.path()).unwrap(), text);
+        }
+    }
+
     #[test]
     fn missing_err() {
         let e = ensure_exists(Path::new("/nope/nope/nope")).unwrap_err();
@@ -0,0 +1,449 @@
+//! SAM/BAM assembly alignments (e.g. `minimap2 -a`) in place of minimap2 PAFs.
+//!
+//! Each mapped record becomes a `PafRecord`: QNAME is the query, RNAME the
+//! target. The target interval is POS plus the reference bases the CIGAR
+//! covers; the query interval is what the clips leave of the query, counted on
+//! the query's own strand, as PAF does (flag 16 swaps the clips). Identity
+//! comes from the NM tag: matches = aligned columns - (NM - inserted - deleted
+//! bases), over the block length (aligned columns plus indel bases). Without
+//! NM, a CIGAR in `=`/`X` form gives the matches directly. Secondary records
+//! (flag 256) are `tp:A:S`; unmapped ones are skipped.
+//!
+//! BAM is decoded here rather than through samtools: `lines` turns each
+//! record into the SAM line the parser reads, keeping only the fields and the
+//! NM tag it uses. `bam_index` decodes the records of indexed
+//! region queries the same way.
+
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+use crate::io::paf::{gap_compressed_identity, IdentityKind, PafRecord};
+use crate::io::runfiles;
+use crate::util::cigar::{Cigar, Op};
+
+const FLAG_UNMAPPED: u16 = 0x4;
+const FLAG_REVERSE: u16 = 0x10;
+const FLAG_SECONDARY: u16 = 0x100;
+
+const BAM_MAGIC: &[u8; 4] = b"BAM\x01";
+/// BAM's CIGAR op codes, in order.
+const BAM_CIGAR_OPS: &[u8; 9] = b"MIDNSHP=X";
+
+/// One SAM line as a `PafRecord`. Header lines and unmapped records are
+/// `Ok(None)`.
+pub fn parse_line(line: &str) -> Result<Option<PafRecord>, String> {
+    if line.starts_with('@') {
+        return Ok(None);
+    }
+    let cols: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+    if cols.len() < 11 {
+        return Err(format!(
+            "expected at least 11 tab-separated columns, got {}",
+            cols.len()
+        ));
+    }
+    let flag: u16 = cols[1]
+        .parse()
+        .map_err(|_| format!("FLAG is not a number: {:?}", cols[1]))?;
+    if flag & FLAG_UNMAPPED != 0 {
+        return Ok(None);
+    }
+    let (qname, tname) = (cols[0], cols[2]);
+    if qname.is_empty() || qname == "*" || tname.is_empty() || tname == "*" {
+        return Err("mapped record without QNAME or RNAME".into());
+    }
+    let pos: u32 = cols[3]
+        .parse()
+        .ok()
+        .filter(|&p| p > 0)
+        .ok_or_else(|| format!("POS is not a 1-based position: {:?}", cols[3]))?;
+    let mapq: u8 = cols[4]
+        .parse()
+        .map_err(|_| format!("MAPQ is not a number: {:?}", cols[4]))?;
+    let cigar =
+        Cigar::parse(cols[5].as_bytes()).map_err(|e| format!("CIGAR {:?}: {e}", cols[5]))?;
+    let nm = cols[11..]
+        .iter()
+        .find_map(|t| t.strip_prefix("NM:i:"))
+        .map(|v| {
+            v.parse::<u64>()
+                .map_err(|_| format!("NM is not a number: {v:?}"))
+        })
+        .transpose()?;
+
+    let indel = |op: Op| {
+        cigar
+            .ops()
+            .iter()
+            .filter(|(o, _)| *o == op)
+            .map(|&(_, n)| u64::from(n))
+            .sum::<u64>()
+    };
+    let (ins, del) = (indel(Op::Ins), indel(Op::Del));
+    let aligned = cigar.aligned();
+    let matches = match nm {
+        Some(nm) if nm < ins + del => {
+            return Err(format!(
+                "NM:i:{nm} is less than the {} inserted and deleted bases",
+                ins + del
+            ))
+        }
+        Some(nm) => aligned.saturating_sub(nm - ins - del),
+        None if indel(Op::Match) == 0 => indel(Op::Equal),
+        None => return Err("no NM tag, and M in the CIGAR, so no identity".into()),
+    };
+    let alnlen = aligned + ins + del;
+
+    let hard = indel(Op::HardClip);
+    let qlen = cigar.query_consumed() + hard;
+    let (lead, trail) = (cigar.leading_clip(), cigar.trailing_clip());
+    let reverse = flag & FLAG_REVERSE != 0;
+    let (qstart, qend) = if reverse {
+        (trail, qlen - lead)
+    } else {
+        (lead, qlen - trail)
+    };
+    let tstart = u64::from(pos - 1);
+    let tend = tstart + cigar.ref_consumed();
+    let to_u32 = |v: u64, what: &str| u32::try_from(v).map_err(|_| format!("{what} overflows"));
+    let (matches, alnlen) = (to_u32(matches, "matches")?, to_u32(alnlen, "block length")?);
+    let identity = if alnlen == 0 {
+        0.0
+    } else {
+        matches as f32 / alnlen as f32
+    };
+    Ok(Some(PafRecord {
+        qname: qname.to_string(),
+        qstart: to_u32(qstart, "query start")?,
+        qend: to_u32(qend, "query end")?,
+        tname: tname.to_string(),
+        tstart: to_u32(tstart, "target start")?,
+        tend: to_u32(tend, "target end")?,
+        matches,
+        alnlen,
+        mapq,
+        identity,
+        identity_kind: IdentityKind::Blast,
+        blast_identity: identity,
+        gap_compressed_identity: gap_compressed_identity(cols[5], matches),
+        strand: if reverse { '-' } else { '+' },
+        tp: Some(if flag & FLAG_SECONDARY != 0 { 'S' } else { 'P' }),
+        parent: None,
+    }))
+}
+
+/// The lines of a SAM file (plain or compressed, see `runfiles`), or of a
+/// BAM file as SAM lines.
+pub fn lines(p: &Path) -> anyhow::Result<Box<dyn Iterator<Item = io::Result<String>>>> {
+    let mut rdr = runfiles::open_maybe_compressed(p)?;
+    if rdr.fill_buf()?.starts_with(BAM_MAGIC) {
+        Ok(Box::new(BamLines::new(rdr)?))
+    } else {
+        Ok(Box::new(rdr.lines()))
+    }
+}
+
+/// The records of a (decompressed) BAM stream as SAM lines.
+struct BamLines {
+    rdr: Box<dyn BufRead>,
+    refs: Vec<String>,
+}
+
+fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(i32::from_le_bytes(b))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("BAM: {msg}"))
+}
+
+fn le_u32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+impl BamLines {
+    fn new(mut rdr: Box<dyn BufRead>) -> io::Result<Self> {
+        let refs = read_header(&mut rdr)?.into_iter().map(|(n, _)| n).collect();
+        Ok(Self { rdr, refs })
+    }
+}
+
+/// The reference names and lengths of a BAM header, leaving `rdr` at the
+/// first record.
+pub(crate) fn read_header(rdr: &mut impl Read) -> io::Result<Vec<(String, u32)>> {
+    let mut magic = [0; 4];
+    rdr.read_exact(&mut magic)?;
+    if &magic != BAM_MAGIC {
+        return Err(invalid("not a BAM stream"));
+    }
+    let l_text = read_i32(rdr)?;
+    io::copy(&mut rdr.take(l_text.max(0) as u64), &mut io::sink())?;
+    let n_ref = read_i32(rdr)?;
+    let mut refs = Vec::with_capacity(n_ref.max(0) as usize);
+    for _ in 0..n_ref {
+        let l_name = read_i32(rdr)?;
+        let mut name = vec![0; l_name.max(0) as usize];
+        rdr.read_exact(&mut name)?;
+        if name.last() == Some(&0) {
+            name.pop();
+        }
+        let len = read_i32(rdr)?.max(0) as u32;
+        refs.push((String::from_utf8_lossy(&name).into_owned(), len));
+    }
+    Ok(refs)
+}
+
+/// The next record of a BAM stream (without its length), `None` at the end.
+pub(crate) fn read_record(rdr: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut size = [0; 4];
+    if rdr.read(&mut size[..1])? == 0 {
+        return Ok(None);
+    }
+    let mut b = Vec::new();
+    let read = rdr.read_exact(&mut size[1..]).and_then(|_| {
+        b.resize(i32::from_le_bytes(size).max(0) as usize, 0);
+        rdr.read_exact(&mut b)
+    });
+    match read {
+        Ok(()) => Ok(Some(b)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(invalid("truncated record")),
+        Err(e) => Err(e),
+    }
+}
+
+/// One BAM record (`b`, without its length) as the SAM line the parser reads.
+pub(crate) fn record_line(refs: &[String], b: &[u8]) -> io::Result<String> {
+    let short = || invalid("truncated record");
+    if b.len() < 32 {
+        return Err(short());
+    }
+    let ref_id = i32::from_le_bytes(b[0..4].try_into().unwrap());
+    let pos = i32::from_le_bytes(b[4..8].try_into().unwrap());
+    let (l_name, mapq) = (b[8] as usize, b[9]);
+    let n_cigar = u16::from_le_bytes([b[12], b[13]]) as usize;
+    let flag = u16::from_le_bytes([b[14], b[15]]);
+    let l_seq = le_u32(b, 16) as usize;
+    let name_end = 32 + l_name;
+    let cigar_end = name_end + 4 * n_cigar;
+    let tags_at = cigar_end + l_seq.div_ceil(2) + l_seq;
+    if b.len() < tags_at {
+        return Err(short());
+    }
+    let name = String::from_utf8_lossy(&b[32..name_end]);
+    let name = name.trim_end_matches('\0');
+    let (cg, nm) = tags(&b[tags_at..])?;
+    let mut cigar = cigar_string(&b[name_end..cigar_end]);
+    // over 65535 ops: the real CIGAR is the CG tag (SAM spec 4.2.2)
+    if let Some(cg) = cg {
+        cigar = cigar_string(cg);
+    }
+    let rname = match usize::try_from(ref_id) {
+        Ok(i) => refs
+            .get(i)
+            .ok_or_else(|| invalid("reference id out of range"))?,
+        Err(_) => "*",
+    };
+    let mut line = format!(
+        "{name}\t{flag}\t{rname}\t{}\t{mapq}\t{}\t*\t0\t0\t*\t*",
+        pos + 1,
+        if cigar.is_empty() { "*" } else { &cigar }
+    );
+    if let Some(nm) = nm {
+        line.push_str(&format!("\tNM:i:{nm}"));
+    }
+    Ok(line)
+}
+
+fn cigar_string(ops: &[u8]) -> String {
+    ops.chunks_exact(4)
+        .map(|c| {
+            let v = u32::from_le_bytes(c.try_into().unwrap());
+            let op = BAM_CIGAR_OPS
+                .get((v & 0xf) as usize)
+                .copied()
+                .unwrap_or(b'?');
+            format!("{}{}", v >> 4, op as char)
+        })
+        .collect()
+}
+
+/// The CG (B,I) and NM (any integer) tags of a record's aux data.
+fn tags(mut aux: &[u8]) -> io::Result<(Option<&[u8]>, Option<i64>)> {
+    let short = || invalid("truncated tag");
+    let (mut cg, mut nm) = (None, None);
+    while aux.len() >= 3 {
+        let (tag, ty, rest) = (&aux[..2], aux[2], &aux[3..]);
+        let int = |n: usize| -> io::Result<i64> {
+            let v = rest.get(..n).ok_or_else(short)?;
+            Ok(match ty {
+                b'c' => i64::from(v[0] as i8),
+                b'C' => i64::from(v[0]),
+                b's' => i64::from(i16::from_le_bytes([v[0], v[1]])),
+                b'S' => i64::from(u16::from_le_bytes([v[0], v[1]])),
+                b'i' => i64::from(i32::from_le_bytes(v.try_into().unwrap())),
+                _ => i64::from(u32::from_le_bytes(v.try_into().unwrap())),
+            })
+        };
+        let len = match ty {
+            b'A' | b'c' | b'C' => 1,
+            b's' | b'S' => 2,
+            b'i' | b'I' | b'f' => 4,
+            b'Z' | b'H' => rest.iter().position(|&c| c == 0).ok_or_else(short)? + 1,
+            b'B' => {
+                let sub = *rest.first().ok_or_else(short)?;
+                let n = le_u32(rest.get(..5).ok_or_else(short)?, 1) as usize;
+                let size = match sub {
+                    b'c' | b'C' => 1,
+                    b's' | b'S' => 2,
+                    b'i' | b'I' | b'f' => 4,
+                    _ => return Err(invalid("unknown array tag type")),
+                };
+                if tag == b"CG" && sub == b'I' {
+                    cg = Some(rest.get(5..5 + n * size).ok_or_else(short)?);
+                }
+                5 + n * size
+            }
+            _ => return Err(invalid("unknown tag type")),
+        };
+        if tag == b"NM" && matches!(ty, b'c' | b'C' | b's' | b'S' | b'i' | b'I') {
+            nm = Some(int(len)?);
+        }
+        aux = rest.get(len..).ok_or_else(short)?;
+    }
+    Ok((cg, nm))
+}
+
+impl Iterator for BamLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match read_record(&mut self.rdr) {
+            Ok(Some(b)) => Some(record_line(&self.refs, &b)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> PafRecord {
+        parse_line(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn cigar_gives_the_coordinates() {
+        // 10 clipped, 100 aligned with a 5 bp insertion and a 3 bp deletion,
+        // 20 clipped: query 135 bp long
+        let fwd = "mito\t0\tchr1\t5001\t60\t10S50M5I45M3D5M20H\t*\t0\t0\t*\t*\tNM:i:10";
+        let r = parse(fwd);
+        assert_eq!(
+            (r.qname.as_str(), r.qstart, r.qend, r.tname.as_str()),
+            ("mito", 10, 115, "chr1")
+        );
+        assert_eq!(
+            (r.tstart, r.tend, r.strand, r.tp),
+            (5000, 5103, '+', Some('P'))
+        );
+        assert_eq!((r.alnlen, r.mapq), (108, 60));
+        // flag 16: the clips count from the other end of the query
+        let r = parse(&fwd.replacen("\t0\t", "\t16\t", 1));
+        assert_eq!((r.qstart, r.qend, r.strand), (20, 125, '-'));
+        assert_eq!((r.tstart, r.tend), (5000, 5103));
+        // secondary
+        let r = parse(&fwd.replacen("\t0\t", "\t256\t", 1));
+        assert_eq!(r.tp, Some('S'));
+    }
+
+    #[test]
+    fn identity_from_nm() {
+        // NM 10 = 5 inserted + 3 deleted + 2 mismatches
+        let r = parse("mito\t0\tchr1\t5001\t60\t10S50M5I45M3D5M20H\t*\t0\t0\t*\t*\tNM:i:10");
+        assert_eq!(r.matches, 98);
+        assert!((r.identity - 98.0 / 108.0).abs() < 1e-6);
+        // gap-compressed: 98 / (100 aligned + 2 gap openings)
+        let gc = r.gap_compressed_identity.unwrap();
+        assert!((gc - 98.0 / 102.0).abs() < 1e-6);
+        // =/X CIGARs need no NM
+        let r = parse("mito\t0\tchr1\t1\t60\t40=2X58=\t*\t0\t0\t*\t*");
+        assert_eq!((r.matches, r.alnlen), (98, 100));
+        // no NM with M, or an NM below the indels, is an error
+        assert!(parse_line("mito\t0\tchr1\t1\t60\t100M\t*\t0\t0\t*\t*").is_err());
+        assert!(parse_line("mito\t0\tchr1\t1\t60\t50M5I50M\t*\t0\t0\t*\t*\tNM:i:2").is_err());
+    }
+
+    #[test]
+    fn skipped_and_malformed_lines() {
+        assert!(parse_line("@SQ\tSN:chr1\tLN:100").unwrap().is_none());
+        assert!(parse_line("mito\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*")
+            .unwrap()
+            .is_none());
+        for bad in [
+            "mito\t0\tchr1\t1\t60",
+            "mito\tx\tchr1\t1\t60\t100M\t*\t0\t0\t*\t*\tNM:i:0",
+            "mito\t0\tchr1\t0\t60\t100M\t*\t0\t0\t*\t*\tNM:i:0",
+            "mito\t0\tchr1\t1\t60\t*\t*\t0\t0\t*\t*\tNM:i:0",
+            "mito\t0\t*\t1\t60\t100M\t*\t0\t0\t*\t*\tNM:i:0",
+        ] {
+            assert!(parse_line(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn bam_records_read_as_sam_lines() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+        let mut raw = BAM_MAGIC.to_vec();
+        let text = b"@SQ\tSN:chr1\tLN:9000\n";
+        raw.extend((text.len() as i32).to_le_bytes());
+        raw.extend(text);
+        raw.extend(1i32.to_le_bytes());
+        raw.extend(5i32.to_le_bytes());
+        raw.extend(b"chr1\0");
+        raw.extend(9000i32.to_le_bytes());
+        // mito, flag 16, chr1:5001, MAPQ 60, 10S90M, 4 bases, NM:C:3 and a Z tag
+        let mut rec = Vec::new();
+        rec.extend(0i32.to_le_bytes());
+        rec.extend(5000i32.to_le_bytes());
+        rec.extend([5u8, 60]);
+        rec.extend(0u16.to_le_bytes());
+        rec.extend(2u16.to_le_bytes());
+        rec.extend(16u16.to_le_bytes());
+        rec.extend(4u32.to_le_bytes());
+        rec.extend((-1i32).to_le_bytes());
+        rec.extend((-1i32).to_le_bytes());
+        rec.extend(0i32.to_le_bytes());
+        rec.extend(b"mito\0");
+        rec.extend((10u32 << 4 | 4).to_le_bytes());
+        rec.extend((90u32 << 4).to_le_bytes());
+        rec.extend([0x12, 0x48, 30, 30, 30, 30]);
+        rec.extend(b"RGZx\0NMC\x03");
+        raw.extend((rec.len() as i32).to_le_bytes());
+        raw.extend(&rec);
+
+        let bam = |raw: &[u8]| {
+            let f = tempfile::NamedTempFile::new().unwrap();
+            let mut gz = GzEncoder::new(f.reopen().unwrap(), Compression::default());
+            gz.write_all(raw).unwrap();
+            gz.finish().unwrap();
+            f
+        };
+        let f = bam(&raw);
+        let got: Vec<String> = lines(f.path()).unwrap().map(|l| l.unwrap()).collect();
+        assert_eq!(
+            got,
+            ["mito\t16\tchr1\t5001\t60\t10S90M\t*\t0\t0\t*\t*\tNM:i:3"]
+        );
+        let r = parse(&got[0]);
+        assert_eq!((r.qstart, r.qend, r.tend, r.matches), (0, 90, 5090, 87));
+
+        // cut off mid-record
+        let f = bam(&raw[..raw.len() - 3]);
+        let got: Vec<_> = lines(f.path()).unwrap().collect();
+        assert!(got[0].is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Just enough of the tar format (POSIX ustar) for run bundles: regular files
+//! under relative paths, written and read as streams. Directories, links and
+//! the pax/GNU extension records other tars write are skipped on reading;
+//! paths that are absolute or climb out with `..` are refused, so unpacking
+//! never writes outside its directory.
+
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
+
+const BLOCK: usize = 512;
+/// ustar splits longer paths into a 155-byte prefix and a 100-byte name.
+const NAME_LEN: usize = 100;
+const PREFIX_LEN: usize = 155;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("tar: {msg}"))
+}
+
+/// Whether `name` is a relative path without `..`, safe to unpack.
+pub fn safe_path(name: &str) -> bool {
+    !name.is_empty()
+        && Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn octal(field: &mut [u8], n: u64) -> io::Result<()> {
+    let s = format!("{n:0width$o}\0", width = field.len() - 1);
+    if s.len() > field.len() {
+        return Err(invalid(format!("{n} does not fit a header field")));
+    }
+    field.copy_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let s = std::str::from_utf8(field).ok()?;
+    let s = s.trim_matches(|c: char| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(s, 8).ok()
+}
+
+fn checksum(header: &[u8; BLOCK]) -> u64 {
+    // the checksum field itself counts as spaces
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum()
+}
+
+fn field_str(field: &[u8]) -> &[u8] {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Writes regular files into a tar stream; `finish` writes the end marker.
+pub struct TarWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Add `size` bytes from `data` as the file `name`, read-only for the
+    /// group and others, with modification time `mtime` (Unix seconds).
+    pub fn append(
+        &mut self,
+        name: &str,
+        size: u64,
+        mtime: u64,
+        data: &mut impl Read,
+    ) -> io::Result<()> {
+        if !safe_path(name) {
+            return Err(invalid(format!("{name:?} is not a relative path")));
+        }
+        let mut h = [0u8; BLOCK];
+        let (prefix, base) = match name.len() {
+            n if n <= NAME_LEN => ("", name),
+            _ => name
+                .char_indices()
+                .filter(|&(i, c)| c == '/' && i <= PREFIX_LEN && name.len() - i - 1 <= NAME_LEN)
+                .map(|(i, _)| (&name[..i], &name[i + 1..]))
+                .next()
+                .ok_or_else(|| invalid(format!("{name:?} is too long")))?,
+        };
+        h[..base.len()].copy_from_slice(base.as_bytes());
+        octal(&mut h[100..108], 0o644)?;
+        octal(&mut h[108..116], 0)?;
+        octal(&mut h[116..124], 0)?;
+        octal(&mut h[124..136], size)?;
+        octal(&mut h[136..148], mtime)?;
+        h[156] = b'0';
+        h[257..263].copy_from_slice(b"ustar\0");
+        h[263..265].copy_from_slice(b"00");
+        h[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        let sum = checksum(&h);
+        h[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+        self.out.write_all(&h)?;
+
+        let copied = io::copy(&mut data.take(size), &mut self.out)?;
+        if copied != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("tar: {name} ended after {copied} of {size} bytes"),
+            ));
+        }
+        self.pad(size)
+    }
+
+    fn pad(&mut self, size: u64) -> io::Result<()> {
+        let rem = (size % BLOCK as u64) as usize;
+        if rem > 0 {
+            self.out.write_all(&[0; BLOCK][rem..])?;
+        }
+        Ok(())
+    }
+
+    /// Write the two empty blocks that end an archive; the stream back.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0; 2 * BLOCK])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads the regular files of a tar stream, one at a time.
+pub struct TarReader<R: Read> {
+    inp: R,
+    /// Bytes of the current entry not read yet, and its padding.
+    left: u64,
+    pad: u64,
+}
+
+/// One regular file of the archive; read its contents from the reader
+/// before asking for the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(inp: R) -> Self {
+        Self {
+            inp,
+            left: 0,
+            pad: 0,
+        }
+    }
+
+    /// The next regular file, `None` at the end of the archive. Whatever of
+    /// the previous one was not read is skipped.
+    pub fn next_entry(&mut self) -> io::Result<Option<TarEntry>> {
+        loop {
+            io::copy(
+                &mut (&mut self.inp).take(self.left + self.pad),
+                &mut io::sink(),
+            )?;
+            self.left = 0;
+            self.pad = 0;
+            let mut h = [0u8; BLOCK];
+            if read_block(&mut self.inp, &mut h)?.is_none() || h.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+            let stored = parse_octal(&h[148..156]);
+            if stored != Some(checksum(&h)) {
+                return Err(invalid(
+                    "header checksum mismatch (not a tar archive?)".into(),
+                ));
+            }
+            let size =
+                parse_octal(&h[124..136]).ok_or_else(|| invalid("unreadable entry size".into()))?;
+            self.left = size;
+            self.pad = (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64;
+            if !matches!(h[156], b'0' | 0) {
+                continue;
+            }
+            let (base, prefix) = (
+                field_str(&h[..NAME_LEN]),
+                field_str(&h[345..345 + PREFIX_LEN]),
+            );
+            let name = if prefix.is_empty() {
+                String::from_utf8_lossy(base).into_owned()
+            } else {
+                format!(
+                    "{}/{}",
+                    String::from_utf8_lossy(prefix),
+                    String::from_utf8_lossy(base)
+                )
+            };
+            if !safe_path(&name) {
+                return Err(invalid(format!("{name:?} is not a relative path")));
+            }
+            return Ok(Some(TarEntry { name, size }));
+        }
+    }
+}
+
+impl<R: Read> Read for TarReader<R> {
+    /// The contents of the current entry.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let want = buf.len().min(self.left.min(usize::MAX as u64) as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.inp.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "tar: archive ends inside an entry",
+            ));
+        }
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Fill `h`; `None` at a clean end of the stream (some writers leave out the
+/// end marker).
+fn read_block(r: &mut impl Read, h: &mut [u8; BLOCK]) -> io::Result<Option<()>> {
+    let mut got = 0;
+    while got < BLOCK {
+        match r.read(&mut h[got..])? {
+            0 if got == 0 => return Ok(None),
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "tar: archive ends inside a header",
+                ))
+            }
+            n => got += n,
+        }
+    }
+    Ok(Some(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut w = TarWriter::new(Vec::new());
+        for (name, data) in files {
+            w.append(name, data.len() as u64, 1_700_000_000, &mut &data[..])
+                .unwrap();
+        }
+        w.finish().unwrap()
+    }
+
+    fn entries(tar: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut r = TarReader::new(tar);
+        let mut out = Vec::new();
+        while let Some(e) = r.next_entry().unwrap() {
+            let mut data = Vec::new();
+            r.read_to_end(&mut data).unwrap();
+            assert_eq!(data.len() as u64, e.size);
+            out.push((e.name, data));
+        }
+        out
+    }
+
+    #[test]
+    fn files_round_trip() {
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let big = vec![7u8; 3 * BLOCK + 5];
+        let files: Vec<(&str, &[u8])> = vec![
+            ("run_manifest.json", b"{}\n"),
+            ("tmp/evidence_cache.jsonl", &big),
+            ("empty.tsv", b""),
+            (&long, b"x"),
+        ];
+        let tar = archive(&files);
+        assert_eq!(tar.len() % BLOCK, 0);
+        let got = entries(&tar);
+        let want: Vec<(String, Vec<u8>)> = files
+            .iter()
+            .map(|(n, d)| (n.to_string(), d.to_vec()))
+            .collect();
+        assert_eq!(got, want);
+
+        // an entry left unread is skipped
+        let mut r = TarReader::new(&tar[..]);
+        r.next_entry().unwrap();
+        r.next_entry().unwrap();
+        assert_eq!(r.next_entry().unwrap().unwrap().name, "empty.tsv");
+    }
+
+    #[test]
+    fn unsafe_and_damaged_archives_are_refused() {
+        let mut w = TarWriter::new(Vec::new());
+        for bad in ["/etc/passwd", "../up", "a/../../b", ""] {
+            assert!(w.append(bad, 0, 0, &mut &b""[..]).is_err(), "{bad:?}");
+        }
+        assert!(w.append(&"x".repeat(300), 0, 0, &mut &b""[..]).is_err());
+        // fewer bytes than promised
+        assert!(w.append("short", 10, 0, &mut &b"abc"[..]).is_err());
+
+        // a name rewritten to climb out, with a valid checksum
+        let mut tar = archive(&[("ab/cd", b"x")]);
+        tar[..5].copy_from_slice(b"../cd");
+        tar[148..156].fill(b' ');
+        let h: &[u8; BLOCK] = tar[..BLOCK].try_into().unwrap();
+        let sum = checksum(h);
+        tar[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+        let e = TarReader::new(&tar[..]).next_entry().unwrap_err();
+        assert!(e.to_string().contains("not a relative path"), "{e}");
+
+        // a flipped byte, and a cut
+        let mut tar = archive(&[("a.tsv", b"abc")]);
+        tar[10] ^= 1;
+        assert!(TarReader::new(&tar[..]).next_entry().is_err());
+        let tar = archive(&[("a.tsv", b"abc")]);
+        let mut r = TarReader::new(&tar[..BLOCK + 1]);
+        r.next_entry().unwrap();
+        assert!(r.read_to_end(&mut Vec::new()).is_err());
+    }
+}
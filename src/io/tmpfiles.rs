@@ -0,0 +1,543 @@
+//! A run's `tmp/` directory: what `classify` keeps there (`--keep`) and what
+//! `reuse` can redo with whatever is left.
+//!
+//! Rough sizes for a plant genome with HiFi reads:
+//!   * `reads_to_*.bam` (+ `.bai`) – tens of GB; needed to recompute evidence.
+//!     A `reads_to_*.cram` (+ `.crai`) converted from it stands in for it.
+//!   * `*_to_*.paf` – a few MB; enough to re-pair.
+//!   * `evidence_cache.jsonl` – ~200 bytes per pair; enough to rescore.
+//!   * `evidence_partial.jsonl` – the evidence cache of a run stopped by
+//!     `--max-runtime` or a signal, for `classify --resume`; removed once the
+//!     run completes.
+//!   * `evidence/cache/` – BAM window fetches (`io::window_cache`); only
+//!     useful with the BAMs, so kept with them.
+//!   * `paf_rejected.tsv` – the PAF lines the filters dropped, written only
+//!     with `--debug-filters` and kept whatever `--keep` says.
+//!
+//! Rescoring also works from the run's own `results.json` (normalized
+//! features per pair), so it needs nothing from `tmp/` at all.
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::io::window_cache::WINDOW_CACHE_DIR;
+
+pub const PAF_MITO_TO_NUC: &str = "mito_to_nuc.paf";
+pub const PAF_NUC_TO_MITO: &str = "nuc_to_mito.paf";
+pub const BAM_READS_TO_NUC: &str = "reads_to_nuc.bam";
+pub const BAM_READS_TO_MITO: &str = "reads_to_mito.bam";
+pub const CRAM_READS_TO_NUC: &str = "reads_to_nuc.cram";
+pub const CRAM_READS_TO_MITO: &str = "reads_to_mito.cram";
+pub const EVIDENCE_CACHE: &str = "evidence_cache.jsonl";
+pub const EVIDENCE_PARTIAL: &str = "evidence_partial.jsonl";
+pub const PAF_REJECTED: &str = "paf_rejected.tsv";
+/// In the run directory, next to `tmp/`.
+pub const RESULTS_JSON: &str = "results.json";
+
+/// Suffixes of compressed copies that stand in for a kept file, e.g. PAFs
+/// gzipped after the run (`io::runfiles` reads them transparently).
+const COMPRESSED_SUFFIXES: [&str; 2] = [".gz", ".zst"];
+
+/// What `classify` leaves in `tmp/` when it finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeepPolicy {
+    /// Remove tmp/ entirely.
+    None,
+    /// The two assembly PAFs (reuse can re-pair, but has no evidence to score with).
+    Paf,
+    /// PAFs plus the per-locus evidence cache (reuse can re-pair and rescore).
+    #[default]
+    Evidence,
+    /// Everything, including the read BAMs (reuse can recompute evidence).
+    All,
+}
+
+impl KeepPolicy {
+    /// File names kept by this policy; `None` means keep everything.
+    fn kept(self) -> Option<&'static [&'static str]> {
+        match self {
+            KeepPolicy::None => Some(&[]),
+            KeepPolicy::Paf => Some(&[PAF_MITO_TO_NUC, PAF_NUC_TO_MITO]),
+            KeepPolicy::Evidence => Some(&[PAF_MITO_TO_NUC, PAF_NUC_TO_MITO, EVIDENCE_CACHE]),
+            KeepPolicy::All => None,
+        }
+    }
+}
+
+/// Trim `tmp` down to what `policy` keeps (removing the directory if nothing is).
+pub fn apply_keep_policy(tmp: &Path, policy: KeepPolicy) -> Result<()> {
+    let Some(kept) = policy.kept() else {
+        return Ok(());
+    };
+    if !tmp.exists() {
+        return Ok(());
+    }
+    if kept.is_empty() && !tmp.join(PAF_REJECTED).exists() {
+        fs::remove_dir_all(tmp)?;
+        return Ok(());
+    }
+    for entry in fs::read_dir(tmp)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name == PAF_REJECTED {
+            continue;
+        }
+        if kept.iter().any(|k| {
+            name == *k
+                || COMPRESSED_SUFFIXES
+                    .iter()
+                    .any(|s| name.strip_suffix(s) == Some(k))
+        }) {
+            continue;
+        }
+        let p = entry.path();
+        if p.is_dir() {
+            fs::remove_dir_all(&p)?;
+        } else {
+            fs::remove_file(&p)?;
+        }
+    }
+    Ok(())
+}
+
+/// The earliest step `reuse` redoes; everything after it is redone too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReuseStage {
+    /// The earliest stage the kept files allow.
+    #[default]
+    Auto,
+    /// Recompute read evidence from the BAMs (needs PAFs + BAMs).
+    Evidence,
+    /// Re-pair from the PAFs, scoring with cached evidence (needs PAFs + evidence cache).
+    Pairing,
+    /// Rescore the cached loci and evidence (needs the evidence cache, or the run's results.json).
+    Scoring,
+}
+
+impl ReuseStage {
+    fn describe(self) -> &'static str {
+        match self {
+            ReuseStage::Auto => "reuse",
+            ReuseStage::Evidence => "recomputing evidence",
+            ReuseStage::Pairing => "re-pairing",
+            ReuseStage::Scoring => "rescoring",
+        }
+    }
+
+    fn flag(self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Paths of the reusable artifacts inside a run's `tmp/`.
+#[derive(Debug, Clone)]
+pub struct TmpArtifacts {
+    pub dir: PathBuf,
+    pub paf_m2n: PathBuf,
+    pub paf_n2m: PathBuf,
+    pub bam_r2n: PathBuf,
+    pub bam_r2m: PathBuf,
+    pub evidence_cache: PathBuf,
+    /// Evidence checkpointed by an interrupted `classify`.
+    pub evidence_partial: PathBuf,
+    pub window_cache: PathBuf,
+    /// The run's `results.json`: rescoring's fallback when the cache is gone.
+    pub results: PathBuf,
+}
+
+impl TmpArtifacts {
+    pub fn in_dir(tmp: &Path) -> Self {
+        Self {
+            dir: tmp.to_path_buf(),
+            paf_m2n: maybe_compressed(tmp, PAF_MITO_TO_NUC),
+            paf_n2m: maybe_compressed(tmp, PAF_NUC_TO_MITO),
+            bam_r2n: bam_or_cram(tmp, BAM_READS_TO_NUC, CRAM_READS_TO_NUC),
+            bam_r2m: bam_or_cram(tmp, BAM_READS_TO_MITO, CRAM_READS_TO_MITO),
+            evidence_cache: tmp.join(EVIDENCE_CACHE),
+            evidence_partial: tmp.join(EVIDENCE_PARTIAL),
+            window_cache: tmp.join(WINDOW_CACHE_DIR),
+            results: tmp.parent().unwrap_or(tmp).join(RESULTS_JSON),
+        }
+    }
+
+    /// Whether rescoring has to fall back to `results.json`.
+    pub fn rescore_from_results(&self) -> bool {
+        !self.evidence_cache.exists() && self.results.exists()
+    }
+
+    fn required(&self, stage: ReuseStage) -> Vec<&Path> {
+        match stage {
+            ReuseStage::Auto => vec![],
+            ReuseStage::Evidence => {
+                vec![&self.paf_m2n, &self.paf_n2m, &self.bam_r2n, &self.bam_r2m]
+            }
+            ReuseStage::Pairing => vec![&self.paf_m2n, &self.paf_n2m, &self.evidence_cache],
+            ReuseStage::Scoring => vec![&self.evidence_cache],
+        }
+    }
+
+    /// Artifacts `stage` needs that aren't on disk.
+    pub fn missing(&self, stage: ReuseStage) -> Vec<&Path> {
+        if stage == ReuseStage::Scoring && self.results.exists() {
+            return vec![];
+        }
+        self.required(stage)
+            .into_iter()
+            .filter(|p| !p.exists())
+            .collect()
+    }
+
+    /// Stages the artifacts on disk allow, earliest first.
+    pub fn possible(&self) -> Vec<ReuseStage> {
+        [
+            ReuseStage::Evidence,
+            ReuseStage::Pairing,
+            ReuseStage::Scoring,
+        ]
+        .into_iter()
+        .filter(|&s| self.missing(s).is_empty())
+        .collect()
+    }
+
+    /// `reuse --pairs-from` skips pairing, so recomputing evidence needs the
+    /// BAMs only.
+    pub fn check_bams(&self) -> Result<()> {
+        let missing: Vec<&Path> = [&self.bam_r2n, &self.bam_r2m]
+            .into_iter()
+            .map(PathBuf::as_path)
+            .filter(|p| !p.exists())
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "--pairs-from recomputes evidence from {} in {}, which the previous run did not keep \
+             (rerun classify with --keep evidence or --keep all)",
+            names(&missing),
+            self.dir.display()
+        ))
+    }
+
+    /// Resolve `Auto` to the earliest possible stage, or check an explicit one.
+    pub fn resolve_stage(&self, requested: ReuseStage) -> Result<ReuseStage> {
+        let possible = self.possible();
+        if requested != ReuseStage::Auto {
+            if possible.contains(&requested) {
+                return Ok(requested);
+            }
+            let alternatives = if possible.is_empty() {
+                "nothing else can be redone either".to_string()
+            } else {
+                let v: Vec<String> = possible
+                    .iter()
+                    .map(|s| format!("{} (--redo-from {})", s.describe(), s.flag()))
+                    .collect();
+                format!("what is there allows {}", v.join(" or "))
+            };
+            return Err(anyhow!(
+                "{} needs {} in {}, which the previous run did not keep (see classify --keep); {}",
+                requested.describe(),
+                names(&self.missing(requested)),
+                self.dir.display(),
+                alternatives
+            ));
+        }
+        possible.first().copied().ok_or_else(|| {
+            anyhow!(
+                "nothing to reuse in {}: rescoring needs {} (or the run's {}), re-pairing needs {} \
+                 plus {}, and recomputing evidence needs {} (rerun classify with --keep evidence or --keep all)",
+                self.dir.display(),
+                EVIDENCE_CACHE,
+                RESULTS_JSON,
+                names(&[&self.paf_m2n, &self.paf_n2m]),
+                EVIDENCE_CACHE,
+                names(&[&self.bam_r2n, &self.bam_r2m]),
+            )
+        })
+    }
+}
+
+/// `tmp/name`, or its compressed copy when only that is there.
+fn maybe_compressed(tmp: &Path, name: &str) -> PathBuf {
+    let plain = tmp.join(name);
+    if plain.exists() {
+        return plain;
+    }
+    COMPRESSED_SUFFIXES
+        .iter()
+        .map(|s| tmp.join(format!("{name}{s}")))
+        .find(|p| p.exists())
+        .unwrap_or(plain)
+}
+
+/// `tmp/bam`, or `tmp/cram` when only the CRAM of the same reads is there.
+fn bam_or_cram(tmp: &Path, bam: &str, cram: &str) -> PathBuf {
+    let bam = tmp.join(bam);
+    if bam.exists() || !tmp.join(cram).exists() {
+        return bam;
+    }
+    tmp.join(cram)
+}
+
+fn names(paths: &[&Path]) -> String {
+    paths
+        .iter()
+        .map(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| p.display().to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ALL_FILES: &[&str] = &[
+        PAF_MITO_TO_NUC,
+        PAF_NUC_TO_MITO,
+        BAM_READS_TO_NUC,
+        "reads_to_nuc.bam.bai",
+        BAM_READS_TO_MITO,
+        "reads_to_mito.bam.bai",
+        EVIDENCE_CACHE,
+    ];
+
+    /// A run directory whose `tmp/` holds `files` (`results.json` goes in the
+    /// run directory itself); returns the guard and the `tmp/` path.
+    fn synthetic_tmp(files: &[&str]) -> (TempDir, PathBuf) {
+        let td = TempDir::new().unwrap();
+        let tmp = td.path().join("tmp");
+        fs::create_dir(&tmp).unwrap();
+        for f in files {
+            let dir = if *f == RESULTS_JSON { td.path() } else { &tmp };
+            fs::write(dir.join(f), "x").unwrap();
+        }
+        (td, tmp)
+    }
+
+    fn listing(dir: &Path) -> Vec<String> {
+        let mut v: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn keep_policies_trim_tmp() {
+        let (_td, tmp) = synthetic_tmp(ALL_FILES);
+        fs::create_dir(tmp.join("scratch")).unwrap();
+        apply_keep_policy(&tmp, KeepPolicy::All).unwrap();
+        assert_eq!(listing(&tmp).len(), ALL_FILES.len() + 1);
+
+        apply_keep_policy(&tmp, KeepPolicy::Evidence).unwrap();
+        assert_eq!(
+            listing(&tmp),
+            [EVIDENCE_CACHE, PAF_MITO_TO_NUC, PAF_NUC_TO_MITO]
+        );
+
+        apply_keep_policy(&tmp, KeepPolicy::Paf).unwrap();
+        assert_eq!(listing(&tmp), [PAF_MITO_TO_NUC, PAF_NUC_TO_MITO]);
+
+        apply_keep_policy(&tmp, KeepPolicy::None).unwrap();
+        assert!(!tmp.exists());
+        // already gone: nothing to do
+        apply_keep_policy(&tmp, KeepPolicy::Paf).unwrap();
+
+        // the --debug-filters audit outlives every policy
+        let (_td, tmp) = synthetic_tmp(&[PAF_MITO_TO_NUC, PAF_REJECTED]);
+        apply_keep_policy(&tmp, KeepPolicy::None).unwrap();
+        assert_eq!(listing(&tmp), [PAF_REJECTED]);
+    }
+
+    #[test]
+    fn auto_stage_follows_what_was_kept() {
+        use ReuseStage::*;
+        let cases: [(KeepPolicy, Option<ReuseStage>, Option<ReuseStage>); 4] = [
+            (KeepPolicy::All, Some(Evidence), Some(Evidence)),
+            (KeepPolicy::Evidence, Some(Pairing), Some(Pairing)),
+            (KeepPolicy::Paf, None, Some(Scoring)),
+            (KeepPolicy::None, None, Some(Scoring)),
+        ];
+        for (policy, without_results, with_results) in cases {
+            for (results, want) in [(false, without_results), (true, with_results)] {
+                let (td, tmp) = synthetic_tmp(ALL_FILES);
+                if results {
+                    fs::write(td.path().join(RESULTS_JSON), "x").unwrap();
+                }
+                apply_keep_policy(&tmp, policy).unwrap();
+                let got = TmpArtifacts::in_dir(&tmp).resolve_stage(Auto).ok();
+                assert_eq!(got, want, "{policy:?}, results.json: {results}");
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_pafs_count_as_kept() {
+        let (_td, tmp) = synthetic_tmp(&[
+            "mito_to_nuc.paf.gz",
+            "nuc_to_mito.paf.zst",
+            EVIDENCE_CACHE,
+            BAM_READS_TO_NUC,
+        ]);
+        apply_keep_policy(&tmp, KeepPolicy::Evidence).unwrap();
+        assert_eq!(
+            listing(&tmp),
+            [EVIDENCE_CACHE, "mito_to_nuc.paf.gz", "nuc_to_mito.paf.zst"]
+        );
+        let a = TmpArtifacts::in_dir(&tmp);
+        assert_eq!(a.paf_m2n, tmp.join("mito_to_nuc.paf.gz"));
+        assert_eq!(
+            a.resolve_stage(ReuseStage::Auto).ok(),
+            Some(ReuseStage::Pairing)
+        );
+        // the plain file wins when both are there
+        fs::write(tmp.join(PAF_MITO_TO_NUC), "x").unwrap();
+        assert_eq!(
+            TmpArtifacts::in_dir(&tmp).paf_m2n,
+            tmp.join(PAF_MITO_TO_NUC)
+        );
+    }
+
+    #[test]
+    fn crams_stand_in_for_the_bams() {
+        let (_td, tmp) = synthetic_tmp(&[
+            PAF_MITO_TO_NUC,
+            PAF_NUC_TO_MITO,
+            CRAM_READS_TO_NUC,
+            BAM_READS_TO_MITO,
+        ]);
+        let a = TmpArtifacts::in_dir(&tmp);
+        assert_eq!(a.bam_r2n, tmp.join(CRAM_READS_TO_NUC));
+        assert_eq!(a.bam_r2m, tmp.join(BAM_READS_TO_MITO));
+        assert_eq!(
+            a.resolve_stage(ReuseStage::Auto).ok(),
+            Some(ReuseStage::Evidence)
+        );
+        // the BAM wins when both are there
+        fs::write(tmp.join(BAM_READS_TO_NUC), "x").unwrap();
+        assert_eq!(
+            TmpArtifacts::in_dir(&tmp).bam_r2n,
+            tmp.join(BAM_READS_TO_NUC)
+        );
+    }
+
+    /// Every combination of kept artifacts: which stages are possible, and
+    /// that each impossible one fails naming only what is missing.
+    #[test]
+    fn stage_matrix_over_kept_artifacts() {
+        use ReuseStage::*;
+        const PAFS: &[&str] = &[PAF_MITO_TO_NUC, PAF_NUC_TO_MITO];
+        const BAMS: &[&str] = &[BAM_READS_TO_NUC, BAM_READS_TO_MITO];
+        for mask in 0..16u8 {
+            let has = |bit: u8| mask & (1 << bit) != 0;
+            let (pafs, bams, cache, results) = (has(0), has(1), has(2), has(3));
+            let mut files: Vec<&str> = Vec::new();
+            if pafs {
+                files.extend(PAFS);
+            }
+            if bams {
+                files.extend(BAMS);
+            }
+            if cache {
+                files.push(EVIDENCE_CACHE);
+            }
+            if results {
+                files.push(RESULTS_JSON);
+            }
+            let (_td, tmp) = synthetic_tmp(&files);
+            let a = TmpArtifacts::in_dir(&tmp);
+
+            let mut want = Vec::new();
+            if pafs && bams {
+                want.push(Evidence);
+            }
+            if pafs && cache {
+                want.push(Pairing);
+            }
+            if cache || results {
+                want.push(Scoring);
+            }
+            assert_eq!(a.possible(), want, "{files:?}");
+            assert_eq!(
+                a.resolve_stage(Auto).ok(),
+                want.first().copied(),
+                "{files:?}"
+            );
+            assert_eq!(a.rescore_from_results(), results && !cache, "{files:?}");
+
+            for stage in [Evidence, Pairing, Scoring] {
+                let r = a.resolve_stage(stage);
+                if want.contains(&stage) {
+                    assert_eq!(r.unwrap(), stage, "{files:?}");
+                    continue;
+                }
+                let e = r.unwrap_err().to_string();
+                for f in a.missing(stage) {
+                    let name = f.file_name().unwrap().to_string_lossy();
+                    assert!(e.contains(&*name), "{files:?}: {e}");
+                }
+                for f in &files {
+                    assert!(!e.contains(&format!("needs {f}")), "{files:?}: {e}");
+                }
+                if want.is_empty() {
+                    assert!(e.ends_with("nothing else can be redone either"), "{e}");
+                } else {
+                    let first = format!("allows {} (--redo-from", want[0].describe());
+                    assert!(e.contains(&first), "{files:?}: {e}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_stage_names_missing_files() {
+        let (_td, tmp) = synthetic_tmp(&[EVIDENCE_CACHE]);
+        let a = TmpArtifacts::in_dir(&tmp);
+        assert_eq!(
+            a.resolve_stage(ReuseStage::Scoring).unwrap(),
+            ReuseStage::Scoring
+        );
+        let e = a
+            .resolve_stage(ReuseStage::Pairing)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("re-pairing needs mito_to_nuc.paf, nuc_to_mito.paf"),
+            "{e}"
+        );
+        assert!(
+            e.ends_with("what is there allows rescoring (--redo-from scoring)"),
+            "{e}"
+        );
+        let e = a
+            .resolve_stage(ReuseStage::Evidence)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("reads_to_nuc.bam, reads_to_mito.bam"), "{e}");
+        assert!(!e.contains(&format!("needs {EVIDENCE_CACHE}")), "{e}");
+
+        let (_td, tmp) = synthetic_tmp(&[PAF_MITO_TO_NUC, PAF_NUC_TO_MITO]);
+        let e = TmpArtifacts::in_dir(&tmp)
+            .resolve_stage(ReuseStage::Auto)
+            .unwrap_err()
+            .to_string();
+        assert!(e.starts_with("nothing to reuse"), "{e}");
+        assert!(
+            e.contains(EVIDENCE_CACHE) && e.contains(RESULTS_JSON),
+            "{e}"
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! On-disk cache of BAM window fetches (`tmp/evidence/cache/`).
+//!
+//! Every evidence pass runs `samtools view`/`depth` on the same windows of the
+//! same read BAMs, and on a network filesystem those calls dominate. Each
+//! fetch is stored as one small bincode file, named by a hash of its key:
+//! what was fetched (`view`/`depth`), the BAM's stamp (size, mtime and a
+//! checksum of its first 64 KiB), the contig, the window and the filters
+//! applied to the records. A rewritten BAM gets a new stamp, so its old
+//! entries are simply never hit again and age out. The full key is stored in
+//! the file too, so a hash collision is a miss, never a wrong answer.
+//!
+//! The cache holds at most `max_files` entries; past that the least recently
+//! used ones (by file mtime, refreshed on every hit) are removed.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::io::runfiles;
+
+/// Under a run's `tmp/`.
+pub const WINDOW_CACHE_DIR: &str = "evidence/cache";
+/// Default entry cap: a few hundred MB at most for typical windows.
+pub const DEFAULT_MAX_FILES: usize = 200_000;
+/// Bumped when the stored records change shape.
+const FORMAT_VERSION: u32 = 3;
+const ENTRY_EXT: &str = "bin";
+/// Bytes of the BAM hashed into its stamp (the BGZF header blocks).
+const STAMP_PREFIX_BYTES: usize = 64 * 1024;
+
+/// FNV-1a, also used to name the entries.
+pub(crate) fn fnv1a(bytes: &[u8], mut h: u64) -> u64 {
+    for &b in bytes {
+        h = (h ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Identifies the content of a BAM without reading all of it.
+pub fn bam_stamp(bam: &Path) -> Result<String> {
+    let md = fs_err::metadata(bam)?;
+    let mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    let mut prefix = Vec::with_capacity(STAMP_PREFIX_BYTES);
+    fs_err::File::open(bam)?
+        .take(STAMP_PREFIX_BYTES as u64)
+        .read_to_end(&mut prefix)?;
+    Ok(format!(
+        "{}:{mtime}:{:016x}",
+        md.len(),
+        fnv1a(&prefix, FNV_OFFSET)
+    ))
+}
+
+/// Everything a cached fetch depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowKey {
+    pub kind: &'static str,
+    pub bam_stamp: String,
+    pub contig: String,
+    pub start: i32,
+    pub end: i32,
+    /// The record filters applied before storing, e.g. `mapped,mapq>=20`.
+    pub filters: String,
+}
+
+impl WindowKey {
+    fn text(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.kind, self.bam_stamp, self.contig, self.start, self.end, self.filters
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    version: u32,
+    key: String,
+    value: T,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evicted: u64,
+}
+
+#[derive(Debug)]
+pub struct WindowCache {
+    dir: PathBuf,
+    max_files: usize,
+    /// Entries on disk (as far as this process knows) and the counters.
+    state: Mutex<(usize, CacheStats)>,
+    /// `bam_stamp` per BAM, taken once per run.
+    stamps: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl WindowCache {
+    /// Open (creating) the cache in `dir`.
+    pub fn open(dir: &Path, max_files: usize) -> Result<Self> {
+        fs_err::create_dir_all(dir)?;
+        let n = entries(dir)?.len();
+        log::debug!("window cache {}: {n} entries", dir.display());
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_files: max_files.max(1),
+            state: Mutex::new((n, CacheStats::default())),
+            stamps: Mutex::default(),
+        })
+    }
+
+    /// `bam_stamp(bam)`, read once per cache.
+    pub fn stamp(&self, bam: &Path) -> Result<String> {
+        let mut stamps = self.stamps.lock().unwrap();
+        if let Some(s) = stamps.get(bam) {
+            return Ok(s.clone());
+        }
+        let s = bam_stamp(bam)?;
+        stamps.insert(bam.to_path_buf(), s.clone());
+        Ok(s)
+    }
+
+    fn log_stats(&self) {
+        let s = self.stats();
+        if s.hits + s.misses == 0 {
+            return;
+        }
+        log::info!(
+            "window cache {}: {} of {} BAM windows cached{}",
+            self.dir.display(),
+            s.hits,
+            s.hits + s.misses,
+            if s.evicted > 0 {
+                format!(", {} old entries evicted", s.evicted)
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().1
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{:016x}.{ENTRY_EXT}",
+            fnv1a(key.as_bytes(), FNV_OFFSET)
+        ))
+    }
+
+    /// The cached value for `key`, else `fetch()`'s, which is stored.
+    pub fn get_or_fetch<T: Serialize + DeserializeOwned>(
+        &self,
+        key: &WindowKey,
+        fetch: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let text = key.text();
+        let path = self.path(&text);
+        if let Some(v) = self.load(&path, &text) {
+            self.state.lock().unwrap().1.hits += 1;
+            return Ok(v);
+        }
+        let v = fetch()?;
+        self.state.lock().unwrap().1.misses += 1;
+        if let Err(e) = self.store(&path, &text, &v) {
+            log::debug!("window cache: could not store {}: {e:#}", path.display());
+        }
+        Ok(v)
+    }
+
+    /// Whether a `T` is stored for `key`; not counted as a hit.
+    pub fn contains<T: DeserializeOwned>(&self, key: &WindowKey) -> bool {
+        let text = key.text();
+        self.load::<T>(&self.path(&text), &text).is_some()
+    }
+
+    fn load<T: DeserializeOwned>(&self, path: &Path, key: &str) -> Option<T> {
+        let bytes = std::fs::read(path).ok()?;
+        let entry: Entry<T> = match bincode::deserialize(&bytes) {
+            Ok(e) => e,
+            Err(e) => {
+                log::debug!("window cache: unreadable {}: {e}", path.display());
+                return None;
+            }
+        };
+        if entry.version != FORMAT_VERSION || entry.key != key {
+            return None;
+        }
+        // refresh for LRU; a read-only cache still works, it just ages wrongly
+        if let Ok(f) = std::fs::File::options().write(true).open(path) {
+            let _ = f.set_modified(SystemTime::now());
+        }
+        Some(entry.value)
+    }
+
+    fn store<T: Serialize>(&self, path: &Path, key: &str, value: &T) -> Result<()> {
+        let entry = Entry {
+            version: FORMAT_VERSION,
+            key: key.to_string(),
+            value,
+        };
+        let existed = path.exists();
+        // a killed run never leaves half an entry
+        runfiles::write_atomic(path, &bincode::serialize(&entry)?)?;
+        let mut state = self.state.lock().unwrap();
+        if !existed {
+            state.0 += 1;
+        }
+        if state.0 > self.max_files {
+            let (n, evicted) = evict(&self.dir, self.max_files)?;
+            state.0 = n;
+            state.1.evicted += evicted;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WindowCache {
+    /// How many windows came from the cache.
+    fn drop(&mut self) {
+        self.log_stats();
+    }
+}
+
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut v = Vec::new();
+    for e in fs_err::read_dir(dir)? {
+        let p = e?.path();
+        if p.extension().is_some_and(|x| x == ENTRY_EXT) {
+            v.push(p);
+        }
+    }
+    Ok(v)
+}
+
+/// Remove the least recently used entries down to 90% of `max_files` (so
+/// eviction does not run on every store); returns (left, removed).
+fn evict(dir: &Path, max_files: usize) -> Result<(usize, u64)> {
+    let mut files: Vec<(SystemTime, PathBuf)> = entries(dir)?
+        .into_iter()
+        .map(|p| {
+            let t = std::fs::metadata(&p)
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH);
+            (t, p)
+        })
+        .collect();
+    let keep = max_files - max_files / 10;
+    if files.len() <= keep {
+        return Ok((files.len(), 0));
+    }
+    files.sort();
+    let n_remove = files.len() - keep;
+    for (_, p) in &files[..n_remove] {
+        let _ = std::fs::remove_file(p);
+    }
+    log::debug!(
+        "window cache {}: evicted {n_remove} least recently used entries",
+        dir.display()
+    );
+    Ok((keep, n_remove as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn key(contig: &str, start: i32) -> WindowKey {
+        WindowKey {
+            kind: "view",
+            bam_stamp: "1:2:3".into(),
+            contig: contig.into(),
+            start,
+            end: start + 500,
+            filters: "mapped".into(),
+        }
+    }
+
+    #[test]
+    fn hits_after_a_fetch_and_keys_separate_entries() {
+        let td = TempDir::new().unwrap();
+        let cache = WindowCache::open(&td.path().join("c"), 100).unwrap();
+        let mut calls = 0;
+        let mut get = |k: &WindowKey, v: Vec<u32>| {
+            cache
+                .get_or_fetch(k, || {
+                    calls += 1;
+                    Ok(v)
+                })
+                .unwrap()
+        };
+        assert_eq!(get(&key("chr1", 0), vec![1, 2]), [1, 2]);
+        assert_eq!(get(&key("chr1", 0), vec![9]), [1, 2]);
+        assert_eq!(get(&key("chr1", 1), vec![3]), [3]);
+        let other = WindowKey {
+            filters: "mapped,mapq>=20".into(),
+            ..key("chr1", 0)
+        };
+        assert_eq!(get(&other, vec![4]), [4]);
+        assert_eq!(calls, 3);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                evicted: 0
+            }
+        );
+
+        // reopened: still there; a corrupt entry is refetched
+        let cache = WindowCache::open(&td.path().join("c"), 100).unwrap();
+        let k = key("chr1", 0);
+        let p = cache.path(&k.text());
+        assert_eq!(cache.get_or_fetch(&k, || Ok(vec![0u32])).unwrap(), [1, 2]);
+        fs_err::write(&p, b"junk").unwrap();
+        assert_eq!(cache.get_or_fetch(&k, || Ok(vec![7u32])).unwrap(), [7]);
+        assert_eq!(cache.get_or_fetch(&k, || Ok(vec![8u32])).unwrap(), [7]);
+    }
+
+    #[test]
+    fn bam_changes_change_the_stamp() {
+        let td = TempDir::new().unwrap();
+        let bam = td.path().join("r.bam");
+        fs_err::write(&bam, b"BAM\x01 reads").unwrap();
+        let a = bam_stamp(&bam).unwrap();
+        assert_eq!(bam_stamp(&bam).unwrap(), a);
+        // same size, same mtime, other bytes
+        let mtime = fs_err::metadata(&bam).unwrap().modified().unwrap();
+        fs_err::write(&bam, b"BAM\x01 rEads").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&bam)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_ne!(bam_stamp(&bam).unwrap(), a);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let td = TempDir::new().unwrap();
+        let cache = WindowCache::open(td.path(), 10).unwrap();
+        let t0 = SystemTime::now() - Duration::from_secs(3600);
+        for i in 0..10 {
+            let k = key("chr1", i * 1000);
+            cache.get_or_fetch(&k, || Ok(i)).unwrap();
+            // give the entries distinct, increasing ages
+            std::fs::File::options()
+                .write(true)
+                .open(cache.path(&k.text()))
+                .unwrap()
+                .set_modified(t0 + Duration::from_secs(i as u64))
+                .unwrap();
+        }
+        // a hit makes the oldest entry the newest
+        assert_eq!(cache.get_or_fetch(&key("chr1", 0), || Ok(-1)).unwrap(), 0);
+        cache.get_or_fetch(&key("chr2", 0), || Ok(10)).unwrap();
+        assert_eq!(entries(td.path()).unwrap().len(), 9);
+        assert_eq!(cache.stats().evicted, 2);
+        let cached = |i: i32| cache.path(&key("chr1", i * 1000).text()).exists();
+        assert!(cached(0));
+        assert!(!cached(1) && !cached(2));
+        assert!((3..10).all(cached));
+    }
+}
@@ -1,20 +1,79 @@
+pub mod boundary;
+pub mod bundle;
+pub mod columns;
+pub mod compat;
+pub mod control_region;
+pub mod crossref;
+pub mod density;
+pub mod example;
+pub mod indexes;
+pub mod liftover;
+pub mod mapping_stats;
+pub mod mask;
+pub mod mito_meta;
 pub mod model;
+pub mod organelles;
+pub mod pairing;
+pub mod pipeline;
+pub mod regions;
+pub mod run_stats;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod scoring;
+pub mod self_assembly;
+pub mod self_exclusion;
 pub mod summary;
+pub mod tracking;
 
 pub mod io {
     pub mod bam;
+    pub mod bam_index;
+    pub mod bed;
+    pub mod bgzf;
+    pub mod blast;
     pub mod fasta;
+    pub mod headers;
     pub mod paf;
+    #[cfg(feature = "http")]
+    pub mod post;
+    pub mod reads;
     pub mod runfiles;
+    pub mod sam;
+    pub mod tar;
+    pub mod tmpfiles;
+    pub mod window_cache;
 }
 
 pub mod util {
+    pub mod cigar;
+    pub mod cpus;
+    pub mod glob;
     pub mod logging;
     pub mod mapping;
+    pub mod progress;
+    pub mod region;
+    pub mod runlock;
+    pub mod shutdown;
+    pub mod stats;
+    pub mod status;
+    pub mod strict;
+    pub mod version;
+    pub mod warnings;
 }
 
 pub mod subcommands {
+    pub mod bundle;
     pub mod classify;
+    pub mod doctor;
+    pub mod example;
+    pub mod explain;
+    pub mod liftover;
+    pub mod mask;
+    pub mod pair;
+    pub mod reconcile;
     pub mod reuse;
+    #[cfg(feature = "schema")]
+    pub mod schema;
+    pub mod stats;
+    pub mod syscheck;
 }
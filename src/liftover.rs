@@ -0,0 +1,603 @@
+//! Lift nuclear intervals from an old assembly onto a new one (polished,
+//! re-scaffolded) through a user-supplied old→new PAF: query = old assembly,
+//! target = new, e.g. `minimap2 -c -x asm5 new.fa old.fa > old_to_new.paf`.
+//!
+//! Each alignment becomes a chain of gap-free blocks. With a `cg:Z` CIGAR the
+//! blocks are its M/=/X runs and the lift is base-exact; without one the whole
+//! alignment is a single block mapped by linear interpolation (exact at both
+//! ends), as in `model::orientation`. An interval is cut at every alignment it
+//! touches and each alignment's pieces form one segment (alignments are
+//! colinear), so a locus can come out split across new contigs or partly
+//! inverted; both are reported rather than papered over.
+
+use anyhow::{anyhow, Context, Result};
+use paf::Reader as PafReader;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::model::orientation::Strand;
+use crate::util::cigar::Cigar;
+
+/// Minimum fraction of an interval's bases that must lift for `lifted`.
+pub const LIFT_MIN_COVERAGE: f32 = 0.95;
+
+/// One gap-free stretch of an alignment. Lengths differ only for the single
+/// interpolated block of an alignment without a CIGAR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    old_start: u32,
+    old_len: u32,
+    new_start: u32,
+    new_len: u32,
+}
+
+impl Block {
+    fn old_end(&self) -> u32 {
+        self.old_start + self.old_len
+    }
+
+    /// New-assembly offset of old offset `o` (0..=old_len).
+    fn scale(&self, o: u32) -> u32 {
+        if self.old_len == self.new_len {
+            o
+        } else {
+            (o as u64 * self.new_len as u64 / self.old_len.max(1) as u64) as u32
+        }
+    }
+
+    /// `[a, b)` (old, within this block) on the new assembly, forward coordinates.
+    fn map(&self, a: u32, b: u32, strand: Strand) -> (u32, u32) {
+        let (oa, ob) = (a - self.old_start, b - self.old_start);
+        match strand {
+            Strand::Forward => (
+                self.new_start + self.scale(oa),
+                self.new_start + self.scale(ob),
+            ),
+            // the first old base pairs with the last new base
+            Strand::Reverse => {
+                let new_end = self.new_start + self.new_len;
+                (new_end - self.scale(ob), new_end - self.scale(oa))
+            }
+        }
+    }
+}
+
+/// One old→new alignment as blocks in old-coordinate order.
+#[derive(Debug, Clone)]
+pub struct LiftChain {
+    pub old_contig: String,
+    pub new_contig: String,
+    pub strand: Strand,
+    /// Built from a CIGAR (base-exact) rather than interpolated.
+    pub base_level: bool,
+    blocks: Vec<Block>,
+}
+
+impl LiftChain {
+    /// `old` and `new` are the alignment's `[start, end)` on each assembly
+    /// (PAF query and target columns); `cigar` is the `cg:Z` value, if any.
+    pub fn new(
+        old_contig: &str,
+        old: (u32, u32),
+        new_contig: &str,
+        new: (u32, u32),
+        strand: Strand,
+        cigar: Option<&str>,
+    ) -> Result<Self> {
+        let (old_len, new_len) = (old.1.saturating_sub(old.0), new.1.saturating_sub(new.0));
+        let blocks = match cigar {
+            None => vec![Block {
+                old_start: old.0,
+                old_len,
+                new_start: new.0,
+                new_len,
+            }],
+            Some(cg) => cigar_blocks(cg, old, new.0, strand).and_then(|(blocks, q, t)| {
+                if q != old_len || t != new_len {
+                    Err(anyhow!(
+                        "CIGAR spans {q} old / {t} new bases but the alignment is \
+                         {old_len} / {new_len}"
+                    ))
+                } else {
+                    Ok(blocks)
+                }
+            })?,
+        };
+        Ok(Self {
+            old_contig: old_contig.to_string(),
+            new_contig: new_contig.to_string(),
+            strand,
+            base_level: cigar.is_some(),
+            blocks,
+        })
+    }
+
+    fn old_span(&self) -> (u32, u32) {
+        match (self.blocks.first(), self.blocks.last()) {
+            (Some(f), Some(l)) => (f.old_start, l.old_end()),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// Blocks (sorted by old start) and the old/new bases the CIGAR consumes.
+fn cigar_blocks(
+    cg: &str,
+    old: (u32, u32),
+    new_start: u32,
+    strand: Strand,
+) -> Result<(Vec<Block>, u32, u32)> {
+    let cigar = Cigar::parse(cg.as_bytes())?;
+    if cigar.leading_clip() + cigar.trailing_clip() > 0 {
+        return Err(anyhow!("clipped CIGAR in cg:Z: {cg}"));
+    }
+    let fit = |n: u64| u32::try_from(n).map_err(|_| anyhow!("CIGAR too long: {cg}"));
+    let (q, t) = (fit(cigar.query_consumed())?, fit(cigar.ref_consumed())?);
+    let mut blocks = cigar
+        .iter_ref_blocks()
+        .map(|b| {
+            // on `-` the old interval is walked from its end
+            let (qo, to) = (b.query_offset as u32, b.ref_offset as u32);
+            let old_start = match strand {
+                Strand::Forward => old.0 + qo,
+                Strand::Reverse => old.1.saturating_sub(qo + b.len),
+            };
+            Block {
+                old_start,
+                old_len: b.len,
+                new_start: new_start + to,
+                new_len: b.len,
+            }
+        })
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|b| b.old_start);
+    Ok((blocks, q, t))
+}
+
+/// Where (part of) an interval landed on the new assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiftSegment {
+    pub new_contig: String,
+    pub start: u32,
+    pub end: u32,
+    /// Orientation relative to the old assembly (`-`: inverted).
+    pub strand: Strand,
+    /// Old bases that lifted into this segment.
+    pub old_bp: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiftStatus {
+    /// One segment carrying at least the minimum coverage.
+    Lifted,
+    /// One segment, but too few bases lifted (the rest fell in unaligned
+    /// sequence or insertions).
+    Partial,
+    /// Several segments: across new contigs, or partly inverted.
+    Split,
+    /// Nothing lifted.
+    Failed,
+}
+
+impl LiftStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LiftStatus::Lifted => "lifted",
+            LiftStatus::Partial => "partial",
+            LiftStatus::Split => "split",
+            LiftStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Result of lifting one interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lift {
+    pub status: LiftStatus,
+    /// Fraction of the interval's old bases that lifted anywhere.
+    pub coverage: f32,
+    /// Largest first.
+    pub segments: Vec<LiftSegment>,
+}
+
+/// All chains, by old contig.
+#[derive(Debug, Default)]
+pub struct LiftIndex {
+    by_old: HashMap<String, Vec<LiftChain>>,
+}
+
+/// Counts from reading an old→new PAF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiftPafStats {
+    pub n_chains: usize,
+    pub n_base_level: usize,
+    pub n_secondary_skipped: usize,
+}
+
+impl LiftIndex {
+    pub fn new(chains: Vec<LiftChain>) -> Self {
+        let mut by_old: HashMap<String, Vec<LiftChain>> = HashMap::new();
+        for c in chains {
+            by_old.entry(c.old_contig.clone()).or_default().push(c);
+        }
+        Self { by_old }
+    }
+
+    /// Read an old→new PAF. Secondary alignments (`tp:A:S`) are skipped unless
+    /// `keep_secondary`, since they would lift a locus to every paralog.
+    pub fn from_paf(path: &Path, keep_secondary: bool) -> Result<(Self, LiftPafStats)> {
+        let f = fs_err::File::open(path)?;
+        let mut chains = Vec::new();
+        let mut stats = LiftPafStats::default();
+        for (i, line) in BufReader::new(f).lines().enumerate() {
+            let lineno = i + 1;
+            let line = line.with_context(|| format!("read {}:{lineno}", path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let r = match PafReader::from_reader(line.as_bytes()).read_record() {
+                Ok(Some(r)) => r,
+                Ok(None) => continue,
+                Err(e) => {
+                    return Err(anyhow!(
+                        "malformed PAF record at {}:{lineno}: {e}",
+                        path.display()
+                    ))
+                }
+            };
+            // not `r.tp()` / `r.cg()`: they panic on a mistyped tag
+            let tag = |k: &str| r.optional_fields().get(k);
+            if let Some(paf::Tag::tp(t)) = tag("tp") {
+                if t.get_char() == Some(&'S') && !keep_secondary {
+                    stats.n_secondary_skipped += 1;
+                    continue;
+                }
+            }
+            let cg = match tag("cg") {
+                Some(paf::Tag::cg(t)) => t.get_string().map(String::as_str),
+                _ => None,
+            };
+            let chain = LiftChain::new(
+                r.query_name(),
+                (r.query_start(), r.query_end()),
+                r.target_name(),
+                (r.target_start(), r.target_end()),
+                Strand::from_paf(r.strand()),
+                cg,
+            )
+            .with_context(|| format!("{}:{lineno}", path.display()))?;
+            stats.n_chains += 1;
+            stats.n_base_level += chain.base_level as usize;
+            chains.push(chain);
+        }
+        Ok((Self::new(chains), stats))
+    }
+
+    /// Lift `[start, end)` on old contig `contig`.
+    pub fn lift(&self, contig: &str, start: u32, end: u32, min_coverage: f32) -> Lift {
+        let mut segments = Vec::new();
+        let mut covered: Vec<(u32, u32)> = Vec::new();
+        for chain in self.by_old.get(contig).into_iter().flatten() {
+            let (cs, ce) = chain.old_span();
+            if ce <= start || cs >= end {
+                continue;
+            }
+            let first = chain.blocks.partition_point(|b| b.old_end() <= start);
+            let mut seg: Option<LiftSegment> = None;
+            for b in chain.blocks[first..]
+                .iter()
+                .take_while(|b| b.old_start < end)
+            {
+                let (a, z) = (start.max(b.old_start), end.min(b.old_end()));
+                if a >= z {
+                    continue;
+                }
+                covered.push((a, z));
+                let (ns, ne) = b.map(a, z, chain.strand);
+                let s = seg.get_or_insert(LiftSegment {
+                    new_contig: chain.new_contig.clone(),
+                    start: ns,
+                    end: ne,
+                    strand: chain.strand,
+                    old_bp: 0,
+                });
+                s.start = s.start.min(ns);
+                s.end = s.end.max(ne);
+                s.old_bp += z - a;
+            }
+            segments.extend(seg);
+        }
+        segments.sort_by_key(|s| std::cmp::Reverse(s.old_bp));
+
+        let coverage = union_len(&mut covered) as f32 / end.saturating_sub(start).max(1) as f32;
+        let status = match segments.len() {
+            0 => LiftStatus::Failed,
+            1 if coverage >= min_coverage => LiftStatus::Lifted,
+            1 => LiftStatus::Partial,
+            _ => LiftStatus::Split,
+        };
+        Lift {
+            status,
+            coverage,
+            segments,
+        }
+    }
+}
+
+fn union_len(v: &mut [(u32, u32)]) -> u64 {
+    v.sort_unstable();
+    let mut total = 0u64;
+    let mut cur: Option<(u32, u32)> = None;
+    for &(s, e) in v.iter() {
+        match cur {
+            Some((cs, ce)) if s <= ce => cur = Some((cs, ce.max(e))),
+            Some((cs, ce)) => {
+                total += (ce - cs) as u64;
+                cur = Some((s, e));
+            }
+            None => cur = Some((s, e)),
+        }
+    }
+    if let Some((cs, ce)) = cur {
+        total += (ce - cs) as u64;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn seg(c: &str, start: u32, end: u32, strand: Strand, old_bp: u32) -> LiftSegment {
+        LiftSegment {
+            new_contig: c.into(),
+            start,
+            end,
+            strand,
+            old_bp,
+        }
+    }
+
+    #[test]
+    fn forward_cigar_with_indels_is_base_exact() {
+        // old chr1:1000-2000 → new ctgA:5000-5995
+        // blocks: old 1000..1100→5000, [10 bp insertion], 1110..1310→5100,
+        //         [5 bp deletion], 1310..2000→5305
+        let chain = LiftChain::new(
+            "chr1",
+            (1000, 2000),
+            "ctgA",
+            (5000, 5995),
+            Strand::Forward,
+            Some("100M10I200M5D690M"),
+        )
+        .unwrap();
+        assert!(chain.base_level);
+        let idx = LiftIndex::new(vec![chain]);
+
+        // across the insertion: its 10 bases don't lift
+        let l = idx.lift("chr1", 1050, 1150, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Partial);
+        assert!((l.coverage - 0.9).abs() < 1e-6);
+        assert_eq!(
+            l.segments,
+            vec![seg("ctgA", 5050, 5140, Strand::Forward, 90)]
+        );
+        // across the deletion: the deleted new bases fall inside the segment
+        let l = idx.lift("chr1", 1300, 1400, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Lifted);
+        assert_eq!(l.coverage, 1.0);
+        assert_eq!(l.segments[0], seg("ctgA", 5290, 5395, Strand::Forward, 100));
+        // single bases either side of the deletion
+        assert_eq!(idx.lift("chr1", 1309, 1310, 1.0).segments[0].start, 5299);
+        assert_eq!(idx.lift("chr1", 1310, 1311, 1.0).segments[0].start, 5305);
+        // overhanging the alignment's old end
+        let l = idx.lift("chr1", 1900, 2100, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Partial);
+        assert!((l.coverage - 0.5).abs() < 1e-6);
+        assert_eq!(l.segments[0], seg("ctgA", 5895, 5995, Strand::Forward, 100));
+    }
+
+    #[test]
+    fn reverse_cigar_maps_old_start_to_new_end() {
+        // old chr1:0-100 → new ctgB:1000-1105 on `-`: walking the target
+        // forward, 40M takes old 60..100 → new 1000..1040, 5D skips new
+        // 1040..1045, 60M takes old 0..60 → new 1045..1105
+        let idx = LiftIndex::new(vec![LiftChain::new(
+            "chr1",
+            (0, 100),
+            "ctgB",
+            (1000, 1105),
+            Strand::Reverse,
+            Some("40M5D60M"),
+        )
+        .unwrap()]);
+        let at = |s, e| idx.lift("chr1", s, e, LIFT_MIN_COVERAGE);
+        assert_eq!(
+            at(0, 10).segments,
+            vec![seg("ctgB", 1095, 1105, Strand::Reverse, 10)]
+        );
+        assert_eq!(at(0, 1).segments[0].start, 1104);
+        assert_eq!(at(99, 100).segments[0].start, 1000);
+        // across the deletion
+        let l = at(50, 70);
+        assert_eq!(l.status, LiftStatus::Lifted);
+        assert_eq!(
+            l.segments,
+            vec![seg("ctgB", 1030, 1055, Strand::Reverse, 20)]
+        );
+        // whole alignment
+        assert_eq!(
+            at(0, 100).segments[0],
+            seg("ctgB", 1000, 1105, Strand::Reverse, 100)
+        );
+    }
+
+    #[test]
+    fn reverse_cigar_with_insertion() {
+        // `-`, 30M 20I 50M: old 70..100 → new 0..30, old 50..70 inserted,
+        // old 0..50 → new 30..80
+        let idx = LiftIndex::new(vec![LiftChain::new(
+            "chr1",
+            (0, 100),
+            "n",
+            (0, 80),
+            Strand::Reverse,
+            Some("30M20I50M"),
+        )
+        .unwrap()]);
+        let l = idx.lift("chr1", 40, 80, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Partial);
+        assert!((l.coverage - 0.5).abs() < 1e-6);
+        assert_eq!(l.segments, vec![seg("n", 20, 40, Strand::Reverse, 20)]);
+    }
+
+    #[test]
+    fn without_cigar_interpolates_the_block() {
+        let chain =
+            LiftChain::new("chr2", (0, 1000), "x", (0, 2000), Strand::Forward, None).unwrap();
+        assert!(!chain.base_level);
+        let idx = LiftIndex::new(vec![
+            chain,
+            LiftChain::new("chr2", (2000, 3000), "y", (0, 500), Strand::Reverse, None).unwrap(),
+        ]);
+        assert_eq!(
+            idx.lift("chr2", 250, 500, 0.95).segments,
+            vec![seg("x", 500, 1000, Strand::Forward, 250)]
+        );
+        // ends are exact on either strand
+        assert_eq!(
+            idx.lift("chr2", 2000, 3000, 0.95).segments,
+            vec![seg("y", 0, 500, Strand::Reverse, 1000)]
+        );
+        assert_eq!(
+            idx.lift("chr2", 2000, 2200, 0.95).segments,
+            vec![seg("y", 400, 500, Strand::Reverse, 200)]
+        );
+    }
+
+    #[test]
+    fn split_across_contigs_and_inversions() {
+        // chr3 0..600 → ctgA forward, 600..1000 → ctgB inverted
+        let idx = LiftIndex::new(vec![
+            LiftChain::new(
+                "chr3",
+                (0, 600),
+                "ctgA",
+                (0, 600),
+                Strand::Forward,
+                Some("600M"),
+            )
+            .unwrap(),
+            LiftChain::new(
+                "chr3",
+                (600, 1000),
+                "ctgB",
+                (100, 500),
+                Strand::Reverse,
+                Some("400M"),
+            )
+            .unwrap(),
+        ]);
+        let l = idx.lift("chr3", 450, 700, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Split);
+        assert_eq!(l.coverage, 1.0);
+        assert_eq!(
+            l.segments,
+            vec![
+                seg("ctgA", 450, 600, Strand::Forward, 150),
+                seg("ctgB", 400, 500, Strand::Reverse, 100),
+            ]
+        );
+        // an inversion within one new contig is split too
+        let idx = LiftIndex::new(vec![
+            LiftChain::new("c", (0, 100), "n", (0, 100), Strand::Forward, Some("100M")).unwrap(),
+            LiftChain::new(
+                "c",
+                (100, 200),
+                "n",
+                (100, 200),
+                Strand::Reverse,
+                Some("100M"),
+            )
+            .unwrap(),
+            LiftChain::new(
+                "c",
+                (200, 300),
+                "n",
+                (200, 300),
+                Strand::Forward,
+                Some("100M"),
+            )
+            .unwrap(),
+        ]);
+        let l = idx.lift("c", 50, 250, LIFT_MIN_COVERAGE);
+        assert_eq!(l.status, LiftStatus::Split);
+        assert_eq!(l.segments.len(), 3);
+        assert_eq!(l.segments[0], seg("n", 100, 200, Strand::Reverse, 100));
+    }
+
+    #[test]
+    fn unaligned_intervals_fail() {
+        let idx = LiftIndex::new(vec![LiftChain::new(
+            "chr1",
+            (100, 200),
+            "n",
+            (0, 100),
+            Strand::Forward,
+            None,
+        )
+        .unwrap()]);
+        for (c, s, e) in [("chr1", 0, 100), ("chr1", 200, 300), ("chr9", 100, 200)] {
+            let l = idx.lift(c, s, e, LIFT_MIN_COVERAGE);
+            assert_eq!((l.status, l.coverage), (LiftStatus::Failed, 0.0));
+            assert!(l.segments.is_empty());
+        }
+    }
+
+    #[test]
+    fn bad_cigars_rejected() {
+        let chain = |cg| LiftChain::new("c", (0, 100), "n", (0, 100), Strand::Forward, Some(cg));
+        let e = chain("90M").unwrap_err().to_string();
+        assert!(e.contains("90 old / 90 new"), "{e}");
+        assert!(chain("50M5Q45M").is_err());
+        assert!(chain("100").is_err());
+        assert!(chain("5S95M").is_err());
+        assert!(chain("50=50X").is_ok());
+    }
+
+    #[test]
+    fn reads_paf_with_and_without_cg() {
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(
+            f,
+            "chr1\t5000\t1000\t2000\t+\tctgA\t9000\t5000\t5995\t990\t1000\t60\ttp:A:P\tcg:Z:100M10I200M5D690M\n\
+             chr1\t5000\t1000\t2000\t+\tctgZ\t9000\t0\t1000\t900\t1000\t0\ttp:A:S\n\
+             chr2\t3000\t0\t1000\t-\tctgB\t4000\t0\t1000\t1000\t1000\t60"
+        )
+        .unwrap();
+        let (idx, stats) = LiftIndex::from_paf(f.path(), false).unwrap();
+        assert_eq!(
+            stats,
+            LiftPafStats {
+                n_chains: 2,
+                n_base_level: 1,
+                n_secondary_skipped: 1
+            }
+        );
+        assert_eq!(
+            idx.lift("chr1", 1300, 1400, 0.95).segments[0],
+            seg("ctgA", 5290, 5395, Strand::Forward, 100)
+        );
+        assert_eq!(
+            idx.lift("chr2", 0, 10, 0.95).segments[0],
+            seg("ctgB", 990, 1000, Strand::Reverse, 10)
+        );
+        // keeping secondaries turns chr1 into a split
+        let (idx, _) = LiftIndex::from_paf(f.path(), true).unwrap();
+        assert_eq!(idx.lift("chr1", 1300, 1400, 0.95).status, LiftStatus::Split);
+    }
+}
@@ -1,22 +1,81 @@
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 
 #[derive(Parser)]
-#[command(name = "onsm", version, about = "Minimal NUMT/NIMT detector")]
+#[command(
+    name = "onsm",
+    version,
+    about = "Minimal NUMT/NIMT detector",
+    args_conflicts_with_subcommands = true
+)]
 struct Cli {
+    /// Print build provenance and minimap2/samtools versions as JSON
+    #[arg(long)]
+    version_json: bool,
+
     #[command(subcommand)]
-    cmd: Cmd,
+    cmd: Option<Cmd>,
 }
 
 #[derive(Subcommand)]
 enum Cmd {
-    Classify(onsm::subcommands::classify::CmdClassify),
-    Reuse(onsm::subcommands::reuse::CmdReuse),
+    /// One-shot pipeline: detect & score NUMTs vs NIMTs
+    Classify(Box<onsm::subcommands::classify::CmdClassify>),
+    /// Reuse existing PAF/BAM outputs to rescore without remapping
+    Reuse(Box<onsm::subcommands::reuse::CmdReuse>),
+    /// Filter and pair PAFs from any aligner, without reads or scoring
+    Pair(onsm::subcommands::pair::CmdPair),
+    /// Explain in plain language why a pair got its call
+    Explain(onsm::subcommands::explain::CmdExplain),
+    /// Descriptive statistics for an existing run directory
+    Stats(onsm::subcommands::stats::CmdStats),
+    /// Diagnose a failed or incomplete run directory
+    Doctor(onsm::subcommands::doctor::CmdDoctor),
+    /// Lift a run's nuclear loci onto a new assembly via an old→new PAF
+    Liftover(onsm::subcommands::liftover::CmdLiftover),
+    /// Write one of a run's assemblies with its called insertions masked
+    Mask(onsm::subcommands::mask::CmdMask),
+    /// Resolve nuclear loci claimed by the runs of several organelles
+    Reconcile(onsm::subcommands::reconcile::CmdReconcile),
+    /// Pack a run directory into one file to share without its BAMs
+    ExportBundle(onsm::subcommands::bundle::CmdExportBundle),
+    /// Unpack and check a bundle from `export-bundle`
+    ImportBundle(onsm::subcommands::bundle::CmdImportBundle),
+    /// Write a miniature example dataset with planted NUMTs
+    Example(onsm::subcommands::example::CmdExample),
+    /// Check environment, external tools, and features
+    Syscheck(onsm::subcommands::syscheck::CmdSyscheck),
+    /// Print the JSON Schema of a run artifact
+    #[cfg(feature = "schema")]
+    Schema(onsm::subcommands::schema::CmdSchema),
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    match cli.cmd {
-        Cmd::Classify(cmd) => cmd.run(),
-        Cmd::Reuse(cmd) => cmd.run(),
+    onsm::util::logging::init_stderr();
+    if cli.version_json {
+        return onsm::util::version::print_json();
+    }
+    let Some(cmd) = cli.cmd else {
+        Cli::command()
+            .error(ErrorKind::MissingSubcommand, "a subcommand is required")
+            .exit()
+    };
+    match cmd {
+        Cmd::Classify(cmd) => (*cmd).run(),
+        Cmd::Reuse(cmd) => (*cmd).run(),
+        Cmd::Pair(cmd) => cmd.run(),
+        Cmd::Explain(cmd) => cmd.run(),
+        Cmd::Stats(cmd) => cmd.run(),
+        Cmd::Doctor(cmd) => cmd.run(),
+        Cmd::Liftover(cmd) => cmd.run(),
+        Cmd::Mask(cmd) => cmd.run(),
+        Cmd::Reconcile(cmd) => cmd.run(),
+        Cmd::ExportBundle(cmd) => cmd.run(),
+        Cmd::ImportBundle(cmd) => cmd.run(),
+        Cmd::Example(cmd) => cmd.run(),
+        Cmd::Syscheck(cmd) => cmd.run(),
+        #[cfg(feature = "schema")]
+        Cmd::Schema(cmd) => cmd.run(),
     }
 }
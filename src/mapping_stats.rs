@@ -0,0 +1,321 @@
+//! How many reads mapped to each assembly (`samtools flagstat` on the
+//! reads→assembly BAMs).
+//!
+//! Every depth and span onsm measures assumes the reads come from the genome
+//! they are mapped to. When few of them map to the nuclear assembly (reads of
+//! another species, contamination, the wrong read set), local depths are low
+//! and noisy, and nothing else in the outputs says why. After mapping,
+//! `classify` records the counts of both BAMs in mapping_stats.json and the
+//! run manifest, logs the mapped fractions, and warns (`low_mapping_rate`,
+//! which `--strict` fails on) when the nuclear one is below
+//! `--min-mapped-frac`. The mito BAM is only recorded: mito reads are a small
+//! share of any read set, and the rest are unmapped there by design. Reads
+//! from the mitogenome count as unmapped on the nuclear side, which is why the
+//! default floor is well below what a clean read set gives.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::util::shutdown::{self, CancelToken};
+use crate::util::warnings;
+
+pub const MAPPING_STATS_FILE: &str = "mapping_stats.json";
+/// Default `--min-mapped-frac`.
+pub const DEFAULT_MIN_MAPPED_FRAC: f32 = 0.5;
+
+/// One BAM's `samtools flagstat` counts, QC-passed and QC-failed reads
+/// together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FlagStats {
+    /// Records: primary, secondary and supplementary.
+    pub total: u64,
+    /// One per read.
+    pub primary: u64,
+    pub secondary: u64,
+    pub supplementary: u64,
+    /// Mapped records of any kind.
+    pub mapped: u64,
+    /// Reads that mapped.
+    pub primary_mapped: u64,
+}
+
+impl FlagStats {
+    /// Parse flagstat's text output (`N + M label` lines). Releases before
+    /// 1.13 print no `primary` lines; the primary counts are then derived, as
+    /// secondary and supplementary records are always mapped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let (mut total, mut mapped) = (None, None);
+        let (mut primary, mut primary_mapped) = (None, None);
+        let mut s = FlagStats::default();
+        for line in text.lines() {
+            let Some((passed, rest)) = line.split_once(" + ") else {
+                continue;
+            };
+            let Some((failed, label)) = rest.split_once(' ') else {
+                continue;
+            };
+            let (Ok(passed), Ok(failed)) = (passed.trim().parse::<u64>(), failed.parse::<u64>())
+            else {
+                continue;
+            };
+            let n = passed + failed;
+            // "mapped (97.10% : N/A)": the percentages are recomputed
+            match label.split(" (").next().unwrap_or_default().trim() {
+                "in total" => total = Some(n),
+                "primary" => primary = Some(n),
+                "secondary" => s.secondary = n,
+                "supplementary" => s.supplementary = n,
+                "mapped" => mapped = Some(n),
+                "primary mapped" => primary_mapped = Some(n),
+                _ => {}
+            }
+        }
+        let (Some(total), Some(mapped)) = (total, mapped) else {
+            bail!("not samtools flagstat output (no \"in total\" and \"mapped\" lines)");
+        };
+        let not_primary = s.secondary + s.supplementary;
+        Ok(FlagStats {
+            total,
+            mapped,
+            primary: primary.unwrap_or(total.saturating_sub(not_primary)),
+            primary_mapped: primary_mapped.unwrap_or(mapped.saturating_sub(not_primary)),
+            ..s
+        })
+    }
+
+    /// Fraction of the reads that mapped; `None` without reads.
+    pub fn mapped_frac(&self) -> Option<f64> {
+        (self.primary > 0).then(|| self.primary_mapped as f64 / self.primary as f64)
+    }
+}
+
+/// `samtools flagstat` of one BAM; a cancel kills it.
+pub fn flagstat(
+    samtools: &Path,
+    bam: &Path,
+    threads: usize,
+    cancel: &CancelToken,
+) -> Result<FlagStats> {
+    let mut child = Command::new(samtools)
+        .arg("flagstat")
+        .arg("-@")
+        .arg(threads.max(1).to_string())
+        .arg(bam)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("spawn samtools flagstat for {}", bam.display()))?;
+    // a few hundred bytes: the pipes never fill while it runs
+    let status = shutdown::wait_child(&mut child, cancel)?;
+    let out = child.wait_with_output()?;
+    if !status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(anyhow!("samtools flagstat failed: {}", err.trim()));
+    }
+    FlagStats::parse(&String::from_utf8_lossy(&out.stdout))
+        .with_context(|| format!("samtools flagstat {}", bam.display()))
+}
+
+/// The counts of both reads→assembly BAMs (mapping_stats.json).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MappingStats {
+    pub nuclear: FlagStats,
+    pub mito: FlagStats,
+    /// `--min-mapped-frac` the nuclear fraction was checked against.
+    pub min_mapped_frac: f32,
+}
+
+impl MappingStats {
+    /// flagstat both BAMs.
+    pub fn collect(
+        samtools: &Path,
+        bam_nuclear: &Path,
+        bam_mito: &Path,
+        threads: usize,
+        min_mapped_frac: f32,
+        cancel: &CancelToken,
+    ) -> Result<Self> {
+        Ok(Self {
+            nuclear: flagstat(samtools, bam_nuclear, threads, cancel)?,
+            mito: flagstat(samtools, bam_mito, threads, cancel)?,
+            min_mapped_frac,
+        })
+    }
+
+    /// Log the mapped fractions, and warn when the nuclear one is below the
+    /// floor; true if it was.
+    pub fn check(&self) -> bool {
+        let pct = |s: &FlagStats| {
+            s.mapped_frac()
+                .map_or("no reads".to_string(), |f| format!("{:.1}%", 100.0 * f))
+        };
+        log::info!(
+            "MAPPING: {} of {} reads mapped to the nuclear assembly, {} to the mito",
+            pct(&self.nuclear),
+            self.nuclear.primary,
+            pct(&self.mito)
+        );
+        let frac = self.nuclear.mapped_frac().unwrap_or(0.0);
+        if frac >= f64::from(self.min_mapped_frac) {
+            return false;
+        }
+        warnings::warn(
+            warnings::LOW_MAPPING_RATE,
+            format!(
+                "only {} of {} reads mapped to the nuclear assembly (--min-mapped-frac {}); \
+                 depths and spans are unreliable: are these reads from this genome, or contaminated?",
+                pct(&self.nuclear),
+                self.nuclear.primary,
+                self.min_mapped_frac
+            ),
+        );
+        true
+    }
+
+    pub fn save_to(&self, dir: &Path) -> Result<()> {
+        let f = fs_err::File::create(dir.join(MAPPING_STATS_FILE))?;
+        serde_json::to_writer_pretty(f, self)?;
+        Ok(())
+    }
+
+    /// `None` if the run directory has no mapping_stats.json.
+    pub fn load_from(dir: &Path) -> Result<Option<Self>> {
+        let p = dir.join(MAPPING_STATS_FILE);
+        if !p.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_reader(fs_err::File::open(p)?)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// samtools ≥ 1.13.
+    const FLAGSTAT: &str = "\
+10500 + 20 in total (QC-passed reads + QC-failed reads)
+10000 + 20 primary
+300 + 0 secondary
+200 + 0 supplementary
+0 + 0 duplicates
+0 + 0 primary duplicates
+4400 + 5 mapped (41.90% : 25.00%)
+3900 + 5 primary mapped (39.00% : 25.00%)
+0 + 0 paired in sequencing
+0 + 0 read1
+0 + 0 read2
+0 + 0 properly paired (N/A : N/A)
+0 + 0 with itself and mate mapped
+0 + 0 singletons (N/A : N/A)
+0 + 0 with mate mapped to a different chr
+0 + 0 with mate mapped to a different chr (mapQ>=5)
+";
+
+    fn stats(nuclear: FlagStats) -> MappingStats {
+        MappingStats {
+            nuclear,
+            mito: FlagStats::default(),
+            min_mapped_frac: DEFAULT_MIN_MAPPED_FRAC,
+        }
+    }
+
+    #[test]
+    fn flagstat_text_parses() {
+        let s = FlagStats::parse(FLAGSTAT).unwrap();
+        assert_eq!(
+            s,
+            FlagStats {
+                total: 10_520,
+                primary: 10_020,
+                secondary: 300,
+                supplementary: 200,
+                mapped: 4_405,
+                primary_mapped: 3_905,
+            }
+        );
+        assert!((s.mapped_frac().unwrap() - 3_905.0 / 10_020.0).abs() < 1e-12);
+
+        // older releases: no primary lines
+        let old: String = FLAGSTAT
+            .lines()
+            .filter(|l| !l.contains("primary"))
+            .map(|l| format!("{l}\n"))
+            .collect();
+        assert_eq!(FlagStats::parse(&old).unwrap(), s);
+
+        // no reads: no fraction
+        let empty = "0 + 0 in total (QC-passed reads + QC-failed reads)\n\
+                     0 + 0 mapped (N/A : N/A)\n";
+        assert_eq!(FlagStats::parse(empty).unwrap().mapped_frac(), None);
+        for bad in ["", "samtools: command not found\n", "10 + 0 in total\n"] {
+            assert!(FlagStats::parse(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn low_nuclear_mapping_is_warned_about() {
+        let low = FlagStats::parse(FLAGSTAT).unwrap();
+        assert!(stats(low).check());
+        let good = FlagStats {
+            primary_mapped: 9_000,
+            ..low
+        };
+        assert!(!stats(good).check());
+        // the floor is the option's; an empty BAM is below any
+        assert!(MappingStats {
+            min_mapped_frac: 0.95,
+            ..stats(good)
+        }
+        .check());
+        assert!(stats(FlagStats::default()).check());
+        assert!(!MappingStats {
+            min_mapped_frac: 0.0,
+            ..stats(FlagStats::default())
+        }
+        .check());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn flagstat_runs_samtools_and_round_trips() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let samtools = dir.path().join("samtools");
+        let out = dir.path().join("flagstat.txt");
+        fs_err::write(&out, FLAGSTAT).unwrap();
+        fs_err::write(
+            &samtools,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> \"$(dirname \"$0\")/calls.log\"\ncase \"$4\" in\n\
+                 *bad.bam) echo \"bad.bam: truncated file\" >&2; exit 1 ;;\nesac\ncat {}\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let cancel = CancelToken::default();
+        let (nuc, mito) = (dir.path().join("r2n.bam"), dir.path().join("r2m.bam"));
+        let s = MappingStats::collect(&samtools, &nuc, &mito, 4, 0.5, &cancel).unwrap();
+        assert_eq!(s.nuclear, FlagStats::parse(FLAGSTAT).unwrap());
+        let calls = fs_err::read_to_string(dir.path().join("calls.log")).unwrap();
+        assert_eq!(
+            calls.lines().collect::<Vec<_>>(),
+            [
+                format!("flagstat -@ 4 {}", nuc.display()),
+                format!("flagstat -@ 4 {}", mito.display())
+            ]
+        );
+        let e = flagstat(&samtools, &dir.path().join("bad.bam"), 1, &cancel).unwrap_err();
+        assert!(format!("{e:#}").contains("truncated file"), "{e:#}");
+
+        assert_eq!(MappingStats::load_from(dir.path()).unwrap(), None);
+        s.save_to(dir.path()).unwrap();
+        assert_eq!(MappingStats::load_from(dir.path()).unwrap(), Some(s));
+    }
+}
@@ -0,0 +1,500 @@
+//! Mask the insertions a run called in one of its assemblies (`onsm mask`):
+//! the mito side of Likely_NIMT pairs, for curators submitting a mitogenome,
+//! or the nuclear side of Likely_NUMT pairs, e.g. before variant calling.
+//!
+//! Each interval is padded at both ends. On a circular mito contig (see
+//! `mito_meta`) padding past either end continues across the origin, so an
+//! insertion that the linear assembly splits into a locus at each end is
+//! masked as one stretch. On linear contigs, and in the nuclear assembly, it
+//! stops at the contig end. Bases that are already N (assembly gaps) are left
+//! as they are and counted apart.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use needletail::parse_fastx_file;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::io::fasta::{self, FastaCompression, FastaWriter};
+use crate::mito_meta::MitoContigMeta;
+use crate::model::orientation::Side;
+use crate::model::PairedLocus;
+use crate::scoring::{Call, ScoredPair};
+use crate::summary::Tier;
+
+pub const BASES_TSV_HEADER: &str = "contig\tpos\tstatus\tpair_ids";
+
+/// Which assembly to mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MaskGenome {
+    /// The mito intervals of Likely_NIMT pairs.
+    Mito,
+    /// The nuclear intervals of Likely_NUMT pairs.
+    Nuclear,
+}
+
+impl MaskGenome {
+    /// The call whose pairs are masked.
+    pub fn call(self) -> Call {
+        match self {
+            MaskGenome::Mito => Call::NIMT,
+            MaskGenome::Nuclear => Call::NUMT,
+        }
+    }
+
+    /// The side of a pair that is masked.
+    pub fn side(self) -> Side {
+        match self {
+            MaskGenome::Mito => Side::Mito,
+            MaskGenome::Nuclear => Side::Nuclear,
+        }
+    }
+
+    fn interval(self, l: &PairedLocus) -> (&str, u32, u32) {
+        match self {
+            MaskGenome::Mito => (&l.mito_contig, l.mito_start, l.mito_end),
+            MaskGenome::Nuclear => (&l.nuc_contig, l.nuc_start, l.nuc_end),
+        }
+    }
+}
+
+/// How masked bases are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MaskMode {
+    /// Lowercase, keeping the sequence.
+    #[default]
+    Soft,
+    /// `N`.
+    Hard,
+}
+
+/// Bases of the masked FASTA, over all contigs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaskCounts {
+    pub masked_bp: u64,
+    /// Bases inside a masked interval that were N already.
+    pub gap_bp: u64,
+}
+
+/// The intervals to mask, per contig, each with its pair.
+#[derive(Debug, Clone)]
+pub struct MaskPlan {
+    genome: MaskGenome,
+    pad: u32,
+    min_confidence: f32,
+    tier: Tier,
+    loci: HashMap<String, Vec<(u32, u32, String)>>,
+}
+
+impl MaskPlan {
+    pub fn new(genome: MaskGenome, pad: u32, min_confidence: f32) -> Self {
+        Self {
+            genome,
+            pad,
+            min_confidence,
+            tier: Tier::All,
+            loci: HashMap::new(),
+        }
+    }
+
+    /// Only the pairs of `tier` from `with_pairs`.
+    pub fn with_tier(mut self, tier: Tier) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    /// Every pair of the tier with the genome's call and at least the
+    /// minimum confidence.
+    pub fn with_pairs(mut self, pairs: &[ScoredPair]) -> Self {
+        for sp in pairs {
+            if self.selects(sp) {
+                self.add(&sp.locus, sp.score.call, sp.score.confidence);
+            }
+        }
+        self
+    }
+
+    /// Whether `with_pairs` masks `sp`.
+    pub fn selects(&self, sp: &ScoredPair) -> bool {
+        self.tier.includes(sp.score.high_confidence)
+            && sp.score.call == self.genome.call()
+            && sp.score.confidence >= self.min_confidence
+    }
+
+    pub fn add(&mut self, l: &PairedLocus, call: Call, confidence: f32) {
+        if call != self.genome.call() || confidence < self.min_confidence {
+            return;
+        }
+        let (contig, start, end) = self.genome.interval(l);
+        self.loci
+            .entry(contig.to_string())
+            .or_default()
+            .push((start, end, l.pair_id.clone()));
+    }
+
+    pub fn n_pairs(&self) -> usize {
+        self.loci.values().map(Vec::len).sum()
+    }
+
+    /// The padded intervals on `contig` as linear half-open pieces, each
+    /// with its pair; a padded interval across the origin is two.
+    pub fn pieces(&self, contig: &str, len: u32, circular: bool) -> Vec<(u32, u32, &str)> {
+        let mut out = Vec::new();
+        for (start, end, id) in self.loci.get(contig).into_iter().flatten() {
+            let s = i64::from(*start) - i64::from(self.pad);
+            let e = i64::from(*end) + i64::from(self.pad);
+            let n = i64::from(len);
+            let linear = if !circular || e - s >= n {
+                vec![(s.max(0), e.min(n))]
+            } else if s < 0 {
+                vec![(n + s, n), (0, e)]
+            } else if e > n {
+                vec![(s, n), (0, e - n)]
+            } else {
+                vec![(s, e)]
+            };
+            out.extend(
+                linear
+                    .into_iter()
+                    .filter(|(s, e)| s < e)
+                    .map(|(s, e)| (s as u32, e as u32, id.as_str())),
+            );
+        }
+        out.sort_unstable();
+        out
+    }
+}
+
+/// Mask `pieces` of `seq` in place (overlaps once).
+pub fn mask_sequence(seq: &mut [u8], pieces: &[(u32, u32, &str)], mode: MaskMode) -> MaskCounts {
+    let mut counts = MaskCounts::default();
+    let mut done = 0usize;
+    for &(s, e, _) in pieces {
+        let (s, e) = ((s as usize).max(done), (e as usize).min(seq.len()));
+        for b in seq.get_mut(s..e).into_iter().flatten() {
+            if b.eq_ignore_ascii_case(&b'N') {
+                counts.gap_bp += 1;
+                continue;
+            }
+            *b = match mode {
+                MaskMode::Soft => b.to_ascii_lowercase(),
+                MaskMode::Hard => b'N',
+            };
+            counts.masked_bp += 1;
+        }
+        done = done.max(e);
+    }
+    counts
+}
+
+/// Write `fasta_in` (plain or compressed) to `out` with the plan's intervals
+/// masked, compressed as `compression`; with `bases`, also one TSV line per
+/// base inside them: its position (0-based), `masked` or `gap`, and the pairs
+/// covering it.
+///
+/// Every contig the plan names must be in `fasta_in`.
+pub fn write_masked(
+    plan: &MaskPlan,
+    meta: &MitoContigMeta,
+    mode: MaskMode,
+    fasta_in: &Path,
+    out: &Path,
+    compression: FastaCompression,
+    bases: Option<&Path>,
+) -> Result<MaskCounts> {
+    let mut rdr =
+        parse_fastx_file(fasta_in).with_context(|| format!("open fasta {}", fasta_in.display()))?;
+    let mut w = FastaWriter::create(out, compression)?;
+    let mut bases_w = match bases {
+        Some(p) => {
+            let mut w = BufWriter::new(fs_err::File::create(p)?);
+            writeln!(w, "{BASES_TSV_HEADER}")?;
+            Some(w)
+        }
+        None => None,
+    };
+    let mut counts = MaskCounts::default();
+    let mut seen = 0;
+    while let Some(rec) = rdr
+        .next()
+        .transpose()
+        .with_context(|| format!("read fasta {}", fasta_in.display()))?
+    {
+        let id = String::from_utf8_lossy(rec.id()).to_string();
+        let mut seq = rec.seq().into_owned();
+        let circular = plan.genome == MaskGenome::Mito && meta.circular(&id);
+        let pieces = plan.pieces(&id, seq.len() as u32, circular);
+        if plan.loci.contains_key(&id) {
+            seen += 1;
+        }
+        if let Some(bw) = bases_w.as_mut() {
+            write_bases(bw, &id, &seq, &pieces)?;
+        }
+        let c = mask_sequence(&mut seq, &pieces, mode);
+        counts.masked_bp += c.masked_bp;
+        counts.gap_bp += c.gap_bp;
+        fasta::write_fasta_record(&mut w, &id, &seq)?;
+    }
+    if seen < plan.loci.len() {
+        let mut contigs: Vec<_> = plan.loci.keys().collect();
+        contigs.sort();
+        return Err(anyhow!(
+            "{} has no contig(s) named in the run's pairs (is it the run's assembly?); expected among: {}",
+            fasta_in.display(),
+            contigs
+                .iter()
+                .map(|c| c.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        ));
+    }
+    w.finish()?;
+    if let Some(mut bw) = bases_w {
+        bw.flush()?;
+    }
+    Ok(counts)
+}
+
+/// The `bases` TSV lines of one contig (before masking).
+fn write_bases(
+    w: &mut impl Write,
+    contig: &str,
+    seq: &[u8],
+    pieces: &[(u32, u32, &str)],
+) -> Result<()> {
+    let mut by_pos: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+    for &(s, e, id) in pieces {
+        for pos in s..e.min(seq.len() as u32) {
+            let ids = by_pos.entry(pos).or_default();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    for (pos, ids) in by_pos {
+        let status = if seq[pos as usize].eq_ignore_ascii_case(&b'N') {
+            "gap"
+        } else {
+            "masked"
+        };
+        writeln!(w, "{contig}\t{pos}\t{status}\t{}", ids.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn mito_locus(id: &str, contig: &str, start: u32, end: u32) -> PairedLocus {
+        PairedLocus {
+            pair_id: id.to_string(),
+            nuc_contig: "chr1".to_string(),
+            nuc_start: 5000,
+            nuc_end: 5000 + end - start,
+            mito_contig: contig.to_string(),
+            mito_start: start,
+            mito_end: end,
+            aln_len: end - start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn padding_wraps_the_origin_of_circular_contigs_only() {
+        let mut plan = MaskPlan::new(MaskGenome::Mito, 10, 0.5);
+        plan.add(&mito_locus("P1", "mt", 980, 1000), Call::NIMT, 0.9);
+        plan.add(&mito_locus("P2", "mt", 5, 30), Call::NIMT, 0.9);
+        // other calls, and confidence below the minimum, are not masked
+        plan.add(&mito_locus("P3", "mt", 400, 500), Call::NUMT, 0.9);
+        plan.add(&mito_locus("P4", "mt", 600, 700), Call::NIMT, 0.4);
+        assert_eq!(plan.n_pairs(), 2);
+        assert_eq!(
+            plan.pieces("mt", 1000, true),
+            [
+                (0, 10, "P1"),
+                (0, 40, "P2"),
+                (970, 1000, "P1"),
+                (995, 1000, "P2")
+            ]
+        );
+        assert_eq!(
+            plan.pieces("mt", 1000, false),
+            [(0, 40, "P2"), (970, 1000, "P1")]
+        );
+        // nuclear pairs are masked on their nuclear side
+        let mut nuc = MaskPlan::new(MaskGenome::Nuclear, 0, 0.0);
+        nuc.add(&mito_locus("P5", "mt", 0, 100), Call::NUMT, 0.0);
+        assert_eq!(nuc.pieces("chr1", 10_000, false), [(5000, 5100, "P5")]);
+    }
+
+    #[test]
+    fn high_tier_masks_high_confidence_pairs_only() {
+        use crate::control_region::ControlRegionOverlap;
+        use crate::io::fasta::GapStats;
+        use crate::model::{ClassifyParams, PairEvidence, Weights};
+        use crate::scoring::{score_pair, DepthBaselines};
+
+        // NIMT-like evidence: mito depth and spans, none on the nuclear side
+        let ev = PairEvidence {
+            depth: (2.0, 30.0),
+            span: (0.05, 0.9),
+            read_len: (0.0, 0.0),
+            read_div: Default::default(),
+            span_reads: None,
+            junction_clips: (0, 0),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let baselines = DepthBaselines {
+            nuclear_median: 30.0,
+            mito_median: 30.0,
+        };
+        let scored = |l: PairedLocus, high_confidence: bool| {
+            let mut sp = score_pair(
+                &l,
+                &ev,
+                GapStats::default(),
+                ControlRegionOverlap::None,
+                baselines,
+                Weights::default(),
+                ClassifyParams::default(),
+            );
+            assert_eq!(sp.score.call, Call::NIMT);
+            sp.score.high_confidence = high_confidence;
+            sp
+        };
+        let pairs = [
+            scored(mito_locus("P1", "mt", 100, 200), true),
+            scored(mito_locus("P2", "mt", 300, 400), false),
+        ];
+        let all = MaskPlan::new(MaskGenome::Mito, 0, 0.0).with_pairs(&pairs);
+        assert_eq!(all.n_pairs(), 2);
+        let high = MaskPlan::new(MaskGenome::Mito, 0, 0.0)
+            .with_tier(Tier::High)
+            .with_pairs(&pairs);
+        assert_eq!(high.pieces("mt", 1000, true), [(100, 200, "P1")]);
+    }
+
+    #[test]
+    fn masks_soft_or_hard_and_leaves_gaps() {
+        let pieces = [(1, 4, "P1"), (3, 6, "P2")];
+        let mut seq = b"ACGTNNAC".to_vec();
+        let c = mask_sequence(&mut seq, &pieces, MaskMode::Soft);
+        assert_eq!(seq, b"AcgtNNAC");
+        assert_eq!((c.masked_bp, c.gap_bp), (3, 2));
+        let mut seq = b"ACGTNNAC".to_vec();
+        mask_sequence(&mut seq, &pieces, MaskMode::Hard);
+        assert_eq!(seq, b"ANNNNNAC");
+    }
+
+    #[test]
+    fn wrap_around_nimt_on_a_circular_fixture() {
+        let td = TempDir::new().unwrap();
+        let fa = td.path().join("mito.fa");
+        let mt: String = "ACGT".repeat(25);
+        let pl: String = "TTGCA".repeat(8);
+        fs_err::write(&fa, format!(">mt\n{mt}\n>pl1\n{pl}\n")).unwrap();
+        // one NIMT split by the origin of mt into two loci; one at the end of
+        // the linear plasmid
+        let mut plan = MaskPlan::new(MaskGenome::Mito, 3, 0.0);
+        plan.add(&mito_locus("P1", "mt", 95, 100), Call::NIMT, 1.0);
+        plan.add(&mito_locus("P2", "mt", 0, 4), Call::NIMT, 1.0);
+        plan.add(&mito_locus("P3", "pl1", 36, 40), Call::NIMT, 1.0);
+        let meta = MitoContigMeta::parse("pl1\tplasmid\tno\n").unwrap();
+        let (out, bases) = (td.path().join("masked.fa"), td.path().join("bases.tsv"));
+        let c = write_masked(
+            &plan,
+            &meta,
+            MaskMode::Hard,
+            &fa,
+            &out,
+            FastaCompression::None,
+            Some(&bases),
+        )
+        .unwrap();
+
+        let masked = fasta::subsequence(&out, "mt", 0, 100).unwrap();
+        let n: Vec<usize> = (0..100).filter(|&i| masked[i] == b'N').collect();
+        let want: Vec<usize> = (0..7).chain(92..100).collect();
+        assert_eq!(n, want);
+        let pl_masked = fasta::subsequence(&out, "pl1", 0, 40).unwrap();
+        assert_eq!(&pl_masked[33..], b"NNNNNNN");
+        assert_eq!(&pl_masked[..33], &pl.as_bytes()[..33]);
+        assert_eq!(c.masked_bp, 15 + 7);
+
+        let tsv = fs_err::read_to_string(&bases).unwrap();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], BASES_TSV_HEADER);
+        assert_eq!(lines.len(), 1 + 22);
+        // padding of each locus reaches across the origin into the other
+        assert!(lines.contains(&"mt\t0\tmasked\tP1,P2"));
+        assert!(lines.contains(&"mt\t6\tmasked\tP2"));
+        assert!(lines.contains(&"mt\t97\tmasked\tP1,P2"));
+        assert!(lines.contains(&"pl1\t39\tmasked\tP3"));
+
+        // an assembly without the run's contigs is refused
+        let other = td.path().join("other.fa");
+        fs_err::write(&other, ">chrM\nACGT\n").unwrap();
+        let e = write_masked(
+            &plan,
+            &meta,
+            MaskMode::Soft,
+            &other,
+            &out,
+            FastaCompression::None,
+            None,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(e.contains("expected among: mt,pl1"), "{e}");
+    }
+
+    #[test]
+    fn bgzipped_assembly_in_and_out() {
+        let td = TempDir::new().unwrap();
+        let contigs = vec![("mt".to_string(), b"ACGT".repeat(25_000))];
+        let (plain, bgz) = (td.path().join("mito.fa"), td.path().join("mito.fa.gz"));
+        fasta::write_fasta(&plain, &contigs).unwrap();
+        fasta::write_fasta(&bgz, &contigs).unwrap();
+        let mut plan = MaskPlan::new(MaskGenome::Mito, 0, 0.0);
+        plan.add(&mito_locus("P1", "mt", 70_000, 70_100), Call::NIMT, 1.0);
+        let meta = MitoContigMeta::default();
+        let masked = |fa: &Path, out: &str, c: FastaCompression| {
+            let out = td.path().join(out);
+            write_masked(&plan, &meta, MaskMode::Hard, fa, &out, c, None).unwrap();
+            out
+        };
+        let want = fs_err::read(masked(&plain, "a.fa", FastaCompression::None)).unwrap();
+        assert_eq!(
+            fs_err::read(masked(&bgz, "b.fa", FastaCompression::None)).unwrap(),
+            want
+        );
+        for c in [FastaCompression::Gzip, FastaCompression::Bgzip] {
+            let out = masked(&bgz, "c.fa.gz", c);
+            assert_eq!(FastaCompression::detect(&out).unwrap(), c);
+            let mut got = Vec::new();
+            flate2::read::MultiGzDecoder::new(fs_err::File::open(&out).unwrap())
+                .read_to_end(&mut got)
+                .unwrap();
+            assert_eq!(got, want);
+            assert_eq!(
+                fasta::subsequence(&out, "mt", 69_999, 70_101).unwrap(),
+                [b"T".as_slice(), &[b'N'; 100], b"A"].concat()
+            );
+        }
+    }
+}
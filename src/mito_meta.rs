@@ -0,0 +1,169 @@
+//! Per-contig metadata for the mito assembly (`--mito-contig-meta`).
+//!
+//! Fungal and plant mitogenomes often ship with plasmid-like molecules or
+//! linear fragments next to the circular genome. They stay on the organelle
+//! side, but a TSV of `contig`, `type` and `circular` (yes/no) says which is
+//! which: each pair carries the `mito_molecule_type` of its mito contig,
+//! summary.tsv splits the NIMT figures by type, and a span window cut short at
+//! the start of a circular contig is reported as cut at its origin (reads
+//! across the origin are split in a linear BAM). Contigs not listed are
+//! circular and of type `mito`, as every contig was before.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Type of the contigs the metadata does not list.
+pub const DEFAULT_MOLECULE_TYPE: &str = "mito";
+
+/// One mito contig's molecule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Molecule {
+    /// e.g. `mito`, `plasmid`; used in summary metric names.
+    #[serde(rename = "type")]
+    pub molecule_type: String,
+    pub circular: bool,
+}
+
+/// The listed contigs by name (stored in `run_manifest.json`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct MitoContigMeta {
+    by_contig: BTreeMap<String, Molecule>,
+}
+
+impl MitoContigMeta {
+    /// From the TSV text: `contig<TAB>type<TAB>circular`, `circular` being
+    /// yes/no (or true/false). An optional header starts with `contig`; `#`
+    /// lines and blank lines are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut by_contig = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let f: Vec<&str> = line.split('\t').map(str::trim).collect();
+            if i == 0 && f[0] == "contig" {
+                continue;
+            }
+            let at = || format!("line {}", i + 1);
+            let [contig, molecule_type, circular] = f[..] else {
+                return Err(anyhow!(
+                    "{}: expected 3 tab-separated fields (contig, type, circular), got {}",
+                    at(),
+                    f.len()
+                ));
+            };
+            if molecule_type.is_empty()
+                || !molecule_type
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(anyhow!(
+                    "{}: type '{molecule_type}' must be letters, digits, '_' or '-'",
+                    at()
+                ));
+            }
+            let circular = match circular.to_ascii_lowercase().as_str() {
+                "yes" | "true" => true,
+                "no" | "false" => false,
+                _ => {
+                    return Err(anyhow!(
+                        "{}: circular must be yes or no, got '{circular}'",
+                        at()
+                    ))
+                }
+            };
+            let m = Molecule {
+                molecule_type: molecule_type.to_string(),
+                circular,
+            };
+            if by_contig.insert(contig.to_string(), m).is_some() {
+                return Err(anyhow!("{}: contig {contig} listed twice", at()));
+            }
+        }
+        Ok(Self { by_contig })
+    }
+
+    /// `parse` the file, refusing contigs the mito assembly does not have
+    /// (`mito_lens`), which are most likely typos.
+    pub fn load(path: &Path, mito_lens: &HashMap<String, u64>) -> Result<Self> {
+        let ctx = || format!("--mito-contig-meta {}", path.display());
+        let meta =
+            Self::parse(&fs_err::read_to_string(path).with_context(ctx)?).with_context(ctx)?;
+        if let Some(c) = meta.by_contig.keys().find(|c| !mito_lens.contains_key(*c)) {
+            return Err(anyhow!(
+                "{}: {c} is not a contig of the mito assembly",
+                ctx()
+            ));
+        }
+        Ok(meta)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_contig.is_empty()
+    }
+
+    pub fn molecule_type<'a>(&'a self, contig: &str) -> &'a str {
+        self.by_contig
+            .get(contig)
+            .map_or(DEFAULT_MOLECULE_TYPE, |m| m.molecule_type.as_str())
+    }
+
+    pub fn circular(&self, contig: &str) -> bool {
+        self.by_contig.get(contig).is_none_or(|m| m.circular)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_types_and_topology() {
+        let meta = MitoContigMeta::parse(
+            "contig\ttype\tcircular\n# the main genome\nmt\tmito\tyes\n\npl1\tplasmid\tNo\r\nfrag\tlinear_fragment\tfalse\n",
+        )
+        .unwrap();
+        assert_eq!(meta.molecule_type("pl1"), "plasmid");
+        assert!(!meta.circular("pl1"));
+        assert!(!meta.circular("frag"));
+        assert!(meta.circular("mt"));
+        // unlisted contigs keep the old behaviour
+        assert_eq!(meta.molecule_type("other"), DEFAULT_MOLECULE_TYPE);
+        assert!(meta.circular("other"));
+        assert!(MitoContigMeta::parse("").unwrap().is_empty());
+
+        // round trip through run_manifest.json
+        let json = serde_json::to_string(&meta).unwrap();
+        assert!(json.starts_with(r#"{"frag":{"type":"linear_fragment","circular":false}"#));
+        assert_eq!(serde_json::from_str::<MitoContigMeta>(&json).unwrap(), meta);
+    }
+
+    #[test]
+    fn malformed_rows_are_errors() {
+        for (text, msg) in [
+            ("mt\tmito\n", "line 1: expected 3"),
+            (
+                "mt\tmito\tyes\nmt\tplasmid\tno\n",
+                "line 2: contig mt listed twice",
+            ),
+            ("mt\tmito\tmaybe\n", "circular must be yes or no"),
+            ("mt\tmy plasmid\tyes\n", "type 'my plasmid' must be"),
+            ("mt\t\tyes\n", "type '' must be"),
+        ] {
+            let e = MitoContigMeta::parse(text).unwrap_err().to_string();
+            assert!(e.contains(msg), "{text:?}: {e}");
+        }
+
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut f, b"mt\tmito\tyes\npl\tplasmid\tno\n").unwrap();
+        let lens: HashMap<String, u64> = [("mt".to_string(), 16_000)].into();
+        let e = format!("{:#}", MitoContigMeta::load(f.path(), &lens).unwrap_err());
+        assert!(e.contains("pl is not a contig of the mito assembly"), "{e}");
+    }
+}
@@ -2,23 +2,65 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use crate::compat::OutputCompat;
+use crate::control_region::ControlRegions;
+use crate::io::bam::WindowError;
+use crate::io::paf::{AlnFormat, IdentityKind};
+use crate::io::reads::ReadStats;
+use crate::mapping_stats::MappingStats;
+use crate::mito_meta::MitoContigMeta;
+use crate::pairing::PairingMethod;
+use crate::self_assembly::{IdentitySample, SelfAssembly};
+use crate::summary::GenomeSizes;
+use crate::util::mapping::AsmMapOptions;
+use crate::util::version::VersionInfo;
+
+pub mod orientation;
+
+use orientation::Strand;
+
 /// Default algorithm constants (few knobs, sensible defaults)
 pub const MIN_ID: f32 = 0.90;
 pub const MIN_LEN: u32 = 100;
+pub const MIN_PAF_MAPQ: u8 = 0; // assembly alignments kept whatever their MAPQ
 pub const MERGE_GAP: u32 = 50;
+pub const RECIPROCAL_MIN_OVERLAP: f32 = 0.0; // any overlapping hit counts as reciprocal
+pub const COLLAPSE_MIN_OVERLAP: f32 = 0.8; // nuclear overlap (of the shorter locus) that collapses loci
+pub const SPLIT_INDEL: u32 = 200; // assembly alignments are split at longer indels
 pub const FLANK_BP: u32 = 500; // window half-width
 pub const WIN_BP: u32 = 250; // “spanning” sub-window half-width
+pub const MIN_MAPQ: u8 = 20; // reads counted for spans and read lengths (`--span-mapq`)
+pub const MAX_READS_PER_WINDOW: u32 = 2_000; // span windows deeper than this are subsampled
+/// Span windows cut by a contig end to fewer bases than this are flagged
+/// `short_window`: too little of the locus is left to span.
+pub const MIN_SPAN_WINDOW_BP: u32 = 250;
+/// Reads clipped by at least this many bases (`--junction-min-clip`)…
+pub const JUNCTION_MIN_CLIP: u32 = 100;
+/// …where they end within this many bp of a nuclear locus end are junction reads.
+pub const JUNCTION_WINDOW_BP: u32 = 25;
 pub const CALL_THRESHOLD: f32 = 0.15;
 pub const HIGHCONF_THRESHOLD: f32 = 0.30;
+/// Estimated nuclear copy number at/above which a locus is flagged `possible_collapsed_repeat`
+pub const COLLAPSED_REPEAT_CN: f32 = 1.5;
+/// A locus otherwise Ambiguous is `Shared_Support` when reads span it on both
+/// sides at least this often…
+pub const SHARED_MIN_SPAN: f32 = 0.5;
+/// …and both depth ratios are within this of 1.
+pub const SHARED_DEPTH_TOL: f32 = 0.25;
+/// Identity weight of pairs inside a mito control region, relative to `W_A`
+/// (`--control-region-identity-weight`; 1 = unchanged).
+pub const CONTROL_REGION_IDENTITY_WEIGHT: f32 = 1.0;
 
 // Scoring weights
 pub const W_A: f32 = 0.25; // alignment identity
 pub const W_L: f32 = 0.15; // alignment length (soft-saturated)
 pub const W_D: f32 = 0.25; // depth consistency
 pub const W_S: f32 = 0.25; // spanning support
+pub const W_J: f32 = 0.0; // junction reads (reported, not yet scored)
 
 /// A paired locus after reciprocal mapping/merging.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PairedLocus {
     pub pair_id: String,
     pub nuc_contig: String,
@@ -29,39 +71,419 @@ pub struct PairedLocus {
     pub mito_end: u32,
     pub aln_len: u32,
     pub aln_ident: f32, // [0,1]
+    /// PAF strand; `-` reverses the nuclear↔mito correspondence (see `orientation`).
+    /// Absent in older pair stores/results → `+`.
+    #[serde(default)]
+    pub strand: Strand,
+    /// Raw alignments merged or collapsed into this locus (see
+    /// `pairing::finalize_loci`). Absent in older pair stores/results → 1.
+    #[serde(default = "one")]
+    pub n_support_alignments: u32,
+    /// Which identity `aln_ident` is (`--aln-identity`). Absent in older pair
+    /// stores/results → BLAST identity, the only one then.
+    #[serde(default)]
+    pub aln_ident_kind: IdentityKind,
+    /// ± bp at the nuclear start and end (see `boundary`). Absent in older
+    /// pair stores/results → 0.
+    #[serde(default)]
+    pub nuc_start_ci: u32,
+    #[serde(default)]
+    pub nuc_end_ci: u32,
+    /// Whether a nuclear→mito alignment backs the locus as well as a
+    /// mito→nuclear one (see `pairing`). Absent in older pair stores/results
+    /// → true, as they were scored without the distinction.
+    #[serde(default = "yes")]
+    pub reciprocal: bool,
+    /// The alignment this locus is a piece of, when it was split at a long
+    /// indel (`--split-indel`): its nuclear interval as `contig:start-end`,
+    /// shared by every piece. Absent → not split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// `--reciprocal-best`: whether the chosen nuclear→mito hit is also the
+    /// most identical one covering the nuclear interval (see `pairing`).
+    /// Absent → not checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rbh: Option<bool>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// Parameters shared by every pairing strategy (see `crate::pairing`).
+///
+/// The one place a pairing setting is declared: `classify` and `pair` take
+/// their flags from it (`#[command(flatten)]`), the run manifest stores it
+/// (flattened, under the keys older manifests had), and `reuse` compares its
+/// overrides with it, so a setting cannot be accepted and then ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, clap::Args)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PairingParams {
+    /// Loci on the same contigs and strand within this many bp on both
+    /// genomes are merged (see `pairing::finalize_loci`).
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = MERGE_GAP,
+        help = "Merge loci on the same contigs and strand whose nuclear and mito intervals overlap or lie within this many bp (minimap2 often splits one insertion at its indels)"
+    )]
+    pub merge_gap: u32,
+    /// Reciprocal pairing: a nuclear→mito hit only counts as the reciprocal of
+    /// a mito→nuclear one if they overlap by at least this fraction on both
+    /// genomes (0 = any overlap).
+    #[serde(default)]
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = RECIPROCAL_MIN_OVERLAP,
+        value_parser = crate::pairing::parse_fraction,
+        help = "Reciprocal pairing: minimum overlap (0-1, on both genomes) for a nuclear→mito hit to count as reciprocal; 0 = any hit overlapping it on both genomes"
+    )]
+    pub reciprocal_min_overlap: f32,
+    /// Loci whose nuclear intervals overlap by at least this fraction of the
+    /// shorter one collapse into the most identical (0 = off; see
+    /// `pairing::finalize_loci`).
+    #[serde(default)]
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = COLLAPSE_MIN_OVERLAP,
+        value_parser = crate::pairing::parse_fraction,
+        help = "Collapse loci whose nuclear intervals overlap by at least this fraction of the shorter (e.g. hits on a tandem repeat) into the most identical; 0 = off"
+    )]
+    pub collapse_min_overlap: f32,
+    /// Reciprocal pairing: check each locus's reciprocal hit for being the
+    /// best hit of its nuclear interval (`rbh`).
+    #[serde(default)]
+    #[arg(
+        long,
+        help = "Reciprocal pairing: flag loci whose nuclear→mito hit is not the most identical one covering half their nuclear interval (rbh=false); with --require-reciprocal they are set aside too"
+    )]
+    pub reciprocal_best: bool,
+}
+
+impl Default for PairingParams {
+    fn default() -> Self {
+        Self {
+            merge_gap: MERGE_GAP,
+            reciprocal_min_overlap: RECIPROCAL_MIN_OVERLAP,
+            collapse_min_overlap: COLLAPSE_MIN_OVERLAP,
+            reciprocal_best: false,
+        }
+    }
+}
+
+/// Settings the read evidence was collected with. Evidence from other
+/// settings is not comparable, so scoring refuses it (see `pipeline`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EvidenceParams {
+    /// Depth window half-width.
+    pub flank_bp: u32,
+    /// Spanning window half-width.
+    pub win_bp: u32,
+    /// Lowest MAPQ of the reads counted for spans (`--span-mapq`).
+    pub min_mapq: u8,
+    /// Whether supplementary alignments counted towards depth
+    /// (`--depth-include-supplementary`; absent → they did, as before).
+    #[serde(default = "default_depth_supplementary")]
+    pub depth_supplementary: bool,
+    /// `--junction-min-clip`; 0 = junction reads not counted (absent → 0,
+    /// as before).
+    #[serde(default)]
+    pub junction_min_clip: u32,
+}
+
+impl EvidenceParams {
+    /// Depth without supplementary alignments and junction reads counted,
+    /// the defaults.
+    pub fn new(flank_bp: u32, win_bp: u32) -> Self {
+        Self {
+            flank_bp,
+            win_bp,
+            min_mapq: MIN_MAPQ,
+            depth_supplementary: false,
+            junction_min_clip: JUNCTION_MIN_CLIP,
+        }
+    }
+
+    pub fn with_depth_supplementary(mut self, yes: bool) -> Self {
+        self.depth_supplementary = yes;
+        self
+    }
+
+    pub fn with_min_mapq(mut self, q: u8) -> Self {
+        self.min_mapq = q;
+        self
+    }
+
+    pub fn with_junction_min_clip(mut self, bp: u32) -> Self {
+        self.junction_min_clip = bp;
+        self
+    }
+
+    /// The settings that differ from `expected`, as `name found → expected`.
+    pub fn diff(&self, expected: &Self) -> Vec<String> {
+        let mut d = Vec::new();
+        let mut cmp = |name: &str, found: u32, want: u32| {
+            if found != want {
+                d.push(format!("{name} {found} → {want}"));
+            }
+        };
+        cmp("flank_bp", self.flank_bp, expected.flank_bp);
+        cmp("win_bp", self.win_bp, expected.win_bp);
+        cmp("min_mapq", self.min_mapq as u32, expected.min_mapq as u32);
+        cmp(
+            "junction_min_clip",
+            self.junction_min_clip,
+            expected.junction_min_clip,
+        );
+        if self.depth_supplementary != expected.depth_supplementary {
+            d.push(format!(
+                "depth_supplementary {} → {}",
+                self.depth_supplementary, expected.depth_supplementary
+            ));
+        }
+        d
+    }
 }
 
 /// Depth/coverage summary.
 /// `per_pair[pid] = (nuc_local_median_depth, mito_local_median_depth)`
 /// Medians are *absolute* here; scoring will normalize by the genome-wide medians below.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CoverageSummary {
     pub nuclear_median: f64,
     pub mito_median: f64,
+    /// (nuclear, mito) median of the pairs' local medians, the baselines
+    /// before genome-wide sampling, kept for comparison (absent in
+    /// coverage.json from older runs, whose baselines these were).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loci_medians: Option<(f64, f64)>,
     pub per_pair: HashMap<String, (f32, f32)>,
+    /// How the depths were collected (absent in coverage.json from older runs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<EvidenceParams>,
 }
 
 /// Spanning-read support summary.
 /// `per_pair[pid] = (frac_spanning_nuc_window, frac_spanning_mito_window)` in [0,1].
+/// `read_len[pid]` = median reference-consumed length (bp) of the reads in each
+/// span window (0 = no reads / not measured).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpanSummary {
     pub per_pair: HashMap<String, (f32, f32)>,
+    #[serde(default)]
+    pub read_len: HashMap<String, (f32, f32)>,
+    /// `read_div[pid]` = median read divergence (NM / alignment columns) in
+    /// each span window; `None` when no read there had an NM tag.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub read_div: HashMap<String, (Option<f32>, Option<f32>)>,
+    /// `reads[pid]` = the reads behind the two fractions (empty when unknown).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub reads: HashMap<String, (SpanReads, SpanReads)>,
+    /// `invalid_window[pid]` = why the pair has no evidence windows (see
+    /// `PairEvidence::invalid_window`).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub invalid_window: HashMap<String, WindowError>,
+    /// `junction_clips[pid]` = junction reads at the nuclear start and end
+    /// (see `PairEvidence::junction_clips`); absent when there are none or
+    /// they were not counted.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub junction_clips: HashMap<String, (u32, u32)>,
+    /// `clamped_window[pid]` = widths of span windows a contig end cut (see
+    /// `PairEvidence::clamped_window`); absent for full windows.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub clamped_window: HashMap<String, (u32, u32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<EvidenceParams>,
+    /// `cost[pid]` = what collecting the pair's evidence took, and `stage_cost`
+    /// the whole evidence stage (empty unless collected in this run).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub cost: HashMap<String, EvidenceCost>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage_cost: Option<EvidenceCost>,
+}
+
+/// Wall time and subprocesses (samtools calls; cached windows need none) spent
+/// collecting evidence, for one pair or a whole stage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EvidenceCost {
+    pub cost_ms: f64,
+    pub n_subprocess_calls: u64,
+}
+
+impl EvidenceCost {
+    pub fn add(&mut self, other: EvidenceCost) {
+        self.cost_ms += other.cost_ms;
+        self.n_subprocess_calls += other.n_subprocess_calls;
+    }
+}
+
+/// Raw (unnormalized) read evidence for one pair; each field is (nuclear, mito).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PairEvidence {
+    /// Local median depths (without supplementary alignments unless
+    /// `EvidenceParams::depth_supplementary`).
+    pub depth: (f32, f32),
+    /// Fractions of reads spanning the window.
+    pub span: (f32, f32),
+    /// Median reference-consumed read length in the span window (absent in
+    /// older caches → 0, unknown).
+    #[serde(default)]
+    pub read_len: (f32, f32),
+    /// Median read divergence in the span window, over the reads with an NM
+    /// tag (absent in older caches → unknown).
+    #[serde(default)]
+    pub read_div: (Option<f32>, Option<f32>),
+    /// Reads behind `span` and `read_len` (absent in older caches).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span_reads: Option<(SpanReads, SpanReads)>,
+    /// Reads ending within `JUNCTION_WINDOW_BP` of the nuclear start and of
+    /// the nuclear end with a clip of at least `--junction-min-clip` bases
+    /// there: the (start, end) junctions of an insertion (absent in older
+    /// caches → 0, not counted).
+    #[serde(default)]
+    pub junction_clips: (u32, u32),
+    /// Bases of the nuclear and mito span windows, when a contig end cut
+    /// either short of 2 × `win_bp`: the interval `span` was measured over
+    /// (absent → both full).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamped_window: Option<(u32, u32)>,
+    /// Set when the pair's windows could not be built: everything else is
+    /// then zero, and the pair is called Ambiguous (`invalid_window`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invalid_window: Option<WindowError>,
+}
+
+/// Alignments in a span window passing `--span-mapq`, and how many of them
+/// the span fraction and read length were estimated from: a uniform sample
+/// of `--max-reads-per-window` when the window has more.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanReads {
+    pub total: u32,
+    pub sampled: u32,
+    /// Sampled reads without an NM tag, left out of the read divergence.
+    #[serde(default)]
+    pub without_nm: u32,
+    /// Of `total`, primary and supplementary alignments (the rest are
+    /// secondary); absent in older caches.
+    #[serde(default)]
+    pub primary: u32,
+    #[serde(default)]
+    pub supplementary: u32,
+    /// Mapped alignments left out for a MAPQ below `--span-mapq`, not in
+    /// `total`.
+    #[serde(default)]
+    pub low_mapq: u32,
+}
+
+impl SpanReads {
+    pub fn subsampled(self) -> bool {
+        self.sampled < self.total
+    }
 }
 
 /// Immutable scoring params (constants exposed here).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ClassifyParams {
     pub call_threshold: f32,
     pub highconf_threshold: f32,
+    /// Absent in results.json from older runs → default.
+    #[serde(default = "default_collapsed_repeat_cn")]
+    pub collapsed_repeat_cn: f32,
+    /// Spanning window width (2 × win), compared with the median read length.
+    #[serde(default = "default_span_window")]
+    pub span_window: u32,
+    /// Scale the span weight by median read length / `span_window` when reads
+    /// are shorter than the window.
+    #[serde(default)]
+    pub span_read_len_scaling: bool,
+    /// Span weight forced to 0 because the reads are too short to span loci
+    /// (`--allow-short-reads`).
+    #[serde(default)]
+    pub span_evidence_disabled: bool,
+    /// The mito was assembled from the run's reads (declared or detected):
+    /// NIMT calls get `self_assembly_caveat`.
+    #[serde(default)]
+    pub self_assembly_caveat: bool,
+    /// `--max-softmask-frac`: loci more soft-masked than this are called
+    /// Ambiguous (`softmasked_locus`).
+    #[serde(default)]
+    pub max_softmask_frac: Option<f32>,
+    /// Both-sides-strong pattern that makes an Ambiguous locus `Shared_Support`
+    /// (`--shared-min-span`, `--shared-depth-tol`).
+    #[serde(default = "default_shared_min_span")]
+    pub shared_min_span: f32,
+    #[serde(default = "default_shared_depth_tol")]
+    pub shared_depth_tol: f32,
+    /// Count `Shared_Support` bp as NUMT (nuclear) and NIMT (mito) in the
+    /// summary percentages (`--shared-in-percentages`).
+    #[serde(default)]
+    pub shared_in_percentages: bool,
+    /// Multiplies `w_a` for pairs whose mito side lies inside a control
+    /// region (`--control-region-identity-weight`).
+    #[serde(default = "default_control_region_identity_weight")]
+    pub control_region_identity_weight: f32,
+    /// The loci came from a hand-edited list (`reuse --pairs-from`), not
+    /// from pairing; the summary says so (`manual_pair_list`).
+    #[serde(default)]
+    pub manual_pair_list: bool,
+}
+
+/// Evidence and manifests from before `--depth-include-supplementary`
+/// counted supplementary alignments towards depth.
+fn default_depth_supplementary() -> bool {
+    true
+}
+
+fn default_min_span_mapq() -> u8 {
+    MIN_MAPQ
+}
+
+/// Runs from before `--keep-secondary` paired secondary alignments.
+fn default_keep_secondary() -> bool {
+    true
+}
+
+fn default_collapsed_repeat_cn() -> f32 {
+    COLLAPSED_REPEAT_CN
+}
+
+fn default_shared_min_span() -> f32 {
+    SHARED_MIN_SPAN
+}
+
+fn default_shared_depth_tol() -> f32 {
+    SHARED_DEPTH_TOL
+}
+
+fn default_control_region_identity_weight() -> f32 {
+    CONTROL_REGION_IDENTITY_WEIGHT
+}
+
+fn default_span_window() -> u32 {
+    2 * WIN_BP
 }
 
 /// Weights (pulled from constants)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Weights {
     pub w_a: f32,
     pub w_l: f32,
     pub w_d: f32,
     pub w_s: f32,
+    /// Junction reads (absent in results.json from older runs → 0).
+    #[serde(default)]
+    pub w_j: f32,
 }
 
 impl Default for Weights {
@@ -71,6 +493,7 @@ impl Default for Weights {
             w_l: crate::model::W_L,
             w_d: crate::model::W_D,
             w_s: crate::model::W_S,
+            w_j: crate::model::W_J,
         }
     }
 }
@@ -80,11 +503,23 @@ impl Default for ClassifyParams {
         Self {
             call_threshold: CALL_THRESHOLD,
             highconf_threshold: HIGHCONF_THRESHOLD,
+            collapsed_repeat_cn: COLLAPSED_REPEAT_CN,
+            span_window: default_span_window(),
+            span_read_len_scaling: false,
+            span_evidence_disabled: false,
+            self_assembly_caveat: false,
+            max_softmask_frac: None,
+            shared_min_span: SHARED_MIN_SPAN,
+            shared_depth_tol: SHARED_DEPTH_TOL,
+            shared_in_percentages: false,
+            control_region_identity_weight: CONTROL_REGION_IDENTITY_WEIGHT,
+            manual_pair_list: false,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RunManifest {
     pub mito: PathBuf,
     pub nuclear: PathBuf,
@@ -95,12 +530,133 @@ pub struct RunManifest {
     // fixed thresholds used by your simplified pipeline
     pub min_id: f32,
     pub min_len: u32,
-    pub merge_gap: u32,
+    /// `merge_gap`, `reciprocal_min_overlap`, `collapse_min_overlap` and
+    /// `reciprocal_best` (absent → 0, 0 and false; older runs did not use
+    /// them). `merge_gap` is in every manifest.
+    #[serde(flatten)]
+    pub pairing_params: PairingParams,
     pub flank_bp: u32,
     pub win_bp: u32,
+
+    /// Pairing strategy (absent in older manifests → reciprocal).
+    #[serde(default)]
+    pub pairing: PairingMethod,
+
+    /// minimap2 asm→asm secondary settings (absent → minimap2 defaults).
+    #[serde(default)]
+    pub mm2_asm: AsmMapOptions,
+
+    /// Nuclear contigs found to be the mito itself and left out of pairing
+    /// and the nuclear total (absent → none).
+    #[serde(default)]
+    pub excluded_nuclear: Vec<String>,
+
+    /// `--split-indel`: alignments were split at longer indels (absent → 0,
+    /// older runs did not split).
+    #[serde(default)]
+    pub split_indel: u32,
+
+    /// The onsm build (and tools) that made the run (absent in older manifests).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_info: Option<VersionInfo>,
+
+    /// Read lengths seen before mapping (absent in older manifests).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_stats: Option<ReadStats>,
+
+    /// Reads mapped to each assembly (also in mapping_stats.json; absent in
+    /// older manifests).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping_stats: Option<MappingStats>,
+
+    /// The reads were too short for span evidence and `--allow-short-reads`
+    /// was given; reuse keeps the span weight at 0 (absent → false).
+    #[serde(default)]
+    pub span_evidence_disabled: bool,
+
+    /// Assembly alignments given with `--m2n-aln`/`--n2m-aln` instead of
+    /// minimap2's PAFs in tmp/ (absent → those), and their format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub m2n_aln: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n2m_aln: Option<PathBuf>,
+    #[serde(default)]
+    pub aln_format: AlnFormat,
+    /// `--aln-identity`; absent → BLAST identity, the only one then.
+    #[serde(default)]
+    pub aln_identity: IdentityKind,
+
+    /// Whether the mito was assembled from these reads
+    /// (`--mito-assembled-from-these-reads`, or detected); absent → no.
+    #[serde(default)]
+    pub self_assembly: SelfAssembly,
+
+    /// Read identity and depth sampled for the self-assembly check (absent in
+    /// older manifests).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_identity: Option<IdentitySample>,
+
+    /// `--min-paf-mapq` (absent → 0, no MAPQ filter, as before).
+    #[serde(default)]
+    pub min_paf_mapq: u8,
+
+    /// `--keep-secondary` (absent → kept: older runs paired every record).
+    #[serde(default = "default_keep_secondary")]
+    pub keep_secondary: bool,
+
+    /// `--boundary-clips`: the CIs include the clip dispersion.
+    #[serde(default)]
+    pub boundary_clips: bool,
+
+    /// `--require-reciprocal`: one-sided loci went to pairs_unsupported.tsv
+    /// rather than being scored (absent → scored, as before).
+    #[serde(default)]
+    pub require_reciprocal: bool,
+
+    /// `--output-compat` (absent → latest).
+    #[serde(default)]
+    pub output_compat: OutputCompat,
+
+    /// `--max-reads-per-window` (0 = no cap, as in older manifests).
+    #[serde(default)]
+    pub max_reads_per_window: u32,
+
+    /// `--depth-include-supplementary` (absent → included, as before).
+    #[serde(default = "default_depth_supplementary")]
+    pub depth_supplementary: bool,
+
+    /// `--span-mapq` (absent → 20, as before).
+    #[serde(default = "default_min_span_mapq")]
+    pub min_span_mapq: u8,
+
+    /// `--junction-min-clip` (absent → 0, junction reads not counted, as
+    /// before).
+    #[serde(default)]
+    pub junction_min_clip: u32,
+
+    /// `--mito-contig-meta`: molecule type and topology of the listed mito
+    /// contigs (absent → every contig circular, type `mito`).
+    #[serde(default, skip_serializing_if = "MitoContigMeta::is_empty")]
+    pub mito_contig_meta: MitoContigMeta,
+
+    /// `--mito-control-region`s (absent → none).
+    #[serde(default, skip_serializing_if = "ControlRegions::is_empty")]
+    pub mito_control_regions: ControlRegions,
+
+    /// Assembly totals and contig lengths, so the summary can be computed
+    /// without the FASTAs (absent in older runs → read from them).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genome_sizes: Option<GenomeSizes>,
+
+    /// `reuse --pairs-from`: the hand-edited pair list the loci were read
+    /// from instead of being paired (absent → paired). Only in the manifest
+    /// such a reuse writes to its output directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairs_from: Option<PathBuf>,
 }
 
 impl RunManifest {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         mito: &Path,
         nuclear: &Path,
@@ -121,9 +677,39 @@ impl RunManifest {
             threads,
             min_id,
             min_len,
-            merge_gap,
+            pairing_params: PairingParams {
+                merge_gap,
+                ..PairingParams::default()
+            },
             flank_bp,
             win_bp,
+            pairing: PairingMethod::default(),
+            mm2_asm: AsmMapOptions::default(),
+            excluded_nuclear: Vec::new(),
+            split_indel: SPLIT_INDEL,
+            build_info: Some(VersionInfo::build()),
+            read_stats: None,
+            mapping_stats: None,
+            span_evidence_disabled: false,
+            m2n_aln: None,
+            n2m_aln: None,
+            aln_format: AlnFormat::Paf,
+            aln_identity: IdentityKind::Blast,
+            self_assembly: SelfAssembly::No,
+            read_identity: None,
+            min_paf_mapq: MIN_PAF_MAPQ,
+            keep_secondary: false,
+            boundary_clips: false,
+            require_reciprocal: false,
+            output_compat: OutputCompat::Latest,
+            max_reads_per_window: MAX_READS_PER_WINDOW,
+            depth_supplementary: false,
+            min_span_mapq: MIN_MAPQ,
+            junction_min_clip: JUNCTION_MIN_CLIP,
+            mito_contig_meta: MitoContigMeta::default(),
+            mito_control_regions: ControlRegions::default(),
+            genome_sizes: None,
+            pairs_from: None,
         }
     }
 
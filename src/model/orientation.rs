@@ -0,0 +1,224 @@
+//! Strand-aware mapping of positions between the two sides of a paired locus.
+//!
+//! PAF target coordinates are always on the target's forward strand. For a `-`
+//! alignment the correspondence runs backwards: the nuclear start pairs with the
+//! mito *end* and vice versa. Anything that carries a position across the pair
+//! (junctions, extracted-sequence orientation, block order) goes through here.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::PairedLocus;
+
+/// Relative orientation of the two sides of a locus (PAF strand column).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Strand {
+    #[default]
+    #[serde(rename = "+")]
+    Forward,
+    #[serde(rename = "-")]
+    Reverse,
+}
+
+impl Strand {
+    /// From the PAF strand column; anything but `-` is forward.
+    pub fn from_paf(c: char) -> Self {
+        if c == '-' {
+            Strand::Reverse
+        } else {
+            Strand::Forward
+        }
+    }
+
+    pub fn as_char(self) -> char {
+        match self {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        }
+    }
+}
+
+/// Which genome of a pair a coordinate is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Nuclear,
+    Mito,
+}
+
+impl Side {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Nuclear => "nuc",
+            Side::Mito => "mito",
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Side::Nuclear => Side::Mito,
+            Side::Mito => Side::Nuclear,
+        }
+    }
+}
+
+/// `[start, end)` of one side of a locus.
+pub fn side_interval(p: &PairedLocus, side: Side) -> (u32, u32) {
+    match side {
+        Side::Nuclear => (p.nuc_start, p.nuc_end),
+        Side::Mito => (p.mito_start, p.mito_end),
+    }
+}
+
+/// Map the base at `pos` on `side` to the corresponding base on the other side.
+///
+/// Without the CIGAR the mapping interpolates linearly along the alignment; it is
+/// exact at both ends (and throughout when the two sides have equal length).
+/// On the reverse strand the first base of one side pairs with the last base of
+/// the other. `None` if `pos` is outside `side`'s interval or either side is empty.
+pub fn map_locus_position(p: &PairedLocus, side: Side, pos: u32) -> Option<u32> {
+    let (a_start, a_end) = side_interval(p, side);
+    let (b_start, b_end) = side_interval(p, side.other());
+    if pos < a_start || pos >= a_end || b_start >= b_end {
+        return None;
+    }
+    let (la, lb) = ((a_end - a_start) as u64, (b_end - b_start) as u64);
+    let o = (pos - a_start) as u64;
+    let ob = if la == 1 {
+        0
+    } else {
+        // round(o * (lb-1) / (la-1)), so first↔first and last↔last
+        (2 * o * (lb - 1) + (la - 1)) / (2 * (la - 1))
+    } as u32;
+    Some(match p.strand {
+        Strand::Forward => b_start + ob,
+        Strand::Reverse => b_end - 1 - ob,
+    })
+}
+
+/// Mito bases joined to the left (`nuc_start`) and right (`nuc_end - 1`) nuclear
+/// junctions of an insertion, in that order.
+pub fn mito_at_nuclear_junctions(p: &PairedLocus) -> Option<(u32, u32)> {
+    if p.nuc_end == 0 {
+        return None;
+    }
+    Some((
+        map_locus_position(p, Side::Nuclear, p.nuc_start)?,
+        map_locus_position(p, Side::Nuclear, p.nuc_end - 1)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+
+    fn locus(nuc: (u32, u32), mito: (u32, u32), strand: Strand) -> PairedLocus {
+        PairedLocus {
+            pair_id: "P1".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: nuc.0,
+            nuc_end: nuc.1,
+            mito_contig: "mt".into(),
+            mito_start: mito.0,
+            mito_end: mito.1,
+            aln_len: nuc.1 - nuc.0,
+            aln_ident: 0.99,
+            strand,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn strand_from_paf_and_serde() {
+        assert_eq!(Strand::from_paf('-'), Strand::Reverse);
+        assert_eq!(Strand::from_paf('+'), Strand::Forward);
+        assert_eq!(serde_json::to_string(&Strand::Reverse).unwrap(), "\"-\"");
+        let s: Strand = serde_json::from_str("\"+\"").unwrap();
+        assert_eq!(s.as_char(), '+');
+    }
+
+    #[test]
+    fn map_matrix_strand_by_side() {
+        // nuclear [1000,1100) ↔ mito [200,300): equal lengths, so exact everywhere
+        // (strand, from side, pos, expected)
+        let cases = [
+            (Strand::Forward, Side::Nuclear, 1000, Some(200)),
+            (Strand::Forward, Side::Nuclear, 1099, Some(299)),
+            (Strand::Forward, Side::Nuclear, 1030, Some(230)),
+            (Strand::Forward, Side::Mito, 200, Some(1000)),
+            (Strand::Forward, Side::Mito, 299, Some(1099)),
+            (Strand::Forward, Side::Mito, 230, Some(1030)),
+            (Strand::Reverse, Side::Nuclear, 1000, Some(299)),
+            (Strand::Reverse, Side::Nuclear, 1099, Some(200)),
+            (Strand::Reverse, Side::Nuclear, 1030, Some(269)),
+            (Strand::Reverse, Side::Mito, 200, Some(1099)),
+            (Strand::Reverse, Side::Mito, 299, Some(1000)),
+            (Strand::Reverse, Side::Mito, 230, Some(1069)),
+            // half-open: end is outside, as is anything before start
+            (Strand::Forward, Side::Nuclear, 1100, None),
+            (Strand::Reverse, Side::Nuclear, 999, None),
+            (Strand::Forward, Side::Mito, 300, None),
+            (Strand::Reverse, Side::Mito, 199, None),
+        ];
+        for (strand, side, pos, want) in cases {
+            let p = locus((1000, 1100), (200, 300), strand);
+            assert_eq!(
+                map_locus_position(&p, side, pos),
+                want,
+                "{strand:?} {side:?} {pos}"
+            );
+            // and back again
+            if let Some(q) = want {
+                assert_eq!(map_locus_position(&p, side.other(), q), Some(pos));
+            }
+        }
+    }
+
+    #[test]
+    fn unequal_lengths_are_exact_at_the_ends() {
+        for strand in [Strand::Forward, Strand::Reverse] {
+            // nuclear 10 bp ↔ mito 40 bp, and the other way round
+            for (nuc, mito) in [((0, 10), (100, 140)), ((0, 40), (100, 110))] {
+                let p = locus(nuc, mito, strand);
+                for side in [Side::Nuclear, Side::Mito] {
+                    let (a0, a1) = side_interval(&p, side);
+                    let (b0, b1) = side_interval(&p, side.other());
+                    let (first, last) = match strand {
+                        Strand::Forward => (b0, b1 - 1),
+                        Strand::Reverse => (b1 - 1, b0),
+                    };
+                    assert_eq!(map_locus_position(&p, side, a0), Some(first));
+                    assert_eq!(map_locus_position(&p, side, a1 - 1), Some(last));
+                    // monotone in the strand's direction
+                    let mapped: Vec<u32> = (a0..a1)
+                        .map(|x| map_locus_position(&p, side, x).unwrap())
+                        .collect();
+                    assert!(mapped.iter().all(|m| (b0..b1).contains(m)));
+                    assert!(mapped.windows(2).all(|w| match strand {
+                        Strand::Forward => w[0] <= w[1],
+                        Strand::Reverse => w[0] >= w[1],
+                    }));
+                }
+            }
+        }
+        // single-base side
+        let p = locus((5, 6), (100, 140), Strand::Reverse);
+        assert_eq!(map_locus_position(&p, Side::Nuclear, 5), Some(139));
+    }
+
+    #[test]
+    fn junctions_follow_strand() {
+        let fwd = locus((1000, 1100), (200, 300), Strand::Forward);
+        assert_eq!(mito_at_nuclear_junctions(&fwd), Some((200, 299)));
+        let rev = locus((1000, 1100), (200, 300), Strand::Reverse);
+        assert_eq!(mito_at_nuclear_junctions(&rev), Some((299, 200)));
+        let empty = locus((1000, 1000), (200, 300), Strand::Forward);
+        assert_eq!(mito_at_nuclear_junctions(&empty), None);
+    }
+}
@@ -0,0 +1,400 @@
+//! Cross-organelle reconciliation: plant genomes with both a mitogenome and a
+//! plastome are run once per organelle, and mito and plastid share transferred
+//! regions (MIPTs), so one nuclear insertion can be called a NUMT by both runs.
+//!
+//! The Likely_NUMT loci of every run are swept per nuclear contig; loci that
+//! overlap form a cluster, and a cluster claimed by more than one organelle is
+//! a conflict. The organelle whose best pair in the cluster has the highest
+//! alignment identity owns it, unless the runner-up is within the identity
+//! margin: then it is `shared_organelle_homology`. Nuclear bp "covered by any
+//! organelle homolog" is the union over all organelles, not the sum of the
+//! per-organelle totals. Coordinates are 0-based half-open.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::summary::IntervalBuckets;
+
+/// Default identity difference needed to give a conflict to one organelle.
+pub const DEFAULT_IDENT_MARGIN: f32 = 0.01;
+
+pub const RECONCILED_PAIRS_HEADER: &str =
+    "organelle\tpair_id\tnuc_contig\tnuc_start\tnuc_end\taln_ident\tconflict_id\tstatus";
+pub const ORGANELLE_SUMMARY_HEADER: &str =
+    "organelle\tn_numt\tnuclear_bp_numt\tn_unique\tn_owned\tn_ceded\tn_shared";
+
+/// Label for the row of `organelle_summary.tsv` over all organelles.
+pub const ANY_ORGANELLE: &str = "any";
+
+/// One Likely_NUMT locus of one organelle's run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrganelleLocus {
+    pub pair_id: String,
+    pub nuc_contig: String,
+    pub nuc_start: u32,
+    pub nuc_end: u32,
+    pub aln_ident: f32,
+}
+
+/// How a locus came out of reconciliation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// No other organelle claims an overlapping locus.
+    Unique,
+    /// In a conflict this organelle won on identity.
+    Owned,
+    /// In a conflict another organelle won on identity.
+    Ceded,
+    /// In a conflict no organelle won by the margin.
+    Shared,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Unique => "unique",
+            Status::Owned => "owned",
+            Status::Ceded => "ceded",
+            Status::Shared => "shared_organelle_homology",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconciledLocus {
+    pub organelle: String,
+    pub locus: OrganelleLocus,
+    /// `C000001`, … when the locus is part of a conflict.
+    pub conflict_id: Option<String>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrganelleCounts {
+    pub n_numt: usize,
+    /// Union of the organelle's loci (for `any`: of all loci).
+    pub nuclear_bp_numt: u64,
+    pub n_unique: usize,
+    pub n_owned: usize,
+    pub n_ceded: usize,
+    pub n_shared: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconciliation {
+    /// Sorted by contig, start, organelle and pair_id.
+    pub loci: Vec<ReconciledLocus>,
+    pub n_conflicts: usize,
+    /// Per organelle, in input order, then `any`.
+    pub counts: Vec<(String, OrganelleCounts)>,
+    /// Sum of the per-organelle `nuclear_bp_numt`, for comparison with the union.
+    pub nuclear_bp_numt_sum: u64,
+}
+
+impl Reconciliation {
+    /// Nuclear bp covered by any organelle homolog (the union).
+    pub fn nuclear_bp_numt_any(&self) -> u64 {
+        self.counts
+            .last()
+            .map(|(_, c)| c.nuclear_bp_numt)
+            .unwrap_or(0)
+    }
+}
+
+/// Reconcile the Likely_NUMT loci of several organelles' runs on one nuclear
+/// assembly. `margin` is the identity difference a conflict's best organelle
+/// needs over the runner-up to own it.
+pub fn reconcile(runs: &[(String, Vec<OrganelleLocus>)], margin: f32) -> Reconciliation {
+    // (organelle index, locus), per contig
+    let mut by_contig: BTreeMap<&str, Vec<(usize, &OrganelleLocus)>> = BTreeMap::new();
+    for (o, (_, loci)) in runs.iter().enumerate() {
+        for l in loci {
+            by_contig.entry(&l.nuc_contig).or_default().push((o, l));
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut n_conflicts = 0;
+    for (_, mut loci) in by_contig {
+        loci.sort_by(|a, b| {
+            (a.1.nuc_start, a.1.nuc_end, a.0, &a.1.pair_id).cmp(&(
+                b.1.nuc_start,
+                b.1.nuc_end,
+                b.0,
+                &b.1.pair_id,
+            ))
+        });
+        let mut i = 0;
+        while i < loci.len() {
+            // overlapping loci (abutting ones share no bp)
+            let mut end = loci[i].1.nuc_end;
+            let mut j = i + 1;
+            while j < loci.len() && loci[j].1.nuc_start < end {
+                end = end.max(loci[j].1.nuc_end);
+                j += 1;
+            }
+            let cluster = &loci[i..j];
+            let statuses = resolve(cluster, runs.len(), margin);
+            let conflict_id = statuses.iter().any(|s| *s != Status::Unique).then(|| {
+                n_conflicts += 1;
+                format!("C{n_conflicts:06}")
+            });
+            for (&(o, l), status) in cluster.iter().zip(statuses) {
+                out.push(ReconciledLocus {
+                    organelle: runs[o].0.clone(),
+                    locus: l.clone(),
+                    conflict_id: conflict_id.clone(),
+                    status,
+                });
+            }
+            i = j;
+        }
+    }
+
+    let mut counts: Vec<(String, OrganelleCounts)> = runs
+        .iter()
+        .map(|(name, _)| (name.clone(), OrganelleCounts::default()))
+        .collect();
+    let mut per_organelle: Vec<IntervalBuckets> =
+        runs.iter().map(|_| IntervalBuckets::default()).collect();
+    let mut any = IntervalBuckets::default();
+    for r in &out {
+        let o = runs.iter().position(|(n, _)| *n == r.organelle).unwrap();
+        let l = &r.locus;
+        per_organelle[o].add(&l.nuc_contig, l.nuc_start, l.nuc_end);
+        any.add(&l.nuc_contig, l.nuc_start, l.nuc_end);
+        let c = &mut counts[o].1;
+        c.n_numt += 1;
+        match r.status {
+            Status::Unique => c.n_unique += 1,
+            Status::Owned => c.n_owned += 1,
+            Status::Ceded => c.n_ceded += 1,
+            Status::Shared => c.n_shared += 1,
+        }
+    }
+    for ((_, c), b) in counts.iter_mut().zip(&per_organelle) {
+        c.nuclear_bp_numt = b.union_len();
+    }
+    let nuclear_bp_numt_sum = counts.iter().map(|(_, c)| c.nuclear_bp_numt).sum();
+    let mut total = OrganelleCounts {
+        nuclear_bp_numt: any.union_len(),
+        ..Default::default()
+    };
+    for (_, c) in &counts {
+        total.n_numt += c.n_numt;
+        total.n_unique += c.n_unique;
+        total.n_owned += c.n_owned;
+        total.n_ceded += c.n_ceded;
+        total.n_shared += c.n_shared;
+    }
+    counts.push((ANY_ORGANELLE.to_string(), total));
+
+    Reconciliation {
+        loci: out,
+        n_conflicts,
+        counts,
+        nuclear_bp_numt_sum,
+    }
+}
+
+/// Status of each locus of one cluster of overlapping loci.
+fn resolve(cluster: &[(usize, &OrganelleLocus)], n_organelles: usize, margin: f32) -> Vec<Status> {
+    // best identity per organelle present
+    let mut best: Vec<Option<f32>> = vec![None; n_organelles];
+    for &(o, l) in cluster {
+        best[o] = Some(best[o].map_or(l.aln_ident, |b| b.max(l.aln_ident)));
+    }
+    let mut ranked: Vec<(usize, f32)> = best
+        .iter()
+        .enumerate()
+        .filter_map(|(o, b)| b.map(|b| (o, b)))
+        .collect();
+    if ranked.len() < 2 {
+        return vec![Status::Unique; cluster.len()];
+    }
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let owner = (ranked[0].1 - ranked[1].1 >= margin).then_some(ranked[0].0);
+    cluster
+        .iter()
+        .map(|&(o, _)| match owner {
+            None => Status::Shared,
+            Some(w) if w == o => Status::Owned,
+            Some(_) => Status::Ceded,
+        })
+        .collect()
+}
+
+pub fn reconciled_pairs_tsv(r: &Reconciliation) -> String {
+    let mut s = format!("{RECONCILED_PAIRS_HEADER}\n");
+    for x in &r.loci {
+        let l = &x.locus;
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}",
+            x.organelle,
+            l.pair_id,
+            l.nuc_contig,
+            l.nuc_start,
+            l.nuc_end,
+            l.aln_ident,
+            x.conflict_id.as_deref().unwrap_or("."),
+            x.status.as_str()
+        );
+    }
+    s
+}
+
+pub fn organelle_summary_tsv(r: &Reconciliation) -> String {
+    let mut s = format!("{ORGANELLE_SUMMARY_HEADER}\n");
+    for (name, c) in &r.counts {
+        let _ = writeln!(
+            s,
+            "{name}\t{}\t{}\t{}\t{}\t{}\t{}",
+            c.n_numt, c.nuclear_bp_numt, c.n_unique, c.n_owned, c.n_ceded, c.n_shared
+        );
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locus(id: &str, contig: &str, start: u32, end: u32, ident: f32) -> OrganelleLocus {
+        OrganelleLocus {
+            pair_id: id.to_string(),
+            nuc_contig: contig.to_string(),
+            nuc_start: start,
+            nuc_end: end,
+            aln_ident: ident,
+        }
+    }
+
+    fn statuses(r: &Reconciliation) -> Vec<(String, &str, &str)> {
+        r.loci
+            .iter()
+            .map(|x| {
+                (
+                    format!("{}:{}", x.organelle, x.locus.pair_id),
+                    x.conflict_id.as_deref().unwrap_or("."),
+                    x.status.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_go_to_the_higher_identity() {
+        let runs = vec![
+            (
+                "mito".to_string(),
+                vec![
+                    locus("P1", "chr1", 1_000, 2_000, 0.98),
+                    locus("P2", "chr1", 5_000, 6_000, 0.91),
+                    locus("P3", "chr2", 0, 500, 0.95),
+                    locus("P4", "chr1", 9_000, 9_500, 0.90),
+                ],
+            ),
+            (
+                "plastid".to_string(),
+                vec![
+                    // overlaps mito P1, lower identity: mito owns it
+                    locus("P1", "chr1", 1_500, 2_500, 0.93),
+                    // overlaps mito P2, clearly higher: plastid owns it
+                    locus("P2", "chr1", 5_500, 5_800, 0.97),
+                    // overlaps mito P3 within the margin: shared
+                    locus("P3", "chr2", 400, 900, 0.955),
+                    // abuts mito P4: no shared bp, no conflict
+                    locus("P4", "chr1", 9_500, 9_700, 0.99),
+                ],
+            ),
+        ];
+        let r = reconcile(&runs, DEFAULT_IDENT_MARGIN);
+        let s = |o: &str, c: &'static str, st: &'static str| (o.to_string(), c, st);
+        assert_eq!(
+            statuses(&r),
+            [
+                s("mito:P1", "C000001", "owned"),
+                s("plastid:P1", "C000001", "ceded"),
+                s("mito:P2", "C000002", "ceded"),
+                s("plastid:P2", "C000002", "owned"),
+                s("mito:P4", ".", "unique"),
+                s("plastid:P4", ".", "unique"),
+                s("mito:P3", "C000003", "shared_organelle_homology"),
+                s("plastid:P3", "C000003", "shared_organelle_homology"),
+            ]
+        );
+        assert_eq!(r.n_conflicts, 3);
+        // a wider margin turns every conflict into shared homology
+        let wide = reconcile(&runs, 0.1);
+        assert!(wide
+            .loci
+            .iter()
+            .all(|x| x.conflict_id.is_none() || x.status == Status::Shared));
+    }
+
+    #[test]
+    fn any_organelle_bp_is_a_union_not_a_sum() {
+        let runs = vec![
+            (
+                "mito".to_string(),
+                vec![
+                    locus("P1", "chr1", 0, 1_000, 0.95),
+                    // overlaps P1 within the same run: counted once
+                    locus("P2", "chr1", 500, 1_500, 0.95),
+                ],
+            ),
+            (
+                "plastid".to_string(),
+                vec![
+                    locus("P1", "chr1", 1_000, 2_000, 0.99),
+                    locus("P2", "chr3", 0, 100, 0.99),
+                ],
+            ),
+            ("empty".to_string(), vec![]),
+        ];
+        let r = reconcile(&runs, DEFAULT_IDENT_MARGIN);
+        let bp: Vec<(&str, u64)> = r
+            .counts
+            .iter()
+            .map(|(n, c)| (n.as_str(), c.nuclear_bp_numt))
+            .collect();
+        // mito [0,1500), plastid [1000,2000) + [0,100) on chr3
+        assert_eq!(
+            bp,
+            [
+                ("mito", 1_500),
+                ("plastid", 1_100),
+                ("empty", 0),
+                ("any", 2_100)
+            ]
+        );
+        assert_eq!(r.nuclear_bp_numt_sum, 2_600);
+        assert_eq!(r.nuclear_bp_numt_any(), 2_100);
+        // one cluster holds both mito pairs and the plastid pair; plastid wins
+        let any = &r.counts[3].1;
+        assert_eq!(
+            (
+                any.n_numt,
+                any.n_unique,
+                any.n_owned,
+                any.n_ceded,
+                any.n_shared
+            ),
+            (4, 1, 1, 2, 0)
+        );
+
+        assert_eq!(
+            organelle_summary_tsv(&r),
+            format!(
+                "{ORGANELLE_SUMMARY_HEADER}\n\
+                 mito\t2\t1500\t0\t0\t2\t0\n\
+                 plastid\t2\t1100\t1\t1\t0\t0\n\
+                 empty\t0\t0\t0\t0\t0\t0\n\
+                 any\t4\t2100\t1\t1\t2\t0\n"
+            )
+        );
+        assert!(reconciled_pairs_tsv(&r)
+            .contains("plastid\tP1\tchr1\t1000\t2000\t0.9900\tC000001\towned\n"));
+    }
+}
@@ -0,0 +1,1547 @@
+//! Pairing strategies: turn filtered mito→nuclear and nuclear→mito PAF records
+//! into candidate `PairedLocus` entries.
+//!
+//! Every strategy produces raw loci; merging, collapsing and ID assignment are
+//! shared (`finalize_loci`) so outputs are comparable across strategies.
+//!
+//! Built-ins:
+//!   * `ReciprocalBest` — drive by m2n records, attach the best reciprocal n2m hit.
+//!   * `OverlapCluster` — union-find over all records (both directions) linked by
+//!     interval overlap on *both* genomes; one locus per cluster. Handles
+//!     many-to-many fragmentations naturally.
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use crate::boundary;
+use crate::io::paf::{IdentityKind, PafRecord};
+use crate::model::orientation::Strand;
+use crate::model::{PairedLocus, PairingParams};
+
+/// A pairing algorithm.
+pub trait PairingStrategy {
+    /// Short name used in logs and the manifest.
+    fn name(&self) -> &'static str;
+
+    /// Pair filtered records into candidate loci (IDs assigned, merged).
+    fn pair(
+        &self,
+        m2n: &[PafRecord],
+        n2m: &[PafRecord],
+        params: &PairingParams,
+    ) -> Result<Vec<PairedLocus>>;
+
+    /// `pair`, plus per-locus reciprocity diagnostics if the strategy has them.
+    fn pair_with_report(
+        &self,
+        m2n: &[PafRecord],
+        n2m: &[PafRecord],
+        params: &PairingParams,
+    ) -> Result<(Vec<PairedLocus>, Option<Vec<ReciprocityRow>>)> {
+        Ok((self.pair(m2n, n2m, params)?, None))
+    }
+}
+
+/// CLI/manifest selector for the built-in strategies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum PairingMethod {
+    #[default]
+    Reciprocal,
+    Cluster,
+}
+
+impl PairingMethod {
+    /// `--reciprocal-best` judges the hit reciprocal pairing chose, so it
+    /// needs that strategy.
+    pub fn check_reciprocal_best(self, reciprocal_best: bool) -> Result<()> {
+        if reciprocal_best && self != PairingMethod::Reciprocal {
+            bail!("--reciprocal-best needs --pairing reciprocal");
+        }
+        Ok(())
+    }
+
+    pub fn strategy(self) -> Box<dyn PairingStrategy> {
+        match self {
+            PairingMethod::Reciprocal => Box::new(ReciprocalBest),
+            PairingMethod::Cluster => Box::new(OverlapCluster),
+        }
+    }
+}
+
+/// Shared post-processing: merging and collapsing (per `params`), then
+/// sequential `P000001` IDs.
+///
+/// minimap2 often splits one indel-rich insertion into several adjacent
+/// alignments. Loci on the same nuclear and mito contigs, with the same
+/// strand, whose intervals overlap or lie within `params.merge_gap` bp of each
+/// other on both genomes become one locus: the outer coordinates on both
+/// genomes, `aln_len` the summed block lengths and `aln_ident` their
+/// length-weighted identity (`blast` as its kind if any member's is). Merged
+/// loci keep the order of their first member. Pieces of one alignment split
+/// at a long indel (same `parent_id`) are never merged back together.
+///
+/// A nuclear tandem repeat instead draws hits from several mito segments onto
+/// the same nuclear stretch; those are not near on the mito side, so they are
+/// not merged. Collapsing keeps only the most identical of loci whose nuclear
+/// intervals overlap by at least `params.collapse_min_overlap` of the shorter
+/// one (see `collapse_overlapping`).
+///
+/// `n_support_alignments` counts the raw loci each output locus stands for;
+/// `nuc_start_ci`/`nuc_end_ci` take in how far their ends disagree (see
+/// `boundary`). A merged locus is `reciprocal` if any of its members is.
+pub fn finalize_loci(loci: Vec<PairedLocus>, params: &PairingParams) -> Vec<PairedLocus> {
+    finalize_with_origin(loci, params).0
+}
+
+/// `finalize_loci`, plus the index of the output locus each input went into.
+fn finalize_with_origin(
+    loci: Vec<PairedLocus>,
+    params: &PairingParams,
+) -> (Vec<PairedLocus>, Vec<usize>) {
+    let (merged, to_merged) = merge_loci(loci, params.merge_gap);
+    let (mut kept, to_kept) = collapse_overlapping(merged, params.collapse_min_overlap);
+    for (k, l) in kept.iter_mut().enumerate() {
+        l.pair_id = format!("P{:06}", k + 1);
+    }
+    (kept, to_merged.into_iter().map(|k| to_kept[k]).collect())
+}
+
+/// Merging step of `finalize_loci`, plus the index of the merged locus each
+/// input went into.
+fn merge_loci(loci: Vec<PairedLocus>, gap: u32) -> (Vec<PairedLocus>, Vec<usize>) {
+    let near = |a: (u32, u32), b: (u32, u32)| {
+        a.0 <= b.1.saturating_add(gap) && b.0 <= a.1.saturating_add(gap)
+    };
+    let hull = |a: (u32, u32), b: (u32, u32)| (a.0.min(b.0), a.1.max(b.1));
+    let mut groups: HashMap<(&str, &str, Strand), Vec<usize>> = HashMap::new();
+    for (i, l) in loci.iter().enumerate() {
+        groups
+            .entry((l.nuc_contig.as_str(), l.mito_contig.as_str(), l.strand))
+            .or_default()
+            .push(i);
+    }
+    // Sweep each group by nuclear start, growing a run while the next locus
+    // is near it on both genomes.
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    for mut idxs in groups.into_values() {
+        idxs.sort_by_key(|&i| (loci[i].nuc_start, loci[i].nuc_end, i));
+        let mut span: Option<((u32, u32), (u32, u32))> = None;
+        for i in idxs {
+            let nuc = (loci[i].nuc_start, loci[i].nuc_end);
+            let mito = (loci[i].mito_start, loci[i].mito_end);
+            // pieces of one split alignment stay apart
+            let sibling = |run: &Vec<usize>| {
+                loci[i].parent_id.is_some()
+                    && run.iter().any(|&j| loci[j].parent_id == loci[i].parent_id)
+            };
+            match &mut span {
+                Some((n, m))
+                    if near(*n, nuc) && near(*m, mito) && !sibling(runs.last().unwrap()) =>
+                {
+                    (*n, *m) = (hull(*n, nuc), hull(*m, mito));
+                    runs.last_mut().unwrap().push(i);
+                }
+                _ => {
+                    span = Some((nuc, mito));
+                    runs.push(vec![i]);
+                }
+            }
+        }
+    }
+    for run in &mut runs {
+        run.sort_unstable();
+    }
+    runs.sort_unstable_by_key(|run| run[0]);
+
+    let mut origin = vec![0; loci.len()];
+    let mut merged = Vec::with_capacity(runs.len());
+    for (k, run) in runs.iter().enumerate() {
+        let mut l = loci[run[0]].clone();
+        let members: Vec<&PairedLocus> = run.iter().map(|&i| &loci[i]).collect();
+        (l.nuc_start_ci, l.nuc_end_ci) = boundary::from_hull(&members);
+        let (mut len, mut weighted) = (0u64, 0f64);
+        l.n_support_alignments = 0;
+        for &i in run {
+            origin[i] = k;
+            let m = &loci[i];
+            l.n_support_alignments += m.n_support_alignments;
+            l.reciprocal |= m.reciprocal;
+            l.rbh = l.rbh.max(m.rbh);
+            (l.nuc_start, l.nuc_end) = hull((l.nuc_start, l.nuc_end), (m.nuc_start, m.nuc_end));
+            (l.mito_start, l.mito_end) =
+                hull((l.mito_start, l.mito_end), (m.mito_start, m.mito_end));
+            len += m.aln_len as u64;
+            weighted += m.aln_ident as f64 * m.aln_len as f64;
+        }
+        if run.len() > 1 && len > 0 {
+            l.aln_len = len.min(u32::MAX as u64) as u32;
+            l.aln_ident = (weighted / len as f64) as f32;
+            // a mix averages some BLAST identity in
+            if run
+                .iter()
+                .any(|&i| loci[i].aln_ident_kind == IdentityKind::Blast)
+            {
+                l.aln_ident_kind = IdentityKind::Blast;
+            }
+        }
+        merged.push(l);
+    }
+    (merged, origin)
+}
+
+/// Collapsing step of `finalize_loci`: best identity first (then longer
+/// block, then input order), each locus either joins the first kept locus
+/// whose nuclear interval covers at least `min_overlap` of the shorter of the
+/// two, or is kept itself. Kept loci stay as they are, in input order, adding
+/// up the `n_support_alignments` of those they absorb. `min_overlap` 0 turns
+/// collapsing off. Also returns the index of the kept locus each input went
+/// into.
+pub fn collapse_overlapping(
+    mut loci: Vec<PairedLocus>,
+    min_overlap: f32,
+) -> (Vec<PairedLocus>, Vec<usize>) {
+    if min_overlap <= 0.0 {
+        let origin = (0..loci.len()).collect();
+        return (loci, origin);
+    }
+    let mut order: Vec<usize> = (0..loci.len()).collect();
+    order.sort_by(|&a, &b| {
+        loci[b]
+            .aln_ident
+            .total_cmp(&loci[a].aln_ident)
+            .then(loci[b].aln_len.cmp(&loci[a].aln_len))
+            .then(a.cmp(&b))
+    });
+    // kept loci per nuclear contig, (start, rank) → (end, index), and the
+    // longest; a lower rank was kept earlier, so is the better representative
+    type Kept = (BTreeMap<(u32, usize), (u32, usize)>, u32);
+    let mut kept: HashMap<&str, Kept> = HashMap::new();
+    let mut rep = vec![0; loci.len()];
+    for (rank, &i) in order.iter().enumerate() {
+        let l = &loci[i];
+        let (start, end) = (l.nuc_start, l.nuc_end);
+        let (reps, longest) = kept.entry(l.nuc_contig.as_str()).or_default();
+        let best = reps
+            .range((start.saturating_sub(*longest), 0)..(end, 0))
+            .filter(|&(&(s, _), &(e, _))| {
+                let shared = end.min(e).saturating_sub(start.max(s));
+                let shorter = (end - start).min(e - s).max(1);
+                shared > 0 && shared as f32 >= min_overlap * shorter as f32
+            })
+            .min_by_key(|&(&(_, r), _)| r);
+        match best {
+            Some((_, &(_, r))) => rep[i] = r,
+            None => {
+                rep[i] = i;
+                reps.insert((start, rank), (end, i));
+                *longest = (*longest).max(end - start);
+            }
+        }
+    }
+    let mut absorbed: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..loci.len() {
+        if rep[i] != i {
+            let n = loci[i].n_support_alignments;
+            loci[rep[i]].n_support_alignments += n;
+            absorbed
+                .entry(rep[i])
+                .or_insert_with(|| vec![rep[i]])
+                .push(i);
+        }
+    }
+    for (r, members) in absorbed {
+        let members: Vec<&PairedLocus> = members.iter().map(|&i| &loci[i]).collect();
+        let ci = boundary::from_members(&loci[r], &members);
+        (loci[r].nuc_start_ci, loci[r].nuc_end_ci) = ci;
+    }
+    let mut position = vec![usize::MAX; loci.len()];
+    let mut out = Vec::new();
+    for (i, l) in loci.into_iter().enumerate() {
+        if rep[i] == i {
+            position[i] = out.len();
+            out.push(l);
+        }
+    }
+    let origin = rep.iter().map(|&r| position[r]).collect();
+    (out, origin)
+}
+
+/* ------------------------- reciprocal best ------------------------- */
+
+/// Share of a locus's nuclear interval a nuclear→mito hit must cover to
+/// compete for its reciprocal best hit (`--reciprocal-best`).
+pub const RBH_MIN_COVER: f32 = 0.5;
+
+/// Drive by mito→nuclear records, look for the best reciprocal nuclear→mito
+/// record.
+///
+/// A nuclear→mito hit is a reciprocal candidate if it overlaps the mito→nuclear
+/// record on both genomes, by at least `params.reciprocal_min_overlap` (see
+/// `reciprocal_overlap`); the most identical candidate is taken. A hit
+/// elsewhere on the same contigs, e.g. another NUMT of the same mito region,
+/// is not one. Records without one are kept as one-sided loci (`reciprocal`
+/// false).
+///
+/// With `params.reciprocal_best`, each locus also gets `rbh`: whether its
+/// reciprocal hit is the most identical nuclear→mito record covering at least
+/// `RBH_MIN_COVER` of its nuclear interval, on any mito region or contig, as in
+/// reciprocal-best-hit orthology. A nuclear repeat that aligns better to
+/// another mito region fails it. One-sided loci fail it too.
+pub struct ReciprocalBest;
+
+impl PairingStrategy for ReciprocalBest {
+    fn name(&self) -> &'static str {
+        "reciprocal"
+    }
+
+    fn pair(
+        &self,
+        m2n: &[PafRecord],
+        n2m: &[PafRecord],
+        params: &PairingParams,
+    ) -> Result<Vec<PairedLocus>> {
+        self.pair_with_report(m2n, n2m, params)
+            .map(|(loci, _)| loci)
+    }
+
+    fn pair_with_report(
+        &self,
+        m2n: &[PafRecord],
+        n2m: &[PafRecord],
+        params: &PairingParams,
+    ) -> Result<(Vec<PairedLocus>, Option<Vec<ReciprocityRow>>)> {
+        let index = ReciprocalIndex::new(n2m, params.reciprocal_best);
+        let mut loci = Vec::with_capacity(m2n.len());
+        let mut report = Vec::with_capacity(m2n.len());
+        for rec in m2n {
+            // the most identical candidate; of equals, the last in the input
+            let best = index
+                .overlapping(rec)
+                .map(|(i, r)| (i, r, reciprocal_overlap(rec, r)))
+                .filter(|&(.., ov)| is_reciprocal(ov, params))
+                .max_by(|a, b| a.1.identity.total_cmp(&b.1.identity).then(a.0.cmp(&b.0)))
+                .map(|(_, r, ov)| (r, ov));
+
+            let mut l = locus_from_m2n(rec);
+            l.reciprocal = best.is_some();
+            if params.reciprocal_best {
+                l.rbh = Some(best.is_some_and(|(b, _)| index.is_best_hit(&l, b)));
+            }
+            if let Some((b, _)) = best {
+                (l.nuc_start_ci, l.nuc_end_ci) = boundary::reciprocal(&l, &locus_from_n2m(b));
+            }
+            if let Some((b, _)) = best.filter(|(b, _)| b.identity > rec.identity) {
+                (l.aln_ident, l.aln_ident_kind) = (b.identity, b.identity_kind);
+            }
+            report.push(ReciprocityRow::new(&l, rec, best));
+            loci.push(l);
+        }
+        // one report row per m2n record, under the ID of the locus it went into
+        let (loci, origin) = finalize_with_origin(loci, params);
+        for (row, k) in report.iter_mut().zip(origin) {
+            row.pair_id = loci[k].pair_id.clone();
+        }
+        Ok((loci, Some(report)))
+    }
+}
+
+/// Nuclear→mito records by `(nuclear contig, mito contig)` and, within each,
+/// by nuclear start, so the reciprocal candidates of a mito→nuclear record are
+/// the few that overlap it, not every hit between its two contigs. With
+/// `--reciprocal-best` also by nuclear contig alone, for the best hit of a
+/// nuclear interval whatever mito contig it is on.
+struct ReciprocalIndex<'a> {
+    by_names: HashMap<(&'a str, &'a str), usize>,
+    groups: Vec<NuclearIntervals<'a>>,
+    by_nuclear: HashMap<&'a str, NuclearIntervals<'a>>,
+}
+
+/// One contig pair's records as `(nuclear start, input position, record)`,
+/// sorted, and the longest nuclear interval among them: a record starting
+/// further before a query than that cannot reach it.
+#[derive(Default)]
+struct NuclearIntervals<'a> {
+    by_start: Vec<(u32, usize, &'a PafRecord)>,
+    max_len: u32,
+}
+
+impl<'a> NuclearIntervals<'a> {
+    fn push(&mut self, i: usize, r: &'a PafRecord) {
+        let (start, end) = (r.qstart.min(r.qend), r.qstart.max(r.qend));
+        self.by_start.push((start, i, r));
+        self.max_len = self.max_len.max(end - start);
+    }
+
+    fn sort(&mut self) {
+        self.by_start
+            .sort_unstable_by_key(|&(start, i, _)| (start, i));
+    }
+
+    /// The records whose nuclear interval overlaps `[start, end)`, with their
+    /// input position.
+    fn overlapping(
+        &self,
+        start: u32,
+        end: u32,
+    ) -> impl Iterator<Item = (usize, &'a PafRecord)> + '_ {
+        let from = self
+            .by_start
+            .partition_point(|&(s, ..)| s.saturating_add(self.max_len) <= start);
+        let to = self.by_start.partition_point(|&(s, ..)| s < end);
+        self.by_start[from..to.max(from)]
+            .iter()
+            .filter(move |(_, _, r)| r.qstart.max(r.qend) > start)
+            .map(|&(_, i, r)| (i, r))
+    }
+}
+
+impl<'a> ReciprocalIndex<'a> {
+    fn new(n2m: &'a [PafRecord], by_nuclear: bool) -> Self {
+        let mut index = Self {
+            by_names: HashMap::new(),
+            groups: Vec::new(),
+            by_nuclear: HashMap::new(),
+        };
+        for (i, r) in n2m.iter().enumerate() {
+            let g = *index
+                .by_names
+                .entry((r.qname.as_str(), r.tname.as_str()))
+                .or_insert_with(|| {
+                    index.groups.push(NuclearIntervals::default());
+                    index.groups.len() - 1
+                });
+            index.groups[g].push(i, r);
+            if by_nuclear {
+                index
+                    .by_nuclear
+                    .entry(r.qname.as_str())
+                    .or_default()
+                    .push(i, r);
+            }
+        }
+        for g in index.groups.iter_mut().chain(index.by_nuclear.values_mut()) {
+            g.sort();
+        }
+        index
+    }
+
+    /// The records between the contigs of mito→nuclear `rec` whose nuclear
+    /// interval overlaps its own, with their input position.
+    fn overlapping<'s>(
+        &'s self,
+        rec: &PafRecord,
+    ) -> impl Iterator<Item = (usize, &'a PafRecord)> + 's {
+        let (start, end) = (rec.tstart.min(rec.tend), rec.tstart.max(rec.tend));
+        self.by_names
+            .get(&(rec.tname.as_str(), rec.qname.as_str()))
+            .map(|&g| &self.groups[g])
+            .into_iter()
+            .flat_map(move |e| e.overlapping(start, end))
+    }
+
+    /// Whether `hit` is the reciprocal best hit of locus `l`: it covers at
+    /// least `RBH_MIN_COVER` of the nuclear interval, and no record on any
+    /// mito contig that does has a higher identity.
+    fn is_best_hit(&self, l: &PairedLocus, hit: &PafRecord) -> bool {
+        let (start, end) = (l.nuc_start, l.nuc_end);
+        let covers = |r: &PafRecord| {
+            let shared = r
+                .qstart
+                .max(r.qend)
+                .min(end)
+                .saturating_sub(r.qstart.min(r.qend).max(start));
+            shared > 0 && shared as f32 >= RBH_MIN_COVER * (end - start) as f32
+        };
+        covers(hit)
+            && self.by_nuclear.get(l.nuc_contig.as_str()).is_none_or(|e| {
+                e.overlapping(start, end)
+                    .all(|(_, r)| !covers(r) || r.identity <= hit.identity)
+            })
+    }
+}
+
+/// Reciprocal overlap of a mito→nuclear record and a nuclear→mito one: on each
+/// genome, the shared bp over the longer of the two intervals; the smaller of
+/// the two genomes' fractions. 1.0 = identical intervals, 0.0 = disjoint.
+pub fn reciprocal_overlap(m2n: &PafRecord, n2m: &PafRecord) -> f32 {
+    let a = locus_from_m2n(m2n);
+    let b = locus_from_n2m(n2m);
+    let frac = |x: (u32, u32), y: (u32, u32)| {
+        let shared = x.1.min(y.1).saturating_sub(x.0.max(y.0));
+        let longer = (x.1 - x.0).max(y.1 - y.0);
+        if longer == 0 {
+            0.0
+        } else {
+            shared as f32 / longer as f32
+        }
+    };
+    frac((a.nuc_start, a.nuc_end), (b.nuc_start, b.nuc_end))
+        .min(frac((a.mito_start, a.mito_end), (b.mito_start, b.mito_end)))
+}
+
+/// An overlap makes a reciprocal candidate: some on both genomes, and at
+/// least `params.reciprocal_min_overlap`.
+fn is_reciprocal(overlap: f32, params: &PairingParams) -> bool {
+    overlap > 0.0 && overlap >= params.reciprocal_min_overlap
+}
+
+/// Clap parser for fractions in [0, 1].
+pub fn parse_fraction(s: &str) -> Result<f32, String> {
+    let v: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&v) {
+        Ok(v)
+    } else {
+        Err(format!("{v} is not between 0 and 1"))
+    }
+}
+
+/* ------------------------- pairing report ------------------------- */
+
+pub const PAIRING_REPORT_HEADER: &str = "pair_id\tm2n_identity\tn2m_identity\tidentity_delta\t\
+nuc_start_offset\tnuc_end_offset\tmito_start_offset\tmito_end_offset\treciprocal_overlap\tstrand_consistent";
+
+/// How a mito→nuclear record and its chosen reciprocal hit differ.
+///
+/// Offsets are reciprocal minus forward coordinate (bp); a constant ±1 across
+/// pairs points at an off-by-one, large end offsets at soft-clipping. A
+/// reciprocal hit on the other strand is kept but flagged, since one of the two
+/// alignments is then misplaced. The reciprocal columns are empty when no hit
+/// qualified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReciprocityRow {
+    pub pair_id: String,
+    pub m2n_identity: f32,
+    pub n2m_identity: Option<f32>,
+    /// (nuc start, nuc end, mito start, mito end)
+    pub offsets: Option<(i64, i64, i64, i64)>,
+    pub reciprocal_overlap: Option<f32>,
+    /// Whether the reciprocal hit has the forward record's strand.
+    pub strand_consistent: Option<bool>,
+}
+
+impl ReciprocityRow {
+    fn new(l: &PairedLocus, rec: &PafRecord, best: Option<(&PafRecord, f32)>) -> Self {
+        let offsets = best.map(|(b, _)| {
+            let r = locus_from_n2m(b);
+            let d = |x: u32, y: u32| x as i64 - y as i64;
+            (
+                d(r.nuc_start, l.nuc_start),
+                d(r.nuc_end, l.nuc_end),
+                d(r.mito_start, l.mito_start),
+                d(r.mito_end, l.mito_end),
+            )
+        });
+        Self {
+            pair_id: l.pair_id.clone(),
+            m2n_identity: rec.identity,
+            n2m_identity: best.map(|(b, _)| b.identity),
+            offsets,
+            reciprocal_overlap: best.map(|(_, ov)| ov),
+            strand_consistent: best.map(|(b, _)| b.strand == rec.strand),
+        }
+    }
+
+    pub fn to_tsv_line(&self) -> String {
+        let opt = |v: Option<f32>| v.map(|x| format!("{x:.4}")).unwrap_or_default();
+        let (ns, ne, ms, me) = match self.offsets {
+            Some((a, b, c, d)) => (a.to_string(), b.to_string(), c.to_string(), d.to_string()),
+            None => Default::default(),
+        };
+        format!(
+            "{}\t{:.4}\t{}\t{}\t{ns}\t{ne}\t{ms}\t{me}\t{}\t{}",
+            self.pair_id,
+            self.m2n_identity,
+            opt(self.n2m_identity),
+            opt(self.n2m_identity.map(|n| n - self.m2n_identity)),
+            opt(self.reciprocal_overlap),
+            self.strand_consistent
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Write `pairing_report.tsv`.
+pub fn write_pairing_report(path: &std::path::Path, rows: &[ReciprocityRow]) -> Result<()> {
+    let mut s = String::with_capacity(64 * (rows.len() + 1));
+    s.push_str(PAIRING_REPORT_HEADER);
+    s.push('\n');
+    for r in rows {
+        s.push_str(&r.to_tsv_line());
+        s.push('\n');
+    }
+    fs_err::write(path, s)?;
+    Ok(())
+}
+
+/* ------------------------- locus table ------------------------- */
+
+/// The locus columns of pairs.tsv, for loci that have not been scored.
+pub const LOCI_TSV_HEADER: &str = "pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\tstrand\tnuc_start_ci\tnuc_end_ci\treciprocal\tparent_id\trbh";
+
+/// One row per locus, formatted like the same pairs.tsv columns.
+pub fn loci_tsv(pairs: &[PairedLocus]) -> String {
+    let mut s = format!("{LOCI_TSV_HEADER}\n");
+    for p in pairs {
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}\t{}\t{}\t{}\t{}",
+            p.pair_id,
+            p.nuc_contig,
+            p.nuc_start,
+            p.nuc_end,
+            p.mito_contig,
+            p.mito_start,
+            p.mito_end,
+            p.aln_len,
+            p.aln_ident,
+            p.strand.as_char(),
+            p.nuc_start_ci,
+            p.nuc_end_ci,
+            p.reciprocal,
+            p.parent_id.as_deref().unwrap_or("."),
+            fmt_rbh(p.rbh)
+        );
+    }
+    s
+}
+
+/// `rbh` as written to locus tables; `.` when it was not checked.
+pub fn fmt_rbh(rbh: Option<bool>) -> &'static str {
+    match rbh {
+        Some(true) => "true",
+        Some(false) => "false",
+        None => ".",
+    }
+}
+
+/* ------------------------- overlap clustering ------------------------- */
+
+/// Cluster all records by two-sided interval overlap (union-find).
+///
+/// Each cluster becomes one locus spanning the outer coordinates of its members
+/// on both genomes; `aln_len` is the longest member block and `aln_ident` the
+/// best member identity (mirroring `ReciprocalBest`'s max-of-both-directions).
+/// A cluster with records from both directions is `reciprocal`.
+pub struct OverlapCluster;
+
+impl PairingStrategy for OverlapCluster {
+    fn name(&self) -> &'static str {
+        "cluster"
+    }
+
+    fn pair(
+        &self,
+        m2n: &[PafRecord],
+        n2m: &[PafRecord],
+        params: &PairingParams,
+    ) -> Result<Vec<PairedLocus>> {
+        // Normalize both directions to mito/nuclear-side loci.
+        let items: Vec<PairedLocus> = m2n
+            .iter()
+            .map(locus_from_m2n)
+            .chain(n2m.iter().map(locus_from_n2m))
+            .collect();
+
+        let mut uf = UnionFind::new(items.len());
+
+        // Sweep per (nuc_contig, mito_contig) sorted by nuclear start.
+        let mut groups: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+        for (i, it) in items.iter().enumerate() {
+            groups
+                .entry((it.nuc_contig.as_str(), it.mito_contig.as_str()))
+                .or_default()
+                .push(i);
+        }
+        for idxs in groups.values_mut() {
+            idxs.sort_by_key(|&i| (items[i].nuc_start, items[i].nuc_end));
+            for (a_pos, &a) in idxs.iter().enumerate() {
+                for &b in &idxs[a_pos + 1..] {
+                    if items[b].nuc_start >= items[a].nuc_end {
+                        break; // sorted by start: nothing later overlaps `a`
+                    }
+                    if overlaps(
+                        (items[a].mito_start, items[a].mito_end),
+                        (items[b].mito_start, items[b].mito_end),
+                    ) {
+                        uf.union(a, b);
+                    }
+                }
+            }
+        }
+
+        // Collapse clusters, ordered by their earliest member (m2n first).
+        let mut clusters: HashMap<usize, PairedLocus> = HashMap::new();
+        let mut members: HashMap<usize, Vec<&PairedLocus>> = HashMap::new();
+        let mut first_member: HashMap<usize, usize> = HashMap::new();
+        let mut directions: HashMap<usize, (bool, bool)> = HashMap::new();
+        for (i, it) in items.iter().enumerate() {
+            let root = uf.find(i);
+            first_member.entry(root).or_insert(i);
+            members.entry(root).or_default().push(it);
+            // (any m2n member, any n2m member)
+            let d = directions.entry(root).or_insert((false, false));
+            if i < m2n.len() {
+                d.0 = true;
+            } else {
+                d.1 = true;
+            }
+            clusters
+                .entry(root)
+                .and_modify(|c| {
+                    // orientation of the longest member
+                    if it.aln_len > c.aln_len {
+                        c.strand = it.strand;
+                    }
+                    c.nuc_start = c.nuc_start.min(it.nuc_start);
+                    c.nuc_end = c.nuc_end.max(it.nuc_end);
+                    c.mito_start = c.mito_start.min(it.mito_start);
+                    c.mito_end = c.mito_end.max(it.mito_end);
+                    c.aln_len = c.aln_len.max(it.aln_len);
+                    if it.aln_ident > c.aln_ident {
+                        (c.aln_ident, c.aln_ident_kind) = (it.aln_ident, it.aln_ident_kind);
+                    }
+                })
+                .or_insert_with(|| it.clone());
+        }
+        let mut roots: Vec<usize> = clusters.keys().copied().collect();
+        roots.sort_by_key(|r| first_member[r]);
+        let loci = roots
+            .into_iter()
+            .map(|r| {
+                let mut c = clusters.remove(&r).unwrap();
+                (c.nuc_start_ci, c.nuc_end_ci) = boundary::from_hull(&members[&r]);
+                c.reciprocal = directions[&r] == (true, true);
+                c
+            })
+            .collect();
+
+        Ok(finalize_loci(loci, params))
+    }
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            // keep the smaller index as root so results don't depend on edge order
+            let (lo, hi) = (ra.min(rb), ra.max(rb));
+            self.parent[hi] = lo;
+        }
+    }
+}
+
+/* ------------------------- internal helpers ------------------------- */
+
+fn overlaps(a: (u32, u32), b: (u32, u32)) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+/// mito is the query, nuclear the target.
+fn locus_from_m2n(rec: &PafRecord) -> PairedLocus {
+    PairedLocus {
+        pair_id: String::new(),
+        nuc_contig: rec.tname.clone(),
+        nuc_start: rec.tstart.min(rec.tend),
+        nuc_end: rec.tstart.max(rec.tend),
+        mito_contig: rec.qname.clone(),
+        mito_start: rec.qstart.min(rec.qend),
+        mito_end: rec.qstart.max(rec.qend),
+        aln_len: rec.alnlen,
+        aln_ident: rec.identity,
+        strand: Strand::from_paf(rec.strand),
+        n_support_alignments: 1,
+        aln_ident_kind: rec.identity_kind,
+        nuc_start_ci: 0,
+        nuc_end_ci: 0,
+        reciprocal: false,
+        parent_id: rec
+            .parent
+            .map(|p| format!("{}:{}-{}", rec.tname, p.target.0, p.target.1)),
+        rbh: None,
+    }
+}
+
+/// nuclear is the query, mito the target.
+fn locus_from_n2m(rec: &PafRecord) -> PairedLocus {
+    PairedLocus {
+        pair_id: String::new(),
+        nuc_contig: rec.qname.clone(),
+        nuc_start: rec.qstart.min(rec.qend),
+        nuc_end: rec.qstart.max(rec.qend),
+        mito_contig: rec.tname.clone(),
+        mito_start: rec.tstart.min(rec.tend),
+        mito_end: rec.tstart.max(rec.tend),
+        aln_len: rec.alnlen,
+        aln_ident: rec.identity,
+        strand: Strand::from_paf(rec.strand),
+        n_support_alignments: 1,
+        aln_ident_kind: rec.identity_kind,
+        nuc_start_ci: 0,
+        nuc_end_ci: 0,
+        reciprocal: false,
+        parent_id: rec
+            .parent
+            .map(|p| format!("{}:{}-{}", rec.qname, p.query.0, p.query.1)),
+        rbh: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::read_paf;
+    use crate::model::COLLAPSE_MIN_OVERLAP;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    // One NUMT on chr1 fragmented by minimap2 into two overlapping blocks,
+    // plus a separate NUMT on chr2.
+    const M2N: &str = "\
+mt\t16000\t100\t1100\t+\tchr1\t100000\t5000\t6000\t950\t1000\t60
+mt\t16000\t1000\t2000\t+\tchr1\t100000\t5900\t6900\t960\t1000\t60
+mt\t16000\t8000\t9000\t+\tchr2\t50000\t200\t1200\t990\t1000\t60
+";
+    const N2M: &str = "\
+chr1\t100000\t5000\t6900\t+\tmt\t16000\t100\t2000\t1805\t1900\t60
+chr2\t50000\t200\t1200\t+\tmt\t16000\t8000\t9000\t995\t1000\t60
+";
+
+    fn fixture(txt: &str) -> Vec<PafRecord> {
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(txt.as_bytes()).unwrap();
+        read_paf(f.path(), 0.9, 100, 0).unwrap()
+    }
+
+    #[test]
+    fn reciprocal_best_merges_fragments() {
+        let (m2n, n2m) = (fixture(M2N), fixture(N2M));
+        let loci = ReciprocalBest
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        // the two overlapping chr1 fragments are one locus
+        assert_eq!(loci.len(), 2);
+        assert_eq!(loci[0].pair_id, "P000001");
+        assert_eq!((loci[0].nuc_start, loci[0].nuc_end), (5000, 6900));
+        assert_eq!((loci[0].mito_start, loci[0].mito_end), (100, 2000));
+        assert_eq!(loci[0].aln_len, 2000);
+        // best reciprocal identity wins (0.995 on chr2)
+        assert!((loci[1].aln_ident - 0.995).abs() < 1e-6);
+    }
+
+    #[test]
+    fn loci_without_a_nuclear_to_mito_hit_are_one_sided() {
+        let (m2n, n2m) = (fixture(M2N), fixture(N2M));
+        // only the chr2 NUMT has its reciprocal hit
+        let n2m_chr2 = &n2m[1..];
+        for strategy in [PairingMethod::Reciprocal, PairingMethod::Cluster] {
+            let strategy = strategy.strategy();
+            let both = strategy
+                .pair(&m2n, &n2m, &PairingParams::default())
+                .unwrap();
+            assert!(both.iter().all(|l| l.reciprocal), "{}", strategy.name());
+            let loci = strategy
+                .pair(&m2n, n2m_chr2, &PairingParams::default())
+                .unwrap();
+            let flags: Vec<(&str, bool)> = loci
+                .iter()
+                .map(|l| (l.nuc_contig.as_str(), l.reciprocal))
+                .collect();
+            assert_eq!(
+                flags,
+                [("chr1", false), ("chr2", true)],
+                "{}",
+                strategy.name()
+            );
+        }
+    }
+
+    #[test]
+    fn identity_kind_follows_the_identity_taken() {
+        use IdentityKind::{Blast, GapCompressed};
+        let gap_compressed = |txt: &str, gc: &[Option<f32>]| {
+            let mut recs = fixture(txt);
+            for (r, &gc) in recs.iter_mut().zip(gc) {
+                r.gap_compressed_identity = gc;
+                r.use_identity(IdentityKind::GapCompressed);
+            }
+            recs
+        };
+        let kinds = |m2n: &[PafRecord], n2m: &[PafRecord]| {
+            ReciprocalBest
+                .pair(m2n, n2m, &PairingParams::default())
+                .unwrap()
+                .iter()
+                .map(|l| l.aln_ident_kind)
+                .collect::<Vec<_>>()
+        };
+        let m2n = gap_compressed(M2N, &[Some(0.99), Some(0.99), Some(0.999)]);
+        assert_eq!(kinds(&m2n, &[]), [GapCompressed, GapCompressed]);
+        // one chr1 fragment without a CIGAR: the merged identity is partly BLAST
+        let m2n = gap_compressed(M2N, &[Some(0.99), None, Some(0.999)]);
+        assert_eq!(kinds(&m2n, &[]), [Blast, GapCompressed]);
+        // the reciprocal hit's kind comes with its identity, if it is better
+        let m2n = gap_compressed(M2N, &[None, None, None]);
+        let n2m = gap_compressed(N2M, &[Some(0.999), Some(0.9)]);
+        assert_eq!(kinds(&m2n, &n2m), [GapCompressed, Blast]);
+    }
+
+    #[test]
+    fn merge_gap_joins_nearby_fragments_only() {
+        // chr1: adjacent (30 bp apart on both genomes) and overlapping fragments
+        // of one NUMT, then one 500 bp further on; chr2: within the gap on the
+        // nuclear side only, and on the other strand
+        let m2n = fixture(
+            "mt\t16000\t0\t1000\t+\tchr1\t100000\t0\t1000\t900\t1000\t60\n\
+             mt\t16000\t1030\t1530\t+\tchr1\t100000\t1030\t1530\t500\t500\t60\n\
+             mt\t16000\t1400\t2000\t+\tchr1\t100000\t1500\t2100\t570\t600\t60\n\
+             mt\t16000\t2500\t3000\t+\tchr1\t100000\t2600\t3100\t480\t500\t60\n\
+             mt\t16000\t0\t1000\t+\tchr2\t100000\t0\t1000\t950\t1000\t60\n\
+             mt\t16000\t9000\t10000\t+\tchr2\t100000\t1010\t2010\t950\t1000\t60\n\
+             mt\t16000\t1000\t2000\t-\tchr2\t100000\t2020\t3020\t950\t1000\t60\n",
+        );
+        let (loci, report) = ReciprocalBest
+            .pair_with_report(&m2n, &[], &PairingParams::default())
+            .unwrap();
+        let spans: Vec<_> = loci
+            .iter()
+            .map(|l| {
+                (
+                    l.pair_id.as_str(),
+                    l.nuc_start,
+                    l.nuc_end,
+                    l.mito_start,
+                    l.mito_end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("P000001", 0, 2100, 0, 2000),
+                ("P000002", 2600, 3100, 2500, 3000),
+                ("P000003", 0, 1000, 0, 1000),
+                ("P000004", 1010, 2010, 9000, 10000),
+                ("P000005", 2020, 3020, 1000, 2000),
+            ]
+        );
+        // summed blocks, length-weighted identity: (900 + 500 + 570) / 2100
+        assert_eq!(loci[0].aln_len, 2100);
+        assert!((loci[0].aln_ident - 1970.0 / 2100.0).abs() < 1e-6);
+        assert!((loci[1].aln_ident - 0.96).abs() < 1e-6);
+        // the report keeps a row per record, under the merged locus's ID
+        let ids: Vec<_> = report.unwrap().into_iter().map(|r| r.pair_id).collect();
+        assert_eq!(ids[..4], ["P000001", "P000001", "P000001", "P000002"]);
+
+        // no gap allowed: only the overlapping fragments merge
+        let params = PairingParams {
+            merge_gap: 0,
+            ..PairingParams::default()
+        };
+        let loci = ReciprocalBest.pair(&m2n, &[], &params).unwrap();
+        assert_eq!(loci.len(), 6);
+        assert_eq!((loci[1].nuc_start, loci[1].nuc_end), (1030, 2100));
+    }
+
+    #[test]
+    fn every_pairing_param_changes_the_loci() {
+        // chr1: fragments 30 bp apart on both genomes; chr2: loci overlapping
+        // on the nuclear side only; chr3: a reciprocal hit overlapping by half
+        let m2n = fixture(
+            "mt\t16000\t0\t1000\t+\tchr1\t100000\t0\t1000\t900\t1000\t60\n\
+             mt\t16000\t1030\t1530\t+\tchr1\t100000\t1030\t1530\t450\t500\t60\n\
+             mt\t16000\t3000\t4000\t+\tchr2\t100000\t10000\t11000\t950\t1000\t60\n\
+             mt\t16000\t8000\t9000\t+\tchr2\t100000\t10050\t11050\t900\t1000\t60\n\
+             mt\t16000\t12000\t13000\t+\tchr3\t100000\t5000\t6000\t920\t1000\t60\n",
+        );
+        let n2m = fixture("chr3\t100000\t5500\t6500\t+\tmt\t16000\t12500\t13500\t990\t1000\t60\n");
+        let loci = |params: &PairingParams| {
+            format!("{:?}", ReciprocalBest.pair(&m2n, &n2m, params).unwrap())
+        };
+        let default = PairingParams::default();
+        // listed without `..`: a new setting fails to compile here until it
+        // gets a value of its own
+        let all = PairingParams {
+            merge_gap: 0,
+            reciprocal_min_overlap: 0.8,
+            collapse_min_overlap: 0.0,
+            reciprocal_best: true,
+        };
+        let PairingParams {
+            merge_gap,
+            reciprocal_min_overlap,
+            collapse_min_overlap,
+            reciprocal_best,
+        } = all.clone();
+        for (name, params) in [
+            (
+                "merge_gap",
+                PairingParams {
+                    merge_gap,
+                    ..default.clone()
+                },
+            ),
+            (
+                "reciprocal_min_overlap",
+                PairingParams {
+                    reciprocal_min_overlap,
+                    ..default.clone()
+                },
+            ),
+            (
+                "collapse_min_overlap",
+                PairingParams {
+                    collapse_min_overlap,
+                    ..default.clone()
+                },
+            ),
+            (
+                "reciprocal_best",
+                PairingParams {
+                    reciprocal_best,
+                    ..default.clone()
+                },
+            ),
+        ] {
+            assert_ne!(params, default, "{name}");
+            assert_ne!(loci(&params), loci(&default), "{name} is not honored");
+        }
+        let (n_default, n_all) = (
+            ReciprocalBest.pair(&m2n, &n2m, &default).unwrap(),
+            ReciprocalBest.pair(&m2n, &n2m, &all).unwrap(),
+        );
+        // merged and collapsed by default; neither with `all`
+        assert_eq!((n_default.len(), n_all.len()), (3, 5));
+        assert!(n_all.iter().all(|l| l.rbh.is_some()));
+        assert!(n_default.iter().all(|l| l.rbh.is_none()));
+        // the wrapper takes the same settings
+        assert_eq!(
+            format!(
+                "{:?}",
+                crate::io::paf::pair_and_merge(&m2n, &n2m, &all).unwrap()
+            ),
+            format!("{n_all:?}")
+        );
+    }
+
+    #[test]
+    fn reciprocal_min_overlap_selects_candidates() {
+        let m2n = fixture("mt\t16000\t100\t1100\t+\tchr1\t100000\t5000\t6000\t920\t1000\t60\n");
+        // same intervals; shifted by 500 bp on both genomes; elsewhere entirely
+        let n2m = fixture(
+            "chr1\t100000\t5000\t6000\t+\tmt\t16000\t100\t1100\t950\t1000\t60\n\
+             chr1\t100000\t5500\t6500\t+\tmt\t16000\t600\t1600\t990\t1000\t60\n\
+             chr1\t100000\t9000\t10000\t+\tmt\t16000\t5000\t6000\t999\t1000\t60\n",
+        );
+        assert!((reciprocal_overlap(&m2n[0], &n2m[0]) - 1.0).abs() < 1e-6);
+        assert!((reciprocal_overlap(&m2n[0], &n2m[1]) - 0.5).abs() < 1e-6);
+        assert_eq!(reciprocal_overlap(&m2n[0], &n2m[2]), 0.0);
+
+        let ident_at = |min: f32, n2m: &[PafRecord]| {
+            let params = PairingParams {
+                reciprocal_min_overlap: min,
+                ..PairingParams::default()
+            };
+            ReciprocalBest.pair(&m2n, n2m, &params).unwrap()[0].aln_ident
+        };
+        // the disjoint hit never counts, however identical
+        for (min, want) in [
+            (0.0, 0.99),
+            (0.3, 0.99),
+            (0.5, 0.99),
+            (0.8, 0.95),
+            (1.0, 0.95),
+        ] {
+            assert!((ident_at(min, &n2m) - want).abs() < 1e-6, "min {min}");
+        }
+        // nothing qualifies: the m2n identity stands
+        assert!((ident_at(0.8, &n2m[1..]) - 0.92).abs() < 1e-6);
+    }
+
+    #[test]
+    fn two_numts_on_one_chromosome_pair_with_their_own_hits() {
+        // the same mito region inserted twice on chr1, far apart; the reverse
+        // hit of the second is the more identical
+        let m2n = fixture(
+            "mt\t16000\t100\t1100\t+\tchr1\t100000\t5000\t6000\t900\t1000\t60\n\
+             mt\t16000\t100\t1100\t+\tchr1\t100000\t70000\t71000\t910\t1000\t60\n",
+        );
+        let n2m = fixture(
+            "chr1\t100000\t5010\t5990\t+\tmt\t16000\t110\t1090\t931\t980\t60\n\
+             chr1\t100000\t70020\t71000\t+\tmt\t16000\t120\t1100\t970\t980\t60\n",
+        );
+        let (loci, report) = ReciprocalBest
+            .pair_with_report(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        let got: Vec<_> = loci
+            .iter()
+            .map(|l| (l.nuc_start, l.aln_ident, l.nuc_start_ci, l.nuc_end_ci))
+            .collect();
+        let own = |i: usize| n2m[i].identity;
+        assert_eq!(got, [(5000, own(0), 10, 10), (70000, own(1), 20, 0)]);
+        assert!(loci.iter().all(|l| l.reciprocal));
+        let report = report.unwrap();
+        assert_eq!(
+            (report[0].n2m_identity, report[1].n2m_identity),
+            (Some(own(0)), Some(own(1)))
+        );
+
+        // without its own hit the first NUMT is one-sided, not paired with
+        // the other NUMT's
+        let loci = ReciprocalBest
+            .pair(&m2n, &n2m[1..], &PairingParams::default())
+            .unwrap();
+        assert_eq!(
+            loci.iter()
+                .map(|l| (l.reciprocal, l.aln_ident))
+                .collect::<Vec<_>>(),
+            [(false, m2n[0].identity), (true, n2m[1].identity)]
+        );
+    }
+
+    #[test]
+    fn reciprocal_best_flags_repeats_that_map_better_elsewhere() {
+        // chr1:5000-6000 is a repeat aligning to two mito regions, its reverse
+        // hit to the second the more identical; chr2's locus has a better hit
+        // to the plastid, but over too little of it; chr3's is one-sided
+        let m2n = fixture(
+            "mt\t16000\t100\t1100\t+\tchr1\t100000\t5000\t6000\t950\t1000\t60\n\
+             mt\t16000\t8000\t9000\t+\tchr1\t100000\t5000\t6000\t950\t1000\t60\n\
+             mt\t16000\t3000\t4000\t+\tchr2\t100000\t0\t1000\t950\t1000\t60\n\
+             mt\t16000\t5000\t6000\t+\tchr3\t100000\t0\t1000\t950\t1000\t60\n",
+        );
+        let n2m = fixture(
+            "chr1\t100000\t5000\t6000\t+\tmt\t16000\t100\t1100\t930\t1000\t60\n\
+             chr1\t100000\t5000\t6000\t+\tmt\t16000\t8000\t9000\t980\t1000\t60\n\
+             chr2\t100000\t0\t1000\t+\tmt\t16000\t3000\t4000\t940\t1000\t60\n\
+             chr2\t100000\t600\t1000\t+\tpt\t150000\t100\t500\t396\t400\t60\n",
+        );
+        let params = PairingParams {
+            collapse_min_overlap: 0.0,
+            reciprocal_best: true,
+            ..PairingParams::default()
+        };
+        let rbh = |loci: &[PairedLocus]| -> Vec<_> {
+            loci.iter()
+                .map(|l| (l.nuc_contig.clone(), l.mito_start, l.rbh))
+                .collect()
+        };
+        let loci = ReciprocalBest.pair(&m2n, &n2m, &params).unwrap();
+        assert_eq!(
+            rbh(&loci),
+            [
+                ("chr1".to_string(), 100, Some(false)),
+                ("chr1".to_string(), 8000, Some(true)),
+                ("chr2".to_string(), 3000, Some(true)),
+                ("chr3".to_string(), 5000, Some(false)),
+            ]
+        );
+        // the same better hit on another mito contig still wins
+        let elsewhere = n2m[1].clone();
+        let n2m_pt = [
+            n2m[0].clone(),
+            PafRecord {
+                tname: "pt".into(),
+                ..elsewhere
+            },
+        ];
+        let loci = ReciprocalBest.pair(&m2n[..1], &n2m_pt, &params).unwrap();
+        assert_eq!(loci[0].rbh, Some(false));
+        // a merged locus passes if any fragment does; unchecked, it is `.`
+        let merged = PairingParams {
+            merge_gap: 10_000,
+            ..params
+        };
+        let loci = ReciprocalBest.pair(&m2n[..2], &n2m, &merged).unwrap();
+        assert_eq!(rbh(&loci), [("chr1".to_string(), 100, Some(true))]);
+        let loci = ReciprocalBest
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        assert!(loci.iter().all(|l| l.rbh.is_none()));
+        assert!(loci_tsv(&loci).lines().nth(1).unwrap().ends_with("\t.\t."));
+    }
+
+    #[test]
+    fn reciprocal_disagreement_is_the_boundary_ci() {
+        let m2n = fixture("mt\t16000\t100\t1100\t+\tchr1\t100000\t5000\t6000\t950\t1000\t60\n");
+        let n2m = fixture("chr1\t100000\t5012\t5995\t+\tmt\t16000\t112\t1095\t950\t983\t60\n");
+        let ci = |n2m: &[PafRecord]| {
+            let l = &ReciprocalBest
+                .pair(&m2n, n2m, &PairingParams::default())
+                .unwrap()[0];
+            (l.nuc_start, l.nuc_end, l.nuc_start_ci, l.nuc_end_ci)
+        };
+        // the m2n coordinates stand; the reverse hit only widens the CI
+        assert_eq!(ci(&n2m), (5000, 6000, 12, 5));
+        // no reciprocal hit: nothing to disagree with
+        assert_eq!(ci(&[]), (5000, 6000, 0, 0));
+    }
+
+    /// `ReciprocalBest` without its index: every n2m record, for every m2n one.
+    struct Scan;
+
+    impl PairingStrategy for Scan {
+        fn name(&self) -> &'static str {
+            "scan"
+        }
+
+        fn pair(
+            &self,
+            m2n: &[PafRecord],
+            n2m: &[PafRecord],
+            params: &PairingParams,
+        ) -> Result<Vec<PairedLocus>> {
+            let mut loci = Vec::new();
+            for rec in m2n {
+                let best = n2m
+                    .iter()
+                    .filter(|r| r.qname == rec.tname && r.tname == rec.qname)
+                    .map(|r| (r, reciprocal_overlap(rec, r)))
+                    .filter(|&(_, ov)| is_reciprocal(ov, params))
+                    .max_by(|a, b| a.0.identity.total_cmp(&b.0.identity));
+                let mut l = locus_from_m2n(rec);
+                l.reciprocal = best.is_some();
+                if let Some((b, _)) = best {
+                    (l.nuc_start_ci, l.nuc_end_ci) = boundary::reciprocal(&l, &locus_from_n2m(b));
+                }
+                if let Some((b, _)) = best.filter(|(b, _)| b.identity > rec.identity) {
+                    (l.aln_ident, l.aln_ident_kind) = (b.identity, b.identity_kind);
+                }
+                loci.push(l);
+            }
+            Ok(finalize_loci(loci, params))
+        }
+    }
+
+    #[test]
+    fn indexed_reciprocal_search_matches_the_scan() {
+        // many contigs, NUMTs with 0-3 reciprocal hits of jittered ends and
+        // identities (ties included), and hits between unrelated contigs
+        let (mut m2n, mut n2m) = (String::new(), String::new());
+        let mut seed = 7u64;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        for i in 0..3000u64 {
+            let (chr, mt) = (format!("chr{}", i % 41), format!("mt{}", i % 3));
+            let (ns, ms) = (20_000 * (i / 41) + next(5000), next(15_000));
+            let len = 300 + next(1500);
+            let m = 900 + next(100);
+            m2n.push_str(&format!(
+                "{mt}\t16000\t{ms}\t{}\t+\t{chr}\t10000000\t{ns}\t{}\t{}\t1000\t60\n",
+                ms + len,
+                ns + len,
+                m
+            ));
+            if next(5) == 0 {
+                // the same mito region hit elsewhere on the chromosome
+                let at = next(9_000_000);
+                n2m.push_str(&format!(
+                    "{chr}\t10000000\t{at}\t{}\t+\t{mt}\t16000\t{ms}\t{}\t999\t1000\t60\n",
+                    at + len,
+                    ms + len
+                ));
+            }
+            for _ in 0..next(4) {
+                let (a, b) = (next(60), next(60));
+                let other = if next(10) == 0 { "chrX" } else { &chr };
+                n2m.push_str(&format!(
+                    "{other}\t10000000\t{}\t{}\t+\t{mt}\t16000\t{}\t{}\t{}\t1000\t60\n",
+                    ns + a,
+                    ns + len - b,
+                    ms + a,
+                    ms + len - b,
+                    900 + 10 * next(10)
+                ));
+            }
+        }
+        let (m2n, n2m) = (fixture(&m2n), fixture(&n2m));
+        assert!(m2n.len() > 2500 && n2m.len() > 3000);
+        for reciprocal_min_overlap in [0.0, 0.9] {
+            let params = PairingParams {
+                reciprocal_min_overlap,
+                ..PairingParams::default()
+            };
+            let json = |l: &[PairedLocus]| serde_json::to_string(l).unwrap();
+            let indexed = ReciprocalBest.pair(&m2n, &n2m, &params).unwrap();
+            assert!(indexed.iter().any(|l| l.nuc_start_ci > 0));
+            assert_eq!(
+                json(&indexed),
+                json(&Scan.pair(&m2n, &n2m, &params).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn pairing_report_rows() {
+        let m2n = fixture(M2N);
+        let n2m = fixture(N2M);
+        let params = PairingParams {
+            reciprocal_min_overlap: 0.3,
+            ..PairingParams::default()
+        };
+        let (loci, report) = ReciprocalBest
+            .pair_with_report(&m2n, &n2m, &params)
+            .unwrap();
+        let report = report.unwrap();
+        assert_eq!((report.len(), loci.len()), (3, 2));
+        // the second chr1 fragment shares 1000 bp of the 1900 bp n2m block (0.53);
+        // it is reported against its own coordinates, under the merged ID
+        assert_eq!(report[1].pair_id, "P000001");
+        assert_eq!(report[1].offsets, Some((-900, 0, -900, 0)));
+        assert_eq!(
+            report[2].to_tsv_line(),
+            "P000002\t0.9900\t0.9950\t0.0050\t0\t0\t0\t0\t1.0000\ttrue"
+        );
+
+        // a strict threshold leaves the fragments without a reciprocal hit
+        let strict = PairingParams {
+            reciprocal_min_overlap: 0.8,
+            ..PairingParams::default()
+        };
+        let (_, report) = ReciprocalBest
+            .pair_with_report(&m2n, &n2m, &strict)
+            .unwrap();
+        let line = report.unwrap()[0].to_tsv_line();
+        assert_eq!(line, "P000001\t0.9500\t\t\t\t\t\t\t\t");
+        assert!(OverlapCluster
+            .pair_with_report(&m2n, &n2m, &params)
+            .unwrap()
+            .1
+            .is_none());
+    }
+
+    #[test]
+    fn reciprocal_hit_on_the_other_strand_is_flagged() {
+        let m2n = fixture(M2N);
+        let n2m = fixture(&N2M.replace("+\tmt\t16000\t8000", "-\tmt\t16000\t8000"));
+        let (loci, report) = ReciprocalBest
+            .pair_with_report(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        let flags: Vec<_> = report
+            .unwrap()
+            .iter()
+            .map(|r| r.strand_consistent)
+            .collect();
+        assert_eq!(flags, [Some(true), Some(true), Some(false)]);
+        // the locus keeps the forward record's strand
+        assert_eq!(loci[1].strand, Strand::Forward);
+    }
+
+    #[test]
+    fn fraction_parser_bounds() {
+        assert_eq!(parse_fraction("0.3"), Ok(0.3));
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("-0.1").is_err());
+        assert!(parse_fraction("x").is_err());
+    }
+
+    #[test]
+    fn cluster_collapses_fragmented_numt() {
+        let (m2n, n2m) = (fixture(M2N), fixture(N2M));
+        let loci = OverlapCluster
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        // Where the strategies differ: the two chr1 fragments (and their
+        // reciprocal n2m record) form one cluster.
+        assert_eq!(loci.len(), 2);
+        let chr1 = &loci[0];
+        assert_eq!(chr1.pair_id, "P000001");
+        assert_eq!(chr1.nuc_contig, "chr1");
+        assert_eq!((chr1.nuc_start, chr1.nuc_end), (5000, 6900));
+        assert_eq!((chr1.mito_start, chr1.mito_end), (100, 2000));
+        assert_eq!(chr1.aln_len, 1900);
+        let chr2 = &loci[1];
+        assert_eq!((chr2.nuc_start, chr2.nuc_end), (200, 1200));
+        assert!((chr2.aln_ident - 0.995).abs() < 1e-6);
+    }
+
+    #[test]
+    fn strand_is_carried_onto_loci() {
+        // chr2 NUMT inserted in reverse; the cluster takes its longest member's strand
+        let m2n = fixture(&M2N.replace("+\tchr2", "-\tchr2"));
+        let n2m = fixture(&N2M.replace("+\tmt\t16000\t8000", "-\tmt\t16000\t8000"));
+        let loci = ReciprocalBest
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        let strands: Vec<char> = loci.iter().map(|l| l.strand.as_char()).collect();
+        assert_eq!(strands, ['+', '-']);
+        let loci = OverlapCluster
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        let strands: Vec<char> = loci.iter().map(|l| l.strand.as_char()).collect();
+        assert_eq!(strands, ['+', '-']);
+    }
+
+    #[test]
+    fn cluster_requires_overlap_on_both_genomes() {
+        // Same nuclear interval but disjoint mito intervals → two clusters.
+        let m2n = fixture(
+            "mt\t16000\t0\t1000\t+\tchr1\t100000\t0\t1000\t950\t1000\t60\n\
+             mt\t16000\t5000\t6000\t+\tchr1\t100000\t500\t1500\t950\t1000\t60\n",
+        );
+        let loci = OverlapCluster
+            .pair(&m2n, &[], &PairingParams::default())
+            .unwrap();
+        assert_eq!(loci.len(), 2);
+    }
+
+    fn locus(nuc: (u32, u32), mito_start: u32, ident: f32) -> PairedLocus {
+        PairedLocus {
+            pair_id: String::new(),
+            nuc_contig: "chr1".into(),
+            nuc_start: nuc.0,
+            nuc_end: nuc.1,
+            mito_contig: "mt".into(),
+            mito_start,
+            mito_end: mito_start + (nuc.1 - nuc.0),
+            aln_len: nuc.1 - nuc.0,
+            aln_ident: ident,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    #[test]
+    fn split_pieces_are_not_merged_back() {
+        // two pieces of one alignment either side of a 500 bp deletion, and a
+        // fragment of another alignment right after the second piece
+        let mut pieces = vec![
+            locus((1000, 2000), 0, 0.99),
+            locus((2500, 3500), 1000, 0.99),
+            locus((3520, 4000), 2020, 0.95),
+        ];
+        pieces[0].parent_id = Some("chr1:1000-3500".into());
+        pieces[1].parent_id = Some("chr1:1000-3500".into());
+        let (merged, origin) = merge_loci(pieces.clone(), 1000);
+        assert_eq!(origin, [0, 1, 1]);
+        assert_eq!(
+            (
+                merged[1].nuc_start,
+                merged[1].nuc_end,
+                merged[1].n_support_alignments
+            ),
+            (2500, 4000, 2)
+        );
+        // without the shared parent, the gap alone decides
+        for l in &mut pieces {
+            l.parent_id = None;
+        }
+        let (merged, _) = merge_loci(pieces, 1000);
+        assert_eq!(merged.len(), 1);
+    }
+
+    #[test]
+    fn collapse_keeps_most_identical_of_overlapping_loci() {
+        let loci = vec![
+            // nested: all of the shorter one is shared
+            locus((1000, 2000), 0, 0.90),
+            locus((1200, 1800), 5000, 0.97),
+            // identical intervals
+            locus((10_000, 11_000), 0, 0.95),
+            locus((10_000, 11_000), 8000, 0.95),
+            // partial: 900 of 1000 bp shared, then 500 of 1000 bp
+            locus((20_000, 21_000), 0, 0.92),
+            locus((20_100, 21_100), 3000, 0.96),
+            locus((20_600, 21_600), 9000, 0.99),
+        ];
+        let (kept, origin) = collapse_overlapping(loci.clone(), COLLAPSE_MIN_OVERLAP);
+        let summary: Vec<_> = kept
+            .iter()
+            .map(|l| (l.nuc_start, l.mito_start, l.n_support_alignments))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1200, 5000, 2),
+                (10_000, 0, 2),
+                (20_100, 3000, 2),
+                (20_600, 9000, 1)
+            ]
+        );
+        assert_eq!(origin, [0, 0, 1, 1, 2, 2, 3]);
+        // the best identity of a group does not depend on the input order
+        let mut rev = loci.clone();
+        rev.reverse();
+        let (kept, _) = collapse_overlapping(rev, COLLAPSE_MIN_OVERLAP);
+        assert_eq!(kept.len(), 4);
+        assert!(kept.iter().any(|l| l.mito_start == 5000));
+        // 0 turns collapsing off
+        let (kept, origin) = collapse_overlapping(loci, 0.0);
+        assert_eq!(kept.len(), 7);
+        assert_eq!(origin, [0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn collapse_is_per_nuclear_contig() {
+        let mut other = locus((1000, 2000), 0, 0.99);
+        other.nuc_contig = "chr2".into();
+        let (kept, _) = collapse_overlapping(
+            vec![locus((1000, 2000), 0, 0.95), other],
+            COLLAPSE_MIN_OVERLAP,
+        );
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn tandem_repeat_hits_collapse_after_merging() {
+        // two adjacent fragments of one NUMT (merged first), and a hit from a
+        // distant mito segment onto the same nuclear stretch
+        let m2n = fixture(
+            "mt\t16000\t0\t1000\t+\tchr1\t100000\t0\t1000\t990\t1000\t60\n\
+             mt\t16000\t1010\t2000\t+\tchr1\t100000\t1010\t2000\t980\t990\t60\n\
+             mt\t16000\t9000\t9900\t+\tchr1\t100000\t1050\t1950\t880\t900\t60\n\
+             mt\t16000\t0\t1000\t+\tchr2\t50000\t0\t1000\t950\t1000\t60\n",
+        );
+        let (loci, report) = ReciprocalBest
+            .pair_with_report(&m2n, &[], &PairingParams::default())
+            .unwrap();
+        let ids: Vec<_> = loci
+            .iter()
+            .map(|l| (l.pair_id.as_str(), l.nuc_contig.as_str(), l.mito_start))
+            .collect();
+        assert_eq!(ids, [("P000001", "chr1", 0), ("P000002", "chr2", 0)]);
+        assert_eq!(loci[0].n_support_alignments, 3);
+        assert_eq!(loci[1].n_support_alignments, 1);
+        // report rows carry the ID of the locus their record ended up in
+        let report_ids: Vec<_> = report.unwrap().into_iter().map(|r| r.pair_id).collect();
+        assert_eq!(report_ids, ["P000001", "P000001", "P000001", "P000002"]);
+    }
+
+    #[test]
+    fn method_selects_strategy() {
+        assert_eq!(PairingMethod::Reciprocal.strategy().name(), "reciprocal");
+        assert_eq!(PairingMethod::Cluster.strategy().name(), "cluster");
+        assert_eq!(PairingMethod::default(), PairingMethod::Reciprocal);
+    }
+}
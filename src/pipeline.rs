@@ -0,0 +1,2823 @@
+//! Stages shared by `classify`, `reuse` and `pair`.
+//!
+//! PAF filtering and pairing (`PairingStage`) turns the assembly alignments
+//! into candidate loci; `pair` stops there. Before evidence is collected, the
+//! candidate count is checked against `--max-pairs` (`check_pair_count`).
+//!
+//! Evidence → scoring → output has two interchangeable paths that write the
+//! same files (pairs.tsv, classification.tsv, coverage.json, results.json,
+//! summary.tsv, summary.json):
+//!   * `write_in_memory` — pairs, evidence maps and rendered tables all live in RAM.
+//!   * `write_low_memory` (`--low-memory`) — pairs go to an on-disk store, evidence is
+//!     computed and appended to disk in chunks, and scoring/writing stream over both
+//!     files in step. Evidence and rendered rows are held a chunk at a time; a few
+//!     compact records per pair are still kept for the whole run (see `write_low_memory`).
+//!
+//! Both also write the NUMT regions (see `regions`), the optional density tracks
+//! (`--density-bedgraph`, see `density`) and the per-locus evidence cache that
+//! `reuse` rescores from (see `io::tmpfiles`).
+//!
+//! Evidence carries the settings it was collected with (`EvidenceParams`);
+//! both paths refuse evidence whose settings differ from the run's unless
+//! `--ignore-evidence-params`, and only warn when they are unknown.
+//!
+//! `classify` collects evidence through `CheckpointedEvidence`, which keeps a
+//! partial cache pair by pair so that `--max-runtime` or a SIGTERM can stop
+//! the run between pairs and `--resume` carry on (see `util::shutdown`).
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::boundary::{BedBounds, BoundsBedBuilder};
+use crate::columns::PairsColumns;
+use crate::compat::OutputCompat;
+use crate::control_region::{ControlRegionOverlap, ControlRegions};
+use crate::crossref::{CrossRefBuilder, CrossRefs};
+use crate::density::{self, DensityOptions};
+use crate::io::bam;
+use crate::io::bam_index::IndexedBams;
+use crate::io::fasta::{GapIndex, LengthCache};
+use crate::io::paf::{self, PafFilter, PafFilterStats, PafRecord};
+use crate::io::window_cache::WindowCache;
+use crate::mito_meta::MitoContigMeta;
+use crate::model::{
+    ClassifyParams, CoverageSummary, EvidenceCost, EvidenceParams, PairEvidence, PairedLocus,
+    PairingParams, SpanReads, SpanSummary, Weights,
+};
+use crate::pairing::{self, PairingMethod};
+use crate::regions::{self, RegionBuilder};
+use crate::run_stats::{RunStats, RunStatsBuilder};
+use crate::scoring::{self, DepthBaselines, ScoredPair, ScoringResults};
+use crate::self_exclusion;
+use crate::summary::{
+    self, ContigLengths, GenomeSizes, LocusFlags, Summary, SummaryBuilder, SummaryUnits, Tier,
+};
+use crate::util::mapping::AsmMapOptions;
+use crate::util::progress::Progress;
+use crate::util::shutdown::{Interrupted, Shutdown};
+use crate::util::stats::Quartiles;
+use crate::util::version::VersionInfo;
+use crate::util::warnings;
+
+/// Pairs per evidence chunk in `--low-memory` mode.
+pub const DEFAULT_CHUNK_SIZE: usize = 10_000;
+
+pub const PAF_FILTER_STATS_FILE: &str = "paf_filter_stats.json";
+pub const PAIRING_REPORT_FILE: &str = "pairing_report.tsv";
+pub const PAIRS_UNSUPPORTED_FILE: &str = "pairs_unsupported.tsv";
+
+/// Default `--max-pairs`: more candidates than this are refused before the
+/// evidence stage; a quarter of it only warns.
+pub const DEFAULT_MAX_PAIRS: usize = 2_000_000;
+
+/// Which files a run writes (`--outputs`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLevel {
+    /// run_manifest.json, summary.tsv / summary.json and warnings.json only:
+    /// nothing per pair is formatted or written.
+    Summary,
+    /// The per-pair and per-region files as well.
+    #[default]
+    Standard,
+    /// Also the optional ones: run_stats.json with the cost columns, the
+    /// density tracks and the rejected PAF lines.
+    Full,
+}
+
+impl OutputLevel {
+    /// Whether the per-pair, per-region and pairing-diagnostic files are written.
+    pub fn per_pair(self) -> bool {
+        self != OutputLevel::Summary
+    }
+
+    pub fn full(self) -> bool {
+        self == OutputLevel::Full
+    }
+
+    /// Refuse the `(flag, given)` options whose output `--outputs summary`
+    /// leaves out.
+    pub fn check_options(self, options: &[(&str, bool)]) -> Result<()> {
+        if self.per_pair() {
+            return Ok(());
+        }
+        match options.iter().find(|(_, given)| *given) {
+            Some((flag, _)) => Err(anyhow!(
+                "{flag} conflicts with --outputs summary, which writes no per-pair files"
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// PAF filters and pairing settings.
+pub struct PairingStage<'a> {
+    pub filter: PafFilter,
+    pub method: PairingMethod,
+    pub params: PairingParams,
+    /// minimap2 settings that wrote the PAFs, if onsm ran it (for the filter stats).
+    pub aligner: Option<&'a AsmMapOptions>,
+    /// Where to list the PAF lines the filters drop (`--debug-filters`).
+    pub rejected: Option<PathBuf>,
+    /// Set one-sided loci aside (`--require-reciprocal`, see `drop_one_sided`).
+    pub require_reciprocal: bool,
+    /// With `Summary`, the diagnostics below are not written.
+    pub outputs: OutputLevel,
+}
+
+/// Both PAFs after filtering.
+pub struct FilteredPafs {
+    pub m2n: Vec<PafRecord>,
+    pub n2m: Vec<PafRecord>,
+    pub m2n_stats: PafFilterStats,
+    pub n2m_stats: PafFilterStats,
+}
+
+impl PairingStage<'_> {
+    /// Read and filter the mito→nuclear and nuclear→mito PAFs.
+    pub fn read(&self, paf_m2n: &Path, paf_n2m: &Path) -> Result<FilteredPafs> {
+        let mut audit = match &self.rejected {
+            Some(p) => {
+                let mut w = BufWriter::new(fs::File::create(p)?);
+                writeln!(w, "{}", paf::REJECTED_TSV_HEADER)?;
+                Some(w)
+            }
+            None => None,
+        };
+        let (m2n, m2n_stats) = paf::read_paf_audited(
+            paf_m2n,
+            &self.filter,
+            audit.as_mut().map(|w| w as &mut dyn Write),
+        )?;
+        let (n2m, n2m_stats) = paf::read_paf_audited(
+            paf_n2m,
+            &self.filter,
+            audit.as_mut().map(|w| w as &mut dyn Write),
+        )?;
+        if let (Some(mut w), Some(p)) = (audit, &self.rejected) {
+            w.flush()?;
+            log::info!("rejected PAF records → {}", p.display());
+        }
+        Ok(FilteredPafs {
+            m2n,
+            n2m,
+            m2n_stats,
+            n2m_stats,
+        })
+    }
+
+    /// Drop records on `excluded_nuclear` contigs and pair the rest. Writes
+    /// paf_filter_stats.json, (for strategies that have one) pairing_report.tsv
+    /// and with `require_reciprocal` pairs_unsupported.tsv, unless `outputs`
+    /// is `Summary`.
+    pub fn pair(
+        &self,
+        pafs: FilteredPafs,
+        excluded_nuclear: &[String],
+        out_dir: &Path,
+    ) -> Result<Vec<PairedLocus>> {
+        let FilteredPafs {
+            mut m2n,
+            mut n2m,
+            m2n_stats,
+            n2m_stats,
+        } = pafs;
+        let n = self_exclusion::drop_excluded(&mut m2n, &mut n2m, excluded_nuclear);
+        if n > 0 {
+            log::info!("dropped {n} PAF record(s) on excluded nuclear contigs");
+        }
+        let reports = self.outputs.per_pair();
+        if reports {
+            paf::write_filter_stats(
+                &out_dir.join(PAF_FILTER_STATS_FILE),
+                &m2n_stats,
+                &n2m_stats,
+                self.aligner,
+            )?;
+        }
+        let strategy = self.method.strategy();
+        let (pairs, report) = strategy.pair_with_report(&m2n, &n2m, &self.params)?;
+        if let Some(rows) = report {
+            if reports {
+                pairing::write_pairing_report(&out_dir.join(PAIRING_REPORT_FILE), &rows)?;
+            }
+            let n = rows
+                .iter()
+                .filter(|r| r.strand_consistent == Some(false))
+                .count();
+            if n > 0 {
+                warnings::warn(
+                    warnings::STRAND_MISMATCH,
+                    format!(
+                        "{n} of {} mito→nuclear alignments have their reciprocal hit on the other strand (strand_consistent in {PAIRING_REPORT_FILE})",
+                        rows.len()
+                    ),
+                );
+            }
+        }
+        log::info!(
+            "paired {} candidate loci ({} pairing)",
+            pairs.len(),
+            strategy.name()
+        );
+        if self.require_reciprocal {
+            return drop_one_sided(pairs, reports.then_some(out_dir));
+        }
+        Ok(pairs)
+    }
+}
+
+/// `--require-reciprocal`: the loci a nuclear→mito alignment backs too; the
+/// one-sided rest, and with `--reciprocal-best` those whose hit is not the
+/// best one (`rbh` false), are listed in pairs_unsupported.tsv (the locus
+/// columns of pairs.tsv, under their pair IDs) in `out_dir` instead of being
+/// scored.
+pub fn drop_one_sided(pairs: Vec<PairedLocus>, out_dir: Option<&Path>) -> Result<Vec<PairedLocus>> {
+    let (kept, one_sided): (Vec<_>, Vec<_>) = pairs
+        .into_iter()
+        .partition(|p| p.reciprocal && p.rbh != Some(false));
+    let n_not_best = one_sided.iter().filter(|p| p.reciprocal).count();
+    let to = match out_dir {
+        Some(dir) => {
+            let path = dir.join(PAIRS_UNSUPPORTED_FILE);
+            fs::write(&path, pairing::loci_tsv(&one_sided))?;
+            format!(" → {}", path.display())
+        }
+        None => String::new(),
+    };
+    log::info!(
+        "--require-reciprocal: set {} one-sided loci and {n_not_best} without a reciprocal best hit aside{to}",
+        one_sided.len() - n_not_best,
+    );
+    Ok(kept)
+}
+
+/// Where the per-pair read evidence comes from.
+pub trait EvidenceSource {
+    /// Depths, spanning fractions and read lengths for one pair.
+    fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence>;
+
+    /// Settings the evidence is collected with; `None` if unknown (a cache
+    /// written by an older onsm).
+    fn params(&self) -> Option<EvidenceParams>;
+
+    /// Subprocesses run so far, for `--cost-columns`.
+    fn subprocess_calls(&self) -> u64 {
+        0
+    }
+
+    /// Fetch what is cheaper fetched for many pairs at once, before their
+    /// `pair_evidence` calls; nothing by default. Its cost goes to the stage,
+    /// not to the pairs.
+    fn prefetch(&self, _pairs: &[PairedLocus]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Genome-wide (nuclear, mito) median depths to normalize by; `None`
+    /// when the source cannot measure them, and the pairs' own medians are
+    /// used (see `bam::depth_baselines`).
+    fn genome_medians(&self) -> Result<Option<(f32, f32)>> {
+        Ok(None)
+    }
+}
+
+/// Refuse more than `max_pairs` candidates (0: no limit) before any evidence
+/// is collected, and warn above a quarter of it. Both messages describe the
+/// candidates' identities and lengths and what stricter filters would leave.
+pub fn check_pair_count(
+    pairs: &[PairedLocus],
+    max_pairs: usize,
+    min_id: f32,
+    min_len: u32,
+) -> Result<()> {
+    let n = pairs.len();
+    if max_pairs == 0 || n <= max_pairs / 4 {
+        return Ok(());
+    }
+    let guidance = pair_count_guidance(pairs, min_id, min_len);
+    if n > max_pairs {
+        return Err(anyhow!(
+            "{n} candidate pairs exceed --max-pairs {max_pairs}; stopping before the evidence \
+             stage, which would take samtools a very long time. {guidance}. Or raise \
+             --max-pairs (0: no limit)"
+        ));
+    }
+    warnings::warn(
+        warnings::PAIR_COUNT,
+        format!(
+            "{n} candidate pairs (--max-pairs {max_pairs}): evidence collection will be slow. {guidance}"
+        ),
+    );
+    Ok(())
+}
+
+/// Identity and length distribution of the candidates, and how many would
+/// pass a few stricter `--min-id` / `--min-len` values.
+fn pair_count_guidance(pairs: &[PairedLocus], min_id: f32, min_len: u32) -> String {
+    let idents: Vec<f64> = pairs.iter().map(|p| p.aln_ident as f64).collect();
+    let lens: Vec<f64> = pairs.iter().map(|p| p.aln_len as f64).collect();
+    let (qi, ql) = (Quartiles::of(&idents), Quartiles::of(&lens));
+    let mut s = format!(
+        "Candidates: identity {:.3}/{:.3}/{:.3}/{:.3}/{:.3}, length {:.0}/{:.0}/{:.0}/{:.0}/{:.0} bp \
+         (min/q1/median/q3/max)",
+        qi.min, qi.q1, qi.median, qi.q3, qi.max, ql.min, ql.q1, ql.median, ql.q3, ql.max
+    );
+    let at_id: Vec<String> = [0.8, 0.9, 0.95, 0.98, 0.99]
+        .into_iter()
+        .filter(|&t| t > min_id)
+        .take(3)
+        .map(|t| {
+            let k = pairs.iter().filter(|p| p.aln_ident >= t).count();
+            format!("--min-id {t}: {k}")
+        })
+        .collect();
+    let at_len: Vec<String> = [500, 1_000, 2_000, 5_000]
+        .into_iter()
+        .filter(|&t| t > min_len)
+        .take(3)
+        .map(|t| {
+            let k = pairs.iter().filter(|p| p.aln_len >= t).count();
+            format!("--min-len {t}: {k}")
+        })
+        .collect();
+    let at: Vec<String> = at_id.into_iter().chain(at_len).collect();
+    if !at.is_empty() {
+        s.push_str(&format!("; pairs left at classify {}", at.join(", ")));
+    }
+    s.push_str(
+        ". Try a stricter classify --min-id/--min-len, --mm2-secondary no (one placement per \
+         mito segment) or --pairing cluster (one locus per fragmented insertion)",
+    );
+    s
+}
+
+/// Evidence from the reads→assembly BAMs via `samtools`, or read from their
+/// indexes in process with `native` (see `io::bam`).
+pub struct SamtoolsEvidence {
+    pub bam_reads_to_nuc: PathBuf,
+    pub bam_reads_to_mito: PathBuf,
+    pub samtools: PathBuf,
+    pub flank: u32,
+    pub win: u32,
+    /// `--max-reads-per-window` (0 = no cap).
+    pub max_reads: u32,
+    /// `--depth-include-supplementary`.
+    pub depth_supplementary: bool,
+    /// `--span-mapq`.
+    pub min_span_mapq: u8,
+    /// `--junction-min-clip` (0: junction reads not counted).
+    pub junction_min_clip: u32,
+    /// BAM windows fetched before (`None` with `--no-cache`).
+    pub cache: Option<WindowCache>,
+    /// Topology of the mito contigs (`--mito-contig-meta`).
+    pub mito_meta: MitoContigMeta,
+    /// samtools runs so far; start it at 0.
+    pub calls: Cell<u64>,
+    /// `--bam-backend native`: the BAMs, opened on first use.
+    pub native: Option<IndexedBams>,
+    /// Depths of the pairs `prefetch` was given: one `samtools depth` per
+    /// BAM instead of one per window.
+    pub batched: RefCell<bam::BatchDepths>,
+    /// (CRAM, FASTA) for either input that is a CRAM (`reuse` of a run whose
+    /// BAMs were converted); empty for BAMs.
+    pub cram_references: Vec<(PathBuf, PathBuf)>,
+    /// Contig lengths of the two assemblies, to clamp windows at contig
+    /// ends (`None`: windows are left as they are).
+    pub contig_lengths: Option<ContigLengths>,
+}
+
+impl SamtoolsEvidence {
+    fn fetch(&self) -> bam::WindowFetch<'_> {
+        bam::WindowFetch::new(&self.samtools, self.cache.as_ref())
+            .with_native(self.native.as_ref())
+            .with_depth_supplementary(self.depth_supplementary)
+            .with_min_span_mapq(self.min_span_mapq)
+            .with_junction_min_clip(self.junction_min_clip)
+            .with_cram_references(&self.cram_references)
+            .with_contig_lengths(self.contig_lengths.as_ref())
+    }
+}
+
+impl EvidenceSource for SamtoolsEvidence {
+    fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+        let batched = self.batched.borrow();
+        let fetch = self.fetch().with_batched(Some(&batched));
+        let ev = bam::pair_evidence(
+            &self.bam_reads_to_nuc,
+            &self.bam_reads_to_mito,
+            p,
+            self.flank,
+            self.win,
+            self.max_reads,
+            self.mito_meta.circular(&p.mito_contig),
+            &fetch,
+        );
+        self.calls.set(self.calls.get() + fetch.subprocess_calls());
+        ev
+    }
+
+    fn subprocess_calls(&self) -> u64 {
+        self.calls.get()
+    }
+
+    fn prefetch(&self, pairs: &[PairedLocus]) -> Result<()> {
+        let fetch = self.fetch();
+        let batched = fetch.batch_depths(
+            &self.bam_reads_to_nuc,
+            &self.bam_reads_to_mito,
+            pairs,
+            self.flank,
+        );
+        self.calls.set(self.calls.get() + fetch.subprocess_calls());
+        *self.batched.borrow_mut() = batched?;
+        Ok(())
+    }
+
+    fn genome_medians(&self) -> Result<Option<(f32, f32)>> {
+        let fetch = self.fetch();
+        let medians = fetch.genome_median_depths(&self.bam_reads_to_nuc, &self.bam_reads_to_mito);
+        self.calls.set(self.calls.get() + fetch.subprocess_calls());
+        Ok(Some(medians?))
+    }
+
+    fn params(&self) -> Option<EvidenceParams> {
+        Some(
+            EvidenceParams::new(self.flank, self.win)
+                .with_depth_supplementary(self.depth_supplementary)
+                .with_min_mapq(self.min_span_mapq)
+                .with_junction_min_clip(self.junction_min_clip),
+        )
+    }
+}
+
+/// Output directory, assemblies (for genome totals) and scoring settings.
+pub struct OutputContext<'a> {
+    pub out_dir: &'a Path,
+    pub mito_fa: &'a Path,
+    pub nuc_fa: &'a Path,
+    /// The run's contig-length cache, so the FASTAs aren't rescanned here.
+    pub lengths: &'a LengthCache,
+    /// Assembly sizes recorded in the run manifest; read from the FASTAs
+    /// when absent.
+    pub genome_sizes: Option<&'a GenomeSizes>,
+    pub weights: Weights,
+    pub params: ClassifyParams,
+    /// Write `numt_density.bedgraph` / `nimt_density.bedgraph` with these settings.
+    pub density: Option<DensityOptions>,
+    /// pairs.tsv columns (`--columns`).
+    pub pairs_columns: &'a PairsColumns,
+    /// Likely_NUMT pairs this close on the nuclear side form one region (see `regions`).
+    pub region_merge_gap: u32,
+    /// Pairs of any call this close on the nuclear side form one region of
+    /// `regions.tsv` (`--region-gap`).
+    pub region_gap: u32,
+    /// Units of the percentage metrics in summary.tsv / summary.json.
+    pub summary_units: SummaryUnits,
+    /// Molecule type per mito contig (`--mito-contig-meta`).
+    pub mito_meta: &'a MitoContigMeta,
+    /// Also write each locus with its evidence here, for `reuse` without BAMs.
+    pub evidence_cache: Option<&'a Path>,
+    /// Nuclear contigs left out of the nuclear total (see `self_exclusion`).
+    pub excluded_nuclear: &'a [String],
+    /// N-runs and soft-masked runs of the nuclear assembly, for `spans_assembly_gap`,
+    /// `softmask_frac_nuc` and the NUMT bp figures.
+    pub gaps: &'a GapIndex,
+    /// `--mito-control-region`s, for `in_control_region`.
+    pub control_regions: &'a ControlRegions,
+    /// Settings the evidence must have been collected with…
+    pub evidence_params: EvidenceParams,
+    /// …unless this is set (`--ignore-evidence-params`), which only warns.
+    pub ignore_evidence_params: bool,
+    /// Also write the nuclear side of every pair as these BEDs (`--bed-bounds`).
+    pub bed_bounds: &'a [BedBounds],
+    /// Which pairs those BEDs take (`--bed-tier`).
+    pub bed_tier: Tier,
+    /// Output schema of classification.tsv and the summary (`--output-compat`;
+    /// pairs.tsv follows `pairs_columns`).
+    pub output_compat: OutputCompat,
+    /// With `Summary`, only summary.tsv / summary.json (`--outputs`).
+    pub outputs: OutputLevel,
+}
+
+/// Refuse evidence from `what` collected with other settings than the run's.
+fn check_evidence_params(
+    ctx: &OutputContext,
+    found: Option<EvidenceParams>,
+    what: &str,
+) -> Result<()> {
+    let Some(found) = found else {
+        warnings::warn(
+            warnings::EVIDENCE_PARAMS,
+            format!("{what} does not record its settings (older onsm?); assuming they match"),
+        );
+        return Ok(());
+    };
+    let diff = found.diff(&ctx.evidence_params);
+    if diff.is_empty() {
+        return Ok(());
+    }
+    let msg = format!(
+        "{what} was collected with other settings than this run ({})",
+        diff.join(", ")
+    );
+    if !ctx.ignore_evidence_params {
+        return Err(anyhow!(
+            "{msg}: mixing artifacts from different runs? Pass --ignore-evidence-params to score it anyway"
+        ));
+    }
+    warnings::warn(
+        warnings::EVIDENCE_PARAMS,
+        format!("{msg}; scoring it anyway (--ignore-evidence-params)"),
+    );
+    Ok(())
+}
+
+/// What `f` took: wall time, and the calls `source` made meanwhile.
+fn measured<T>(source: &dyn EvidenceSource, f: impl FnOnce() -> T) -> (T, EvidenceCost) {
+    let (t0, calls0) = (Instant::now(), source.subprocess_calls());
+    let v = f();
+    let cost = EvidenceCost {
+        cost_ms: t0.elapsed().as_secs_f64() * 1e3,
+        n_subprocess_calls: source.subprocess_calls() - calls0,
+    };
+    (v, cost)
+}
+
+/// Evidence for every pair from `source`, as the in-memory path expects it,
+/// with what each pair and the whole stage cost.
+pub fn collect_evidence(
+    pairs: &[PairedLocus],
+    source: &dyn EvidenceSource,
+) -> Result<(CoverageSummary, SpanSummary)> {
+    let mut per_pair_depth = HashMap::with_capacity(pairs.len());
+    let mut per_pair_span = HashMap::with_capacity(pairs.len());
+    let mut per_pair_read_len = HashMap::with_capacity(pairs.len());
+    let mut per_pair_read_div = HashMap::with_capacity(pairs.len());
+    let mut per_pair_reads = HashMap::new();
+    let mut per_pair_invalid = HashMap::new();
+    let mut per_pair_junction = HashMap::new();
+    let mut per_pair_clamped = HashMap::new();
+    let mut per_pair_cost = HashMap::with_capacity(pairs.len());
+    let mut nuc_locals = Vec::with_capacity(pairs.len());
+    let mut mito_locals = Vec::with_capacity(pairs.len());
+    let (t0, calls0) = (Instant::now(), source.subprocess_calls());
+    source.prefetch(pairs)?;
+    for (i, p) in pairs.iter().enumerate() {
+        if (i + 1) % 50 == 0 || i == 0 {
+            log::info!("evidence: {}/{} …", i + 1, pairs.len());
+        }
+        let (ev, cost) = measured(source, || source.pair_evidence(p));
+        let ev = bam::flag_invalid_window(p, ev)?;
+        per_pair_cost.insert(p.pair_id.clone(), cost);
+        per_pair_depth.insert(p.pair_id.clone(), ev.depth);
+        per_pair_span.insert(p.pair_id.clone(), ev.span);
+        per_pair_read_len.insert(p.pair_id.clone(), ev.read_len);
+        per_pair_read_div.insert(p.pair_id.clone(), ev.read_div);
+        if let Some(reads) = ev.span_reads {
+            per_pair_reads.insert(p.pair_id.clone(), reads);
+        }
+        if ev.junction_clips != (0, 0) {
+            per_pair_junction.insert(p.pair_id.clone(), ev.junction_clips);
+        }
+        if let Some(widths) = ev.clamped_window {
+            per_pair_clamped.insert(p.pair_id.clone(), widths);
+        }
+        // a flagged pair's zero depths are no sample of the genome's
+        match ev.invalid_window {
+            Some(invalid) => {
+                per_pair_invalid.insert(p.pair_id.clone(), invalid);
+            }
+            None => {
+                nuc_locals.push(ev.depth.0);
+                mito_locals.push(ev.depth.1);
+            }
+        }
+    }
+    let genome = source.genome_medians()?;
+    let stage_cost = EvidenceCost {
+        cost_ms: t0.elapsed().as_secs_f64() * 1e3,
+        n_subprocess_calls: source.subprocess_calls() - calls0,
+    };
+    let ((nuclear_median, mito_median), loci) =
+        bam::depth_baselines(genome, nuc_locals, mito_locals);
+    Ok((
+        CoverageSummary {
+            nuclear_median,
+            mito_median,
+            loci_medians: Some(loci),
+            per_pair: per_pair_depth,
+            params: source.params(),
+        },
+        SpanSummary {
+            per_pair: per_pair_span,
+            read_len: per_pair_read_len,
+            read_div: per_pair_read_div,
+            reads: per_pair_reads,
+            clamped_window: per_pair_clamped,
+            invalid_window: per_pair_invalid,
+            junction_clips: per_pair_junction,
+            params: source.params(),
+            cost: per_pair_cost,
+            stage_cost: Some(stage_cost),
+        },
+    ))
+}
+
+/// One line of the evidence cache: the locus and its raw (unnormalized) evidence.
+#[derive(Debug, Serialize, Deserialize)]
+struct EvidenceCacheRow {
+    locus: PairedLocus,
+    #[serde(flatten)]
+    evidence: PairEvidence,
+}
+
+/// Optional first line of the evidence cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct EvidenceCacheHeader {
+    evidence_params: EvidenceParams,
+    /// The run's depth baselines, so rescoring the cache normalizes as the
+    /// run did without the BAMs (absent from older caches).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    genome_medians: Option<(f32, f32)>,
+}
+
+/// Both-genome coordinates; pair IDs are renumbered by every pairing, so the
+/// cache is looked up by where a locus is rather than what it was called.
+type LocusKey = (String, u32, u32, String, u32, u32);
+
+fn locus_key(p: &PairedLocus) -> LocusKey {
+    (
+        p.nuc_contig.clone(),
+        p.nuc_start,
+        p.nuc_end,
+        p.mito_contig.clone(),
+        p.mito_start,
+        p.mito_end,
+    )
+}
+
+/// Evidence read back from a previous run's cache, per locus.
+pub struct CachedEvidence {
+    by_locus: HashMap<LocusKey, PairEvidence>,
+    /// From the header line; caches from older runs have none.
+    params: Option<EvidenceParams>,
+    /// From the header line too.
+    genome_medians: Option<(f32, f32)>,
+}
+
+impl CachedEvidence {
+    /// Load a cache; also returns its loci in the original pair order.
+    pub fn load(path: &Path) -> Result<(Vec<PairedLocus>, Self)> {
+        Self::read(path, false)
+    }
+
+    /// `load` for the partial cache of an interrupted run, whose last row may
+    /// have been cut short.
+    fn load_partial(path: &Path) -> Result<(Vec<PairedLocus>, Self)> {
+        Self::read(path, true)
+    }
+
+    fn read(path: &Path, partial: bool) -> Result<(Vec<PairedLocus>, Self)> {
+        let rdr = BufReader::new(fs::File::open(path)?);
+        let mut loci = Vec::new();
+        let mut by_locus = HashMap::new();
+        let (mut params, mut genome_medians) = (None, None);
+        let mut lines = rdr.lines().enumerate().peekable();
+        while let Some((i, line)) = lines.next() {
+            let line = line?;
+            if i == 0 {
+                if let Ok(h) = serde_json::from_str::<EvidenceCacheHeader>(&line) {
+                    (params, genome_medians) = (Some(h.evidence_params), h.genome_medians);
+                    continue;
+                }
+            }
+            let row: EvidenceCacheRow = match serde_json::from_str(&line) {
+                Err(_) if partial && lines.peek().is_none() => break,
+                r => r.with_context(|| format!("parse evidence cache {}", path.display()))?,
+            };
+            by_locus.insert(locus_key(&row.locus), row.evidence);
+            loci.push(row.locus);
+        }
+        Ok((
+            loci,
+            Self {
+                by_locus,
+                params,
+                genome_medians,
+            },
+        ))
+    }
+}
+
+impl EvidenceSource for CachedEvidence {
+    fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+        self.by_locus.get(&locus_key(p)).copied().ok_or_else(|| {
+            anyhow!(
+                "no cached evidence for {} (nuclear {}:{}-{}, mito {}:{}-{}): re-pairing produced \
+                 a locus the previous run did not score; recomputing evidence needs the read BAMs \
+                 (classify --keep all)",
+                p.pair_id,
+                p.nuc_contig,
+                p.nuc_start,
+                p.nuc_end,
+                p.mito_contig,
+                p.mito_start,
+                p.mito_end
+            )
+        })
+    }
+
+    fn params(&self) -> Option<EvidenceParams> {
+        self.params
+    }
+
+    fn genome_medians(&self) -> Result<Option<(f32, f32)>> {
+        Ok(self.genome_medians)
+    }
+}
+
+/// Evidence from `inner`, appended to a partial cache as each pair finishes so
+/// an interrupted run loses at most the pair in flight. Between pairs it checks
+/// `shutdown` and stops with `shutdown::Interrupted`; `--resume` reopens the
+/// file and only asks `inner` for the pairs it lacks. With a `Progress`, each
+/// pair it computes advances `progress.json`.
+pub struct CheckpointedEvidence<'a> {
+    inner: &'a dyn EvidenceSource,
+    shutdown: &'a Shutdown,
+    progress: Option<&'a Progress>,
+    done: HashMap<LocusKey, PairEvidence>,
+    out: RefCell<BufWriter<fs::File>>,
+    n_done: Cell<usize>,
+    n_total: usize,
+}
+
+impl<'a> CheckpointedEvidence<'a> {
+    /// Start `path` afresh, or with `resume` keep the rows it already has (a
+    /// row cut short by a kill is dropped).
+    pub fn open(
+        path: &Path,
+        inner: &'a dyn EvidenceSource,
+        shutdown: &'a Shutdown,
+        n_total: usize,
+        resume: bool,
+    ) -> Result<Self> {
+        let mut done = HashMap::new();
+        let mut rows = Vec::new();
+        if resume && path.exists() {
+            let (loci, cached) = CachedEvidence::load_partial(path)?;
+            if let (Some(found), Some(want)) = (cached.params, inner.params()) {
+                let diff = found.diff(&want);
+                if !diff.is_empty() {
+                    return Err(anyhow!(
+                        "--resume: {} was collected with other settings ({})",
+                        path.display(),
+                        diff.join(", ")
+                    ));
+                }
+            }
+            for locus in loci {
+                let ev = cached.by_locus[&locus_key(&locus)];
+                rows.push((locus, ev));
+            }
+            done = cached.by_locus;
+            log::info!(
+                "resuming with evidence for {} pair(s) from {}",
+                done.len(),
+                path.display()
+            );
+        }
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        write_cache_header(&mut w, inner.params(), None)?;
+        for (locus, ev) in rows {
+            write_cache_row(&mut w, locus, ev)?;
+        }
+        w.flush()?;
+        Ok(Self {
+            inner,
+            shutdown,
+            progress: None,
+            n_done: Cell::new(done.len()),
+            done,
+            out: RefCell::new(w),
+            n_total,
+        })
+    }
+
+    /// Report each pair to `progress` (see `util::progress`).
+    pub fn with_progress(mut self, progress: &'a Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Pairs whose evidence is in the partial cache.
+    pub fn pairs_done(&self) -> usize {
+        self.n_done.get()
+    }
+}
+
+impl EvidenceSource for CheckpointedEvidence<'_> {
+    fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+        if let Some(ev) = self.done.get(&locus_key(p)) {
+            return Ok(*ev);
+        }
+        if let Some(reason) = self.shutdown.check() {
+            return Err(Interrupted {
+                reason,
+                pairs_done: self.n_done.get(),
+                pairs_total: self.n_total,
+            }
+            .into());
+        }
+        let ev = self.inner.pair_evidence(p)?;
+        let mut w = self.out.borrow_mut();
+        write_cache_row(&mut *w, p.clone(), ev)?;
+        w.flush()?;
+        self.n_done.set(self.n_done.get() + 1);
+        if let Some(progress) = self.progress {
+            progress.advance(self.n_done.get() as u64);
+        }
+        Ok(ev)
+    }
+
+    fn params(&self) -> Option<EvidenceParams> {
+        self.inner.params()
+    }
+
+    fn subprocess_calls(&self) -> u64 {
+        self.inner.subprocess_calls()
+    }
+
+    /// Only the pairs the partial cache does not have.
+    fn prefetch(&self, pairs: &[PairedLocus]) -> Result<()> {
+        let todo: Vec<PairedLocus> = pairs
+            .iter()
+            .filter(|p| !self.done.contains_key(&locus_key(p)))
+            .cloned()
+            .collect();
+        self.inner.prefetch(&todo)
+    }
+
+    fn genome_medians(&self) -> Result<Option<(f32, f32)>> {
+        self.inner.genome_medians()
+    }
+}
+
+/// Loci and evidence rebuilt from a previous run's `results.json`, for
+/// rescoring when its evidence cache was not kept. The run's `coverage.json`
+/// supplies the raw depths and genome-wide medians when present; otherwise the
+/// stored ratios are used as depths over unit baselines, which scores the same.
+pub fn stored_evidence(run_dir: &Path) -> Result<(Vec<PairedLocus>, CoverageSummary, SpanSummary)> {
+    let results = ScoringResults::load_from(run_dir)
+        .with_context(|| format!("load results.json from {}", run_dir.display()))?;
+    let cov_path = run_dir.join("coverage.json");
+    let stored_cov: Option<CoverageSummary> = fs::File::open(&cov_path)
+        .ok()
+        .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok());
+    let mut coverage = stored_cov.unwrap_or_else(|| CoverageSummary {
+        nuclear_median: 1.0,
+        mito_median: 1.0,
+        loci_medians: None,
+        per_pair: HashMap::new(),
+        params: None,
+    });
+    // the spans in results.json were collected with coverage.json's depths
+    let mut spans = SpanSummary {
+        per_pair: HashMap::with_capacity(results.pairs.len()),
+        read_len: HashMap::with_capacity(results.pairs.len()),
+        read_div: HashMap::with_capacity(results.pairs.len()),
+        reads: HashMap::new(),
+        clamped_window: HashMap::new(),
+        invalid_window: HashMap::new(),
+        junction_clips: HashMap::new(),
+        params: coverage.params,
+        cost: HashMap::new(),
+        stage_cost: None,
+    };
+    let mut loci = Vec::with_capacity(results.pairs.len());
+    for sp in results.pairs {
+        let (id, f) = (&sp.locus.pair_id, &sp.features);
+        if !coverage.per_pair.contains_key(id) {
+            // ratio over its own baseline: scores back to the stored ratio
+            let (n, m) = (coverage.nuclear_median as f32, coverage.mito_median as f32);
+            coverage
+                .per_pair
+                .insert(id.clone(), (f.rnuc * n, f.rmito * m));
+        }
+        spans.per_pair.insert(id.clone(), (f.s_nuc, f.s_mito));
+        spans
+            .read_len
+            .insert(id.clone(), (f.read_len_nuc, f.read_len_mito));
+        spans
+            .read_div
+            .insert(id.clone(), (f.read_div_nuc, f.read_div_mito));
+        if let Some(invalid) = f.invalid_window {
+            spans.invalid_window.insert(id.clone(), invalid);
+        }
+        if let Some(widths) = f.clamped_window {
+            spans.clamped_window.insert(id.clone(), widths);
+        }
+        if (f.junction_clips_start, f.junction_clips_end) != (0, 0) {
+            spans
+                .junction_clips
+                .insert(id.clone(), (f.junction_clips_start, f.junction_clips_end));
+        }
+        // results.json keeps only the MAPQ exclusions of the window reads
+        if (f.low_mapq_nuc, f.low_mapq_mito) != (0, 0) {
+            let low = |low_mapq| SpanReads {
+                low_mapq,
+                ..Default::default()
+            };
+            spans
+                .reads
+                .insert(id.clone(), (low(f.low_mapq_nuc), low(f.low_mapq_mito)));
+        }
+        loci.push(sp.locus);
+    }
+    Ok((loci, coverage, spans))
+}
+
+fn write_cache_header(
+    w: &mut impl Write,
+    params: Option<EvidenceParams>,
+    genome_medians: Option<(f32, f32)>,
+) -> Result<()> {
+    if let Some(evidence_params) = params {
+        let header = EvidenceCacheHeader {
+            evidence_params,
+            genome_medians,
+        };
+        serde_json::to_writer(&mut *w, &header)?;
+        w.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn write_cache_row(w: &mut impl Write, locus: PairedLocus, evidence: PairEvidence) -> Result<()> {
+    serde_json::to_writer(&mut *w, &EvidenceCacheRow { locus, evidence })?;
+    w.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Score precomputed evidence and write every output from memory.
+pub fn write_in_memory(
+    ctx: &OutputContext,
+    pairs: &[PairedLocus],
+    coverage: &CoverageSummary,
+    spans: &SpanSummary,
+) -> Result<Summary> {
+    check_evidence_params(ctx, coverage.params, "the depth evidence")?;
+    check_evidence_params(ctx, spans.params, "the spanning evidence")?;
+    let out = ctx.out_dir;
+    if let Some(path) = ctx.evidence_cache {
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        // baselines a run before genome-wide sampling took from its loci are
+        // left to be taken from them again
+        let baselines = coverage
+            .loci_medians
+            .map(|_| (coverage.nuclear_median as f32, coverage.mito_median as f32));
+        write_cache_header(&mut w, coverage.params, baselines)?;
+        for p in pairs {
+            let get =
+                |m: &HashMap<String, (f32, f32)>| m.get(&p.pair_id).copied().unwrap_or_default();
+            let evidence = PairEvidence {
+                depth: get(&coverage.per_pair),
+                span: get(&spans.per_pair),
+                read_len: get(&spans.read_len),
+                read_div: spans.read_div.get(&p.pair_id).copied().unwrap_or_default(),
+                span_reads: spans.reads.get(&p.pair_id).copied(),
+                junction_clips: spans
+                    .junction_clips
+                    .get(&p.pair_id)
+                    .copied()
+                    .unwrap_or_default(),
+                clamped_window: spans.clamped_window.get(&p.pair_id).copied(),
+                invalid_window: spans.invalid_window.get(&p.pair_id).copied(),
+            };
+            write_cache_row(&mut w, p.clone(), evidence)?;
+        }
+        w.flush()?;
+    }
+    let mut scored = scoring::score_pairs(
+        pairs,
+        coverage,
+        spans,
+        ctx.gaps,
+        ctx.control_regions,
+        ctx.weights,
+        ctx.params,
+    );
+    for sp in &mut scored {
+        set_molecule_type(ctx, sp);
+        if ctx.pairs_columns.cost() {
+            sp.evidence_cost = spans.cost.get(&sp.locus.pair_id).copied();
+        }
+    }
+    let per_pair = ctx.outputs.per_pair();
+    let cross_refs =
+        per_pair.then(|| CrossRefs::of_pairs(scored.iter().map(|sp| (&sp.locus, sp.score.call))));
+    if let Some(cross_refs) = &cross_refs {
+        for sp in &mut scored {
+            sp.related_pairs = cross_refs.related(&sp.locus.pair_id);
+        }
+    }
+    let mut builder =
+        SummaryBuilder::default().with_shared_in_percentages(ctx.params.shared_in_percentages);
+    let mut regions = RegionBuilder::default();
+    let mut pair_regions = RegionBuilder::any_call();
+    let mut beds = BoundsBedBuilder::new(ctx.bed_bounds).with_tier(ctx.bed_tier);
+    let mut run_stats = RunStatsBuilder::default();
+    for sp in &scored {
+        add_to_summary(&mut builder, sp);
+        regions.add(sp);
+        pair_regions.add(sp);
+        beds.add(&sp.locus, sp.score.high_confidence);
+        run_stats.add(sp);
+    }
+    if ctx.pairs_columns.cost() {
+        RunStats::save_to(out, &run_stats.finish(spans.stage_cost, pairs.len()))?;
+    }
+
+    if let Some(cross_refs) = cross_refs {
+        fs::write(
+            out.join("pairs.tsv"),
+            scoring::pairs_tsv(&scored, ctx.pairs_columns),
+        )?;
+        fs::write(
+            out.join("classification.tsv"),
+            ctx.output_compat.classification_tsv(&scored),
+        )?;
+        cross_refs.write(out)?;
+        serde_json::to_writer_pretty(fs::File::create(out.join("coverage.json"))?, coverage)?;
+        ScoringResults::save_to(
+            out,
+            &ScoringResults {
+                weights: ctx.weights,
+                params: ctx.params,
+                pairs: scored,
+                build_info: Some(VersionInfo::build()),
+            },
+        )?;
+    }
+
+    write_summary_outputs(ctx, builder, [regions, pair_regions], beds)
+}
+
+fn set_molecule_type(ctx: &OutputContext, sp: &mut ScoredPair) {
+    sp.mito_molecule_type = ctx
+        .mito_meta
+        .molecule_type(&sp.locus.mito_contig)
+        .to_string();
+}
+
+fn add_to_summary(builder: &mut SummaryBuilder, sp: &ScoredPair) {
+    let flags = LocusFlags {
+        collapsed_repeat: sp.score.possible_collapsed_repeat(),
+        high_confidence: sp.score.high_confidence,
+        control_region: sp.features.control_region == ControlRegionOverlap::Full,
+    };
+    builder.add_flagged(&sp.locus, sp.score.call.as_str(), flags);
+}
+
+/// summary.tsv / summary.json and (unless `--outputs summary`) the NUMT
+/// regions and the regions of all pairs, plus the density tracks and
+/// `--bed-bounds` BEDs when requested.
+fn write_summary_outputs(
+    ctx: &OutputContext,
+    builder: SummaryBuilder,
+    [regions, pair_regions]: [RegionBuilder; 2],
+    beds: BoundsBedBuilder,
+) -> Result<Summary> {
+    let out = ctx.out_dir;
+    let regions = regions.finish(ctx.region_merge_gap);
+    let pair_regions = pair_regions.finish(ctx.region_gap);
+    if ctx.outputs.per_pair() {
+        regions::write_numt_regions(out, &regions)?;
+        regions::write_regions(out, &pair_regions)?;
+    }
+    if let Some(opts) = ctx.density {
+        density::write_density_tracks(out, ctx.mito_fa, ctx.nuc_fa, ctx.lengths, &builder, opts)?;
+    }
+    let read;
+    let sizes = match ctx.genome_sizes {
+        Some(sizes) => sizes,
+        None => {
+            read = GenomeSizes::read(ctx.lengths, ctx.mito_fa, ctx.nuc_fa)?;
+            &read
+        }
+    };
+    beds.write(out, &sizes.contigs.nuclear)?;
+    let (mito_bp_total, nuclear_bp_total) = sizes.totals(ctx.excluded_nuclear);
+    let numt_intervals = builder.numt_nuclear_intervals();
+    let by_type = if ctx.mito_meta.is_empty() {
+        Default::default()
+    } else {
+        builder.nimt_by_molecule_type(ctx.mito_meta, &sizes.contigs.mito)
+    };
+    let mut summary_tbl = builder
+        .finish(mito_bp_total, nuclear_bp_total)
+        .with_numt_gap_bp(ctx.gaps.n_bases_in(&numt_intervals))
+        .with_numt_softmask_bp(ctx.gaps.masked_bases_in(&numt_intervals))
+        .with_numt_regions(regions.len())
+        .with_regions(pair_regions.len())
+        .with_nimt_by_molecule_type(by_type);
+    if ctx.params.span_evidence_disabled {
+        summary_tbl = summary_tbl.with_caveat(scoring::SPAN_EVIDENCE_DISABLED);
+    }
+    if ctx.params.self_assembly_caveat {
+        summary_tbl = summary_tbl.with_caveat(scoring::SELF_ASSEMBLY_CAVEAT);
+    }
+    if ctx.params.manual_pair_list {
+        summary_tbl = summary_tbl.with_caveat(scoring::MANUAL_PAIR_LIST);
+    }
+    let compat = ctx.output_compat;
+    fs::write(
+        out.join("summary.tsv"),
+        compat.summary_tsv(summary::summary_tsv(&summary_tbl, ctx.summary_units)?),
+    )?;
+    serde_json::to_writer_pretty(
+        fs::File::create(out.join("summary.json"))?,
+        &compat.summary_json(summary::summary_json(&summary_tbl, ctx.summary_units)),
+    )?;
+    Ok(summary_tbl)
+}
+
+/// One line of the on-disk evidence store.
+#[derive(Debug, Serialize, Deserialize)]
+struct EvidenceRow {
+    pair_id: String,
+    #[serde(flatten)]
+    evidence: PairEvidence,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cost: Option<EvidenceCost>,
+}
+
+/// Streaming equivalent of evidence collection + `write_in_memory`.
+///
+/// `work_dir` holds the temporary stores (`pairs.jsonl`, `evidence.jsonl`);
+/// they are removed once the outputs are written.
+///
+/// Only the evidence and the rendered rows are bounded by `chunk_size`. Kept
+/// for every pair until the outputs are written: the two depths behind the
+/// depth medians, a region component (`regions.tsv`, and `numt_regions.*` for
+/// NUMTs), the identities behind the summary and, with `--bed-bounds`, the
+/// locus. With the per-pair files it also keeps each call, the loci of the
+/// decided calls and every pair's `related_pairs`, which grow with how many
+/// pairs overlap one another.
+pub fn write_low_memory(
+    ctx: &OutputContext,
+    pairs: impl IntoIterator<Item = PairedLocus>,
+    evidence: &dyn EvidenceSource,
+    work_dir: &Path,
+    chunk_size: usize,
+) -> Result<Summary> {
+    if chunk_size == 0 {
+        return Err(anyhow!("--chunk-size must be at least 1"));
+    }
+    check_evidence_params(ctx, evidence.params(), "the read evidence")?;
+    fs::create_dir_all(work_dir)?;
+    let pair_store = work_dir.join("pairs.jsonl");
+    let evidence_store = work_dir.join("evidence.jsonl");
+
+    // 1) Pairs → disk, in pair_id order (the order pairing assigns IDs)
+    let n_pairs = write_pair_store(&pair_store, pairs)?;
+    log::info!(
+        "low-memory: stored {n_pairs} pairs in {} (chunk size {chunk_size})",
+        pair_store.display()
+    );
+
+    // 2) Evidence, chunk by chunk, appended to disk
+    let mut nuc_locals = Vec::with_capacity(n_pairs);
+    let mut mito_locals = Vec::with_capacity(n_pairs);
+    let (t0, calls0) = (Instant::now(), evidence.subprocess_calls());
+    {
+        let mut ev_out = BufWriter::new(fs::File::create(&evidence_store)?);
+        let mut store = read_pair_store(&pair_store)?;
+        let mut chunk = Vec::with_capacity(chunk_size.min(n_pairs));
+        let mut done = 0usize;
+        loop {
+            chunk.clear();
+            for p in store.by_ref().take(chunk_size) {
+                chunk.push(p?);
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            evidence.prefetch(&chunk)?;
+            for p in &chunk {
+                let (ev, cost) = measured(evidence, || evidence.pair_evidence(p));
+                let ev = bam::flag_invalid_window(p, ev)?;
+                if ev.invalid_window.is_none() {
+                    nuc_locals.push(ev.depth.0);
+                    mito_locals.push(ev.depth.1);
+                }
+                let row = EvidenceRow {
+                    pair_id: p.pair_id.clone(),
+                    evidence: ev,
+                    cost: ctx.pairs_columns.cost().then_some(cost),
+                };
+                serde_json::to_writer(&mut ev_out, &row)?;
+                ev_out.write_all(b"\n")?;
+            }
+            done += chunk.len();
+            log::info!("low-memory: evidence {done}/{n_pairs} …");
+        }
+        ev_out.flush()?;
+    }
+    let genome = evidence.genome_medians()?;
+    let stage_cost = EvidenceCost {
+        cost_ms: t0.elapsed().as_secs_f64() * 1e3,
+        n_subprocess_calls: evidence.subprocess_calls() - calls0,
+    };
+
+    // Same baselines as `bam::compute_coverage_and_spans_with_tools`
+    let ((nuclear_median, mito_median), loci) =
+        bam::depth_baselines(genome, nuc_locals, mito_locals);
+    let baselines = DepthBaselines {
+        nuclear_median: nuclear_median as f32,
+        mito_median: mito_median as f32,
+    };
+
+    let score = |p: &PairedLocus, ev: &EvidenceRow| {
+        let gaps = ctx.gaps.stats(&p.nuc_contig, p.nuc_start, p.nuc_end);
+        let control_region = ctx
+            .control_regions
+            .overlap(&p.mito_contig, p.mito_start, p.mito_end);
+        let mut sp = scoring::score_pair(
+            p,
+            &ev.evidence,
+            gaps,
+            control_region,
+            baselines,
+            ctx.weights,
+            ctx.params,
+        );
+        set_molecule_type(ctx, &mut sp);
+        sp.evidence_cost = ev.cost;
+        sp
+    };
+
+    // 3) Cross-references: the loci of the decided calls, then every pair's
+    // counterparts against them (only for the per-pair files)
+    let cross_refs = if ctx.outputs.per_pair() {
+        let mut xref = CrossRefBuilder::default();
+        let mut calls = Vec::with_capacity(n_pairs);
+        for row in stored_rows(&pair_store, &evidence_store)? {
+            let (p, ev) = row?;
+            let call = score(&p, &ev).score.call;
+            xref.add(&p, call);
+            calls.push(call);
+        }
+        let mut xref = xref.index();
+        for (p, call) in read_pair_store(&pair_store)?.zip(calls) {
+            xref.add(&p?, call);
+        }
+        Some(xref.finish())
+    } else {
+        None
+    };
+
+    // 4) Score & write, streaming over both stores in step
+    let out = ctx.out_dir;
+    let mut pair_w = match cross_refs {
+        Some(_) => Some(PairWriters::create(
+            ctx,
+            evidence.params(),
+            (nuclear_median, mito_median),
+            loci,
+        )?),
+        None => None,
+    };
+    let mut cache_w = match ctx.evidence_cache {
+        Some(path) => {
+            let mut w = BufWriter::new(fs::File::create(path)?);
+            let baselines = (nuclear_median as f32, mito_median as f32);
+            write_cache_header(&mut w, evidence.params(), Some(baselines))?;
+            Some(w)
+        }
+        None => None,
+    };
+
+    let mut builder =
+        SummaryBuilder::default().with_shared_in_percentages(ctx.params.shared_in_percentages);
+    let mut regions = RegionBuilder::default();
+    let mut pair_regions = RegionBuilder::any_call();
+    let mut beds = BoundsBedBuilder::new(ctx.bed_bounds).with_tier(ctx.bed_tier);
+    let mut run_stats = RunStatsBuilder::default();
+    for row in stored_rows(&pair_store, &evidence_store)? {
+        let (p, ev) = row?;
+        let mut sp = score(&p, &ev);
+        if let (Some(w), Some(cross_refs)) = (pair_w.as_mut(), &cross_refs) {
+            sp.related_pairs = cross_refs.related(&p.pair_id);
+            w.row(ctx, &sp, ev.evidence.depth)?;
+        }
+        add_to_summary(&mut builder, &sp);
+        regions.add(&sp);
+        pair_regions.add(&sp);
+        beds.add(&sp.locus, sp.score.high_confidence);
+        run_stats.add(&sp);
+        if let Some(w) = cache_w.as_mut() {
+            write_cache_row(w, p, ev.evidence)?;
+        }
+    }
+    if let Some(w) = pair_w {
+        w.finish()?;
+    }
+    if let Some(w) = cache_w.as_mut() {
+        w.flush()?;
+    }
+
+    if ctx.pairs_columns.cost() {
+        RunStats::save_to(out, &run_stats.finish(Some(stage_cost), n_pairs))?;
+    }
+    if let Some(cross_refs) = cross_refs {
+        cross_refs.write(out)?;
+    }
+    let summary_tbl = write_summary_outputs(ctx, builder, [regions, pair_regions], beds)?;
+
+    let _ = fs::remove_file(&pair_store);
+    let _ = fs::remove_file(&evidence_store);
+    Ok(summary_tbl)
+}
+
+/// pairs.tsv, classification.tsv, coverage.json and results.json of the
+/// streaming path, written a pair at a time.
+struct PairWriters {
+    pairs: BufWriter<fs::File>,
+    class: BufWriter<fs::File>,
+    cov: BufWriter<fs::File>,
+    res: BufWriter<fs::File>,
+    n: usize,
+}
+
+impl PairWriters {
+    /// Create the files and write everything before the first pair.
+    fn create(
+        ctx: &OutputContext,
+        params: Option<EvidenceParams>,
+        (nuclear_median, mito_median): (f64, f64),
+        loci_medians: (f64, f64),
+    ) -> Result<Self> {
+        let out = ctx.out_dir;
+        let mut w = Self {
+            pairs: BufWriter::new(fs::File::create(out.join("pairs.tsv"))?),
+            class: BufWriter::new(fs::File::create(out.join("classification.tsv"))?),
+            cov: BufWriter::new(fs::File::create(out.join("coverage.json"))?),
+            res: BufWriter::new(fs::File::create(out.join("results.json"))?),
+            n: 0,
+        };
+        writeln!(w.pairs, "{}", ctx.pairs_columns.header())?;
+        writeln!(w.class, "{}", ctx.output_compat.classification_header())?;
+        write!(
+            w.cov,
+            "{{\"nuclear_median\":{},\"mito_median\":{},\"loci_medians\":{},",
+            serde_json::to_string(&nuclear_median)?,
+            serde_json::to_string(&mito_median)?,
+            serde_json::to_string(&loci_medians)?
+        )?;
+        if let Some(params) = params {
+            write!(w.cov, "\"params\":{},", serde_json::to_string(&params)?)?;
+        }
+        write!(w.cov, "\"per_pair\":{{")?;
+        write!(
+            w.res,
+            "{{\"weights\":{},\"params\":{},\"build_info\":{},\"pairs\":[",
+            serde_json::to_string(&ctx.weights)?,
+            serde_json::to_string(&ctx.params)?,
+            serde_json::to_string(&VersionInfo::build())?
+        )?;
+        Ok(w)
+    }
+
+    fn row(&mut self, ctx: &OutputContext, sp: &ScoredPair, depth: (f32, f32)) -> Result<()> {
+        writeln!(self.pairs, "{}", ctx.pairs_columns.row(sp))?;
+        writeln!(self.class, "{}", ctx.output_compat.classification_row(sp))?;
+        let sep = if self.n == 0 { "" } else { "," };
+        write!(
+            self.cov,
+            "{sep}{}:{}",
+            serde_json::to_string(&sp.locus.pair_id)?,
+            serde_json::to_string(&depth)?
+        )?;
+        write!(self.res, "{sep}")?;
+        serde_json::to_writer(&mut self.res, sp)?;
+        self.n += 1;
+        Ok(())
+    }
+
+    /// Close the JSON documents and flush.
+    fn finish(mut self) -> Result<()> {
+        write!(self.cov, "}}}}")?;
+        write!(self.res, "]}}")?;
+        for w in [
+            &mut self.pairs,
+            &mut self.class,
+            &mut self.cov,
+            &mut self.res,
+        ] {
+            w.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Write pairs as JSON lines; returns how many were written.
+fn write_pair_store(path: &Path, pairs: impl IntoIterator<Item = PairedLocus>) -> Result<usize> {
+    let mut w = BufWriter::new(fs::File::create(path)?);
+    let mut n = 0usize;
+    for p in pairs {
+        serde_json::to_writer(&mut w, &p)?;
+        w.write_all(b"\n")?;
+        n += 1;
+    }
+    w.flush()?;
+    Ok(n)
+}
+
+/// The pair and evidence stores, read in step.
+fn stored_rows(
+    pair_store: &Path,
+    evidence_store: &Path,
+) -> Result<impl Iterator<Item = Result<(PairedLocus, EvidenceRow)>>> {
+    let display = evidence_store.display().to_string();
+    let ev_rdr = BufReader::new(fs::File::open(evidence_store)?).lines();
+    Ok(read_pair_store(pair_store)?
+        .zip(ev_rdr)
+        .map(move |(p, ev)| {
+            let p = p?;
+            let ev: EvidenceRow =
+                serde_json::from_str(&ev?).with_context(|| format!("parse {display}"))?;
+            if ev.pair_id != p.pair_id {
+                return Err(anyhow!(
+                    "evidence store out of step with pair store at {} (expected {})",
+                    ev.pair_id,
+                    p.pair_id
+                ));
+            }
+            Ok((p, ev))
+        }))
+}
+
+/// Sequential reader over a pair store.
+fn read_pair_store(path: &Path) -> Result<impl Iterator<Item = Result<PairedLocus>>> {
+    let display = path.display().to_string();
+    let rdr = BufReader::new(fs::File::open(path)?);
+    Ok(rdr.lines().map(move |line| {
+        let line = line?;
+        serde_json::from_str(&line).with_context(|| format!("parse pair store {display}"))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fasta::{self, FastaCompression};
+    use crate::io::paf::IdentityKind;
+    use crate::model::{self, orientation::Strand};
+    use crate::util::progress::{ProgressReport, ProgressState, Stage, PROGRESS_FILE};
+    use tempfile::TempDir;
+
+    fn synthetic_pair(i: usize) -> PairedLocus {
+        let nuc_start = (i as u32 / 3) * 3_000;
+        let mito_start = ((i as u32) * 911) % 15_000;
+        PairedLocus {
+            pair_id: format!("P{:06}", i + 1),
+            nuc_contig: format!("chr{}", 1 + i % 3),
+            nuc_start,
+            nuc_end: nuc_start + 500 + (i as u32 % 7) * 100,
+            mito_contig: "mt".into(),
+            mito_start,
+            mito_end: mito_start + 500 + (i as u32 % 7) * 100,
+            aln_len: 500 + (i as u32 % 7) * 100,
+            aln_ident: 0.9 + (i % 10) as f32 / 100.0,
+            strand: if i.is_multiple_of(4) {
+                Strand::Reverse
+            } else {
+                Strand::Forward
+            },
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    fn write_fastas(dir: &Path) -> (PathBuf, PathBuf) {
+        let mito = dir.join("mito.fa");
+        let nuc = dir.join("nuc.fa");
+        fs::write(&mito, format!(">mt\n{}\n", "A".repeat(16_000))).unwrap();
+        let mut s = String::new();
+        for c in 1..=3 {
+            s.push_str(&format!(">chr{c}\n{}\n", "C".repeat(200_000)));
+        }
+        fs::write(&nuc, s).unwrap();
+        (mito, nuc)
+    }
+
+    /// Deterministic evidence that yields a mix of NUMT/NIMT/Ambiguous calls.
+    struct Synthetic;
+
+    impl EvidenceSource for Synthetic {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            let k = p.nuc_start / 3_000 + p.aln_len;
+            Ok(PairEvidence {
+                depth: (20.0 + (k % 5) as f32, 100.0 * ((k % 4) as f32) / 2.0),
+                span: ((k % 3) as f32 / 3.0, (k % 5) as f32 / 5.0),
+                read_len: (200.0 + 400.0 * (k % 3) as f32, 15_000.0),
+                read_div: Default::default(),
+                span_reads: None,
+                junction_clips: (0, 0),
+                clamped_window: None,
+                invalid_window: None,
+            })
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Some(EvidenceParams::new(model::FLANK_BP, model::WIN_BP))
+        }
+    }
+
+    static LENGTHS: std::sync::LazyLock<LengthCache> = std::sync::LazyLock::new(Default::default);
+    static GAPS: std::sync::LazyLock<GapIndex> = std::sync::LazyLock::new(Default::default);
+    static CONTROL_REGIONS: std::sync::LazyLock<ControlRegions> =
+        std::sync::LazyLock::new(Default::default);
+    static COLUMNS: std::sync::LazyLock<PairsColumns> = std::sync::LazyLock::new(Default::default);
+    static MITO_META: std::sync::LazyLock<MitoContigMeta> =
+        std::sync::LazyLock::new(Default::default);
+
+    fn ctx<'a>(out: &'a Path, mito: &'a Path, nuc: &'a Path) -> OutputContext<'a> {
+        OutputContext {
+            out_dir: out,
+            mito_fa: mito,
+            nuc_fa: nuc,
+            lengths: &LENGTHS,
+            genome_sizes: None,
+            weights: Weights::default(),
+            params: ClassifyParams::default(),
+            density: Some(DensityOptions {
+                window: 50_000,
+                keep_empty: true,
+            }),
+            pairs_columns: &COLUMNS,
+            region_merge_gap: regions::DEFAULT_REGION_MERGE_GAP,
+            region_gap: regions::DEFAULT_REGION_GAP,
+            summary_units: SummaryUnits::default(),
+            mito_meta: &MITO_META,
+            evidence_cache: None,
+            excluded_nuclear: &[],
+            gaps: &GAPS,
+            control_regions: &CONTROL_REGIONS,
+            evidence_params: EvidenceParams::new(model::FLANK_BP, model::WIN_BP),
+            ignore_evidence_params: false,
+            bed_bounds: &[],
+            bed_tier: Tier::All,
+            output_compat: OutputCompat::Latest,
+            outputs: OutputLevel::Standard,
+        }
+    }
+
+    fn read_json(p: &Path) -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(p).unwrap()).unwrap()
+    }
+
+    fn assert_same_outputs(a: &Path, b: &Path) {
+        for f in [
+            "pairs.tsv",
+            "classification.tsv",
+            "summary.tsv",
+            "numt_density.bedgraph",
+            "nimt_density.bedgraph",
+            "numt_regions.tsv",
+            "numt_regions.bed",
+            "regions.tsv",
+            "cross_call_conflicts.tsv",
+        ] {
+            assert_eq!(
+                fs::read_to_string(a.join(f)).unwrap(),
+                fs::read_to_string(b.join(f)).unwrap(),
+                "{f} differs"
+            );
+        }
+        for f in ["coverage.json", "results.json", "summary.json"] {
+            assert_eq!(read_json(&a.join(f)), read_json(&b.join(f)), "{f} differs");
+        }
+        let results = ScoringResults::load_from(b).unwrap();
+        assert_eq!(results.build_info, Some(VersionInfo::build()));
+    }
+
+    #[test]
+    fn low_memory_matches_in_memory() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..53).map(synthetic_pair).collect();
+
+        // in-memory reference, evidence gathered the same way
+        let a = dir.path().join("a");
+        fs::create_dir_all(&a).unwrap();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        // one cache for both runs, as within a single classify
+        let lengths = LengthCache::default();
+        let ctx_a = OutputContext {
+            lengths: &lengths,
+            genome_sizes: None,
+            ..ctx(&a, &mito, &nuc)
+        };
+        let sa = write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+
+        // streaming, with a chunk size that doesn't divide the pair count
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        let work = dir.path().join("work");
+        let ctx_b = OutputContext {
+            lengths: &lengths,
+            genome_sizes: None,
+            ..ctx(&b, &mito, &nuc)
+        };
+        let sb = write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &work, 7).unwrap();
+        // totals and density windows, on both paths, from one scan per FASTA
+        assert_eq!(lengths.scans(), 2);
+
+        assert!(
+            sa.n_numt > 0 && sa.n_nimt > 0,
+            "fixture exercises both calls"
+        );
+        assert_eq!(sa.n_pairs, sb.n_pairs);
+        let conflicts = fs::read_to_string(a.join(crate::crossref::CROSS_CALL_CONFLICTS_FILE));
+        assert!(
+            conflicts.unwrap().lines().count() > 1,
+            "fixture has cross-call conflicts"
+        );
+        assert_same_outputs(&a, &b);
+        assert!(!work.join("pairs.jsonl").exists(), "stores cleaned up");
+    }
+
+    #[test]
+    fn bgzipped_assemblies_match_plain() {
+        let dir = TempDir::new().unwrap();
+        let mito_seq = b"ACGT".repeat(4_000);
+        let nuc: Vec<(String, Vec<u8>)> = (1..=3)
+            .map(|c| {
+                // gaps and soft-masked stretches in every locus, and one
+                // across a bgzip block boundary
+                let mut seq = b"CATG".repeat(50_000);
+                for start in (0..60_000).step_by(3_000) {
+                    seq[start + 100..start + 200].fill(b'N');
+                    seq[start + 200..start + 300].make_ascii_lowercase();
+                }
+                seq[65_000..65_600].fill(b'N');
+                (format!("chr{c}"), seq)
+            })
+            .collect();
+        let pairs: Vec<PairedLocus> = (0..53).map(synthetic_pair).collect();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let run = |name: &str, ext: &str| {
+            let (mito, nuc_fa) = (
+                dir.path().join(format!("mito.{ext}")),
+                dir.path().join(format!("nuc.{ext}")),
+            );
+            fasta::write_fasta(&mito, &[("mt".to_string(), mito_seq.clone())]).unwrap();
+            fasta::write_fasta(&nuc_fa, &nuc).unwrap();
+            fasta::validate_fasta(&nuc_fa).unwrap();
+            fasta::check_not_swapped(
+                &fasta::fasta_report(&mito).unwrap(),
+                &fasta::fasta_report(&nuc_fa).unwrap(),
+            )
+            .unwrap();
+            let gaps = GapIndex::scan(&nuc_fa).unwrap();
+            assert_eq!(gaps.stats("chr2", 64_000, 66_000).n_bases, 600);
+            assert_eq!(gaps.stats("chr2", 3_000, 3_500).masked_bases, 100);
+            let out = dir.path().join(name);
+            fs::create_dir_all(&out).unwrap();
+            let lengths = LengthCache::default();
+            let ctx = OutputContext {
+                lengths: &lengths,
+                gaps: &gaps,
+                ..ctx(&out, &mito, &nuc_fa)
+            };
+            let s = write_in_memory(&ctx, &pairs, &coverage, &spans).unwrap();
+            (out, s)
+        };
+        let (plain, s) = run("plain", "fa");
+        let (bgz, _) = run("bgz", "fa.gz");
+        assert_eq!(
+            FastaCompression::detect(&dir.path().join("nuc.fa.gz")).unwrap(),
+            FastaCompression::Bgzip
+        );
+        assert!(s.nuclear_bp_numt_n > 0 && s.nuclear_bp_numt_softmasked > 0);
+        assert_same_outputs(&plain, &bgz);
+    }
+
+    /// `Synthetic`, for pairs whose windows can be built.
+    struct Windowed;
+
+    impl EvidenceSource for Windowed {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            bam::pair_windows(p, model::FLANK_BP, None)?;
+            Synthetic.pair_evidence(p)
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Synthetic.params()
+        }
+    }
+
+    #[test]
+    fn invalid_windows_are_flagged_on_both_paths() {
+        use crate::io::bam::WindowError;
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let mut pairs: Vec<PairedLocus> = (0..12).map(synthetic_pair).collect();
+        pairs[3].nuc_end = pairs[3].nuc_start;
+        let p = &mut pairs[7];
+        (p.mito_start, p.mito_end) = (p.mito_end, p.mito_start);
+
+        let (coverage, spans) = collect_evidence(&pairs, &Windowed).unwrap();
+        let flagged = |id: &str| spans.invalid_window.get(id).copied();
+        assert_eq!(spans.invalid_window.len(), 2);
+        assert_eq!(flagged("P000004"), Some(WindowError::ZeroWidth));
+        assert_eq!(flagged("P000008"), Some(WindowError::Inverted));
+        // the genome-wide medians leave them out
+        let valid: Vec<PairedLocus> = pairs
+            .iter()
+            .filter(|p| flagged(&p.pair_id).is_none())
+            .cloned()
+            .collect();
+        let (valid_cov, _) = collect_evidence(&valid, &Synthetic).unwrap();
+        assert_eq!(coverage.nuclear_median, valid_cov.nuclear_median);
+        assert_eq!(coverage.mito_median, valid_cov.mito_median);
+
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        write_in_memory(&ctx(&a, &mito, &nuc), &pairs, &coverage, &spans).unwrap();
+        let work = dir.path().join("work");
+        write_low_memory(&ctx(&b, &mito, &nuc), pairs.clone(), &Windowed, &work, 5).unwrap();
+        assert_same_outputs(&a, &b);
+
+        let results = ScoringResults::load_from(&a).unwrap();
+        for sp in &results.pairs {
+            let invalid = flagged(&sp.locus.pair_id);
+            assert_eq!(sp.features.invalid_window, invalid);
+            if invalid.is_some() {
+                assert_eq!(sp.score.call, scoring::Call::Ambiguous);
+                assert!(!sp.score.high_confidence);
+                assert_eq!(
+                    sp.score.reason_codes.last().unwrap(),
+                    scoring::INVALID_WINDOW
+                );
+            } else {
+                assert!(!sp
+                    .score
+                    .reason_codes
+                    .iter()
+                    .any(|r| r == scoring::INVALID_WINDOW));
+            }
+        }
+        // rescoring from results.json keeps the flags
+        let (_, _, stored) = stored_evidence(&a).unwrap();
+        assert_eq!(stored.invalid_window, spans.invalid_window);
+    }
+
+    /// `Synthetic`, counting 1–3 subprocess calls per pair.
+    #[derive(Default)]
+    struct Counted(Cell<u64>);
+
+    impl EvidenceSource for Counted {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            self.0.set(self.0.get() + 1 + p.aln_len as u64 % 3);
+            Synthetic.pair_evidence(p)
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Synthetic.params()
+        }
+
+        fn subprocess_calls(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn cost_columns_sum_to_the_stage_totals() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..23).map(synthetic_pair).collect();
+        let expected: u64 = pairs.iter().map(|p| 1 + p.aln_len as u64 % 3).sum();
+        let cols = PairsColumns::default().with_cost(true);
+        for low_memory in [false, true] {
+            let out = dir.path().join(format!("out{low_memory}"));
+            fs::create_dir_all(&out).unwrap();
+            let ctx = OutputContext {
+                pairs_columns: &cols,
+                ..ctx(&out, &mito, &nuc)
+            };
+            let source = Counted::default();
+            if low_memory {
+                write_low_memory(&ctx, pairs.clone(), &source, &out.join("w"), 4).unwrap();
+            } else {
+                let (coverage, spans) = collect_evidence(&pairs, &source).unwrap();
+                write_in_memory(&ctx, &pairs, &coverage, &spans).unwrap();
+            }
+
+            let stats = RunStats::load_from(&out).unwrap();
+            let stage = stats.evidence.unwrap();
+            assert_eq!(stage.n_pairs, pairs.len());
+            assert_eq!(stage.n_subprocess_calls, expected);
+            assert_eq!(stats.pairs.n_pairs, pairs.len());
+            assert_eq!(stats.pairs.n_subprocess_calls, expected);
+            assert!(stats.pairs.cost_ms <= stage.wall_ms);
+            for by in [&stats.by_contig, &stats.by_call] {
+                assert_eq!(by.values().map(|t| t.n_pairs).sum::<usize>(), pairs.len());
+                let calls: u64 = by.values().map(|t| t.n_subprocess_calls).sum();
+                assert_eq!(calls, expected);
+                let ms: f64 = by.values().map(|t| t.cost_ms).sum();
+                assert!((ms - stats.pairs.cost_ms).abs() < 1e-6);
+            }
+            assert_eq!(stats.by_contig.len(), 3);
+
+            // pairs.tsv carries the same per-pair calls
+            let tsv = fs::read_to_string(out.join("pairs.tsv")).unwrap();
+            let mut lines = tsv.lines();
+            assert!(lines
+                .next()
+                .unwrap()
+                .ends_with("\tcost_ms\tn_subprocess_calls"));
+            let calls: u64 = lines
+                .map(|l| l.rsplit('\t').next().unwrap().parse::<u64>().unwrap())
+                .sum();
+            assert_eq!(calls, expected);
+        }
+    }
+
+    /// What an onsm 0.1 consumer parses, at `--output-compat 0.1`.
+    const GOLDEN_0_1_PAIRS: &str = "pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\trnuc\trmito\ts_nuc\ts_mito\tscore_numt\tscore_nimt";
+    const GOLDEN_0_1_CLASSIFICATION: &str = "pair_id\tcall\tconfidence\treason_codes";
+    const GOLDEN_0_1_SUMMARY: &[&str] = &[
+        "n_pairs",
+        "n_numt",
+        "n_nimt",
+        "nuclear_bp_total",
+        "nuclear_bp_numt",
+        "nuclear_pct_numt",
+        "mito_bp_total",
+        "mito_bp_nimt",
+        "mito_pct_nimt",
+        "mito_bp_covered_by_numt_homologs",
+        "mito_pct_covered_by_numt_homologs",
+        "nuc_bp_covered_by_nimt_homologs",
+        "nuc_pct_covered_by_nimt_homologs",
+    ];
+
+    #[test]
+    fn output_compat_schemas_on_both_paths() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..20).map(synthetic_pair).collect();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let first_field = |l: &str| l.split('\t').next().unwrap().to_string();
+        for compat in [OutputCompat::V0_1, OutputCompat::Latest] {
+            let cols = compat.pairs_columns(&[], Default::default()).unwrap();
+            let (a, b) = (
+                dir.path().join(format!("{}-a", compat.as_str())),
+                dir.path().join(format!("{}-b", compat.as_str())),
+            );
+            for d in [&a, &b] {
+                fs::create_dir_all(d).unwrap();
+            }
+            let ctx_a = OutputContext {
+                pairs_columns: &cols,
+                output_compat: compat,
+                ..ctx(&a, &mito, &nuc)
+            };
+            write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+            let ctx_b = OutputContext {
+                pairs_columns: &cols,
+                output_compat: compat,
+                ..ctx(&b, &mito, &nuc)
+            };
+            write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &dir.path().join("w"), 6).unwrap();
+            assert_same_outputs(&a, &b);
+
+            let read = |f: &str| fs::read_to_string(a.join(f)).unwrap();
+            let header = |f: &str| read(f).lines().next().unwrap().to_string();
+            let summary_metrics: Vec<String> = read("summary.tsv")
+                .lines()
+                .skip(1)
+                .map(first_field)
+                .collect();
+            let json = read_json(&a.join("summary.json"));
+            let json_keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+            match compat {
+                OutputCompat::V0_1 => {
+                    assert_eq!(header("pairs.tsv"), GOLDEN_0_1_PAIRS);
+                    assert_eq!(header("classification.tsv"), GOLDEN_0_1_CLASSIFICATION);
+                    assert_eq!(summary_metrics, GOLDEN_0_1_SUMMARY);
+                    let mut golden = GOLDEN_0_1_SUMMARY.to_vec();
+                    golden.sort_unstable();
+                    let mut keys = json_keys.clone();
+                    keys.sort_unstable();
+                    assert_eq!(keys, golden);
+                    // every row as wide as its header
+                    for f in ["pairs.tsv", "classification.tsv"] {
+                        let n = header(f).split('\t').count();
+                        assert!(read(f).lines().all(|l| l.split('\t').count() == n), "{f}");
+                    }
+                }
+                OutputCompat::Latest => {
+                    assert_eq!(header("pairs.tsv"), scoring::PAIRS_TSV_HEADER);
+                    assert_eq!(
+                        header("classification.tsv"),
+                        scoring::CLASSIFICATION_TSV_HEADER
+                    );
+                    assert!(summary_metrics.len() > GOLDEN_0_1_SUMMARY.len());
+                    assert!(json_keys.len() > GOLDEN_0_1_SUMMARY.len());
+                }
+            }
+            // the 0.1 metrics are in every schema
+            assert!(GOLDEN_0_1_SUMMARY
+                .iter()
+                .all(|m| summary_metrics.iter().any(|s| s == m)));
+        }
+        // the default tracks the latest schema
+        assert_eq!(OutputCompat::default(), OutputCompat::Latest);
+    }
+
+    #[test]
+    fn molecule_types_on_both_paths() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        // a plasmid next to the mitogenome
+        fs::write(
+            &mito,
+            format!(
+                ">mt\n{}\n>pl1\n{}\n",
+                "A".repeat(16_000),
+                "A".repeat(16_000)
+            ),
+        )
+        .unwrap();
+        let pairs: Vec<PairedLocus> = (0..30)
+            .map(|i| PairedLocus {
+                mito_contig: if i % 2 == 0 { "mt" } else { "pl1" }.into(),
+                ..synthetic_pair(i)
+            })
+            .collect();
+        let meta = MitoContigMeta::parse("pl1\tplasmid\tno\n").unwrap();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        for d in [&a, &b] {
+            fs::create_dir_all(d).unwrap();
+        }
+        let ctx_a = OutputContext {
+            mito_meta: &meta,
+            ..ctx(&a, &mito, &nuc)
+        };
+        let sa = write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+        let ctx_b = OutputContext {
+            mito_meta: &meta,
+            ..ctx(&b, &mito, &nuc)
+        };
+        write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &dir.path().join("w"), 4).unwrap();
+        assert_same_outputs(&a, &b);
+
+        let tsv = fs::read_to_string(a.join("pairs.tsv")).unwrap();
+        for line in tsv.lines().skip(1) {
+            let molecule = if line.contains("\tpl1\t") {
+                "plasmid"
+            } else {
+                "mito"
+            };
+            assert!(
+                line.contains(&format!(
+                    "\t{molecule}\tfalse\t1\t.\t.\tblast\t0\t0\ttrue\t.\t"
+                )),
+                "{line}"
+            );
+        }
+        let by_type = &sa.nimt_by_molecule_type;
+        assert_eq!(by_type.keys().collect::<Vec<_>>(), ["mito", "plasmid"]);
+        assert_eq!(by_type.values().map(|m| m.n_nimt).sum::<usize>(), sa.n_nimt);
+        assert_eq!(by_type["plasmid"].mito_bp_total, 16_000);
+        let summary = fs::read_to_string(a.join("summary.tsv")).unwrap();
+        assert!(
+            summary.contains("\nmito_bp_total_plasmid\t16000\n"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn narrowed_pairs_tsv_on_both_paths() {
+        use crate::columns::ColumnsPreset;
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..12).map(synthetic_pair).collect();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+
+        let full = dir.path().join("full");
+        fs::create_dir_all(&full).unwrap();
+        write_in_memory(&ctx(&full, &mito, &nuc), &pairs, &coverage, &spans).unwrap();
+
+        let cols = PairsColumns::parse(&["rnuc", "nuc_contig", "strand"]).unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        for d in [&a, &b] {
+            fs::create_dir_all(d).unwrap();
+        }
+        let ctx_a = OutputContext {
+            pairs_columns: &cols,
+            ..ctx(&a, &mito, &nuc)
+        };
+        write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+        let ctx_b = OutputContext {
+            pairs_columns: &cols,
+            ..ctx(&b, &mito, &nuc)
+        };
+        let work = dir.path().join("work");
+        write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &work, 5).unwrap();
+        assert_same_outputs(&a, &b);
+
+        let narrow = fs::read_to_string(a.join("pairs.tsv")).unwrap();
+        let wide = fs::read_to_string(full.join("pairs.tsv")).unwrap();
+        assert!(
+            narrow.starts_with("pair_id\trnuc\tnuc_contig\tstrand\n"),
+            "{narrow}"
+        );
+        for (n, w) in narrow.lines().zip(wide.lines()).skip(1) {
+            let w: Vec<&str> = w.split('\t').collect();
+            assert_eq!(n, [w[0], w[9], w[1], w[16]].join("\t"));
+        }
+        // only the pairs.tsv layout changes
+        for f in ["classification.tsv", "summary.tsv", "numt_regions.tsv"] {
+            assert_eq!(
+                fs::read_to_string(a.join(f)).unwrap(),
+                fs::read_to_string(full.join(f)).unwrap(),
+                "{f} differs"
+            );
+        }
+
+        // readers fall back to results.json for the missing columns (which
+        // has full precision where pairs.tsv rounds, so compare the loci)
+        let key = |r: &scoring::PairsTsvRow| {
+            let l = &r.locus;
+            let at = |c: &str, s: u32, e: u32| format!("{c}:{s}-{e}");
+            format!(
+                "{} {} {} {:?} {}",
+                l.pair_id,
+                at(&l.nuc_contig, l.nuc_start, l.nuc_end),
+                at(&l.mito_contig, l.mito_start, l.mito_end),
+                l.strand,
+                l.aln_len
+            )
+        };
+        let expected: Vec<String> = scoring::read_run_pairs(&full)
+            .unwrap()
+            .iter()
+            .map(key)
+            .collect();
+        let back: Vec<String> = scoring::read_run_pairs(&a)
+            .unwrap()
+            .iter()
+            .map(key)
+            .collect();
+        assert_eq!(back, expected);
+        let e = scoring::read_pairs_tsv(&a.join("pairs.tsv")).unwrap_err();
+        assert!(format!("{e:#}").contains("no 'nuc_start' column"), "{e:#}");
+
+        // the minimal preset keeps coordinates, but not the alignment identity
+        let minimal = PairsColumns::preset(ColumnsPreset::Minimal);
+        let c = dir.path().join("c");
+        fs::create_dir_all(&c).unwrap();
+        let ctx_c = OutputContext {
+            pairs_columns: &minimal,
+            ..ctx(&c, &mito, &nuc)
+        };
+        write_in_memory(&ctx_c, &pairs, &coverage, &spans).unwrap();
+        let back = scoring::read_run_pairs(&c).unwrap();
+        assert_eq!(back.iter().map(key).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn rescoring_from_evidence_cache_matches() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..40).map(synthetic_pair).collect();
+
+        let a = dir.path().join("a");
+        fs::create_dir_all(&a).unwrap();
+        let cache_a = dir.path().join("cache_a.jsonl");
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let ctx_a = OutputContext {
+            evidence_cache: Some(&cache_a),
+            ..ctx(&a, &mito, &nuc)
+        };
+        write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+
+        // the streaming path writes the same cache
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        let cache_b = dir.path().join("cache_b.jsonl");
+        let ctx_b = OutputContext {
+            evidence_cache: Some(&cache_b),
+            ..ctx(&b, &mito, &nuc)
+        };
+        let work = dir.path().join("work");
+        write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &work, 9).unwrap();
+        assert_eq!(
+            fs::read_to_string(&cache_a).unwrap(),
+            fs::read_to_string(&cache_b).unwrap()
+        );
+
+        // rescoring the cached loci reproduces the run, on either path
+        let (loci, cached) = CachedEvidence::load(&cache_a).unwrap();
+        assert_eq!(loci.len(), pairs.len());
+        let c = dir.path().join("c");
+        fs::create_dir_all(&c).unwrap();
+        let (coverage, spans) = collect_evidence(&loci, &cached).unwrap();
+        write_in_memory(&ctx(&c, &mito, &nuc), &loci, &coverage, &spans).unwrap();
+        assert_same_outputs(&a, &c);
+        let d = dir.path().join("d");
+        fs::create_dir_all(&d).unwrap();
+        write_low_memory(&ctx(&d, &mito, &nuc), loci, &cached, &work, 9).unwrap();
+        assert_same_outputs(&a, &d);
+
+        // a locus the cache never saw is reported, not scored as zero evidence
+        let mut novel = synthetic_pair(0);
+        novel.nuc_start += 1;
+        let e = cached.pair_evidence(&novel).unwrap_err().to_string();
+        assert!(e.contains("no cached evidence for P000001"), "{e}");
+        assert!(e.contains("--keep all"), "{e}");
+    }
+
+    /// `Synthetic` evidence that counts its calls and asks for a shutdown
+    /// after `stop_after` of them, as a SIGTERM mid-run would.
+    struct Interrupting<'a> {
+        shutdown: &'a Shutdown,
+        stop_after: usize,
+        calls: Cell<usize>,
+    }
+
+    impl EvidenceSource for Interrupting<'_> {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() == self.stop_after {
+                self.shutdown.request();
+            }
+            Synthetic.pair_evidence(p)
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Synthetic.params()
+        }
+    }
+
+    #[test]
+    fn interrupted_evidence_resumes_where_it_stopped() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..40).map(synthetic_pair).collect();
+        let a = dir.path().join("a");
+        fs::create_dir_all(&a).unwrap();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        write_in_memory(&ctx(&a, &mito, &nuc), &pairs, &coverage, &spans).unwrap();
+
+        // stop after the 17th pair: the pair in flight finishes, then the loop stops
+        let partial = dir.path().join("evidence_partial.jsonl");
+        let shutdown = Shutdown::default();
+        let first = Interrupting {
+            shutdown: &shutdown,
+            stop_after: 17,
+            calls: Cell::new(0),
+        };
+        let ev =
+            CheckpointedEvidence::open(&partial, &first, &shutdown, pairs.len(), false).unwrap();
+        let e = collect_evidence(&pairs, &ev).unwrap_err();
+        let stop = e.downcast_ref::<Interrupted>().unwrap();
+        assert_eq!((stop.pairs_done, stop.pairs_total), (17, 40));
+        assert_eq!(first.calls.get(), 17);
+        drop(ev);
+        // the header and one row per pair; a kill can cut the next row short
+        assert_eq!(fs::read_to_string(&partial).unwrap().lines().count(), 18);
+        let mut f = fs::OpenOptions::new().append(true).open(&partial).unwrap();
+        f.write_all(b"{\"locus\":{\"pair_id\":\"P0000").unwrap();
+        drop(f);
+
+        // resuming (here on the streaming path) only computes the other 23
+        let shutdown = Shutdown::default();
+        let second = Interrupting {
+            shutdown: &shutdown,
+            stop_after: usize::MAX,
+            calls: Cell::new(0),
+        };
+        let ev =
+            CheckpointedEvidence::open(&partial, &second, &shutdown, pairs.len(), true).unwrap();
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        let work = dir.path().join("work");
+        write_low_memory(&ctx(&b, &mito, &nuc), pairs.clone(), &ev, &work, 9).unwrap();
+        assert_eq!((second.calls.get(), ev.pairs_done()), (23, 40));
+        assert_same_outputs(&a, &b);
+        drop(ev);
+        let (loci, _) = CachedEvidence::load(&partial).unwrap();
+        assert_eq!(loci.len(), 40);
+
+        // without --resume the partial cache starts over
+        let ev =
+            CheckpointedEvidence::open(&partial, &second, &shutdown, pairs.len(), false).unwrap();
+        assert_eq!(ev.pairs_done(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancelling_mid_evidence_keeps_the_completed_prefix() {
+        use crate::util::shutdown::{CancelToken, StopReason};
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+
+        let dir = TempDir::new().unwrap();
+        let pairs: Vec<PairedLocus> = (0..40).map(synthetic_pair).collect();
+        // Stand-in samtools, slow enough for the cancel to land mid-loop
+        let samtools = dir.path().join("samtools");
+        fs::write(
+            &samtools,
+            "#!/bin/sh\nsleep 0.01\n[ \"$1\" = depth ] && printf 'c\\t1\\t5\\n'\nexit 0\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+        for bam in ["r2n.bam", "r2m.bam"] {
+            fs::write(dir.path().join(bam), bam).unwrap();
+        }
+        let samtools = SamtoolsEvidence {
+            bam_reads_to_nuc: dir.path().join("r2n.bam"),
+            bam_reads_to_mito: dir.path().join("r2m.bam"),
+            samtools,
+            flank: 500,
+            win: 250,
+            max_reads: model::MAX_READS_PER_WINDOW,
+            depth_supplementary: false,
+            min_span_mapq: model::MIN_MAPQ,
+            junction_min_clip: 0,
+            mito_meta: MitoContigMeta::default(),
+            calls: Default::default(),
+            native: None,
+            batched: Default::default(),
+            cram_references: Vec::new(),
+            contig_lengths: None,
+            cache: None,
+        };
+
+        // another thread cancels once five pairs are checkpointed
+        let token = CancelToken::default();
+        let shutdown = Shutdown::default().with_cancel(token.clone());
+        let partial = dir.path().join("evidence_partial.jsonl");
+        let ev =
+            CheckpointedEvidence::open(&partial, &samtools, &shutdown, pairs.len(), false).unwrap();
+        let watched = partial.clone();
+        let canceller = std::thread::spawn(move || {
+            while fs::read_to_string(&watched).unwrap().lines().count() < 6 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            token.cancel();
+            Instant::now()
+        });
+        let e = collect_evidence(&pairs, &ev).unwrap_err();
+        let returned = Instant::now();
+        let cancelled = canceller.join().unwrap();
+        // at most the pair in flight finishes
+        assert!(returned.duration_since(cancelled) < Duration::from_secs(5));
+        let stop = e.downcast_ref::<Interrupted>().unwrap();
+        assert_eq!(stop.reason, StopReason::Cancelled);
+        assert!((5..pairs.len()).contains(&stop.pairs_done), "{stop:?}");
+        drop(ev);
+
+        // the cache holds exactly the pairs done, in order
+        let (loci, cached) = CachedEvidence::load_partial(&partial).unwrap();
+        let ids = |ls: &[PairedLocus]| ls.iter().map(|l| l.pair_id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&loci), ids(&pairs[..stop.pairs_done]));
+        assert_eq!(cached.by_locus.len(), stop.pairs_done);
+    }
+
+    /// `Synthetic` evidence that reads progress.json before each pair, as a
+    /// dashboard polling it would.
+    struct Watched<'a> {
+        progress_file: &'a Path,
+        seen: RefCell<Vec<ProgressReport>>,
+    }
+
+    impl EvidenceSource for Watched<'_> {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            let text = fs::read_to_string(self.progress_file)?;
+            self.seen.borrow_mut().push(serde_json::from_str(&text)?);
+            Synthetic.pair_evidence(p)
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Synthetic.params()
+        }
+    }
+
+    #[test]
+    fn progress_file_follows_the_evidence_loop() {
+        let dir = TempDir::new().unwrap();
+        let pairs: Vec<PairedLocus> = (0..12).map(synthetic_pair).collect();
+        let progress_file = dir.path().join(PROGRESS_FILE);
+        let watched = Watched {
+            progress_file: &progress_file,
+            seen: RefCell::new(Vec::new()),
+        };
+        let progress = Progress::start(dir.path(), false)
+            .unwrap()
+            .with_cadence(5, std::time::Duration::ZERO);
+        let shutdown = Shutdown::default();
+        let partial = dir.path().join("evidence_partial.jsonl");
+        let ev = CheckpointedEvidence::open(&partial, &watched, &shutdown, pairs.len(), false)
+            .unwrap()
+            .with_progress(&progress);
+        progress.stage(Stage::Evidence, 0, Some(pairs.len() as u64));
+        collect_evidence(&pairs, &ev).unwrap();
+
+        // written at the transition, then after every fifth pair
+        let seen = watched.seen.take();
+        let done: Vec<u64> = seen.iter().map(|r| r.done).collect();
+        assert_eq!(done, [0, 0, 0, 0, 0, 5, 5, 5, 5, 5, 10, 10]);
+        assert!(seen.iter().all(|r| r.stage == Stage::Evidence
+            && r.total == Some(12)
+            && r.state == ProgressState::Running));
+        assert_eq!(seen[0].eta_secs, None);
+        assert!(seen[5].eta_secs.is_some());
+
+        progress.stage(Stage::Scoring, 0, None);
+        progress.finish(ProgressState::Finished);
+        let last = ProgressReport::load_from(dir.path()).unwrap();
+        assert_eq!(
+            (last.state, last.stage, last.total),
+            (ProgressState::Finished, Stage::Done, None)
+        );
+        assert!(last.stage_started >= seen[0].stage_started);
+        assert_eq!(last.run_started, seen[0].run_started);
+    }
+
+    #[test]
+    fn self_assembled_mito_caveats_nimt_calls() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..20).map(synthetic_pair).collect();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let ctx = OutputContext {
+            params: ClassifyParams {
+                self_assembly_caveat: true,
+                ..ClassifyParams::default()
+            },
+            ..ctx(dir.path(), &mito, &nuc)
+        };
+        let s = write_in_memory(&ctx, &pairs, &coverage, &spans).unwrap();
+        assert_eq!(s.caveats, [scoring::SELF_ASSEMBLY_CAVEAT]);
+        let tsv = fs::read_to_string(dir.path().join("summary.tsv")).unwrap();
+        assert!(tsv.ends_with("\ncaveats\tself_assembly_caveat\n"), "{tsv}");
+        let results = ScoringResults::load_from(dir.path()).unwrap();
+        assert!(results.params.self_assembly_caveat);
+        let nimt: Vec<_> = results
+            .pairs
+            .iter()
+            .map(|p| {
+                (
+                    p.score.call == scoring::Call::NIMT,
+                    p.score
+                        .reason_codes
+                        .iter()
+                        .any(|c| c == scoring::SELF_ASSEMBLY_CAVEAT),
+                )
+            })
+            .collect();
+        assert!(nimt.iter().any(|&(is_nimt, _)| is_nimt));
+        assert!(nimt.iter().all(|&(is_nimt, caveated)| is_nimt == caveated));
+    }
+
+    #[test]
+    fn disabled_span_evidence_is_caveated() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..20).map(synthetic_pair).collect();
+        let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+        let params = ClassifyParams {
+            span_evidence_disabled: true,
+            ..ClassifyParams::default()
+        };
+
+        let a = dir.path().join("a");
+        fs::create_dir_all(&a).unwrap();
+        let ctx_a = OutputContext {
+            params,
+            ..ctx(&a, &mito, &nuc)
+        };
+        let s = write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+        assert_eq!(s.caveats, [scoring::SPAN_EVIDENCE_DISABLED]);
+        let tsv = fs::read_to_string(a.join("summary.tsv")).unwrap();
+        assert!(
+            tsv.ends_with("\ncaveats\tspan_evidence_disabled\n"),
+            "{tsv}"
+        );
+        assert_eq!(
+            read_json(&a.join("summary.json"))["caveats"],
+            serde_json::json!(["span_evidence_disabled"])
+        );
+        let rows = fs::read_to_string(a.join("classification.tsv")).unwrap();
+        assert_eq!(rows.lines().count(), 21);
+        assert!(rows
+            .lines()
+            .skip(1)
+            .all(|l| l.contains(scoring::SPAN_EVIDENCE_DISABLED)));
+        let results = ScoringResults::load_from(&a).unwrap();
+        assert!(results.params.span_evidence_disabled);
+        assert!(results.pairs.iter().all(|p| p.score.terms.span == 0.0));
+
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        let ctx_b = OutputContext {
+            params,
+            ..ctx(&b, &mito, &nuc)
+        };
+        write_low_memory(&ctx_b, pairs.clone(), &Synthetic, &dir.path().join("w"), 6).unwrap();
+        assert_same_outputs(&a, &b);
+
+        // no caveat, no row
+        let c = dir.path().join("c");
+        fs::create_dir_all(&c).unwrap();
+        let s = write_in_memory(&ctx(&c, &mito, &nuc), &pairs, &coverage, &spans).unwrap();
+        assert!(s.caveats.is_empty());
+        let tsv = fs::read_to_string(c.join("summary.tsv")).unwrap();
+        assert!(!tsv.contains("caveats"), "{tsv}");
+        assert!(read_json(&c.join("summary.json")).get("caveats").is_none());
+    }
+
+    #[test]
+    fn pair_count_limits() {
+        let _runs = crate::util::logging::TEST_RUNS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new().unwrap();
+        let _w = warnings::RunWarnings::begin(dir.path());
+        let pairs: Vec<PairedLocus> = (0..100).map(synthetic_pair).collect();
+        let n_warned = || {
+            warnings::current_report()
+                .warnings
+                .iter()
+                .find(|w| w.category == warnings::PAIR_COUNT)
+                .map_or(0, |w| w.count)
+        };
+
+        // well under the limit, or no limit at all
+        check_pair_count(&pairs, 1_000, model::MIN_ID, model::MIN_LEN).unwrap();
+        check_pair_count(&pairs, 0, model::MIN_ID, model::MIN_LEN).unwrap();
+        assert_eq!(n_warned(), 0);
+
+        // over a quarter of it: soft limit
+        check_pair_count(&pairs, 300, model::MIN_ID, model::MIN_LEN).unwrap();
+        assert_eq!(n_warned(), 1);
+
+        // over it: refused, with the distribution and stricter filters
+        let e = check_pair_count(&pairs, 50, model::MIN_ID, model::MIN_LEN)
+            .unwrap_err()
+            .to_string();
+        for want in [
+            "100 candidate pairs exceed --max-pairs 50",
+            "before the evidence stage",
+            "identity 0.900/",
+            "length 500/600/800/1000/1100 bp (min/q1/median/q3/max)",
+            "--min-id 0.95: ",
+            "--min-len 500: 100, --min-len 1000: 28, --min-len 2000: 0",
+            "--mm2-secondary no",
+            "--pairing cluster",
+            "raise --max-pairs",
+        ] {
+            assert!(e.contains(want), "{want:?} not in {e}");
+        }
+        // only thresholds stricter than the current filters are offered
+        let e = check_pair_count(&pairs, 50, 0.97, 1_000)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("classify --min-id 0.98: "), "{e}");
+        assert!(
+            !e.contains("--min-id 0.95") && !e.contains("--min-len 500:"),
+            "{e}"
+        );
+        assert!(e.contains("--min-len 2000: 0, --min-len 5000: 0"), "{e}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn low_memory_matches_in_memory_with_samtools() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let pairs: Vec<PairedLocus> = (0..12).map(synthetic_pair).collect();
+
+        // Stand-in samtools: depth varies along the contigs, per region (-r) or
+        // once per position of the BED windows (-b), as samtools reports it; some
+        // windows get a read spanning them, every window gets a short
+        // non-spanning one; both BAMs have every contig. Calls are logged next
+        // to it.
+        let samtools = dir.path().join("samtools");
+        fs::write(
+            &samtools,
+            r#"#!/bin/sh
+echo "$1" >> "$(dirname "$0")/calls.log"
+case "$1" in
+depth)
+  if [ "$2" = -b ]; then
+    sort -k1,1 -k2,2n "$3"
+  else
+    r="$3"; s="${r##*:}"; printf '%s\t%d\t%d\n' "${r%:*}" $(( ${s%-*} - 1 )) "${s#*-}"
+  fi | awk -F '\t' '$1 != c { c = $1; last = 0 }
+    { for (x = ($2 > last ? $2 : last) + 1; x <= $3; x++) printf "%s\t%d\t%d\n", c, x, (x / 97 + x % 5) % 37 + 1
+      if ($3 > last) last = $3 }'
+  ;;
+idxstats)
+  printf '%s\t%d\t0\t0\n' chr1 40000 chr2 40000 chr3 40000 mt 16569 '*' 0
+  ;;
+view)
+  r="$3"; n="${r%:*}"; s="${r##*:}"; s="${s%-*}"
+  if [ $(( s % 3 )) -eq 0 ]; then printf 'a\t0\t%s\t1\t60\t1000000M\t*\t0\t0\t*\t*\n' "$n"; fi
+  printf 'b\t0\t%s\t%d\t60\t10M\t*\t0\t0\t*\t*\n' "$n" "$s"
+  ;;
+esac
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&samtools, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let calls = || {
+            fs::read_to_string(dir.path().join("calls.log"))
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+        for bam in ["r2n.bam", "r2m.bam"] {
+            fs::write(dir.path().join(bam), bam).unwrap();
+        }
+        let window_cache = dir.path().join("window_cache");
+        let ev = SamtoolsEvidence {
+            bam_reads_to_nuc: dir.path().join("r2n.bam"),
+            bam_reads_to_mito: dir.path().join("r2m.bam"),
+            samtools: samtools.clone(),
+            flank: 500,
+            win: 250,
+            max_reads: model::MAX_READS_PER_WINDOW,
+            depth_supplementary: false,
+            min_span_mapq: model::MIN_MAPQ,
+            junction_min_clip: 0,
+            mito_meta: MitoContigMeta::default(),
+            calls: Default::default(),
+            native: None,
+            batched: Default::default(),
+            cram_references: Vec::new(),
+            contig_lengths: None,
+            cache: Some(WindowCache::open(&window_cache, 1_000).unwrap()),
+        };
+
+        let a = dir.path().join("a");
+        fs::create_dir_all(&a).unwrap();
+        let (coverage, spans) = bam::compute_coverage_and_spans_with_tools(
+            &ev.bam_reads_to_nuc,
+            &ev.bam_reads_to_mito,
+            &pairs,
+            ev.flank,
+            ev.win,
+            ev.max_reads,
+            &ev.mito_meta,
+            &bam::WindowFetch::new(&samtools, None),
+        )
+        .unwrap();
+        // one depth call per BAM, two view calls per pair, and an idxstats
+        // and a depth call per BAM for the genome-wide depths
+        let n = pairs.len();
+        assert_eq!(calls(), 2 + 2 * n + 4);
+        let cache_a = dir.path().join("cache_a.jsonl");
+        let ctx_a = OutputContext {
+            evidence_cache: Some(&cache_a),
+            evidence_params: ev.params().unwrap(),
+            ..ctx(&a, &mito, &nuc)
+        };
+        write_in_memory(&ctx_a, &pairs, &coverage, &spans).unwrap();
+
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        let cache_b = dir.path().join("cache_b.jsonl");
+        let ctx_b = OutputContext {
+            evidence_cache: Some(&cache_b),
+            evidence_params: ev.params().unwrap(),
+            ..ctx(&b, &mito, &nuc)
+        };
+        write_low_memory(&ctx_b, pairs.clone(), &ev, &dir.path().join("w"), 5).unwrap();
+        // …per chunk of 5
+        assert_eq!(calls(), 2 + 2 * n + 4 + 2 * n.div_ceil(5) + 2 * n + 4);
+        assert_eq!(
+            ev.subprocess_calls() as usize,
+            2 * n.div_ceil(5) + 2 * n + 4
+        );
+        assert!(coverage.nuclear_median > 0.0 && coverage.loci_medians.is_some());
+
+        assert_same_outputs(&a, &b);
+
+        // the cached windows give what samtools gave, without calling it
+        let collect = |cache: &WindowCache| {
+            bam::compute_coverage_and_spans_with_tools(
+                &ev.bam_reads_to_nuc,
+                &ev.bam_reads_to_mito,
+                &pairs,
+                ev.flank,
+                ev.win,
+                ev.max_reads,
+                &ev.mito_meta,
+                &bam::WindowFetch::new(&samtools, Some(cache)),
+            )
+            .unwrap()
+        };
+        let cache = WindowCache::open(&window_cache, 1_000).unwrap();
+        let before = calls();
+        let (c_coverage, c_spans) = collect(&cache);
+        assert_eq!(calls(), before);
+        assert_eq!(cache.stats().hits, 4 * n as u64 + 2);
+        assert_eq!(c_coverage.per_pair, coverage.per_pair);
+        assert_eq!(
+            (c_spans.per_pair, c_spans.read_len, c_spans.reads),
+            (
+                spans.per_pair.clone(),
+                spans.read_len.clone(),
+                spans.reads.clone()
+            )
+        );
+        // a rewritten BAM is fetched again
+        fs::write(&ev.bam_reads_to_nuc, "re-aligned").unwrap();
+        let cache = WindowCache::open(&window_cache, 1_000).unwrap();
+        collect(&cache);
+        assert_eq!(calls(), before + 1 + n + 2);
+        // both caches record how many reads each span fraction came from
+        let cache = fs::read_to_string(&cache_a).unwrap();
+        assert_eq!(cache, fs::read_to_string(&cache_b).unwrap());
+        let (_, cached) = CachedEvidence::load(&cache_a).unwrap();
+        // …and the baselines, for rescoring without the BAMs
+        assert_eq!(
+            cached.genome_medians().unwrap(),
+            Some((coverage.nuclear_median as f32, coverage.mito_median as f32))
+        );
+        for p in &pairs {
+            let (nuc, mito) = cached.pair_evidence(p).unwrap().span_reads.unwrap();
+            for reads in [nuc, mito] {
+                assert!(!reads.subsampled());
+                assert!((1..=2).contains(&reads.total), "{p:?}: {reads:?}");
+            }
+        }
+    }
+
+    /// Peak RSS (VmHWM) in KiB, Linux only.
+    fn peak_rss_kib() -> Option<u64> {
+        let s = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = s.lines().find(|l| l.starts_with("VmHWM:"))?;
+        line.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    /// Coarse memory bound on a generated (never materialized) multi-million-pair run.
+    /// Writes a few GB to the temp dir; run alone:
+    /// `cargo test --release low_memory_rss_is_bounded -- --ignored --test-threads=1`
+    #[test]
+    #[ignore]
+    fn low_memory_rss_is_bounded() {
+        const N: usize = 3_000_000;
+        let Some(before) = peak_rss_kib() else {
+            eprintln!("no /proc/self/status; skipping");
+            return;
+        };
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+
+        // the synthetic pairs all overlap on the mitogenome, so their
+        // cross-references would grow with N²
+        let ctx = OutputContext {
+            outputs: OutputLevel::Summary,
+            ..ctx(&out, &mito, &nuc)
+        };
+        let s = write_low_memory(
+            &ctx,
+            (0..N).map(synthetic_pair),
+            &Synthetic,
+            &dir.path().join("work"),
+            DEFAULT_CHUNK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(s.n_pairs, N);
+
+        // Holding the pairs alone would take ~N × 150 B ≈ 450 MB; the streaming
+        // path keeps well under that per pair (see the allocation test below).
+        let grown_mib = peak_rss_kib().unwrap().saturating_sub(before) / 1024;
+        eprintln!("peak RSS grew by {grown_mib} MiB for {N} pairs");
+        assert!(grown_mib < 256, "peak RSS grew by {grown_mib} MiB");
+    }
+
+    /// Counts the bytes each thread has live, so a test can bound what a call
+    /// allocates without the other tests running beside it getting in the way.
+    struct ThreadAlloc;
+
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn track(delta: isize) {
+        // `try_with`: the allocator still runs while a thread's locals are torn down
+        let _ = LIVE.try_with(|live| {
+            live.set(live.get() + delta);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for ThreadAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let p = unsafe { std::alloc::System.alloc(layout) };
+            if !p.is_null() {
+                track(layout.size() as isize);
+            }
+            p
+        }
+
+        unsafe fn dealloc(&self, p: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(p, layout) };
+            track(-(layout.size() as isize));
+        }
+
+        unsafe fn realloc(&self, p: *mut u8, layout: std::alloc::Layout, size: usize) -> *mut u8 {
+            let q = unsafe { std::alloc::System.realloc(p, layout, size) };
+            if !q.is_null() {
+                track(size as isize - layout.size() as isize);
+            }
+            q
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: ThreadAlloc = ThreadAlloc;
+
+    /// Peak bytes this thread had live while `f` ran, over what it had before.
+    fn peak_alloc<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let base = LIVE.with(Cell::get);
+        PEAK.with(|peak| peak.set(base));
+        let t = f();
+        (t, (PEAK.with(Cell::get) - base).max(0) as usize)
+    }
+
+    /// What the streaming path keeps for the whole run, per pair: the depths,
+    /// region components and identities of `write_low_memory`'s doc, but no
+    /// pairs or evidence. Runs in CI, unlike the RSS test above. Summary
+    /// outputs only, as the synthetic pairs' cross-references grow with N².
+    #[test]
+    fn low_memory_allocation_per_pair_is_bounded() {
+        let dir = TempDir::new().unwrap();
+        let (mito, nuc) = write_fastas(dir.path());
+        let lengths = LengthCache::default();
+        let run = |name: &str, n: usize, low_memory: bool| {
+            let out = dir.path().join(name);
+            fs::create_dir_all(&out).unwrap();
+            let ctx = OutputContext {
+                lengths: &lengths,
+                outputs: OutputLevel::Summary,
+                ..ctx(&out, &mito, &nuc)
+            };
+            let work = dir.path().join(format!("{name}.work"));
+            let (s, peak) = peak_alloc(|| {
+                if low_memory {
+                    write_low_memory(&ctx, (0..n).map(synthetic_pair), &Synthetic, &work, 500)
+                } else {
+                    let pairs: Vec<PairedLocus> = (0..n).map(synthetic_pair).collect();
+                    let (coverage, spans) = collect_evidence(&pairs, &Synthetic).unwrap();
+                    write_in_memory(&ctx, &pairs, &coverage, &spans)
+                }
+            });
+            assert_eq!(s.unwrap().n_pairs, n);
+            peak
+        };
+        // the first run scans the FASTAs into `lengths`
+        run("warm", 10, true);
+        let per_pair = |low_memory| {
+            let small = run(&format!("small{low_memory}"), 2_000, low_memory);
+            let large = run(&format!("large{low_memory}"), 8_000, low_memory);
+            large.saturating_sub(small) / 6_000
+        };
+        let (streamed, in_memory) = (per_pair(true), per_pair(false));
+        assert!(
+            streamed < 768 && streamed * 2 < in_memory,
+            "{streamed} B per pair streamed, {in_memory} B in memory"
+        );
+    }
+}
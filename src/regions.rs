@@ -0,0 +1,521 @@
+//! NUMT regions (`numt_regions.tsv` / `numt_regions.bed`): Likely_NUMT pairs
+//! merged into contiguous stretches of the nuclear genome, for reporting one
+//! insertion event rather than every alignment-derived pair.
+//!
+//! Pairs on the same nuclear contig join a region when their loci overlap or
+//! lie within `--region-merge-gap` bp of it, unless a pair with another call
+//! (Ambiguous or Likely_NIMT) sits in the gap between them. Coordinates are
+//! 0-based half-open; pairs.tsv is not affected.
+//!
+//! `regions.tsv` clusters the same way, but every pair whatever its call and
+//! within `--region-gap` bp: nothing but distance splits those regions.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::io::bed::{self, BedRecord};
+use crate::model::orientation::Strand;
+use crate::scoring::{Call, ScoredPair};
+use crate::summary::IntervalBuckets;
+
+pub const DEFAULT_REGION_MERGE_GAP: u32 = 1_000;
+pub const DEFAULT_REGION_GAP: u32 = 2_000;
+
+pub const NUMT_REGIONS_TSV_HEADER: &str =
+    "region_id\tnuc_contig\tstart\tend\tnumt_bp\taln_bp\tn_pairs\tmin_ident\tmax_ident\tconfidence\tpair_ids";
+
+pub const REGIONS_TSV_HEADER: &str =
+    "region_id\tnuc_contig\tstart\tend\tspan_bp\tcovered_bp\taln_bp\tn_pairs\tcalls\tpair_ids";
+
+/// One merged region and its aggregate statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumtRegion {
+    pub region_id: String,
+    pub nuc_contig: String,
+    pub start: u32,
+    pub end: u32,
+    /// bp covered by the component loci (the span minus any gaps between them).
+    pub numt_bp: u64,
+    /// Aligned bp summed over the components; overlapping loci count twice.
+    pub aln_bp: u64,
+    pub n_pairs: usize,
+    pub min_ident: f32,
+    pub max_ident: f32,
+    /// Highest confidence among the components.
+    pub confidence: f32,
+    pub pair_ids: Vec<String>,
+    /// The call of each of `pair_ids`.
+    pub calls: Vec<Call>,
+    /// The strand its pairs share, `None` when they differ.
+    pub strand: Option<Strand>,
+}
+
+#[derive(Debug, Clone)]
+struct Component {
+    pair_id: String,
+    start: u32,
+    end: u32,
+    aln_len: u32,
+    ident: f32,
+    confidence: f32,
+    strand: Strand,
+    call: Call,
+}
+
+/// Incremental region building: feed every scored pair, then `finish`.
+///
+/// Other-call loci are only kept as merged intervals, so with `--low-memory`
+/// memory grows with the NUMT pairs, not with all pairs. `any_call` builds
+/// the regions of `regions.tsv` instead, from every pair.
+#[derive(Debug, Default)]
+pub struct RegionBuilder {
+    any_call: bool,
+    members: HashMap<String, Vec<Component>>,
+    others: IntervalBuckets,
+}
+
+impl RegionBuilder {
+    /// Regions of all pairs, whatever their call, numbered `G000001`, ….
+    pub fn any_call() -> Self {
+        Self {
+            any_call: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn add(&mut self, sp: &ScoredPair) {
+        let p = &sp.locus;
+        if !self.any_call && sp.score.call != Call::NUMT {
+            self.others.add(&p.nuc_contig, p.nuc_start, p.nuc_end);
+            return;
+        }
+        self.members
+            .entry(p.nuc_contig.clone())
+            .or_default()
+            .push(Component {
+                pair_id: p.pair_id.clone(),
+                start: p.nuc_start,
+                end: p.nuc_end,
+                aln_len: p.aln_len,
+                ident: sp.features.aln_ident,
+                confidence: sp.score.confidence,
+                strand: p.strand,
+                call: sp.score.call,
+            });
+    }
+
+    /// Regions sorted by contig and start, numbered `R000001`, ….
+    pub fn finish(self, merge_gap: u32) -> Vec<NumtRegion> {
+        let others = self.others.merged();
+        let mut contigs: Vec<(String, Vec<Component>)> = self.members.into_iter().collect();
+        contigs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut regions = Vec::new();
+        for (contig, mut comps) in contigs {
+            comps.sort_by_key(|c| (c.start, c.end));
+            let blockers = others.get(&contig).map(Vec::as_slice).unwrap_or(&[]);
+            let mut current: Vec<Component> = Vec::new();
+            let mut end = 0u32;
+            for c in comps {
+                let joins = !current.is_empty()
+                    && c.start <= end.saturating_add(merge_gap)
+                    && !interrupted(blockers, end, c.start);
+                if !joins && !current.is_empty() {
+                    regions.push(aggregate(&contig, std::mem::take(&mut current)));
+                }
+                end = if current.is_empty() {
+                    c.end
+                } else {
+                    end.max(c.end)
+                };
+                current.push(c);
+            }
+            if !current.is_empty() {
+                regions.push(aggregate(&contig, current));
+            }
+        }
+        let prefix = if self.any_call { 'G' } else { 'R' };
+        for (i, r) in regions.iter_mut().enumerate() {
+            r.region_id = format!("{prefix}{:06}", i + 1);
+        }
+        regions
+    }
+}
+
+/// Whether any of the sorted, disjoint `blockers` intersects the gap
+/// `[end, next_start)` (an overlap or abutting loci have no gap to interrupt).
+fn interrupted(blockers: &[(u32, u32)], end: u32, next_start: u32) -> bool {
+    if next_start <= end {
+        return false;
+    }
+    let i = blockers.partition_point(|b| b.1 <= end);
+    blockers.get(i).is_some_and(|b| b.0 < next_start)
+}
+
+fn aggregate(contig: &str, comps: Vec<Component>) -> NumtRegion {
+    // components are sorted by start, so one sweep gives the union
+    let (mut numt_bp, mut run) = (0u64, None::<(u32, u32)>);
+    for c in &comps {
+        run = match run {
+            Some((s, e)) if c.start <= e => Some((s, e.max(c.end))),
+            Some((s, e)) => {
+                numt_bp += (e - s) as u64;
+                Some((c.start, c.end))
+            }
+            None => Some((c.start, c.end)),
+        };
+    }
+    if let Some((s, e)) = run {
+        numt_bp += (e - s) as u64;
+    }
+    NumtRegion {
+        region_id: String::new(),
+        nuc_contig: contig.to_string(),
+        start: comps.iter().map(|c| c.start).min().unwrap_or(0),
+        end: comps.iter().map(|c| c.end).max().unwrap_or(0),
+        numt_bp,
+        aln_bp: comps.iter().map(|c| c.aln_len as u64).sum(),
+        n_pairs: comps.len(),
+        min_ident: comps.iter().map(|c| c.ident).fold(f32::INFINITY, f32::min),
+        max_ident: comps.iter().map(|c| c.ident).fold(0.0, f32::max),
+        confidence: comps.iter().map(|c| c.confidence).fold(0.0, f32::max),
+        strand: comps
+            .iter()
+            .all(|c| c.strand == comps[0].strand)
+            .then(|| comps[0].strand),
+        calls: comps.iter().map(|c| c.call).collect(),
+        pair_ids: comps.into_iter().map(|c| c.pair_id).collect(),
+    }
+}
+
+pub fn numt_regions_tsv(regions: &[NumtRegion]) -> String {
+    let mut s = format!("{NUMT_REGIONS_TSV_HEADER}\n");
+    for r in regions {
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}\t{:.4}\t{}",
+            r.region_id,
+            r.nuc_contig,
+            r.start,
+            r.end,
+            r.numt_bp,
+            r.aln_bp,
+            r.n_pairs,
+            r.min_ident,
+            r.max_ident,
+            r.confidence,
+            r.pair_ids.join(",")
+        );
+    }
+    s
+}
+
+/// `regions.tsv`: `covered_bp` is the regions' `numt_bp`, and `calls` counts
+/// the pairs by call, e.g. `Likely_NUMT:2,Ambiguous:1`.
+pub fn regions_tsv(regions: &[NumtRegion]) -> String {
+    let mut s = format!("{REGIONS_TSV_HEADER}\n");
+    for r in regions {
+        let calls: Vec<String> = [Call::NUMT, Call::NIMT, Call::SharedSupport, Call::Ambiguous]
+            .into_iter()
+            .filter_map(|call| {
+                let n = r.calls.iter().filter(|&&c| c == call).count();
+                (n > 0).then(|| format!("{}:{n}", call.as_str()))
+            })
+            .collect();
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            r.region_id,
+            r.nuc_contig,
+            r.start,
+            r.end,
+            r.end - r.start,
+            r.numt_bp,
+            r.aln_bp,
+            r.n_pairs,
+            calls.join(","),
+            r.pair_ids.join(",")
+        );
+    }
+    s
+}
+
+/// BED6 records; score = confidence × 1000, capped at 1000; strand `.` when
+/// the pairs' strands differ.
+pub fn numt_regions_bed(regions: &[NumtRegion]) -> Vec<BedRecord> {
+    regions
+        .iter()
+        .map(|r| BedRecord {
+            name: Some(r.region_id.clone()),
+            score: Some(f64::from(
+                (r.confidence * 1000.0).round().clamp(0.0, 1000.0),
+            )),
+            strand: Some(r.strand.map_or('.', Strand::as_char)),
+            ..BedRecord::new(&r.nuc_contig, r.start, r.end)
+        })
+        .collect()
+}
+
+/// Write `numt_regions.tsv` and `numt_regions.bed`.
+pub fn write_numt_regions(out_dir: &Path, regions: &[NumtRegion]) -> Result<()> {
+    fs_err::write(out_dir.join("numt_regions.tsv"), numt_regions_tsv(regions))?;
+    bed::write_bed(
+        &out_dir.join("numt_regions.bed"),
+        &numt_regions_bed(regions),
+    )?;
+    Ok(())
+}
+
+/// Write `regions.tsv`.
+pub fn write_regions(out_dir: &Path, regions: &[NumtRegion]) -> Result<()> {
+    fs_err::write(out_dir.join("regions.tsv"), regions_tsv(regions))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::PairedLocus;
+    use crate::scoring::{DeltaTerms, PairFeatures, PairScore};
+
+    fn pair(
+        id: &str,
+        contig: &str,
+        start: u32,
+        end: u32,
+        call: Call,
+        ident: f32,
+        conf: f32,
+    ) -> ScoredPair {
+        ScoredPair {
+            locus: PairedLocus {
+                pair_id: id.to_string(),
+                nuc_contig: contig.to_string(),
+                nuc_start: start,
+                nuc_end: end,
+                mito_contig: "mt".to_string(),
+                mito_start: 0,
+                mito_end: end - start,
+                aln_len: end - start,
+                aln_ident: ident,
+                strand: Strand::Forward,
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: true,
+                parent_id: None,
+                rbh: None,
+            },
+            features: PairFeatures {
+                aln_ident: ident,
+                aln_len: end - start,
+                rnuc: 1.0,
+                rmito: 1.0,
+                s_nuc: 0.0,
+                s_mito: 0.0,
+                read_len_nuc: 0.0,
+                read_len_mito: 0.0,
+                read_div_nuc: None,
+                read_div_mito: None,
+                low_mapq_nuc: 0,
+                low_mapq_mito: 0,
+                junction_clips_start: 0,
+                junction_clips_end: 0,
+                nuc_n_bases: 0,
+                nuc_longest_n_run: 0,
+                softmask_frac_nuc: 0.0,
+                control_region: Default::default(),
+                clamped_window: None,
+                invalid_window: None,
+            },
+            score: PairScore {
+                score_numt: 0.0,
+                score_nimt: 0.0,
+                delta: conf,
+                call,
+                confidence: conf,
+                high_confidence: false,
+                reason_codes: vec![],
+                terms: DeltaTerms {
+                    depth_consistency: 0.0,
+                    depth_contrast: 0.0,
+                    span: 0.0,
+                    junction: 0.0,
+                },
+                est_copy_number_nuc: 1.0,
+                rules: vec![],
+            },
+            mito_molecule_type: "mito".to_string(),
+            evidence_cost: None,
+            related_pairs: Vec::new(),
+        }
+    }
+
+    fn regions(pairs: &[ScoredPair], gap: u32) -> Vec<NumtRegion> {
+        let mut b = RegionBuilder::default();
+        pairs.iter().for_each(|p| b.add(p));
+        b.finish(gap)
+    }
+
+    fn spans(rs: &[NumtRegion]) -> Vec<(&str, u32, u32, usize)> {
+        rs.iter()
+            .map(|r| (r.nuc_contig.as_str(), r.start, r.end, r.n_pairs))
+            .collect()
+    }
+
+    #[test]
+    fn merges_within_gap_per_contig() {
+        use Call::NUMT;
+        let ps = [
+            // too far from P4's end
+            pair("P3", "chr1", 5_500, 6_000, NUMT, 0.95, 0.4),
+            pair("P1", "chr1", 1_000, 2_000, NUMT, 0.90, 0.3),
+            // overlaps P1
+            pair("P2", "chr1", 1_800, 2_500, NUMT, 0.99, 0.6),
+            // exactly gap bp after P2's end: joins
+            pair("P4", "chr1", 3_500, 3_600, NUMT, 0.92, 0.1),
+            // another contig
+            pair("P5", "chr2", 1_000, 1_100, NUMT, 0.97, 0.2),
+        ];
+        let rs = regions(&ps, 1_000);
+        assert_eq!(
+            spans(&rs),
+            [
+                ("chr1", 1_000, 3_600, 3),
+                ("chr1", 5_500, 6_000, 1),
+                ("chr2", 1_000, 1_100, 1)
+            ]
+        );
+        let r = &rs[0];
+        assert_eq!(r.region_id, "R000001");
+        assert_eq!(r.pair_ids, ["P1", "P2", "P4"]);
+        // union of [1000,2500) and [3500,3600), not the span
+        assert_eq!(r.numt_bp, 1_600);
+        // the aligned bp of P1, P2 and P4, the overlap counted twice
+        assert_eq!(r.aln_bp, 1_800);
+        assert_eq!((r.min_ident, r.max_ident), (0.90, 0.99));
+        assert_eq!(r.confidence, 0.6);
+        assert_eq!(rs[2].region_id, "R000003");
+
+        // a zero gap only merges overlapping or abutting loci
+        assert_eq!(regions(&ps, 0).len(), 4);
+    }
+
+    #[test]
+    fn other_calls_in_the_gap_split_regions() {
+        use Call::{Ambiguous, NIMT, NUMT};
+        let numts = [
+            pair("P1", "chr1", 1_000, 2_000, NUMT, 0.9, 0.5),
+            pair("P2", "chr1", 2_500, 3_000, NUMT, 0.9, 0.5),
+        ];
+        assert_eq!(regions(&numts, 1_000).len(), 1);
+
+        // an Ambiguous locus between them breaks the region…
+        let mut ps = numts.to_vec();
+        ps.push(pair("A", "chr1", 2_100, 2_200, Ambiguous, 0.9, 0.0));
+        assert_eq!(
+            spans(&regions(&ps, 1_000)),
+            [("chr1", 1_000, 2_000, 1), ("chr1", 2_500, 3_000, 1)]
+        );
+        // …so does a NIMT, even one that only reaches into the gap
+        let mut ps = numts.to_vec();
+        ps.push(pair("N", "chr1", 1_500, 2_001, NIMT, 0.9, 0.5));
+        assert_eq!(regions(&ps, 1_000).len(), 2);
+
+        // but not one inside a NUMT locus, next to the gap, or on another contig
+        let mut ps = numts.to_vec();
+        ps.push(pair("A1", "chr1", 1_200, 1_300, Ambiguous, 0.9, 0.0));
+        ps.push(pair("A2", "chr1", 1_500, 2_000, Ambiguous, 0.9, 0.0));
+        ps.push(pair("A3", "chr1", 2_500, 2_600, Ambiguous, 0.9, 0.0));
+        ps.push(pair("A4", "chr2", 2_100, 2_200, Ambiguous, 0.9, 0.0));
+        assert_eq!(regions(&ps, 1_000).len(), 1);
+
+        // overlapping NUMTs have no gap to interrupt
+        let ps = [
+            pair("P1", "chr1", 1_000, 2_000, NUMT, 0.9, 0.5),
+            pair("P2", "chr1", 1_900, 3_000, NUMT, 0.9, 0.5),
+            pair("A", "chr1", 1_950, 1_960, Ambiguous, 0.9, 0.0),
+        ];
+        assert_eq!(regions(&ps, 0).len(), 1);
+    }
+
+    #[test]
+    fn tsv_and_bed_rows() {
+        let ps = [
+            pair("P1", "chr1", 100, 200, Call::NUMT, 0.9, 2.5),
+            pair("P2", "chr1", 150, 300, Call::NUMT, 0.95, 0.25),
+        ];
+        let rs = regions(&ps, 1_000);
+        assert_eq!(
+            numt_regions_tsv(&rs),
+            format!("{NUMT_REGIONS_TSV_HEADER}\nR000001\tchr1\t100\t300\t200\t250\t2\t0.9000\t0.9500\t2.5000\tP1,P2\n")
+        );
+        assert_eq!(
+            bed::bed_string(&numt_regions_bed(&rs)),
+            "chr1\t100\t300\tR000001\t1000\t+\n"
+        );
+        // pairs on both strands
+        let mut ps = ps;
+        ps[1].locus.strand = Strand::Reverse;
+        assert_eq!(
+            bed::bed_string(&numt_regions_bed(&regions(&ps, 1_000))),
+            "chr1\t100\t300\tR000001\t1000\t.\n"
+        );
+        assert_eq!(regions(&[], 1_000), []);
+    }
+
+    fn any_call_regions(pairs: &[ScoredPair], gap: u32) -> Vec<NumtRegion> {
+        let mut b = RegionBuilder::any_call();
+        pairs.iter().for_each(|p| b.add(p));
+        b.finish(gap)
+    }
+
+    #[test]
+    fn any_call_regions_chain_every_pair() {
+        use Call::{Ambiguous, NIMT, NUMT};
+        let ps = [
+            // a chain: each within 2 kb of the one before, whatever its call
+            pair("P1", "chr1", 1_000, 2_000, NUMT, 0.9, 0.5),
+            pair("P2", "chr1", 3_500, 4_000, Ambiguous, 0.9, 0.0),
+            pair("P3", "chr1", 6_000, 6_500, NIMT, 0.95, 0.4),
+            // a singleton, 2 kb and one base past the chain
+            pair("P4", "chr1", 8_501, 9_000, NUMT, 0.9, 0.5),
+            // the same coordinates on another contig
+            pair("P5", "chr2", 3_500, 4_000, NUMT, 0.9, 0.5),
+        ];
+        let rs = any_call_regions(&ps, DEFAULT_REGION_GAP);
+        assert_eq!(
+            spans(&rs),
+            [
+                ("chr1", 1_000, 6_500, 3),
+                ("chr1", 8_501, 9_000, 1),
+                ("chr2", 3_500, 4_000, 1)
+            ]
+        );
+        assert_eq!(rs[0].pair_ids, ["P1", "P2", "P3"]);
+        assert_eq!(rs[0].calls, [NUMT, Ambiguous, NIMT]);
+        assert_eq!((rs[0].numt_bp, rs[0].aln_bp), (2_000, 2_000));
+        assert_eq!(rs[1].pair_ids, ["P4"]);
+        assert_eq!(
+            rs.iter().map(|r| r.region_id.as_str()).collect::<Vec<_>>(),
+            ["G000001", "G000002", "G000003"]
+        );
+        // a gap wide enough for P1 and P4 joins everything on chr1; the
+        // NUMT regions still split at the other calls between them
+        assert_eq!(any_call_regions(&ps, 10_000).len(), 2);
+        assert_eq!(regions(&ps, 10_000).len(), 3);
+
+        assert_eq!(
+            regions_tsv(&rs[..2]),
+            format!(
+                "{REGIONS_TSV_HEADER}\n\
+                 G000001\tchr1\t1000\t6500\t5500\t2000\t2000\t3\tLikely_NUMT:1,Likely_NIMT:1,Ambiguous:1\tP1,P2,P3\n\
+                 G000002\tchr1\t8501\t9000\t499\t499\t499\t1\tLikely_NUMT:1\tP4\n"
+            )
+        );
+    }
+}
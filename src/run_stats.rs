@@ -0,0 +1,106 @@
+//! run_stats.json (`--cost-columns`): what collecting evidence cost — the
+//! stage as a whole, and the per-pair costs summed by nuclear contig and by
+//! call, to find the loci (usually the deep ones) that dominate a run.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::model::EvidenceCost;
+use crate::scoring::ScoredPair;
+
+pub const RUN_STATS_FILE: &str = "run_stats.json";
+
+/// Per-pair costs summed over a group of pairs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CostTally {
+    pub n_pairs: usize,
+    pub cost_ms: f64,
+    pub n_subprocess_calls: u64,
+}
+
+impl CostTally {
+    fn add(&mut self, cost: EvidenceCost) {
+        self.n_pairs += 1;
+        self.cost_ms += cost.cost_ms;
+        self.n_subprocess_calls += cost.n_subprocess_calls;
+    }
+}
+
+/// The evidence stage as a whole: its wall time also covers the work between
+/// pairs, so it is at least the sum of the per-pair `cost_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StageCost {
+    pub n_pairs: usize,
+    pub wall_ms: f64,
+    pub n_subprocess_calls: u64,
+}
+
+/// Serialized as run_stats.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RunStats {
+    /// Absent when the evidence was not collected in this run (rescored
+    /// from results.json).
+    pub evidence: Option<StageCost>,
+    /// Sum over the pairs with a cost.
+    pub pairs: CostTally,
+    pub by_contig: BTreeMap<String, CostTally>,
+    pub by_call: BTreeMap<String, CostTally>,
+}
+
+impl RunStats {
+    pub fn save_to(out_dir: &Path, r: &Self) -> Result<()> {
+        let f = fs_err::File::create(out_dir.join(RUN_STATS_FILE))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(f), r)?;
+        Ok(())
+    }
+
+    pub fn load_from(out_dir: &Path) -> Result<Self> {
+        let f = fs_err::File::open(out_dir.join(RUN_STATS_FILE))?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(f))?)
+    }
+}
+
+/// Sums the cost of each scored pair as it is written.
+#[derive(Debug, Default)]
+pub struct RunStatsBuilder {
+    pairs: CostTally,
+    by_contig: BTreeMap<String, CostTally>,
+    by_call: BTreeMap<String, CostTally>,
+}
+
+impl RunStatsBuilder {
+    /// Pairs without an `evidence_cost` are left out.
+    pub fn add(&mut self, sp: &ScoredPair) {
+        let Some(cost) = sp.evidence_cost else {
+            return;
+        };
+        self.pairs.add(cost);
+        self.by_contig
+            .entry(sp.locus.nuc_contig.clone())
+            .or_default()
+            .add(cost);
+        self.by_call
+            .entry(sp.score.call.as_str().to_string())
+            .or_default()
+            .add(cost);
+    }
+
+    /// `stage` is the whole stage's cost over its `n_pairs`.
+    pub fn finish(self, stage: Option<EvidenceCost>, n_pairs: usize) -> RunStats {
+        RunStats {
+            evidence: stage.map(|s| StageCost {
+                n_pairs,
+                wall_ms: s.cost_ms,
+                n_subprocess_calls: s.n_subprocess_calls,
+            }),
+            pairs: self.pairs,
+            by_contig: self.by_contig,
+            by_call: self.by_call,
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! JSON Schemas for the JSON files a run writes (feature `schema`).
+//!
+//! The schemas are derived from the serialized types, so they follow any
+//! change to them. `onsm schema --json-schema ARTIFACT` prints one, and
+//! `onsm doctor` checks each JSON file in a run directory against the schema
+//! of the running build (noting when the file was written by another version).
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::mapping_stats::{self, MappingStats};
+use crate::model::{CoverageSummary, RunManifest};
+use crate::run_stats::{self, RunStats};
+use crate::scoring::ScoringResults;
+use crate::summary::Summary;
+use crate::util::progress::{self, ProgressReport};
+use crate::util::strict::{self, StrictError};
+use crate::util::warnings::{self, WarningsReport};
+
+/// A JSON file with a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Artifact {
+    /// run_manifest.json
+    RunManifest,
+    /// coverage.json
+    Coverage,
+    /// results.json
+    Results,
+    /// summary.json
+    Summary,
+    /// warnings.json
+    Warnings,
+    /// error.json (--strict)
+    Error,
+    /// progress.json
+    Progress,
+    /// run_stats.json (--cost-columns)
+    RunStats,
+    /// mapping_stats.json
+    MappingStats,
+}
+
+impl Artifact {
+    pub const ALL: [Artifact; 9] = [
+        Artifact::RunManifest,
+        Artifact::Coverage,
+        Artifact::Results,
+        Artifact::Summary,
+        Artifact::Warnings,
+        Artifact::Error,
+        Artifact::Progress,
+        Artifact::RunStats,
+        Artifact::MappingStats,
+    ];
+
+    /// File name in the run directory.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Artifact::RunManifest => "run_manifest.json",
+            Artifact::Coverage => "coverage.json",
+            Artifact::Results => "results.json",
+            Artifact::Summary => "summary.json",
+            Artifact::Warnings => warnings::WARNINGS_FILE,
+            Artifact::Error => strict::ERROR_FILE,
+            Artifact::Progress => progress::PROGRESS_FILE,
+            Artifact::RunStats => run_stats::RUN_STATS_FILE,
+            Artifact::MappingStats => mapping_stats::MAPPING_STATS_FILE,
+        }
+    }
+
+    /// The schema, titled with the file name and stamped with this build's version.
+    pub fn schema(self) -> Value {
+        let mut s = match self {
+            Artifact::RunManifest => schemars::schema_for!(RunManifest),
+            Artifact::Coverage => schemars::schema_for!(CoverageSummary),
+            Artifact::Results => schemars::schema_for!(ScoringResults),
+            Artifact::Summary => schemars::schema_for!(Summary),
+            Artifact::Warnings => schemars::schema_for!(WarningsReport),
+            Artifact::Error => schemars::schema_for!(StrictError),
+            Artifact::Progress => schemars::schema_for!(ProgressReport),
+            Artifact::RunStats => schemars::schema_for!(RunStats),
+            Artifact::MappingStats => schemars::schema_for!(MappingStats),
+        };
+        s.insert("title".into(), self.file_name().into());
+        s.insert(
+            "$comment".into(),
+            format!("onsm {} {}", env!("CARGO_PKG_VERSION"), self.file_name()).into(),
+        );
+        s.into()
+    }
+
+    /// Schema violations of `instance`, as `/json/pointer: message` lines
+    /// (empty if it is valid).
+    pub fn validate(self, instance: &Value) -> Vec<String> {
+        let validator = jsonschema::validator_for(&self.schema())
+            .expect("derived schemas are valid JSON Schema");
+        validator
+            .iter_errors(instance)
+            .map(|e| {
+                let at = e.instance_path.to_string();
+                format!("{}: {e}", if at.is_empty() { "/" } else { &at })
+            })
+            .collect()
+    }
+
+    /// `validate` for a file on disk; unparseable JSON is an error.
+    pub fn validate_file(self, path: &Path) -> Result<Vec<String>> {
+        let text = fs_err::read_to_string(path)?;
+        let v: Value = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not valid JSON", path.display()))?;
+        Ok(self.validate(&v))
+    }
+}
+
+/// The onsm version recorded in an artifact (run_manifest.json and
+/// results.json carry `build_info`).
+pub fn written_by(instance: &Value) -> Option<&str> {
+    instance.pointer("/build_info/version")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::PairsColumns;
+    use crate::control_region::ControlRegions;
+    use crate::io::fasta::{GapIndex, LengthCache};
+    use crate::io::paf::IdentityKind;
+    use crate::mito_meta::MitoContigMeta;
+    use crate::model::orientation::Strand;
+    use crate::model::{self, ClassifyParams, EvidenceParams, PairEvidence, PairedLocus, Weights};
+    use crate::pipeline::{self, EvidenceSource, OutputContext};
+    use crate::regions;
+    use crate::summary::{SummaryUnits, Tier};
+    use crate::util::progress::{Progress, ProgressState};
+    use crate::util::strict::StrictPolicy;
+    use crate::util::warnings::Warnings;
+    use tempfile::TempDir;
+
+    struct Fixed;
+
+    impl EvidenceSource for Fixed {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            let numt = p.nuc_contig == "chr1";
+            Ok(PairEvidence {
+                depth: if numt { (30.0, 5.0) } else { (5.0, 300.0) },
+                span: if numt { (0.8, 0.1) } else { (0.1, 0.9) },
+                read_len: (15_000.0, 14_000.0),
+                read_div: Default::default(),
+                span_reads: None,
+                junction_clips: (0, 0),
+                clamped_window: None,
+                invalid_window: None,
+            })
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Some(EvidenceParams::new(model::FLANK_BP, model::WIN_BP))
+        }
+    }
+
+    /// A finished run directory with every schema'd artifact in it.
+    fn fixture_run(dir: &Path, low_memory: bool) {
+        let mito = dir.join("mito.fa");
+        let nuc = dir.join("nuc.fa");
+        fs_err::write(&mito, format!(">mt\n{}\n", "A".repeat(2_000))).unwrap();
+        fs_err::write(
+            &nuc,
+            format!(
+                ">chr1\n{}\n>chr2\n{}\n",
+                "C".repeat(9_000),
+                "G".repeat(9_000)
+            ),
+        )
+        .unwrap();
+        let mut manifest = RunManifest::new(
+            &mito,
+            &nuc,
+            &[],
+            "hifi",
+            1,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MERGE_GAP,
+            model::FLANK_BP,
+            model::WIN_BP,
+        );
+        if low_memory {
+            // and the per-molecule-type NIMT figures
+            manifest.mito_contig_meta = MitoContigMeta::parse("mt\tmito\tyes\n").unwrap();
+        }
+        RunManifest::save_to(dir, &manifest).unwrap();
+
+        let pairs: Vec<PairedLocus> = (0..4u32)
+            .map(|i| PairedLocus {
+                pair_id: format!("P{:06}", i + 1),
+                nuc_contig: if i % 2 == 0 { "chr1" } else { "chr2" }.to_string(),
+                nuc_start: 1_000 * i,
+                nuc_end: 1_000 * i + 500,
+                mito_contig: "mt".to_string(),
+                mito_start: 100 * i,
+                mito_end: 100 * i + 500,
+                aln_len: 500,
+                aln_ident: 0.97,
+                strand: if i == 3 {
+                    Strand::Reverse
+                } else {
+                    Strand::Forward
+                },
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: true,
+                parent_id: None,
+                rbh: None,
+            })
+            .collect();
+        let (coverage, spans) = pipeline::collect_evidence(&pairs, &Fixed).unwrap();
+        let ctx = OutputContext {
+            out_dir: dir,
+            mito_fa: &mito,
+            nuc_fa: &nuc,
+            lengths: &LengthCache::default(),
+            genome_sizes: None,
+            weights: Weights::default(),
+            params: ClassifyParams::default(),
+            density: None,
+            pairs_columns: &PairsColumns::default().with_cost(true),
+            region_merge_gap: regions::DEFAULT_REGION_MERGE_GAP,
+            region_gap: regions::DEFAULT_REGION_GAP,
+            // both spellings of summary.json must match its schema
+            summary_units: if low_memory {
+                SummaryUnits::Ppm
+            } else {
+                SummaryUnits::Percent
+            },
+            mito_meta: &manifest.mito_contig_meta,
+            evidence_cache: None,
+            excluded_nuclear: &[],
+            gaps: &GapIndex::default(),
+            control_regions: &ControlRegions::default(),
+            evidence_params: EvidenceParams::new(model::FLANK_BP, model::WIN_BP),
+            ignore_evidence_params: false,
+            bed_bounds: &[],
+            bed_tier: Tier::All,
+            output_compat: Default::default(),
+            outputs: Default::default(),
+        };
+        if low_memory {
+            pipeline::write_low_memory(&ctx, pairs, &Fixed, &dir.join("work"), 3).unwrap();
+        } else {
+            pipeline::write_in_memory(&ctx, &pairs, &coverage, &spans).unwrap();
+        }
+
+        let w = Warnings::default();
+        for i in 0..7 {
+            w.record(warnings::MALFORMED_PAF_LINE, &format!("line {i}"));
+        }
+        let report = w.report();
+        report.save_to(dir).unwrap();
+        let flagstat = "10 + 0 in total (QC-passed reads + QC-failed reads)\n\
+                        9 + 0 mapped (90.00% : N/A)\n";
+        let counts = mapping_stats::FlagStats::parse(flagstat).unwrap();
+        MappingStats {
+            nuclear: counts,
+            mito: counts,
+            min_mapped_frac: mapping_stats::DEFAULT_MIN_MAPPED_FRAC,
+        }
+        .save_to(dir)
+        .unwrap();
+        Progress::start(dir, false)
+            .unwrap()
+            .finish(ProgressState::Finished);
+        assert!(StrictPolicy::default().enforce(dir, &report, 4).is_err());
+    }
+
+    #[test]
+    fn fresh_artifacts_match_their_schemas() {
+        // --low-memory writes results.json by hand, so check both paths
+        for low_memory in [false, true] {
+            let td = TempDir::new().unwrap();
+            fixture_run(td.path(), low_memory);
+            for a in Artifact::ALL {
+                let path = td.path().join(a.file_name());
+                let errors = a.validate_file(&path).unwrap();
+                assert!(errors.is_empty(), "{}: {errors:?}", a.file_name());
+                assert_eq!(a.schema()["title"], a.file_name());
+            }
+            let results: Value = serde_json::from_str(
+                &fs_err::read_to_string(td.path().join("results.json")).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(written_by(&results), Some(env!("CARGO_PKG_VERSION")));
+        }
+    }
+
+    #[test]
+    fn broken_artifacts_fail_with_a_pointer() {
+        let td = TempDir::new().unwrap();
+        fixture_run(td.path(), false);
+        let path = td.path().join("results.json");
+        let mut v: Value = serde_json::from_str(&fs_err::read_to_string(&path).unwrap()).unwrap();
+        v["pairs"][1]["locus"]["nuc_start"] = "12".into();
+        v["pairs"][2]["score"]["call"] = "Likely_Something".into();
+        v.as_object_mut().unwrap().remove("weights");
+        fs_err::write(&path, v.to_string()).unwrap();
+        let errors = Artifact::Results.validate_file(&path).unwrap();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/pairs/1/locus/nuc_start: ")),
+            "{errors:?}"
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/pairs/2/score/call: ")),
+            "{errors:?}"
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/: ") && e.contains("weights")),
+            "{errors:?}"
+        );
+
+        // wrong type at the top, and not JSON at all
+        assert!(!Artifact::Coverage.validate(&Value::from(3)).is_empty());
+        fs_err::write(&path, "{\"weights\":").unwrap();
+        let e = Artifact::Results.validate_file(&path).unwrap_err();
+        assert!(format!("{e:#}").contains("not valid JSON"), "{e:#}");
+    }
+}
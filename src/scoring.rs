@@ -1,26 +1,360 @@
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
-use crate::model::{ClassifyParams, PairedLocus, Weights};
-use crate::model::{CoverageSummary, SpanSummary};
+use crate::columns::PairsColumns;
+use crate::control_region::{ControlRegionOverlap, ControlRegions};
+use crate::io::bam::WindowError;
+use crate::io::fasta::{GapIndex, GapStats};
+use crate::io::paf::IdentityKind;
+use crate::mito_meta;
+use crate::model::orientation::Strand;
+use crate::model::{ClassifyParams, PairEvidence, PairedLocus, Weights};
+use crate::model::{CoverageSummary, EvidenceCost, SpanSummary};
+use crate::summary::ContigLengths;
+use crate::util::version::VersionInfo;
 use std::fmt::Write as _;
 
-#[derive(Debug, Clone)]
+pub mod rules;
+
+use rules::RuleInput;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[allow(clippy::upper_case_acronyms)]
-enum Call {
+pub enum Call {
+    #[serde(rename = "Likely_NUMT")]
     NUMT,
+    #[serde(rename = "Likely_NIMT")]
     NIMT,
     Ambiguous,
+    /// Strong evidence on both sides (recent transfer still segregating, or
+    /// an assembly duplication) rather than none either way.
+    #[serde(rename = "Shared_Support")]
+    SharedSupport,
 }
 
 impl Call {
-    fn as_str_and_reason(&self) -> (&'static str, &'static str) {
+    pub fn as_str_and_reason(&self) -> (&'static str, &'static str) {
         match self {
             Call::NUMT => ("Likely_NUMT", "score_difference"),
             Call::NIMT => ("Likely_NIMT", "score_difference"),
             Call::Ambiguous => ("Ambiguous", "delta_below_threshold"),
+            Call::SharedSupport => ("Shared_Support", "both_sides_supported"),
         }
     }
+
+    /// NUMT or NIMT.
+    pub fn is_decided(&self) -> bool {
+        matches!(self, Call::NUMT | Call::NIMT)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.as_str_and_reason().0
+    }
+}
+
+/// Reason code added when the nuclear side looks like a collapsed multi-copy region.
+pub const POSSIBLE_COLLAPSED_REPEAT: &str = "possible_collapsed_repeat";
+
+/// Reason code added when the median read length on either side is shorter than
+/// the spanning window, so a low spanning fraction says little about the locus.
+pub const READS_SHORTER_THAN_WINDOW: &str = "reads_shorter_than_window";
+
+/// Reason code on every pair of a run whose reads were too short for span
+/// evidence (`--allow-short-reads`): the span weight is 0 and the call rests
+/// on depth alone.
+pub const SPAN_EVIDENCE_DISABLED: &str = "span_evidence_disabled";
+
+/// Reason code on NIMT calls of a run whose mito was assembled from its own
+/// reads (see `self_assembly`): the mito-side read support is partly circular.
+pub const SELF_ASSEMBLY_CAVEAT: &str = "self_assembly_caveat";
+
+/// Summary caveat of a run that scored a hand-edited pair list
+/// (`reuse --pairs-from`) instead of the loci pairing found.
+pub const MANUAL_PAIR_LIST: &str = "manual_pair_list";
+
+/// Reason code added when the nuclear locus contains an assembly gap (N-run):
+/// the homology is real on both sides, but the insertion's length and
+/// continuity are unknown.
+pub const SPANS_ASSEMBLY_GAP: &str = "spans_assembly_gap";
+/// Reason code added with `--max-softmask-frac` when more of the nuclear locus
+/// than that is soft-masked (lowercase) in the assembly; the call is then
+/// Ambiguous whatever the scores said.
+pub const SOFTMASKED_LOCUS: &str = "softmasked_locus";
+
+/// Reason code added when the mito side lies entirely inside a control region
+/// (`--mito-control-region`), where identity says little about direction…
+pub const IN_CONTROL_REGION: &str = "in_control_region";
+/// …and when it lies partly inside one.
+pub const OVERLAPS_CONTROL_REGION: &str = "overlaps_control_region";
+
+/// Reason code added when the pair's depth and span windows could not be
+/// built (an inverted, empty or out-of-range locus); the call is then
+/// Ambiguous, as there is no read evidence to score.
+pub const INVALID_WINDOW: &str = "invalid_window";
+
+/// Reason code added when a contig end leaves a span window narrower than
+/// `MIN_SPAN_WINDOW_BP`: its span fraction rests on few bases. The call is
+/// left alone.
+pub const SHORT_WINDOW: &str = "short_window";
+
+/// More than this fraction of N bases in the nuclear locus…
+pub const GAP_MAX_N_FRAC: f32 = 0.10;
+/// …or any N-run at least this long flags `spans_assembly_gap`.
+pub const GAP_MIN_N_RUN: u32 = 100;
+/// Junction reads at which `PairFeatures::junction_support` is one half.
+pub const JUNCTION_HALF_READS: f32 = 4.0;
+
+/// Whether a nuclear locus of `len` bp with these N bases spans an assembly gap.
+pub fn spans_assembly_gap(gaps: GapStats, len: u32) -> bool {
+    gaps.longest_run >= GAP_MIN_N_RUN || gaps.n_bases as f32 > GAP_MAX_N_FRAC * len as f32
+}
+
+/// Nuclear copy number implied by the normalized depth, rounded to the nearest 0.5.
+/// rnuc ≈ 2 suggests the assembler collapsed two copies (or a segmental duplication).
+pub fn est_copy_number(rnuc: f32) -> f32 {
+    (rnuc * 2.0).round() / 2.0
+}
+
+/// Everything the scorer looks at for one pair (depths already normalized).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PairFeatures {
+    pub aln_ident: f32,
+    pub aln_len: u32,
+    pub rnuc: f32,
+    pub rmito: f32,
+    pub s_nuc: f32,
+    pub s_mito: f32,
+    /// Median reference-consumed read length (bp) in each span window; 0 = unknown
+    /// (and absent in results.json from older runs).
+    #[serde(default)]
+    pub read_len_nuc: f32,
+    #[serde(default)]
+    pub read_len_mito: f32,
+    /// Median read divergence (NM / alignment columns) in each span window;
+    /// `None` = unknown (no read with an NM tag, or an older run).
+    #[serde(default)]
+    pub read_div_nuc: Option<f32>,
+    #[serde(default)]
+    pub read_div_mito: Option<f32>,
+    /// Mapped reads in each span window left out for a MAPQ below
+    /// `--span-mapq`; many suggest the cutoff hides support.
+    #[serde(default)]
+    pub low_mapq_nuc: u32,
+    #[serde(default)]
+    pub low_mapq_mito: u32,
+    /// Junction reads at the nuclear start and end (see
+    /// `PairEvidence::junction_clips`); 0 when not counted.
+    #[serde(default)]
+    pub junction_clips_start: u32,
+    #[serde(default)]
+    pub junction_clips_end: u32,
+    /// N bases, and the longest N-run, in the nuclear locus.
+    #[serde(default)]
+    pub nuc_n_bases: u32,
+    #[serde(default)]
+    pub nuc_longest_n_run: u32,
+    /// Fraction of the nuclear locus soft-masked (lowercase) in the assembly.
+    #[serde(default)]
+    pub softmask_frac_nuc: f32,
+    /// Where the mito side lies relative to the `--mito-control-region`s.
+    #[serde(default)]
+    pub control_region: ControlRegionOverlap,
+    /// Widths of the span windows when a contig end cut either (see
+    /// `PairEvidence::clamped_window`); `short_window` below
+    /// `MIN_SPAN_WINDOW_BP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamped_window: Option<(u32, u32)>,
+    /// Why the pair has no evidence windows, if it has none; its read
+    /// features are then zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invalid_window: Option<WindowError>,
+}
+
+impl PairFeatures {
+    /// Junction reads at both nuclear ends, soft-saturated to [0, 1): half
+    /// way at `JUNCTION_HALF_READS` reads.
+    pub fn junction_support(&self) -> f32 {
+        let n = self
+            .junction_clips_start
+            .saturating_add(self.junction_clips_end) as f32;
+        n / (n + JUNCTION_HALF_READS)
+    }
+
+    /// Shortest known median read length over the span window width, capped at 1;
+    /// `None` if both lengths are unknown or every known one covers the window.
+    pub fn read_len_factor(&self, span_window: u32) -> Option<f32> {
+        let shortest = [self.read_len_nuc, self.read_len_mito]
+            .into_iter()
+            .filter(|&l| l > 0.0)
+            .reduce(f32::min)?;
+        (shortest < span_window as f32).then(|| shortest / span_window.max(1) as f32)
+    }
+
+    /// Compact, spreadsheet-friendly digest of the features, e.g.
+    /// `D:nuc=1.0x,mito=37x|S:nuc=82%,mito=3%|len=4.2kb|id=96.1%`.
+    pub fn evidence_summary(&self) -> String {
+        format!(
+            "D:nuc={},mito={}|S:nuc={},mito={}|len={}|id={}",
+            fmt_ratio(self.rnuc),
+            fmt_ratio(self.rmito),
+            fmt_pct(self.s_nuc),
+            fmt_pct(self.s_mito),
+            fmt_len(self.aln_len),
+            fmt_identity(self.aln_ident)
+        )
+    }
+}
+
+/// Depth ratio: one decimal below 10×, whole numbers up to 999×, then `>999x`.
+fn fmt_ratio(r: f32) -> String {
+    if !r.is_finite() || r < 0.0 {
+        return "?x".to_string();
+    }
+    if (r * 10.0).round() < 100.0 {
+        format!("{r:.1}x")
+    } else if r.round() < 1000.0 {
+        format!("{r:.0}x")
+    } else {
+        ">999x".to_string()
+    }
+}
+
+/// Fraction as a whole percentage; never rounds a nonzero value to 0% or a
+/// partial one to 100%.
+fn fmt_pct(f: f32) -> String {
+    let f = clamp01(f);
+    let pct = (100.0 * f).round();
+    if f > 0.0 && pct == 0.0 {
+        "<1%".to_string()
+    } else if f < 1.0 && pct == 100.0 {
+        ">99%".to_string()
+    } else {
+        format!("{pct:.0}%")
+    }
+}
+
+/// Length in bp below 1 kb, then kb (one decimal below 10 kb), then Mb.
+fn fmt_len(bp: u32) -> String {
+    let kb = bp as f64 / 1000.0;
+    if bp < 1000 {
+        format!("{bp}bp")
+    } else if (kb * 10.0).round() < 100.0 {
+        format!("{kb:.1}kb")
+    } else if kb.round() < 1000.0 {
+        format!("{kb:.0}kb")
+    } else {
+        format!("{:.1}Mb", kb / 1000.0)
+    }
+}
+
+/// Identity with one decimal; just-below-100% stays distinguishable from 100%.
+fn fmt_identity(i: f32) -> String {
+    let i = clamp01(i);
+    let pct = (1000.0 * i).round() / 10.0;
+    if i < 1.0 && pct >= 100.0 {
+        ">99.9%".to_string()
+    } else {
+        format!("{pct:.1}%")
+    }
+}
+
+/// Each term's contribution to Δ = score_numt − score_nimt (positive favours NUMT).
+/// Alignment identity/length add equally to both scores and so never move Δ.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DeltaTerms {
+    pub depth_consistency: f32,
+    pub depth_contrast: f32,
+    pub span: f32,
+    /// Junction reads (`w_j`); absent in results.json from older runs.
+    #[serde(default)]
+    pub junction: f32,
+}
+
+/// Scores, call and explanation for one pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PairScore {
+    pub score_numt: f32,
+    pub score_nimt: f32,
+    pub delta: f32,
+    pub call: Call,
+    pub confidence: f32,
+    pub high_confidence: bool,
+    pub reason_codes: Vec<String>,
+    pub terms: DeltaTerms,
+    /// `est_copy_number(rnuc)`; absent in results.json from older runs.
+    #[serde(default)]
+    pub est_copy_number_nuc: f32,
+    /// The `rules::RULES` that fired, in table order (absent in results.json
+    /// from older runs).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+}
+
+impl PairScore {
+    pub fn possible_collapsed_repeat(&self) -> bool {
+        self.reason_codes
+            .iter()
+            .any(|r| r == POSSIBLE_COLLAPSED_REPEAT)
+    }
+}
+
+/// A scored locus: the single structure every per-pair output is rendered from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ScoredPair {
+    pub locus: PairedLocus,
+    pub features: PairFeatures,
+    pub score: PairScore,
+    /// `--mito-contig-meta` type of the mito contig (absent in results.json
+    /// from older runs → `mito`).
+    #[serde(default = "default_molecule_type")]
+    pub mito_molecule_type: String,
+    /// What collecting the pair's evidence cost (`--cost-columns` only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence_cost: Option<EvidenceCost>,
+    /// Pairs whose locus overlaps this one's counterpart or the other way
+    /// round (see `crossref`); absent in results.json from older runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_pairs: Vec<String>,
+}
+
+fn default_molecule_type() -> String {
+    mito_meta::DEFAULT_MOLECULE_TYPE.to_string()
+}
+
+/// Scored pairs plus the settings that produced them (serialized as `results.json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ScoringResults {
+    pub weights: Weights,
+    pub params: ClassifyParams,
+    pub pairs: Vec<ScoredPair>,
+    /// The onsm build that scored them (absent in results.json from older runs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_info: Option<VersionInfo>,
+}
+
+impl ScoringResults {
+    pub fn save_to(out_dir: &Path, r: &Self) -> Result<()> {
+        let f = fs_err::File::create(out_dir.join("results.json"))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(f), r)?;
+        Ok(())
+    }
+
+    pub fn load_from(out_dir: &Path) -> Result<Self> {
+        let f = fs_err::File::open(out_dir.join("results.json"))?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(f))?)
+    }
+
+    pub fn get(&self, pair_id: &str) -> Option<&ScoredPair> {
+        self.pairs.iter().find(|p| p.locus.pair_id == pair_id)
+    }
 }
 
 fn clamp01(x: f32) -> f32 {
@@ -34,13 +368,166 @@ fn scale_len(len_bp: u32) -> f32 {
     clamp01(l / (l + l50))
 }
 
-pub fn classify_pairs(
+/// Score one pair's features under both hypotheses and make the call.
+pub fn score_features(f: &PairFeatures, w: Weights, params: ClassifyParams) -> PairScore {
+    score_locus(f, None, w, params)
+}
+
+/// `score_features`, with the length of the nuclear locus for the rules that
+/// need it.
+fn score_locus(
+    f: &PairFeatures,
+    nuc_len: Option<u32>,
+    w: Weights,
+    params: ClassifyParams,
+) -> PairScore {
+    let a = clamp01(f.aln_ident);
+    let l = scale_len(f.aln_len);
+    let w_a = match f.control_region {
+        ControlRegionOverlap::Full => w.w_a * params.control_region_identity_weight,
+        _ => w.w_a,
+    };
+    let base = w_a * a + w.w_l * l;
+    let (rnuc, rmito, s_nuc, s_mito) = (f.rnuc, f.rmito, f.s_nuc, f.s_mito);
+    let short_reads = f.read_len_factor(params.span_window);
+    let w_s = match short_reads {
+        _ if params.span_evidence_disabled => 0.0,
+        Some(k) if params.span_read_len_scaling => w.w_s * k,
+        _ => w.w_s,
+    };
+
+    // Depth consistency terms (favor ~1.0)
+    let d_numt = clamp01(1.0 - (rnuc - 1.0).abs());
+    let d_nimt = clamp01(1.0 - (rmito - 1.0).abs());
+
+    // Contrast boosters (signed): + favors NUMT, − favors NIMT
+    let eps = 1e-3_f32;
+    let log2_ratio = ((rnuc + eps) / (rmito + eps)).ln() / std::f32::consts::LN_2;
+    let depth_contrast = (1.25 * log2_ratio).tanh(); // (-1..1)
+    let span_contrast = s_nuc - s_mito; // (-1..1)
+
+    // Build scores
+    let pro_numt = w.w_d * d_numt + w_s * s_nuc;
+    let pro_nimt = w.w_d * d_nimt + w_s * s_mito;
+    let pen_numt = w.w_d * d_nimt + w_s * s_mito;
+    let pen_nimt = w.w_d * d_numt + w_s * s_nuc;
+    let boost_numt = w.w_d * depth_contrast + w_s * span_contrast;
+    let boost_nimt = -w.w_d * depth_contrast - w_s * span_contrast;
+
+    // Junction reads only speak for an insertion in the nuclear genome
+    let junction = w.w_j * f.junction_support();
+
+    let score_numt = base + pro_numt - pen_numt + boost_numt + junction;
+    let score_nimt = base + pro_nimt - pen_nimt + boost_nimt;
+
+    let diff = score_numt - score_nimt;
+    let est_copy_number_nuc = est_copy_number(rnuc);
+    let decision = rules::evaluate(
+        rules::RULES,
+        &RuleInput {
+            features: f,
+            diff,
+            est_copy_number_nuc,
+            short_reads,
+            nuc_len,
+            params,
+        },
+    );
+
+    PairScore {
+        score_numt,
+        score_nimt,
+        delta: diff,
+        call: decision.call.unwrap_or(Call::Ambiguous),
+        confidence: diff.abs(),
+        high_confidence: decision.high_confidence,
+        reason_codes: decision.reason_codes,
+        terms: DeltaTerms {
+            depth_consistency: 2.0 * w.w_d * (d_numt - d_nimt),
+            depth_contrast: 2.0 * w.w_d * depth_contrast,
+            span: 2.0 * w_s * (s_nuc - s_mito) + 2.0 * w_s * span_contrast,
+            junction,
+        },
+        est_copy_number_nuc,
+        rules: decision.trace.iter().map(|r| r.to_string()).collect(),
+    }
+}
+
+/// Genome-wide depth baselines used to normalize local depths.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthBaselines {
+    pub nuclear_median: f32,
+    pub mito_median: f32,
+}
+
+/// Normalize one pair's evidence and score it; `gaps` are the N and
+/// soft-masked bases of its nuclear locus, `control_region` where its mito
+/// side lies relative to the control regions.
+pub fn score_pair(
+    p: &PairedLocus,
+    ev: &PairEvidence,
+    gaps: GapStats,
+    control_region: ControlRegionOverlap,
+    baselines: DepthBaselines,
+    w: Weights,
+    params: ClassifyParams,
+) -> ScoredPair {
+    let (d_n_loc, d_m_loc) = ev.depth;
+    let (dn_med, dm_med) = (baselines.nuclear_median, baselines.mito_median);
+    // normalized local medians
+    let rnuc = if dn_med > 0.0 { d_n_loc / dn_med } else { 0.0 };
+    let rmito = if dm_med > 0.0 { d_m_loc / dm_med } else { 0.0 };
+
+    // Spanning
+    let (s_nuc, s_mito) = ev.span;
+    let nuc_len = p.nuc_end.saturating_sub(p.nuc_start);
+    let (low_mapq_nuc, low_mapq_mito) = ev
+        .span_reads
+        .map_or((0, 0), |(n, m)| (n.low_mapq, m.low_mapq));
+
+    let features = PairFeatures {
+        aln_ident: clamp01(p.aln_ident),
+        aln_len: p.aln_len,
+        rnuc,
+        rmito,
+        s_nuc,
+        s_mito,
+        read_len_nuc: ev.read_len.0,
+        read_len_mito: ev.read_len.1,
+        read_div_nuc: ev.read_div.0,
+        read_div_mito: ev.read_div.1,
+        low_mapq_nuc,
+        low_mapq_mito,
+        junction_clips_start: ev.junction_clips.0,
+        junction_clips_end: ev.junction_clips.1,
+        nuc_n_bases: gaps.n_bases,
+        nuc_longest_n_run: gaps.longest_run,
+        softmask_frac_nuc: gaps.masked_bases as f32 / nuc_len.max(1) as f32,
+        control_region,
+        clamped_window: ev.clamped_window,
+        invalid_window: ev.invalid_window,
+    };
+    let score = score_locus(&features, Some(nuc_len), w, params);
+    ScoredPair {
+        locus: p.clone(),
+        features,
+        score,
+        mito_molecule_type: default_molecule_type(),
+        evidence_cost: None,
+        related_pairs: Vec::new(),
+    }
+}
+
+/// Normalize evidence and score every pair.
+pub fn score_pairs(
     pairs: &[PairedLocus],
     coverage: &CoverageSummary,
     spans: &SpanSummary,
+    gaps: &GapIndex,
+    control_regions: &ControlRegions,
     w: Weights,
     params: ClassifyParams,
-) -> Result<(String, String)> {
+) -> Vec<ScoredPair> {
     // lookups
     let depth_map: HashMap<&str, (f32, f32)> = coverage
         .per_pair
@@ -52,89 +539,487 @@ pub fn classify_pairs(
         .iter()
         .map(|(k, v)| (k.as_str(), *v))
         .collect();
+    let read_len_map: HashMap<&str, (f32, f32)> = spans
+        .read_len
+        .iter()
+        .map(|(k, v)| (k.as_str(), *v))
+        .collect();
+    let read_div_map: HashMap<&str, (Option<f32>, Option<f32>)> = spans
+        .read_div
+        .iter()
+        .map(|(k, v)| (k.as_str(), *v))
+        .collect();
 
-    let dn_med = coverage.nuclear_median as f32;
-    let dm_med = coverage.mito_median as f32;
+    let baselines = DepthBaselines {
+        nuclear_median: coverage.nuclear_median as f32,
+        mito_median: coverage.mito_median as f32,
+    };
 
-    let mut pairs_tsv = String::from(
-        "pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\trnuc\trmito\ts_nuc\ts_mito\tscore_numt\tscore_nimt\n"
-    );
-    let mut class_tsv = String::from("pair_id\tcall\tconfidence\treason_codes\n");
-
-    for p in pairs {
-        let a = clamp01(p.aln_ident);
-        let l = scale_len(p.aln_len);
-        let base = w.w_a * a + w.w_l * l;
-
-        let (d_n_loc, d_m_loc) = depth_map
-            .get(p.pair_id.as_str())
-            .copied()
-            .unwrap_or((0.0, 0.0));
-        // normalized local medians
-        let rnuc = if dn_med > 0.0 { d_n_loc / dn_med } else { 0.0 };
-        let rmito = if dm_med > 0.0 { d_m_loc / dm_med } else { 0.0 };
-
-        // Depth consistency terms (favor ~1.0)
-        let d_numt = clamp01(1.0 - (rnuc - 1.0).abs());
-        let d_nimt = clamp01(1.0 - (rmito - 1.0).abs());
-
-        // Spanning
-        let (s_nuc, s_mito) = span_map
-            .get(p.pair_id.as_str())
-            .copied()
-            .unwrap_or((0.0, 0.0));
-
-        // Contrast boosters (signed): + favors NUMT, − favors NIMT
-        let eps = 1e-3_f32;
-        let log2_ratio = ((rnuc + eps) / (rmito + eps)).ln() / std::f32::consts::LN_2;
-        let depth_contrast = (1.25 * log2_ratio).tanh(); // (-1..1)
-        let span_contrast = s_nuc - s_mito; // (-1..1)
-
-        // Build scores
-        let pro_numt = w.w_d * d_numt + w.w_s * s_nuc;
-        let pro_nimt = w.w_d * d_nimt + w.w_s * s_mito;
-        let pen_numt = w.w_d * d_nimt + w.w_s * s_mito;
-        let pen_nimt = w.w_d * d_numt + w.w_s * s_nuc;
-        let boost_numt = w.w_d * depth_contrast + w.w_s * span_contrast;
-        let boost_nimt = -w.w_d * depth_contrast - w.w_s * span_contrast;
-
-        let score_numt = base + pro_numt - pen_numt + boost_numt;
-        let score_nimt = base + pro_nimt - pen_nimt + boost_nimt;
-
-        let diff = score_numt - score_nimt;
-        let delta = diff.abs();
-        let call = if diff >= params.call_threshold {
-            Call::NUMT
-        } else if -diff >= params.call_threshold {
-            Call::NIMT
+    pairs
+        .iter()
+        .map(|p| {
+            let depth = depth_map
+                .get(p.pair_id.as_str())
+                .copied()
+                .unwrap_or((0.0, 0.0));
+            let span = span_map
+                .get(p.pair_id.as_str())
+                .copied()
+                .unwrap_or((0.0, 0.0));
+            let read_len = read_len_map
+                .get(p.pair_id.as_str())
+                .copied()
+                .unwrap_or((0.0, 0.0));
+            let read_div = read_div_map
+                .get(p.pair_id.as_str())
+                .copied()
+                .unwrap_or_default();
+            let ev = PairEvidence {
+                depth,
+                span,
+                read_len,
+                read_div,
+                span_reads: spans.reads.get(&p.pair_id).copied(),
+                junction_clips: spans
+                    .junction_clips
+                    .get(&p.pair_id)
+                    .copied()
+                    .unwrap_or_default(),
+                clamped_window: spans.clamped_window.get(&p.pair_id).copied(),
+                invalid_window: spans.invalid_window.get(&p.pair_id).copied(),
+            };
+            let g = gaps.stats(&p.nuc_contig, p.nuc_start, p.nuc_end);
+            let c = control_regions.overlap(&p.mito_contig, p.mito_start, p.mito_end);
+            score_pair(p, &ev, g, c, baselines, w, params)
+        })
+        .collect()
+}
+
+pub const PAIRS_TSV_HEADER: &str = "pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\trnuc\trmito\ts_nuc\ts_mito\tscore_numt\tscore_nimt\test_copy_number_nuc\tstrand\tread_len_nuc\tread_len_mito\tnuc_n_bases\tnuc_longest_n_run\tsoftmask_frac_nuc\tmito_molecule_type\tin_control_region\tn_support_alignments\tread_div_nuc\tread_div_mito\taln_ident_kind\tnuc_start_ci\tnuc_end_ci\treciprocal\tparent_id\trelated_pairs\trbh\tlow_mapq_nuc\tlow_mapq_mito\tjunction_clips_start\tjunction_clips_end";
+const OPTIONAL_PAIRS_COLUMNS: &[&str] = &[
+    "est_copy_number_nuc",
+    "strand",
+    "read_len_nuc",
+    "read_len_mito",
+    "nuc_n_bases",
+    "nuc_longest_n_run",
+    "softmask_frac_nuc",
+    "mito_molecule_type",
+    "in_control_region",
+    "n_support_alignments",
+    "read_div_nuc",
+    "read_div_mito",
+    "aln_ident_kind",
+    "nuc_start_ci",
+    "nuc_end_ci",
+    "reciprocal",
+    "parent_id",
+    "related_pairs",
+    "rbh",
+    "low_mapq_nuc",
+    "low_mapq_mito",
+    "junction_clips_start",
+    "junction_clips_end",
+];
+pub const CLASSIFICATION_TSV_HEADER: &str =
+    "pair_id\tcall\tconfidence\treason_codes\tevidence_summary";
+
+/// A read divergence for pairs.tsv; `.` when unknown.
+fn fmt_div(d: Option<f32>) -> String {
+    d.map_or_else(|| ".".to_string(), |d| format!("{d:.5}"))
+}
+
+/// One pairs.tsv row (no trailing newline).
+pub fn pairs_tsv_row(sp: &ScoredPair) -> String {
+    let (p, f, s) = (&sp.locus, &sp.features, &sp.score);
+    format!(
+        "{pid}\t{nc}\t{ns}\t{ne}\t{mc}\t{ms}\t{me}\t{al}\t{ai:.4}\t{rn:.3}\t{rm:.3}\t{sn:.3}\t{sm:.3}\t{snmt:.4}\t{simt:.4}\t{cn:.1}\t{st}\t{rln:.0}\t{rlm:.0}\t{nn}\t{nr}\t{sm_frac:.3}\t{mt}\t{cr}\t{nsa}\t{rdn}\t{rdm}\t{aik}\t{nsci}\t{neci}\t{rec}\t{par}\t{rel}\t{rbh}\t{lmn}\t{lmm}\t{jcs}\t{jce}",
+        pid = p.pair_id,
+        nc = p.nuc_contig, ns = p.nuc_start, ne = p.nuc_end,
+        mc = p.mito_contig, ms = p.mito_start, me = p.mito_end,
+        al = p.aln_len, ai = f.aln_ident,
+        rn = f.rnuc, rm = f.rmito,
+        sn = f.s_nuc, sm = f.s_mito,
+        snmt = s.score_numt, simt = s.score_nimt,
+        cn = s.est_copy_number_nuc,
+        st = p.strand.as_char(),
+        rln = f.read_len_nuc, rlm = f.read_len_mito,
+        nn = f.nuc_n_bases, nr = f.nuc_longest_n_run,
+        sm_frac = f.softmask_frac_nuc,
+        mt = sp.mito_molecule_type,
+        cr = f.control_region == ControlRegionOverlap::Full,
+        nsa = p.n_support_alignments,
+        rdn = fmt_div(f.read_div_nuc),
+        rdm = fmt_div(f.read_div_mito),
+        aik = p.aln_ident_kind.as_str(),
+        nsci = p.nuc_start_ci,
+        neci = p.nuc_end_ci,
+        rec = p.reciprocal,
+        par = p.parent_id.as_deref().unwrap_or("."),
+        rel = if sp.related_pairs.is_empty() {
+            ".".to_string()
         } else {
-            Call::Ambiguous
+            sp.related_pairs.join(",")
+        },
+        rbh = crate::pairing::fmt_rbh(p.rbh),
+        lmn = f.low_mapq_nuc,
+        lmm = f.low_mapq_mito,
+        jcs = f.junction_clips_start,
+        jce = f.junction_clips_end
+    )
+}
+
+/// One classification.tsv row (no trailing newline).
+pub fn classification_tsv_row(sp: &ScoredPair) -> String {
+    format!(
+        "{pid}\t{call}\t{conf:.4}\t{reason}\t{summary}",
+        pid = sp.locus.pair_id,
+        call = sp.score.call.as_str(),
+        conf = sp.score.confidence,
+        reason = sp.score.reason_codes.join(","),
+        summary = sp.features.evidence_summary()
+    )
+}
+
+/// Render pairs.tsv with the selected columns.
+pub fn pairs_tsv(scored: &[ScoredPair], cols: &PairsColumns) -> String {
+    let mut t = format!("{}\n", cols.header());
+    for sp in scored {
+        let _ = writeln!(&mut t, "{}", cols.row(sp));
+    }
+    t
+}
+
+/// Render classification.tsv.
+pub fn classification_tsv(scored: &[ScoredPair]) -> String {
+    let mut t = format!("{CLASSIFICATION_TSV_HEADER}\n");
+    for sp in scored {
+        let _ = writeln!(&mut t, "{}", classification_tsv_row(sp));
+    }
+    t
+}
+
+/// One parsed pairs.tsv row.
+#[derive(Debug, Clone)]
+pub struct PairsTsvRow {
+    pub locus: PairedLocus,
+    pub features: PairFeatures,
+    pub score_numt: f32,
+    pub score_nimt: f32,
+}
+
+/// Read pairs.tsv back. Columns are located by header name, so extra or
+/// reordered columns are tolerated.
+pub fn read_pairs_tsv(path: &Path) -> Result<Vec<PairsTsvRow>> {
+    let txt = fs_err::read_to_string(path)?;
+    parse_pairs_tsv_str(&txt).with_context(|| format!("parse {}", path.display()))
+}
+
+/// The pairs of a run directory from its pairs.tsv, or from results.json
+/// when pairs.tsv was narrowed with `--columns` and lacks a column needed here.
+pub fn read_run_pairs(run_dir: &Path) -> Result<Vec<PairsTsvRow>> {
+    let pairs_p = run_dir.join("pairs.tsv");
+    let header = fs_err::read_to_string(&pairs_p)?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let narrowed = PAIRS_TSV_HEADER
+        .split('\t')
+        .filter(|h| !OPTIONAL_PAIRS_COLUMNS.contains(h))
+        .any(|h| !header.split('\t').any(|c| c == h));
+    if !narrowed || !run_dir.join("results.json").exists() {
+        return read_pairs_tsv(&pairs_p);
+    }
+    log::info!(
+        "{} does not have every column (--columns?); reading the pairs from results.json",
+        pairs_p.display()
+    );
+    let results = ScoringResults::load_from(run_dir)
+        .with_context(|| format!("parse {}", run_dir.join("results.json").display()))?;
+    Ok(results
+        .pairs
+        .into_iter()
+        .map(|sp| PairsTsvRow {
+            locus: sp.locus,
+            features: sp.features,
+            score_numt: sp.score.score_numt,
+            score_nimt: sp.score.score_nimt,
+        })
+        .collect())
+}
+
+pub fn parse_pairs_tsv_str(s: &str) -> Result<Vec<PairsTsvRow>> {
+    let mut lines = s.lines();
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| anyhow!("empty pairs.tsv"))?
+        .split('\t')
+        .collect();
+    let col = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| anyhow!("pairs.tsv has no '{name}' column"))
+    };
+    // Newer columns, absent from older runs: est_copy_number_nuc is derived from
+    // rnuc, and a missing strand means `+`
+    let idx: Vec<usize> = PAIRS_TSV_HEADER
+        .split('\t')
+        .filter(|h| !OPTIONAL_PAIRS_COLUMNS.contains(h))
+        .map(col)
+        .collect::<Result<_>>()?;
+    let locus_cols = LocusColumns::locate(&col)?;
+    let read_len_cols = (col("read_len_nuc").ok(), col("read_len_mito").ok());
+    let gap_cols = (col("nuc_n_bases").ok(), col("nuc_longest_n_run").ok());
+    let softmask_col = col("softmask_frac_nuc").ok();
+    let control_col = col("in_control_region").ok();
+    let read_div_cols = (col("read_div_nuc").ok(), col("read_div_mito").ok());
+    let low_mapq_cols = (col("low_mapq_nuc").ok(), col("low_mapq_mito").ok());
+    let junction_cols = (
+        col("junction_clips_start").ok(),
+        col("junction_clips_end").ok(),
+    );
+
+    let mut out = Vec::new();
+    for (i, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let f: Vec<&str> = line.split('\t').collect();
+        let get = |k: usize| -> Result<&str> {
+            f.get(idx[k])
+                .copied()
+                .ok_or_else(|| anyhow!("line {}: too few columns", i + 2))
+        };
+        let num = |k: usize| -> Result<f32> {
+            let v = get(k)?;
+            v.parse()
+                .map_err(|_| anyhow!("line {}: bad number '{v}'", i + 2))
+        };
+        // read divergence: absent or `.` → unknown
+        let opt_div = |c: Option<usize>| -> Result<Option<f32>> {
+            match c.and_then(|c| f.get(c)) {
+                Some(&".") | None => Ok(None),
+                Some(v) => v
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow!("line {}: bad read divergence '{v}'", i + 2)),
+            }
+        };
+        // optional column by absolute index: absent → 0
+        let opt_num = |c: Option<usize>| -> Result<f32> {
+            match c.and_then(|c| f.get(c)) {
+                Some(v) => v
+                    .parse()
+                    .map_err(|_| anyhow!("line {}: bad number '{v}'", i + 2)),
+                None => Ok(0.0),
+            }
+        };
+        let locus = locus_cols.parse(&f, i + 2)?;
+        let features = PairFeatures {
+            aln_ident: locus.aln_ident,
+            aln_len: locus.aln_len,
+            rnuc: num(9)?,
+            rmito: num(10)?,
+            s_nuc: num(11)?,
+            s_mito: num(12)?,
+            read_len_nuc: opt_num(read_len_cols.0)?,
+            read_len_mito: opt_num(read_len_cols.1)?,
+            read_div_nuc: opt_div(read_div_cols.0)?,
+            read_div_mito: opt_div(read_div_cols.1)?,
+            low_mapq_nuc: opt_num(low_mapq_cols.0)? as u32,
+            low_mapq_mito: opt_num(low_mapq_cols.1)? as u32,
+            junction_clips_start: opt_num(junction_cols.0)? as u32,
+            junction_clips_end: opt_num(junction_cols.1)? as u32,
+            nuc_n_bases: opt_num(gap_cols.0)? as u32,
+            nuc_longest_n_run: opt_num(gap_cols.1)? as u32,
+            softmask_frac_nuc: opt_num(softmask_col)?,
+            // the column only says whether the pair is inside; partial overlaps read back as outside
+            control_region: match control_col.and_then(|c| f.get(c)) {
+                Some(&"true") => ControlRegionOverlap::Full,
+                Some(&"false") | None => ControlRegionOverlap::None,
+                Some(v) => return Err(anyhow!("line {}: bad in_control_region '{v}'", i + 2)),
+            },
+            // not a pairs.tsv column; results.json keeps it
+            clamped_window: None,
+            invalid_window: None,
+        };
+        out.push(PairsTsvRow {
+            locus,
+            features,
+            score_numt: num(13)?,
+            score_nimt: num(14)?,
+        });
+    }
+    Ok(out)
+}
+
+/// Where a locus table keeps its loci: the pairs.tsv columns up to
+/// `aln_ident` are required, the newer locus columns are read when present.
+struct LocusColumns {
+    idx: Vec<usize>,
+    strand: Option<usize>,
+    support: Option<usize>,
+    ident_kind: Option<usize>,
+    ci: (Option<usize>, Option<usize>),
+    reciprocal: Option<usize>,
+    parent: Option<usize>,
+    rbh: Option<usize>,
+}
+
+impl LocusColumns {
+    fn locate(col: &dyn Fn(&str) -> Result<usize>) -> Result<Self> {
+        Ok(Self {
+            idx: PAIRS_TSV_HEADER
+                .split('\t')
+                .take(9)
+                .map(col)
+                .collect::<Result<_>>()?,
+            strand: col("strand").ok(),
+            support: col("n_support_alignments").ok(),
+            ident_kind: col("aln_ident_kind").ok(),
+            ci: (col("nuc_start_ci").ok(), col("nuc_end_ci").ok()),
+            reciprocal: col("reciprocal").ok(),
+            parent: col("parent_id").ok(),
+            rbh: col("rbh").ok(),
+        })
+    }
+
+    /// The locus of row `f`, line `line` of the file.
+    fn parse(&self, f: &[&str], line: usize) -> Result<PairedLocus> {
+        let get = |k: usize| -> Result<&str> {
+            f.get(self.idx[k])
+                .copied()
+                .ok_or_else(|| anyhow!("line {line}: too few columns"))
         };
+        let int = |k: usize| -> Result<u32> {
+            let v = get(k)?;
+            v.parse()
+                .map_err(|_| anyhow!("line {line}: bad integer '{v}'"))
+        };
+        // boundary CI: absent → 0
+        let opt_ci = |c: Option<usize>| -> Result<u32> {
+            match c.and_then(|c| f.get(c)) {
+                Some(v) => v
+                    .parse()
+                    .map_err(|_| anyhow!("line {line}: bad boundary CI '{v}'")),
+                None => Ok(0),
+            }
+        };
+        Ok(PairedLocus {
+            pair_id: get(0)?.to_string(),
+            nuc_contig: get(1)?.to_string(),
+            nuc_start: int(2)?,
+            nuc_end: int(3)?,
+            mito_contig: get(4)?.to_string(),
+            mito_start: int(5)?,
+            mito_end: int(6)?,
+            aln_len: int(7)?,
+            aln_ident: {
+                let v = get(8)?;
+                v.parse()
+                    .map_err(|_| anyhow!("line {line}: bad number '{v}'"))?
+            },
+            strand: match self.strand.and_then(|c| f.get(c)) {
+                Some(&"-") => Strand::Reverse,
+                Some(&"+") | None => Strand::Forward,
+                Some(v) => return Err(anyhow!("line {line}: bad strand '{v}'")),
+            },
+            n_support_alignments: match self.support.and_then(|c| f.get(c)) {
+                Some(v) => v
+                    .parse()
+                    .map_err(|_| anyhow!("line {line}: bad n_support_alignments '{v}'"))?,
+                None => 1,
+            },
+            aln_ident_kind: match self.ident_kind.and_then(|c| f.get(c)) {
+                Some(v) => IdentityKind::parse(v)
+                    .ok_or_else(|| anyhow!("line {line}: bad aln_ident_kind '{v}'"))?,
+                None => IdentityKind::Blast,
+            },
+            nuc_start_ci: opt_ci(self.ci.0)?,
+            nuc_end_ci: opt_ci(self.ci.1)?,
+            reciprocal: match self.reciprocal.and_then(|c| f.get(c)) {
+                Some(&"true") | None => true,
+                Some(&"false") => false,
+                Some(v) => return Err(anyhow!("line {line}: bad reciprocal '{v}'")),
+            },
+            parent_id: match self.parent.and_then(|c| f.get(c)) {
+                Some(&".") | Some(&"") | None => None,
+                Some(v) => Some(v.to_string()),
+            },
+            rbh: match self.rbh.and_then(|c| f.get(c)) {
+                Some(&".") | Some(&"") | None => None,
+                Some(&"true") => Some(true),
+                Some(&"false") => Some(false),
+                Some(v) => return Err(anyhow!("line {line}: bad rbh '{v}'")),
+            },
+        })
+    }
+}
 
-        let (call_str, reason) = call.as_str_and_reason();
+/// Read a hand-edited pair list (`reuse --pairs-from`): pairs.tsv,
+/// candidates.tsv, or any table with their locus columns, located by header
+/// as in pairs.tsv. Only the loci are taken; evidence and scores are
+/// recomputed. Every row must lie within `lengths` and have its own pair_id.
+pub fn read_pair_list(path: &Path, lengths: &ContigLengths) -> Result<Vec<PairedLocus>> {
+    let txt = fs_err::read_to_string(path)?;
+    parse_pair_list_str(&txt, lengths).with_context(|| format!("parse {}", path.display()))
+}
 
-        let _ = writeln!(
-            &mut pairs_tsv,
-            "{pid}\t{nc}\t{ns}\t{ne}\t{mc}\t{ms}\t{me}\t{al}\t{ai:.4}\t{rn:.3}\t{rm:.3}\t{sn:.3}\t{sm:.3}\t{snmt:.4}\t{simt:.4}",
-            pid = p.pair_id,
-            nc = p.nuc_contig, ns = p.nuc_start, ne = p.nuc_end,
-            mc = p.mito_contig, ms = p.mito_start, me = p.mito_end,
-            al = p.aln_len, ai = a,
-            rn = rnuc, rm = rmito,
-            sn = s_nuc, sm = s_mito,
-            snmt = score_numt, simt = score_nimt
-        );
-        let _ = writeln!(
-            &mut class_tsv,
-            "{pid}\t{call}\t{conf:.4}\t{reason}",
-            pid = p.pair_id,
-            call = call_str,
-            conf = delta,
-            reason = reason
-        );
+fn parse_pair_list_str(s: &str, lengths: &ContigLengths) -> Result<Vec<PairedLocus>> {
+    let mut lines = s.lines();
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| anyhow!("empty pair list"))?
+        .split('\t')
+        .collect();
+    let col = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| anyhow!("the pair list has no '{name}' column"))
+    };
+    let cols = LocusColumns::locate(&col)?;
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for (i, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let f: Vec<&str> = line.split('\t').collect();
+        let p = cols.parse(&f, i + 2)?;
+        if p.nuc_start >= p.nuc_end || p.mito_start >= p.mito_end {
+            bail!("line {}: {}: start is not before end", i + 2, p.pair_id);
+        }
+        lengths
+            .check(&p)
+            .map_err(|e| anyhow!("line {}: {e}", i + 2))?;
+        if !seen.insert(p.pair_id.clone()) {
+            bail!("line {}: {}: pair_id listed twice", i + 2, p.pair_id);
+        }
+        out.push(p);
+    }
+    if out.is_empty() {
+        bail!("no pairs listed");
     }
+    Ok(out)
+}
 
-    Ok((pairs_tsv, class_tsv))
+pub fn classify_pairs(
+    pairs: &[PairedLocus],
+    coverage: &CoverageSummary,
+    spans: &SpanSummary,
+    gaps: &GapIndex,
+    control_regions: &ControlRegions,
+    w: Weights,
+    params: ClassifyParams,
+) -> Result<(String, String)> {
+    let scored = score_pairs(pairs, coverage, spans, gaps, control_regions, w, params);
+    Ok((
+        pairs_tsv(&scored, &PairsColumns::default()),
+        classification_tsv(&scored),
+    ))
 }
 
 #[cfg(test)]
@@ -142,6 +1027,79 @@ mod tests {
     use super::*;
     use crate::model::{CoverageSummary, SpanSummary};
 
+    #[test]
+    fn evidence_summary_formatting() {
+        // depth ratios: one decimal, whole numbers from 10×, capped
+        let r: Vec<String> = [
+            0.0,
+            0.04,
+            1.0,
+            9.94,
+            9.96,
+            37.2,
+            999.4,
+            999.6,
+            1e6,
+            f32::NAN,
+        ]
+        .into_iter()
+        .map(fmt_ratio)
+        .collect();
+        assert_eq!(
+            r,
+            ["0.0x", "0.0x", "1.0x", "9.9x", "10x", "37x", "999x", ">999x", ">999x", "?x"]
+        );
+        // spanning fractions: whole percentages, never a false 0% or 100%
+        let s: Vec<String> = [0.0, 0.001, 0.005, 0.824, 0.996, 1.0, 1.7]
+            .into_iter()
+            .map(fmt_pct)
+            .collect();
+        assert_eq!(s, ["0%", "<1%", "1%", "82%", ">99%", "100%", "100%"]);
+        // lengths: bp, kb with one decimal below 10 kb, then Mb
+        let l: Vec<String> = [
+            0, 999, 1000, 4_249, 9_949, 9_960, 123_456, 999_600, 2_345_678,
+        ]
+        .into_iter()
+        .map(fmt_len)
+        .collect();
+        assert_eq!(
+            l,
+            ["0bp", "999bp", "1.0kb", "4.2kb", "9.9kb", "10kb", "123kb", "1.0Mb", "2.3Mb"]
+        );
+        let i: Vec<String> = [0.9612, 0.8, 0.99994, 1.0]
+            .into_iter()
+            .map(fmt_identity)
+            .collect();
+        assert_eq!(i, ["96.1%", "80.0%", ">99.9%", "100.0%"]);
+
+        let f = PairFeatures {
+            aln_ident: 0.961,
+            aln_len: 4_213,
+            rnuc: 1.02,
+            rmito: 37.4,
+            s_nuc: 0.82,
+            s_mito: 0.031,
+            read_len_nuc: 0.0,
+            read_len_mito: 0.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        assert_eq!(
+            f.evidence_summary(),
+            "D:nuc=1.0x,mito=37x|S:nuc=82%,mito=3%|len=4.2kb|id=96.1%"
+        );
+    }
+
     #[test]
     fn favors_numt_when_nuclear_support_strong() {
         let pairs = vec![PairedLocus {
@@ -154,24 +1112,835 @@ mod tests {
             mito_end: 150,
             aln_len: 5000,
             aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
         }];
         let cov = CoverageSummary {
             nuclear_median: 30.0,
             mito_median: 30.0,
+            loci_medians: None,
             per_pair: [("P1".into(), (30.0, 10.0))].into_iter().collect(), // rnuc=1.0, rmito=0.33
+            params: None,
         };
         let spans = SpanSummary {
             per_pair: [("P1".into(), (0.8, 0.1))].into_iter().collect(),
+            read_len: [("P1".into(), (14_800.0, 0.0))].into_iter().collect(),
+            read_div: HashMap::new(),
+            reads: HashMap::new(),
+            clamped_window: HashMap::new(),
+            invalid_window: HashMap::new(),
+            junction_clips: [("P1".into(), (3, 1))].into_iter().collect(),
+            params: None,
+            cost: HashMap::new(),
+            stage_cost: None,
         };
         let (pairs_tsv, class_tsv) = classify_pairs(
             &pairs,
             &cov,
             &spans,
+            &GapIndex::default(),
+            &ControlRegions::default(),
             Weights::default(),
             ClassifyParams::default(),
         )
         .unwrap();
         assert!(pairs_tsv.contains("score_numt"));
         assert!(class_tsv.contains("Likely_NUMT"));
+
+        let back = parse_pairs_tsv_str(&pairs_tsv).unwrap();
+        assert_eq!(back.len(), 1);
+        assert_eq!(back[0].locus.pair_id, "P1");
+        assert_eq!(back[0].locus.aln_len, 5000);
+        assert!((back[0].features.s_nuc - 0.8).abs() < 1e-6);
+        assert_eq!(back[0].locus.strand, Strand::Forward);
+        assert_eq!(back[0].features.read_len_nuc, 14_800.0);
+        assert!(
+            pairs_tsv.ends_with(
+                "\t+\t14800\t0\t0\t0\t0.000\tmito\tfalse\t1\t.\t.\tblast\t0\t0\ttrue\t.\t.\t.\t0\t0\t3\t1\n"
+            ),
+            "{pairs_tsv}"
+        );
+        assert_eq!(
+            (
+                back[0].features.junction_clips_start,
+                back[0].features.junction_clips_end
+            ),
+            (3, 1)
+        );
+
+        // junction reads are reported, and move the NUMT score only with w_j
+        let f = back[0].features;
+        let plain = score_features(&f, Weights::default(), ClassifyParams::default());
+        assert_eq!(plain.terms.junction, 0.0);
+        let w = Weights {
+            w_j: 0.5,
+            ..Weights::default()
+        };
+        let with_j = score_features(&f, w, ClassifyParams::default());
+        assert!(
+            (with_j.terms.junction - 0.25).abs() < 1e-6,
+            "{:?}",
+            with_j.terms
+        );
+        assert!((with_j.score_numt - plain.score_numt - 0.25).abs() < 1e-6);
+        assert_eq!(with_j.score_nimt, plain.score_nimt);
+
+        // strand round-trips, and older files without the newer columns read as
+        // `+` with unknown read lengths
+        let rev = pairs_tsv.replace("\t+\t", "\t-\t");
+        assert_eq!(
+            parse_pairs_tsv_str(&rev).unwrap()[0].locus.strand,
+            Strand::Reverse
+        );
+        let old: String = pairs_tsv
+            .lines()
+            .map(|l| {
+                format!(
+                    "{}\n",
+                    l.splitn(18, '\t').take(16).collect::<Vec<_>>().join("\t")
+                )
+            })
+            .collect();
+        let old = parse_pairs_tsv_str(&old).unwrap();
+        assert_eq!(old[0].locus.strand, Strand::Forward);
+        assert_eq!(old[0].features.read_len_nuc, 0.0);
+        assert_eq!(old[0].locus.n_support_alignments, 1);
+        let three = pairs_tsv.replace("\tfalse\t1\t", "\tfalse\t3\t");
+        assert_eq!(
+            parse_pairs_tsv_str(&three).unwrap()[0]
+                .locus
+                .n_support_alignments,
+            3
+        );
+        assert_eq!(old[0].features.read_div_nuc, None);
+
+        // read divergence: `.` without NM tags, five decimals with them
+        let div = pairs_tsv.replace("\t1\t.\t.\t", "\t1\t0.01234\t.\t");
+        let back = &parse_pairs_tsv_str(&div).unwrap()[0].features;
+        assert_eq!(
+            (back.read_div_nuc, back.read_div_mito),
+            (Some(0.01234), None)
+        );
+        assert_eq!(fmt_div(Some(0.012344)), "0.01234");
+
+        // the identity definition, BLAST in older files
+        assert_eq!(old[0].locus.aln_ident_kind, IdentityKind::Blast);
+        let gc = pairs_tsv.replace("\tblast\t", "\tgap_compressed\t");
+        assert_eq!(
+            parse_pairs_tsv_str(&gc).unwrap()[0].locus.aln_ident_kind,
+            IdentityKind::GapCompressed
+        );
+        let bad = pairs_tsv.replace("\tblast\t", "\tgap-compressed\t");
+        let e = parse_pairs_tsv_str(&bad).unwrap_err().to_string();
+        assert!(e.contains("bad aln_ident_kind"), "{e}");
+
+        // boundary CIs, 0 in older files
+        assert_eq!((old[0].locus.nuc_start_ci, old[0].locus.nuc_end_ci), (0, 0));
+        let ci = pairs_tsv.replace(
+            "\tblast\t0\t0\ttrue\t.\t.\t.\t0\t0\t3\t1\n",
+            "\tblast\t12\t340\n",
+        );
+        let back = &parse_pairs_tsv_str(&ci).unwrap()[0].locus;
+        assert_eq!((back.nuc_start_ci, back.nuc_end_ci), (12, 340));
+        let bad = pairs_tsv.replace(
+            "\tblast\t0\t0\ttrue\t.\t.\t.\t0\t0\t3\t1\n",
+            "\tblast\t12\t-3\n",
+        );
+        let e = parse_pairs_tsv_str(&bad).unwrap_err().to_string();
+        assert!(e.contains("bad boundary CI"), "{e}");
+    }
+
+    #[test]
+    fn pair_list_takes_locus_columns_and_names_bad_rows() {
+        let lengths = ContigLengths {
+            mito: [("m1".to_string(), 16_000)].into_iter().collect(),
+            nuclear: [("chr1".to_string(), 100_000)].into_iter().collect(),
+        };
+        // reordered locus columns with a curator's note, no strand or CIs
+        let list = "note\taln_ident\tpair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\n\
+                    kept\t0.97\tP1\tchr1\t1000\t2000\tm1\t100\t1100\t1000\n\
+                    \n\
+                    moved\t0.95\tP3\tchr1\t5500\t7000\tm1\t3000\t4500\t1500\n";
+        let loci = parse_pair_list_str(list, &lengths).unwrap();
+        let ids: Vec<&str> = loci.iter().map(|p| p.pair_id.as_str()).collect();
+        assert_eq!(ids, ["P1", "P3"]);
+        assert_eq!((loci[1].nuc_start, loci[1].nuc_end), (5500, 7000));
+        assert_eq!(loci[1].strand, Strand::Forward);
+        assert!(loci[1].reciprocal);
+
+        // errors name the line, and the pair once it is read
+        let bad = |row: &str| {
+            parse_pair_list_str(&format!("{list}{row}\n"), &lengths)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            bad("x\t0.9\tP4\tchr1\t99000\t100500\tm1\t0\t1500\t1500"),
+            "line 5: P4: ends at 100500, past the end of chr1 (100000 bp)"
+        );
+        assert_eq!(
+            bad("x\t0.9\tP4\tchrX\t1000\t2000\tm1\t0\t1000\t1000"),
+            "line 5: P4: chrX is not a contig of the nuclear assembly"
+        );
+        assert_eq!(
+            bad("x\t0.9\tP4\tchr1\t2000\t1000\tm1\t0\t1000\t1000"),
+            "line 5: P4: start is not before end"
+        );
+        assert_eq!(
+            bad("x\t0.9\tP1\tchr1\t8000\t9000\tm1\t0\t1000\t1000"),
+            "line 5: P1: pair_id listed twice"
+        );
+        assert_eq!(
+            bad("x\t0.9\tP4\tchr1\t1k\t2000\tm1\t0\t1000\t1000"),
+            "line 5: bad integer '1k'"
+        );
+        let e = parse_pair_list_str("pair_id\tnuc_contig\n", &lengths)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("no 'nuc_start' column"), "{e}");
+        let header = list.lines().next().unwrap();
+        let e = parse_pair_list_str(header, &lengths)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(e, "no pairs listed");
+    }
+
+    #[test]
+    fn assembly_gaps_flagged_and_reported() {
+        let locus = |nuc_start: u32, nuc_end: u32| PairedLocus {
+            pair_id: "P1".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start,
+            nuc_end,
+            mito_contig: "m1".into(),
+            mito_start: 0,
+            mito_end: nuc_end - nuc_start,
+            aln_len: nuc_end - nuc_start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        // 99 bp run at 1000..1099, 100 bp run at 5000..5100, 3 x 50 bp at 8000..8300
+        let gaps = GapIndex::from_runs(
+            [(
+                "chr1".to_string(),
+                vec![
+                    (1000, 1099),
+                    (5000, 5100),
+                    (8000, 8050),
+                    (8100, 8150),
+                    (8200, 8250),
+                ],
+            )]
+            .into(),
+        );
+        let ev = PairEvidence {
+            depth: (30.0, 10.0),
+            span: (0.8, 0.1),
+            read_len: (0.0, 0.0),
+            read_div: Default::default(),
+            span_reads: None,
+            junction_clips: (0, 0),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let baselines = DepthBaselines {
+            nuclear_median: 30.0,
+            mito_median: 30.0,
+        };
+        let score = |s: u32, e: u32| {
+            let p = locus(s, e);
+            let g = gaps.stats(&p.nuc_contig, p.nuc_start, p.nuc_end);
+            score_pair(
+                &p,
+                &ev,
+                g,
+                ControlRegionOverlap::None,
+                baselines,
+                Weights::default(),
+                ClassifyParams::default(),
+            )
+        };
+        let flagged = |sp: &ScoredPair| {
+            sp.score
+                .reason_codes
+                .iter()
+                .any(|r| r == SPANS_ASSEMBLY_GAP)
+        };
+
+        // 99 bp of N in 2 kb: under both thresholds
+        let sp = score(0, 2000);
+        assert_eq!(
+            (sp.features.nuc_n_bases, sp.features.nuc_longest_n_run),
+            (99, 99)
+        );
+        assert!(!flagged(&sp));
+        // a 100 bp run, at the very end of the locus
+        assert!(flagged(&score(4000, 5100)));
+        // only part of that run inside the locus, starting it
+        let sp = score(5050, 6000);
+        assert_eq!(sp.features.nuc_longest_n_run, 50);
+        assert!(!flagged(&sp));
+        // short runs adding up to > 10%
+        let sp = score(7500, 8500);
+        assert_eq!(sp.features.nuc_n_bases, 150);
+        assert!(flagged(&sp));
+        assert!(!flagged(&score(7000, 9000)), "150 / 2000 is 7.5%");
+        // the call itself is untouched
+        assert_eq!(score(4000, 5100).score.call, score(0, 2000).score.call);
+
+        let tsv = pairs_tsv(&[score(4000, 5100)], &PairsColumns::default());
+        assert!(
+            tsv.ends_with(
+                "\t100\t100\t0.000\tmito\tfalse\t1\t.\t.\tblast\t0\t0\ttrue\t.\t.\t.\t0\t0\t0\t0\n"
+            ),
+            "{tsv}"
+        );
+        let back = parse_pairs_tsv_str(&tsv).unwrap();
+        assert_eq!(back[0].features.nuc_n_bases, 100);
+        assert_eq!(back[0].features.nuc_longest_n_run, 100);
+    }
+
+    #[test]
+    fn softmasked_fraction_reported_and_optionally_ambiguous() {
+        let p = PairedLocus {
+            pair_id: "P1".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: 1_000,
+            nuc_end: 2_000,
+            mito_contig: "m1".into(),
+            mito_start: 0,
+            mito_end: 1_000,
+            aln_len: 1_000,
+            aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        // 500 of the locus' 1000 bp masked, in runs reaching past both ends
+        let gaps = GapIndex::default().with_masked(
+            [(
+                "chr1".to_string(),
+                vec![(900, 1_200), (1_500, 1_600), (1_800, 2_500)],
+            )]
+            .into(),
+        );
+        let g = gaps.stats("chr1", p.nuc_start, p.nuc_end);
+        assert_eq!((g.masked_bases, g.n_bases), (500, 0));
+        // NUMT-like evidence
+        let ev = PairEvidence {
+            depth: (30.0, 10.0),
+            span: (0.8, 0.1),
+            read_len: (0.0, 0.0),
+            read_div: Default::default(),
+            span_reads: None,
+            junction_clips: (0, 0),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let baselines = DepthBaselines {
+            nuclear_median: 30.0,
+            mito_median: 30.0,
+        };
+        let score = |max_softmask_frac| {
+            let params = ClassifyParams {
+                max_softmask_frac,
+                ..ClassifyParams::default()
+            };
+            score_pair(
+                &p,
+                &ev,
+                g,
+                ControlRegionOverlap::None,
+                baselines,
+                Weights::default(),
+                params,
+            )
+        };
+        let masked = |sp: &ScoredPair| sp.score.reason_codes.iter().any(|r| r == SOFTMASKED_LOCUS);
+
+        let sp = score(None);
+        assert_eq!(sp.features.softmask_frac_nuc, 0.5);
+        assert_eq!(sp.score.call, Call::NUMT);
+        assert!(!masked(&sp));
+        // at the threshold the locus stays; above it, Ambiguous with the reason
+        assert!(!masked(&score(Some(0.5))));
+        let sp = score(Some(0.4));
+        assert!(masked(&sp));
+        assert_eq!(sp.score.call, Call::Ambiguous);
+        assert!(!sp.score.high_confidence);
+        assert_eq!(sp.score.reason_codes[0], "score_difference");
+
+        let tsv = pairs_tsv(&[sp], &PairsColumns::default());
+        assert!(
+            tsv.ends_with(
+                "\t0\t0\t0.500\tmito\tfalse\t1\t.\t.\tblast\t0\t0\ttrue\t.\t.\t.\t0\t0\t0\t0\n"
+            ),
+            "{tsv}"
+        );
+        assert_eq!(
+            parse_pairs_tsv_str(&tsv).unwrap()[0]
+                .features
+                .softmask_frac_nuc,
+            0.5
+        );
+    }
+
+    #[test]
+    fn delta_terms_sum_to_delta() {
+        let f = PairFeatures {
+            aln_ident: 0.97,
+            aln_len: 3000,
+            rnuc: 1.1,
+            rmito: 0.2,
+            s_nuc: 0.7,
+            s_mito: 0.05,
+            read_len_nuc: 0.0,
+            read_len_mito: 0.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let s = score_features(&f, Weights::default(), ClassifyParams::default());
+        let t = s.terms;
+        let sum = t.depth_consistency + t.depth_contrast + t.span;
+        assert!((sum - s.delta).abs() < 1e-5, "{sum} vs {}", s.delta);
+        assert!((s.score_numt - s.score_nimt - s.delta).abs() < 1e-6);
+        assert_eq!(s.call, Call::NUMT);
+        assert!(s.high_confidence);
+    }
+
+    #[test]
+    fn shared_support_is_not_weak_evidence() {
+        let feats = |rnuc, rmito, s_nuc, s_mito| PairFeatures {
+            aln_ident: 0.99,
+            aln_len: 3_000,
+            rnuc,
+            rmito,
+            s_nuc,
+            s_mito,
+            read_len_nuc: 0.0,
+            read_len_mito: 0.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let call = |f: PairFeatures, params| score_features(&f, Weights::default(), params);
+        let params = ClassifyParams::default();
+
+        // spanned and single-copy on both sides: the scores cancel, but not for lack of reads
+        let s = call(feats(1.0, 0.95, 0.75, 0.8), params);
+        assert_eq!(s.call, Call::SharedSupport);
+        assert_eq!(s.reason_codes[0], "both_sides_supported");
+        assert!(!s.high_confidence);
+        // no reads spanning either side: Ambiguous by weak evidence
+        let s = call(feats(1.0, 1.0, 0.05, 0.1), params);
+        assert_eq!(s.call, Call::Ambiguous);
+        assert_eq!(s.reason_codes[0], "delta_below_threshold");
+        // one side too sparse, or a depth off single-copy
+        assert_eq!(
+            call(feats(1.0, 1.0, 0.55, 0.45), params).call,
+            Call::Ambiguous
+        );
+        assert_eq!(
+            call(feats(1.0, 1.3, 0.8, 0.8), params).call,
+            Call::Ambiguous
+        );
+        // a decided call stays decided
+        assert_eq!(call(feats(1.0, 0.8, 0.95, 0.5), params).call, Call::NUMT);
+        // thresholds are settings, and span evidence is required
+        let loose = ClassifyParams {
+            shared_min_span: 0.3,
+            shared_depth_tol: 0.35,
+            ..params
+        };
+        assert_eq!(
+            call(feats(1.0, 1.3, 0.55, 0.45), loose).call,
+            Call::SharedSupport
+        );
+        let no_span = ClassifyParams {
+            span_evidence_disabled: true,
+            ..params
+        };
+        assert_eq!(
+            call(feats(1.0, 0.95, 0.75, 0.8), no_span).call,
+            Call::Ambiguous
+        );
+        assert_eq!(Call::SharedSupport.as_str(), "Shared_Support");
+        assert_eq!(
+            serde_json::to_string(&Call::SharedSupport).unwrap(),
+            "\"Shared_Support\""
+        );
+    }
+
+    #[test]
+    fn short_reads_flag_and_scale_span_weight() {
+        let feats = |read_len_nuc: f32, read_len_mito: f32| PairFeatures {
+            aln_ident: 0.97,
+            aln_len: 3000,
+            rnuc: 1.1,
+            rmito: 0.2,
+            s_nuc: 0.4,
+            s_mito: 0.05,
+            read_len_nuc,
+            read_len_mito,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let params = ClassifyParams::default();
+        assert_eq!(params.span_window, 500);
+        let flagged = |f: &PairFeatures, p: ClassifyParams| {
+            score_features(f, Weights::default(), p)
+                .reason_codes
+                .contains(&READS_SHORTER_THAN_WINDOW.to_string())
+        };
+
+        // unknown lengths, or reads at least as long as the window: no trigger
+        for (n, m) in [(0.0, 0.0), (500.0, 0.0), (15_000.0, 9_000.0)] {
+            assert_eq!(feats(n, m).read_len_factor(500), None, "{n}/{m}");
+            assert!(!flagged(&feats(n, m), params));
+        }
+        // the shorter known side sets the factor
+        assert_eq!(feats(250.0, 0.0).read_len_factor(500), Some(0.5));
+        assert_eq!(feats(15_000.0, 100.0).read_len_factor(500), Some(0.2));
+
+        let f = feats(250.0, 12_000.0);
+        let plain = score_features(&f, Weights::default(), params);
+        assert!(flagged(&f, params));
+        let scaling = ClassifyParams {
+            span_read_len_scaling: true,
+            ..params
+        };
+        let scaled = score_features(&f, Weights::default(), scaling);
+        assert!(flagged(&f, scaling));
+        assert!((scaled.terms.span - 0.5 * plain.terms.span).abs() < 1e-6);
+        assert_eq!(scaled.terms.depth_contrast, plain.terms.depth_contrast);
+        // scaling only ever applies when triggered
+        let long = feats(15_000.0, 12_000.0);
+        assert_eq!(
+            score_features(&long, Weights::default(), scaling).delta,
+            score_features(&long, Weights::default(), params).delta
+        );
+    }
+
+    #[test]
+    fn disabled_span_evidence_leaves_depth_alone() {
+        // spans say NUMT, depth mildly says NIMT
+        let f = PairFeatures {
+            aln_ident: 0.97,
+            aln_len: 3000,
+            rnuc: 0.8,
+            rmito: 1.0,
+            s_nuc: 0.9,
+            s_mito: 0.0,
+            read_len_nuc: 300.0,
+            read_len_mito: 300.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let params = ClassifyParams::default();
+        let disabled = ClassifyParams {
+            span_evidence_disabled: true,
+            span_read_len_scaling: true,
+            ..params
+        };
+        let on = score_features(&f, Weights::default(), params);
+        let off = score_features(&f, Weights::default(), disabled);
+        assert_eq!(on.call, Call::NUMT);
+        assert_eq!(off.terms.span, 0.0);
+        assert_eq!(off.terms.depth_contrast, on.terms.depth_contrast);
+        assert!(off.delta < 0.0, "{off:?}");
+        assert!(off
+            .reason_codes
+            .contains(&SPAN_EVIDENCE_DISABLED.to_string()));
+        assert!(!on
+            .reason_codes
+            .contains(&SPAN_EVIDENCE_DISABLED.to_string()));
+    }
+
+    #[test]
+    fn self_assembly_caveats_only_nimt_calls() {
+        let nimt = PairFeatures {
+            aln_ident: 0.97,
+            aln_len: 3000,
+            rnuc: 0.0,
+            rmito: 1.0,
+            s_nuc: 0.0,
+            s_mito: 0.9,
+            read_len_nuc: 15_000.0,
+            read_len_mito: 15_000.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: Default::default(),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let numt = PairFeatures {
+            rnuc: 1.0,
+            rmito: 0.0,
+            s_nuc: 0.9,
+            s_mito: 0.0,
+            ..nimt
+        };
+        let params = ClassifyParams {
+            self_assembly_caveat: true,
+            ..ClassifyParams::default()
+        };
+        let caveated = |f: &PairFeatures, params| {
+            let s = score_features(f, Weights::default(), params);
+            (
+                s.call,
+                s.reason_codes.contains(&SELF_ASSEMBLY_CAVEAT.to_string()),
+            )
+        };
+        assert_eq!(caveated(&nimt, params), (Call::NIMT, true));
+        assert_eq!(caveated(&numt, params), (Call::NUMT, false));
+        // the call itself is unchanged
+        assert_eq!(
+            caveated(&nimt, ClassifyParams::default()),
+            (Call::NIMT, false)
+        );
+    }
+
+    #[test]
+    fn control_region_column_across_the_origin() {
+        use ControlRegionOverlap::{Full, None as Outside, Partial};
+        let locus = |pid: &str, mito_start: u32, mito_end: u32| PairedLocus {
+            pair_id: pid.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: 0,
+            nuc_end: mito_end - mito_start,
+            mito_contig: "mt".into(),
+            mito_start,
+            mito_end,
+            aln_len: mito_end - mito_start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let regions = ControlRegions::resolve(
+            &[crate::control_region::parse_spec("mt:16024-576").unwrap()],
+            &[("mt".to_string(), 16_569)].into_iter().collect(),
+            &Default::default(),
+        )
+        .unwrap();
+        let pairs = [
+            locus("P1", 16_100, 16_500),
+            locus("P2", 0, 400),
+            locus("P3", 500, 1000),
+            locus("P4", 2000, 3000),
+        ];
+        let cov = CoverageSummary {
+            nuclear_median: 30.0,
+            mito_median: 30.0,
+            loci_medians: None,
+            per_pair: HashMap::new(),
+            params: None,
+        };
+        let spans = SpanSummary {
+            per_pair: HashMap::new(),
+            read_len: HashMap::new(),
+            read_div: HashMap::new(),
+            reads: HashMap::new(),
+            clamped_window: HashMap::new(),
+            invalid_window: HashMap::new(),
+            junction_clips: HashMap::new(),
+            params: None,
+            cost: HashMap::new(),
+            stage_cost: None,
+        };
+        let scored = score_pairs(
+            &pairs,
+            &cov,
+            &spans,
+            &GapIndex::default(),
+            &regions,
+            Weights::default(),
+            ClassifyParams::default(),
+        );
+        let overlaps: Vec<_> = scored.iter().map(|sp| sp.features.control_region).collect();
+        assert_eq!(overlaps, [Full, Full, Partial, Outside]);
+
+        // only full containment is `true`; partial overlap reads back as outside
+        let tsv = pairs_tsv(&scored, &PairsColumns::default());
+        let flags: Vec<_> = tsv
+            .lines()
+            .skip(1)
+            .map(|l| l.rsplit('\t').nth(14).unwrap())
+            .collect();
+        assert_eq!(flags, ["true", "true", "false", "false"]);
+        let back = parse_pairs_tsv_str(&tsv).unwrap();
+        assert_eq!(back[1].features.control_region, Full);
+        assert_eq!(back[2].features.control_region, Outside);
+        assert!(classification_tsv(&scored).contains(IN_CONTROL_REGION));
+    }
+
+    #[test]
+    fn control_region_weakens_identity_not_the_call() {
+        let f = |control_region| PairFeatures {
+            aln_ident: 0.97,
+            aln_len: 3000,
+            rnuc: 0.8,
+            rmito: 0.2,
+            s_nuc: 0.9,
+            s_mito: 0.0,
+            read_len_nuc: 0.0,
+            read_len_mito: 0.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region,
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let params = ClassifyParams {
+            control_region_identity_weight: 0.5,
+            ..ClassifyParams::default()
+        };
+        let outside = score_features(&f(ControlRegionOverlap::None), Weights::default(), params);
+        let partial = score_features(
+            &f(ControlRegionOverlap::Partial),
+            Weights::default(),
+            params,
+        );
+        let inside = score_features(&f(ControlRegionOverlap::Full), Weights::default(), params);
+        assert!(inside.score_numt < outside.score_numt);
+        assert!(inside.score_nimt < outside.score_nimt);
+        assert!((inside.delta - outside.delta).abs() < 1e-6);
+        assert_eq!(inside.call, outside.call);
+        assert_eq!(partial.score_numt, outside.score_numt);
+        let has = |s: &PairScore, code: &str| s.reason_codes.iter().any(|c| c == code);
+        assert!(has(&inside, IN_CONTROL_REGION));
+        assert!(has(&partial, OVERLAPS_CONTROL_REGION));
+        assert!(!has(&outside, IN_CONTROL_REGION) && !has(&outside, OVERLAPS_CONTROL_REGION));
+    }
+
+    #[test]
+    fn collapsed_repeat_flag_at_boundaries() {
+        let score = |rnuc: f32, collapsed_repeat_cn: f32| {
+            let f = PairFeatures {
+                aln_ident: 0.97,
+                aln_len: 3000,
+                rnuc,
+                rmito: 0.2,
+                s_nuc: 0.7,
+                s_mito: 0.05,
+                read_len_nuc: 0.0,
+                read_len_mito: 0.0,
+                read_div_nuc: None,
+                read_div_mito: None,
+                low_mapq_nuc: 0,
+                low_mapq_mito: 0,
+                junction_clips_start: 0,
+                junction_clips_end: 0,
+                nuc_n_bases: 0,
+                nuc_longest_n_run: 0,
+                softmask_frac_nuc: 0.0,
+                control_region: Default::default(),
+                clamped_window: None,
+                invalid_window: None,
+            };
+            let params = ClassifyParams {
+                collapsed_repeat_cn,
+                ..ClassifyParams::default()
+            };
+            score_features(&f, Weights::default(), params)
+        };
+        // (rnuc, estimate, flagged at the default 1.5)
+        for (rnuc, cn, flagged) in [
+            (0.0, 0.0, false),
+            (1.0, 1.0, false),
+            (1.24, 1.0, false),
+            (1.25, 1.5, true),
+            (1.5, 1.5, true),
+            (1.74, 1.5, true),
+            (1.75, 2.0, true),
+            (2.1, 2.0, true),
+        ] {
+            let s = score(rnuc, 1.5);
+            assert_eq!(s.est_copy_number_nuc, cn, "rnuc {rnuc}");
+            assert_eq!(s.possible_collapsed_repeat(), flagged, "rnuc {rnuc}");
+        }
+        // threshold is configurable
+        assert!(!score(1.6, 2.0).possible_collapsed_repeat());
+        assert!(score(1.8, 2.0).possible_collapsed_repeat());
+        // the flag is additive: the call and its reason are unchanged
+        let s = score(2.0, 1.5);
+        assert_eq!(s.reason_codes[0], "score_difference");
+        assert_eq!(s.reason_codes[1], POSSIBLE_COLLAPSED_REPEAT);
     }
 }
@@ -0,0 +1,629 @@
+//! The decision step of scoring: an ordered table of rules.
+//!
+//! Once a pair has its two scores, `RULES` decides the call, its confidence
+//! tier and its reason codes. Each rule is a named predicate over the pair's
+//! features and the decision so far; when it fires, its `Effect` may set the
+//! call, set the tier and add a reason code. Rules run in table order, every
+//! one of them, and the names of those that fired are kept as the trace
+//! (`PairScore::rules`, printed by `onsm explain`).
+//!
+//! The table, in order:
+//!
+//! 1. `numt_by_score`, `nimt_by_score`: Δ beyond the call threshold calls
+//!    the favoured side (`score_difference`), high confidence from
+//!    `highconf_threshold` on.
+//! 2. `shared_support`: otherwise, span and single-copy depth on both sides
+//!    (`both_sides_supported`).
+//! 3. `ambiguous_by_score`: otherwise Ambiguous (`delta_below_threshold`).
+//! 4. Flags that leave the call alone: `possible_collapsed_repeat`,
+//!    `reads_shorter_than_window`, `span_evidence_disabled`,
+//!    `self_assembly_caveat` (NIMT calls only), `in_control_region`,
+//!    `overlaps_control_region`, `spans_assembly_gap`, `short_window`.
+//! 5. `softmasked_locus`, `invalid_window`: force Ambiguous, last so that
+//!    nothing undoes it. The first reason code still says what the scores
+//!    said.
+//!
+//! A new condition on the call is a new rule in this table, not an `if` in
+//! `score_features`.
+
+use crate::control_region::ControlRegionOverlap;
+use crate::io::fasta::GapStats;
+use crate::model::{ClassifyParams, MIN_SPAN_WINDOW_BP};
+
+use super::{
+    spans_assembly_gap, Call, PairFeatures, INVALID_WINDOW, IN_CONTROL_REGION,
+    OVERLAPS_CONTROL_REGION, POSSIBLE_COLLAPSED_REPEAT, READS_SHORTER_THAN_WINDOW,
+    SELF_ASSEMBLY_CAVEAT, SHORT_WINDOW, SOFTMASKED_LOCUS, SPANS_ASSEMBLY_GAP,
+    SPAN_EVIDENCE_DISABLED,
+};
+
+/// What the rules look at for one pair.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleInput<'a> {
+    pub features: &'a PairFeatures,
+    /// score_numt − score_nimt (positive favours NUMT).
+    pub diff: f32,
+    pub est_copy_number_nuc: f32,
+    /// `PairFeatures::read_len_factor` at the span window.
+    pub short_reads: Option<f32>,
+    /// Length of the nuclear locus; `None` when scoring bare features.
+    pub nuc_len: Option<u32>,
+    pub params: ClassifyParams,
+}
+
+/// The call, tier and reason codes so far, and the rules that fired.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decision {
+    pub call: Option<Call>,
+    pub high_confidence: bool,
+    pub reason_codes: Vec<String>,
+    pub trace: Vec<&'static str>,
+}
+
+/// What a rule does when it fires.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Effect {
+    pub call: Option<Call>,
+    pub high_confidence: Option<bool>,
+    pub reason: Option<&'static str>,
+}
+
+impl Effect {
+    /// Make `call`, with its reason code.
+    fn call(call: Call, high_confidence: bool) -> Self {
+        Self {
+            call: Some(call),
+            high_confidence: Some(high_confidence),
+            reason: Some(call.as_str_and_reason().1),
+        }
+    }
+
+    /// Add a reason code.
+    fn flag(reason: &'static str) -> Self {
+        Self {
+            reason: Some(reason),
+            ..Self::default()
+        }
+    }
+}
+
+pub struct Rule {
+    pub name: &'static str,
+    /// One line for `onsm explain`.
+    pub description: &'static str,
+    pub fires: fn(&RuleInput, &Decision) -> Option<Effect>,
+}
+
+impl std::fmt::Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// The default table; see the module documentation for its order.
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "numt_by_score",
+        description: "Δ ≥ the call threshold: Likely_NUMT",
+        fires: |i, d| {
+            (d.call.is_none() && i.diff >= i.params.call_threshold)
+                .then(|| Effect::call(Call::NUMT, i.diff >= i.params.highconf_threshold))
+        },
+    },
+    Rule {
+        name: "nimt_by_score",
+        description: "−Δ ≥ the call threshold: Likely_NIMT",
+        fires: |i, d| {
+            (d.call.is_none() && -i.diff >= i.params.call_threshold)
+                .then(|| Effect::call(Call::NIMT, -i.diff >= i.params.highconf_threshold))
+        },
+    },
+    Rule {
+        name: "shared_support",
+        description:
+            "no call, but reads span both loci and both depths look single-copy: Shared_Support",
+        fires: |i, d| {
+            (d.call.is_none() && shared_support(i.features, i.params))
+                .then(|| Effect::call(Call::SharedSupport, false))
+        },
+    },
+    Rule {
+        name: "ambiguous_by_score",
+        description: "no call otherwise: Ambiguous",
+        fires: |_, d| {
+            d.call
+                .is_none()
+                .then(|| Effect::call(Call::Ambiguous, false))
+        },
+    },
+    Rule {
+        name: POSSIBLE_COLLAPSED_REPEAT,
+        description: "estimated nuclear copy number ≥ --collapsed-repeat-cn",
+        fires: |i, _| {
+            (i.est_copy_number_nuc >= i.params.collapsed_repeat_cn)
+                .then(|| Effect::flag(POSSIBLE_COLLAPSED_REPEAT))
+        },
+    },
+    Rule {
+        name: READS_SHORTER_THAN_WINDOW,
+        description: "median read length shorter than the span window",
+        fires: |i, _| {
+            i.short_reads
+                .is_some()
+                .then(|| Effect::flag(READS_SHORTER_THAN_WINDOW))
+        },
+    },
+    Rule {
+        name: SPAN_EVIDENCE_DISABLED,
+        description: "span evidence off for the run (--allow-short-reads)",
+        fires: |i, _| {
+            i.params
+                .span_evidence_disabled
+                .then(|| Effect::flag(SPAN_EVIDENCE_DISABLED))
+        },
+    },
+    Rule {
+        name: SELF_ASSEMBLY_CAVEAT,
+        description: "a NIMT call in a run whose mito was assembled from its own reads",
+        fires: |i, d| {
+            (i.params.self_assembly_caveat && d.call == Some(Call::NIMT))
+                .then(|| Effect::flag(SELF_ASSEMBLY_CAVEAT))
+        },
+    },
+    Rule {
+        name: IN_CONTROL_REGION,
+        description: "mito side entirely inside a --mito-control-region",
+        fires: |i, _| {
+            (i.features.control_region == ControlRegionOverlap::Full)
+                .then(|| Effect::flag(IN_CONTROL_REGION))
+        },
+    },
+    Rule {
+        name: OVERLAPS_CONTROL_REGION,
+        description: "mito side partly inside a --mito-control-region",
+        fires: |i, _| {
+            (i.features.control_region == ControlRegionOverlap::Partial)
+                .then(|| Effect::flag(OVERLAPS_CONTROL_REGION))
+        },
+    },
+    Rule {
+        name: SPANS_ASSEMBLY_GAP,
+        description: "nuclear locus over 10% N, or with an N-run of 100 bp or more",
+        fires: |i, _| {
+            let gaps = GapStats {
+                n_bases: i.features.nuc_n_bases,
+                longest_run: i.features.nuc_longest_n_run,
+                masked_bases: 0,
+            };
+            i.nuc_len
+                .is_some_and(|len| spans_assembly_gap(gaps, len))
+                .then(|| Effect::flag(SPANS_ASSEMBLY_GAP))
+        },
+    },
+    Rule {
+        name: SHORT_WINDOW,
+        description: "a contig end leaves a span window under 250 bp",
+        fires: |i, _| {
+            i.features
+                .clamped_window
+                .is_some_and(|(nuc, mito)| nuc.min(mito) < MIN_SPAN_WINDOW_BP)
+                .then(|| Effect::flag(SHORT_WINDOW))
+        },
+    },
+    Rule {
+        name: SOFTMASKED_LOCUS,
+        description: "nuclear locus soft-masked beyond --max-softmask-frac: Ambiguous",
+        fires: |i, _| {
+            i.params
+                .max_softmask_frac
+                .is_some_and(|max| i.features.softmask_frac_nuc > max)
+                .then_some(Effect {
+                    call: Some(Call::Ambiguous),
+                    high_confidence: Some(false),
+                    reason: Some(SOFTMASKED_LOCUS),
+                })
+        },
+    },
+    Rule {
+        name: INVALID_WINDOW,
+        description: "no depth or span window could be built around the pair: Ambiguous",
+        fires: |i, _| {
+            i.features.invalid_window.is_some().then_some(Effect {
+                call: Some(Call::Ambiguous),
+                high_confidence: Some(false),
+                reason: Some(INVALID_WINDOW),
+            })
+        },
+    },
+];
+
+/// The rule called `name` in the default table.
+pub fn rule(name: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.name == name)
+}
+
+/// Reads span both loci and both depths look single-copy: the evidence
+/// supports each side, so the scores cancel. Never without span evidence.
+fn shared_support(f: &PairFeatures, params: ClassifyParams) -> bool {
+    let near_one = |r: f32| (r - 1.0).abs() <= params.shared_depth_tol;
+    !params.span_evidence_disabled
+        && f.s_nuc >= params.shared_min_span
+        && f.s_mito >= params.shared_min_span
+        && near_one(f.rnuc)
+        && near_one(f.rmito)
+}
+
+/// Run `table` over `input`, in order.
+pub fn evaluate(table: &[Rule], input: &RuleInput) -> Decision {
+    let mut d = Decision::default();
+    for rule in table {
+        let Some(e) = (rule.fires)(input, &d) else {
+            continue;
+        };
+        if let Some(call) = e.call {
+            d.call = Some(call);
+        }
+        if let Some(high) = e.high_confidence {
+            d.high_confidence = high;
+        }
+        if let Some(reason) = e.reason {
+            d.reason_codes.push(reason.to_string());
+        }
+        d.trace.push(rule.name);
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features() -> PairFeatures {
+        PairFeatures {
+            aln_ident: 0.95,
+            aln_len: 2000,
+            rnuc: 1.0,
+            rmito: 1.0,
+            s_nuc: 0.0,
+            s_mito: 0.0,
+            read_len_nuc: 0.0,
+            read_len_mito: 0.0,
+            read_div_nuc: None,
+            read_div_mito: None,
+            low_mapq_nuc: 0,
+            low_mapq_mito: 0,
+            junction_clips_start: 0,
+            junction_clips_end: 0,
+            nuc_n_bases: 0,
+            nuc_longest_n_run: 0,
+            softmask_frac_nuc: 0.0,
+            control_region: ControlRegionOverlap::None,
+            clamped_window: None,
+            invalid_window: None,
+        }
+    }
+
+    fn input(f: &PairFeatures, diff: f32, params: ClassifyParams) -> RuleInput<'_> {
+        RuleInput {
+            features: f,
+            diff,
+            est_copy_number_nuc: 1.0,
+            short_reads: None,
+            nuc_len: Some(1000),
+            params,
+        }
+    }
+
+    /// Fire one rule of the default table on an empty decision.
+    fn fires(name: &str, i: &RuleInput) -> Option<Effect> {
+        (rule(name).unwrap().fires)(i, &Decision::default())
+    }
+
+    #[test]
+    fn rule_names_are_unique() {
+        let mut names: Vec<_> = RULES.iter().map(|r| r.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), RULES.len());
+    }
+
+    #[test]
+    fn score_rules() {
+        let f = features();
+        let p = ClassifyParams::default();
+        let (call, high) = (p.call_threshold, p.highconf_threshold);
+        let numt = |diff| fires("numt_by_score", &input(&f, diff, p));
+        assert_eq!(numt(call - 0.01), None);
+        assert_eq!(numt(call), Some(Effect::call(Call::NUMT, false)));
+        assert_eq!(numt(high), Some(Effect::call(Call::NUMT, true)));
+        let nimt = |diff| fires("nimt_by_score", &input(&f, diff, p));
+        assert_eq!(nimt(call), None);
+        assert_eq!(nimt(-high), Some(Effect::call(Call::NIMT, true)));
+        // a call already made is left alone
+        let made = Decision {
+            call: Some(Call::NUMT),
+            ..Decision::default()
+        };
+        let amb = rule("ambiguous_by_score").unwrap();
+        assert_eq!((amb.fires)(&input(&f, 0.0, p), &made), None);
+        assert_eq!(
+            (amb.fires)(&input(&f, 0.0, p), &Decision::default()),
+            Some(Effect::call(Call::Ambiguous, false))
+        );
+    }
+
+    #[test]
+    fn shared_support_rule() {
+        let p = ClassifyParams::default();
+        let both = PairFeatures {
+            s_nuc: 0.6,
+            s_mito: 0.6,
+            ..features()
+        };
+        assert_eq!(
+            fires("shared_support", &input(&both, 0.0, p)),
+            Some(Effect::call(Call::SharedSupport, false))
+        );
+        let one = PairFeatures {
+            s_mito: 0.0,
+            ..both
+        };
+        assert_eq!(fires("shared_support", &input(&one, 0.0, p)), None);
+        let off = ClassifyParams {
+            span_evidence_disabled: true,
+            ..p
+        };
+        assert_eq!(fires("shared_support", &input(&both, 0.0, off)), None);
+    }
+
+    #[test]
+    fn flag_rules() {
+        let p = ClassifyParams::default();
+        let f = features();
+        let flag = |code| Some(Effect::flag(code));
+        let i = RuleInput {
+            est_copy_number_nuc: p.collapsed_repeat_cn,
+            short_reads: Some(0.5),
+            ..input(&f, 0.0, p)
+        };
+        assert_eq!(
+            fires(POSSIBLE_COLLAPSED_REPEAT, &i),
+            flag(POSSIBLE_COLLAPSED_REPEAT)
+        );
+        assert_eq!(
+            fires(READS_SHORTER_THAN_WINDOW, &i),
+            flag(READS_SHORTER_THAN_WINDOW)
+        );
+        assert_eq!(fires(POSSIBLE_COLLAPSED_REPEAT, &input(&f, 0.0, p)), None);
+
+        let caveat = ClassifyParams {
+            self_assembly_caveat: true,
+            ..p
+        };
+        let rule = rule(SELF_ASSEMBLY_CAVEAT).unwrap();
+        let with = |call| Decision {
+            call: Some(call),
+            ..Decision::default()
+        };
+        let i = input(&f, 0.0, caveat);
+        assert_eq!(
+            (rule.fires)(&i, &with(Call::NIMT)),
+            flag(SELF_ASSEMBLY_CAVEAT)
+        );
+        assert_eq!((rule.fires)(&i, &with(Call::NUMT)), None);
+
+        let inside = PairFeatures {
+            control_region: ControlRegionOverlap::Full,
+            ..f
+        };
+        assert_eq!(
+            fires(IN_CONTROL_REGION, &input(&inside, 0.0, p)),
+            flag(IN_CONTROL_REGION)
+        );
+        assert_eq!(
+            fires(OVERLAPS_CONTROL_REGION, &input(&inside, 0.0, p)),
+            None
+        );
+
+        let near_end = |nuc| PairFeatures {
+            clamped_window: Some((nuc, 1000)),
+            ..f
+        };
+        assert_eq!(
+            fires(SHORT_WINDOW, &input(&near_end(200), 0.0, p)),
+            flag(SHORT_WINDOW)
+        );
+        assert_eq!(fires(SHORT_WINDOW, &input(&near_end(600), 0.0, p)), None);
+        assert_eq!(fires(SHORT_WINDOW, &input(&f, 0.0, p)), None);
+    }
+
+    #[test]
+    fn locus_rules() {
+        let p = ClassifyParams::default();
+        let gappy = PairFeatures {
+            nuc_n_bases: 150,
+            nuc_longest_n_run: 50,
+            ..features()
+        };
+        // 15% N of a 1 kb locus; unknown without the locus length
+        assert_eq!(
+            fires(SPANS_ASSEMBLY_GAP, &input(&gappy, 0.0, p)),
+            Some(Effect::flag(SPANS_ASSEMBLY_GAP))
+        );
+        let bare = RuleInput {
+            nuc_len: None,
+            ..input(&gappy, 0.0, p)
+        };
+        assert_eq!(fires(SPANS_ASSEMBLY_GAP, &bare), None);
+
+        let masked = PairFeatures {
+            softmask_frac_nuc: 0.9,
+            ..features()
+        };
+        assert_eq!(fires(SOFTMASKED_LOCUS, &input(&masked, 1.0, p)), None);
+        let strict = ClassifyParams {
+            max_softmask_frac: Some(0.8),
+            ..p
+        };
+        let e = fires(SOFTMASKED_LOCUS, &input(&masked, 1.0, strict)).unwrap();
+        assert_eq!(
+            (e.call, e.high_confidence, e.reason),
+            (Some(Call::Ambiguous), Some(false), Some(SOFTMASKED_LOCUS))
+        );
+
+        let invalid = PairFeatures {
+            clamped_window: None,
+            invalid_window: Some(crate::io::bam::WindowError::Inverted),
+            ..features()
+        };
+        assert_eq!(fires(INVALID_WINDOW, &input(&features(), 1.0, p)), None);
+        let d = evaluate(RULES, &input(&invalid, 1.0, p));
+        assert_eq!((d.call, d.high_confidence), (Some(Call::Ambiguous), false));
+        assert_eq!(d.reason_codes, ["score_difference", INVALID_WINDOW]);
+    }
+
+    #[test]
+    fn evaluation_follows_the_table() {
+        let masked = PairFeatures {
+            softmask_frac_nuc: 0.9,
+            control_region: ControlRegionOverlap::Partial,
+            ..features()
+        };
+        let params = ClassifyParams {
+            max_softmask_frac: Some(0.8),
+            self_assembly_caveat: true,
+            ..ClassifyParams::default()
+        };
+        let d = evaluate(RULES, &input(&masked, -1.0, params));
+        assert_eq!(d.call, Some(Call::Ambiguous));
+        assert!(!d.high_confidence);
+        assert_eq!(
+            d.reason_codes,
+            [
+                "score_difference",
+                SELF_ASSEMBLY_CAVEAT,
+                OVERLAPS_CONTROL_REGION,
+                SOFTMASKED_LOCUS
+            ]
+        );
+        assert_eq!(
+            d.trace,
+            [
+                "nimt_by_score",
+                SELF_ASSEMBLY_CAVEAT,
+                OVERLAPS_CONTROL_REGION,
+                SOFTMASKED_LOCUS
+            ]
+        );
+        // order matters: softmasked first, the score rules find a call made
+        let reversed: Vec<Rule> = RULES
+            .iter()
+            .rev()
+            .map(|r| Rule {
+                name: r.name,
+                description: r.description,
+                fires: r.fires,
+            })
+            .collect();
+        let d = evaluate(&reversed, &input(&masked, -1.0, params));
+        assert_eq!(d.call, Some(Call::Ambiguous));
+        assert_eq!(d.trace, [SOFTMASKED_LOCUS, OVERLAPS_CONTROL_REGION]);
+    }
+
+    /// The if-cascade the table replaced, kept to check the table against.
+    fn cascade(i: &RuleInput) -> (Call, bool, Vec<String>) {
+        let (f, params, diff) = (i.features, i.params, i.diff);
+        let call = if diff >= params.call_threshold {
+            Call::NUMT
+        } else if -diff >= params.call_threshold {
+            Call::NIMT
+        } else if shared_support(f, params) {
+            Call::SharedSupport
+        } else {
+            Call::Ambiguous
+        };
+        let mut reasons = vec![call.as_str_and_reason().1.to_string()];
+        if i.est_copy_number_nuc >= params.collapsed_repeat_cn {
+            reasons.push(POSSIBLE_COLLAPSED_REPEAT.to_string());
+        }
+        if i.short_reads.is_some() {
+            reasons.push(READS_SHORTER_THAN_WINDOW.to_string());
+        }
+        if params.span_evidence_disabled {
+            reasons.push(SPAN_EVIDENCE_DISABLED.to_string());
+        }
+        if params.self_assembly_caveat && call == Call::NIMT {
+            reasons.push(SELF_ASSEMBLY_CAVEAT.to_string());
+        }
+        match f.control_region {
+            ControlRegionOverlap::Full => reasons.push(IN_CONTROL_REGION.to_string()),
+            ControlRegionOverlap::Partial => reasons.push(OVERLAPS_CONTROL_REGION.to_string()),
+            ControlRegionOverlap::None => {}
+        }
+        let mut high = call.is_decided() && diff.abs() >= params.highconf_threshold;
+        let gaps = GapStats {
+            n_bases: f.nuc_n_bases,
+            longest_run: f.nuc_longest_n_run,
+            masked_bases: 0,
+        };
+        if i.nuc_len.is_some_and(|len| spans_assembly_gap(gaps, len)) {
+            reasons.push(SPANS_ASSEMBLY_GAP.to_string());
+        }
+        let mut call = call;
+        if params
+            .max_softmask_frac
+            .is_some_and(|max| f.softmask_frac_nuc > max)
+        {
+            call = Call::Ambiguous;
+            high = false;
+            reasons.push(SOFTMASKED_LOCUS.to_string());
+        }
+        (call, high, reasons)
+    }
+
+    #[test]
+    fn default_table_matches_the_cascade() {
+        let mut n = 0;
+        for diff in [
+            -0.9, -0.3, -0.2, -0.15, -0.1, 0.0, 0.1, 0.15, 0.29, 0.3, 1.2,
+        ] {
+            for (s, r) in [(0.0, 1.0), (0.6, 1.0), (0.6, 1.4), (0.6, 2.0)] {
+                for control_region in [
+                    ControlRegionOverlap::None,
+                    ControlRegionOverlap::Partial,
+                    ControlRegionOverlap::Full,
+                ] {
+                    for (nuc_n_bases, softmask_frac_nuc) in [(0, 0.0), (200, 0.5), (0, 0.95)] {
+                        for flags in 0..16u8 {
+                            let f = PairFeatures {
+                                s_nuc: s,
+                                s_mito: s,
+                                rnuc: r,
+                                nuc_n_bases,
+                                softmask_frac_nuc,
+                                control_region,
+                                ..features()
+                            };
+                            let params = ClassifyParams {
+                                span_evidence_disabled: flags & 1 != 0,
+                                self_assembly_caveat: flags & 2 != 0,
+                                max_softmask_frac: (flags & 4 != 0).then_some(0.8),
+                                ..ClassifyParams::default()
+                            };
+                            let i = RuleInput {
+                                est_copy_number_nuc: r,
+                                short_reads: (flags & 8 != 0).then_some(0.5),
+                                ..input(&f, diff, params)
+                            };
+                            let d = evaluate(RULES, &i);
+                            let got = (d.call.unwrap(), d.high_confidence, d.reason_codes);
+                            assert_eq!(got, cascade(&i), "{i:?}");
+                            n += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(n, 11 * 4 * 3 * 3 * 16);
+    }
+}
@@ -0,0 +1,266 @@
+//! Mito contigs assembled from the same reads onsm maps back to them.
+//!
+//! A mitogenome assembled from these reads agrees with them by construction:
+//! reads that are really nuclear (NUMT copies, or a nuclear insertion the
+//! assembler folded into the mito consensus) support the mito side because
+//! they built it. Mito-side read support then says little, and NIMT calls,
+//! which rest on it, are partly circular. `classify
+//! --mito-assembled-from-these-reads` declares it; otherwise read identity is
+//! sampled from a few mito windows (the NM tags of `samtools view`) and taken
+//! as a sign of it when every window is near-perfect and the mito depth is far
+//! above the nuclear one. Either way the calls are kept; NIMT calls get the
+//! `self_assembly_caveat` reason code and the summary a caveat.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::io::bam::{self, MismatchTally, Window};
+use crate::util::warnings;
+
+/// Mito windows sampled (spread over the longest mito contig), and nuclear
+/// windows for the depth baseline (one on each of the longest contigs).
+pub const N_WINDOWS: usize = 5;
+pub const WINDOW_BP: u32 = 2_000;
+/// Every sampled mito window must be at most this many edits per aligned base…
+pub const MAX_MISMATCH_RATE: f64 = 0.001;
+/// …and the mito depth at least this many times the nuclear depth.
+pub const MIN_DEPTH_RATIO: f32 = 50.0;
+
+/// Whether the mito was assembled from the run's own reads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SelfAssembly {
+    /// Not declared, and the reads don't look like it.
+    #[default]
+    No,
+    /// `--mito-assembled-from-these-reads`.
+    Declared,
+    /// The read-identity check (`IdentitySample::looks_self_assembled`).
+    Detected,
+}
+
+impl SelfAssembly {
+    /// NIMT calls and the summary carry `self_assembly_caveat`.
+    pub fn is_caveated(self) -> bool {
+        self != SelfAssembly::No
+    }
+}
+
+/// Read identity and depth in the sampled windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct IdentitySample {
+    /// One tally per sampled mito window.
+    pub mito_windows: Vec<MismatchTally>,
+    /// Median of the windows' median depths.
+    pub mito_depth: f32,
+    pub nuclear_depth: f32,
+}
+
+impl IdentitySample {
+    pub fn depth_ratio(&self) -> Option<f32> {
+        (self.nuclear_depth > 0.0).then(|| self.mito_depth / self.nuclear_depth)
+    }
+
+    /// Near-perfect identity in every window with reads, and mito depth far
+    /// above nuclear. Never without reads on both assemblies.
+    pub fn looks_self_assembled(&self) -> bool {
+        let mut rates = self.mito_windows.iter().filter_map(MismatchTally::rate);
+        let first = rates.next();
+        first.is_some_and(|r| r <= MAX_MISMATCH_RATE)
+            && rates.all(|r| r <= MAX_MISMATCH_RATE)
+            && self.depth_ratio().is_some_and(|r| r >= MIN_DEPTH_RATIO)
+    }
+}
+
+/// `n` windows of `width` bp centred at evenly spread points of a contig of
+/// `len` bp; a shorter contig is one window over all of it.
+fn spread(len: u64, n: usize, width: u32) -> Vec<Window> {
+    let len = i32::try_from(len).unwrap_or(i32::MAX);
+    let width = width as i32;
+    if len <= width {
+        return vec![Window { start: 0, end: len }];
+    }
+    (0..n as i64)
+        .map(|i| {
+            let centre = (len as i64 * (2 * i + 1) / (2 * n as i64)) as i32;
+            let start = (centre - width / 2).clamp(0, len - width);
+            Window {
+                start,
+                end: start + width,
+            }
+        })
+        .collect()
+}
+
+/// The `n` longest contigs, longest first (ties by name).
+fn longest<'a>(lens: &'a HashMap<String, u64>, skip: &[String], n: usize) -> Vec<(&'a str, u64)> {
+    let mut v: Vec<_> = lens
+        .iter()
+        .filter(|(name, _)| !skip.contains(name))
+        .map(|(name, &len)| (name.as_str(), len))
+        .collect();
+    v.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    v.truncate(n);
+    v
+}
+
+/// Sample read identity over the longest mito contig, and depth there and on
+/// the longest nuclear contigs (`excluded_nuclear` left out; supplementary
+/// alignments only with `depth_supplementary`, as for the evidence).
+pub fn sample(
+    samtools: &Path,
+    bam_mito: &Path,
+    bam_nuclear: &Path,
+    mito_lens: &HashMap<String, u64>,
+    nuclear_lens: &HashMap<String, u64>,
+    excluded_nuclear: &[String],
+    depth_supplementary: bool,
+) -> Result<IdentitySample> {
+    let mut mito_windows = Vec::new();
+    let mut mito_depths = Vec::new();
+    if let Some(&(contig, len)) = longest(mito_lens, &[], 1).first() {
+        for w in spread(len, N_WINDOWS, WINDOW_BP) {
+            mito_windows.push(bam::window_mismatches(samtools, bam_mito, contig, w)?);
+            mito_depths.push(bam::local_median_depth(
+                samtools,
+                bam_mito,
+                None,
+                contig,
+                w,
+                depth_supplementary,
+            )?);
+        }
+    }
+    let mut nuclear_depths = Vec::new();
+    for (contig, len) in longest(nuclear_lens, excluded_nuclear, N_WINDOWS) {
+        let w = spread(len, 1, WINDOW_BP)[0];
+        nuclear_depths.push(bam::local_median_depth(
+            samtools,
+            bam_nuclear,
+            None,
+            contig,
+            w,
+            depth_supplementary,
+        )?);
+    }
+    Ok(IdentitySample {
+        mito_windows,
+        mito_depth: bam::median_f32(mito_depths),
+        nuclear_depth: bam::median_f32(nuclear_depths),
+    })
+}
+
+/// Declared, detected from `sample`, or neither; a detection is warned about.
+pub fn assess(declared: bool, sample: &IdentitySample) -> SelfAssembly {
+    if declared {
+        return SelfAssembly::Declared;
+    }
+    if !sample.looks_self_assembled() {
+        return SelfAssembly::No;
+    }
+    warnings::warn(
+        warnings::SELF_ASSEMBLY,
+        format!(
+            "reads match the mito almost perfectly in every sampled window, at {:.0}× the nuclear depth; \
+             the mito looks assembled from these reads, so NIMT calls are caveated (self_assembly_caveat)",
+            sample.depth_ratio().unwrap_or_default()
+        ),
+    );
+    SelfAssembly::Detected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(aligned_bp: u64, edits: u64) -> MismatchTally {
+        MismatchTally {
+            n_reads: 10,
+            aligned_bp,
+            edits,
+        }
+    }
+
+    fn sample(windows: &[MismatchTally], mito_depth: f32, nuclear_depth: f32) -> IdentitySample {
+        IdentitySample {
+            mito_windows: windows.to_vec(),
+            mito_depth,
+            nuclear_depth,
+        }
+    }
+
+    #[test]
+    fn uniform_high_identity_at_high_depth_looks_self_assembled() {
+        let perfect = [tally(150_000, 0), tally(140_000, 30), tally(160_000, 10)];
+        assert!(sample(&perfect, 3000.0, 30.0).looks_self_assembled());
+        // one window at ordinary read identity is enough to rule it out
+        let mut mixed = perfect.to_vec();
+        mixed.push(tally(150_000, 1_500));
+        assert!(!sample(&mixed, 3000.0, 30.0).looks_self_assembled());
+        // identity alone is not enough: the mito must be far deeper
+        assert!(!sample(&perfect, 600.0, 30.0).looks_self_assembled());
+    }
+
+    #[test]
+    fn no_reads_is_not_self_assembly() {
+        // windows without reads are skipped, but some must have reads…
+        assert!(!sample(&[MismatchTally::default()], 3000.0, 30.0).looks_self_assembled());
+        assert!(
+            sample(&[MismatchTally::default(), tally(1000, 0)], 3000.0, 30.0)
+                .looks_self_assembled()
+        );
+        // …and the nuclear assembly too
+        assert!(!sample(&[tally(1000, 0)], 3000.0, 0.0).looks_self_assembled());
+    }
+
+    #[test]
+    fn declaration_wins_over_the_sample() {
+        let ordinary = sample(&[tally(150_000, 1_500)], 3000.0, 30.0);
+        assert_eq!(assess(false, &ordinary), SelfAssembly::No);
+        assert_eq!(assess(true, &ordinary), SelfAssembly::Declared);
+        let perfect = sample(&[tally(150_000, 0)], 3000.0, 30.0);
+        assert_eq!(assess(false, &perfect), SelfAssembly::Detected);
+        assert!(!SelfAssembly::No.is_caveated());
+        assert!(SelfAssembly::Detected.is_caveated());
+    }
+
+    #[test]
+    fn windows_spread_over_the_contig() {
+        let ws: Vec<_> = spread(16_569, 5, 2000)
+            .iter()
+            .map(|w| (w.start, w.end))
+            .collect();
+        assert_eq!(
+            ws,
+            [
+                (656, 2656),
+                (3970, 5970),
+                (7284, 9284),
+                (10598, 12598),
+                (13912, 15912)
+            ]
+        );
+        let short = spread(1500, 5, 2000);
+        assert_eq!((short.len(), short[0].start, short[0].end), (1, 0, 1500));
+        // windows stay on the contig
+        assert!(spread(2100, 5, 2000)
+            .iter()
+            .all(|w| w.start >= 0 && w.end <= 2100));
+    }
+
+    #[test]
+    fn longest_contigs_skip_excluded() {
+        let lens: HashMap<String, u64> = [("chr1", 500), ("chr2", 900), ("chrM_copy", 900)]
+            .map(|(n, l)| (n.to_string(), l))
+            .into();
+        assert_eq!(longest(&lens, &[], 2), [("chr2", 900), ("chrM_copy", 900)]);
+        assert_eq!(
+            longest(&lens, &["chrM_copy".to_string()], 5),
+            [("chr2", 900), ("chr1", 500)]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Nuclear contigs that are really the supplied mitogenome.
+//!
+//! A nuclear assembly that still contains the mito contig, run against that
+//! same mito, yields one whole-genome 100%-identity "NUMT". Such contigs are
+//! found up front (same name and length, or identical sequence on either
+//! strand) and again after the assembly alignment (a single hit covering
+//! nearly all of a mito contig at near-perfect identity, on a nuclear contig
+//! of nearly the same length). Their PAF records are dropped before pairing
+//! and their length is left out of the nuclear total.
+
+use anyhow::{Context, Result};
+use needletail::parse_fastx_file;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::hash::Hasher;
+use std::path::Path;
+
+use crate::io::fasta::LengthCache;
+use crate::io::paf::PafRecord;
+use crate::util::warnings;
+
+pub const EXCLUDED_CONTIGS_FILE: &str = "excluded_contigs.txt";
+
+/// A single mito→nuclear hit must cover at least this fraction of the mito contig…
+pub const SELF_MIN_MITO_COV: f64 = 0.99;
+/// …at this identity…
+pub const SELF_MIN_IDENTITY: f32 = 0.999;
+/// …on a nuclear contig whose length is within this fraction of the mito's.
+pub const SELF_LEN_TOLERANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// Same contig name (and nearly the same length).
+    Name,
+    /// Identical sequence, forward or reverse complement.
+    Checksum,
+    /// Near-complete, near-perfect assembly alignment.
+    Alignment,
+}
+
+impl ExclusionReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExclusionReason::Name => "name",
+            ExclusionReason::Checksum => "checksum",
+            ExclusionReason::Alignment => "alignment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludedContig {
+    pub nuclear: String,
+    pub mito: String,
+    pub reason: ExclusionReason,
+}
+
+fn similar_len(a: u64, b: u64) -> bool {
+    a.abs_diff(b) as f64 <= SELF_LEN_TOLERANCE * a.max(b) as f64
+}
+
+fn checksum(seq: impl Iterator<Item = u8>) -> u64 {
+    let mut h = DefaultHasher::new();
+    for b in seq {
+        h.write_u8(b.to_ascii_uppercase());
+    }
+    h.finish()
+}
+
+pub(crate) fn complement(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        other => other,
+    }
+}
+
+/// Nuclear contigs with a mito contig's name (at a similar length) or sequence.
+///
+/// Only nuclear contigs exactly as long as some mito contig are hashed; if there
+/// are none the nuclear FASTA is not read again.
+pub fn find_identical(
+    mito_fa: &Path,
+    nuc_fa: &Path,
+    lengths: &LengthCache,
+) -> Result<Vec<ExcludedContig>> {
+    let mito_lens = lengths.lengths(mito_fa)?;
+    let nuc_lens = lengths.lengths(nuc_fa)?;
+
+    let mut out = Vec::new();
+    let mut names: Vec<&String> = nuc_lens.keys().collect();
+    names.sort();
+    for n in names {
+        if let Some(&ml) = mito_lens.get(n) {
+            if similar_len(ml, nuc_lens[n]) {
+                out.push(ExcludedContig {
+                    nuclear: n.clone(),
+                    mito: n.clone(),
+                    reason: ExclusionReason::Name,
+                });
+            }
+        }
+    }
+
+    let sizes: HashSet<u64> = mito_lens.values().copied().collect();
+    if !nuc_lens.values().any(|l| sizes.contains(l)) {
+        return Ok(out);
+    }
+    // checksum (forward and reverse complement) → mito contig
+    let mut mito_sums: HashMap<u64, String> = HashMap::new();
+    let mut rdr =
+        parse_fastx_file(mito_fa).with_context(|| format!("open fasta {}", mito_fa.display()))?;
+    while let Some(rec) = rdr.next() {
+        let rec = rec.with_context(|| format!("read fasta {}", mito_fa.display()))?;
+        let id = String::from_utf8_lossy(rec.id()).to_string();
+        let seq = rec.seq();
+        mito_sums.insert(checksum(seq.iter().copied()), id.clone());
+        mito_sums.insert(checksum(seq.iter().rev().map(|&b| complement(b))), id);
+    }
+    let mut rdr =
+        parse_fastx_file(nuc_fa).with_context(|| format!("open fasta {}", nuc_fa.display()))?;
+    while let Some(rec) = rdr.next() {
+        let rec = rec.with_context(|| format!("read fasta {}", nuc_fa.display()))?;
+        let seq = rec.seq();
+        if !sizes.contains(&(seq.len() as u64)) {
+            continue;
+        }
+        let id = String::from_utf8_lossy(rec.id()).to_string();
+        if out.iter().any(|e| e.nuclear == id) {
+            continue;
+        }
+        if let Some(m) = mito_sums.get(&checksum(seq.iter().copied())) {
+            out.push(ExcludedContig {
+                nuclear: id,
+                mito: m.clone(),
+                reason: ExclusionReason::Checksum,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Nuclear contigs that a single mito→nuclear hit shows to be the mito itself.
+pub fn find_by_alignment(
+    m2n: &[PafRecord],
+    mito_lens: &HashMap<String, u64>,
+    nuc_lens: &HashMap<String, u64>,
+) -> Vec<ExcludedContig> {
+    let mut out: Vec<ExcludedContig> = Vec::new();
+    for r in m2n {
+        let (Some(&ml), Some(&nl)) = (mito_lens.get(&r.qname), nuc_lens.get(&r.tname)) else {
+            continue;
+        };
+        let cov = r.qend.saturating_sub(r.qstart) as f64 / ml.max(1) as f64;
+        if cov >= SELF_MIN_MITO_COV
+            && r.identity >= SELF_MIN_IDENTITY
+            && similar_len(ml, nl)
+            && !out.iter().any(|e| e.nuclear == r.tname)
+        {
+            out.push(ExcludedContig {
+                nuclear: r.tname.clone(),
+                mito: r.qname.clone(),
+                reason: ExclusionReason::Alignment,
+            });
+        }
+    }
+    out
+}
+
+/// Drop records touching an excluded nuclear contig (target of mito→nuclear,
+/// query of nuclear→mito). Returns how many were dropped.
+pub fn drop_excluded(
+    m2n: &mut Vec<PafRecord>,
+    n2m: &mut Vec<PafRecord>,
+    excluded: &[String],
+) -> usize {
+    if excluded.is_empty() {
+        return 0;
+    }
+    let before = m2n.len() + n2m.len();
+    m2n.retain(|r| !excluded.contains(&r.tname));
+    n2m.retain(|r| !excluded.contains(&r.qname));
+    before - m2n.len() - n2m.len()
+}
+
+/// `excluded_contigs.txt`: nuclear contig, matching mito contig, reason.
+pub fn write_excluded(out_dir: &Path, excluded: &[ExcludedContig]) -> Result<()> {
+    let mut s = String::from("nuclear_contig\tmito_contig\treason\n");
+    for e in excluded {
+        let _ = writeln!(s, "{}\t{}\t{}", e.nuclear, e.mito, e.reason.as_str());
+    }
+    fs_err::write(out_dir.join(EXCLUDED_CONTIGS_FILE), s)?;
+    Ok(())
+}
+
+/// Warn about each exclusion once.
+pub fn log_excluded(excluded: &[ExcludedContig], nuc_lens: &HashMap<String, u64>) {
+    for e in excluded {
+        warnings::warn(
+            warnings::SELF_EXCLUDED_CONTIG,
+            format!(
+                "nuclear contig {} ({} bp) is the mito contig {} (matched by {}); excluding it \
+                 from the nuclear side (--no-self-exclusion to keep it)",
+                e.nuclear,
+                nuc_lens.get(&e.nuclear).copied().unwrap_or(0),
+                e.mito,
+                e.reason.as_str()
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use tempfile::TempDir;
+
+    /// A 6 kb non-repetitive "mitogenome".
+    fn mito_seq() -> String {
+        let mut x: u32 = 7;
+        (0..6_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                b"ACGT"[(x >> 16) as usize % 4] as char
+            })
+            .collect()
+    }
+
+    fn revcomp(s: &str) -> String {
+        s.bytes().rev().map(|b| complement(b) as char).collect()
+    }
+
+    fn fixture(nuc_extra: &str) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+        let td = TempDir::new().unwrap();
+        let mito = td.path().join("mito.fa");
+        let nuc = td.path().join("nuc.fa");
+        fs_err::write(&mito, format!(">mt\n{}\n", mito_seq())).unwrap();
+        fs_err::write(&nuc, format!(">chr1\n{}\n{nuc_extra}", "C".repeat(50_000))).unwrap();
+        (td, mito, nuc)
+    }
+
+    #[test]
+    fn verbatim_mito_in_nuclear_is_found() {
+        // renamed, and lowercase: only the checksum can match
+        let (_td, mito, nuc) = fixture(&format!(">scaffold_99\n{}\n", mito_seq().to_lowercase()));
+        let got = find_identical(&mito, &nuc, &LengthCache::default()).unwrap();
+        assert_eq!(
+            got,
+            vec![ExcludedContig {
+                nuclear: "scaffold_99".into(),
+                mito: "mt".into(),
+                reason: ExclusionReason::Checksum,
+            }]
+        );
+        // the nuclear total no longer counts it
+        let (_, nuc_bp) = crate::summary::genome_totals(
+            &LengthCache::default(),
+            &mito,
+            &nuc,
+            &["scaffold_99".to_string()],
+        )
+        .unwrap();
+        assert_eq!(nuc_bp, 50_000);
+
+        // reverse complement
+        let (_td, mito, nuc) = fixture(&format!(">rc\n{}\n", revcomp(&mito_seq())));
+        let got = find_identical(&mito, &nuc, &LengthCache::default()).unwrap();
+        assert_eq!(got[0].nuclear, "rc");
+
+        // same name, slightly different length
+        let (_td, mito, nuc) = fixture(&format!(">mt\n{}A\n", mito_seq()));
+        let got = find_identical(&mito, &nuc, &LengthCache::default()).unwrap();
+        assert_eq!(got[0].reason, ExclusionReason::Name);
+
+        // nothing to find
+        let (_td, mito, nuc) = fixture("");
+        assert!(find_identical(&mito, &nuc, &LengthCache::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    fn hit(q: &str, qs: u32, qe: u32, t: &str, identity: f32) -> PafRecord {
+        PafRecord {
+            qname: q.into(),
+            qstart: qs,
+            qend: qe,
+            tname: t.into(),
+            tstart: 0,
+            tend: qe - qs,
+            matches: 0,
+            alnlen: qe - qs,
+            mapq: 60,
+            identity,
+            identity_kind: IdentityKind::Blast,
+            blast_identity: identity,
+            gap_compressed_identity: None,
+            strand: '+',
+            tp: Some('P'),
+            parent: None,
+        }
+    }
+
+    #[test]
+    fn near_complete_hit_on_mito_sized_contig() {
+        let mito: HashMap<String, u64> = [("mt".to_string(), 10_000)].into();
+        let nuc: HashMap<String, u64> = [
+            ("chr1".to_string(), 1_000_000),
+            ("ctg_mt".to_string(), 10_200),
+            ("ctg_small".to_string(), 10_100),
+        ]
+        .into();
+        let m2n = vec![
+            // a real NUMT: whole mito, but on a big chromosome
+            hit("mt", 0, 10_000, "chr1", 1.0),
+            hit("mt", 0, 9_950, "ctg_mt", 0.9995),
+            // too divergent
+            hit("mt", 0, 10_000, "ctg_small", 0.99),
+        ];
+        let got = find_by_alignment(&m2n, &mito, &nuc);
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].nuclear, "ctg_mt");
+
+        let mut m2n = m2n;
+        let mut n2m = vec![
+            hit("ctg_mt", 0, 9_950, "mt", 1.0),
+            hit("chr1", 0, 10, "mt", 1.0),
+        ];
+        assert_eq!(drop_excluded(&mut m2n, &mut n2m, &["ctg_mt".into()]), 2);
+        assert_eq!((m2n.len(), n2m.len()), (2, 1));
+    }
+}
@@ -0,0 +1,76 @@
+//! `onsm export-bundle` / `onsm import-bundle` — pack a run directory into one
+//! file to share without its BAMs, and unpack one (see `crate::bundle`).
+
+use anyhow::{bail, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::bundle;
+use crate::subcommands::doctor::human_bytes;
+
+#[derive(Args, Debug)]
+pub struct CmdExportBundle {
+    /// Output directory from a previous `onsm classify`/`onsm reuse`
+    #[arg(long, value_name = "DIR")]
+    pub run: PathBuf,
+
+    /// Where to write the bundle (`.onsm` by convention)
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    /// Also pack each pair's nuclear and mito sequence (loci.fa), cut from the run's assemblies
+    #[arg(long)]
+    pub sequences: bool,
+}
+
+impl CmdExportBundle {
+    pub fn run(self) -> Result<()> {
+        if !self.run.is_dir() {
+            bail!("{} is not a run directory", self.run.display());
+        }
+        let m = bundle::export(&self.run, &self.out, self.sequences)?;
+        for member in &m.members {
+            println!("{}\t{}", member.path, human_bytes(member.bytes));
+        }
+        let packed = fs_err::metadata(&self.out)?.len();
+        println!(
+            "{} files, {} unpacked, {} packed → {}",
+            m.members.len(),
+            human_bytes(m.total_bytes()),
+            human_bytes(packed),
+            self.out.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CmdImportBundle {
+    /// A bundle from `onsm export-bundle`
+    #[arg(long, value_name = "FILE")]
+    pub bundle: PathBuf,
+
+    /// Directory to unpack the run into
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+}
+
+impl CmdImportBundle {
+    pub fn run(self) -> Result<()> {
+        if self.out.join("run_manifest.json").exists() {
+            bail!(
+                "{} already holds a run; import into a new directory",
+                self.out.display()
+            );
+        }
+        let m = bundle::unpack(&self.bundle, &self.out)?;
+        println!(
+            "{} files ({}) checked and unpacked → {} (packed from {})",
+            m.members.len(),
+            human_bytes(m.total_bytes()),
+            self.out.display(),
+            m.run_dir.display()
+        );
+        Ok(())
+    }
+}
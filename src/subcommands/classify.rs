@@ -1,13 +1,48 @@
 use anyhow::Result;
-use clap::Args;
+use clap::{Args, ValueEnum};
 use fs_err as fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use crate::io::{bam, fasta, paf, runfiles};
-use crate::model::{ClassifyParams, Weights};
-use crate::scoring;
-use crate::util::{logging, mapping};
-use crate::{model, summary};
+use crate::boundary::{self, BedBounds};
+use crate::columns::ColumnsPreset;
+use crate::compat::OutputCompat;
+use crate::control_region::{self, ControlRegionSpec, ControlRegions};
+use crate::density::{self, DensityOptions};
+use crate::indexes::IndexDir;
+use crate::io::bam::{self, BamBackend};
+use crate::io::bam_index::IndexedBams;
+use crate::io::fasta::LengthCache;
+use crate::io::paf::{AlnFormat, IdentityKind, PafErrorPolicy, SecondaryPolicy};
+#[cfg(feature = "http")]
+use crate::io::post;
+use crate::io::tmpfiles::{self, KeepPolicy, TmpArtifacts};
+use crate::io::window_cache::{self, WindowCache};
+use crate::io::{fasta, paf, reads, runfiles};
+use crate::mapping_stats::{self, MappingStats};
+use crate::mito_meta::MitoContigMeta;
+use crate::model::orientation::Side;
+use crate::model::{ClassifyParams, EvidenceParams, PairingParams, Weights};
+use crate::pairing::{self, PairingMethod};
+use crate::pipeline::OutputLevel;
+use crate::summary::{GenomeSizes, SummaryUnits, Tier};
+use crate::tracking::{self, TrackingReference};
+use crate::util::mapping::{AsmMapOptions, Mm2Secondary, SamtoolsFeature};
+use crate::util::progress::{Progress, ProgressState, Stage};
+use crate::util::runlock::RunLock;
+use crate::util::shutdown::{self, CancelToken, Interrupted, ResumeHint, Shutdown};
+use crate::util::status::{self, RunStatus};
+use crate::util::version::VersionInfo;
+use crate::util::{cpus, logging, mapping, strict, warnings};
+use crate::{model, pipeline, regions, self_assembly, self_exclusion};
+
+/// Outputs of a run; any of them in --out means a previous run wrote there.
+const PREVIOUS_RUN_FILES: [&str; 4] = [
+    "run_manifest.json",
+    "pairs.tsv",
+    "classification.tsv",
+    "results.json",
+];
 
 #[derive(Args, Debug)]
 pub struct CmdClassify {
@@ -19,9 +54,14 @@ pub struct CmdClassify {
         long,
         value_delimiter = ',',
         num_args = 1..,           // ← at least one value required
-        help = "One or more reads files (FASTQ/FASTA; .gz ok). Repeat or comma-separate."
+        help = "One or more reads files (FASTQ/FASTA; .gz ok), directories of them, or wildcard patterns (quoted, expanded by onsm). Repeat or comma-separate."
     )]
     pub reads: Vec<PathBuf>,
+    #[arg(
+        long,
+        help = "Take reads from the subdirectories of a --reads directory too"
+    )]
+    pub reads_recursive: bool,
     #[arg(long, value_parser=["hifi","ont"])]
     pub platform: String,
     #[arg(long)]
@@ -31,25 +71,642 @@ pub struct CmdClassify {
     pub minimap2: Option<PathBuf>,
     #[arg(long, help = "Path to samtools (else PATH)")]
     pub samtools: Option<PathBuf>,
-    #[arg(long, help = "Threads (default: logical CPUs, capped at 16)")]
+    #[arg(
+        long,
+        help = "Threads (default: logical CPUs, within the cgroup CPU quota, capped at 16)"
+    )]
     pub threads: Option<usize>,
-    #[arg(long, help = "Keep tmp/ outputs so they can be reused")]
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = KeepPolicy::Evidence,
+        help = "What to keep in tmp/ for `onsm reuse`: none, paf (re-pairing), evidence (+ rescoring; a few MB) or all (+ the read BAMs; recomputing evidence)"
+    )]
+    pub keep: KeepPolicy,
+    #[arg(long, hide = true, help = "Same as --keep all")]
     pub keep_tmp: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PairingMethod::Reciprocal,
+        help = "Pairing strategy: reciprocal best hit, or overlap clustering of both directions"
+    )]
+    pub pairing: PairingMethod,
+    #[command(flatten)]
+    pub pairing_params: PairingParams,
+    #[arg(
+        long,
+        help = "Score only loci a nuclear→mito alignment backs too; list the one-sided rest in pairs_unsupported.tsv"
+    )]
+    pub require_reciprocal: bool,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = model::SPLIT_INDEL,
+        help = "Split assembly alignments at insertions or deletions longer than this (from the cg:Z CIGAR), so fragments bridged over unrelated sequence become separate loci sharing a parent_id; 0 = off"
+    )]
+    pub split_indel: u32,
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = model::MIN_ID,
+        value_parser = pairing::parse_fraction,
+        help = "Drop assembly alignments below this identity (see --aln-identity)"
+    )]
+    pub min_id: f32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = IdentityKind::Blast,
+        help = "Identity --min-id applies to and aln_ident reports: blast (matches / block length), or gap-compressed (matches / (matches + mismatches + gap openings), from the cg:Z CIGAR; long indels cost one gap each)"
+    )]
+    pub aln_identity: IdentityKind,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = model::MIN_LEN,
+        help = "Drop assembly alignments with a shorter block"
+    )]
+    pub min_len: u32,
+    #[arg(
+        long,
+        value_name = "Q",
+        default_value_t = model::MIN_PAF_MAPQ,
+        help = "Drop assembly alignments below this mapping quality (0: keep all; MAPQ 0 marks mito segments placed equally well at several nuclear loci)"
+    )]
+    pub min_paf_mapq: u8,
+    #[arg(
+        long,
+        help = "Pair secondary assembly alignments (tp:A:S) too, e.g. to study multi-copy NUMT families (default: dropped)"
+    )]
+    pub keep_secondary: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = pipeline::DEFAULT_MAX_PAIRS,
+        help = "Stop before collecting evidence if pairing gives more candidate pairs than this (0: no limit); a quarter of it warns"
+    )]
+    pub max_pairs: usize,
+    #[arg(
+        long,
+        value_name = "RUN_DIR",
+        help = "Give each pair a stable_id inherited from the best-overlapping pair of this earlier run (tracking.tsv)"
+    )]
+    pub track_against: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = tracking::DEFAULT_TRACK_MIN_OVERLAP,
+        value_parser = pairing::parse_fraction,
+        help = "With --track-against: minimum nuclear reciprocal overlap (0-1) to inherit an ID"
+    )]
+    pub track_min_overlap: f32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PafErrorPolicy::Strict,
+        help = "Malformed PAF lines: abort (strict) or count and skip (skip)"
+    )]
+    pub paf_error_policy: PafErrorPolicy,
+    #[arg(
+        long,
+        default_value_t = paf::MAX_PAF_ERROR_FRAC,
+        help = "With --paf-error-policy skip: fail if more than this fraction of lines is malformed"
+    )]
+    pub paf_max_error_frac: f64,
+
+    #[arg(
+        long,
+        help = "List the PAF lines the filters drop, with the filter that dropped each, in tmp/paf_rejected.tsv"
+    )]
+    pub debug_filters: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "n2m_aln",
+        visible_alias = "asm-aln-mito2nuc",
+        help = "Mito→nuclear assembly alignments from elsewhere (mito as query), instead of running minimap2 on the assemblies"
+    )]
+    pub m2n_aln: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "m2n_aln",
+        visible_alias = "asm-aln-nuc2mito",
+        help = "Nuclear→mito assembly alignments from elsewhere (nuclear as query); with --m2n-aln"
+    )]
+    pub n2m_aln: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AlnFormat::Paf,
+        help = "Format of --m2n-aln/--n2m-aln: paf, blast6 (BLAST -outfmt 6), or sam (SAM or BAM, e.g. minimap2 -a)"
+    )]
+    pub aln_format: AlnFormat,
+    #[arg(
+        long,
+        value_enum,
+        help = "minimap2 --secondary=yes|no for the assembly alignments (default: minimap2's, yes)"
+    )]
+    pub mm2_secondary: Option<Mm2Secondary>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "minimap2 -N (max secondary alignments) for the assembly alignments (default: minimap2's, 5)"
+    )]
+    pub mm2_max_secondary: Option<u32>,
+    #[arg(
+        long,
+        help = "Skip the preflight check for swapped --mito/--nuclear assemblies"
+    )]
+    pub no_input_sanity: bool,
+    #[arg(
+        long,
+        help = "Keep nuclear contigs that are the --mito sequence itself (default: exclude them, see excluded_contigs.txt)"
+    )]
+    pub no_self_exclusion: bool,
+    #[arg(
+        long,
+        help = "Stream pairs and evidence through tmp/ so memory scales with --chunk-size, not pair count"
+    )]
+    pub low_memory: bool,
+    #[arg(
+        long,
+        help = "Fetch every BAM window with samtools, bypassing the window cache in tmp/evidence/cache/"
+    )]
+    pub no_cache: bool,
+    #[cfg(feature = "http")]
+    #[arg(
+        long,
+        value_name = "URL",
+        value_parser = post::parse_url,
+        help = "At the end of the run, POST summary.json and the run's identity as JSON to this URL (token from ONSM_POST_TOKEN); failures only warn"
+    )]
+    pub post_summary: Option<String>,
+    #[cfg(feature = "http")]
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = post::DEFAULT_TIMEOUT_SECS,
+        help = "With --post-summary: timeout of each attempt"
+    )]
+    pub post_timeout: u64,
+    #[arg(
+        long,
+        default_value_t = pipeline::DEFAULT_CHUNK_SIZE,
+        help = "Pairs per evidence chunk with --low-memory"
+    )]
+    pub chunk_size: usize,
+    #[arg(
+        long,
+        help = "Write numt_density.bedgraph / nimt_density.bedgraph (called bp per window)"
+    )]
+    pub density_bedgraph: bool,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = density::DEFAULT_DENSITY_WINDOW,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Window size for --density-bedgraph"
+    )]
+    pub density_window: u32,
+    #[arg(
+        long,
+        help = "With --density-bedgraph, write empty windows as 0 instead of omitting them"
+    )]
+    pub density_zero_windows: bool,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = regions::DEFAULT_REGION_MERGE_GAP,
+        help = "Merge Likely_NUMT pairs within this many bp on a nuclear contig into one region (numt_regions.tsv/.bed)"
+    )]
+    pub region_merge_gap: u32,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = regions::DEFAULT_REGION_GAP,
+        help = "Cluster pairs of any call within this many bp on a nuclear contig into one region (regions.tsv)"
+    )]
+    pub region_gap: u32,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BOUNDS",
+        value_delimiter = ',',
+        help = "Also write the nuclear side of every pair as pairs_nuc.<bounds>.bed: outer (ends moved out by their CIs), inner (moved in), point (as aligned); comma-separated"
+    )]
+    pub bed_bounds: Vec<BedBounds>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Tier::All,
+        help = "Pairs the --bed-bounds BEDs take: all, or high (high-confidence calls only)"
+    )]
+    pub bed_tier: Tier,
+    #[arg(
+        long,
+        help = "Widen nuc_start_ci/nuc_end_ci by the spread of read clip positions around each nuclear end (one samtools view per end)"
+    )]
+    pub boundary_clips: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SummaryUnits::Percent,
+        help = "Units of the percentage metrics in summary.tsv/summary.json (ppm: e.g. nuclear_numt_ppm)"
+    )]
+    pub summary_units: SummaryUnits,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VERSION",
+        default_value_t = OutputCompat::Latest,
+        help = "Write pairs.tsv, classification.tsv and the summary with the columns of an earlier release only, for downstream parsers pinned to it"
+    )]
+    pub output_compat: OutputCompat,
+    #[arg(
+        long,
+        value_name = "TSV",
+        help = "Mito contig metadata: contig<TAB>type<TAB>circular(yes/no), e.g. plasmids or linear fragments; NIMT stats are split by type"
+    )]
+    pub mito_contig_meta: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "CONTIG:START-END",
+        value_parser = control_region::parse_spec,
+        help = "Control region (D-loop) of a mito contig, 0-based half-open, START > END across the origin; pairs inside get in_control_region (repeatable)"
+    )]
+    pub mito_control_region: Vec<ControlRegionSpec>,
+    #[arg(
+        long,
+        value_name = "W",
+        default_value_t = model::CONTROL_REGION_IDENTITY_WEIGHT,
+        help = "Scale the identity term of pairs inside a --mito-control-region by this (1: leave it alone)"
+    )]
+    pub control_region_identity_weight: f32,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = model::MAX_READS_PER_WINDOW,
+        help = "Estimate span fractions from a random sample of this many reads in deeper windows (0: use every read)"
+    )]
+    pub max_reads_per_window: u32,
+    #[arg(
+        long,
+        help = "Count supplementary alignments towards local depth (by default a read split across a junction counts once; span windows always keep them)"
+    )]
+    pub depth_include_supplementary: bool,
+    #[arg(
+        long,
+        value_name = "Q",
+        default_value_t = model::MIN_MAPQ,
+        help = "Count only reads at or above this mapping quality in span windows (reads across a recent NUMT map equally well to its mito source and get MAPQ 0)"
+    )]
+    pub span_mapq: u8,
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = model::JUNCTION_MIN_CLIP,
+        help = "Count reads clipped by at least this many bases where they end near a nuclear locus end as junction reads (0: don't count)"
+    )]
+    pub junction_min_clip: u32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = BamBackend::Samtools,
+        help = "Read the evidence windows with samtools, or natively from the indexed BAMs (no process per window)"
+    )]
+    pub bam_backend: BamBackend,
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = mapping_stats::DEFAULT_MIN_MAPPED_FRAC,
+        value_parser = pairing::parse_fraction,
+        help = "Warn (low_mapping_rate; an error with --strict) when less than this fraction of the reads maps to the nuclear assembly"
+    )]
+    pub min_mapped_frac: f32,
+    #[arg(
+        long,
+        value_name = "COLS",
+        value_delimiter = ',',
+        conflicts_with = "columns_preset",
+        help = "pairs.tsv columns to write, comma-separated (pair_id is always first)"
+    )]
+    pub columns: Vec<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ColumnsPreset::Full,
+        help = "Column set for pairs.tsv when --columns is not given"
+    )]
+    pub columns_preset: ColumnsPreset,
+    #[arg(
+        long,
+        help = "Add cost_ms and n_subprocess_calls (what each pair's evidence took) to pairs.tsv, and write run_stats.json"
+    )]
+    pub cost_columns: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputLevel::Standard,
+        help = "Files to write: summary (run_manifest.json, summary.tsv/.json, warnings.json only), standard, or full (also --cost-columns, --density-bedgraph and --debug-filters)"
+    )]
+    pub outputs: OutputLevel,
+    #[arg(
+        long,
+        value_name = "CN",
+        default_value_t = model::COLLAPSED_REPEAT_CN,
+        help = "Flag loci whose estimated nuclear copy number (rnuc, to the nearest 0.5) is at least this as possible_collapsed_repeat"
+    )]
+    pub collapsed_repeat_cn: f32,
+    #[arg(
+        long,
+        value_name = "FRAC",
+        value_parser = pairing::parse_fraction,
+        help = "Call loci whose nuclear side is more than this fraction soft-masked (lowercase) in the assembly Ambiguous, with reason softmasked_locus"
+    )]
+    pub max_softmask_frac: Option<f32>,
+    #[arg(
+        long,
+        value_name = "FRAC",
+        default_value_t = model::SHARED_MIN_SPAN,
+        value_parser = pairing::parse_fraction,
+        help = "Call an otherwise Ambiguous locus Shared_Support when reads span both sides at least this often..."
+    )]
+    pub shared_min_span: f32,
+    #[arg(
+        long,
+        value_name = "X",
+        default_value_t = model::SHARED_DEPTH_TOL,
+        help = "...and both depth ratios (rnuc, rmito) are within this of 1"
+    )]
+    pub shared_depth_tol: f32,
+    #[arg(
+        long,
+        help = "Count Shared_Support bp as NUMT (nuclear) and NIMT (mito) in the summary percentages"
+    )]
+    pub shared_in_percentages: bool,
+    #[arg(
+        long,
+        help = "Scale the span weight down by median read length / spanning window when reads are shorter than the window"
+    )]
+    pub span_read_len_scaling: bool,
+    #[arg(
+        long,
+        value_name = "BP",
+        help = "Reject reads whose median length is below this (default: 3000 for hifi, 1000 for ont)"
+    )]
+    pub min_median_read_len: Option<u32>,
+    #[arg(
+        long,
+        help = "Use reads below --min-median-read-len anyway, with span evidence disabled (calls on depth alone)"
+    )]
+    pub allow_short_reads: bool,
+    #[arg(
+        long,
+        help = "The mito was assembled from these reads: caveat NIMT calls (self_assembly_caveat), whose mito-side support is then partly circular"
+    )]
+    pub mito_assembled_from_these_reads: bool,
+    #[arg(
+        long,
+        help = "Fail the run (after writing outputs; see error.json) when warnings exceed the strict policy"
+    )]
+    pub strict: bool,
+    #[arg(
+        long,
+        value_name = "JSON",
+        requires = "strict",
+        help = "Per-category thresholds for --strict, overriding the defaults"
+    )]
+    pub strict_policy: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Exit with code 10 when the run completed but its warnings exceed the strict policy (ONSM_STATUS: OK_WITH_WARNINGS)"
+    )]
+    pub warnings_as_exit_code: bool,
+    #[arg(
+        long,
+        value_name = "HH:MM",
+        value_parser = shutdown::parse_runtime,
+        help = "Stop cleanly before this walltime (minus a margin of up to 5 min), checkpointing the evidence for --resume; exits with code 75"
+    )]
+    pub max_runtime: Option<Duration>,
+    #[arg(
+        long,
+        help = "Continue a run stopped by --max-runtime or SIGTERM/SIGINT (see resume_hint.json): reuse its alignments and the evidence already collected"
+    )]
+    pub resume: bool,
+    #[arg(
+        long,
+        help = "Start even though onsm.lock says another run is using --out (for a lock left by a run on another host, or a crash)"
+    )]
+    pub force_lock: bool,
+    #[arg(
+        long,
+        help = "Write into an --out that holds a previous run, replacing its outputs (index artifacts there are kept and reused)"
+    )]
+    pub overwrite: bool,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Where to reuse and write index artifacts (mito/nuclear .mmi and .fai, contig_lengths.json), each checked against its FASTA by a .source.json checksum sidecar [default: --out]"
+    )]
+    pub index_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Build minimap2 (.mmi) and samtools (.fai) indexes of both assemblies in the index directory, unless current ones are there, and map against them"
+    )]
+    pub build_indexes: bool,
+    /// For an application running the pipeline in-process: cancelling it from
+    /// another thread stops the run between stages or pairs, killing any
+    /// aligner or samtools process in flight (see `util::shutdown`).
+    #[arg(skip)]
+    pub cancel: CancelToken,
 }
 
 impl CmdClassify {
+    fn density_options(&self) -> Option<DensityOptions> {
+        self.density_bedgraph.then_some(DensityOptions {
+            window: self.density_window,
+            keep_empty: self.density_zero_windows,
+        })
+    }
+
+    /// FASTA validation plus the swapped-inputs heuristic (unless `--no-input-sanity`).
+    fn check_assemblies(&self, lengths: &LengthCache) -> Result<()> {
+        fasta::validate_fasta(&self.mito)?;
+        fasta::validate_fasta(&self.nuclear)?;
+        if !self.no_input_sanity {
+            fasta::check_not_swapped(
+                &lengths.report(&self.mito)?,
+                &lengths.report(&self.nuclear)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Refuse an --out that holds a previous run, unless it is being
+    /// replaced (`--overwrite`) or continued (`--resume`). Index artifacts
+    /// (see `indexes`) and other files are not a previous run.
+    fn check_previous_run(&self) -> Result<()> {
+        if self.overwrite || self.resume {
+            return Ok(());
+        }
+        if let Some(found) = PREVIOUS_RUN_FILES
+            .iter()
+            .find(|name| self.out.join(name).exists())
+        {
+            anyhow::bail!(
+                "{} holds a previous run ({found}); pass --overwrite to replace it, --resume to continue it, or choose another --out",
+                self.out.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Record how far an interrupted run got in `resume_hint.json` and
+    /// `progress.json`, leaving tmp/ as it is; other errors pass through.
+    fn checkpoint(
+        &self,
+        e: anyhow::Error,
+        started: Instant,
+        partial: &Path,
+        progress: &Progress,
+    ) -> anyhow::Error {
+        let Some(stop) = e.downcast_ref::<Interrupted>() else {
+            return e;
+        };
+        progress.finish(ProgressState::Interrupted);
+        let hint = ResumeHint {
+            reason: stop.reason,
+            pairs_done: stop.pairs_done,
+            pairs_total: stop.pairs_total,
+            elapsed_secs: started.elapsed().as_secs(),
+            partial_evidence: partial.to_path_buf(),
+            command: shutdown::resume_command(),
+        };
+        if let Err(err) = hint.save_to(&self.out) {
+            return err.context(e.to_string());
+        }
+        log::warn!(
+            "{e}. Evidence so far: {}; resume hint: {}",
+            partial.display(),
+            self.out.join(shutdown::RESUME_HINT_FILE).display()
+        );
+        e
+    }
+
     pub fn run(self) -> Result<()> {
+        let warnings_as_exit_code = self.warnings_as_exit_code;
+        status::finish(self.run_status(), warnings_as_exit_code)
+    }
+
+    /// The run itself; `warnings.json` is written by the time it returns.
+    pub fn run_status(mut self) -> Result<RunStatus> {
         // 0) Preflight
+        let started = Instant::now();
         fs::create_dir_all(&self.out)?;
-        logging::init_logging(&self.out)?;
+        // Before the log is truncated: a second run on this --out stops here
+        let lock = RunLock::acquire(&self.out, self.force_lock)?;
+        self.check_previous_run()?;
+        let _log = logging::init_logging(&self.out)?;
+        let _warnings = warnings::RunWarnings::begin(&self.out);
+        lock.report_takeover();
         log::info!("onsm classify started");
+        let hint_path = self.out.join(shutdown::RESUME_HINT_FILE);
+        if self.resume {
+            let hint = ResumeHint::load_from(&self.out)?;
+            log::info!(
+                "resuming a run stopped ({:?}) after evidence for {}/{} pairs",
+                hint.reason,
+                hint.pairs_done,
+                hint.pairs_total
+            );
+        } else if hint_path.exists() {
+            fs::remove_file(&hint_path)?;
+        }
+        let progress = Progress::start(&self.out, self.resume)?;
+        let strict = strict::policy(self.strict, self.strict_policy.as_deref())?;
+        self.pairing
+            .check_reciprocal_best(self.pairing_params.reciprocal_best)?;
+        self.output_compat.check_options(&[
+            (
+                "--summary-units",
+                self.summary_units != SummaryUnits::Percent,
+            ),
+            ("--mito-contig-meta", self.mito_contig_meta.is_some()),
+            ("--cost-columns", self.cost_columns),
+            ("--outputs full", self.outputs.full()),
+        ])?;
+        self.outputs.check_options(&[
+            ("--cost-columns", self.cost_columns),
+            ("--density-bedgraph", self.density_bedgraph),
+            ("--bed-bounds", !self.bed_bounds.is_empty()),
+            ("--track-against", self.track_against.is_some()),
+        ])?;
+        if self.outputs.full() {
+            self.cost_columns = true;
+            self.density_bedgraph = true;
+            self.debug_filters = true;
+        }
+        let pairs_columns = self
+            .output_compat
+            .pairs_columns(&self.columns, self.columns_preset)?
+            .with_cost(self.cost_columns);
+        let track_against = self
+            .track_against
+            .as_deref()
+            .map(TrackingReference::load)
+            .transpose()?;
 
-        fasta::validate_fasta(&self.mito)?;
-        fasta::validate_fasta(&self.nuclear)?;
+        // Contig lengths for the whole run: each FASTA is scanned at most once
+        let lengths = LengthCache::default();
+        let indexes = IndexDir::new(self.index_dir.as_deref().unwrap_or(&self.out));
+        indexes.seed_lengths(&lengths, &self.mito, &self.nuclear)?;
+        self.check_assemblies(&lengths)?;
+        let mito_meta = match &self.mito_contig_meta {
+            Some(path) => MitoContigMeta::load(path, &*lengths.lengths(&self.mito)?)?,
+            None => MitoContigMeta::default(),
+        };
+        let control_regions = ControlRegions::resolve(
+            &self.mito_control_region,
+            &*lengths.lengths(&self.mito)?,
+            &mito_meta,
+        )?;
+        // Nuclear contigs that are the mito itself (more may turn up in the PAF)
+        let mut excluded = if self.no_self_exclusion {
+            Vec::new()
+        } else {
+            self_exclusion::find_identical(&self.mito, &self.nuclear, &lengths)?
+        };
 
-        for r in &self.reads {
-            runfiles::ensure_exists(r)?;
+        // Directories and patterns become files here, for the manifest too
+        self.reads = reads::expand_inputs(&self.reads, self.reads_recursive)?;
+        let given_alignments = self.m2n_aln.as_ref().zip(self.n2m_aln.as_ref());
+        match given_alignments {
+            Some((m2n, n2m)) => {
+                runfiles::ensure_exists(m2n)?;
+                runfiles::ensure_exists(n2m)?;
+            }
+            None if self.aln_format != AlnFormat::Paf => anyhow::bail!(
+                "--aln-format {} needs --m2n-aln and --n2m-aln (onsm's own assembly alignments are PAF)",
+                self.aln_format.to_possible_value().unwrap().get_name()
+            ),
+            None => {}
         }
+        let read_stats = reads::scan(&self.reads, reads::READ_STATS_MAX_RECORDS)?;
+        log::info!(
+            "reads: {} looked at, median length {} bp, N50 {} bp",
+            read_stats.n_reads,
+            read_stats.median_len,
+            read_stats.n50
+        );
+        let span_evidence_disabled = reads::assess(
+            &read_stats,
+            self.min_median_read_len
+                .unwrap_or_else(|| reads::default_min_median_len(&self.platform)),
+            self.allow_short_reads,
+        )?;
 
         // Resolve binaries once
         let (mm2_bin, sam_bin) =
@@ -62,99 +719,484 @@ impl CmdClassify {
         if let Ok(v) = mapping::get_version(&sam_bin) {
             log::info!("samtools: {v}");
         }
+        // Fail now, not halfway through mapping or the coverage loop
+        let sam_caps = mapping::probe_samtools(&sam_bin);
+        sam_caps.require(mapping::MAPPING_FEATURES)?;
+        let mut depth_supplementary = self.depth_include_supplementary;
+        // the native backend leaves samtools to the mapping
+        let native = self.bam_backend == BamBackend::Native;
+        if !native && !depth_supplementary && !sam_caps.supports(SamtoolsFeature::DepthExclFlags) {
+            warnings::warn(
+                warnings::TOOL_VERSION,
+                format!(
+                    "samtools ≥ {} is needed for {}; supplementary alignments count towards depth",
+                    SamtoolsFeature::DepthExclFlags.min_version(),
+                    SamtoolsFeature::DepthExclFlags.describe()
+                ),
+            );
+            depth_supplementary = true;
+        }
+        if !native {
+            sam_caps.require(bam::evidence_features(depth_supplementary))?;
+        }
 
-        let threads = self.threads.unwrap_or_else(|| {
-            let n = std::thread::available_parallelism()
-                .map(|n| n.get())
-                .unwrap_or(4);
-            n.min(16)
-        });
-        log::info!("Threads: {threads}");
+        let threads = cpus::resolve_threads(self.threads);
 
-        let manifest = model::RunManifest::new(
+        let mut manifest = model::RunManifest::new(
             &self.mito,
             &self.nuclear,
             &self.reads,
             &self.platform,
             threads,
-            model::MIN_ID,
-            model::MIN_LEN,
+            self.min_id,
+            self.min_len,
             model::MERGE_GAP,
             model::FLANK_BP,
             model::WIN_BP,
         );
+        let mm2_asm = AsmMapOptions {
+            secondary: self.mm2_secondary,
+            max_secondary: self.mm2_max_secondary,
+        };
+        if mm2_asm.secondary == Some(Mm2Secondary::No) && mm2_asm.max_secondary.is_some() {
+            warnings::warn(
+                warnings::IGNORED_OPTION,
+                "--mm2-max-secondary has no effect with --mm2-secondary no",
+            );
+        }
+        manifest.pairing = self.pairing;
+        manifest.pairing_params = self.pairing_params.clone();
+        manifest.split_indel = self.split_indel;
+        manifest.mm2_asm = mm2_asm;
+        manifest.aln_format = self.aln_format;
+        manifest.aln_identity = self.aln_identity;
+        manifest.min_paf_mapq = self.min_paf_mapq;
+        manifest.keep_secondary = self.keep_secondary;
+        manifest.require_reciprocal = self.require_reciprocal;
+        manifest.boundary_clips = self.boundary_clips;
+        manifest.output_compat = self.output_compat;
+        manifest.m2n_aln = self.m2n_aln.clone();
+        manifest.n2m_aln = self.n2m_aln.clone();
+        manifest.read_stats = Some(read_stats);
+        manifest.span_evidence_disabled = span_evidence_disabled;
+        manifest.max_reads_per_window = self.max_reads_per_window;
+        manifest.depth_supplementary = depth_supplementary;
+        manifest.min_span_mapq = self.span_mapq;
+        manifest.junction_min_clip = self.junction_min_clip;
+        manifest.mito_contig_meta = mito_meta;
+        manifest.mito_control_regions = control_regions;
+        manifest.genome_sizes = Some(GenomeSizes::read(&lengths, &self.mito, &self.nuclear)?);
+        indexes.save_contig_lengths(&lengths, &self.mito, &self.nuclear)?;
+        manifest.build_info = Some(
+            VersionInfo::build()
+                .with_tool("minimap2", &mm2_bin)
+                .with_tool("samtools", &sam_bin),
+        );
         model::RunManifest::save_to(&self.out, &manifest)?;
 
         // 1) Asm↔Asm → PAF
         let tmp = self.out.join("tmp");
         fs::create_dir_all(&tmp)?;
-        let paf_m2n = tmp.join("mito_to_nuc.paf");
-        let paf_n2m = tmp.join("nuc_to_mito.paf");
-        mapping::map_asm_to_asm(&mm2_bin, &self.mito, &self.nuclear, &paf_m2n, threads)?;
-        mapping::map_asm_to_asm(&mm2_bin, &self.nuclear, &self.mito, &paf_n2m, threads)?;
-
-        // 2) reads→ref → BAM
-        let bam_r2n = tmp.join("reads_to_nuc.bam");
-        let bam_r2m = tmp.join("reads_to_mito.bam");
-        mapping::map_reads_to_ref(
-            &mm2_bin,
-            &sam_bin,
-            &self.platform,
-            &self.reads,
-            &self.nuclear,
-            &bam_r2n,
-            threads,
-        )?;
-        mapping::map_reads_to_ref(
-            &mm2_bin,
-            &sam_bin,
-            &self.platform,
-            &self.reads,
-            &self.mito,
-            &bam_r2m,
-            threads,
-        )?;
+        let keep = if self.keep_tmp {
+            KeepPolicy::All
+        } else {
+            self.keep
+        };
+        let TmpArtifacts {
+            paf_m2n,
+            paf_n2m,
+            bam_r2n,
+            bam_r2m,
+            evidence_cache,
+            evidence_partial,
+            window_cache,
+            ..
+        } = TmpArtifacts::in_dir(&tmp);
+        // Assembly alignments: given, or minimap2's in tmp/
+        let (aln_m2n, aln_n2m) = match given_alignments {
+            Some((m2n, n2m)) => (m2n.clone(), n2m.clone()),
+            None => (paf_m2n.clone(), paf_n2m.clone()),
+        };
+        let mapped = [&aln_m2n, &aln_n2m, &bam_r2n, &bam_r2m]
+            .iter()
+            .all(|p| p.exists());
+        // A stop outside the evidence loop (a cancel, the deadline) also
+        // leaves a resume hint
+        let shutdown = Shutdown::new(started, self.max_runtime).with_cancel(self.cancel.clone());
+        let checkpoint = |e| self.checkpoint(e, started, &evidence_partial, &progress);
+        shutdown.stop_point(0, 0).map_err(checkpoint)?;
+        progress.stage(Stage::Mapping, 0, None);
+        if self.resume && mapped {
+            log::info!("--resume: reusing the alignments in {}", tmp.display());
+        } else {
+            if self.build_indexes {
+                for (side, fasta) in [(Side::Mito, &self.mito), (Side::Nuclear, &self.nuclear)] {
+                    indexes.build_fai(&sam_bin, side, fasta)?;
+                    indexes
+                        .build_mmi(&mm2_bin, side, fasta, threads, &self.cancel)
+                        .map_err(checkpoint)?;
+                }
+            }
+            if given_alignments.is_none() {
+                // each assembly as a target: its current minimap2 index, else itself
+                let target = |side, fasta: &PathBuf| -> Result<PathBuf> {
+                    Ok(indexes.mmi(side, fasta)?.unwrap_or_else(|| fasta.clone()))
+                };
+                mapping::map_asm_to_asm(
+                    &mm2_bin,
+                    &self.mito,
+                    &target(Side::Nuclear, &self.nuclear)?,
+                    &paf_m2n,
+                    threads,
+                    &mm2_asm,
+                    &self.cancel,
+                )
+                .map_err(checkpoint)?;
+                mapping::map_asm_to_asm(
+                    &mm2_bin,
+                    &self.nuclear,
+                    &target(Side::Mito, &self.mito)?,
+                    &paf_n2m,
+                    threads,
+                    &mm2_asm,
+                    &self.cancel,
+                )
+                .map_err(checkpoint)?;
+            }
 
-        // 3) Parse PAF + pair
-        let m2n = paf::read_paf(&paf_m2n, model::MIN_ID, model::MIN_LEN)?;
-        let n2m = paf::read_paf(&paf_n2m, model::MIN_ID, model::MIN_LEN)?;
-        let pairs = paf::pair_and_merge(&m2n, n2m, model::MERGE_GAP)?;
-        log::info!("paired {} candidate loci", pairs.len());
+            // 2) reads→ref → BAM
+            mapping::map_reads_to_ref(
+                &mm2_bin,
+                &sam_bin,
+                &self.platform,
+                &self.reads,
+                &self.nuclear,
+                &bam_r2n,
+                threads,
+                &sam_caps,
+                &self.cancel,
+            )
+            .map_err(checkpoint)?;
+            mapping::map_reads_to_ref(
+                &mm2_bin,
+                &sam_bin,
+                &self.platform,
+                &self.reads,
+                &self.mito,
+                &bam_r2m,
+                threads,
+                &sam_caps,
+                &self.cancel,
+            )
+            .map_err(checkpoint)?;
+        }
+        // Did the reads map at all? (counted again unless resuming)
+        let stored = if self.resume && mapped {
+            MappingStats::load_from(&self.out)?
+        } else {
+            None
+        };
+        let mapping_stats = match stored {
+            Some(s) => MappingStats {
+                min_mapped_frac: self.min_mapped_frac,
+                ..s
+            },
+            None => MappingStats::collect(
+                &sam_bin,
+                &bam_r2n,
+                &bam_r2m,
+                threads,
+                self.min_mapped_frac,
+                &self.cancel,
+            )
+            .map_err(checkpoint)?,
+        };
+        mapping_stats.check();
+        if self.outputs.per_pair() {
+            mapping_stats.save_to(&self.out)?;
+        }
+        manifest.mapping_stats = Some(mapping_stats);
+        model::RunManifest::save_to(&self.out, &manifest)?;
 
-        // 4) Coverage & spans (samtools)
-        let (coverage, spans) = bam::compute_coverage_and_spans_with_tools(
-            &bam_r2n,
-            &bam_r2m,
-            &pairs,
-            model::FLANK_BP,
-            model::WIN_BP,
+        // 3) Parse PAF + pair
+        shutdown.stop_point(0, 0).map_err(checkpoint)?;
+        progress.stage(Stage::Pairing, 0, None);
+        let stage = pipeline::PairingStage {
+            filter: paf::PafFilter {
+                min_mapq: self.min_paf_mapq,
+                secondary: SecondaryPolicy::keeping(self.keep_secondary),
+                error_policy: self.paf_error_policy,
+                format: self.aln_format,
+                identity: self.aln_identity,
+                max_error_frac: self.paf_max_error_frac,
+                split_indel: self.split_indel,
+                ..paf::PafFilter::new(self.min_id, self.min_len)
+            },
+            method: self.pairing,
+            params: self.pairing_params.clone(),
+            aligner: given_alignments.is_none().then_some(&mm2_asm),
+            rejected: self.debug_filters.then(|| tmp.join(tmpfiles::PAF_REJECTED)),
+            require_reciprocal: self.require_reciprocal,
+            outputs: self.outputs,
+        };
+        let pafs = stage.read(&aln_m2n, &aln_n2m)?;
+        if !self.no_self_exclusion {
+            let nuc_lens = lengths.lengths(&self.nuclear)?;
+            for e in self_exclusion::find_by_alignment(
+                &pafs.m2n,
+                &*lengths.lengths(&self.mito)?,
+                &nuc_lens,
+            ) {
+                if !excluded.iter().any(|x| x.nuclear == e.nuclear) {
+                    excluded.push(e);
+                }
+            }
+            if !excluded.is_empty() {
+                self_exclusion::log_excluded(&excluded, &nuc_lens);
+                if self.outputs.per_pair() {
+                    self_exclusion::write_excluded(&self.out, &excluded)?;
+                }
+                manifest.excluded_nuclear = excluded.iter().map(|e| e.nuclear.clone()).collect();
+                model::RunManifest::save_to(&self.out, &manifest)?;
+            }
+        }
+        // Was the mito assembled from these reads? Declared, or sampled from
+        // the read alignments
+        let read_identity = self_assembly::sample(
             &sam_bin,
+            &bam_r2m,
+            &bam_r2n,
+            &*lengths.lengths(&self.mito)?,
+            &*lengths.lengths(&self.nuclear)?,
+            &manifest.excluded_nuclear,
+            manifest.depth_supplementary,
         )?;
+        manifest.self_assembly =
+            self_assembly::assess(self.mito_assembled_from_these_reads, &read_identity);
+        manifest.read_identity = Some(read_identity);
+        model::RunManifest::save_to(&self.out, &manifest)?;
 
-        // 5) Score & classify (fixed params)
-        let weights = Weights::default();
-        let params = ClassifyParams::default();
-        let (pairs_tsv, classes_tsv) =
-            scoring::classify_pairs(&pairs, &coverage, &spans, weights, params)?;
-
-        // 6) Write outputs
-        fs::write(self.out.join("pairs.tsv"), pairs_tsv)?;
-        fs::write(self.out.join("classification.tsv"), classes_tsv.clone())?;
-        serde_json::to_writer_pretty(fs::File::create(self.out.join("coverage.json"))?, &coverage)?;
+        let mut pairs = stage.pair(pafs, &manifest.excluded_nuclear, &self.out)?;
+        pipeline::check_pair_count(&pairs, self.max_pairs, self.min_id, self.min_len)?;
+        if self.boundary_clips {
+            boundary::add_clip_dispersion(&mut pairs, &sam_bin, &bam_r2n, None)?;
+        }
 
-        let calls = summary::parse_calls_tsv_str(&classes_tsv);
+        // Assembly gaps in the nuclear genome, to flag loci that span them
+        let gaps = fasta::GapIndex::scan(&self.nuclear)?;
 
-        let summary_tbl = summary::compute_percentages(&self.mito, &self.nuclear, &pairs, &calls)?;
-        summary::write_summary_tsv(&self.out.join("summary.tsv"), &summary_tbl)?;
+        // 4) Coverage & spans (samtools), 5) score & classify (fixed params), 6) write outputs
+        let ctx = pipeline::OutputContext {
+            out_dir: &self.out,
+            mito_fa: &self.mito,
+            nuc_fa: &self.nuclear,
+            lengths: &lengths,
+            genome_sizes: manifest.genome_sizes.as_ref(),
+            weights: Weights::default(),
+            params: ClassifyParams {
+                collapsed_repeat_cn: self.collapsed_repeat_cn,
+                max_softmask_frac: self.max_softmask_frac,
+                shared_min_span: self.shared_min_span,
+                shared_depth_tol: self.shared_depth_tol,
+                shared_in_percentages: self.shared_in_percentages,
+                span_window: 2 * model::WIN_BP,
+                span_read_len_scaling: self.span_read_len_scaling,
+                span_evidence_disabled,
+                self_assembly_caveat: manifest.self_assembly.is_caveated(),
+                control_region_identity_weight: self.control_region_identity_weight,
+                ..ClassifyParams::default()
+            },
+            density: self.density_options(),
+            pairs_columns: &pairs_columns,
+            region_merge_gap: self.region_merge_gap,
+            region_gap: self.region_gap,
+            summary_units: self.summary_units,
+            mito_meta: &manifest.mito_contig_meta,
+            evidence_cache: matches!(keep, KeepPolicy::Evidence | KeepPolicy::All)
+                .then_some(evidence_cache.as_path()),
+            excluded_nuclear: &manifest.excluded_nuclear,
+            gaps: &gaps,
+            control_regions: &manifest.mito_control_regions,
+            evidence_params: EvidenceParams::new(model::FLANK_BP, model::WIN_BP)
+                .with_depth_supplementary(manifest.depth_supplementary)
+                .with_min_mapq(manifest.min_span_mapq)
+                .with_junction_min_clip(manifest.junction_min_clip),
+            ignore_evidence_params: false,
+            bed_bounds: &self.bed_bounds,
+            bed_tier: self.bed_tier,
+            outputs: self.outputs,
+            output_compat: self.output_compat,
+        };
+        let cache = if self.no_cache {
+            None
+        } else {
+            Some(WindowCache::open(
+                &window_cache,
+                window_cache::DEFAULT_MAX_FILES,
+            )?)
+        };
+        let pairs_total = pairs.len();
+        shutdown.stop_point(0, pairs_total).map_err(checkpoint)?;
+        shutdown::install_signal_handlers();
+        let summary = {
+            let samtools = pipeline::SamtoolsEvidence {
+                bam_reads_to_nuc: bam_r2n,
+                bam_reads_to_mito: bam_r2m,
+                samtools: sam_bin,
+                flank: model::FLANK_BP,
+                win: model::WIN_BP,
+                max_reads: self.max_reads_per_window,
+                depth_supplementary: manifest.depth_supplementary,
+                min_span_mapq: manifest.min_span_mapq,
+                junction_min_clip: manifest.junction_min_clip,
+                mito_meta: manifest.mito_contig_meta.clone(),
+                calls: Default::default(),
+                native: native.then(IndexedBams::default),
+                batched: Default::default(),
+                cram_references: Vec::new(),
+                contig_lengths: manifest.genome_sizes.as_ref().map(|g| g.contigs.clone()),
+                cache,
+            };
+            let evidence = pipeline::CheckpointedEvidence::open(
+                &evidence_partial,
+                &samtools,
+                &shutdown,
+                pairs_total,
+                self.resume,
+            )?
+            .with_progress(&progress);
+            progress.stage(
+                Stage::Evidence,
+                evidence.pairs_done() as u64,
+                Some(pairs_total as u64),
+            );
+            let written = if self.low_memory {
+                pipeline::write_low_memory(&ctx, pairs, &evidence, &tmp, self.chunk_size)
+            } else {
+                pipeline::collect_evidence(&pairs, &evidence).and_then(|(coverage, spans)| {
+                    shutdown.stop_point(pairs_total, pairs_total)?;
+                    progress.stage(Stage::Scoring, 0, None);
+                    pipeline::write_in_memory(&ctx, &pairs, &coverage, &spans)
+                })
+            };
+            written.map_err(checkpoint)?
+        };
+        fs::remove_file(&evidence_partial)?;
+        if hint_path.exists() {
+            fs::remove_file(&hint_path)?;
+        }
 
         // 7) Cleanup
-        if !self.keep_tmp {
-            let _ = fs::remove_dir_all(&tmp);
-        } else {
-            log::info!("Keeping tmp/ for reuse: {}", tmp.display());
+        tmpfiles::apply_keep_policy(&tmp, keep)?;
+        if keep != KeepPolicy::None {
+            log::info!("Keeping tmp/ for reuse ({keep:?}): {}", tmp.display());
+        }
+        if let Some(reference) = &track_against {
+            reference.track_run(&self.out, self.track_min_overlap)?;
+        }
+        #[cfg(feature = "http")]
+        if let Some(url) = &self.post_summary {
+            let payload = post::Payload::new(
+                "classify",
+                &self.out,
+                &manifest,
+                &self.out,
+                &summary,
+                self.summary_units,
+            );
+            post::post_summary(
+                &post::PostOptions::from_env(url, self.post_timeout),
+                &payload,
+            );
         }
+        let report = warnings::current_report();
+        if let Some(policy) = &strict {
+            policy.enforce(&self.out, &report, summary.n_pairs)?;
+        }
+        let status = RunStatus::of_run(&strict.unwrap_or_default(), &report, summary.n_pairs);
 
+        progress.finish(ProgressState::Finished);
         log::info!("done.");
-        Ok(())
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdClassify,
+    }
+
+    fn cmd(mito: &std::path::Path, nuc: &std::path::Path, extra: &[&str]) -> CmdClassify {
+        let mut argv = vec![
+            "onsm".to_string(),
+            "--mito".into(),
+            mito.display().to_string(),
+            "--nuclear".into(),
+            nuc.display().to_string(),
+            "--reads".into(),
+            "r.fq".into(),
+            "--platform".into(),
+            "hifi".into(),
+            "--out".into(),
+            "out".into(),
+        ];
+        argv.extend(extra.iter().map(|s| s.to_string()));
+        Wrap::parse_from(argv).cmd
+    }
+
+    #[test]
+    fn swapped_inputs_rejected_unless_overridden() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let small = dir.path().join("small.fa");
+        let big = dir.path().join("big.fa");
+        fs::write(&small, format!(">mt\n{}\n", "A".repeat(1_000))).unwrap();
+        fs::write(&big, format!(">chr1\n{}\n", "C".repeat(50_000))).unwrap();
+
+        assert!(cmd(&small, &big, &[])
+            .check_assemblies(&LengthCache::default())
+            .is_ok());
+        let e = cmd(&big, &small, &[])
+            .check_assemblies(&LengthCache::default())
+            .unwrap_err();
+        assert!(e.to_string().contains("look swapped"), "{e}");
+        assert!(cmd(&big, &small, &["--no-input-sanity"])
+            .check_assemblies(&LengthCache::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn previous_run_guarded_but_index_artifacts_are_not_one() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        let check = |extra: &[&str]| {
+            let mut c = cmd(Path::new("mt.fa"), Path::new("nuc.fa"), extra);
+            c.out = out.clone();
+            c.check_previous_run()
+        };
+        assert!(check(&[]).is_ok());
+        for name in [
+            "nuclear.mmi",
+            "nuclear.mmi.source.json",
+            "mito.fai",
+            "contig_lengths.json",
+            "onsm.log",
+        ] {
+            fs::write(out.join(name), "").unwrap();
+        }
+        assert!(check(&[]).is_ok());
+
+        fs::write(out.join("run_manifest.json"), "{}").unwrap();
+        let e = check(&[]).unwrap_err().to_string();
+        assert!(e.contains("previous run (run_manifest.json)"), "{e}");
+        assert!(e.contains("--overwrite"), "{e}");
+        assert!(check(&["--overwrite"]).is_ok());
+        assert!(check(&["--resume"]).is_ok());
     }
 }
@@ -0,0 +1,860 @@
+//! `onsm doctor` — diagnose a run directory that failed or looks wrong.
+//!
+//! Nothing is rerun. The diagnosis is built from what the run left behind:
+//!   * which artifacts exist (and whether they are empty), giving the last
+//!     stage that completed;
+//!   * run_manifest.json, to find the inputs again and check that the PAF
+//!     contig names still match their FASTA headers;
+//!   * any captured logs (`onsm.log`, `*.log`, `*.err`, `*stderr*` in the run
+//!     directory or tmp/), matched against known failure messages;
+//!   * warnings.json, the run's de-duplicated warnings, shown as-is;
+//!   * with the `schema` feature, each JSON artifact checked against the
+//!     schema of this build (see `crate::schema`).
+//!
+//! Causes are ranked by how directly the evidence points at them.
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use fs_err as fs;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::io::tmpfiles::{EVIDENCE_CACHE, PAF_MITO_TO_NUC, PAF_NUC_TO_MITO};
+use crate::model::RunManifest;
+use crate::util::warnings::WarningsReport;
+
+/// Pipeline stages in the order `classify` runs them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Preflight,
+    AsmAlignment,
+    ReadMapping,
+    Pairing,
+    Outputs,
+    Summary,
+}
+
+impl Stage {
+    const ALL: [Stage; 6] = [
+        Stage::Preflight,
+        Stage::AsmAlignment,
+        Stage::ReadMapping,
+        Stage::Pairing,
+        Stage::Outputs,
+        Stage::Summary,
+    ];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Stage::Preflight => "preflight (inputs, tools, run_manifest.json)",
+            Stage::AsmAlignment => "assembly alignment (minimap2 → tmp/*.paf)",
+            Stage::ReadMapping => "read mapping (minimap2 | samtools sort → tmp/*.bam)",
+            Stage::Pairing => "PAF filtering and pairing",
+            Stage::Outputs => "evidence, scoring and per-locus outputs",
+            Stage::Summary => "summary",
+        }
+    }
+
+    /// Files a finished stage leaves behind, relative to the run directory.
+    fn artifacts(self) -> &'static [&'static str] {
+        match self {
+            Stage::Preflight => &["run_manifest.json"],
+            Stage::AsmAlignment => &[TMP_PAF_M2N, TMP_PAF_N2M],
+            Stage::ReadMapping => &[TMP_BAM_R2N, TMP_BAM_R2M],
+            Stage::Pairing => &["paf_filter_stats.json"],
+            Stage::Outputs => &[
+                "pairs.tsv",
+                "classification.tsv",
+                "coverage.json",
+                "results.json",
+            ],
+            Stage::Summary => &["summary.tsv", "summary.json"],
+        }
+    }
+
+    fn next(self) -> Option<Stage> {
+        Stage::ALL.get(self as usize + 1).copied()
+    }
+}
+
+// tmp/ artifacts relative to the run directory (see io::tmpfiles)
+const TMP_PAF_M2N: &str = "tmp/mito_to_nuc.paf";
+const TMP_PAF_N2M: &str = "tmp/nuc_to_mito.paf";
+const TMP_BAM_R2N: &str = "tmp/reads_to_nuc.bam";
+const TMP_BAM_R2M: &str = "tmp/reads_to_mito.bam";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactStatus {
+    pub path: String,
+    pub stage: Stage,
+    /// Size in bytes; `None` if the file does not exist.
+    pub size: Option<u64>,
+}
+
+impl ArtifactStatus {
+    fn present(&self) -> bool {
+        self.size.is_some_and(|s| s > 0)
+    }
+}
+
+/// One likely cause with what to do about it. Higher `score` = more likely.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub score: u8,
+    pub cause: String,
+    pub next_step: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnosis {
+    pub run_dir: PathBuf,
+    pub artifacts: Vec<ArtifactStatus>,
+    /// Latest stage whose artifacts are all present (`None`: not even preflight).
+    pub last_completed: Option<Stage>,
+    /// Ranked, most likely first.
+    pub findings: Vec<Finding>,
+    /// Contents of warnings.json, if the run wrote one.
+    pub warnings: Option<WarningsReport>,
+}
+
+/// A known failure message in a captured log.
+struct LogSignature {
+    needles: &'static [&'static str],
+    score: u8,
+    cause: &'static str,
+    next_step: &'static str,
+}
+
+const LOG_SIGNATURES: &[LogSignature] = &[
+    LogSignature {
+        needles: &["no space left on device", "disk quota exceeded"],
+        score: 95,
+        cause: "the disk filled up while writing",
+        next_step: "free space or point --out at a larger volume; the read BAMs in tmp/ \
+                    are the largest files, and --keep evidence drops them once a run finishes",
+    },
+    LogSignature {
+        needles: &["please upgrade samtools"],
+        score: 90,
+        cause: "samtools is too old for the features onsm needs",
+        next_step: "install a newer samtools, or pass --samtools with the path to one",
+    },
+    LogSignature {
+        needles: &[
+            "not found in path",
+            "minimap2 not found at",
+            "samtools not found at",
+        ],
+        score: 90,
+        cause: "minimap2 or samtools could not be found",
+        next_step: "install the missing tool, or pass --minimap2/--samtools explicitly",
+    },
+    LogSignature {
+        needles: &["look swapped"],
+        score: 85,
+        cause: "--mito and --nuclear look swapped",
+        next_step: "swap the arguments, or pass --no-input-sanity if the inputs are intended",
+    },
+    LogSignature {
+        needles: &[
+            "cannot allocate memory",
+            "out of memory",
+            "memory allocation failed",
+            "killed",
+        ],
+        score: 85,
+        cause: "the run ran out of memory (or was killed by the scheduler)",
+        next_step: "rerun with --low-memory (and a smaller --chunk-size), fewer --threads, \
+                    or request more memory",
+    },
+    LogSignature {
+        needles: &["malformed paf", "paf lines are malformed"],
+        score: 80,
+        cause: "an assembly PAF is truncated or corrupted",
+        next_step: "rerun classify to regenerate tmp/*.paf; --paf-error-policy skip tolerates \
+                    a few bad lines",
+    },
+    LogSignature {
+        needles: &["fasta not found", "open fasta"],
+        score: 75,
+        cause: "an input FASTA could not be read",
+        next_step: "check the --mito/--nuclear paths and that the files are readable",
+    },
+    LogSignature {
+        needles: &["reads→ref pipeline failed", "samtools index failed"],
+        score: 70,
+        cause: "read mapping (minimap2 | samtools sort/index) failed",
+        next_step: "check --reads and --platform, and rerun the minimap2 | samtools sort \
+                    pipeline by hand to see its stderr",
+    },
+    LogSignature {
+        needles: &["minimap2 (asm-asm) failed"],
+        score: 70,
+        cause: "assembly-to-assembly minimap2 failed",
+        next_step: "rerun minimap2 -x asm10 on the two assemblies by hand to see its stderr",
+    },
+    LogSignature {
+        needles: &["samtools depth failed", "samtools view failed"],
+        score: 65,
+        cause: "samtools could not read a BAM while collecting evidence",
+        next_step: "check tmp/*.bam with samtools quickcheck; a truncated BAM means the \
+                    mapping step was interrupted, so rerun classify",
+    },
+];
+
+/// At most this many PAF lines are read for the header check.
+const HEADER_CHECK_LINES: usize = 10_000;
+
+#[derive(Args, Debug)]
+pub struct CmdDoctor {
+    /// Run directory from `onsm classify`/`onsm reuse` (complete or not)
+    #[arg(long, value_name = "DIR")]
+    pub from: PathBuf,
+
+    /// samtools used to count reads in the BAMs (default: from PATH, if present)
+    #[arg(long)]
+    pub samtools: Option<PathBuf>,
+
+    /// Print the diagnosis as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl CmdDoctor {
+    pub fn run(self) -> Result<()> {
+        let samtools = self
+            .samtools
+            .clone()
+            .or_else(|| which::which("samtools").ok());
+        let d = diagnose(&self.from, samtools.as_deref())?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&d)?);
+        } else {
+            print!("{}", d.to_text());
+        }
+        Ok(())
+    }
+}
+
+/// Inspect `run_dir` and rank the likely causes of failure.
+pub fn diagnose(run_dir: &Path, samtools: Option<&Path>) -> Result<Diagnosis> {
+    if !run_dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", run_dir.display()));
+    }
+    let artifacts: Vec<ArtifactStatus> = Stage::ALL
+        .iter()
+        .flat_map(|&stage| {
+            stage.artifacts().iter().map(move |rel| ArtifactStatus {
+                path: rel.to_string(),
+                stage,
+                size: fs::metadata(run_dir.join(rel)).ok().map(|m| m.len()),
+            })
+        })
+        .collect();
+    let stage_done = |s: Stage| {
+        artifacts
+            .iter()
+            .filter(|a| a.stage == s)
+            .all(ArtifactStatus::present)
+    };
+    let last_completed = Stage::ALL.iter().rev().copied().find(|&s| stage_done(s));
+
+    let mut findings = Vec::new();
+    log_findings(run_dir, &mut findings)?;
+    artifact_findings(run_dir, &artifacts, samtools, &mut findings);
+
+    let manifest = RunManifest::load_from(run_dir).ok();
+    if let Some(m) = &manifest {
+        input_findings(run_dir, m, &mut findings);
+    }
+    #[cfg(feature = "schema")]
+    schema_findings(run_dir, manifest.as_ref(), &mut findings);
+
+    if last_completed != Some(Stage::Summary) {
+        findings.push(stopped_at(last_completed, manifest.is_some()));
+    }
+
+    // stable sort: equal scores keep discovery order (logs, artifacts, inputs)
+    findings.sort_by_key(|f| std::cmp::Reverse(f.score));
+    Ok(Diagnosis {
+        run_dir: run_dir.to_path_buf(),
+        artifacts,
+        last_completed,
+        findings,
+        warnings: WarningsReport::load_from(run_dir).ok().flatten(),
+    })
+}
+
+/// Captured logs in the run directory and tmp/.
+fn log_files(run_dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    for dir in [run_dir.to_path_buf(), run_dir.join("tmp")] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for e in entries.flatten() {
+            let name = e.file_name().to_string_lossy().to_lowercase();
+            if e.path().is_file()
+                && (name.ends_with(".log") || name.ends_with(".err") || name.contains("stderr"))
+            {
+                out.push(e.path());
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
+fn log_findings(run_dir: &Path, findings: &mut Vec<Finding>) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for path in log_files(run_dir) {
+        let rdr = BufReader::new(fs::File::open(&path)?);
+        for (i, line) in rdr.lines().enumerate() {
+            let line = line?;
+            let lower = line.to_lowercase();
+            for (k, sig) in LOG_SIGNATURES.iter().enumerate() {
+                if seen.contains(&k) || !sig.needles.iter().any(|n| lower.contains(n)) {
+                    continue;
+                }
+                seen.insert(k);
+                findings.push(Finding {
+                    score: sig.score,
+                    cause: format!(
+                        "{} ({}:{}: {})",
+                        sig.cause,
+                        rel(run_dir, &path),
+                        i + 1,
+                        line.trim()
+                    ),
+                    next_step: sig.next_step.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn artifact_findings(
+    run_dir: &Path,
+    artifacts: &[ArtifactStatus],
+    samtools: Option<&Path>,
+    findings: &mut Vec<Finding>,
+) {
+    for a in artifacts {
+        let is_paf = a.path == TMP_PAF_M2N || a.path == TMP_PAF_N2M;
+        let is_bam = a.path == TMP_BAM_R2N || a.path == TMP_BAM_R2M;
+        match a.size {
+            Some(0) if is_paf => findings.push(Finding {
+                score: 80,
+                cause: format!(
+                    "{} is empty: minimap2 found no mito↔nuclear alignments, or stopped before \
+                     writing any",
+                    a.path
+                ),
+                next_step: "check that --mito and --nuclear are assemblies of the same sample; \
+                            rerun minimap2 -x asm10 by hand to see its stderr"
+                    .into(),
+            }),
+            Some(0) if is_bam => findings.push(Finding {
+                score: 80,
+                cause: format!(
+                    "{} is empty: minimap2 | samtools sort stopped before writing",
+                    a.path
+                ),
+                next_step: "rerun classify; if it recurs, run the mapping pipeline by hand to \
+                            see which tool fails"
+                    .into(),
+            }),
+            Some(_) if is_bam => {
+                if let Some(n) = samtools.and_then(|s| count_reads(s, &run_dir.join(&a.path))) {
+                    if n == 0 {
+                        findings.push(Finding {
+                            score: 85,
+                            cause: format!("{} has no reads", a.path),
+                            next_step: "check that --reads are from this sample and that \
+                                        --platform (hifi|ont) matches them"
+                                .into(),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `samtools view -c`; `None` if samtools fails (e.g. a truncated BAM).
+fn count_reads(samtools: &Path, bam: &Path) -> Option<u64> {
+    let out = Command::new(samtools)
+        .arg("view")
+        .arg("-c")
+        .arg(bam)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
+fn input_findings(run_dir: &Path, m: &RunManifest, findings: &mut Vec<Finding>) {
+    let mut missing = Vec::new();
+    for (flag, p) in [("--mito", &m.mito), ("--nuclear", &m.nuclear)] {
+        if !p.exists() {
+            missing.push(format!("{flag} {}", p.display()));
+        }
+    }
+    if !missing.is_empty() {
+        findings.push(Finding {
+            score: 60,
+            cause: format!(
+                "input FASTA recorded in run_manifest.json no longer exists: {}",
+                missing.join(", ")
+            ),
+            next_step: "restore the file at that path; reuse and the header checks need it".into(),
+        });
+        return;
+    }
+
+    // PAF names must match the FASTA headers the manifest points at
+    let (Ok(mito), Ok(nuc)) = (
+        crate::io::fasta::contig_lengths(&m.mito),
+        crate::io::fasta::contig_lengths(&m.nuclear),
+    ) else {
+        return;
+    };
+    let tmp = run_dir.join("tmp");
+    for (name, qry, tgt) in [
+        (PAF_MITO_TO_NUC, &mito, &nuc),
+        (PAF_NUC_TO_MITO, &nuc, &mito),
+    ] {
+        let Some((q, t)) = paf_names(&tmp.join(name)) else {
+            continue;
+        };
+        let unknown: Vec<&String> = q
+            .iter()
+            .filter(|n| !qry.contains_key(*n))
+            .chain(t.iter().filter(|n| !tgt.contains_key(*n)))
+            .collect();
+        if let Some(first) = unknown.first() {
+            findings.push(Finding {
+                score: 75,
+                cause: format!(
+                    "tmp/{name} names {} contig(s) missing from the input FASTA headers \
+                     (e.g. {first}): the assemblies were renamed or replaced after alignment",
+                    unknown.len()
+                ),
+                next_step: "rerun classify from scratch; reuse cannot recover from changed \
+                            inputs"
+                    .into(),
+            });
+        }
+    }
+}
+
+/// JSON artifacts that do not parse or do not match this build's schema.
+#[cfg(feature = "schema")]
+fn schema_findings(run_dir: &Path, m: Option<&RunManifest>, findings: &mut Vec<Finding>) {
+    use crate::schema::{self, Artifact};
+
+    let run_version = m
+        .and_then(|m| m.build_info.as_ref())
+        .map(|b| b.version.clone());
+    for a in Artifact::ALL {
+        let path = run_dir.join(a.file_name());
+        if !fs::metadata(&path).is_ok_and(|md| md.len() > 0) {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
+            findings.push(Finding {
+                score: 75,
+                cause: format!("{} is not valid JSON (truncated?)", a.file_name()),
+                next_step: rewrite_hint(a),
+            });
+            continue;
+        };
+        let errors = a.validate(&v);
+        let Some(first) = errors.first() else {
+            continue;
+        };
+        let written_by = schema::written_by(&v)
+            .map(str::to_string)
+            .or(run_version.clone())
+            .filter(|w| w != env!("CARGO_PKG_VERSION"));
+        let mut cause = format!(
+            "{} does not match its schema ({} problem(s), first {first})",
+            a.file_name(),
+            errors.len()
+        );
+        if let Some(w) = &written_by {
+            let _ = write!(
+                cause,
+                "; it was written by onsm {w}, this is {}",
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        findings.push(Finding {
+            score: if written_by.is_some() { 55 } else { 70 },
+            cause,
+            next_step: rewrite_hint(a),
+        });
+    }
+}
+
+#[cfg(feature = "schema")]
+fn rewrite_hint(a: crate::schema::Artifact) -> String {
+    use crate::schema::Artifact;
+    match a {
+        Artifact::RunManifest => "rerun classify; the manifest is only written at preflight".into(),
+        Artifact::Progress => {
+            "progress.json is only for monitoring a running classify; delete it".into()
+        }
+        Artifact::MappingStats => {
+            "run_manifest.json has the same counts (mapping_stats); delete this copy".into()
+        }
+        _ => format!(
+            "{} was edited or cut short after the run; `onsm reuse --from <dir>` rewrites it",
+            a.file_name()
+        ),
+    }
+}
+
+type NameSets = (BTreeSet<String>, BTreeSet<String>);
+
+/// Query and target names in the first lines of a PAF.
+fn paf_names(path: &Path) -> Option<NameSets> {
+    let rdr = BufReader::new(fs::File::open(path).ok()?);
+    let (mut q, mut t) = (BTreeSet::new(), BTreeSet::new());
+    for line in rdr.lines().take(HEADER_CHECK_LINES) {
+        let line = line.ok()?;
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 12 {
+            continue;
+        }
+        q.insert(cols[0].to_string());
+        t.insert(cols[5].to_string());
+    }
+    Some((q, t))
+}
+
+/// The generic "stopped in stage X" finding for an incomplete run.
+fn stopped_at(last: Option<Stage>, has_manifest: bool) -> Finding {
+    let failed = last.and_then(Stage::next).unwrap_or(Stage::Preflight);
+    let next_step = match failed {
+        Stage::Preflight if !has_manifest => {
+            "rerun classify and read the first error: preflight checks the FASTAs, \
+             minimap2/samtools and the samtools version before writing anything"
+        }
+        Stage::Preflight | Stage::AsmAlignment => {
+            "check the minimap2 error above (or in the job's stderr) and rerun classify"
+        }
+        Stage::ReadMapping => "check --reads/--platform and free disk space, then rerun classify",
+        Stage::Pairing => {
+            "look for PAF errors in the log; `onsm reuse --from <dir>` can redo this \
+             from the kept tmp/ files"
+        }
+        Stage::Outputs | Stage::Summary => {
+            "`onsm reuse --from <dir>` redoes evidence and scoring from the kept tmp/ files"
+        }
+    };
+    Finding {
+        score: 50,
+        cause: format!("run stopped during {}", failed.describe()),
+        next_step: next_step.into(),
+    }
+}
+
+fn rel(base: &Path, p: &Path) -> String {
+    p.strip_prefix(base).unwrap_or(p).display().to_string()
+}
+
+pub(crate) fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = n as f64;
+    let mut u = 0;
+    while v >= 1024.0 && u + 1 < UNITS.len() {
+        v /= 1024.0;
+        u += 1;
+    }
+    if u == 0 {
+        format!("{n} B")
+    } else {
+        format!("{v:.1} {}", UNITS[u])
+    }
+}
+
+impl Diagnosis {
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "Run directory: {}", self.run_dir.display());
+        let _ = writeln!(
+            s,
+            "Last completed stage: {}",
+            self.last_completed
+                .map(Stage::describe)
+                .unwrap_or("none (preflight did not finish)")
+        );
+
+        s.push_str("\nArtifacts:\n");
+        let width = self
+            .artifacts
+            .iter()
+            .map(|a| a.path.len())
+            .max()
+            .unwrap_or(0);
+        for a in &self.artifacts {
+            let status = match a.size {
+                None => "missing".to_string(),
+                Some(0) => "0 B (empty)".to_string(),
+                Some(n) => human_bytes(n),
+            };
+            let _ = writeln!(s, "  {:<width$}  {status}", a.path);
+        }
+        let cache = self.run_dir.join("tmp").join(EVIDENCE_CACHE);
+        if cache.exists() {
+            let _ = writeln!(s, "  (tmp/{EVIDENCE_CACHE} present: reuse can rescore)");
+        }
+
+        if let Some(w) = self.warnings.as_ref().filter(|w| w.total() > 0) {
+            let _ = writeln!(
+                s,
+                "\nWarnings recorded during the run ({} in {} categories):",
+                w.total(),
+                w.warnings.len()
+            );
+            s.push_str(&w.to_text());
+        }
+
+        if self.findings.is_empty() {
+            s.push_str("\nNo problems found: the run completed.\n");
+            return s;
+        }
+        s.push_str("\nLikely causes (most likely first):\n");
+        for (i, f) in self.findings.iter().enumerate() {
+            let _ = writeln!(s, "  {}. {}", i + 1, f.cause);
+            let _ = writeln!(s, "     → {}", f.next_step);
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::ScoringResults;
+    use crate::summary::SummaryBuilder;
+    use tempfile::TempDir;
+
+    const PAF_LINE: &str =
+        "mt\t16000\t100\t2100\t+\tchr1\t50000\t1000\t3000\t1990\t2000\t60\ttp:A:P\n";
+    const PAF_LINE_N2M: &str =
+        "chr1\t50000\t1000\t3000\t+\tmt\t16000\t100\t2100\t1990\t2000\t60\ttp:A:P\n";
+
+    /// A run directory with everything up to and including `upto` written.
+    fn synthetic_run(upto: Stage) -> TempDir {
+        let td = TempDir::new().unwrap();
+        let d = td.path();
+        fs::create_dir_all(d.join("tmp")).unwrap();
+        let mito = d.join("mito.fa");
+        let nuc = d.join("nuc.fa");
+        fs::write(&mito, format!(">mt\n{}\n", "A".repeat(16_000))).unwrap();
+        fs::write(&nuc, format!(">chr1\n{}\n", "C".repeat(50_000))).unwrap();
+        for stage in Stage::ALL.iter().filter(|&&s| s <= upto) {
+            for rel in stage.artifacts() {
+                let body = match *rel {
+                    "run_manifest.json" => serde_json::to_string(&RunManifest::new(
+                        &mito,
+                        &nuc,
+                        &[d.join("reads.fq")],
+                        "hifi",
+                        1,
+                        0.8,
+                        100,
+                        50,
+                        200,
+                        100,
+                    ))
+                    .unwrap(),
+                    "coverage.json" => {
+                        r#"{"nuclear_median":30.0,"mito_median":300.0,"per_pair":{}}"#.to_string()
+                    }
+                    "results.json" => serde_json::to_string(&ScoringResults {
+                        weights: Default::default(),
+                        params: Default::default(),
+                        pairs: vec![],
+                        build_info: None,
+                    })
+                    .unwrap(),
+                    "summary.json" => {
+                        serde_json::to_string(&SummaryBuilder::default().finish(16_000, 50_000))
+                            .unwrap()
+                    }
+                    TMP_PAF_M2N => PAF_LINE.to_string(),
+                    TMP_PAF_N2M => PAF_LINE_N2M.to_string(),
+                    _ => "x".to_string(),
+                };
+                fs::write(d.join(rel), body).unwrap();
+            }
+        }
+        td
+    }
+
+    #[test]
+    fn completed_run_is_clean() {
+        let td = synthetic_run(Stage::Summary);
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.last_completed, Some(Stage::Summary));
+        assert!(d.findings.is_empty(), "{:?}", d.findings);
+        assert!(d.to_text().contains("No problems found"));
+    }
+
+    #[test]
+    fn run_warnings_are_shown() {
+        use crate::util::warnings::{Warnings, SPARSE_EVIDENCE};
+        let td = synthetic_run(Stage::Summary);
+        let w = Warnings::default();
+        w.record(SPARSE_EVIDENCE, "P000004: no MAPQ≥20 reads");
+        w.report().save_to(td.path()).unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.warnings.as_ref().map(WarningsReport::total), Some(1));
+        let text = d.to_text();
+        assert!(
+            text.contains("Warnings recorded during the run (1 in 1"),
+            "{text}"
+        );
+        assert!(text.contains("sparse_evidence ×1"), "{text}");
+        assert!(text.contains("No problems found"), "{text}");
+    }
+
+    #[test]
+    fn empty_paf_ranked_above_generic_stop() {
+        let td = synthetic_run(Stage::AsmAlignment);
+        fs::write(td.path().join(TMP_PAF_M2N), "").unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.last_completed, Some(Stage::Preflight));
+        assert!(d.findings[0].cause.contains("mito_to_nuc.paf is empty"));
+        assert!(d.findings[1].cause.contains("assembly alignment"));
+        let text = d.to_text();
+        assert!(text.contains("0 B (empty)"), "{text}");
+        assert!(text.contains("  1. "), "{text}");
+    }
+
+    #[test]
+    fn out_of_space_in_log_is_top_cause() {
+        let td = synthetic_run(Stage::ReadMapping);
+        fs::write(
+            td.path().join("onsm.log"),
+            "[INFO] paired 12 candidate loci\nsamtools sort: No space left on device\n",
+        )
+        .unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.last_completed, Some(Stage::ReadMapping));
+        let top = &d.findings[0];
+        assert!(top.cause.starts_with("the disk filled up"), "{top:?}");
+        assert!(top.cause.contains("onsm.log:2"), "{top:?}");
+        assert!(d.findings[1].cause.contains("PAF filtering and pairing"));
+    }
+
+    #[test]
+    fn renamed_headers_are_detected() {
+        let td = synthetic_run(Stage::Pairing);
+        fs::write(
+            td.path().join("nuc.fa"),
+            format!(">scaffold_1\n{}\n", "C".repeat(50_000)),
+        )
+        .unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        let f = d
+            .findings
+            .iter()
+            .find(|f| f.cause.contains("missing from the input FASTA headers"))
+            .expect("header finding");
+        assert!(f.cause.contains("e.g. chr1"), "{f:?}");
+        assert_eq!(
+            d.findings
+                .iter()
+                .filter(|f| f.cause.contains("missing from the input FASTA"))
+                .count(),
+            2,
+            "both PAFs"
+        );
+    }
+
+    #[test]
+    fn no_manifest_means_preflight() {
+        let td = TempDir::new().unwrap();
+        fs::write(
+            td.path().join("job.stderr"),
+            "Error: samtools ≥ 1.10 required for index on sort (found 1.9); please upgrade samtools\n",
+        )
+        .unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.last_completed, None);
+        assert!(d.findings[0].cause.contains("samtools is too old"));
+        assert!(d.findings[1].cause.contains("preflight"));
+        assert!(diagnose(&td.path().join("nope"), None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zero_read_bam_via_samtools() {
+        use std::os::unix::fs::PermissionsExt;
+        let td = synthetic_run(Stage::Summary);
+        let sam = td.path().join("samtools");
+        fs::write(&sam, "#!/bin/sh\necho 0\n").unwrap();
+        fs::set_permissions(&sam, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let d = diagnose(td.path(), Some(&sam)).unwrap();
+        assert_eq!(d.findings.len(), 2, "{:?}", d.findings);
+        assert!(d.findings[0].cause.ends_with("has no reads"));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn artifacts_off_schema_are_reported() {
+        let td = synthetic_run(Stage::Summary);
+        let path = td.path().join("coverage.json");
+        fs::write(&path, r#"{"nuclear_median":"30","per_pair":{}}"#).unwrap();
+        fs::write(td.path().join("summary.json"), r#"{"n_pairs":"#).unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        assert_eq!(d.findings.len(), 2, "{:?}", d.findings);
+        assert_eq!(
+            d.findings[0].cause,
+            "summary.json is not valid JSON (truncated?)"
+        );
+        let f = &d.findings[1];
+        assert!(
+            f.cause
+                .starts_with("coverage.json does not match its schema (2 problem(s), first /"),
+            "{f:?}"
+        );
+        assert!(!f.cause.contains("written by"), "{f:?}");
+
+        // an older onsm's results.json is reported as such
+        let mut v: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(td.path().join("results.json")).unwrap())
+                .unwrap();
+        v.as_object_mut().unwrap().remove("params");
+        v["build_info"] = serde_json::to_value(crate::util::version::VersionInfo {
+            version: "0.0.1".into(),
+            ..crate::util::version::VersionInfo::build()
+        })
+        .unwrap();
+        fs::write(td.path().join("results.json"), v.to_string()).unwrap();
+        let d = diagnose(td.path(), None).unwrap();
+        let f = d
+            .findings
+            .iter()
+            .find(|f| f.cause.starts_with("results.json"))
+            .expect("results finding");
+        assert!(f.cause.contains("written by onsm 0.0.1"), "{f:?}");
+        assert_eq!(f.score, 55);
+    }
+}
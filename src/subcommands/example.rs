@@ -0,0 +1,141 @@
+//! `onsm example` — write a miniature dataset with planted NUMTs (see
+//! `crate::example`) plus the command line to classify it.
+
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::example::{self, ExampleDataset};
+
+#[derive(Args, Debug)]
+pub struct CmdExample {
+    /// Directory to write the example into (created if needed; files are overwritten)
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+}
+
+impl CmdExample {
+    pub fn run(self) -> Result<()> {
+        let d = ExampleDataset::generate();
+        d.write_to(&self.out)?;
+        println!(
+            "Wrote {}, {} and {} to {}; the {} planted NUMTs are listed in {}.",
+            example::MITO_FILE,
+            example::NUCLEAR_FILE,
+            example::READS_FILE,
+            self.out.display(),
+            d.planted.len(),
+            example::EXPECTED_FILE
+        );
+        println!("Classify it (needs minimap2 and samtools) with:");
+        println!(
+            "  cd {} && onsm {}",
+            self.out.display(),
+            example::classify_args().join(" ")
+        );
+        println!("or run {}.", self.out.join(example::RUN_SCRIPT).display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring;
+    use crate::subcommands::classify::CmdClassify;
+    use crate::summary;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdExample,
+    }
+
+    #[derive(Parser)]
+    struct WrapClassify {
+        #[command(flatten)]
+        cmd: CmdClassify,
+    }
+
+    #[test]
+    fn writes_the_example_files() {
+        let td = TempDir::new().unwrap();
+        let out = td.path().join("ex");
+        Wrap::try_parse_from(["onsm", "--out", out.to_str().unwrap()])
+            .unwrap()
+            .cmd
+            .run()
+            .unwrap();
+        for f in [
+            example::MITO_FILE,
+            example::NUCLEAR_FILE,
+            example::READS_FILE,
+            example::EXPECTED_FILE,
+            example::RUN_SCRIPT,
+        ] {
+            assert!(out.join(f).exists(), "{f}");
+        }
+        let expected = fs_err::read_to_string(out.join(example::EXPECTED_FILE)).unwrap();
+        assert!(expected.starts_with(example::EXPECTED_HEADER));
+        assert_eq!(expected.lines().count(), 4);
+        let script = fs_err::read_to_string(out.join(example::RUN_SCRIPT)).unwrap();
+        assert!(
+            script.contains("onsm classify --mito mito.fa --nuclear nuclear.fa"),
+            "{script}"
+        );
+    }
+
+    /// End to end with the real aligners; skipped unless minimap2 and samtools
+    /// are on PATH.
+    #[test]
+    fn example_classifies_planted_numts() {
+        if which::which("minimap2").is_err() || which::which("samtools").is_err() {
+            eprintln!("minimap2/samtools not on PATH; skipping");
+            return;
+        }
+        let _runs = crate::util::logging::TEST_RUNS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let td = TempDir::new().unwrap();
+        let ex = td.path();
+        let d = ExampleDataset::generate();
+        d.write_to(ex).unwrap();
+        let p = |f: &str| ex.join(f).display().to_string();
+        let results = ex.join("results");
+        let argv = [
+            "onsm".to_string(),
+            "--mito".into(),
+            p(example::MITO_FILE),
+            "--nuclear".into(),
+            p(example::NUCLEAR_FILE),
+            "--reads".into(),
+            p(example::READS_FILE),
+            "--platform".into(),
+            "hifi".into(),
+            "--out".into(),
+            results.display().to_string(),
+        ];
+        WrapClassify::parse_from(argv).cmd.run().unwrap();
+
+        let rows = scoring::read_pairs_tsv(&results.join("pairs.tsv")).unwrap();
+        let calls = summary::parse_calls_tsv_file(&results.join("classification.tsv")).unwrap();
+        for numt in &d.planted {
+            let hit = rows.iter().find(|r| {
+                let l = &r.locus;
+                l.nuc_contig == numt.nuc_contig
+                    && l.nuc_start < numt.nuc_end
+                    && numt.nuc_start < l.nuc_end
+            });
+            let hit = hit.unwrap_or_else(|| panic!("{} not paired", numt.name));
+            assert_eq!(hit.locus.strand, numt.strand, "{}", numt.name);
+            assert_eq!(
+                calls.get(&hit.locus.pair_id).map(String::as_str),
+                Some("Likely_NUMT"),
+                "{}",
+                numt.name
+            );
+        }
+    }
+}
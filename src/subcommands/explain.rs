@@ -0,0 +1,455 @@
+//! `onsm explain` — plain-language narrative of why one pair got its call.
+//!
+//! The narrative is rendered from `results.json` (the same `ScoredPair`
+//! structures pairs.tsv/classification.tsv are written from), so the numbers
+//! quoted here cannot drift from the tables.
+
+use anyhow::{anyhow, Result};
+use clap::{Args, ValueEnum};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crate::bundle::RunDir;
+use crate::model::{ClassifyParams, Weights};
+use crate::scoring::rules;
+use crate::scoring::{
+    Call, ScoredPair, ScoringResults, IN_CONTROL_REGION, OVERLAPS_CONTROL_REGION,
+    READS_SHORTER_THAN_WINDOW, SHORT_WINDOW, SOFTMASKED_LOCUS, SPANS_ASSEMBLY_GAP,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExplainFormat {
+    #[default]
+    Text,
+    Markdown,
+}
+
+#[derive(Args, Debug)]
+pub struct CmdExplain {
+    /// Output directory from a previous `onsm classify`/`onsm reuse`, or its bundle (`onsm export-bundle`)
+    #[arg(long, value_name = "DIR")]
+    pub from: PathBuf,
+
+    /// Pair to explain (e.g. P000123)
+    #[arg(long, value_name = "ID")]
+    pub pair: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ExplainFormat::Text)]
+    pub format: ExplainFormat,
+}
+
+impl CmdExplain {
+    pub fn run(self) -> Result<()> {
+        let run = RunDir::open(&self.from)?;
+        let results = ScoringResults::load_from(run.path())?;
+        let sp = results.get(&self.pair).ok_or_else(|| {
+            let source = match run.is_bundle() {
+                true => self.from.clone(),
+                false => self.from.join("results.json"),
+            };
+            anyhow!("pair {} not found in {}", self.pair, source.display())
+        })?;
+        print!(
+            "{}",
+            render(sp, results.weights, results.params, self.format)
+        );
+        Ok(())
+    }
+}
+
+/// One titled block of the narrative.
+struct Section {
+    title: &'static str,
+    lines: Vec<String>,
+}
+
+/// Render the explanation for one scored pair.
+pub fn render(sp: &ScoredPair, w: Weights, params: ClassifyParams, fmt: ExplainFormat) -> String {
+    let sections = narrate(sp, w, params);
+    let mut out = String::new();
+    match fmt {
+        ExplainFormat::Text => {
+            let _ = writeln!(out, "{}", headline(sp, params));
+            for s in sections {
+                let _ = writeln!(out, "\n{}", s.title);
+                for l in s.lines {
+                    let _ = writeln!(out, "  {l}");
+                }
+            }
+        }
+        ExplainFormat::Markdown => {
+            let _ = writeln!(out, "## {}\n\n{}", sp.locus.pair_id, headline(sp, params));
+            for s in sections {
+                let _ = writeln!(out, "\n### {}\n", s.title);
+                for l in s.lines {
+                    let _ = writeln!(out, "- {l}");
+                }
+            }
+        }
+    }
+    out
+}
+
+fn headline(sp: &ScoredPair, params: ClassifyParams) -> String {
+    let s = &sp.score;
+    let pid = &sp.locus.pair_id;
+    match s.call {
+        Call::Ambiguous => format!(
+            "{pid} was left Ambiguous: the score difference |Δ| = {:.4} is below the call threshold {:.2}.",
+            s.confidence, params.call_threshold
+        ),
+        Call::SharedSupport => format!(
+            "{pid} has Shared_Support: reads span both loci (≥ {:.0}%) and both depth ratios are within {:.2} of 1, so the evidence backs each side and |Δ| = {:.4} is below the call threshold {:.2}.",
+            100.0 * params.shared_min_span, params.shared_depth_tol, s.confidence, params.call_threshold
+        ),
+        call => {
+            let tier = if s.high_confidence {
+                format!("high confidence; |Δ| = {:.4} ≥ {:.2}", s.confidence, params.highconf_threshold)
+            } else {
+                format!(
+                    "moderate confidence; {:.2} ≤ |Δ| = {:.4} < {:.2}",
+                    params.call_threshold, s.confidence, params.highconf_threshold
+                )
+            };
+            format!("{pid} was called {} ({tier}).", call.as_str())
+        }
+    }
+}
+
+fn narrate(sp: &ScoredPair, w: Weights, params: ClassifyParams) -> Vec<Section> {
+    let (p, f, s) = (&sp.locus, &sp.features, &sp.score);
+    let t = s.terms;
+
+    let locus = Section {
+        title: "Locus",
+        lines: vec![
+            format!(
+                "nuclear: {}:{}-{} ({} bp)",
+                p.nuc_contig,
+                p.nuc_start,
+                p.nuc_end,
+                p.nuc_end.saturating_sub(p.nuc_start)
+            ),
+            format!(
+                "mito: {}:{}-{} ({} bp)",
+                p.mito_contig,
+                p.mito_start,
+                p.mito_end,
+                p.mito_end.saturating_sub(p.mito_start)
+            ),
+            format!(
+                "alignment: {} bp at {:.2}% identity",
+                f.aln_len,
+                100.0 * f.aln_ident
+            ),
+        ],
+    };
+
+    let mut evidence = Section {
+        title: "Evidence",
+        lines: vec![
+            format!(
+                "nuclear depth ratio {:.2} ({}).",
+                f.rnuc,
+                depth_phrase(f.rnuc, "nuclear")
+            ),
+            format!(
+                "mito depth ratio {:.2} ({}).",
+                f.rmito,
+                depth_phrase(f.rmito, "mito")
+            ),
+            format!(
+                "{:.1}% of reads span the nuclear window; {:.1}% span the mito window.",
+                100.0 * f.s_nuc,
+                100.0 * f.s_mito
+            ),
+            format!("summary: {}", f.evidence_summary()),
+        ],
+    };
+    if (f.junction_clips_start, f.junction_clips_end) != (0, 0) {
+        evidence.lines.push(format!(
+            "{} read(s) end clipped at the nuclear start and {} at the nuclear end: junctions of an insertion.",
+            f.junction_clips_start, f.junction_clips_end
+        ));
+    }
+    if s.possible_collapsed_repeat() {
+        evidence.lines.push(format!(
+            "estimated nuclear copy number {:.1} (≥ {:.1}): possibly a collapsed repeat or segmental duplication.",
+            s.est_copy_number_nuc, params.collapsed_repeat_cn
+        ));
+    }
+    if s.reason_codes
+        .iter()
+        .any(|r| r == READS_SHORTER_THAN_WINDOW)
+    {
+        evidence.lines.push(format!(
+            "median read length {:.0} bp (nuclear) / {:.0} bp (mito) is shorter than the {} bp spanning window, so few reads can span it{}.",
+            f.read_len_nuc,
+            f.read_len_mito,
+            params.span_window,
+            if params.span_read_len_scaling {
+                "; the span weight was scaled down accordingly"
+            } else {
+                ""
+            }
+        ));
+    }
+    if s.reason_codes.iter().any(|r| r == SOFTMASKED_LOCUS) {
+        evidence.lines.push(format!(
+            "{:.1}% of the nuclear locus is soft-masked in the assembly (> --max-softmask-frac {:.2}): called Ambiguous whatever the scores say.",
+            100.0 * f.softmask_frac_nuc,
+            params.max_softmask_frac.unwrap_or(0.0)
+        ));
+    }
+    if let Some((nuc, mito)) = f.clamped_window {
+        evidence.lines.push(format!(
+            "a contig end cuts the span windows to {nuc} bp (nuclear) and {mito} bp (mito); span fractions are over those bases{}.",
+            if s.reason_codes.iter().any(|r| r == SHORT_WINDOW) {
+                ", too few to trust (short_window)"
+            } else {
+                ""
+            }
+        ));
+    }
+    if let Some(invalid) = f.invalid_window {
+        evidence.lines.push(format!(
+            "no evidence windows could be built ({invalid}): the pair has no read evidence and is called Ambiguous."
+        ));
+    }
+    if s.reason_codes.iter().any(|r| r == SPANS_ASSEMBLY_GAP) {
+        evidence.lines.push(format!(
+            "the nuclear locus contains {} N bases ({:.1}%; longest run {} bp): it spans an assembly gap, so the insertion's length and continuity are unknown.",
+            f.nuc_n_bases,
+            100.0 * f.nuc_n_bases as f32 / p.nuc_end.saturating_sub(p.nuc_start).max(1) as f32,
+            f.nuc_longest_n_run
+        ));
+    }
+
+    if s.reason_codes.iter().any(|r| r == IN_CONTROL_REGION) {
+        evidence.lines.push(format!(
+            "the mito interval lies inside the control region (D-loop), whose fast evolution and repeats make identity a weak guide{}.",
+            if params.control_region_identity_weight != 1.0 {
+                format!(
+                    "; the identity term was scaled by {:.2}",
+                    params.control_region_identity_weight
+                )
+            } else {
+                String::new()
+            }
+        ));
+    } else if s.reason_codes.iter().any(|r| r == OVERLAPS_CONTROL_REGION) {
+        evidence
+            .lines
+            .push("the mito interval runs into the control region (D-loop).".to_string());
+    }
+
+    let score = Section {
+        title: "Score",
+        lines: vec![
+            format!(
+                "score_numt = {:.4}, score_nimt = {:.4}, Δ = {:+.4} (call threshold ±{:.2}).",
+                s.score_numt, s.score_nimt, s.delta, params.call_threshold
+            ),
+            format!(
+                "depth consistency (w_d = {:.2}) contributes {:+.4} to Δ.",
+                w.w_d, t.depth_consistency
+            ),
+            format!(
+                "depth contrast (w_d = {:.2}) contributes {:+.4} to Δ.",
+                w.w_d, t.depth_contrast
+            ),
+            format!(
+                "spanning reads (w_s = {:.2}) contribute {:+.4} to Δ.",
+                w.w_s, t.span
+            ),
+            format!(
+                "junction reads (w_j = {:.2}) contribute {:+.4} to Δ.",
+                w.w_j, t.junction
+            ),
+            format!(
+                "alignment identity and length (w_a = {:.2}, w_l = {:.2}) add equally to both scores and do not move Δ.",
+                w.w_a, w.w_l
+            ),
+            "positive contributions favour NUMT, negative favour NIMT.".to_string(),
+        ],
+    };
+
+    // the decision rules that fired (none recorded in older results.json)
+    let fired = Section {
+        title: "Rules",
+        lines: s
+            .rules
+            .iter()
+            .map(|name| match rules::rule(name) {
+                Some(r) => format!("{name}: {}", r.description),
+                None => name.clone(),
+            })
+            .collect(),
+    };
+
+    let reasons = Section {
+        title: "Reason codes",
+        lines: vec![s.reason_codes.join(", ")],
+    };
+
+    let mut sections = vec![locus, evidence, score];
+    if !fired.lines.is_empty() {
+        sections.push(fired);
+    }
+    sections.push(reasons);
+    sections
+}
+
+fn depth_phrase(ratio: f32, genome: &str) -> String {
+    if ratio <= 0.0 {
+        format!("no reads relative to the {genome} baseline")
+    } else if ratio < 0.5 {
+        format!("well below the {genome} baseline")
+    } else if ratio <= 1.5 {
+        format!("consistent with the {genome} baseline")
+    } else {
+        format!("{ratio:.1}× above the {genome} baseline")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fasta::GapIndex;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+    use crate::model::{CoverageSummary, PairedLocus, SpanSummary};
+    use crate::scoring::score_pairs;
+
+    fn fixture(rn: f32, rm: f32, sn: f32, sm: f32) -> ScoredPair {
+        let pairs = vec![PairedLocus {
+            pair_id: "P000123".into(),
+            nuc_contig: "chr3".into(),
+            nuc_start: 1000,
+            nuc_end: 5000,
+            mito_contig: "mt".into(),
+            mito_start: 200,
+            mito_end: 4200,
+            aln_len: 4000,
+            aln_ident: 0.961,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }];
+        let cov = CoverageSummary {
+            nuclear_median: 20.0,
+            mito_median: 100.0,
+            loci_medians: None,
+            per_pair: [("P000123".into(), (20.0 * rn, 100.0 * rm))]
+                .into_iter()
+                .collect(),
+            params: None,
+        };
+        let spans = SpanSummary {
+            per_pair: [("P000123".into(), (sn, sm))].into_iter().collect(),
+            read_len: Default::default(),
+            read_div: Default::default(),
+            reads: Default::default(),
+            clamped_window: Default::default(),
+            invalid_window: Default::default(),
+            junction_clips: Default::default(),
+            params: None,
+            cost: Default::default(),
+            stage_cost: None,
+        };
+        score_pairs(
+            &pairs,
+            &cov,
+            &spans,
+            &GapIndex::default(),
+            &crate::control_region::ControlRegions::default(),
+            Weights::default(),
+            ClassifyParams::default(),
+        )
+        .remove(0)
+    }
+
+    #[test]
+    fn golden_numt_text() {
+        let sp = fixture(1.0, 0.02, 0.78, 0.03);
+        let txt = render(
+            &sp,
+            Weights::default(),
+            ClassifyParams::default(),
+            ExplainFormat::Text,
+        );
+        let expected = "\
+P000123 was called Likely_NUMT (high confidence; |Δ| = 1.7400 ≥ 0.30).
+
+Locus
+  nuclear: chr3:1000-5000 (4000 bp)
+  mito: mt:200-4200 (4000 bp)
+  alignment: 4000 bp at 96.10% identity
+
+Evidence
+  nuclear depth ratio 1.00 (consistent with the nuclear baseline).
+  mito depth ratio 0.02 (well below the mito baseline).
+  78.0% of reads span the nuclear window; 3.0% span the mito window.
+  summary: D:nuc=1.0x,mito=0.0x|S:nuc=78%,mito=3%|len=4.0kb|id=96.1%
+
+Score
+  score_numt = 1.1769, score_nimt = -0.5631, Δ = +1.7400 (call threshold ±0.15).
+  depth consistency (w_d = 0.25) contributes +0.4900 to Δ.
+  depth contrast (w_d = 0.25) contributes +0.5000 to Δ.
+  spanning reads (w_s = 0.25) contribute +0.7500 to Δ.
+  junction reads (w_j = 0.00) contribute +0.0000 to Δ.
+  alignment identity and length (w_a = 0.25, w_l = 0.15) add equally to both scores and do not move Δ.
+  positive contributions favour NUMT, negative favour NIMT.
+
+Rules
+  numt_by_score: Δ ≥ the call threshold: Likely_NUMT
+
+Reason codes
+  score_difference
+";
+        assert_eq!(txt, expected);
+    }
+
+    #[test]
+    fn golden_ambiguous_markdown() {
+        let sp = fixture(1.0, 1.0, 0.4, 0.4);
+        let md = render(
+            &sp,
+            Weights::default(),
+            ClassifyParams::default(),
+            ExplainFormat::Markdown,
+        );
+        assert!(md.starts_with(
+            "## P000123\n\nP000123 was left Ambiguous: the score difference |Δ| = 0.0000 is below the call threshold 0.15.\n"
+        ));
+        assert!(md.contains("\n### Evidence\n\n- nuclear depth ratio 1.00"));
+        assert!(md.contains("\n### Rules\n\n- ambiguous_by_score: no call otherwise: Ambiguous\n"));
+        assert!(md.contains("- delta_below_threshold\n"));
+
+        // results.json from before the rules table has no trace to show
+        let mut old = sp.clone();
+        old.score.rules.clear();
+        let md = render(
+            &old,
+            Weights::default(),
+            ClassifyParams::default(),
+            ExplainFormat::Markdown,
+        );
+        assert!(!md.contains("### Rules"));
+    }
+
+    #[test]
+    fn depth_phrases() {
+        assert_eq!(
+            depth_phrase(0.0, "mito"),
+            "no reads relative to the mito baseline"
+        );
+        assert_eq!(depth_phrase(41.0, "mito"), "41.0× above the mito baseline");
+    }
+}
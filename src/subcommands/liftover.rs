@@ -0,0 +1,316 @@
+//! `onsm liftover` — carry a run's nuclear loci onto a new assembly without
+//! rerunning, through an old→new PAF (see `crate::liftover`).
+//!
+//! Reads pairs.tsv + classification.tsv and writes `lifted_pairs.tsv` (one row
+//! per pair, with status and coverage) and `lifted.bed` (one line per segment).
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::io::bed::{self, BedRecord};
+use crate::liftover::{Lift, LiftIndex, LiftStatus, LIFT_MIN_COVERAGE};
+use crate::model::orientation::Strand;
+use crate::pairing::parse_fraction;
+use crate::scoring::{self, PairsTsvRow};
+use crate::summary;
+use crate::util::warnings;
+
+pub const LIFTED_PAIRS_FILE: &str = "lifted_pairs.tsv";
+pub const LIFTED_BED_FILE: &str = "lifted.bed";
+
+pub const LIFTED_PAIRS_HEADER: &str = "pair_id\tcall\told_nuc_contig\told_nuc_start\told_nuc_end\tstatus\tlift_coverage\tnuc_contig\tnuc_start\tnuc_end\tstrand\tmito_contig\tmito_start\tmito_end\tn_segments\tsegments";
+
+#[derive(Args, Debug)]
+pub struct CmdLiftover {
+    /// Output directory from a previous `onsm classify`/`onsm reuse`
+    #[arg(long, value_name = "DIR")]
+    pub from: PathBuf,
+
+    /// Old→new nuclear assembly alignment (query = old, target = new), e.g.
+    /// `minimap2 -c -x asm5 new.fa old.fa`; `cg:Z` CIGARs give base-level lifts
+    #[arg(long, value_name = "PAF")]
+    pub paf: PathBuf,
+
+    /// Where to write lifted_pairs.tsv and lifted.bed
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: PathBuf,
+
+    /// Fraction of a locus that must lift onto a single segment to count as lifted
+    #[arg(long, value_name = "FRAC", default_value_t = LIFT_MIN_COVERAGE, value_parser = parse_fraction)]
+    pub min_coverage: f32,
+
+    /// Also lift through secondary alignments (tp:A:S)
+    #[arg(long)]
+    pub include_secondary: bool,
+}
+
+impl CmdLiftover {
+    pub fn run(self) -> Result<()> {
+        let pairs_p = self.from.join("pairs.tsv");
+        let class_p = self.from.join("classification.tsv");
+        for p in [&pairs_p, &class_p] {
+            if !p.exists() {
+                return Err(anyhow!(
+                    "{} not found (is this an onsm run directory?)",
+                    p.display()
+                ));
+            }
+        }
+        let rows = scoring::read_run_pairs(&self.from)?;
+        let calls = summary::parse_calls_tsv_file(&class_p)?;
+        let (index, stats) = LiftIndex::from_paf(&self.paf, self.include_secondary)?;
+        log::info!(
+            "liftover: {} alignments ({} with cg CIGARs, {} secondary skipped)",
+            stats.n_chains,
+            stats.n_base_level,
+            stats.n_secondary_skipped
+        );
+        if stats.n_chains > stats.n_base_level {
+            warnings::warn(
+                warnings::INTERPOLATED_LIFT,
+                format!(
+                    "liftover: {} alignments have no cg:Z CIGAR and are lifted by interpolation; \
+                     align with `minimap2 -c` for base-level coordinates",
+                    stats.n_chains - stats.n_base_level
+                ),
+            );
+        }
+
+        let lifts: Vec<Lift> = rows
+            .iter()
+            .map(|r| {
+                let l = &r.locus;
+                index.lift(&l.nuc_contig, l.nuc_start, l.nuc_end, self.min_coverage)
+            })
+            .collect();
+        fs_err::create_dir_all(&self.out_dir)?;
+        write_lifted(&self.out_dir, &rows, &calls, &lifts)?;
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for l in &lifts {
+            *counts.entry(l.status.as_str()).or_default() += 1;
+        }
+        let n = |s: LiftStatus| counts.get(s.as_str()).copied().unwrap_or(0);
+        log::info!(
+            "liftover: {} loci: {} lifted, {} partial, {} split, {} failed → {}",
+            lifts.len(),
+            n(LiftStatus::Lifted),
+            n(LiftStatus::Partial),
+            n(LiftStatus::Split),
+            n(LiftStatus::Failed),
+            self.out_dir.join(LIFTED_PAIRS_FILE).display()
+        );
+        Ok(())
+    }
+}
+
+/// `contig:start-end(strand)` per segment, `;`-separated; `.` if none.
+fn segments_string(l: &Lift) -> String {
+    if l.segments.is_empty() {
+        return ".".to_string();
+    }
+    l.segments
+        .iter()
+        .map(|s| {
+            format!(
+                "{}:{}-{}({})",
+                s.new_contig,
+                s.start,
+                s.end,
+                s.strand.as_char()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// One lifted_pairs.tsv row. The nuclear columns give the largest segment; the
+/// strand is the mito↔nuclear strand after the lift (flipped by an inversion).
+pub fn lifted_row(r: &PairsTsvRow, call: &str, l: &Lift) -> String {
+    let p = &r.locus;
+    let mut s = format!(
+        "{}\t{call}\t{}\t{}\t{}\t{}\t{:.4}\t",
+        p.pair_id,
+        p.nuc_contig,
+        p.nuc_start,
+        p.nuc_end,
+        l.status.as_str(),
+        l.coverage
+    );
+    match l.segments.first() {
+        Some(seg) => {
+            let strand = match seg.strand {
+                Strand::Forward => p.strand,
+                Strand::Reverse => match p.strand {
+                    Strand::Forward => Strand::Reverse,
+                    Strand::Reverse => Strand::Forward,
+                },
+            };
+            let _ = write!(
+                s,
+                "{}\t{}\t{}\t{}",
+                seg.new_contig,
+                seg.start,
+                seg.end,
+                strand.as_char()
+            );
+        }
+        None => s.push_str(".\t.\t.\t."),
+    }
+    let _ = write!(
+        s,
+        "\t{}\t{}\t{}\t{}\t{}",
+        p.mito_contig,
+        p.mito_start,
+        p.mito_end,
+        l.segments.len(),
+        segments_string(l)
+    );
+    s
+}
+
+/// BED lines for one pair: one per segment, named `pair_id` (or `pair_id.k`
+/// when split), score = lift coverage × 1000, strand relative to the old assembly.
+pub fn lifted_bed_records(pair_id: &str, l: &Lift) -> Vec<BedRecord> {
+    let split = l.segments.len() > 1;
+    l.segments
+        .iter()
+        .enumerate()
+        .map(|(k, seg)| BedRecord {
+            name: Some(if split {
+                format!("{pair_id}.{}", k + 1)
+            } else {
+                pair_id.to_string()
+            }),
+            score: Some((1000.0 * l.coverage).round() as f64),
+            strand: Some(seg.strand.as_char()),
+            ..BedRecord::new(&seg.new_contig, seg.start, seg.end)
+        })
+        .collect()
+}
+
+fn write_lifted(
+    out_dir: &Path,
+    rows: &[PairsTsvRow],
+    calls: &HashMap<String, String>,
+    lifts: &[Lift],
+) -> Result<()> {
+    let mut t = format!("{LIFTED_PAIRS_HEADER}\n");
+    let mut bed_records = Vec::new();
+    for (r, l) in rows.iter().zip(lifts) {
+        let call = calls
+            .get(&r.locus.pair_id)
+            .map(String::as_str)
+            .unwrap_or("Ambiguous");
+        let _ = writeln!(t, "{}", lifted_row(r, call, l));
+        bed_records.extend(lifted_bed_records(&r.locus.pair_id, l));
+    }
+    fs_err::write(out_dir.join(LIFTED_PAIRS_FILE), t)?;
+    bed::write_bed(&out_dir.join(LIFTED_BED_FILE), &bed_records)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdLiftover,
+    }
+
+    #[test]
+    fn lifts_a_run_directory() {
+        let td = TempDir::new().unwrap();
+        let run = td.path().join("run");
+        fs_err::create_dir_all(&run).unwrap();
+        let header = scoring::PAIRS_TSV_HEADER;
+        let row = |pid: &str, c: &str, s: u32, e: u32, strand: char| {
+            format!("{pid}\t{c}\t{s}\t{e}\tmt\t0\t100\t100\t0.95\t1\t1\t0.5\t0.5\t0.5\t0.4\t1.0\t{strand}\t0\t0\t0\t0\n")
+        };
+        fs_err::write(
+            run.join("pairs.tsv"),
+            format!(
+                "{header}\n{}{}{}",
+                row("P000001", "chr1", 1300, 1400, '+'),
+                row("P000002", "chr3", 450, 700, '-'),
+                row("P000003", "chr9", 0, 100, '+'),
+            ),
+        )
+        .unwrap();
+        fs_err::write(
+            run.join("classification.tsv"),
+            "pair_id\tcall\tconfidence\treason_codes\n\
+             P000001\tLikely_NUMT\t0.5\tscore_difference\n\
+             P000002\tLikely_NUMT\t0.5\tscore_difference\n",
+        )
+        .unwrap();
+        let paf = td.path().join("old_to_new.paf");
+        fs_err::write(
+            &paf,
+            "chr1\t5000\t1000\t2000\t+\tctgA\t9000\t5000\t5995\t990\t1000\t60\tcg:Z:100M10I200M5D690M\n\
+             chr3\t1000\t0\t600\t+\tctgA\t9000\t0\t600\t600\t600\t60\n\
+             chr3\t1000\t600\t1000\t-\tctgB\t900\t100\t500\t400\t400\t60\n",
+        )
+        .unwrap();
+        let out = td.path().join("lifted");
+        let argv = [
+            "onsm",
+            "--from",
+            run.to_str().unwrap(),
+            "--paf",
+            paf.to_str().unwrap(),
+            "--out-dir",
+            out.to_str().unwrap(),
+        ];
+        Wrap::try_parse_from(argv).unwrap().cmd.run().unwrap();
+
+        let tsv = fs_err::read_to_string(out.join(LIFTED_PAIRS_FILE)).unwrap();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], LIFTED_PAIRS_HEADER);
+        assert_eq!(
+            lines[1],
+            "P000001\tLikely_NUMT\tchr1\t1300\t1400\tlifted\t1.0000\tctgA\t5290\t5395\t+\tmt\t0\t100\t1\tctgA:5290-5395(+)"
+        );
+        // largest segment first; its strand composes with the pair's
+        assert_eq!(
+            lines[2],
+            "P000002\tLikely_NUMT\tchr3\t450\t700\tsplit\t1.0000\tctgA\t450\t600\t-\tmt\t0\t100\t2\tctgA:450-600(+);ctgB:400-500(-)"
+        );
+        assert_eq!(
+            lines[3],
+            "P000003\tAmbiguous\tchr9\t0\t100\tfailed\t0.0000\t.\t.\t.\t.\tmt\t0\t100\t0\t."
+        );
+
+        let bed = bed::read_bed(&out.join(LIFTED_BED_FILE)).unwrap();
+        let names: Vec<_> = bed.iter().map(|b| b.name.clone().unwrap()).collect();
+        assert_eq!(names, ["P000001", "P000002.1", "P000002.2"]);
+        assert_eq!(bed[2].strand, Some('-'));
+        assert_eq!(bed[0].score, Some(1000.0));
+    }
+
+    #[test]
+    fn min_coverage_is_a_fraction() {
+        let argv = |v: &'static str| {
+            Wrap::try_parse_from([
+                "onsm",
+                "--from",
+                "r",
+                "--paf",
+                "p",
+                "--out-dir",
+                "o",
+                "--min-coverage",
+                v,
+            ])
+        };
+        assert_eq!(argv("0.8").unwrap().cmd.min_coverage, 0.8);
+        assert!(argv("1.5").is_err());
+    }
+}
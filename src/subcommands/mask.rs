@@ -0,0 +1,255 @@
+//! `onsm mask` — write one of a run's assemblies with its called insertions
+//! masked (see `crate::mask`), and optionally which pairs cover each masked base.
+
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::io::fasta::FastaCompression;
+use crate::io::headers::{self, HeaderFields, RenameScheme};
+use crate::mask::{self, MaskGenome, MaskMode, MaskPlan};
+use crate::model::RunManifest;
+use crate::pairing::parse_fraction;
+use crate::scoring::ScoringResults;
+use crate::summary::Tier;
+
+#[derive(Args, Debug)]
+pub struct CmdMask {
+    /// Output directory from a previous `onsm classify`/`onsm reuse`
+    #[arg(long, value_name = "DIR")]
+    pub from: PathBuf,
+
+    /// Mask the mito side of Likely_NIMT pairs, or the nuclear side of Likely_NUMT pairs
+    #[arg(long, value_enum)]
+    pub genome: MaskGenome,
+
+    /// The assembly to mask [default: the run's, from its manifest]
+    #[arg(long, value_name = "FASTA")]
+    pub fasta: Option<PathBuf>,
+
+    /// Where to write the masked FASTA
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    /// Compression of --out: none, gzip or bgzip [default: bgzip for a .gz/.bgz --out, else none]
+    #[arg(long, value_enum)]
+    pub compression: Option<FastaCompression>,
+
+    /// Lowercase masked bases (soft) or replace them with N (hard)
+    #[arg(long, value_enum, default_value_t = MaskMode::Soft)]
+    pub mode: MaskMode,
+
+    /// Bases masked on either side of each interval
+    #[arg(long, value_name = "BP", default_value_t = 0)]
+    pub pad: u32,
+
+    /// Only mask pairs with at least this confidence
+    #[arg(long, value_name = "FRAC", default_value_t = 0.0, value_parser = parse_fraction)]
+    pub min_confidence: f32,
+
+    /// Mask the pairs of all calls, or of high-confidence calls only (the summary's highconf_ figures)
+    #[arg(long, value_enum, default_value_t = Tier::All)]
+    pub tier: Tier,
+
+    /// Also write each masked base with the pairs covering it (contig, pos, status, pair_ids)
+    #[arg(long, value_name = "FILE")]
+    pub bases_tsv: Option<PathBuf>,
+
+    /// Also write what was masked: each pair's interval, unpadded and unmasked, as one FASTA record
+    #[arg(long, value_name = "FILE")]
+    pub masked_fasta: Option<PathBuf>,
+
+    /// Names for --masked-fasta: full, short or custom:TEMPLATE; FILE.names.tsv maps them back [default: full]
+    #[arg(long, value_name = "SCHEME", requires = "masked_fasta")]
+    pub rename_scheme: Option<RenameScheme>,
+}
+
+impl CmdMask {
+    pub fn run(self) -> Result<()> {
+        let m = RunManifest::load_from(&self.from)?;
+        let results = ScoringResults::load_from(&self.from)?;
+        let plan = MaskPlan::new(self.genome, self.pad, self.min_confidence)
+            .with_tier(self.tier)
+            .with_pairs(&results.pairs);
+        let fasta = self.fasta.unwrap_or(match self.genome {
+            MaskGenome::Mito => m.mito.clone(),
+            MaskGenome::Nuclear => m.nuclear.clone(),
+        });
+        let counts = mask::write_masked(
+            &plan,
+            &m.mito_contig_meta,
+            self.mode,
+            &fasta,
+            &self.out,
+            self.compression
+                .unwrap_or_else(|| FastaCompression::for_path(&self.out)),
+            self.bases_tsv.as_deref(),
+        )?;
+        if let Some(p) = &self.masked_fasta {
+            let masked: Vec<_> = results.pairs.iter().filter(|sp| plan.selects(sp)).collect();
+            let items: Vec<HeaderFields> = masked
+                .iter()
+                .map(|sp| HeaderFields {
+                    locus: &sp.locus,
+                    call: sp.score.call.as_str_and_reason().0,
+                    side: self.genome.side(),
+                })
+                .collect();
+            let scheme = self.rename_scheme.unwrap_or_default();
+            headers::write_renamed_fasta(&scheme, &items, &fasta, p)?;
+        }
+        log::info!(
+            "mask: {} pairs, {} bp masked ({} bp already N) → {}",
+            plan.n_pairs(),
+            counts.masked_bp,
+            counts.gap_bp,
+            self.out.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_region::ControlRegionOverlap;
+    use crate::io::fasta::GapStats;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+    use crate::model::{ClassifyParams, PairEvidence, PairedLocus, Weights};
+    use crate::scoring::{score_pair, DepthBaselines, ScoredPair};
+    use tempfile::TempDir;
+
+    /// A Likely_NIMT pair on `mt:start-end`.
+    fn nimt(id: &str, start: u32, end: u32) -> ScoredPair {
+        let l = PairedLocus {
+            pair_id: id.to_string(),
+            nuc_contig: "chr1".to_string(),
+            nuc_start: 5000,
+            nuc_end: 5000 + end - start,
+            mito_contig: "mt".to_string(),
+            mito_start: start,
+            mito_end: end,
+            aln_len: end - start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let ev = PairEvidence {
+            depth: (2.0, 30.0),
+            span: (0.05, 0.9),
+            read_len: (0.0, 0.0),
+            read_div: Default::default(),
+            span_reads: None,
+            junction_clips: (0, 0),
+            clamped_window: None,
+            invalid_window: None,
+        };
+        let baselines = DepthBaselines {
+            nuclear_median: 30.0,
+            mito_median: 30.0,
+        };
+        let sp = score_pair(
+            &l,
+            &ev,
+            GapStats::default(),
+            ControlRegionOverlap::None,
+            baselines,
+            Weights::default(),
+            ClassifyParams::default(),
+        );
+        assert_eq!(sp.score.call, crate::scoring::Call::NIMT);
+        sp
+    }
+
+    #[test]
+    fn masked_fasta_named_by_the_rename_scheme() {
+        let td = TempDir::new().unwrap();
+        let run = td.path();
+        let (mito, nuc) = (run.join("mt.fa"), run.join("nuc.fa"));
+        let mt = "ACGT".repeat(250);
+        fs_err::write(&mito, format!(">mt\n{mt}\n")).unwrap();
+        fs_err::write(&nuc, format!(">chr1\n{}\n", "C".repeat(10_000))).unwrap();
+        let m = RunManifest::new(
+            &mito,
+            &nuc,
+            &[],
+            "hifi",
+            1,
+            crate::model::MIN_ID,
+            crate::model::MIN_LEN,
+            crate::model::MERGE_GAP,
+            crate::model::FLANK_BP,
+            crate::model::WIN_BP,
+        );
+        RunManifest::save_to(run, &m).unwrap();
+        let results = ScoringResults {
+            weights: Weights::default(),
+            params: ClassifyParams::default(),
+            pairs: vec![nimt("P1", 100, 200), nimt("P2", 300, 400)],
+            build_info: None,
+        };
+        ScoringResults::save_to(run, &results).unwrap();
+
+        let (out, extracted) = (run.join("mito.masked.fa"), run.join("masked.fa"));
+        let mask = |scheme: Option<&str>| {
+            CmdMask {
+                from: run.to_path_buf(),
+                genome: MaskGenome::Mito,
+                fasta: None,
+                out: out.clone(),
+                compression: None,
+                mode: MaskMode::Hard,
+                pad: 5,
+                min_confidence: 0.0,
+                tier: Tier::All,
+                bases_tsv: None,
+                masked_fasta: Some(extracted.clone()),
+                rename_scheme: scheme.map(|s| s.parse().unwrap()),
+            }
+            .run()
+        };
+
+        mask(Some("custom:{pair_id}:{contig}:{start}-{end}")).unwrap();
+        let fa = fs_err::read_to_string(&extracted).unwrap();
+        let records: Vec<(&str, String)> = fa
+            .split('>')
+            .skip(1)
+            .map(|r| {
+                let (name, seq) = r.split_once('\n').unwrap();
+                (name, seq.replace('\n', ""))
+            })
+            .collect();
+        // the unpadded intervals, as they were before masking
+        assert_eq!(
+            records,
+            [
+                ("P1:mt:100-200", mt[100..200].to_string()),
+                ("P2:mt:300-400", mt[300..400].to_string())
+            ]
+        );
+        let map = fs_err::read_to_string(run.join("masked.fa.names.tsv")).unwrap();
+        assert_eq!(map.lines().count(), 3);
+        assert!(
+            map.contains("\nP1:mt:100-200\tP1\tmito\tmt\t100\t200\t"),
+            "{map}"
+        );
+
+        // full by default
+        mask(None).unwrap();
+        let fa = fs_err::read_to_string(&extracted).unwrap();
+        assert!(
+            fa.starts_with(">P1_mito mt:100-200 call=Likely_NIMT "),
+            "{fa}"
+        );
+
+        let e = mask(Some("custom:{call}")).unwrap_err().to_string();
+        assert!(e.contains("duplicate name \"Likely_NIMT\""), "{e}");
+    }
+}
@@ -0,0 +1,545 @@
+//! `onsm pair` — PAF filtering and pairing only, on alignments made elsewhere
+//! (wfmash, nucmer via PAF, BLAST tabular with `--aln-format blast6`, …).
+//! No reads, no evidence, no scores.
+//!
+//! Runs the same `pipeline::PairingStage` as classify's step 3 and writes
+//! `candidates.tsv` (the locus columns of pairs.tsv), `paf_filter_stats.json`,
+//! `pairing_report.tsv` (reciprocal pairing), with `--require-reciprocal`
+//! `pairs_unsupported.tsv` and, with `--bed`,
+//! `candidates_nuc.bed` / `candidates_mito.bed`.
+
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::io::bed::{self, BedRecord};
+use crate::io::paf::{self, AlnFormat, IdentityKind, PafErrorPolicy, SecondaryPolicy};
+use crate::model::{self, PairedLocus, PairingParams};
+use crate::pairing::{self, PairingMethod};
+use crate::pipeline;
+
+pub const CANDIDATES_FILE: &str = "candidates.tsv";
+pub const CANDIDATES_NUC_BED_FILE: &str = "candidates_nuc.bed";
+pub const CANDIDATES_MITO_BED_FILE: &str = "candidates_mito.bed";
+
+pub const CANDIDATES_HEADER: &str = pairing::LOCI_TSV_HEADER;
+
+#[derive(Args, Debug)]
+pub struct CmdPair {
+    /// Mito→nuclear alignments (query = mito, target = nuclear)
+    #[arg(long, value_name = "PAF")]
+    pub paf_m2n: PathBuf,
+
+    /// Nuclear→mito alignments (query = nuclear, target = mito)
+    #[arg(long, value_name = "PAF")]
+    pub paf_n2m: PathBuf,
+
+    /// Drop alignments below this identity (see --aln-identity)
+    #[arg(long, value_name = "FRAC", default_value_t = model::MIN_ID, value_parser = pairing::parse_fraction)]
+    pub min_id: f32,
+
+    /// Identity --min-id applies to: blast (matches / block length), or gap-compressed (from the cg:Z CIGAR; long indels cost one gap each)
+    #[arg(long, value_enum, default_value_t = IdentityKind::Blast)]
+    pub aln_identity: IdentityKind,
+
+    /// Drop alignments with a shorter block
+    #[arg(long, value_name = "BP", default_value_t = model::MIN_LEN)]
+    pub min_len: u32,
+
+    /// Drop alignments below this mapping quality
+    #[arg(long, value_name = "Q", default_value_t = model::MIN_PAF_MAPQ)]
+    pub min_mapq: u8,
+
+    /// Secondary alignments (tp:A:S): drop them (as `classify` does), or pair
+    /// them like primaries
+    #[arg(long, value_enum, default_value_t = SecondaryPolicy::Drop)]
+    pub secondary: SecondaryPolicy,
+
+    /// Pairing strategy: reciprocal best hit, or overlap clustering of both directions
+    #[arg(long, value_enum, default_value_t = PairingMethod::Reciprocal)]
+    pub pairing: PairingMethod,
+
+    #[command(flatten)]
+    pub pairing_params: PairingParams,
+
+    /// Leave out loci no nuclear→mito alignment backs; they go to pairs_unsupported.tsv
+    #[arg(long)]
+    pub require_reciprocal: bool,
+
+    /// Split alignments at insertions or deletions longer than this (from the cg:Z CIGAR); 0 = off
+    #[arg(long, value_name = "BP", default_value_t = model::SPLIT_INDEL)]
+    pub split_indel: u32,
+
+    /// Format of --paf-m2n/--paf-n2m: paf, blast6 (BLAST -outfmt 6), or sam (SAM or BAM)
+    #[arg(long, value_enum, default_value_t = AlnFormat::Paf)]
+    pub aln_format: AlnFormat,
+
+    /// Malformed PAF lines: abort (strict) or count and skip (skip)
+    #[arg(long, value_enum, default_value_t = PafErrorPolicy::Strict)]
+    pub paf_error_policy: PafErrorPolicy,
+
+    /// With --paf-error-policy skip: fail if more than this fraction of lines is malformed
+    #[arg(long, default_value_t = paf::MAX_PAF_ERROR_FRAC)]
+    pub paf_max_error_frac: f64,
+
+    /// Also write candidates_nuc.bed and candidates_mito.bed
+    #[arg(long)]
+    pub bed: bool,
+
+    /// Where to write candidates.tsv and the filter stats
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+}
+
+impl CmdPair {
+    pub fn run(self) -> Result<()> {
+        self.pairing
+            .check_reciprocal_best(self.pairing_params.reciprocal_best)?;
+        let stage = pipeline::PairingStage {
+            filter: paf::PafFilter {
+                min_mapq: self.min_mapq,
+                secondary: self.secondary,
+                error_policy: self.paf_error_policy,
+                format: self.aln_format,
+                identity: self.aln_identity,
+                max_error_frac: self.paf_max_error_frac,
+                split_indel: self.split_indel,
+                ..paf::PafFilter::new(self.min_id, self.min_len)
+            },
+            method: self.pairing,
+            params: self.pairing_params.clone(),
+            aligner: None,
+            rejected: None,
+            require_reciprocal: self.require_reciprocal,
+            outputs: pipeline::OutputLevel::Standard,
+        };
+        fs_err::create_dir_all(&self.out)?;
+        let pafs = stage.read(&self.paf_m2n, &self.paf_n2m)?;
+        let pairs = stage.pair(pafs, &[], &self.out)?;
+
+        fs_err::write(self.out.join(CANDIDATES_FILE), pairing::loci_tsv(&pairs))?;
+        if self.bed {
+            let (nuc, mito) = candidate_bed_records(&pairs);
+            bed::write_bed(&self.out.join(CANDIDATES_NUC_BED_FILE), &nuc)?;
+            bed::write_bed(&self.out.join(CANDIDATES_MITO_BED_FILE), &mito)?;
+        }
+        log::info!(
+            "pair: {} candidate loci → {}",
+            pairs.len(),
+            self.out.join(CANDIDATES_FILE).display()
+        );
+        Ok(())
+    }
+}
+
+/// Nuclear- and mito-side BED records, named `pair_id`, score = identity × 1000.
+fn candidate_bed_records(pairs: &[PairedLocus]) -> (Vec<BedRecord>, Vec<BedRecord>) {
+    let rec = |p: &PairedLocus, contig: &str, start: u32, end: u32| BedRecord {
+        name: Some(p.pair_id.clone()),
+        score: Some((1000.0 * p.aln_ident).round() as f64),
+        strand: Some(p.strand.as_char()),
+        ..BedRecord::new(contig, start, end)
+    };
+    pairs
+        .iter()
+        .map(|p| {
+            (
+                rec(p, &p.nuc_contig, p.nuc_start, p.nuc_end),
+                rec(p, &p.mito_contig, p.mito_start, p.mito_end),
+            )
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring;
+    use clap::Parser;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdPair,
+    }
+
+    // mt is 2 kb; a primary hit on each nuclear contig with its reciprocal, a
+    // MAPQ 0 secondary, a short hit and a low-identity one
+    const M2N: &str = "mt\t2000\t0\t500\t+\tchr1\t30000\t10000\t10500\t490\t500\t60\ttp:A:P\n\
+                       mt\t2000\t800\t1400\t-\tchr2\t30000\t5000\t5600\t570\t600\t60\ttp:A:P\n\
+                       mt\t2000\t800\t1400\t+\tchr1\t30000\t20000\t20600\t560\t600\t0\ttp:A:S\n\
+                       mt\t2000\t1500\t1550\t+\tchr2\t30000\t100\t150\t50\t50\t60\ttp:A:P\n\
+                       mt\t2000\t1600\t1900\t+\tchr2\t30000\t9000\t9300\t200\t300\t60\ttp:A:P\n";
+    const N2M: &str = "chr1\t30000\t10000\t10500\t+\tmt\t2000\t0\t500\t495\t500\t60\ttp:A:P\n\
+                       chr2\t30000\t5000\t5600\t-\tmt\t2000\t800\t1400\t560\t600\t60\ttp:A:P\n";
+
+    // the PAF fixture as BLAST -outfmt 6 (1-based, sstart > send on the
+    // minus strand); BLAST has no secondaries, so the third hit stays
+    const M2N_BLAST: &str = "mt\tchr1\t98.00\t500\t10\t0\t1\t500\t10001\t10500\t0.0\t900\n\
+                             mt\tchr2\t95.00\t600\t30\t0\t801\t1400\t5600\t5001\t0.0\t1000\n\
+                             mt\tchr1\t93.333\t600\t40\t0\t801\t1400\t20001\t20600\t0.0\t950\n\
+                             mt\tchr2\t100.00\t50\t0\t0\t1501\t1550\t101\t150\t1e-20\t90\n\
+                             mt\tchr2\t66.667\t300\t100\t0\t1601\t1900\t9001\t9300\t1e-10\t200\n";
+    const N2M_BLAST: &str = "# BLASTN 2.15.0+\n\
+                             chr1\tmt\t99.00\t500\t5\t0\t10001\t10500\t1\t500\t0.0\t910\n\
+                             chr2\tmt\t93.333\t600\t40\t0\t5001\t5600\t1400\t801\t0.0\t950\n";
+
+    fn write_pafs(dir: &Path) -> (PathBuf, PathBuf) {
+        let (m2n, n2m) = (dir.join("m2n.paf"), dir.join("n2m.paf"));
+        fs_err::write(&m2n, M2N).unwrap();
+        fs_err::write(&n2m, N2M).unwrap();
+        (m2n, n2m)
+    }
+
+    fn run_pair(m2n: &Path, n2m: &Path, out: &Path, extra: &[&str]) {
+        let mut argv = vec![
+            "onsm".to_string(),
+            "--paf-m2n".into(),
+            m2n.display().to_string(),
+            "--paf-n2m".into(),
+            n2m.display().to_string(),
+            "--out".into(),
+            out.display().to_string(),
+        ];
+        argv.extend(extra.iter().map(|s| s.to_string()));
+        Wrap::try_parse_from(argv).unwrap().cmd.run().unwrap();
+    }
+
+    fn candidate_ids(out: &Path) -> Vec<String> {
+        fs_err::read_to_string(out.join(CANDIDATES_FILE))
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|l| l.split('\t').take(4).collect::<Vec<_>>().join(":"))
+            .collect()
+    }
+
+    #[test]
+    fn filters_and_bed_exports() {
+        let td = TempDir::new().unwrap();
+        let (m2n, n2m) = write_pafs(td.path());
+
+        let out = td.path().join("all");
+        run_pair(&m2n, &n2m, &out, &["--bed", "--secondary", "keep"]);
+        assert_eq!(
+            candidate_ids(&out),
+            [
+                "P000001:chr1:10000:10500",
+                "P000002:chr2:5000:5600",
+                "P000003:chr1:20000:20600"
+            ]
+        );
+        let tsv = fs_err::read_to_string(out.join(CANDIDATES_FILE)).unwrap();
+        assert!(
+            tsv.contains(
+                "\n\
+            P000002\tchr2\t5000\t5600\tmt\t800\t1400\t600\t0.9500\t-\t0\t0\ttrue\t.\t.\n"
+            ),
+            "{tsv}"
+        );
+        let nuc = bed::read_bed(&out.join(CANDIDATES_NUC_BED_FILE)).unwrap();
+        assert_eq!(nuc.len(), 3);
+        assert_eq!(nuc[0].score, Some(990.0));
+        let mito = bed::read_bed(&out.join(CANDIDATES_MITO_BED_FILE)).unwrap();
+        assert_eq!(
+            (mito[1].start, mito[1].end, mito[1].strand),
+            (800, 1400, Some('-'))
+        );
+        assert!(out.join(pipeline::PAIRING_REPORT_FILE).exists());
+        let stats: serde_json::Value = serde_json::from_str(
+            &fs_err::read_to_string(out.join(pipeline::PAF_FILTER_STATS_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert!(stats["aligner"].is_null());
+        assert_eq!(stats["mito_to_nuc"]["n_short"], 1);
+        assert_eq!(stats["mito_to_nuc"]["n_low_identity"], 1);
+
+        // the secondary goes by default, or with a MAPQ filter
+        for (name, extra) in [
+            ("default", &[][..]),
+            ("mapq", &["--secondary", "keep", "--min-mapq", "1"]),
+        ] {
+            let out = td.path().join(name);
+            run_pair(&m2n, &n2m, &out, extra);
+            assert_eq!(candidate_ids(&out).len(), 2, "{extra:?}");
+            assert!(!out.join(CANDIDATES_NUC_BED_FILE).exists());
+        }
+        let out = td.path().join("strict");
+        run_pair(&m2n, &n2m, &out, &["--min-id", "0.96"]);
+        assert_eq!(candidate_ids(&out), ["P000001:chr1:10000:10500"]);
+
+        // no nuclear→mito hit overlaps the secondary: one-sided, set aside
+        let out = td.path().join("reciprocal");
+        run_pair(
+            &m2n,
+            &n2m,
+            &out,
+            &[
+                "--secondary",
+                "keep",
+                "--reciprocal-min-overlap",
+                "0.5",
+                "--require-reciprocal",
+            ],
+        );
+        assert_eq!(candidate_ids(&out).len(), 2);
+        let unsupported =
+            fs_err::read_to_string(out.join(pipeline::PAIRS_UNSUPPORTED_FILE)).unwrap();
+        let rows: Vec<&str> = unsupported.lines().skip(1).collect();
+        assert_eq!(rows.len(), 1);
+        assert!(
+            rows[0].starts_with("P000003\tchr1\t20000\t20600\t")
+                && rows[0].ends_with("\tfalse\t.\t."),
+            "{unsupported}"
+        );
+
+        // the best-hit check flags the secondary rather than dropping it
+        let out = td.path().join("rbh");
+        run_pair(
+            &m2n,
+            &n2m,
+            &out,
+            &["--secondary", "keep", "--reciprocal-best"],
+        );
+        let tsv = fs_err::read_to_string(out.join(CANDIDATES_FILE)).unwrap();
+        let rbh: Vec<&str> = tsv
+            .lines()
+            .skip(1)
+            .map(|l| l.rsplit('\t').next().unwrap())
+            .collect();
+        assert_eq!(rbh, ["true", "true", "false"]);
+        let cluster = Wrap::try_parse_from([
+            "onsm",
+            "--paf-m2n",
+            "m2n.paf",
+            "--paf-n2m",
+            "n2m.paf",
+            "--out",
+            "out",
+            "--pairing",
+            "cluster",
+            "--reciprocal-best",
+        ])
+        .unwrap();
+        let e = cluster.cmd.run().unwrap_err().to_string();
+        assert!(e.contains("--pairing reciprocal"), "{e}");
+    }
+
+    #[test]
+    fn blast_tabular_pairs_like_paf() {
+        let td = TempDir::new().unwrap();
+        let (m2n, n2m) = write_pafs(td.path());
+        let paf_out = td.path().join("paf");
+        run_pair(&m2n, &n2m, &paf_out, &["--secondary", "keep"]);
+
+        let (m2n, n2m) = (td.path().join("m2n.tsv"), td.path().join("n2m.tsv"));
+        fs_err::write(&m2n, M2N_BLAST).unwrap();
+        fs_err::write(&n2m, N2M_BLAST).unwrap();
+        let blast_out = td.path().join("blast");
+        run_pair(&m2n, &n2m, &blast_out, &["--aln-format", "blast6"]);
+        let read = |out: &Path| fs_err::read_to_string(out.join(CANDIDATES_FILE)).unwrap();
+        assert_eq!(read(&blast_out), read(&paf_out));
+        let stats: serde_json::Value = serde_json::from_str(
+            &fs_err::read_to_string(blast_out.join(pipeline::PAF_FILTER_STATS_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(stats["mito_to_nuc"]["n_short"], 1);
+        assert_eq!(stats["mito_to_nuc"]["n_low_identity"], 1);
+        assert_eq!(stats["nuc_to_mito"]["n_records"], 2);
+
+        // PAF lines are not BLAST lines
+        let err = Wrap::try_parse_from([
+            "onsm",
+            "--paf-m2n",
+            td.path().join("m2n.paf").to_str().unwrap(),
+            "--paf-n2m",
+            n2m.to_str().unwrap(),
+            "--aln-format",
+            "blast6",
+            "--out",
+            td.path().join("bad").to_str().unwrap(),
+        ])
+        .unwrap()
+        .cmd
+        .run()
+        .unwrap_err();
+        assert!(err.to_string().contains("malformed BLAST record"), "{err}");
+    }
+
+    #[test]
+    fn candidate_columns_are_pairs_tsv_columns() {
+        let all: Vec<&str> = scoring::PAIRS_TSV_HEADER.split('\t').collect();
+        for c in CANDIDATES_HEADER.split('\t') {
+            assert!(all.contains(&c), "{c}");
+        }
+    }
+
+    /// Full classify with stand-in minimap2 (copies the fixture PAFs) and
+    /// samtools (empty BAMs, flat depth): `pair` on the same PAFs must give
+    /// the same loci, filter stats and pairing report.
+    #[cfg(unix)]
+    #[test]
+    fn pairs_match_a_classify_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = TempDir::new().unwrap();
+        let dir = td.path();
+        let (m2n, n2m) = write_pafs(dir);
+        let mito = dir.join("mito.fa");
+        let nuc = dir.join("nuc.fa");
+        fs_err::write(&mito, format!(">mt\n{}\n", "A".repeat(2_000))).unwrap();
+        fs_err::write(
+            &nuc,
+            format!(
+                ">chr1\n{}\n>chr2\n{}\n",
+                "C".repeat(30_000),
+                "G".repeat(30_000)
+            ),
+        )
+        .unwrap();
+        let reads = dir.join("reads.fq");
+        let read = "ACGT".repeat(1_000);
+        fs_err::write(
+            &reads,
+            format!("@r1\n{read}\n+\n{}\n", "I".repeat(read.len())).repeat(3),
+        )
+        .unwrap();
+
+        let minimap2 = dir.join("minimap2");
+        fs_err::write(
+            &minimap2,
+            format!(
+                r#"#!/bin/sh
+# asm mode ends in QUERY -o OUT; read mapping writes nothing
+prev=""
+while [ $# -gt 1 ]; do
+  if [ "$1" = -o ]; then
+    case "$prev" in
+    *mito.fa) cp '{}' "$2" ;;
+    *) cp '{}' "$2" ;;
+    esac
+  fi
+  prev="$1"; shift
+done
+"#,
+                m2n.display(),
+                n2m.display()
+            ),
+        )
+        .unwrap();
+        let samtools = dir.join("samtools");
+        fs_err::write(
+            &samtools,
+            r#"#!/bin/sh
+case "$1" in
+--version) echo "samtools 1.9" ;;
+sort) cat > /dev/null; for out; do :; done; : > "$out" ;;
+depth) printf 'c\t1\t10\n' ;;
+flagstat) printf '3 + 0 in total (QC-passed reads + QC-failed reads)\n3 + 0 mapped (100.00%% : N/A)\n' ;;
+esac
+"#,
+        )
+        .unwrap();
+        for bin in [&minimap2, &samtools] {
+            std::fs::set_permissions(bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let run = dir.join("run");
+        {
+            let _runs = crate::util::logging::TEST_RUNS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            #[derive(Parser)]
+            struct Classify {
+                #[command(flatten)]
+                cmd: crate::subcommands::classify::CmdClassify,
+            }
+            let classify = |out: &Path, extra: &[&str]| {
+                let mut argv = vec![
+                    "onsm",
+                    "--mito",
+                    mito.to_str().unwrap(),
+                    "--nuclear",
+                    nuc.to_str().unwrap(),
+                    "--reads",
+                    reads.to_str().unwrap(),
+                    "--platform",
+                    "hifi",
+                    "--out",
+                    out.to_str().unwrap(),
+                    "--minimap2",
+                    minimap2.to_str().unwrap(),
+                    "--samtools",
+                    samtools.to_str().unwrap(),
+                    "--threads",
+                    "1",
+                    // BLAST has no secondaries to drop
+                    "--keep-secondary",
+                ];
+                argv.extend_from_slice(extra);
+                Classify::try_parse_from(argv).unwrap().cmd.run().unwrap();
+            };
+            classify(&run, &[]);
+            // the same alignments as BLAST tabular, given instead of mapped
+            let (m2n, n2m) = (dir.join("m2n.tsv"), dir.join("n2m.tsv"));
+            fs_err::write(&m2n, M2N_BLAST).unwrap();
+            fs_err::write(&n2m, N2M_BLAST).unwrap();
+            classify(
+                &dir.join("run_blast"),
+                &[
+                    "--m2n-aln",
+                    m2n.to_str().unwrap(),
+                    "--n2m-aln",
+                    n2m.to_str().unwrap(),
+                    "--aln-format",
+                    "blast6",
+                ],
+            );
+        }
+
+        // pair on the PAFs classify kept, so the stats name the same files
+        let tmp = crate::io::tmpfiles::TmpArtifacts::in_dir(&run.join("tmp"));
+        let out = dir.join("pair");
+        run_pair(&tmp.paf_m2n, &tmp.paf_n2m, &out, &["--secondary", "keep"]);
+
+        let pairs_tsv = fs_err::read_to_string(run.join("pairs.tsv")).unwrap();
+        let mut lines = pairs_tsv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let cols: Vec<usize> = CANDIDATES_HEADER
+            .split('\t')
+            .map(|c| header.iter().position(|h| *h == c).unwrap())
+            .collect();
+        let mut from_classify = format!("{CANDIDATES_HEADER}\n");
+        for l in lines {
+            let f: Vec<&str> = l.split('\t').collect();
+            let row: Vec<&str> = cols.iter().map(|&i| f[i]).collect();
+            from_classify.push_str(&row.join("\t"));
+            from_classify.push('\n');
+        }
+        assert_eq!(
+            fs_err::read_to_string(out.join(CANDIDATES_FILE)).unwrap(),
+            from_classify
+        );
+        assert_eq!(candidate_ids(&out).len(), 3);
+        let blast_pairs = fs_err::read_to_string(dir.join("run_blast/pairs.tsv")).unwrap();
+        assert_eq!(blast_pairs, pairs_tsv);
+        let manifest = model::RunManifest::load_from(&dir.join("run_blast")).unwrap();
+        assert_eq!(manifest.aln_format, AlnFormat::Blast6);
+        assert_eq!(manifest.m2n_aln, Some(dir.join("m2n.tsv")));
+
+        let report = |d: &Path| fs_err::read_to_string(d.join(pipeline::PAIRING_REPORT_FILE));
+        assert_eq!(report(&out).unwrap(), report(&run).unwrap());
+        let stats = |d: &Path| -> serde_json::Value {
+            serde_json::from_str(
+                &fs_err::read_to_string(d.join(pipeline::PAF_FILTER_STATS_FILE)).unwrap(),
+            )
+            .unwrap()
+        };
+        let (a, b) = (stats(&run), stats(&out));
+        for dir in ["mito_to_nuc", "nuc_to_mito"] {
+            assert_eq!(a[dir], b[dir], "{dir}");
+        }
+    }
+}
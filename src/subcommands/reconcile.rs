@@ -0,0 +1,297 @@
+//! `onsm reconcile` — resolve nuclear loci that the runs of several organelles
+//! on one nuclear assembly all call NUMTs (see `crate::organelles`).
+//!
+//! Reads pairs.tsv + classification.tsv of each run and writes
+//! `reconciled_pairs.tsv` (one row per Likely_NUMT pair, with its conflict and
+//! status) and `organelle_summary.tsv` (per organelle, and the union over all).
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+use crate::model::RunManifest;
+use crate::organelles::{self, OrganelleLocus, DEFAULT_IDENT_MARGIN};
+use crate::pairing::parse_fraction;
+use crate::scoring;
+use crate::summary;
+
+pub const RECONCILED_PAIRS_FILE: &str = "reconciled_pairs.tsv";
+pub const ORGANELLE_SUMMARY_FILE: &str = "organelle_summary.tsv";
+
+#[derive(Args, Debug)]
+pub struct CmdReconcile {
+    /// Run directory of one organelle, as NAME=DIR (e.g. `mito=out_mt`,
+    /// `plastid=out_cp`) or DIR (named after the directory); at least two
+    #[arg(long = "run", value_name = "NAME=DIR", required = true)]
+    pub runs: Vec<String>,
+
+    /// Identity lead the best organelle needs over the runner-up to own a
+    /// shared locus; closer ones are `shared_organelle_homology`
+    #[arg(long, value_name = "FRAC", default_value_t = DEFAULT_IDENT_MARGIN, value_parser = parse_fraction)]
+    pub ident_margin: f32,
+
+    /// Where to write reconciled_pairs.tsv and organelle_summary.tsv
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: PathBuf,
+}
+
+/// `NAME=DIR`, or `DIR` named after its last component.
+fn parse_run(spec: &str) -> Result<(String, PathBuf)> {
+    let (name, dir) = match spec.split_once('=') {
+        Some((n, d)) => (n.trim().to_string(), PathBuf::from(d)),
+        None => {
+            let dir = PathBuf::from(spec);
+            let name = dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (name, dir)
+        }
+    };
+    if name.is_empty() || name == organelles::ANY_ORGANELLE || name.contains('\t') {
+        return Err(anyhow!(
+            "--run {spec}: give the organelle a name, as NAME=DIR ('{}' is reserved)",
+            organelles::ANY_ORGANELLE
+        ));
+    }
+    Ok((name, dir))
+}
+
+/// The Likely_NUMT loci of a run directory.
+fn load_numts(dir: &Path) -> Result<Vec<OrganelleLocus>> {
+    let class_p = dir.join("classification.tsv");
+    for p in [dir.join("pairs.tsv"), class_p.clone()] {
+        if !p.exists() {
+            return Err(anyhow!(
+                "{} not found (is this an onsm run directory?)",
+                p.display()
+            ));
+        }
+    }
+    let calls = summary::parse_calls_tsv_file(&class_p)?;
+    Ok(scoring::read_run_pairs(dir)?
+        .into_iter()
+        .filter(|r| calls.get(&r.locus.pair_id).map(String::as_str) == Some("Likely_NUMT"))
+        .map(|r| OrganelleLocus {
+            pair_id: r.locus.pair_id,
+            nuc_contig: r.locus.nuc_contig,
+            nuc_start: r.locus.nuc_start,
+            nuc_end: r.locus.nuc_end,
+            aln_ident: r.locus.aln_ident,
+        })
+        .collect())
+}
+
+/// Refuse runs against different nuclear assemblies; runs without a manifest
+/// cannot be checked.
+fn check_same_nuclear(runs: &[(String, PathBuf)]) -> Result<()> {
+    let mut first: Option<(&str, PathBuf)> = None;
+    for (name, dir) in runs {
+        let Ok(m) = RunManifest::load_from(dir) else {
+            log::warn!(
+                "reconcile: {} has no readable run_manifest.json; cannot check it used the same nuclear assembly",
+                dir.display()
+            );
+            continue;
+        };
+        let nuc = fs_err::canonicalize(&m.nuclear).unwrap_or(m.nuclear);
+        match &first {
+            None => first = Some((name, nuc)),
+            Some((n0, nuc0)) if *nuc0 != nuc => {
+                return Err(anyhow!(
+                    "runs '{n0}' and '{name}' were classified against different nuclear assemblies \
+                     ({} vs {}); reconcile runs of one nuclear assembly",
+                    nuc0.display(),
+                    nuc.display()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl CmdReconcile {
+    pub fn run(self) -> Result<()> {
+        let runs = self
+            .runs
+            .iter()
+            .map(|s| parse_run(s))
+            .collect::<Result<Vec<_>>>()?;
+        if runs.len() < 2 {
+            return Err(anyhow!(
+                "reconcile needs at least two --run directories (one per organelle)"
+            ));
+        }
+        for (i, (name, _)) in runs.iter().enumerate() {
+            if runs[..i].iter().any(|(n, _)| n == name) {
+                return Err(anyhow!(
+                    "--run: organelle name '{name}' is given twice; name them as NAME=DIR"
+                ));
+            }
+        }
+        check_same_nuclear(&runs)?;
+
+        let loci = runs
+            .iter()
+            .map(|(name, dir)| Ok((name.clone(), load_numts(dir)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let r = organelles::reconcile(&loci, self.ident_margin);
+
+        fs_err::create_dir_all(&self.out_dir)?;
+        fs_err::write(
+            self.out_dir.join(RECONCILED_PAIRS_FILE),
+            organelles::reconciled_pairs_tsv(&r),
+        )?;
+        fs_err::write(
+            self.out_dir.join(ORGANELLE_SUMMARY_FILE),
+            organelles::organelle_summary_tsv(&r),
+        )?;
+        let any = r.nuclear_bp_numt_any();
+        log::info!(
+            "reconcile: {} Likely_NUMT loci over {} organelles, {} conflicts; \
+             {} nuclear bp covered by any organelle homolog ({} summed per organelle, {} counted twice) → {}",
+            r.loci.len(),
+            runs.len(),
+            r.n_conflicts,
+            any,
+            r.nuclear_bp_numt_sum,
+            r.nuclear_bp_numt_sum - any,
+            self.out_dir.join(ORGANELLE_SUMMARY_FILE).display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdReconcile,
+    }
+
+    /// A run directory with one Likely_NUMT per `(contig, start, end, ident)`,
+    /// plus one Ambiguous pair that reconcile ignores.
+    fn run_dir(dir: &Path, loci: &[(&str, u32, u32, f32)]) {
+        fs_err::create_dir_all(dir).unwrap();
+        let header = scoring::PAIRS_TSV_HEADER;
+        let row = |pid: &str, c: &str, s: u32, e: u32, id: f32| {
+            format!("{pid}\t{c}\t{s}\t{e}\tmt\t0\t100\t100\t{id}\t1\t1\t0.5\t0.5\t0.5\t0.4\t1.0\t+\t0\t0\t0\t0\n")
+        };
+        let mut pairs = format!("{header}\n");
+        let mut calls = "pair_id\tcall\tconfidence\treason_codes\n".to_string();
+        for (i, &(c, s, e, id)) in loci.iter().enumerate() {
+            let pid = format!("P{:06}", i + 1);
+            pairs.push_str(&row(&pid, c, s, e, id));
+            calls.push_str(&format!("{pid}\tLikely_NUMT\t0.5\tscore_difference\n"));
+        }
+        pairs.push_str(&row("P999999", "chr1", 0, 100_000, 0.99));
+        calls.push_str("P999999\tAmbiguous\t0.1\tscore_difference\n");
+        fs_err::write(dir.join("pairs.tsv"), pairs).unwrap();
+        fs_err::write(dir.join("classification.tsv"), calls).unwrap();
+    }
+
+    fn manifest(dir: &Path, nuclear: &Path) {
+        let m = RunManifest::new(
+            Path::new("organelle.fa"),
+            nuclear,
+            &[],
+            "hifi",
+            1,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MERGE_GAP,
+            model::FLANK_BP,
+            model::WIN_BP,
+        );
+        RunManifest::save_to(dir, &m).unwrap();
+    }
+
+    fn argv(runs: &[String], out: &Path) -> Vec<String> {
+        let mut v = vec!["onsm".to_string()];
+        for r in runs {
+            v.extend(["--run".to_string(), r.clone()]);
+        }
+        v.extend(["--out-dir".to_string(), out.display().to_string()]);
+        v
+    }
+
+    #[test]
+    fn reconciles_run_directories() {
+        let td = TempDir::new().unwrap();
+        let mt = td.path().join("out_mt");
+        let cp = td.path().join("out_cp");
+        run_dir(&mt, &[("chr1", 1_000, 2_000, 0.97), ("chr2", 0, 300, 0.9)]);
+        run_dir(&cp, &[("chr1", 1_800, 2_400, 0.92)]);
+        let out = td.path().join("rec");
+        let runs = [format!("mito={}", mt.display()), cp.display().to_string()];
+        Wrap::try_parse_from(argv(&runs, &out))
+            .unwrap()
+            .cmd
+            .run()
+            .unwrap();
+
+        let pairs = fs_err::read_to_string(out.join(RECONCILED_PAIRS_FILE)).unwrap();
+        let lines: Vec<&str> = pairs.lines().collect();
+        assert_eq!(
+            lines[1..],
+            [
+                "mito\tP000001\tchr1\t1000\t2000\t0.9700\tC000001\towned",
+                "out_cp\tP000001\tchr1\t1800\t2400\t0.9200\tC000001\tceded",
+                "mito\tP000002\tchr2\t0\t300\t0.9000\t.\tunique",
+            ]
+        );
+        let summary = fs_err::read_to_string(out.join(ORGANELLE_SUMMARY_FILE)).unwrap();
+        assert_eq!(
+            summary.lines().last(),
+            Some("any\t3\t1700\t1\t1\t1\t0"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn refuses_bad_run_sets() {
+        let td = TempDir::new().unwrap();
+        let mt = td.path().join("mt");
+        let cp = td.path().join("cp");
+        run_dir(&mt, &[("chr1", 0, 100, 0.9)]);
+        run_dir(&cp, &[("chr1", 0, 100, 0.9)]);
+        let out = td.path().join("rec");
+        let err = |runs: &[String]| {
+            Wrap::try_parse_from(argv(runs, &out))
+                .unwrap()
+                .cmd
+                .run()
+                .unwrap_err()
+                .to_string()
+        };
+        let d = |p: &Path| p.display().to_string();
+
+        let e = err(&[d(&mt)]);
+        assert!(e.contains("at least two --run"), "{e}");
+        let e = err(&[format!("x={}", d(&mt)), format!("x={}", d(&cp))]);
+        assert!(e.contains("'x' is given twice"), "{e}");
+        let e = err(&[format!("any={}", d(&mt)), d(&cp)]);
+        assert!(e.contains("'any' is reserved"), "{e}");
+        let e = err(&[d(&mt), d(&td.path().join("missing"))]);
+        assert!(e.contains("pairs.tsv not found"), "{e}");
+
+        // runs against two nuclear assemblies
+        manifest(&mt, Path::new("/data/nuc_v1.fa"));
+        manifest(&cp, Path::new("/data/nuc_v2.fa"));
+        let e = err(&[d(&mt), d(&cp)]);
+        assert!(e.contains("different nuclear assemblies"), "{e}");
+        manifest(&cp, Path::new("/data/nuc_v1.fa"));
+        Wrap::try_parse_from(argv(&[d(&mt), d(&cp)], &out))
+            .unwrap()
+            .cmd
+            .run()
+            .unwrap();
+    }
+}
@@ -1,17 +1,49 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::Args;
 use fs_err as fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::io::{bam, paf};
-use crate::model::{self, ClassifyParams, Weights};
+use crate::boundary::{self, BedBounds};
+use crate::bundle::RunDir;
+use crate::columns::ColumnsPreset;
+use crate::compat::OutputCompat;
+use crate::control_region::{self, ControlRegionSpec, ControlRegions};
+use crate::density::{self, DensityOptions};
+use crate::io::bam::{self, BamBackend};
+use crate::io::bam_index::{self, IndexedBams};
+use crate::io::fasta::{GapIndex, LengthCache};
+use crate::io::paf;
+use crate::io::paf::{AlnFormat, IdentityKind, PafErrorPolicy};
+#[cfg(feature = "http")]
+use crate::io::post;
+use crate::io::tmpfiles::{ReuseStage, TmpArtifacts};
+use crate::io::window_cache::{self, WindowCache};
+use crate::model::{
+    self, ClassifyParams, CoverageSummary, EvidenceParams, PairedLocus, PairingParams, SpanSummary,
+    Weights,
+};
+use crate::pairing::{self, PairingMethod};
+use crate::pipeline::{self, EvidenceSource, OutputLevel};
+use crate::regions;
 use crate::scoring;
-use crate::summary;
-use crate::util::{logging, mapping};
+use crate::summary::{GenomeSizes, SummaryUnits, Tier};
+use crate::tracking::{self, TrackingReference};
+use crate::util::runlock::RunLock;
+use crate::util::status::{self, RunStatus};
+use crate::util::{logging, mapping, strict, warnings};
+
+/// Per-pair evidence for the scoring step.
+enum Evidence {
+    /// Looked up pair by pair (read BAMs or evidence cache).
+    Source(Box<dyn EvidenceSource>),
+    /// Already complete (rebuilt from the previous run's results).
+    Collected(Box<CoverageSummary>, Box<SpanSummary>),
+}
 
 #[derive(Args, Debug)]
 pub struct CmdReuse {
-    /// Output directory from a previous `onsm classify`
+    /// Output directory from a previous `onsm classify`, or its bundle (`onsm export-bundle`; rescoring only)
     #[arg(long, value_name = "DIR")]
     pub from: PathBuf,
 
@@ -26,67 +58,1546 @@ pub struct CmdReuse {
     /// Optional: override minimap2 for any future embedding features
     #[arg(long)]
     pub minimap2: Option<PathBuf>,
+
+    /// Earliest step to redo (default: the earliest the previous run's --keep allows)
+    #[arg(long, value_enum, default_value_t = ReuseStage::Auto)]
+    pub redo_from: ReuseStage,
+
+    /// Score exactly the loci of this hand-edited pair list (pairs.tsv or candidates.tsv columns) instead of pairing; evidence is recomputed from the previous run's BAMs
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["redo_from", "pairing", "reciprocal_min_overlap", "reciprocal_best", "collapse_min_overlap", "split_indel", "aln_format", "aln_identity"]
+    )]
+    pub pairs_from: Option<PathBuf>,
+
+    /// Pairing strategy (default: the one recorded in the run manifest)
+    #[arg(long, value_enum)]
+    pub pairing: Option<PairingMethod>,
+
+    /// Minimum reciprocal overlap, 0-1 (default: the one recorded in the run manifest)
+    #[arg(long, value_name = "FRAC", value_parser = pairing::parse_fraction)]
+    pub reciprocal_min_overlap: Option<f32>,
+
+    /// Score only loci a nuclear→mito alignment backs too, listing the rest in pairs_unsupported.tsv (default: as the previous run did)
+    #[arg(long)]
+    pub require_reciprocal: bool,
+
+    /// Flag loci whose nuclear→mito hit is not the best hit of their nuclear interval (rbh=false) when re-pairing (default: as the previous run did)
+    #[arg(long)]
+    pub reciprocal_best: bool,
+
+    /// Collapse loci overlapping by this fraction of the shorter on the nuclear side, 0-1, 0 = off (default: the one recorded in the run manifest)
+    #[arg(long, value_name = "FRAC", value_parser = pairing::parse_fraction)]
+    pub collapse_min_overlap: Option<f32>,
+
+    /// Split alignments at indels longer than this when re-pairing, 0 = off (default: the one recorded in the run manifest)
+    #[arg(long, value_name = "BP")]
+    pub split_indel: Option<u32>,
+
+    /// Format of the assembly alignments re-paired from: paf, blast6 or sam (default: the one recorded in the run manifest)
+    #[arg(long, value_enum)]
+    pub aln_format: Option<AlnFormat>,
+
+    /// Identity --min-id applies to when re-pairing: blast or gap-compressed (default: the one recorded in the run manifest)
+    #[arg(long, value_enum)]
+    pub aln_identity: Option<IdentityKind>,
+
+    /// Malformed PAF lines: abort (strict) or count and skip (skip)
+    #[arg(long, value_enum, default_value_t = PafErrorPolicy::Strict)]
+    pub paf_error_policy: PafErrorPolicy,
+
+    /// With --paf-error-policy skip: fail if more than this fraction of lines is malformed
+    #[arg(long, default_value_t = paf::MAX_PAF_ERROR_FRAC)]
+    pub paf_max_error_frac: f64,
+
+    /// When re-pairing: stop before collecting evidence if there are more candidate pairs than this (0: no limit); a quarter of it warns
+    #[arg(long, value_name = "N", default_value_t = pipeline::DEFAULT_MAX_PAIRS)]
+    pub max_pairs: usize,
+
+    /// Give each pair a stable_id inherited from the best-overlapping pair of this earlier run (tracking.tsv)
+    #[arg(long, value_name = "RUN_DIR")]
+    pub track_against: Option<PathBuf>,
+
+    /// With --track-against: minimum nuclear reciprocal overlap (0-1) to inherit an ID
+    #[arg(long, value_name = "FRAC", default_value_t = tracking::DEFAULT_TRACK_MIN_OVERLAP, value_parser = pairing::parse_fraction)]
+    pub track_min_overlap: f32,
+
+    /// Stream pairs and evidence through disk so memory scales with --chunk-size, not pair count
+    #[arg(long)]
+    pub low_memory: bool,
+
+    /// Fetch every BAM window with samtools, bypassing the window cache in the previous run's tmp/evidence/cache/
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Recompute evidence with samtools, or natively from the indexed BAMs in tmp/ (no samtools needed)
+    #[arg(long, value_enum, default_value_t = BamBackend::Samtools)]
+    pub bam_backend: BamBackend,
+
+    /// At the end of the run, POST summary.json and the run's identity as JSON to this URL (token from ONSM_POST_TOKEN); failures only warn
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "URL", value_parser = post::parse_url)]
+    pub post_summary: Option<String>,
+
+    /// With --post-summary: timeout of each attempt
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "SECS", default_value_t = post::DEFAULT_TIMEOUT_SECS)]
+    pub post_timeout: u64,
+
+    /// Pairs per evidence chunk with --low-memory
+    #[arg(long, default_value_t = pipeline::DEFAULT_CHUNK_SIZE)]
+    pub chunk_size: usize,
+
+    /// Write numt_density.bedgraph / nimt_density.bedgraph (called bp per window)
+    #[arg(long)]
+    pub density_bedgraph: bool,
+
+    /// Window size for --density-bedgraph
+    #[arg(
+        long,
+        value_name = "BP",
+        default_value_t = density::DEFAULT_DENSITY_WINDOW,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub density_window: u32,
+
+    /// With --density-bedgraph, write empty windows as 0 instead of omitting them
+    #[arg(long)]
+    pub density_zero_windows: bool,
+
+    /// Merge Likely_NUMT pairs within this many bp on a nuclear contig into one region (numt_regions.tsv/.bed)
+    #[arg(long, value_name = "BP", default_value_t = regions::DEFAULT_REGION_MERGE_GAP)]
+    pub region_merge_gap: u32,
+
+    /// Cluster pairs of any call within this many bp on a nuclear contig into one region (regions.tsv)
+    #[arg(long, value_name = "BP", default_value_t = regions::DEFAULT_REGION_GAP)]
+    pub region_gap: u32,
+
+    /// Also write the nuclear side of every pair as pairs_nuc.<bounds>.bed (outer, inner, point; comma-separated)
+    #[arg(long, value_enum, value_name = "BOUNDS", value_delimiter = ',')]
+    pub bed_bounds: Vec<BedBounds>,
+
+    /// Pairs the --bed-bounds BEDs take: all, or high (high-confidence calls only)
+    #[arg(long, value_enum, default_value_t = Tier::All)]
+    pub bed_tier: Tier,
+
+    /// Units of the percentage metrics in summary.tsv/summary.json (ppm: e.g. nuclear_numt_ppm)
+    #[arg(long, value_enum, default_value_t = SummaryUnits::Percent)]
+    pub summary_units: SummaryUnits,
+
+    /// Write pairs.tsv, classification.tsv and the summary with the columns of an earlier release only (else the run's)
+    #[arg(long, value_enum, value_name = "VERSION")]
+    pub output_compat: Option<OutputCompat>,
+
+    /// pairs.tsv columns to write, comma-separated (pair_id is always first)
+    #[arg(
+        long,
+        value_name = "COLS",
+        value_delimiter = ',',
+        conflicts_with = "columns_preset"
+    )]
+    pub columns: Vec<String>,
+
+    /// Column set for pairs.tsv when --columns is not given
+    #[arg(long, value_enum, default_value_t = ColumnsPreset::Full)]
+    pub columns_preset: ColumnsPreset,
+
+    /// Add cost_ms and n_subprocess_calls (what each pair's evidence took) to pairs.tsv, and write run_stats.json; pairs rescored from results.json have no cost (.)
+    #[arg(long)]
+    pub cost_columns: bool,
+
+    /// Files to write: summary (run_manifest.json, summary.tsv/.json, warnings.json only), standard, or full (also --cost-columns and --density-bedgraph)
+    #[arg(long, value_enum, default_value_t = OutputLevel::Standard)]
+    pub outputs: OutputLevel,
+
+    /// Flag loci whose estimated nuclear copy number (rnuc, to the nearest 0.5) is at least this as possible_collapsed_repeat
+    #[arg(long, value_name = "CN", default_value_t = model::COLLAPSED_REPEAT_CN)]
+    pub collapsed_repeat_cn: f32,
+
+    /// Call loci whose nuclear side is more than this fraction soft-masked (lowercase) in the assembly Ambiguous, with reason softmasked_locus
+    #[arg(long, value_name = "FRAC", value_parser = pairing::parse_fraction)]
+    pub max_softmask_frac: Option<f32>,
+
+    /// Call an otherwise Ambiguous locus Shared_Support when reads span both sides at least this often...
+    #[arg(long, value_name = "FRAC", default_value_t = model::SHARED_MIN_SPAN, value_parser = pairing::parse_fraction)]
+    pub shared_min_span: f32,
+
+    /// ...and both depth ratios (rnuc, rmito) are within this of 1
+    #[arg(long, value_name = "X", default_value_t = model::SHARED_DEPTH_TOL)]
+    pub shared_depth_tol: f32,
+
+    /// Count Shared_Support bp as NUMT (nuclear) and NIMT (mito) in the summary percentages
+    #[arg(long)]
+    pub shared_in_percentages: bool,
+
+    /// Scale the span weight down by median read length / spanning window when reads are shorter than the window
+    #[arg(long)]
+    pub span_read_len_scaling: bool,
+
+    /// The mito was assembled from the run's reads: caveat NIMT calls (self_assembly_caveat), as when the previous run declared or detected it
+    #[arg(long)]
+    pub mito_assembled_from_these_reads: bool,
+
+    /// Control region (D-loop) of a mito contig, as for classify; replaces the run's (repeatable)
+    #[arg(long, value_name = "CONTIG:START-END", value_parser = control_region::parse_spec)]
+    pub mito_control_region: Vec<ControlRegionSpec>,
+
+    /// Scale the identity term of pairs inside a --mito-control-region by this (1: leave it alone)
+    #[arg(long, value_name = "W", default_value_t = model::CONTROL_REGION_IDENTITY_WEIGHT)]
+    pub control_region_identity_weight: f32,
+
+    /// Score evidence (cache, coverage.json) collected with other window/MAPQ settings than the run manifest's, with a warning
+    #[arg(long)]
+    pub ignore_evidence_params: bool,
+
+    /// Fail the run (after writing outputs; see error.json) when warnings exceed the strict policy
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Per-category thresholds for --strict, overriding the defaults
+    #[arg(long, value_name = "JSON", requires = "strict")]
+    pub strict_policy: Option<PathBuf>,
+
+    /// Exit with code 10 when the run completed but its warnings exceed the strict policy (ONSM_STATUS: OK_WITH_WARNINGS)
+    #[arg(long)]
+    pub warnings_as_exit_code: bool,
+
+    /// Start even though onsm.lock says another run is using --out-dir (for a lock left by a run on another host, or a crash)
+    #[arg(long)]
+    pub force_lock: bool,
 }
 
 impl CmdReuse {
     pub fn run(self) -> Result<()> {
-        logging::init_logging(&self.out_dir)?;
+        let warnings_as_exit_code = self.warnings_as_exit_code;
+        status::finish(self.run_status(), warnings_as_exit_code)
+    }
+
+    /// The run itself; `warnings.json` is written by the time it returns.
+    pub fn run_status(mut self) -> Result<RunStatus> {
+        fs::create_dir_all(&self.out_dir)?;
+        // Before the log is truncated: a second run on this --out-dir stops here
+        let lock = RunLock::acquire(&self.out_dir, self.force_lock)?;
+        let _log = logging::init_logging(&self.out_dir)?;
+        let _warnings = warnings::RunWarnings::begin(&self.out_dir);
+        lock.report_takeover();
+        // a bundle is read from a temporary copy for the whole run
+        let source = RunDir::open(&self.from)?;
+        self.from = source.path().to_path_buf();
+        let strict = strict::policy(self.strict, self.strict_policy.as_deref())?;
+        let track_against = self
+            .track_against
+            .as_deref()
+            .map(TrackingReference::load)
+            .transpose()?;
 
-        // 1) Load manifest
+        // 1) Load manifest, see what the previous run kept
         let m = model::RunManifest::load_from(&self.from)?;
-        let tmp = self.from.join("tmp");
-
-        // 2) Resolve tools (samtools used for coverage)
-        let (_mm2_bin, sam_bin) =
-            mapping::resolve_bins(self.minimap2.as_deref(), self.samtools.as_deref())?;
-        log::info!("REUSE: using samtools at {}", sam_bin.display());
-
-        // 3) Derive artifact paths from the previous run
-        let paf_m2n = tmp.join("mito_to_nuc.paf");
-        let paf_n2m = tmp.join("nuc_to_mito.paf");
-        let bam_r2n = tmp.join("reads_to_nuc.bam");
-        let bam_r2m = tmp.join("reads_to_mito.bam");
-
-        for p in [&paf_m2n, &paf_n2m, &bam_r2n, &bam_r2m] {
-            if !p.exists() {
-                anyhow::bail!("Required artifact missing: {}", p.display());
-            }
+        let output_compat = self.output_compat.unwrap_or(m.output_compat);
+        output_compat.check_options(&[
+            (
+                "--summary-units",
+                self.summary_units != SummaryUnits::Percent,
+            ),
+            ("--cost-columns", self.cost_columns),
+            ("--outputs full", self.outputs.full()),
+        ])?;
+        self.outputs.check_options(&[
+            ("--cost-columns", self.cost_columns),
+            ("--density-bedgraph", self.density_bedgraph),
+            ("--bed-bounds", !self.bed_bounds.is_empty()),
+            ("--track-against", self.track_against.is_some()),
+        ])?;
+        let pairs_columns = output_compat
+            .pairs_columns(&self.columns, self.columns_preset)?
+            .with_cost(self.cost_columns || self.outputs.full());
+        let mut tmp = TmpArtifacts::in_dir(&self.from.join("tmp"));
+        // alignments classify was given are re-paired from where they are
+        if let (Some(m2n), Some(n2m)) = (&m.m2n_aln, &m.n2m_aln) {
+            tmp.paf_m2n = m2n.clone();
+            tmp.paf_n2m = n2m.clone();
+        }
+        // a hand-edited pair list replaces pairing: only the BAMs are needed
+        let stage = if self.pairs_from.is_some() {
+            tmp.check_bams()?;
+            ReuseStage::Evidence
+        } else {
+            tmp.resolve_stage(self.redo_from)?
+        };
+        let repair = self.pairing.is_some_and(|p| p != m.pairing)
+            || self.pairing_params(&m) != m.pairing_params
+            || self.aln_identity.is_some_and(|k| k != m.aln_identity)
+            || self.split_indel.is_some_and(|b| b != m.split_indel);
+        if stage == ReuseStage::Scoring && repair {
+            tmp.resolve_stage(ReuseStage::Pairing)
+                .context("--pairing/--reciprocal-min-overlap/--reciprocal-best/--collapse-min-overlap/--aln-identity/--split-indel differ from the previous run")?;
+            anyhow::bail!(
+                "--pairing/--reciprocal-min-overlap/--reciprocal-best/--collapse-min-overlap/--aln-identity/--split-indel differ from the previous run, so the loci must be re-paired: use --redo-from pairing"
+            );
+        }
+        log::info!("REUSE: redoing from {stage:?} ({})", tmp.dir.display());
+        if m.span_evidence_disabled {
+            log::warn!(
+                "REUSE: the run's reads were too short for span evidence; it stays disabled"
+            );
+        }
+        let self_assembly_caveat =
+            self.mito_assembled_from_these_reads || m.self_assembly.is_caveated();
+        if m.self_assembly.is_caveated() {
+            log::warn!(
+                "REUSE: the run's mito looks assembled from its reads; NIMT calls stay caveated"
+            );
         }
 
-        // 4) Prepare new out dir
+        // 2) Prepare new out dir
         fs::create_dir_all(&self.out_dir)?;
 
-        // 5) Parse & pair
-        let m2n = paf::read_paf(&paf_m2n, m.min_id, m.min_len)?;
-        let n2m = paf::read_paf(&paf_n2m, m.min_id, m.min_len)?;
-        let pairs = paf::pair_and_merge(&m2n, n2m, m.merge_gap)?;
-        log::info!("REUSE: paired {} candidate loci", pairs.len());
-
-        // 6) Coverage & spans
-        let (coverage, spans) = bam::compute_coverage_and_spans_with_tools(
-            &bam_r2n, &bam_r2m, &pairs, m.flank_bp, m.win_bp, &sam_bin,
-        )?;
-
-        // 7) Score & classify (same defaults)
-        let weights = Weights::default();
-        let params = ClassifyParams::default();
-        let (pairs_tsv, classes_tsv) =
-            scoring::classify_pairs(&pairs, &coverage, &spans, weights, params)?;
-
-        // 8) Write outputs
-        fs::write(self.out_dir.join("pairs.tsv"), &pairs_tsv)?;
-        fs::write(self.out_dir.join("classification.tsv"), &classes_tsv)?;
-        serde_json::to_writer_pretty(
-            fs::File::create(self.out_dir.join("coverage.json"))?,
-            &coverage,
-        )?;
-
-        // 9) Summary (recomputed on the new outputs)
-        let calls = summary::parse_calls_tsv_str(&classes_tsv);
-        let summary_tbl = summary::compute_percentages(&m.mito, &m.nuclear, &pairs, &calls)?;
-        summary::write_summary_tsv(&self.out_dir.join("summary.tsv"), &summary_tbl)?;
+        // 3) Loci: from the run's results.json when rescoring without the
+        //    evidence cache, else re-paired from the PAFs or taken from the cache
+        let (pairs, evidence) = if let Some(list) = &self.pairs_from {
+            let lengths = match &m.genome_sizes {
+                Some(sizes) => sizes.contigs.clone(),
+                None => GenomeSizes::read(&LengthCache::default(), &m.mito, &m.nuclear)?.contigs,
+            };
+            let loci = scoring::read_pair_list(list, &lengths)?;
+            log::info!(
+                "REUSE: scoring the {} loci of {} (no pairing)",
+                loci.len(),
+                list.display()
+            );
+            (
+                self.drop_one_sided(loci)?,
+                Evidence::Source(self.evidence_source(&m, &tmp, None)?),
+            )
+        } else if stage == ReuseStage::Scoring && tmp.rescore_from_results() {
+            let (loci, coverage, spans) = pipeline::stored_evidence(&self.from)?;
+            log::info!(
+                "REUSE: no {}; rescoring {} loci from {}",
+                tmp.evidence_cache.display(),
+                loci.len(),
+                tmp.results.display()
+            );
+            (
+                self.drop_one_sided(loci)?,
+                Evidence::Collected(Box::new(coverage), Box::new(spans)),
+            )
+        } else {
+            let cached = match stage {
+                ReuseStage::Pairing | ReuseStage::Scoring => {
+                    Some(pipeline::CachedEvidence::load(&tmp.evidence_cache)?)
+                }
+                _ => None,
+            };
+            let (pairs, cached) = match cached {
+                Some((loci, cached)) if stage == ReuseStage::Scoring => {
+                    log::info!("REUSE: rescoring {} cached loci", loci.len());
+                    (self.drop_one_sided(loci)?, Some(cached))
+                }
+                cached => (self.pair(&m, &tmp)?, cached.map(|(_, c)| c)),
+            };
+            (
+                pairs,
+                Evidence::Source(self.evidence_source(&m, &tmp, cached)?),
+            )
+        };
+
+        // 5) Score & classify (same defaults), write outputs and the summary
+        // With the assembly sizes in the manifest the FASTAs are optional;
+        // without the nuclear one, loci just aren't checked for gaps
+        let gaps = if m.genome_sizes.is_some() && !m.nuclear.exists() {
+            warnings::warn(
+                warnings::ASSEMBLY_MISSING,
+                format!(
+                    "{} not found; assembly gaps and soft-masking are not flagged",
+                    m.nuclear.display()
+                ),
+            );
+            GapIndex::default()
+        } else {
+            GapIndex::scan(&m.nuclear)?
+        };
+        let control_regions = if self.mito_control_region.is_empty() {
+            m.mito_control_regions.clone()
+        } else {
+            let mito_lens: HashMap<String, u64> = match &m.genome_sizes {
+                Some(sizes) => sizes.contigs.mito.clone().into_iter().collect(),
+                None => (*LengthCache::default().lengths(&m.mito)?).clone(),
+            };
+            ControlRegions::resolve(&self.mito_control_region, &mito_lens, &m.mito_contig_meta)?
+        };
+        let ctx = pipeline::OutputContext {
+            out_dir: &self.out_dir,
+            mito_fa: &m.mito,
+            nuc_fa: &m.nuclear,
+            lengths: &LengthCache::default(),
+            genome_sizes: m.genome_sizes.as_ref(),
+            weights: Weights::default(),
+            params: ClassifyParams {
+                collapsed_repeat_cn: self.collapsed_repeat_cn,
+                max_softmask_frac: self.max_softmask_frac,
+                shared_min_span: self.shared_min_span,
+                shared_depth_tol: self.shared_depth_tol,
+                shared_in_percentages: self.shared_in_percentages,
+                span_window: 2 * m.win_bp,
+                span_read_len_scaling: self.span_read_len_scaling,
+                span_evidence_disabled: m.span_evidence_disabled,
+                self_assembly_caveat,
+                control_region_identity_weight: self.control_region_identity_weight,
+                manual_pair_list: self.pairs_from.is_some(),
+                ..ClassifyParams::default()
+            },
+            density: (self.density_bedgraph || self.outputs.full()).then_some(DensityOptions {
+                window: self.density_window,
+                keep_empty: self.density_zero_windows,
+            }),
+            pairs_columns: &pairs_columns,
+            region_merge_gap: self.region_merge_gap,
+            region_gap: self.region_gap,
+            summary_units: self.summary_units,
+            mito_meta: &m.mito_contig_meta,
+            evidence_cache: None,
+            excluded_nuclear: &m.excluded_nuclear,
+            gaps: &gaps,
+            control_regions: &control_regions,
+            evidence_params: EvidenceParams::new(m.flank_bp, m.win_bp)
+                .with_depth_supplementary(m.depth_supplementary)
+                .with_min_mapq(m.min_span_mapq)
+                .with_junction_min_clip(m.junction_min_clip),
+            ignore_evidence_params: self.ignore_evidence_params,
+            bed_bounds: &self.bed_bounds,
+            bed_tier: self.bed_tier,
+            output_compat,
+            outputs: self.outputs,
+        };
+        let summary = match evidence {
+            // results.json is already in memory, so --low-memory saves nothing here
+            Evidence::Collected(coverage, spans) => {
+                pipeline::write_in_memory(&ctx, &pairs, &coverage, &spans)?
+            }
+            Evidence::Source(evidence) if self.low_memory => {
+                // keep the source run's tmp/ untouched
+                let work = self.out_dir.join("tmp");
+                let s = pipeline::write_low_memory(
+                    &ctx,
+                    pairs,
+                    evidence.as_ref(),
+                    &work,
+                    self.chunk_size,
+                )?;
+                let _ = fs::remove_dir(&work);
+                s
+            }
+            Evidence::Source(evidence) => {
+                let (coverage, spans) = pipeline::collect_evidence(&pairs, evidence.as_ref())?;
+                pipeline::write_in_memory(&ctx, &pairs, &coverage, &spans)?
+            }
+        };
+        if let Some(list) = &self.pairs_from {
+            // the manifest travels with outputs that no pairing produced
+            let manifest = model::RunManifest {
+                pairs_from: Some(list.clone()),
+                ..m.clone()
+            };
+            model::RunManifest::save_to(&self.out_dir, &manifest)?;
+        }
+        if let Some(reference) = &track_against {
+            reference.track_run(&self.out_dir, self.track_min_overlap)?;
+        }
+        #[cfg(feature = "http")]
+        if let Some(url) = &self.post_summary {
+            let payload = post::Payload::new(
+                "reuse",
+                &self.out_dir,
+                &m,
+                &self.from,
+                &summary,
+                self.summary_units,
+            );
+            post::post_summary(
+                &post::PostOptions::from_env(url, self.post_timeout),
+                &payload,
+            );
+        }
+        let report = warnings::current_report();
+        if let Some(policy) = &strict {
+            policy.enforce(&self.out_dir, &report, summary.n_pairs)?;
+        }
+        let status = RunStatus::of_run(&strict.unwrap_or_default(), &report, summary.n_pairs);
 
         log::info!("REUSE: done → {}", self.out_dir.display());
-        Ok(())
+        Ok(status)
+    }
+
+    /// Evidence from the cache, else recomputed from the read BAMs with samtools
+    /// or, with `--bam-backend native`, without it.
+    fn evidence_source(
+        &self,
+        m: &model::RunManifest,
+        tmp: &TmpArtifacts,
+        cached: Option<pipeline::CachedEvidence>,
+    ) -> Result<Box<dyn EvidenceSource>> {
+        if let Some(cached) = cached {
+            return Ok(Box::new(cached));
+        }
+        let native = self.bam_backend == BamBackend::Native;
+        if native {
+            if let Some(cram) = [&tmp.bam_r2n, &tmp.bam_r2m]
+                .into_iter()
+                .find(|p| bam_index::is_cram(p))
+            {
+                bail!(
+                    "{}: CRAMs are read with samtools; rerun with --bam-backend samtools",
+                    cram.display()
+                );
+            }
+        }
+        let mut cram_references = Vec::new();
+        for (aln, fasta) in [(&tmp.bam_r2n, &m.nuclear), (&tmp.bam_r2m, &m.mito)] {
+            if let Some(fa) = cram_reference(aln, fasta)? {
+                cram_references.push((aln.clone(), fa));
+            }
+        }
+        // Older runs did not record their contig lengths
+        let contig_lengths = match &m.genome_sizes {
+            Some(sizes) => Some(sizes.contigs.clone()),
+            None if m.mito.exists() && m.nuclear.exists() => {
+                Some(GenomeSizes::read(&LengthCache::default(), &m.mito, &m.nuclear)?.contigs)
+            }
+            None => None,
+        };
+        let sam_bin = if native {
+            log::info!("REUSE: reading the BAMs natively");
+            self.samtools.clone().unwrap_or_else(|| "samtools".into())
+        } else {
+            let (_mm2_bin, sam_bin) =
+                mapping::resolve_bins(self.minimap2.as_deref(), self.samtools.as_deref())?;
+            log::info!("REUSE: using samtools at {}", sam_bin.display());
+            mapping::probe_samtools(&sam_bin)
+                .require(bam::evidence_features(m.depth_supplementary))?;
+            sam_bin
+        };
+        Ok(Box::new(pipeline::SamtoolsEvidence {
+            bam_reads_to_nuc: tmp.bam_r2n.clone(),
+            bam_reads_to_mito: tmp.bam_r2m.clone(),
+            samtools: sam_bin,
+            flank: m.flank_bp,
+            win: m.win_bp,
+            max_reads: m.max_reads_per_window,
+            depth_supplementary: m.depth_supplementary,
+            min_span_mapq: m.min_span_mapq,
+            junction_min_clip: m.junction_min_clip,
+            mito_meta: m.mito_contig_meta.clone(),
+            calls: Default::default(),
+            native: native.then(IndexedBams::default),
+            batched: Default::default(),
+            cram_references,
+            contig_lengths,
+            cache: if self.no_cache {
+                None
+            } else {
+                Some(WindowCache::open(
+                    &tmp.window_cache,
+                    window_cache::DEFAULT_MAX_FILES,
+                )?)
+            },
+        }))
+    }
+
+    /// `--require-reciprocal` on loci that were not re-paired; those of a run
+    /// that required it already are reciprocal.
+    fn drop_one_sided(&self, loci: Vec<PairedLocus>) -> Result<Vec<PairedLocus>> {
+        if !self.require_reciprocal {
+            return Ok(loci);
+        }
+        pipeline::drop_one_sided(loci, self.outputs.per_pair().then_some(&*self.out_dir))
+    }
+
+    /// The run's pairing settings with this reuse's overrides.
+    fn pairing_params(&self, m: &model::RunManifest) -> PairingParams {
+        let recorded = &m.pairing_params;
+        PairingParams {
+            merge_gap: recorded.merge_gap,
+            reciprocal_min_overlap: self
+                .reciprocal_min_overlap
+                .unwrap_or(recorded.reciprocal_min_overlap),
+            collapse_min_overlap: self
+                .collapse_min_overlap
+                .unwrap_or(recorded.collapse_min_overlap),
+            reciprocal_best: self.reciprocal_best || recorded.reciprocal_best,
+        }
+    }
+
+    /// Parse the kept PAFs and pair them (writes paf_filter_stats.json).
+    fn pair(&self, m: &model::RunManifest, tmp: &TmpArtifacts) -> Result<Vec<PairedLocus>> {
+        let method = self.pairing.unwrap_or(m.pairing);
+        let params = self.pairing_params(m);
+        method.check_reciprocal_best(params.reciprocal_best)?;
+        let stage = pipeline::PairingStage {
+            filter: paf::PafFilter {
+                min_mapq: m.min_paf_mapq,
+                secondary: paf::SecondaryPolicy::keeping(m.keep_secondary),
+                error_policy: self.paf_error_policy,
+                format: self.aln_format.unwrap_or(m.aln_format),
+                identity: self.aln_identity.unwrap_or(m.aln_identity),
+                max_error_frac: self.paf_max_error_frac,
+                split_indel: self.split_indel.unwrap_or(m.split_indel),
+                ..paf::PafFilter::new(m.min_id, m.min_len)
+            },
+            method,
+            params,
+            aligner: m.m2n_aln.is_none().then_some(&m.mm2_asm),
+            rejected: None,
+            require_reciprocal: self.require_reciprocal || m.require_reciprocal,
+            outputs: self.outputs,
+        };
+        let pafs = stage.read(&tmp.paf_m2n, &tmp.paf_n2m)?;
+        // same nuclear contigs as the original run
+        let mut pairs = stage.pair(pafs, &m.excluded_nuclear, &self.out_dir)?;
+        pipeline::check_pair_count(&pairs, self.max_pairs, m.min_id, m.min_len)?;
+        // re-paired loci have fresh CIs; the run's clip dispersion needs its BAM
+        if m.boundary_clips {
+            if tmp.bam_r2n.exists() {
+                let (_, sam_bin) =
+                    mapping::resolve_bins(self.minimap2.as_deref(), self.samtools.as_deref())?;
+                let reference = cram_reference(&tmp.bam_r2n, &m.nuclear)?;
+                boundary::add_clip_dispersion(
+                    &mut pairs,
+                    &sam_bin,
+                    &tmp.bam_r2n,
+                    reference.as_deref(),
+                )?;
+            } else {
+                warnings::warn(
+                    warnings::IGNORED_OPTION,
+                    format!(
+                        "{} not found; the run's --boundary-clips is left out of the re-paired CIs",
+                        tmp.bam_r2n.display()
+                    ),
+                );
+            }
+        }
+        Ok(pairs)
+    }
+}
+
+/// The FASTA samtools decodes the kept CRAM `aln` with: the run's assembly
+/// `fasta` its reads were mapped to, when it is still there; `None` for a
+/// BAM. A CRAM without its `.crai` is an error, as region queries need it.
+fn cram_reference(aln: &Path, fasta: &Path) -> Result<Option<PathBuf>> {
+    if !bam_index::is_cram(aln) {
+        return Ok(None);
+    }
+    bam_index::require_index(aln)?;
+    if !fasta.exists() {
+        warnings::warn(
+            warnings::ASSEMBLY_MISSING,
+            format!(
+                "{} not found; samtools decodes {} against the reference its header names",
+                fasta.display(),
+                aln.display()
+            ),
+        );
+        return Ok(None);
+    }
+    Ok(Some(fasta.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::PairsColumns;
+    use crate::io::tmpfiles;
+    use crate::mito_meta::MitoContigMeta;
+    use crate::model::PairEvidence;
+    use crate::pairing::PairingStrategy;
+    use clap::Parser;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct Wrap {
+        #[command(flatten)]
+        cmd: CmdReuse,
+    }
+
+    fn reuse(from: &Path, out: &Path, extra: &[&str]) -> Result<()> {
+        let mut argv = vec![
+            "onsm",
+            "--from",
+            from.to_str().unwrap(),
+            "--out-dir",
+            out.to_str().unwrap(),
+        ];
+        argv.extend_from_slice(extra);
+        let _runs = crate::util::logging::TEST_RUNS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        Wrap::try_parse_from(argv).unwrap().cmd.run()
+    }
+
+    /// Evidence that favours NUMT for loci on chr1 and NIMT elsewhere.
+    struct Fixed;
+
+    impl EvidenceSource for Fixed {
+        fn pair_evidence(&self, p: &PairedLocus) -> Result<PairEvidence> {
+            let ((depth, span), read_len) = if p.nuc_contig == "chr1" {
+                (((30.0, 5.0), (0.8, 0.1)), (15_000.0, 14_000.0))
+            } else {
+                (((5.0, 300.0), (0.1, 0.9)), (300.0, 15_000.0))
+            };
+            Ok(PairEvidence {
+                depth,
+                span,
+                read_len,
+                read_div: Default::default(),
+                span_reads: None,
+                junction_clips: (0, 0),
+                clamped_window: None,
+                invalid_window: None,
+            })
+        }
+
+        fn params(&self) -> Option<EvidenceParams> {
+            Some(EvidenceParams::new(model::FLANK_BP, model::WIN_BP))
+        }
+    }
+
+    /// A finished classify run with every tmp/ artifact (BAMs are placeholders).
+    fn synthetic_run(dir: &Path) -> PathBuf {
+        let run = dir.join("run");
+        let tmp = TmpArtifacts::in_dir(&run.join("tmp"));
+        fs::create_dir_all(&tmp.dir).unwrap();
+        let mito = dir.join("mito.fa");
+        let nuc = dir.join("nuc.fa");
+        fs::write(&mito, format!(">mt\n{}\n", "A".repeat(5_000))).unwrap();
+        fs::write(
+            &nuc,
+            format!(
+                ">chr1\n{}\n>chr2\n{}\n",
+                "C".repeat(50_000),
+                "G".repeat(50_000)
+            ),
+        )
+        .unwrap();
+        let manifest = model::RunManifest::new(
+            &mito,
+            &nuc,
+            &[],
+            "hifi",
+            1,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MERGE_GAP,
+            model::FLANK_BP,
+            model::WIN_BP,
+        );
+        model::RunManifest::save_to(&run, &manifest).unwrap();
+
+        fs::write(
+            &tmp.paf_m2n,
+            "mt\t5000\t0\t1000\t+\tchr1\t50000\t5000\t6000\t990\t1000\t60\n\
+             mt\t5000\t2000\t2500\t+\tchr2\t50000\t20000\t20500\t490\t500\t60\n\
+             mt\t5000\t3000\t3400\t-\tchr1\t50000\t30000\t30400\t396\t400\t60\n",
+        )
+        .unwrap();
+        fs::write(
+            &tmp.paf_n2m,
+            "chr1\t50000\t5000\t6000\t+\tmt\t5000\t0\t1000\t995\t1000\t60\n\
+             chr2\t50000\t20000\t20500\t+\tmt\t5000\t2000\t2500\t490\t500\t60\n",
+        )
+        .unwrap();
+        for bam in [&tmp.bam_r2n, &tmp.bam_r2m] {
+            fs::write(bam, "not a real BAM").unwrap();
+        }
+
+        let m2n = paf::read_paf(
+            &tmp.paf_m2n,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MIN_PAF_MAPQ,
+        )
+        .unwrap();
+        let n2m = paf::read_paf(
+            &tmp.paf_n2m,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MIN_PAF_MAPQ,
+        )
+        .unwrap();
+        let pairs = crate::pairing::ReciprocalBest
+            .pair(&m2n, &n2m, &PairingParams::default())
+            .unwrap();
+        let (coverage, spans) = pipeline::collect_evidence(&pairs, &Fixed).unwrap();
+        let ctx = pipeline::OutputContext {
+            out_dir: &run,
+            mito_fa: &mito,
+            nuc_fa: &nuc,
+            lengths: &LengthCache::default(),
+            genome_sizes: None,
+            weights: Weights::default(),
+            params: ClassifyParams::default(),
+            density: None,
+            pairs_columns: &PairsColumns::default(),
+            region_merge_gap: regions::DEFAULT_REGION_MERGE_GAP,
+            region_gap: regions::DEFAULT_REGION_GAP,
+            summary_units: SummaryUnits::default(),
+            mito_meta: &MitoContigMeta::default(),
+            evidence_cache: Some(&tmp.evidence_cache),
+            excluded_nuclear: &[],
+            gaps: &GapIndex::default(),
+            control_regions: &ControlRegions::default(),
+            evidence_params: EvidenceParams::new(model::FLANK_BP, model::WIN_BP),
+            ignore_evidence_params: false,
+            bed_bounds: &[],
+            bed_tier: Tier::All,
+            output_compat: OutputCompat::Latest,
+            outputs: OutputLevel::Standard,
+        };
+        pipeline::write_in_memory(&ctx, &pairs, &coverage, &spans).unwrap();
+        run
+    }
+
+    fn read(p: &Path) -> String {
+        fs::read_to_string(p).unwrap()
+    }
+
+    #[test]
+    fn reuse_applies_the_runs_paf_mapq_filter() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let tmp = run.join("tmp");
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::Evidence).unwrap();
+        // the chr2 locus, placed with MAPQ 5
+        let m2n = read(&tmp.join(tmpfiles::PAF_MITO_TO_NUC)).replacen(
+            "\t490\t500\t60",
+            "\t490\t500\t5",
+            1,
+        );
+        fs::write(tmp.join(tmpfiles::PAF_MITO_TO_NUC), m2n).unwrap();
+
+        // the manifest round-trips the filter; older ones have none
+        let mut m = model::RunManifest::load_from(&run).unwrap();
+        assert_eq!(m.min_paf_mapq, 0);
+        m.min_paf_mapq = 10;
+        model::RunManifest::save_to(&run, &m).unwrap();
+        assert_eq!(
+            model::RunManifest::load_from(&run).unwrap().min_paf_mapq,
+            10
+        );
+        let mut json: serde_json::Value =
+            serde_json::from_str(&read(&run.join("run_manifest.json"))).unwrap();
+        json.as_object_mut().unwrap().remove("min_paf_mapq");
+        json.as_object_mut().unwrap().remove("keep_secondary");
+        json.as_object_mut().unwrap().remove("min_span_mapq");
+        let old: model::RunManifest = serde_json::from_value(json).unwrap();
+        assert_eq!(old.min_paf_mapq, model::MIN_PAF_MAPQ);
+        assert_eq!(old.min_span_mapq, model::MIN_MAPQ);
+        // …and paired their secondaries, which new runs drop by default
+        assert!(old.keep_secondary && !m.keep_secondary);
+
+        let b = dir.path().join("b");
+        reuse(&run, &b, &[]).unwrap();
+        let stats: serde_json::Value =
+            serde_json::from_str(&read(&b.join("paf_filter_stats.json"))).unwrap();
+        assert_eq!(stats["mito_to_nuc"]["n_low_mapq"], 1);
+        assert!(read(&run.join("pairs.tsv")).contains("\tchr2\t"));
+        assert!(!read(&b.join("pairs.tsv")).contains("\tchr2\t"));
+    }
+
+    #[test]
+    fn reuse_degrades_with_what_was_kept() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let tmp = run.join("tmp");
+        let original = read(&run.join("classification.tsv"));
+        assert!(original.contains("Likely_NUMT") && original.contains("Likely_NIMT"));
+
+        // --keep evidence: re-pairs from the PAFs, scores from the cache
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::Evidence).unwrap();
+        let b = dir.path().join("b");
+        reuse(&run, &b, &[]).unwrap();
+        assert_eq!(read(&b.join("classification.tsv")), original);
+        assert!(b.join("paf_filter_stats.json").exists());
+
+        // PAFs that now yield a locus the cache never scored: that needs the BAMs
+        let mut m2n = read(&tmp.join(tmpfiles::PAF_MITO_TO_NUC));
+        m2n.push_str("mt\t5000\t4000\t4500\t+\tchr2\t50000\t40000\t40500\t495\t500\t60\n");
+        fs::write(tmp.join(tmpfiles::PAF_MITO_TO_NUC), m2n).unwrap();
+        let e = reuse(&run, &dir.path().join("x"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("no cached evidence"), "{e}");
+
+        // evidence cache alone: rescoring only
+        fs::remove_file(tmp.join(tmpfiles::PAF_MITO_TO_NUC)).unwrap();
+        let c = dir.path().join("c");
+        reuse(&run, &c, &[]).unwrap();
+        assert_eq!(read(&c.join("classification.tsv")), original);
+        assert_eq!(read(&c.join("pairs.tsv")), read(&run.join("pairs.tsv")));
+        assert!(!c.join("paf_filter_stats.json").exists());
+        let d = dir.path().join("d");
+        reuse(&run, &d, &["--low-memory", "--chunk-size", "2"]).unwrap();
+        assert_eq!(read(&d.join("classification.tsv")), original);
+
+        // asking for more than was kept names what's missing
+        let e = reuse(&run, &dir.path().join("x"), &["--pairing", "cluster"]).unwrap_err();
+        let e = format!("{e:#}");
+        assert!(e.contains("re-pairing needs mito_to_nuc.paf"), "{e}");
+        let e = reuse(&run, &dir.path().join("x"), &["--redo-from", "evidence"])
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("reads_to_nuc.bam"), "{e}");
+
+        // no tmp/ at all (--keep none): rescoring from the run's results.json
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::None).unwrap();
+        for (out, extra) in [("e", &[][..]), ("f", &["--redo-from", "scoring"][..])] {
+            let out = dir.path().join(out);
+            reuse(&run, &out, extra).unwrap();
+            assert_eq!(read(&out.join("classification.tsv")), original);
+            assert_eq!(read(&out.join("pairs.tsv")), read(&run.join("pairs.tsv")));
+            let cov = |d: &Path| -> serde_json::Value {
+                serde_json::from_str(&read(&d.join("coverage.json"))).unwrap()
+            };
+            assert_eq!(cov(&out), cov(&run));
+        }
+        // …which still can't re-pair
+        let e = reuse(&run, &dir.path().join("x"), &["--redo-from", "pairing"])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("what is there allows rescoring (--redo-from scoring)"),
+            "{e}"
+        );
+
+        fs::remove_file(run.join(tmpfiles::RESULTS_JSON)).unwrap();
+        let e = reuse(&run, &dir.path().join("x"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(e.starts_with("nothing to reuse"), "{e}");
+    }
+
+    #[test]
+    fn repairs_from_gzipped_pafs() {
+        use std::io::Write;
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let tmp = run.join("tmp");
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::Evidence).unwrap();
+        for name in [tmpfiles::PAF_MITO_TO_NUC, tmpfiles::PAF_NUC_TO_MITO] {
+            let plain = tmp.join(name);
+            let f = fs::File::create(tmp.join(format!("{name}.gz"))).unwrap();
+            let mut gz = flate2::write::GzEncoder::new(f, flate2::Compression::default());
+            gz.write_all(read(&plain).as_bytes()).unwrap();
+            gz.finish().unwrap();
+            fs::remove_file(plain).unwrap();
+        }
+        let b = dir.path().join("b");
+        reuse(&run, &b, &["--redo-from", "pairing"]).unwrap();
+        assert_eq!(
+            read(&b.join("classification.tsv")),
+            read(&run.join("classification.tsv"))
+        );
+    }
+
+    #[test]
+    fn rescores_from_a_bundle() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::Evidence).unwrap();
+        let bundle = dir.path().join("run.onsm");
+        let m = crate::bundle::export(&run, &bundle, true).unwrap();
+        assert!(m
+            .members
+            .iter()
+            .any(|m| m.path == format!("tmp/{}", tmpfiles::EVIDENCE_CACHE)));
+        let imported = dir.path().join("imported");
+        crate::bundle::unpack(&bundle, &imported).unwrap();
+        let loci = read(&imported.join(crate::bundle::LOCI_FASTA));
+        assert!(loci.contains("_nuc chr1:5000-6000\nCCCC"), "{loci}");
+        assert!(loci.contains("_mito mt:0-1000\nAAAA"), "{loci}");
+
+        // the imported directory and the bundle itself rescore as the run did
+        for (from, out) in [(&imported, "b"), (&bundle, "c")] {
+            let out = dir.path().join(out);
+            reuse(from, &out, &["--redo-from", "scoring"]).unwrap();
+            for f in ["classification.tsv", "pairs.tsv"] {
+                assert_eq!(read(&out.join(f)), read(&run.join(f)), "{f}");
+            }
+        }
+        // …but have no PAFs to re-pair from
+        let e = reuse(&bundle, &dir.path().join("x"), &["--redo-from", "pairing"]).unwrap_err();
+        assert!(format!("{e:#}").contains("mito_to_nuc.paf"), "{e:#}");
+    }
+
+    #[test]
+    fn summary_from_recorded_genome_sizes() {
+        use crate::summary::GenomeSizes;
+        use crate::util::warnings::{WarningsReport, ASSEMBLY_MISSING};
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let (mito, nuc) = (dir.path().join("mito.fa"), dir.path().join("nuc.fa"));
+        let mut m = model::RunManifest::load_from(&run).unwrap();
+        m.genome_sizes = Some(GenomeSizes::read(&LengthCache::default(), &mito, &nuc).unwrap());
+        model::RunManifest::save_to(&run, &m).unwrap();
+
+        // rescoring with the assemblies gone gives the same summary, with a warning
+        fs::remove_file(&mito).unwrap();
+        fs::remove_file(&nuc).unwrap();
+        let b = dir.path().join("b");
+        reuse(&run, &b, &["--redo-from", "scoring"]).unwrap();
+        assert_eq!(read(&b.join("summary.tsv")), read(&run.join("summary.tsv")));
+        assert_eq!(
+            read(&b.join("classification.tsv")),
+            read(&run.join("classification.tsv"))
+        );
+        let report = WarningsReport::load_from(&b).unwrap().unwrap();
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.category == ASSEMBLY_MISSING));
+
+        // older runs without the sizes still need them
+        m.genome_sizes = None;
+        model::RunManifest::save_to(&run, &m).unwrap();
+        assert!(reuse(&run, &dir.path().join("c"), &["--redo-from", "scoring"]).is_err());
+    }
+
+    #[test]
+    fn evidence_from_other_settings_is_refused() {
+        use crate::util::warnings::{WarningsReport, EVIDENCE_PARAMS};
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let tmp = run.join("tmp");
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::Evidence).unwrap();
+        let cache_path = tmp.join(tmpfiles::EVIDENCE_CACHE);
+        let cache = read(&cache_path);
+        let (header, rows) = cache.split_once('\n').unwrap();
+        assert_eq!(
+            header,
+            r#"{"evidence_params":{"flank_bp":500,"win_bp":250,"min_mapq":20,"depth_supplementary":false,"junction_min_clip":100},"genome_medians":[30.0,5.0]}"#
+        );
+        let evidence_warnings = |out: &Path| {
+            WarningsReport::load_from(out)
+                .unwrap()
+                .unwrap()
+                .warnings
+                .into_iter()
+                .find(|w| w.category == EVIDENCE_PARAMS)
+        };
+
+        // a cache from a run with another spanning window, on both paths
+        fs::write(
+            &cache_path,
+            cache.replace("\"win_bp\":250", "\"win_bp\":300"),
+        )
+        .unwrap();
+        for extra in [&[][..], &["--low-memory"][..]] {
+            let e = reuse(&run, &dir.path().join("x"), extra)
+                .unwrap_err()
+                .to_string();
+            assert!(e.contains("(win_bp 300 → 250)"), "{e}");
+            assert!(e.contains("--ignore-evidence-params"), "{e}");
+        }
+        let b = dir.path().join("b");
+        reuse(&run, &b, &["--ignore-evidence-params"]).unwrap();
+        let w = evidence_warnings(&b).expect("warned");
+        assert!(w.examples[0].contains("scoring it anyway"), "{w:?}");
+
+        // caches from before the header are scored, with a warning
+        fs::write(&cache_path, rows).unwrap();
+        let c = dir.path().join("c");
+        reuse(&run, &c, &[]).unwrap();
+        assert_eq!(
+            read(&c.join("classification.tsv")),
+            read(&run.join("classification.tsv"))
+        );
+        let w = evidence_warnings(&c).expect("warned");
+        assert!(
+            w.examples[0].contains("does not record its settings"),
+            "{w:?}"
+        );
+
+        // rescoring from coverage.json + results.json checks coverage.json's
+        tmpfiles::apply_keep_policy(&tmp, tmpfiles::KeepPolicy::None).unwrap();
+        let cov_path = run.join("coverage.json");
+        let mut cov: serde_json::Value = serde_json::from_str(&read(&cov_path)).unwrap();
+        cov["params"]["flank_bp"] = 400.into();
+        cov["params"]["min_mapq"] = 0.into();
+        fs::write(&cov_path, cov.to_string()).unwrap();
+        let e = reuse(&run, &dir.path().join("x"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("depth evidence was collected with other settings than this run (flank_bp 400 → 500, min_mapq 0 → 20)"),
+            "{e}"
+        );
+        cov.as_object_mut().unwrap().remove("params");
+        fs::write(&cov_path, cov.to_string()).unwrap();
+        let d = dir.path().join("d");
+        reuse(&run, &d, &[]).unwrap();
+        assert_eq!(evidence_warnings(&d).map(|w| w.count), Some(2));
+    }
+
+    /// Rescoring from results.json alone (no coverage.json either) reproduces
+    /// the stored ratios and calls.
+    #[test]
+    fn rescoring_needs_only_results_json() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::None).unwrap();
+        fs::remove_file(run.join("coverage.json")).unwrap();
+        let out = dir.path().join("out");
+        reuse(&run, &out, &["--low-memory"]).unwrap();
+        assert_eq!(
+            read(&out.join("classification.tsv")),
+            read(&run.join("classification.tsv"))
+        );
+        assert_eq!(read(&out.join("pairs.tsv")), read(&run.join("pairs.tsv")));
+    }
+
+    #[test]
+    fn track_against_carries_ids_forward() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::None).unwrap();
+        let e = reuse(
+            &run,
+            &dir.path().join("x"),
+            &["--track-against", "/nonexistent"],
+        )
+        .unwrap_err();
+        assert!(
+            format!("{e:#}").contains("--track-against /nonexistent"),
+            "{e:#}"
+        );
+
+        let a = dir.path().join("a");
+        reuse(&run, &a, &["--track-against", run.to_str().unwrap()]).unwrap();
+        let tracking = read(&a.join(tracking::TRACKING_FILE));
+        let mut lines = tracking.lines();
+        assert_eq!(lines.next(), Some(tracking::TRACKING_HEADER));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len() + 1, read(&a.join("pairs.tsv")).lines().count());
+        assert!(rows.iter().all(|r| r.contains("\tmatched\t")), "{tracking}");
+        assert!(rows[0].starts_with("P000001\tL000001\tmatched\tP000001\t1.0000"));
+
+        // tracked against a tracked run: the IDs come from its tracking.tsv
+        let b = dir.path().join("b");
+        reuse(&run, &b, &["--track-against", a.to_str().unwrap()]).unwrap();
+        assert_eq!(read(&b.join(tracking::TRACKING_FILE)), tracking);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn unreachable_post_summary_only_warns() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::None).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{port}/runs");
+        let out = dir.path().join("out");
+        reuse(&run, &out, &["--post-summary", &url, "--post-timeout", "2"]).unwrap();
+        assert!(out.join("summary.json").exists());
+        let w = read(&out.join(warnings::WARNINGS_FILE));
+        assert!(w.contains(warnings::POST_SUMMARY), "{w}");
+    }
+
+    #[test]
+    fn strict_fails_after_writing_outputs() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let paf_m2n = run.join("tmp").join(tmpfiles::PAF_MITO_TO_NUC);
+        let mut m2n = read(&paf_m2n);
+        m2n.push_str(&"garbage line\n".repeat(8));
+        fs::write(&paf_m2n, m2n).unwrap();
+        let lenient = [
+            "--redo-from",
+            "pairing",
+            "--paf-error-policy",
+            "skip",
+            "--paf-max-error-frac",
+            "0.9",
+        ];
+
+        // without --strict the skipped lines are only warnings
+        let a = dir.path().join("a");
+        reuse(&run, &a, &lenient).unwrap();
+        assert!(!a.join(strict::ERROR_FILE).exists());
+
+        let b = dir.path().join("b");
+        let e = reuse(&run, &b, &[&lenient[..], &["--strict"]].concat())
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("malformed_paf_line fired"), "{e}");
+        assert_eq!(
+            read(&b.join("classification.tsv")),
+            read(&a.join("classification.tsv"))
+        );
+        let err: strict::StrictError =
+            serde_json::from_str(&read(&b.join(strict::ERROR_FILE))).unwrap();
+        assert_eq!(err.violations[0].category, warnings::MALFORMED_PAF_LINE);
+
+        // a policy file raising the limit lets the same run pass
+        let policy = dir.path().join("policy.json");
+        fs::write(&policy, r#"{"malformed_paf_line": {"max_count": 1000}}"#).unwrap();
+        let argv = [
+            &lenient[..],
+            &["--strict", "--strict-policy", policy.to_str().unwrap()],
+        ]
+        .concat();
+        reuse(&run, &b, &argv).unwrap();
+        assert!(!b.join(strict::ERROR_FILE).exists());
+
+        // --strict-policy alone is a usage error
+        let e = Wrap::try_parse_from([
+            "onsm",
+            "--from",
+            "r",
+            "--out-dir",
+            "o",
+            "--strict-policy",
+            "p.json",
+        ]);
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn output_levels_change_the_files_not_the_summary() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::Evidence).unwrap();
+        let files = |d: &Path| -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(d)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let run_files = ["onsm.log", "summary.json", "summary.tsv", "warnings.json"];
+        let standard = [
+            &run_files[..],
+            &[
+                "classification.tsv",
+                "coverage.json",
+                "cross_call_conflicts.tsv",
+                "numt_regions.bed",
+                "numt_regions.tsv",
+                "paf_filter_stats.json",
+                "pairing_report.tsv",
+                "pairs.tsv",
+                "regions.tsv",
+                "results.json",
+            ],
+        ]
+        .concat();
+        let full = [
+            &standard[..],
+            &[
+                "nimt_density.bedgraph",
+                "numt_density.bedgraph",
+                "run_stats.json",
+            ],
+        ]
+        .concat();
+        let sorted = |names: &[&str]| {
+            let mut v: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+            v.sort();
+            v
+        };
+        let summary = read(&run.join("summary.tsv"));
+        for (level, expected) in [
+            ("summary", &run_files[..]),
+            ("standard", &standard),
+            ("full", &full),
+        ] {
+            // the streaming path skips the same files
+            for streaming in [false, true] {
+                let out = dir.path().join(format!("{level}_{streaming}"));
+                let mut argv = vec!["--outputs", level];
+                if streaming {
+                    argv.extend(["--low-memory", "--chunk-size", "2"]);
+                }
+                reuse(&run, &out, &argv).unwrap();
+                assert_eq!(files(&out), sorted(expected), "{level} {streaming}");
+                assert_eq!(
+                    read(&out.join("summary.tsv")),
+                    summary,
+                    "{level} {streaming}"
+                );
+            }
+        }
+
+        // outputs --outputs summary would leave out are refused
+        let e = reuse(
+            &run,
+            &dir.path().join("x"),
+            &["--outputs", "summary", "--bed-bounds", "point"],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            e.contains("--bed-bounds conflicts with --outputs summary"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn run_status_after_outputs_are_written() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let status_of = |out: &Path, extra: &[&str]| {
+            let mut argv = vec![
+                "onsm",
+                "--from",
+                run.to_str().unwrap(),
+                "--out-dir",
+                out.to_str().unwrap(),
+            ];
+            argv.extend_from_slice(extra);
+            let _runs = crate::util::logging::TEST_RUNS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            Wrap::try_parse_from(argv).unwrap().cmd.run_status()
+        };
+
+        let a = dir.path().join("a");
+        assert_eq!(
+            status_of(&a, &["--redo-from", "pairing"]).unwrap(),
+            RunStatus::Ok
+        );
+
+        let paf_m2n = run.join("tmp").join(tmpfiles::PAF_MITO_TO_NUC);
+        let mut m2n = read(&paf_m2n);
+        m2n.push_str(&"garbage line\n".repeat(8));
+        fs::write(&paf_m2n, m2n).unwrap();
+        let lenient = [
+            "--redo-from",
+            "pairing",
+            "--paf-error-policy",
+            "skip",
+            "--paf-max-error-frac",
+            "0.9",
+            "--warnings-as-exit-code",
+        ];
+        // over the default policy without --strict: completed, with warnings,
+        // and warnings.json is on disk by the time the status is known
+        let b = dir.path().join("b");
+        let s = status_of(&b, &lenient).unwrap();
+        assert_eq!(s, RunStatus::OkWithWarnings);
+        assert_eq!(s.exit_code(true), status::EXIT_WITH_WARNINGS);
+        assert!(b.join("classification.tsv").exists());
+        assert!(read(&b.join(warnings::WARNINGS_FILE)).contains(warnings::MALFORMED_PAF_LINE));
+        assert!(!b.join(strict::ERROR_FILE).exists());
+
+        // with --strict the same run fails instead
+        let c = dir.path().join("c");
+        assert!(status_of(&c, &[&lenient[..], &["--strict"]].concat()).is_err());
+        assert!(c.join(warnings::WARNINGS_FILE).exists());
+    }
+
+    #[test]
+    fn a_live_lock_on_the_out_dir_blocks_the_run() {
+        use crate::util::runlock::{RunLock, LOCK_FILE};
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        tmpfiles::apply_keep_policy(&run.join("tmp"), tmpfiles::KeepPolicy::Evidence).unwrap();
+        let b = dir.path().join("b");
+        fs::create_dir_all(&b).unwrap();
+        fs::write(b.join(logging::LOG_FILE), "the other run's log\n").unwrap();
+
+        let other = RunLock::acquire(&b, false).unwrap();
+        let e = reuse(&run, &b, &[]).unwrap_err().to_string();
+        assert!(e.contains("held by a running onsm"), "{e}");
+        // refused before touching the other run's files
+        assert_eq!(read(&b.join(logging::LOG_FILE)), "the other run's log\n");
+        assert!(!b.join("classification.tsv").exists());
+
+        reuse(&run, &b, &["--force-lock"]).unwrap();
+        assert!(b.join("classification.tsv").exists());
+        assert!(read(&b.join(warnings::WARNINGS_FILE)).contains(warnings::RUN_LOCK));
+        // the forced run released its own lock; the other guard finds nothing to remove
+        assert!(!b.join(LOCK_FILE).exists());
+        drop(other);
+        reuse(&run, &b, &[]).unwrap();
+        assert!(!b.join(LOCK_FILE).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pairs_from_scores_an_edited_list() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        // evidence is recomputed from the (placeholder) BAMs; minimap2 is never run
+        let samtools = dir.path().join("samtools");
+        let minimap2 = dir.path().join("minimap2");
+        fs::write(
+            &samtools,
+            "#!/bin/sh\ncase \"$1\" in\n--version) echo \"samtools 1.17\" ;;\ndepth) printf 'c\\t1\\t20\\n' ;;\nesac\n",
+        )
+        .unwrap();
+        fs::write(&minimap2, "#!/bin/sh\nexit 1\n").unwrap();
+        for bin in [&samtools, &minimap2] {
+            std::fs::set_permissions(bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let bins = [
+            "--samtools",
+            samtools.to_str().unwrap(),
+            "--minimap2",
+            minimap2.to_str().unwrap(),
+        ];
+
+        // the curator drops the chr2 locus and trims the start of the other
+        let pairs = read(&run.join("pairs.tsv"));
+        let mut lines: Vec<String> = pairs.lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 4, "{pairs}");
+        lines.retain(|l| !l.contains("\tchr2\t"));
+        lines[1] = lines[1].replacen("\t5000\t6000\t", "\t5200\t6000\t", 1);
+        let list = dir.path().join("edited.tsv");
+        fs::write(&list, format!("{}\n", lines.join("\n"))).unwrap();
+        let args = [&["--pairs-from", list.to_str().unwrap()][..], &bins].concat();
+
+        let b = dir.path().join("b");
+        reuse(&run, &b, &args).unwrap();
+        let scored = scoring::read_pairs_tsv(&b.join("pairs.tsv")).unwrap();
+        let loci: Vec<(&str, u32)> = scored
+            .iter()
+            .map(|r| (r.locus.pair_id.as_str(), r.locus.nuc_start))
+            .collect();
+        assert_eq!(loci, [("P000001", 5200), ("P000003", 30000)]);
+        // no pairing, and every output says the list was given
+        assert!(!b.join("paf_filter_stats.json").exists());
+        assert!(read(&b.join("summary.tsv")).ends_with("\ncaveats\tmanual_pair_list\n"));
+        let results = scoring::ScoringResults::load_from(&b).unwrap();
+        assert!(results.params.manual_pair_list);
+        let m = model::RunManifest::load_from(&b).unwrap();
+        assert_eq!(m.pairs_from.as_deref(), Some(list.as_path()));
+        assert_eq!(
+            model::RunManifest::load_from(&run).unwrap().pairs_from,
+            None
+        );
+
+        // a row past the end of its contig is refused by line and pair
+        lines.push(
+            lines[1]
+                .replacen("\t5200\t6000\t", "\t49000\t50500\t", 1)
+                .replacen("P000001", "P_edit", 1),
+        );
+        fs::write(&list, format!("{}\n", lines.join("\n"))).unwrap();
+        let c = dir.path().join("c");
+        let e = format!("{:#}", reuse(&run, &c, &args).unwrap_err());
+        assert!(
+            e.contains("line 4: P_edit: ends at 50500, past the end of chr1 (50000 bp)"),
+            "{e}"
+        );
+        assert!(!c.join("pairs.tsv").exists());
+
+        // pairing options have nothing to act on
+        let e = Wrap::try_parse_from([
+            "onsm",
+            "--from",
+            "run",
+            "--out-dir",
+            "c",
+            "--pairs-from",
+            "edited.tsv",
+            "--pairing",
+            "cluster",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(e.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn native_backend_recomputes_evidence_without_samtools() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        // the fixture BAM has reads on chr1, none on mt
+        let tmp = TmpArtifacts::in_dir(&run.join("tmp"));
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/evidence.bam");
+        for bam in [&tmp.bam_r2n, &tmp.bam_r2m] {
+            fs::copy(&fixture, bam).unwrap();
+            let mut bai = bam.as_os_str().to_owned();
+            bai.push(".bai");
+            fs::copy(fixture.with_extension("bam.bai"), bai).unwrap();
+        }
+        let list = dir.path().join("list.tsv");
+        fs::copy(run.join("pairs.tsv"), &list).unwrap();
+        let args = [
+            "--pairs-from",
+            list.to_str().unwrap(),
+            "--samtools",
+            "/nonexistent/samtools",
+            "--minimap2",
+            "/nonexistent/minimap2",
+        ];
+        let e = format!(
+            "{:#}",
+            reuse(&run, &dir.path().join("a"), &args).unwrap_err()
+        );
+        assert!(e.contains("not found"), "{e}");
+
+        let b = dir.path().join("b");
+        reuse(
+            &run,
+            &b,
+            &[&args[..], &["--bam-backend", "native"]].concat(),
+        )
+        .unwrap();
+        let scored = scoring::read_pairs_tsv(&b.join("pairs.tsv")).unwrap();
+        let chr1: Vec<_> = scored
+            .iter()
+            .filter(|r| r.locus.nuc_contig == "chr1")
+            .collect();
+        assert_eq!(chr1.len(), 2);
+        for r in chr1 {
+            assert!(r.features.read_len_nuc > 0.0, "{:?}", r.locus.pair_id);
+            assert_eq!(r.features.read_len_mito, 0.0);
+        }
+    }
+
+    #[test]
+    fn kept_crams_need_their_index_and_samtools() {
+        let dir = TempDir::new().unwrap();
+        let run = synthetic_run(dir.path());
+        let tmp_dir = run.join("tmp");
+        // the nuclear BAM converted to CRAM
+        fs::remove_file(tmp_dir.join(tmpfiles::BAM_READS_TO_NUC)).unwrap();
+        fs::write(tmp_dir.join(tmpfiles::CRAM_READS_TO_NUC), "CRAM").unwrap();
+        fs::write(tmp_dir.join(tmpfiles::BAM_READS_TO_MITO), "BAM").unwrap();
+        assert!(bam_index::is_cram(&TmpArtifacts::in_dir(&tmp_dir).bam_r2n));
+        let list = dir.path().join("list.tsv");
+        fs::copy(run.join("pairs.tsv"), &list).unwrap();
+        let args = [
+            "--pairs-from",
+            list.to_str().unwrap(),
+            "--samtools",
+            "/nonexistent/samtools",
+            "--minimap2",
+            "/nonexistent/minimap2",
+        ];
+        let e = |out: &str, extra: &[&str]| {
+            let args = [&args[..], extra].concat();
+            format!(
+                "{:#}",
+                reuse(&run, &dir.path().join(out), &args).unwrap_err()
+            )
+        };
+        let native = e("a", &["--bam-backend", "native"]);
+        assert!(native.contains("--bam-backend samtools"), "{native}");
+        let unindexed = e("b", &[]);
+        assert!(unindexed.contains("no .crai index"), "{unindexed}");
+        // indexed, it is samtools' to read
+        fs::write(tmp_dir.join("reads_to_nuc.cram.crai"), "").unwrap();
+        let no_samtools = e("c", &[]);
+        assert!(no_samtools.contains("not found"), "{no_samtools}");
     }
 }
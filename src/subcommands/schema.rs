@@ -0,0 +1,23 @@
+//! `onsm schema` — print the JSON Schema of a run artifact.
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::schema::Artifact;
+
+#[derive(Args, Debug)]
+pub struct CmdSchema {
+    /// Which artifact's schema to print
+    #[arg(long, value_enum, value_name = "ARTIFACT")]
+    pub json_schema: Artifact,
+}
+
+impl CmdSchema {
+    pub fn run(self) -> Result<()> {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&self.json_schema.schema())?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,793 @@
+//! `onsm stats` — descriptive statistics for an existing run directory.
+//!
+//! Reads pairs.tsv + classification.tsv only; nothing is recomputed.
+//! `--region` narrows the statistics to the pairs overlapping one region of
+//! either assembly (found through `run_manifest.json`) and lists them.
+
+use anyhow::{anyhow, Result};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::bundle::RunDir;
+use crate::io::fasta::{self, LengthCache};
+use crate::io::headers::{self, HeaderFields, RenameScheme};
+use crate::model::{orientation, RunManifest};
+use crate::scoring::{self, PairsTsvRow};
+use crate::summary;
+use crate::tracking::IntervalIndex;
+use crate::util::region::{self, Coords, Region, RegionString};
+use crate::util::stats::{self, Quartiles};
+
+/// Width of the mito bins used for hotspot counts.
+pub const HOTSPOT_BIN_BP: u32 = 500;
+
+/// Call labels in report order.
+const CALLS: [&str; 4] = ["Likely_NUMT", "Likely_NIMT", "Shared_Support", "Ambiguous"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum StatsFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct CmdStats {
+    /// Output directory from a previous `onsm classify`/`onsm reuse`, or its bundle (`onsm export-bundle`)
+    #[arg(long, value_name = "DIR")]
+    pub from: PathBuf,
+
+    /// Output format on stdout
+    #[arg(long, value_enum, default_value_t = StatsFormat::Table)]
+    pub format: StatsFormat,
+
+    /// How many of the largest loci and mito hotspots to list
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Also write the statistics as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    pub json_out: Option<PathBuf>,
+
+    /// Only the pairs overlapping this region (CONTIG:START-END) of the nuclear or mito assembly, listed first
+    #[arg(long, value_name = "REGION", value_parser = region::parse)]
+    pub region: Option<RegionString>,
+
+    /// How to read the --region numbers: 1based (inclusive, as in genome browsers) or 0based (half-open, as in pairs.tsv)
+    #[arg(long, value_enum, default_value_t = Coords::OneBased, requires = "region")]
+    pub coords: Coords,
+
+    /// Write the --region sequence to this FASTA
+    #[arg(long, value_name = "FILE", requires = "region")]
+    pub fasta_out: Option<PathBuf>,
+
+    /// Write the pairs overlapping --region to --fasta-out instead, each its sequence on the region's side, named full, short or custom:TEMPLATE; FILE.names.tsv maps the names back
+    #[arg(long, value_name = "SCHEME", requires = "fasta_out")]
+    pub rename_scheme: Option<RenameScheme>,
+}
+
+impl CmdStats {
+    pub fn run(self) -> Result<()> {
+        let run = RunDir::open(&self.from)?;
+        let from = run.path();
+        let st = match &self.region {
+            None => RunStats::from_run_dir(from, self.top)?,
+            Some(r) => {
+                let (region, side, fa) = resolve_region(from, r, self.coords)?;
+                let (rows, calls) = read_run_dir(from)?;
+                match (&self.fasta_out, &self.rename_scheme) {
+                    (Some(p), None) => {
+                        let seq =
+                            fasta::subsequence(&fa, &region.contig, region.start, region.end)?;
+                        fasta::write_fasta(p, &[(region.to_string(), seq)])?;
+                    }
+                    (Some(p), Some(scheme)) => {
+                        let hits = region_hits(&rows, &region, side);
+                        let hit_calls: Vec<String> =
+                            hits.iter().map(|r| call_of(r, &calls)).collect();
+                        let items: Vec<HeaderFields> = hits
+                            .iter()
+                            .zip(&hit_calls)
+                            .map(|(r, call)| HeaderFields {
+                                locus: &r.locus,
+                                call,
+                                side: side.locus_side(),
+                            })
+                            .collect();
+                        headers::write_renamed_fasta(scheme, &items, &fa, p)?;
+                    }
+                    (None, _) => {}
+                }
+                RunStats::in_region(&rows, &calls, self.top, &region, side)
+            }
+        };
+        match self.format {
+            StatsFormat::Table => print!("{}", st.to_table()),
+            StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&st)?),
+        }
+        if let Some(p) = &self.json_out {
+            serde_json::to_writer_pretty(fs_err::File::create(p)?, &st)?;
+        }
+        Ok(())
+    }
+}
+
+/// Length/identity description of the loci with one call.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallStats {
+    pub n: usize,
+    /// Sum of nuclear locus lengths (not unioned).
+    pub nuc_bp: u64,
+    pub nuc_len: Quartiles,
+    pub nuc_len_n50: u64,
+    pub identity: Quartiles,
+    pub identity_hist: BTreeMap<u32, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContigCounts {
+    pub contig: String,
+    pub n: usize,
+    pub n_numt: usize,
+    pub n_nimt: usize,
+    pub n_shared: usize,
+    pub n_ambiguous: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LocusRow {
+    pub pair_id: String,
+    pub call: String,
+    pub nuc_contig: String,
+    pub nuc_start: u32,
+    pub nuc_end: u32,
+    pub nuc_len: u32,
+    pub mito_contig: String,
+    pub mito_start: u32,
+    pub mito_end: u32,
+    pub aln_ident: f32,
+}
+
+/// One mito bin `[start, start + HOTSPOT_BIN_BP)` and how many loci touch it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hotspot {
+    pub mito_contig: String,
+    pub start: u32,
+    pub end: u32,
+    pub n: usize,
+    pub n_numt: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunStats {
+    pub n_pairs: usize,
+    /// Keyed by call label.
+    pub by_call: BTreeMap<String, CallStats>,
+    /// Nuclear contigs, most loci first (ties by name).
+    pub per_contig: Vec<ContigCounts>,
+    /// Largest loci by nuclear length (ties by pair_id).
+    pub top_loci: Vec<LocusRow>,
+    pub hotspot_bin_bp: u32,
+    /// Busiest mito bins (ties by contig, start).
+    pub mito_hotspots: Vec<Hotspot>,
+    /// With `--region`: the figures above cover only these pairs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<RegionPairs>,
+}
+
+/// Which assembly a `--region` contig is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Nuclear,
+    Mito,
+}
+
+impl Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Nuclear => "nuclear",
+            Side::Mito => "mito",
+        }
+    }
+
+    /// The same side as `model::orientation` names it.
+    fn locus_side(self) -> orientation::Side {
+        match self {
+            Side::Nuclear => orientation::Side::Nuclear,
+            Side::Mito => orientation::Side::Mito,
+        }
+    }
+
+    /// The pair's interval on this side.
+    fn interval(self, r: &PairsTsvRow) -> (&str, u32, u32) {
+        let p = &r.locus;
+        match self {
+            Side::Nuclear => (&p.nuc_contig, p.nuc_start, p.nuc_end),
+            Side::Mito => (&p.mito_contig, p.mito_start, p.mito_end),
+        }
+    }
+}
+
+/// The pairs overlapping a `--region` on its side.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionPairs {
+    /// 1-based inclusive `contig:start-end`.
+    pub name: String,
+    pub side: Side,
+    /// 0-based half-open.
+    pub region: Region,
+    /// By position on `side`, then pair_id.
+    pub pairs: Vec<LocusRow>,
+}
+
+/// Look the `--region` contig up in the run's nuclear assembly, then its mito
+/// one, and check the coordinates against it; also returns that FASTA.
+fn resolve_region(dir: &Path, r: &RegionString, coords: Coords) -> Result<(Region, Side, PathBuf)> {
+    let m = RunManifest::load_from(dir)?;
+    let lengths = LengthCache::default();
+    for (side, fa) in [(Side::Nuclear, &m.nuclear), (Side::Mito, &m.mito)] {
+        if let Some(&len) = lengths.lengths(fa)?.get(&r.contig) {
+            let region = r
+                .resolve(coords, len)
+                .map_err(|e| anyhow!("--region {e}"))?;
+            return Ok((region, side, fa.clone()));
+        }
+    }
+    Err(anyhow!(
+        "--region {r}: {} is not a contig of {} or {}",
+        r.contig,
+        m.nuclear.display(),
+        m.mito.display()
+    ))
+}
+
+/// The pairs overlapping `region` on `side`, by position there, then pair_id.
+fn region_hits(rows: &[PairsTsvRow], region: &Region, side: Side) -> Vec<PairsTsvRow> {
+    let index = IntervalIndex::of_intervals(rows.iter().map(|r| side.interval(r)));
+    let mut hits: Vec<PairsTsvRow> = index
+        .overlapping(&region.contig, region.start, region.end)
+        .into_iter()
+        .map(|i| rows[i].clone())
+        .collect();
+    hits.sort_by(|a, b| {
+        let (_, sa, ea) = side.interval(a);
+        let (_, sb, eb) = side.interval(b);
+        (sa, ea)
+            .cmp(&(sb, eb))
+            .then_with(|| a.locus.pair_id.cmp(&b.locus.pair_id))
+    });
+    hits
+}
+
+fn read_run_dir(dir: &Path) -> Result<(Vec<PairsTsvRow>, HashMap<String, String>)> {
+    let pairs_p = dir.join("pairs.tsv");
+    let class_p = dir.join("classification.tsv");
+    for p in [&pairs_p, &class_p] {
+        if !p.exists() {
+            return Err(anyhow!(
+                "{} not found (is this an onsm run directory?)",
+                p.display()
+            ));
+        }
+    }
+    let rows = scoring::read_run_pairs(dir)?;
+    let calls = summary::parse_calls_tsv_file(&class_p)?;
+    Ok((rows, calls))
+}
+
+fn call_of(r: &PairsTsvRow, calls: &HashMap<String, String>) -> String {
+    calls
+        .get(&r.locus.pair_id)
+        .cloned()
+        .unwrap_or_else(|| "Ambiguous".to_string())
+}
+
+fn locus_row(r: &PairsTsvRow, calls: &HashMap<String, String>) -> LocusRow {
+    let p = &r.locus;
+    LocusRow {
+        pair_id: p.pair_id.clone(),
+        call: call_of(r, calls),
+        nuc_contig: p.nuc_contig.clone(),
+        nuc_start: p.nuc_start,
+        nuc_end: p.nuc_end,
+        nuc_len: p.nuc_end.saturating_sub(p.nuc_start),
+        mito_contig: p.mito_contig.clone(),
+        mito_start: p.mito_start,
+        mito_end: p.mito_end,
+        aln_ident: p.aln_ident,
+    }
+}
+
+const LOCUS_ROW_HEADER: &str =
+    "pair_id\tcall\tnuc_contig\tnuc_start\tnuc_end\tnuc_len\tmito_contig\tmito_start\tmito_end\taln_ident";
+
+fn write_locus_row(t: &mut String, l: &LocusRow) {
+    let _ = writeln!(
+        t,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}",
+        l.pair_id,
+        l.call,
+        l.nuc_contig,
+        l.nuc_start,
+        l.nuc_end,
+        l.nuc_len,
+        l.mito_contig,
+        l.mito_start,
+        l.mito_end,
+        l.aln_ident
+    );
+}
+
+impl RunStats {
+    pub fn from_run_dir(dir: &Path, top: usize) -> Result<Self> {
+        let (rows, calls) = read_run_dir(dir)?;
+        Ok(Self::compute(&rows, &calls, top))
+    }
+
+    /// `compute` over the pairs whose `side` interval overlaps `region`.
+    pub fn in_region(
+        rows: &[PairsTsvRow],
+        calls: &HashMap<String, String>,
+        top: usize,
+        region: &Region,
+        side: Side,
+    ) -> Self {
+        let hits = region_hits(rows, region, side);
+        let mut st = Self::compute(&hits, calls, top);
+        st.region = Some(RegionPairs {
+            name: region.to_string(),
+            side,
+            region: region.clone(),
+            pairs: hits.iter().map(|r| locus_row(r, calls)).collect(),
+        });
+        st
+    }
+
+    /// Pairs missing from `calls` count as Ambiguous.
+    pub fn compute(rows: &[PairsTsvRow], calls: &HashMap<String, String>, top: usize) -> Self {
+        let call_of = |r: &PairsTsvRow| call_of(r, calls);
+
+        // Per call type
+        let mut lens: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+        let mut idents: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for c in CALLS {
+            lens.insert(c.to_string(), Vec::new());
+            idents.insert(c.to_string(), Vec::new());
+        }
+        for r in rows {
+            let c = call_of(r);
+            let len = r.locus.nuc_end.saturating_sub(r.locus.nuc_start) as u64;
+            lens.entry(c.clone()).or_default().push(len);
+            idents.entry(c).or_default().push(r.locus.aln_ident as f64);
+        }
+        let by_call = lens
+            .into_iter()
+            .map(|(c, l)| {
+                let id = &idents[&c];
+                let lf: Vec<f64> = l.iter().map(|x| *x as f64).collect();
+                let cs = CallStats {
+                    n: l.len(),
+                    nuc_bp: l.iter().sum(),
+                    nuc_len: Quartiles::of(&lf),
+                    nuc_len_n50: stats::n50(&l),
+                    identity: Quartiles::of(id),
+                    identity_hist: stats::percent_histogram(id),
+                };
+                (c, cs)
+            })
+            .collect();
+
+        // Per nuclear contig
+        let mut contigs: HashMap<&str, ContigCounts> = HashMap::new();
+        for r in rows {
+            let e = contigs
+                .entry(r.locus.nuc_contig.as_str())
+                .or_insert_with(|| ContigCounts {
+                    contig: r.locus.nuc_contig.clone(),
+                    n: 0,
+                    n_numt: 0,
+                    n_nimt: 0,
+                    n_shared: 0,
+                    n_ambiguous: 0,
+                });
+            e.n += 1;
+            match call_of(r).as_str() {
+                "Likely_NUMT" => e.n_numt += 1,
+                "Likely_NIMT" => e.n_nimt += 1,
+                "Shared_Support" => e.n_shared += 1,
+                _ => e.n_ambiguous += 1,
+            }
+        }
+        let mut per_contig: Vec<ContigCounts> = contigs.into_values().collect();
+        per_contig.sort_by(|a, b| b.n.cmp(&a.n).then_with(|| a.contig.cmp(&b.contig)));
+
+        // Largest loci
+        let mut by_len: Vec<&PairsTsvRow> = rows.iter().collect();
+        by_len.sort_by(|a, b| {
+            let la = a.locus.nuc_end.saturating_sub(a.locus.nuc_start);
+            let lb = b.locus.nuc_end.saturating_sub(b.locus.nuc_start);
+            lb.cmp(&la)
+                .then_with(|| a.locus.pair_id.cmp(&b.locus.pair_id))
+        });
+        let top_loci = by_len
+            .into_iter()
+            .take(top)
+            .map(|r| locus_row(r, calls))
+            .collect();
+
+        // Mito hotspots: loci touching each bin
+        let mut bins: BTreeMap<(&str, u32), (usize, usize)> = BTreeMap::new();
+        for r in rows {
+            let p = &r.locus;
+            if p.mito_end <= p.mito_start {
+                continue;
+            }
+            let is_numt = call_of(r) == "Likely_NUMT";
+            for b in p.mito_start / HOTSPOT_BIN_BP..=(p.mito_end - 1) / HOTSPOT_BIN_BP {
+                let e = bins.entry((p.mito_contig.as_str(), b)).or_default();
+                e.0 += 1;
+                e.1 += is_numt as usize;
+            }
+        }
+        let mut hot: Vec<Hotspot> = bins
+            .into_iter()
+            .map(|((c, b), (n, n_numt))| Hotspot {
+                mito_contig: c.to_string(),
+                start: b * HOTSPOT_BIN_BP,
+                end: (b + 1) * HOTSPOT_BIN_BP,
+                n,
+                n_numt,
+            })
+            .collect();
+        // stable sort keeps (contig, start) order among ties
+        hot.sort_by_key(|h| std::cmp::Reverse(h.n));
+        hot.truncate(top);
+
+        Self {
+            n_pairs: rows.len(),
+            by_call,
+            per_contig,
+            top_loci,
+            hotspot_bin_bp: HOTSPOT_BIN_BP,
+            mito_hotspots: hot,
+            region: None,
+        }
+    }
+
+    /// Plain tab-separated sections, one blank line between them.
+    pub fn to_table(&self) -> String {
+        let mut t = String::new();
+        if let Some(r) = &self.region {
+            let _ = writeln!(t, "# region\t{}\t{}", r.name, r.side.as_str());
+            let _ = writeln!(t, "\n# region_pairs\n{LOCUS_ROW_HEADER}");
+            for l in &r.pairs {
+                write_locus_row(&mut t, l);
+            }
+            t.push('\n');
+        }
+        let _ = writeln!(t, "# pairs\t{}", self.n_pairs);
+
+        let _ = writeln!(
+            t,
+            "\n# by_call\ncall\tn\tnuc_bp\tlen_min\tlen_q1\tlen_median\tlen_q3\tlen_max\tlen_n50\tident_q1\tident_median\tident_q3"
+        );
+        for (c, s) in &self.by_call {
+            let _ = writeln!(
+                t,
+                "{c}\t{}\t{}\t{:.0}\t{:.0}\t{:.0}\t{:.0}\t{:.0}\t{}\t{:.4}\t{:.4}\t{:.4}",
+                s.n,
+                s.nuc_bp,
+                s.nuc_len.min,
+                s.nuc_len.q1,
+                s.nuc_len.median,
+                s.nuc_len.q3,
+                s.nuc_len.max,
+                s.nuc_len_n50,
+                s.identity.q1,
+                s.identity.median,
+                s.identity.q3
+            );
+        }
+
+        let _ = writeln!(t, "\n# identity_hist\ncall\tpercent_bin\tcount");
+        for (c, s) in &self.by_call {
+            for (b, n) in &s.identity_hist {
+                let _ = writeln!(t, "{c}\t{b}\t{n}");
+            }
+        }
+
+        let _ = writeln!(
+            t,
+            "\n# per_contig\ncontig\tn\tn_numt\tn_nimt\tn_shared\tn_ambiguous"
+        );
+        for c in &self.per_contig {
+            let _ = writeln!(
+                t,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                c.contig, c.n, c.n_numt, c.n_nimt, c.n_shared, c.n_ambiguous
+            );
+        }
+
+        let _ = writeln!(t, "\n# top_loci\n{LOCUS_ROW_HEADER}");
+        for l in &self.top_loci {
+            write_locus_row(&mut t, l);
+        }
+
+        let _ = writeln!(t, "\n# mito_hotspots\nmito_contig\tstart\tend\tn\tn_numt");
+        for h in &self.mito_hotspots {
+            let _ = writeln!(
+                t,
+                "{}\t{}\t{}\t{}\t{}",
+                h.mito_contig, h.start, h.end, h.n, h.n_numt
+            );
+        }
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PAIRS: &str = "\
+pair_id\tnuc_contig\tnuc_start\tnuc_end\tmito_contig\tmito_start\tmito_end\taln_len\taln_ident\trnuc\trmito\ts_nuc\ts_mito\tscore_numt\tscore_nimt
+P000001\tchr1\t1000\t2000\tmt\t0\t1000\t1000\t0.9500\t1.000\t0.100\t0.800\t0.000\t1.0000\t-0.5000
+P000002\tchr1\t5000\t9000\tmt\t200\t4200\t4000\t0.9900\t1.000\t0.100\t0.800\t0.000\t1.0000\t-0.5000
+P000003\tchr2\t0\t300\tmt\t800\t1100\t300\t0.9100\t0.200\t1.000\t0.000\t0.700\t-0.5000\t1.0000
+P000004\tchr2\t100\t2100\tmt\t600\t2600\t2000\t0.9700\t1.000\t1.000\t0.400\t0.400\t0.3000\t0.3000
+P000005\tchr3\t10\t510\tmt\t14000\t14500\t500\t0.9300\t1.000\t0.100\t0.800\t0.000\t1.0000\t-0.5000
+";
+    const CLASSES: &str = "\
+pair_id\tcall\tconfidence\treason_codes
+P000001\tLikely_NUMT\t1.5000\tscore_difference
+P000002\tLikely_NUMT\t1.5000\tscore_difference
+P000003\tLikely_NIMT\t1.5000\tscore_difference
+P000004\tAmbiguous\t0.0000\tdelta_below_threshold
+P000005\tLikely_NUMT\t1.5000\tscore_difference
+";
+
+    fn fixture() -> RunStats {
+        let dir = TempDir::new().unwrap();
+        fs_err::write(dir.path().join("pairs.tsv"), PAIRS).unwrap();
+        fs_err::write(dir.path().join("classification.tsv"), CLASSES).unwrap();
+        RunStats::from_run_dir(dir.path(), 3).unwrap()
+    }
+
+    #[test]
+    fn pinned_numbers() {
+        let st = fixture();
+        assert_eq!(st.n_pairs, 5);
+
+        let numt = &st.by_call["Likely_NUMT"];
+        assert_eq!((numt.n, numt.nuc_bp), (3, 5500));
+        // lengths 500, 1000, 4000
+        assert_eq!(numt.nuc_len.min, 500.0);
+        assert_eq!(numt.nuc_len.q1, 750.0);
+        assert_eq!(numt.nuc_len.median, 1000.0);
+        assert_eq!(numt.nuc_len.q3, 2500.0);
+        assert_eq!(numt.nuc_len_n50, 4000);
+        assert!((numt.identity.median - 0.95).abs() < 1e-6);
+        assert_eq!(numt.identity_hist.values().sum::<u64>(), 3);
+        assert_eq!(st.by_call["Likely_NIMT"].n, 1);
+        assert_eq!(st.by_call["Ambiguous"].n, 1);
+        assert_eq!(st.by_call["Shared_Support"].n, 0);
+
+        let c: Vec<(&str, usize, usize)> = st
+            .per_contig
+            .iter()
+            .map(|c| (c.contig.as_str(), c.n, c.n_numt))
+            .collect();
+        assert_eq!(c, vec![("chr1", 2, 2), ("chr2", 2, 0), ("chr3", 1, 1)]);
+
+        let top: Vec<&str> = st.top_loci.iter().map(|l| l.pair_id.as_str()).collect();
+        assert_eq!(top, vec!["P000002", "P000004", "P000001"]);
+        assert_eq!(st.top_loci[0].nuc_len, 4000);
+
+        // mt:500-1000 is touched by P1, P2, P3 and P4
+        let h = &st.mito_hotspots[0];
+        assert_eq!((h.start, h.end, h.n, h.n_numt), (500, 1000, 4, 2));
+        assert_eq!(st.mito_hotspots.len(), 3);
+    }
+
+    #[test]
+    fn table_is_stable() {
+        let t = fixture().to_table();
+        assert!(t.starts_with("# pairs\t5\n"));
+        assert!(t.contains(
+            "\nLikely_NUMT\t3\t5500\t500\t750\t1000\t2500\t4000\t4000\t0.9400\t0.9500\t0.9700\n"
+        ));
+        assert!(t.contains(
+            "\n# per_contig\ncontig\tn\tn_numt\tn_nimt\tn_shared\tn_ambiguous\nchr1\t2\t2\t0\t0\t0\n"
+        ));
+        assert!(
+            t.contains("\nP000002\tLikely_NUMT\tchr1\t5000\t9000\t4000\tmt\t200\t4200\t0.9900\n")
+        );
+    }
+
+    #[test]
+    fn region_query_on_either_side() {
+        let rows = scoring::parse_pairs_tsv_str(PAIRS).unwrap();
+        let calls = summary::parse_calls_tsv_str(CLASSES);
+        let query = |s: &str, side| {
+            let region = region::parse(s)
+                .unwrap()
+                .resolve(Coords::OneBased, 100_000)
+                .unwrap();
+            RunStats::in_region(&rows, &calls, 3, &region, side)
+        };
+        let ids = |st: &RunStats| -> Vec<String> {
+            let r = st.region.as_ref().unwrap();
+            r.pairs.iter().map(|l| l.pair_id.clone()).collect()
+        };
+
+        // chr2 [99, 200): P3 [0, 300) and P4 [100, 2100), by start
+        let st = query("chr2:100-200", Side::Nuclear);
+        assert_eq!(ids(&st), ["P000003", "P000004"]);
+        assert_eq!(st.n_pairs, 2);
+        assert_eq!(st.by_call["Likely_NIMT"].n, 1);
+        assert_eq!(st.by_call["Ambiguous"].n, 1);
+        // 1-based: chr1:2001-5000 is [2000, 5000), touching neither chr1 locus
+        assert!(ids(&query("chr1:2,001-5,000", Side::Nuclear)).is_empty());
+        assert_eq!(
+            ids(&query("chr1:2000-5001", Side::Nuclear)),
+            ["P000001", "P000002"]
+        );
+        // the mito side, ordered by mito start; base 1000 is the last of P1
+        assert_eq!(
+            ids(&query("mt:1000-1000", Side::Mito)),
+            ["P000001", "P000002", "P000004", "P000003"]
+        );
+
+        let t = query("chr2:100-200", Side::Nuclear).to_table();
+        assert!(t.starts_with("# region\tchr2:100-200\tnuclear\n\n# region_pairs\npair_id\tcall\t"));
+        assert!(t.contains("\nP000003\tLikely_NIMT\tchr2\t0\t300\t300\tmt\t800\t1100\t0.9100\n"));
+        assert!(t.contains("\n\n# pairs\t2\n"));
+        assert!(fixture().region.is_none());
+    }
+
+    #[test]
+    fn region_checked_and_extracted_via_the_manifest() {
+        let dir = TempDir::new().unwrap();
+        let run = dir.path();
+        fs_err::write(run.join("pairs.tsv"), PAIRS).unwrap();
+        fs_err::write(run.join("classification.tsv"), CLASSES).unwrap();
+        let (mito, nuc) = (run.join("mt.fa"), run.join("nuc.fa"));
+        fs_err::write(&mito, format!(">mt\n{}\n", "A".repeat(16_000))).unwrap();
+        fs_err::write(
+            &nuc,
+            format!(">chr1\n{}\n>chr2\nACGTACGTAC\n", "C".repeat(10_000)),
+        )
+        .unwrap();
+        let m = RunManifest::new(
+            &mito,
+            &nuc,
+            &[],
+            "hifi",
+            1,
+            crate::model::MIN_ID,
+            crate::model::MIN_LEN,
+            crate::model::MERGE_GAP,
+            crate::model::FLANK_BP,
+            crate::model::WIN_BP,
+        );
+        RunManifest::save_to(run, &m).unwrap();
+
+        let resolve = |s: &str, coords| resolve_region(run, &region::parse(s).unwrap(), coords);
+        let (r, side, fa) = resolve("chr2:3-6", Coords::OneBased).unwrap();
+        assert_eq!(
+            (r.start, r.end, side, fa),
+            (2, 6, Side::Nuclear, nuc.clone())
+        );
+        let (r, side, _) = resolve("mt:0-500", Coords::ZeroBased).unwrap();
+        assert_eq!((r.start, r.end, side), (0, 500, Side::Mito));
+        for (bad, why) in [
+            ("chr2:5-11", "past the end of chr2 (10 bp)"),
+            ("chrX:1-10", "chrX is not a contig"),
+        ] {
+            let e = resolve(bad, Coords::OneBased).unwrap_err().to_string();
+            assert!(e.contains(why), "{bad}: {e}");
+        }
+
+        let out = run.join("region.fa");
+        CmdStats {
+            from: run.to_path_buf(),
+            format: StatsFormat::Json,
+            top: 3,
+            json_out: Some(run.join("stats.json")),
+            region: Some(region::parse("chr2:3-6").unwrap()),
+            coords: Coords::OneBased,
+            fasta_out: Some(out.clone()),
+            rename_scheme: None,
+        }
+        .run()
+        .unwrap();
+        assert_eq!(fs_err::read_to_string(&out).unwrap(), ">chr2:3-6\nGTAC\n");
+        let json: serde_json::Value =
+            serde_json::from_str(&fs_err::read_to_string(run.join("stats.json")).unwrap()).unwrap();
+        assert_eq!(json["region"]["side"], "nuclear");
+        assert_eq!(json["region"]["region"]["start"], 2);
+        assert_eq!(json["region"]["pairs"][0]["pair_id"], "P000003");
+        assert_eq!(json["n_pairs"], 1);
+    }
+
+    #[test]
+    fn rename_scheme_writes_the_region_pairs() {
+        let dir = TempDir::new().unwrap();
+        let run = dir.path();
+        fs_err::write(run.join("pairs.tsv"), PAIRS).unwrap();
+        fs_err::write(run.join("classification.tsv"), CLASSES).unwrap();
+        let (mito, nuc) = (run.join("mt.fa"), run.join("nuc.fa"));
+        fs_err::write(&mito, format!(">mt\n{}\n", "A".repeat(16_000))).unwrap();
+        let chr1 = format!(
+            "{}{}{}",
+            "C".repeat(1000),
+            "G".repeat(1000),
+            "C".repeat(8000)
+        );
+        fs_err::write(&nuc, format!(">chr1\n{chr1}\n")).unwrap();
+        let m = RunManifest::new(
+            &mito,
+            &nuc,
+            &[],
+            "hifi",
+            1,
+            crate::model::MIN_ID,
+            crate::model::MIN_LEN,
+            crate::model::MERGE_GAP,
+            crate::model::FLANK_BP,
+            crate::model::WIN_BP,
+        );
+        RunManifest::save_to(run, &m).unwrap();
+
+        let out = run.join("pairs.fa");
+        let stats = |region: &str, scheme: &str| {
+            CmdStats {
+                from: run.to_path_buf(),
+                format: StatsFormat::Json,
+                top: 3,
+                json_out: None,
+                region: Some(region::parse(region).unwrap()),
+                coords: Coords::OneBased,
+                fasta_out: Some(out.clone()),
+                rename_scheme: Some(scheme.parse().unwrap()),
+            }
+            .run()
+        };
+        stats(
+            "chr1:1500-1600",
+            "custom:{pair_id}|{nuc_contig}:{nuc_start}-{nuc_end}|{call}",
+        )
+        .unwrap();
+        let fa = fs_err::read_to_string(&out).unwrap();
+        let mut lines = fa.lines();
+        assert_eq!(lines.next(), Some(">P000001|chr1:1000-2000|Likely_NUMT"));
+        assert_eq!(lines.collect::<String>(), "G".repeat(1000));
+        let map = fs_err::read_to_string(run.join("pairs.fa.names.tsv")).unwrap();
+        assert_eq!(
+            map.lines().nth(1),
+            Some("P000001|chr1:1000-2000|Likely_NUMT\tP000001\tnuc\tchr1\t1000\t2000\tchr1\t1000\t2000\tmt\t0\t1000\t1000\t0.9500\tLikely_NUMT")
+        );
+
+        // both chr1 pairs are on mt, so {mito_contig} alone names them alike
+        let e = stats("chr1:1-10000", "custom:{mito_contig}")
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("duplicate name \"mt\""), "{e}");
+        stats("chr1:1-10000", "short").unwrap();
+        let fa = fs_err::read_to_string(&out).unwrap();
+        let names: Vec<&str> = fa.lines().filter(|l| l.starts_with('>')).collect();
+        assert_eq!(names, [">P000001_nuc", ">P000002_nuc"]);
+    }
+
+    #[test]
+    fn missing_run_dir_files_error() {
+        let dir = TempDir::new().unwrap();
+        let e = RunStats::from_run_dir(dir.path(), 3).unwrap_err();
+        assert!(e.to_string().contains("pairs.tsv not found"));
+    }
+}
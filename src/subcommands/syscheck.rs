@@ -6,7 +6,9 @@ use fs_err as fs;
 use std::path::PathBuf;
 use sysinfo::System;
 
+use crate::util::cpus::CpuLimits;
 use crate::util::mapping;
+use crate::util::version::VersionInfo;
 
 #[derive(Args, Debug)]
 pub struct CmdSyscheck {
@@ -22,33 +24,16 @@ pub struct CmdSyscheck {
 
 impl CmdSyscheck {
     pub fn run(self) -> Result<()> {
-        let mut s = System::new_all();
-        s.refresh_all();
-
         // Resolve binaries (allow CLI flags / env / PATH)
         let (mm2_bin, sam_bin) =
             mapping::resolve_bins(self.minimap2.as_deref(), self.samtools.as_deref())?;
 
-        // Try to grab versions
-        let mm2_version = mapping::get_version(&mm2_bin).unwrap_or_else(|e| format!("error: {e}"));
-        let sam_version = mapping::get_version(&sam_bin).unwrap_or_else(|e| format!("error: {e}"));
+        // Same provenance object as --version-json and run_manifest.json
+        let onsm = VersionInfo::build()
+            .with_tool("minimap2", &mm2_bin)
+            .with_tool("samtools", &sam_bin);
 
-        let obj = serde_json::json!({
-            "onsm_version": env!("CARGO_PKG_VERSION"),
-            "rustc": rustc_version_runtime::version().to_string(),
-            "cpus": s.cpus().len(),
-            "total_memory_mb": s.total_memory() / 1024 / 1024,
-            "executables": {
-                "minimap2": {
-                    "path": mm2_bin,
-                    "version": mm2_version,
-                },
-                "samtools": {
-                    "path": sam_bin,
-                    "version": sam_version,
-                }
-            },
-        });
+        let obj = report(&onsm);
 
         if let Some(path) = self.out {
             serde_json::to_writer_pretty(fs::File::create(path)?, &obj)?;
@@ -59,11 +44,36 @@ impl CmdSyscheck {
     }
 }
 
-// Tiny helper to read rustc version at runtime
-mod rustc_version_runtime {
-    pub fn version() -> String {
-        option_env!("RUSTC_VERSION")
-            .unwrap_or("unknown")
-            .to_string()
+/// The JSON syscheck prints, for the given provenance.
+fn report(onsm: &VersionInfo) -> serde_json::Value {
+    let mut s = System::new_all();
+    s.refresh_all();
+    serde_json::json!({
+        "onsm": onsm,
+        "cpus": s.cpus().len(),
+        "cpu_limits": CpuLimits::detect(),
+        "total_memory_mb": s.total_memory() / 1024 / 1024,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_carries_the_version_json_provenance() {
+        let onsm = VersionInfo::build();
+        let obj = report(&onsm);
+        assert_eq!(obj["onsm"], serde_json::to_value(&onsm).unwrap());
+        assert!(obj["cpus"].as_u64().unwrap() > 0, "{obj}");
+        assert!(obj["total_memory_mb"].as_u64().unwrap() > 0, "{obj}");
+    }
+
+    #[test]
+    fn report_includes_the_thread_defaults() {
+        let obj = report(&VersionInfo::build());
+        let limits = &obj["cpu_limits"];
+        assert_eq!(*limits, serde_json::to_value(CpuLimits::detect()).unwrap());
+        assert!(limits["default_threads"].as_u64().unwrap() >= 1, "{obj}");
     }
 }
@@ -3,200 +3,1150 @@
 //! Given:
 //!   - the mito and nuclear FASTA paths,
 //!   - the paired loci (mito<->nuclear intervals),
-//!   - and a call map {pair_id -> "Likely_NUMT" | "Likely_NIMT" | "Ambiguous"},
+//!   - and a call map {pair_id -> "Likely_NUMT" | "Likely_NIMT" | "Shared_Support" | "Ambiguous"},
 //!
 //! we compute:
 //!   * total assembly lengths (from FASTA),
-//!   * union-lengths of loci on each side stratified by call type,
-//!   * percentages (as PERCENT values; e.g., 0.0207 means 0.0207%).
+//!   * union-lengths of loci on each side stratified by call type (and
+//!     further strata, e.g. high-confidence calls; each figure is declared as
+//!     a `MetricSpec`),
+//!   * percentages (as PERCENT values; e.g., 0.0207 means 0.0207%; the
+//!     output files can give them in ppm or as fractions, see `SummaryUnits`),
+//!   * identity statistics (length-weighted mean, median, 1% histogram) per call type.
 //!
 //! We treat PairedLocus coordinates as 0-based half-open [start, end).
 
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
-use crate::io::fasta;
+use crate::io::fasta::LengthCache;
+use crate::mito_meta::MitoContigMeta;
 use crate::model::PairedLocus;
+use crate::util::stats;
+
+/// Identity distribution over the pairs of one call type.
+/// Identities are fractions in [0,1]; histogram bins are keyed by lower percent.
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct IdentityStats {
+    pub n: usize,
+    pub aln_bp: u64,
+    pub wmean: f64,
+    pub median: f64,
+    pub hist: BTreeMap<u32, u64>,
+}
+
+impl IdentityStats {
+    /// Build from `(aln_ident, aln_len)` of the pairs in one stratum.
+    pub fn from_pairs(v: &[(f32, u32)]) -> Self {
+        let idents: Vec<f64> = v.iter().map(|(i, _)| *i as f64).collect();
+        let weighted: Vec<(f64, f64)> = v.iter().map(|(i, l)| (*i as f64, *l as f64)).collect();
+        Self {
+            n: v.len(),
+            aln_bp: v.iter().map(|(_, l)| *l as u64).sum(),
+            wmean: stats::weighted_mean(&weighted),
+            median: stats::median(&idents),
+            hist: stats::percent_histogram(&idents),
+        }
+    }
+
+    /// Compact `bin:count` list of non-empty bins, e.g. `93:2,99:1`.
+    pub fn hist_string(&self) -> String {
+        self.hist
+            .iter()
+            .map(|(b, c)| format!("{b}:{c}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// NIMT figures for the mito contigs of one molecule type (`--mito-contig-meta`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MoleculeNimt {
+    pub n_nimt: usize,
+    pub mito_bp_total: u64,
+    pub mito_bp_nimt: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
+    pub mito_pct_nimt: f64,
+}
+
+/// How the percentage metrics are written to summary.tsv / summary.json
+/// (`--summary-units`). `Summary` itself always holds percent.
+///
+/// Every unit is rounded to the same resolution in summary.tsv, 1e-8 of the
+/// whole (`{:.6}` percent, `{:.2}` ppm, `{:.8}` fraction); summary.json has the
+/// unrounded values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SummaryUnits {
+    /// Percent, e.g. `nuclear_pct_numt` (the metric names of older runs).
+    #[default]
+    Percent,
+    /// Parts per million, e.g. `nuclear_numt_ppm`.
+    Ppm,
+    /// Fraction of 1, e.g. `nuclear_numt_frac`.
+    Fraction,
+}
+
+impl SummaryUnits {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SummaryUnits::Percent => "percent",
+            SummaryUnits::Ppm => "ppm",
+            SummaryUnits::Fraction => "fraction",
+        }
+    }
+
+    /// Metric name of the percent metric `pct_name` in this unit:
+    /// `nuclear_pct_numt` → `nuclear_numt_ppm`.
+    pub fn metric_name(self, pct_name: &str) -> String {
+        let suffix = match self {
+            SummaryUnits::Percent => return pct_name.to_string(),
+            SummaryUnits::Ppm => "ppm",
+            SummaryUnits::Fraction => "frac",
+        };
+        format!("{}_{suffix}", pct_name.replacen("_pct", "", 1))
+    }
+
+    /// A percent value in this unit.
+    pub fn convert(self, percent: f64) -> f64 {
+        match self {
+            SummaryUnits::Percent => percent,
+            SummaryUnits::Ppm => percent * 1e4,
+            SummaryUnits::Fraction => percent / 100.0,
+        }
+    }
+
+    /// A percent value in this unit, rounded for summary.tsv.
+    pub fn format(self, percent: f64) -> String {
+        let v = self.convert(percent);
+        match self {
+            SummaryUnits::Percent => format!("{v:.6}"),
+            SummaryUnits::Ppm => format!("{v:.2}"),
+            SummaryUnits::Fraction => format!("{v:.8}"),
+        }
+    }
+}
+
+/// Which calls a figure, a BED or a mask takes (`--tier`): all of them, or
+/// only the high-confidence ones. Reported side by side, the two bound the
+/// NUMT content from below (`highconf_`) and above (`all_`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Tier {
+    /// Every call.
+    #[default]
+    All,
+    /// Calls with |Δ| ≥ `ClassifyParams::highconf_threshold` only.
+    High,
+}
+
+impl Tier {
+    /// Prefix of the tier's figures in summary.tsv / summary.json.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Tier::All => "all",
+            Tier::High => "highconf",
+        }
+    }
+
+    /// A call of this confidence is in the tier.
+    pub fn includes(self, high_confidence: bool) -> bool {
+        self == Tier::All || high_confidence
+    }
+}
 
 /// Output struct that directly matches the `summary.tsv` rows you showed.
-#[derive(Debug, Clone)]
+///
+/// In other units than percent, summary.json has e.g. `nuclear_numt_ppm` in
+/// place of `nuclear_pct_numt`, and a `units` field.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Summary {
     pub n_pairs: usize,
     pub n_numt: usize,
     pub n_nimt: usize,
+    /// Shared_Support pairs: strong evidence on both sides.
+    pub n_shared: usize,
+    /// Likely_NUMT pairs merged into regions (see `regions`).
+    pub n_numt_regions: usize,
+    /// Pairs of any call clustered into regions (`regions.tsv`).
+    pub n_regions: usize,
 
     pub nuclear_bp_total: u64,
     pub nuclear_bp_numt: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
     pub nuclear_pct_numt: f64,
 
+    // Gross figures above include assembly-gap Ns inside NUMT loci; the net
+    // figures leave them out
+    pub nuclear_bp_numt_n: u64,
+    pub nuclear_bp_numt_net: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
+    pub nuclear_pct_numt_net: f64,
+    /// Soft-masked (lowercase) bp of the assembly inside NUMT loci.
+    pub nuclear_bp_numt_softmasked: u64,
+
+    // NUMT calls flagged `possible_collapsed_repeat`; the conservative figures
+    // leave them out (nuclear_*_numt above is the liberal figure)
+    pub n_numt_collapsed_repeat: usize,
+    pub nuclear_bp_numt_collapsed_repeat: u64,
+    pub nuclear_bp_numt_conservative: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
+    pub nuclear_pct_numt_conservative: f64,
+
     pub mito_bp_total: u64,
     pub mito_bp_nimt: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
     pub mito_pct_nimt: f64,
+    /// The three figures above (and the NIMT count) by molecule type; only
+    /// with `--mito-contig-meta`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nimt_by_molecule_type: BTreeMap<String, MoleculeNimt>,
 
     // “Homologous coverage” on the opposite genome, stratified by call
     pub mito_bp_covered_by_numt_homologs: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
     pub mito_pct_covered_by_numt_homologs: f64,
 
     pub nuc_bp_covered_by_nimt_homologs: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
     pub nuc_pct_covered_by_nimt_homologs: f64,
+
+    // Shared_Support loci on each side; with `shared_in_percentages` their bp
+    // are also in the NUMT (nuclear) and NIMT (mito) figures above
+    pub nuclear_bp_shared: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
+    pub nuclear_pct_shared: f64,
+    pub mito_bp_shared: u64,
+    #[cfg_attr(feature = "schema", schemars(default))]
+    pub mito_pct_shared: f64,
+    pub shared_in_percentages: bool,
+
+    // Likely_NUMT/Likely_NIMT calls whose mito side lies entirely inside a
+    // `--mito-control-region`, and their mito bp
+    pub n_control_region: usize,
+    pub mito_bp_control_region: u64,
+
+    // Likely_NUMT/Likely_NIMT calls backed by alignments in both directions,
+    // and by a mito→nuclear one alone (see `PairedLocus::reciprocal`)
+    pub n_calls_reciprocal: usize,
+    pub n_calls_one_sided: usize,
+    /// The `STRATA_METRICS` figures by name, e.g. `n_numt_high_confidence`;
+    /// percentages in percent, as above.
+    pub strata: BTreeMap<String, MetricValue>,
+    /// The `FIELD_METRICS` figures once per `Tier`, by prefixed name, e.g.
+    /// `all_n_numt` and `highconf_n_numt`; percentages in percent.
+    pub tiers: BTreeMap<String, MetricValue>,
+
+    // How diverged the called loci are
+    pub numt_identity: IdentityStats,
+    pub nimt_identity: IdentityStats,
+
+    /// Run-level caveats on how far the calls can be trusted, e.g.
+    /// `span_evidence_disabled`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub caveats: Vec<String>,
 }
 
 /// Compute the summary for a run.
 ///
 /// - `pairs`: candidate loci (reciprocal mapping + merging).
 /// - `calls`: map of pair_id → call string ("Likely_NUMT", "Likely_NIMT", or other).
+///
+/// Reads the assembly sizes from the FASTAs; see `compute_percentages_with_sizes`.
 pub fn compute_percentages(
     mito_fa: &Path,
     nuc_fa: &Path,
     pairs: &[PairedLocus],
     calls: &HashMap<String, String>,
 ) -> Result<Summary> {
-    // Assembly lengths
-    let mito_lens = fasta::contig_lengths(mito_fa)?;
-    let nuc_lens = fasta::contig_lengths(nuc_fa)?;
-    let mito_bp_total: u64 = mito_lens.values().sum();
-    let nuclear_bp_total: u64 = nuc_lens.values().sum();
-
-    // Counters & per-contig interval buckets
-    let mut n_numt = 0usize;
-    let mut n_nimt = 0usize;
-
-    // Intervals to union later, keyed by contig
-    let mut nuc_intervals_numt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
-    let mut mito_intervals_nimt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
-
-    // “Homologous coverage on the opposite genome”:
-    //   NUMT calls contribute their *mito* intervals (coverage of mito by NUMT homologs)
-    //   NIMT calls contribute their *nuclear* intervals (coverage of nuclear by NIMT homologs)
-    let mut mito_intervals_from_numt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
-    let mut nuc_intervals_from_nimt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+    let sizes = GenomeSizes::read(&LengthCache::default(), mito_fa, nuc_fa)?;
+    compute_percentages_with_sizes(
+        sizes.mito_bp_total,
+        sizes.nuclear_bp_total,
+        Some(&sizes.contigs),
+        pairs,
+        calls,
+    )
+}
 
+/// `compute_percentages` from assembly sizes known beforehand (e.g. the
+/// `genome_sizes` of `run_manifest.json`), without the FASTAs. With
+/// `contig_lengths`, every locus must lie on a contig of the given length,
+/// which catches sizes from another assembly.
+pub fn compute_percentages_with_sizes(
+    mito_bp_total: u64,
+    nuclear_bp_total: u64,
+    contig_lengths: Option<&ContigLengths>,
+    pairs: &[PairedLocus],
+    calls: &HashMap<String, String>,
+) -> Result<Summary> {
+    if let Some(lens) = contig_lengths {
+        for p in pairs {
+            lens.check(p)?;
+        }
+    }
+    let mut b = SummaryBuilder::default();
     for p in pairs {
         let call = calls
             .get(&p.pair_id)
             .map(String::as_str)
             .unwrap_or("Ambiguous");
-        match call {
-            "Likely_NUMT" => {
-                n_numt += 1;
-
-                // nuclear bp that are NUMT (union across nuclear side of these loci)
-                add_interval(
-                    &mut nuc_intervals_numt,
-                    &p.nuc_contig,
-                    p.nuc_start,
-                    p.nuc_end,
-                );
+        b.add(p, call);
+    }
+    Ok(b.finish(mito_bp_total, nuclear_bp_total))
+}
 
-                // homologous coverage on mito (the counterpart region)
-                add_interval(
-                    &mut mito_intervals_from_numt,
-                    &p.mito_contig,
-                    p.mito_start,
-                    p.mito_end,
-                );
+/// Contig lengths of both assemblies, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ContigLengths {
+    pub mito: BTreeMap<String, u64>,
+    pub nuclear: BTreeMap<String, u64>,
+}
+
+impl ContigLengths {
+    /// Both intervals of `p` lie on a known contig, within its length.
+    pub fn check(&self, p: &PairedLocus) -> Result<()> {
+        for (genome, lens, contig, end) in [
+            ("mito", &self.mito, &p.mito_contig, p.mito_end),
+            ("nuclear", &self.nuclear, &p.nuc_contig, p.nuc_end),
+        ] {
+            match lens.get(contig) {
+                None => bail!(
+                    "{}: {contig} is not a contig of the {genome} assembly",
+                    p.pair_id
+                ),
+                Some(&len) if end as u64 > len => bail!(
+                    "{}: ends at {end}, past the end of {contig} ({len} bp)",
+                    p.pair_id
+                ),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assembly sizes a summary needs, recorded at classify time as
+/// `genome_sizes` in `run_manifest.json` so later runs can summarize without
+/// the FASTAs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GenomeSizes {
+    pub mito_bp_total: u64,
+    /// All nuclear contigs, excluded ones included (see `totals`).
+    pub nuclear_bp_total: u64,
+    #[serde(flatten)]
+    pub contigs: ContigLengths,
+}
+
+impl GenomeSizes {
+    /// Read from the FASTAs, through `lengths`.
+    pub fn read(lengths: &LengthCache, mito_fa: &Path, nuc_fa: &Path) -> Result<Self> {
+        let collect = |p: &Path| -> Result<BTreeMap<String, u64>> {
+            Ok(lengths
+                .lengths(p)?
+                .iter()
+                .map(|(c, &l)| (c.clone(), l))
+                .collect())
+        };
+        let contigs = ContigLengths {
+            mito: collect(mito_fa)?,
+            nuclear: collect(nuc_fa)?,
+        };
+        Ok(Self {
+            mito_bp_total: contigs.mito.values().sum(),
+            nuclear_bp_total: contigs.nuclear.values().sum(),
+            contigs,
+        })
+    }
+
+    /// Total sizes (mito, nuclear) in bp, leaving out the `excluded_nuclear`
+    /// contigs.
+    pub fn totals(&self, excluded_nuclear: &[String]) -> (u64, u64) {
+        let excluded_bp: u64 = excluded_nuclear
+            .iter()
+            .filter_map(|c| self.contigs.nuclear.get(c))
+            .sum();
+        (self.mito_bp_total, self.nuclear_bp_total - excluded_bp)
+    }
+}
+
+/// Total assembly sizes (mito, nuclear) in bp, read from the FASTAs, leaving
+/// out the `excluded_nuclear` contigs.
+pub fn genome_totals(
+    lengths: &LengthCache,
+    mito_fa: &Path,
+    nuc_fa: &Path,
+    excluded_nuclear: &[String],
+) -> Result<(u64, u64)> {
+    Ok(GenomeSizes::read(lengths, mito_fa, nuc_fa)?.totals(excluded_nuclear))
+}
+
+/// The side of a locus whose interval a bp metric takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Nuclear,
+    Mito,
+}
+
+impl Side {
+    fn interval(self, p: &PairedLocus) -> (&str, u32, u32) {
+        match self {
+            Side::Nuclear => (&p.nuc_contig, p.nuc_start, p.nuc_end),
+            Side::Mito => (&p.mito_contig, p.mito_start, p.mito_end),
+        }
+    }
+}
+
+/// What the summary knows of a locus besides its call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocusFlags {
+    /// Flagged `possible_collapsed_repeat`.
+    pub collapsed_repeat: bool,
+    /// Called with high confidence (`ClassifyParams::highconf_threshold`).
+    pub high_confidence: bool,
+    /// Mito interval entirely inside a `--mito-control-region`.
+    pub control_region: bool,
+}
+
+/// Which of the selected loci a metric takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stratum {
+    All,
+    CollapsedRepeat,
+    /// Not flagged `possible_collapsed_repeat` (the conservative figures).
+    SingleCopy,
+    ControlRegion,
+    /// Backed by alignments in both directions (`PairedLocus::reciprocal`).
+    Reciprocal,
+    OneSided,
+    HighConfidence,
+}
+
+impl Stratum {
+    fn includes(self, p: &PairedLocus, f: LocusFlags) -> bool {
+        match self {
+            Stratum::All => true,
+            Stratum::CollapsedRepeat => f.collapsed_repeat,
+            Stratum::SingleCopy => !f.collapsed_repeat,
+            Stratum::ControlRegion => f.control_region,
+            Stratum::Reciprocal => p.reciprocal,
+            Stratum::OneSided => !p.reciprocal,
+            Stratum::HighConfidence => f.high_confidence,
+        }
+    }
+}
+
+/// One count or bp figure of the summary: the loci called one of `calls`,
+/// in `stratum`, counted, or with `side` unioned on that side.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricSpec {
+    pub name: &'static str,
+    pub calls: &'static [&'static str],
+    pub stratum: Stratum,
+    pub side: Option<Side>,
+    /// Name of the bp as a percentage of that side's assembly.
+    pub pct: Option<&'static str>,
+    /// Shared_Support loci count too under `--shared-in-percentages`.
+    pub with_shared: bool,
+}
+
+impl MetricSpec {
+    const fn count(name: &'static str, calls: &'static [&'static str], stratum: Stratum) -> Self {
+        Self {
+            name,
+            calls,
+            stratum,
+            side: None,
+            pct: None,
+            with_shared: false,
+        }
+    }
+
+    const fn bp(
+        name: &'static str,
+        pct: Option<&'static str>,
+        calls: &'static [&'static str],
+        side: Side,
+        stratum: Stratum,
+    ) -> Self {
+        Self {
+            name,
+            calls,
+            stratum,
+            side: Some(side),
+            pct,
+            with_shared: false,
+        }
+    }
+
+    const fn with_shared(mut self) -> Self {
+        self.with_shared = true;
+        self
+    }
+
+    fn selects(&self, p: &PairedLocus, call: &str, flags: LocusFlags, shared: bool) -> bool {
+        (self.calls.contains(&call) || (self.with_shared && shared && call == "Shared_Support"))
+            && self.stratum.includes(p, flags)
+    }
+}
+
+const NUMT: &[&str] = &["Likely_NUMT"];
+const NIMT: &[&str] = &["Likely_NIMT"];
+const SHARED: &[&str] = &["Shared_Support"];
+const CALLED: &[&str] = &["Likely_NUMT", "Likely_NIMT"];
+
+/// The figures with a field of their own in `Summary`.
+const FIELD_METRICS: &[MetricSpec] = {
+    use Side::*;
+    use Stratum::*;
+    &[
+        MetricSpec::count("n_numt", NUMT, All),
+        MetricSpec::count("n_nimt", NIMT, All),
+        MetricSpec::count("n_shared", SHARED, All),
+        MetricSpec::count("n_numt_collapsed_repeat", NUMT, CollapsedRepeat),
+        MetricSpec::count("n_control_region", CALLED, ControlRegion),
+        MetricSpec::count("n_calls_reciprocal", CALLED, Reciprocal),
+        MetricSpec::count("n_calls_one_sided", CALLED, OneSided),
+        MetricSpec::bp(
+            "nuclear_bp_numt",
+            Some("nuclear_pct_numt"),
+            NUMT,
+            Nuclear,
+            All,
+        )
+        .with_shared(),
+        MetricSpec::bp(
+            "nuclear_bp_numt_collapsed_repeat",
+            None,
+            NUMT,
+            Nuclear,
+            CollapsedRepeat,
+        )
+        .with_shared(),
+        MetricSpec::bp(
+            "nuclear_bp_numt_conservative",
+            Some("nuclear_pct_numt_conservative"),
+            NUMT,
+            Nuclear,
+            SingleCopy,
+        )
+        .with_shared(),
+        MetricSpec::bp("mito_bp_nimt", Some("mito_pct_nimt"), NIMT, Mito, All).with_shared(),
+        // “Homologous coverage on the opposite genome”
+        MetricSpec::bp(
+            "mito_bp_covered_by_numt_homologs",
+            Some("mito_pct_covered_by_numt_homologs"),
+            NUMT,
+            Mito,
+            All,
+        ),
+        MetricSpec::bp(
+            "nuc_bp_covered_by_nimt_homologs",
+            Some("nuc_pct_covered_by_nimt_homologs"),
+            NIMT,
+            Nuclear,
+            All,
+        ),
+        MetricSpec::bp(
+            "nuclear_bp_shared",
+            Some("nuclear_pct_shared"),
+            SHARED,
+            Nuclear,
+            All,
+        ),
+        MetricSpec::bp("mito_bp_shared", Some("mito_pct_shared"), SHARED, Mito, All),
+        MetricSpec::bp("mito_bp_control_region", None, CALLED, Mito, ControlRegion),
+    ]
+};
+
+/// Further strata, in `Summary::strata` and at the end of summary.tsv's
+/// counts; a new call type or stratum is a line here.
+pub const STRATA_METRICS: &[MetricSpec] = {
+    use Side::*;
+    use Stratum::*;
+    &[
+        MetricSpec::count("n_numt_high_confidence", NUMT, HighConfidence),
+        MetricSpec::count("n_nimt_high_confidence", NIMT, HighConfidence),
+        MetricSpec::bp(
+            "nuclear_bp_numt_high_confidence",
+            Some("nuclear_pct_numt_high_confidence"),
+            NUMT,
+            Nuclear,
+            HighConfidence,
+        ),
+        MetricSpec::bp(
+            "mito_bp_nimt_high_confidence",
+            Some("mito_pct_nimt_high_confidence"),
+            NIMT,
+            Mito,
+            HighConfidence,
+        ),
+    ]
+};
+
+fn all_metrics() -> impl Iterator<Item = &'static MetricSpec> {
+    FIELD_METRICS.iter().chain(STRATA_METRICS)
+}
+
+/// The names a metric is written under: its own, then its percentage's.
+fn metric_names(m: &MetricSpec) -> impl Iterator<Item = &'static str> {
+    [Some(m.name), m.pct].into_iter().flatten()
+}
+
+/// A count or a percentage in `Summary::strata`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum MetricValue {
+    Count(u64),
+    Percent(f64),
+}
+
+/// The running figure of one `MetricSpec`.
+#[derive(Debug)]
+enum Tally {
+    Count(usize),
+    Bp(IntervalBuckets),
+}
+
+impl Tally {
+    fn for_specs<'a>(specs: impl Iterator<Item = &'a MetricSpec>) -> Vec<Self> {
+        specs
+            .map(|m| match m.side {
+                Some(_) => Tally::Bp(IntervalBuckets::default()),
+                None => Tally::Count(0),
+            })
+            .collect()
+    }
+
+    fn add(&mut self, side: Option<Side>, p: &PairedLocus) {
+        match (self, side) {
+            (Tally::Count(n), _) => *n += 1,
+            (Tally::Bp(b), Some(side)) => {
+                let (contig, start, end) = side.interval(p);
+                b.add(contig, start, end);
+            }
+            (Tally::Bp(_), None) => unreachable!("bp tallies have a side"),
+        }
+    }
+}
+
+/// Counts and union-lengths of `specs` from their `tallies`, each followed
+/// by its percentage (as a *percent* value, e.g., 0.0207 means 0.0207%) of
+/// the metric's side.
+fn spec_figures<'a>(
+    specs: impl Iterator<Item = &'a MetricSpec>,
+    tallies: &[Tally],
+    mito_bp_total: u64,
+    nuclear_bp_total: u64,
+) -> Vec<(&'static str, MetricValue)> {
+    let mut out = Vec::new();
+    for (m, tally) in specs.zip(tallies) {
+        let n = match tally {
+            Tally::Count(n) => *n as u64,
+            Tally::Bp(b) => b.union_len(),
+        };
+        out.push((m.name, MetricValue::Count(n)));
+        if let (Some(name), Some(side)) = (m.pct, m.side) {
+            let total = match side {
+                Side::Nuclear => nuclear_bp_total,
+                Side::Mito => mito_bp_total,
+            };
+            out.push((name, MetricValue::Percent(pct(n, total))));
+        }
+    }
+    out
+}
+
+/// Incremental summary accumulation: feed `(locus, call)` one at a time.
+///
+/// Interval buckets are compacted as they grow, so memory scales with the
+/// number of *disjoint* called regions rather than the number of pairs.
+#[derive(Debug)]
+pub struct SummaryBuilder {
+    n_pairs: usize,
+    /// Add Shared_Support loci to the NUMT/NIMT intervals too.
+    shared_in_percentages: bool,
+    /// One per `FIELD_METRICS` then `STRATA_METRICS`.
+    tallies: Vec<Tally>,
+    /// One per `FIELD_METRICS`, of the `Tier::High` loci only.
+    highconf_tallies: Vec<Tally>,
+    n_nimt_by_mito_contig: HashMap<String, usize>,
+
+    // (aln_ident, aln_len) per call type for identity stats
+    numt_idents: Vec<(f32, u32)>,
+    nimt_idents: Vec<(f32, u32)>,
+}
+
+impl Default for SummaryBuilder {
+    fn default() -> Self {
+        Self {
+            n_pairs: 0,
+            shared_in_percentages: false,
+            tallies: Tally::for_specs(all_metrics()),
+            highconf_tallies: Tally::for_specs(FIELD_METRICS.iter()),
+            n_nimt_by_mito_contig: HashMap::new(),
+            numt_idents: Vec::new(),
+            nimt_idents: Vec::new(),
+        }
+    }
+}
+
+impl SummaryBuilder {
+    /// Count Shared_Support bp in the NUMT (nuclear) and NIMT (mito) figures
+    /// (`--shared-in-percentages`); by default they are only reported apart.
+    pub fn with_shared_in_percentages(mut self, yes: bool) -> Self {
+        self.shared_in_percentages = yes;
+        self
+    }
+
+    pub fn add(&mut self, p: &PairedLocus, call: &str) {
+        self.add_flagged(p, call, LocusFlags::default());
+    }
+
+    /// As `add`, with what else is known of the locus for the strata.
+    pub fn add_flagged(&mut self, p: &PairedLocus, call: &str, flags: LocusFlags) {
+        self.n_pairs += 1;
+        let shared = self.shared_in_percentages;
+        for (m, tally) in all_metrics().zip(&mut self.tallies) {
+            if m.selects(p, call, flags, shared) {
+                tally.add(m.side, p);
             }
+        }
+        if Tier::High.includes(flags.high_confidence) {
+            for (m, tally) in FIELD_METRICS.iter().zip(&mut self.highconf_tallies) {
+                if m.selects(p, call, flags, shared) {
+                    tally.add(m.side, p);
+                }
+            }
+        }
+        match call {
+            "Likely_NUMT" => self.numt_idents.push((p.aln_ident, p.aln_len)),
             "Likely_NIMT" => {
-                n_nimt += 1;
-
-                // mito bp that are NIMT (union across mito side of these loci)
-                add_interval(
-                    &mut mito_intervals_nimt,
-                    &p.mito_contig,
-                    p.mito_start,
-                    p.mito_end,
-                );
+                *self
+                    .n_nimt_by_mito_contig
+                    .entry(p.mito_contig.clone())
+                    .or_default() += 1;
+                self.nimt_idents.push((p.aln_ident, p.aln_len));
+            }
+            _ => {}
+        }
+    }
 
-                // homologous coverage on nuclear side
-                add_interval(
-                    &mut nuc_intervals_from_nimt,
-                    &p.nuc_contig,
-                    p.nuc_start,
-                    p.nuc_end,
-                );
+    fn buckets(&self, name: &str) -> &IntervalBuckets {
+        let i = all_metrics()
+            .position(|m| m.name == name)
+            .expect("a declared metric");
+        match &self.tallies[i] {
+            Tally::Bp(b) => b,
+            Tally::Count(_) => panic!("{name} is a count"),
+        }
+    }
+
+    /// NIMT figures per molecule type of the mito contigs (`mito_lens`), for
+    /// `Summary::with_nimt_by_molecule_type`.
+    pub fn nimt_by_molecule_type(
+        &self,
+        meta: &MitoContigMeta,
+        mito_lens: &BTreeMap<String, u64>,
+    ) -> BTreeMap<String, MoleculeNimt> {
+        let nimt = self.nimt_mito_intervals();
+        let mut by_type: BTreeMap<String, MoleculeNimt> = BTreeMap::new();
+        for (contig, len) in mito_lens {
+            let m = by_type
+                .entry(meta.molecule_type(contig).to_string())
+                .or_default();
+            m.n_nimt += self.n_nimt_by_mito_contig.get(contig).copied().unwrap_or(0);
+            m.mito_bp_total += len;
+            m.mito_bp_nimt += nimt.get(contig).cloned().map_or(0, union_len);
+        }
+        for m in by_type.values_mut() {
+            m.mito_pct_nimt = pct(m.mito_bp_nimt, m.mito_bp_total);
+        }
+        by_type
+    }
+
+    /// Merged nuclear intervals of the Likely_NUMT calls so far, per contig.
+    pub fn numt_nuclear_intervals(&self) -> HashMap<String, Vec<(u32, u32)>> {
+        self.buckets("nuclear_bp_numt").merged()
+    }
+
+    /// Merged mito intervals of the Likely_NIMT calls so far, per contig.
+    pub fn nimt_mito_intervals(&self) -> HashMap<String, Vec<(u32, u32)>> {
+        self.buckets("mito_bp_nimt").merged()
+    }
+
+    pub fn finish(self, mito_bp_total: u64, nuclear_bp_total: u64) -> Summary {
+        let figures: HashMap<&str, MetricValue> = spec_figures(
+            all_metrics(),
+            &self.tallies,
+            mito_bp_total,
+            nuclear_bp_total,
+        )
+        .into_iter()
+        .collect();
+        let n = |name: &str| match figures[name] {
+            MetricValue::Count(n) => n,
+            MetricValue::Percent(_) => unreachable!("{name} is a percentage"),
+        };
+        let p = |name: &str| match figures[name] {
+            MetricValue::Percent(p) => p,
+            MetricValue::Count(_) => unreachable!("{name} is not a percentage"),
+        };
+        let strata = STRATA_METRICS
+            .iter()
+            .flat_map(metric_names)
+            .map(|name| (name.to_string(), figures[name]))
+            .collect();
+        // the all-call figures are those above; FIELD_METRICS lead `tallies`
+        let mut tiers = BTreeMap::new();
+        for (tier, tallies) in [
+            (Tier::All, &self.tallies[..FIELD_METRICS.len()]),
+            (Tier::High, &self.highconf_tallies[..]),
+        ] {
+            let figs = spec_figures(
+                FIELD_METRICS.iter(),
+                tallies,
+                mito_bp_total,
+                nuclear_bp_total,
+            );
+            for (name, v) in figs {
+                tiers.insert(format!("{}_{name}", tier.prefix()), v);
             }
-            _ => { /* Ambiguous – ignored for summary */ }
+        }
+
+        Summary {
+            n_pairs: self.n_pairs,
+            n_numt: n("n_numt") as usize,
+            n_nimt: n("n_nimt") as usize,
+            n_shared: n("n_shared") as usize,
+            n_numt_regions: 0,
+            n_regions: 0,
+
+            nuclear_bp_total,
+            nuclear_bp_numt: n("nuclear_bp_numt"),
+            nuclear_pct_numt: p("nuclear_pct_numt"),
+
+            nuclear_bp_numt_n: 0,
+            nuclear_bp_numt_net: n("nuclear_bp_numt"),
+            nuclear_pct_numt_net: p("nuclear_pct_numt"),
+            nuclear_bp_numt_softmasked: 0,
+
+            n_numt_collapsed_repeat: n("n_numt_collapsed_repeat") as usize,
+            nuclear_bp_numt_collapsed_repeat: n("nuclear_bp_numt_collapsed_repeat"),
+            nuclear_bp_numt_conservative: n("nuclear_bp_numt_conservative"),
+            nuclear_pct_numt_conservative: p("nuclear_pct_numt_conservative"),
+
+            mito_bp_total,
+            mito_bp_nimt: n("mito_bp_nimt"),
+            mito_pct_nimt: p("mito_pct_nimt"),
+            nimt_by_molecule_type: BTreeMap::new(),
+
+            mito_bp_covered_by_numt_homologs: n("mito_bp_covered_by_numt_homologs"),
+            mito_pct_covered_by_numt_homologs: p("mito_pct_covered_by_numt_homologs"),
+
+            nuc_bp_covered_by_nimt_homologs: n("nuc_bp_covered_by_nimt_homologs"),
+            nuc_pct_covered_by_nimt_homologs: p("nuc_pct_covered_by_nimt_homologs"),
+
+            nuclear_bp_shared: n("nuclear_bp_shared"),
+            nuclear_pct_shared: p("nuclear_pct_shared"),
+            mito_bp_shared: n("mito_bp_shared"),
+            mito_pct_shared: p("mito_pct_shared"),
+            shared_in_percentages: self.shared_in_percentages,
+
+            n_control_region: n("n_control_region") as usize,
+            mito_bp_control_region: n("mito_bp_control_region"),
+            n_calls_reciprocal: n("n_calls_reciprocal") as usize,
+            n_calls_one_sided: n("n_calls_one_sided") as usize,
+            strata,
+            tiers,
+
+            numt_identity: IdentityStats::from_pairs(&self.numt_idents),
+            nimt_identity: IdentityStats::from_pairs(&self.nimt_idents),
+            caveats: Vec::new(),
         }
     }
+}
 
-    // Union-lengths
-    let nuclear_bp_numt = union_len_all(&nuc_intervals_numt);
-    let mito_bp_nimt = union_len_all(&mito_intervals_nimt);
+impl Summary {
+    /// Record the N bases (assembly gaps) inside the NUMT nuclear intervals and
+    /// derive the net NUMT figures.
+    pub fn with_numt_gap_bp(mut self, n_bases: u64) -> Self {
+        self.nuclear_bp_numt_n = n_bases;
+        self.nuclear_bp_numt_net = self.nuclear_bp_numt.saturating_sub(n_bases);
+        self.nuclear_pct_numt_net = pct(self.nuclear_bp_numt_net, self.nuclear_bp_total);
+        self
+    }
 
-    let mito_bp_covered_by_numt_homologs = union_len_all(&mito_intervals_from_numt);
-    let nuc_bp_covered_by_nimt_homologs = union_len_all(&nuc_intervals_from_nimt);
+    /// Record the soft-masked bases inside the NUMT nuclear intervals.
+    pub fn with_numt_softmask_bp(mut self, masked_bases: u64) -> Self {
+        self.nuclear_bp_numt_softmasked = masked_bases;
+        self
+    }
 
-    // Percentages (as *percent* values, e.g., 0.0207 means 0.0207%)
-    let nuclear_pct_numt = pct(nuclear_bp_numt, nuclear_bp_total);
-    let mito_pct_nimt = pct(mito_bp_nimt, mito_bp_total);
+    /// Record the NIMT figures per molecule type (see `SummaryBuilder`).
+    pub fn with_nimt_by_molecule_type(mut self, by_type: BTreeMap<String, MoleculeNimt>) -> Self {
+        self.nimt_by_molecule_type = by_type;
+        self
+    }
 
-    let mito_pct_covered_by_numt_homologs = pct(mito_bp_covered_by_numt_homologs, mito_bp_total);
-    let nuc_pct_covered_by_nimt_homologs = pct(nuc_bp_covered_by_nimt_homologs, nuclear_bp_total);
+    /// Record how many NUMT regions the NUMT pairs were merged into.
+    pub fn with_numt_regions(mut self, n: usize) -> Self {
+        self.n_numt_regions = n;
+        self
+    }
 
-    Ok(Summary {
-        n_pairs: pairs.len(),
-        n_numt,
-        n_nimt,
+    /// Record how many regions all pairs were clustered into.
+    pub fn with_regions(mut self, n: usize) -> Self {
+        self.n_regions = n;
+        self
+    }
 
-        nuclear_bp_total,
-        nuclear_bp_numt,
-        nuclear_pct_numt,
+    pub fn with_caveat(mut self, caveat: &str) -> Self {
+        self.caveats.push(caveat.to_string());
+        self
+    }
 
-        mito_bp_total,
-        mito_bp_nimt,
-        mito_pct_nimt,
+    /// The percentage metrics by (percent) name, in output order.
+    pub fn percent_metrics(&self) -> [(&'static str, f64); 8] {
+        [
+            ("nuclear_pct_numt", self.nuclear_pct_numt),
+            ("nuclear_pct_numt_net", self.nuclear_pct_numt_net),
+            (
+                "nuclear_pct_numt_conservative",
+                self.nuclear_pct_numt_conservative,
+            ),
+            ("mito_pct_nimt", self.mito_pct_nimt),
+            (
+                "mito_pct_covered_by_numt_homologs",
+                self.mito_pct_covered_by_numt_homologs,
+            ),
+            (
+                "nuc_pct_covered_by_nimt_homologs",
+                self.nuc_pct_covered_by_nimt_homologs,
+            ),
+            ("nuclear_pct_shared", self.nuclear_pct_shared),
+            ("mito_pct_shared", self.mito_pct_shared),
+        ]
+    }
+}
 
-        mito_bp_covered_by_numt_homologs,
-        mito_pct_covered_by_numt_homologs,
+/// Per-contig interval lists that merge themselves once they double in size.
+#[derive(Debug, Default)]
+pub(crate) struct IntervalBuckets {
+    by_contig: HashMap<String, Vec<(u32, u32)>>,
+    compacted_len: HashMap<String, usize>,
+}
+
+impl IntervalBuckets {
+    pub(crate) fn add(&mut self, contig: &str, start: u32, end: u32) {
+        add_interval(&mut self.by_contig, contig, start, end);
+        let Some(v) = self.by_contig.get_mut(contig) else {
+            return;
+        };
+        let last = self.compacted_len.entry(contig.to_string()).or_insert(0);
+        if v.len() > 2 * *last + 1024 {
+            compact(v);
+            *last = v.len();
+        }
+    }
+
+    pub(crate) fn union_len(&self) -> u64 {
+        union_len_all(&self.by_contig)
+    }
+
+    pub(crate) fn merged(&self) -> HashMap<String, Vec<(u32, u32)>> {
+        let mut m = self.by_contig.clone();
+        m.values_mut().for_each(compact);
+        m
+    }
+}
 
-        nuc_bp_covered_by_nimt_homologs,
-        nuc_pct_covered_by_nimt_homologs,
-    })
+/// Write the summary as a 2-column TSV (metric\tvalue), mirroring your examples,
+/// with the percentage metrics in `units`.
+pub fn write_summary_tsv(out_path: &Path, s: &Summary, units: SummaryUnits) -> Result<()> {
+    fs_err::write(out_path, summary_tsv(s, units)?)?;
+    Ok(())
 }
 
-/// Write the summary as a 2-column TSV (metric\tvalue), mirroring your examples.
-pub fn write_summary_tsv(out_path: &Path, s: &Summary) -> Result<()> {
+/// summary.tsv content.
+pub fn summary_tsv(s: &Summary, units: SummaryUnits) -> Result<String> {
     use std::fmt::Write;
     let mut t = String::new();
+    let pcts: HashMap<&str, f64> = s.percent_metrics().into_iter().collect();
+    let pct = |t: &mut String, name: &str| {
+        writeln!(
+            t,
+            "{}\t{}",
+            units.metric_name(name),
+            units.format(pcts[name])
+        )
+    };
 
     writeln!(&mut t, "metric\tvalue")?;
     writeln!(&mut t, "n_pairs\t{}", s.n_pairs)?;
     writeln!(&mut t, "n_numt\t{}", s.n_numt)?;
     writeln!(&mut t, "n_nimt\t{}", s.n_nimt)?;
+    writeln!(&mut t, "n_shared\t{}", s.n_shared)?;
+    writeln!(&mut t, "n_numt_regions\t{}", s.n_numt_regions)?;
+    writeln!(&mut t, "n_regions\t{}", s.n_regions)?;
     writeln!(&mut t, "nuclear_bp_total\t{}", s.nuclear_bp_total)?;
     writeln!(&mut t, "nuclear_bp_numt\t{}", s.nuclear_bp_numt)?;
-    writeln!(&mut t, "nuclear_pct_numt\t{:.6}", s.nuclear_pct_numt)?;
+    pct(&mut t, "nuclear_pct_numt")?;
+    writeln!(&mut t, "nuclear_bp_numt_n\t{}", s.nuclear_bp_numt_n)?;
+    writeln!(&mut t, "nuclear_bp_numt_net\t{}", s.nuclear_bp_numt_net)?;
+    pct(&mut t, "nuclear_pct_numt_net")?;
+    writeln!(
+        &mut t,
+        "nuclear_bp_numt_softmasked\t{}",
+        s.nuclear_bp_numt_softmasked
+    )?;
+    writeln!(
+        &mut t,
+        "n_numt_collapsed_repeat\t{}",
+        s.n_numt_collapsed_repeat
+    )?;
+    writeln!(
+        &mut t,
+        "nuclear_bp_numt_collapsed_repeat\t{}",
+        s.nuclear_bp_numt_collapsed_repeat
+    )?;
+    writeln!(
+        &mut t,
+        "nuclear_bp_numt_conservative\t{}",
+        s.nuclear_bp_numt_conservative
+    )?;
+    pct(&mut t, "nuclear_pct_numt_conservative")?;
     writeln!(&mut t, "mito_bp_total\t{}", s.mito_bp_total)?;
     writeln!(&mut t, "mito_bp_nimt\t{}", s.mito_bp_nimt)?;
-    writeln!(&mut t, "mito_pct_nimt\t{:.6}", s.mito_pct_nimt)?;
+    pct(&mut t, "mito_pct_nimt")?;
+    for (ty, m) in &s.nimt_by_molecule_type {
+        writeln!(&mut t, "n_nimt_{ty}\t{}", m.n_nimt)?;
+        writeln!(&mut t, "mito_bp_total_{ty}\t{}", m.mito_bp_total)?;
+        writeln!(&mut t, "mito_bp_nimt_{ty}\t{}", m.mito_bp_nimt)?;
+        writeln!(
+            &mut t,
+            "{}\t{}",
+            units.metric_name(&format!("mito_pct_nimt_{ty}")),
+            units.format(m.mito_pct_nimt)
+        )?;
+    }
     writeln!(
         &mut t,
         "mito_bp_covered_by_numt_homologs\t{}",
         s.mito_bp_covered_by_numt_homologs
     )?;
-    writeln!(
-        &mut t,
-        "mito_pct_covered_by_numt_homologs\t{:.6}",
-        s.mito_pct_covered_by_numt_homologs
-    )?;
+    pct(&mut t, "mito_pct_covered_by_numt_homologs")?;
     writeln!(
         &mut t,
         "nuc_bp_covered_by_nimt_homologs\t{}",
         s.nuc_bp_covered_by_nimt_homologs
     )?;
+    pct(&mut t, "nuc_pct_covered_by_nimt_homologs")?;
+    writeln!(&mut t, "nuclear_bp_shared\t{}", s.nuclear_bp_shared)?;
+    pct(&mut t, "nuclear_pct_shared")?;
+    writeln!(&mut t, "mito_bp_shared\t{}", s.mito_bp_shared)?;
+    pct(&mut t, "mito_pct_shared")?;
+    writeln!(&mut t, "shared_in_percentages\t{}", s.shared_in_percentages)?;
+    writeln!(&mut t, "n_control_region\t{}", s.n_control_region)?;
     writeln!(
         &mut t,
-        "nuc_pct_covered_by_nimt_homologs\t{:.6}",
-        s.nuc_pct_covered_by_nimt_homologs
+        "mito_bp_control_region\t{}",
+        s.mito_bp_control_region
     )?;
+    writeln!(&mut t, "n_calls_reciprocal\t{}", s.n_calls_reciprocal)?;
+    writeln!(&mut t, "n_calls_one_sided\t{}", s.n_calls_one_sided)?;
+    let figure = |t: &mut String, name: &str, v: Option<&MetricValue>| match v {
+        Some(MetricValue::Count(n)) => writeln!(t, "{name}\t{n}"),
+        Some(MetricValue::Percent(p)) => {
+            writeln!(t, "{}\t{}", units.metric_name(name), units.format(*p))
+        }
+        None => Ok(()),
+    };
+    for name in STRATA_METRICS.iter().flat_map(metric_names) {
+        figure(&mut t, name, s.strata.get(name))?;
+    }
+    for tier in [Tier::All, Tier::High] {
+        for name in FIELD_METRICS.iter().flat_map(metric_names) {
+            let name = format!("{}_{name}", tier.prefix());
+            figure(&mut t, &name, s.tiers.get(&name))?;
+        }
+    }
+    for (label, st) in [("numt", &s.numt_identity), ("nimt", &s.nimt_identity)] {
+        writeln!(&mut t, "{label}_ident_wmean\t{:.4}", st.wmean)?;
+        writeln!(&mut t, "{label}_ident_median\t{:.4}", st.median)?;
+        writeln!(&mut t, "{label}_ident_hist\t{}", st.hist_string())?;
+    }
+    if !s.caveats.is_empty() {
+        writeln!(&mut t, "caveats\t{}", s.caveats.join(","))?;
+    }
+    Ok(t)
+}
 
-    fs_err::write(out_path, t)?;
+/// summary.json content: the summary with the percentage metrics in `units`.
+/// In percent it is the `Summary` as is (no `units` field, as in older runs).
+pub fn summary_json(s: &Summary, units: SummaryUnits) -> serde_json::Value {
+    // plain data: numbers, strings and integer-keyed maps
+    let mut v = serde_json::to_value(s).expect("Summary serializes to JSON");
+    if units != SummaryUnits::Percent {
+        let obj = v.as_object_mut().expect("Summary serializes to an object");
+        for (name, percent) in s.percent_metrics() {
+            obj.remove(name);
+            obj.insert(units.metric_name(name), units.convert(percent).into());
+        }
+        let strata = obj
+            .get_mut("strata")
+            .and_then(|v| v.as_object_mut())
+            .expect("strata serialize to an object");
+        for name in STRATA_METRICS.iter().filter_map(|m| m.pct) {
+            if let Some(MetricValue::Percent(percent)) = s.strata.get(name) {
+                strata.remove(name);
+                strata.insert(units.metric_name(name), units.convert(*percent).into());
+            }
+        }
+        let tiers = obj
+            .get_mut("tiers")
+            .and_then(|v| v.as_object_mut())
+            .expect("tiers serialize to an object");
+        for (name, v) in &s.tiers {
+            if let MetricValue::Percent(percent) = v {
+                tiers.remove(name);
+                tiers.insert(units.metric_name(name), units.convert(*percent).into());
+            }
+        }
+        let by_type = obj
+            .get_mut("nimt_by_molecule_type")
+            .and_then(|v| v.as_object_mut());
+        for (ty, m) in by_type.into_iter().flatten() {
+            let m = m
+                .as_object_mut()
+                .expect("MoleculeNimt serializes to an object");
+            m.remove("mito_pct_nimt");
+            let percent = s.nimt_by_molecule_type[ty].mito_pct_nimt;
+            m.insert(
+                units.metric_name("mito_pct_nimt"),
+                units.convert(percent).into(),
+            );
+        }
+        obj.insert("units".to_string(), units.as_str().into());
+    }
+    v
+}
+
+/// Write the summary as pretty JSON (same content as the TSV, histograms in full).
+pub fn write_summary_json(out_path: &Path, s: &Summary, units: SummaryUnits) -> Result<()> {
+    serde_json::to_writer_pretty(fs_err::File::create(out_path)?, &summary_json(s, units))?;
     Ok(())
 }
 
 /// Parse the contents of classification.tsv (string) into a call map:
-/// pair_id -> "Likely_NUMT" | "Likely_NIMT" | "Ambiguous" (or whatever is present).
+/// pair_id -> "Likely_NUMT" | "Likely_NIMT" | "Shared_Support" | "Ambiguous" (or whatever is present).
 pub fn parse_calls_tsv_str(s: &str) -> HashMap<String, String> {
     let mut m = HashMap::new();
     for line in s.lines().skip(1) {
@@ -237,6 +1187,24 @@ fn add_interval(map: &mut HashMap<String, Vec<(u32, u32)>>, contig: &str, start:
         .push((start, end));
 }
 
+/// Sort and merge overlapping/touching intervals in place.
+fn compact(v: &mut Vec<(u32, u32)>) {
+    if v.is_empty() {
+        return;
+    }
+    v.sort_by_key(|x| (x.0, x.1));
+    let mut w = 0usize;
+    for i in 1..v.len() {
+        if v[i].0 <= v[w].1 {
+            v[w].1 = v[w].1.max(v[i].1);
+        } else {
+            w += 1;
+            v[w] = v[i];
+        }
+    }
+    v.truncate(w + 1);
+}
+
 /// Merge overlapping intervals for one contig and return total length.
 fn union_len(mut v: Vec<(u32, u32)>) -> u64 {
     if v.is_empty() {
@@ -269,6 +1237,9 @@ fn union_len_all(m: &HashMap<String, Vec<(u32, u32)>>) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+    use std::io::Write;
 
     #[test]
     fn union_merges_overlaps() {
@@ -284,6 +1255,192 @@ mod tests {
         assert!((pct(500, 1000) - 50.0).abs() < 1e-9);
     }
 
+    #[test]
+    fn units_convert_and_round_consistently() {
+        use SummaryUnits::*;
+        // (numer, denom) → (percent, ppm, fraction) as written to summary.tsv
+        let cases = [
+            ((0, 0), ("0.000000", "0.00", "0.00000000")),
+            ((5, 0), ("0.000000", "0.00", "0.00000000")),
+            ((0, 1_000), ("0.000000", "0.00", "0.00000000")),
+            // 0.0005%: the NUMT fraction of a large, NUMT-poor genome
+            ((5, 1_000_000), ("0.000500", "5.00", "0.00000500")),
+            ((1, 3_000_000_000), ("0.000000", "0.00", "0.00000000")),
+            ((7, 300_000_000), ("0.000002", "0.02", "0.00000002")),
+            ((1, 3), ("33.333333", "333333.33", "0.33333333")),
+            ((1, 1), ("100.000000", "1000000.00", "1.00000000")),
+        ];
+        for ((n, d), (percent, ppm, fraction)) in cases {
+            let p = pct(n, d);
+            assert_eq!(Percent.format(p), percent, "{n}/{d}");
+            assert_eq!(Ppm.format(p), ppm, "{n}/{d}");
+            assert_eq!(Fraction.format(p), fraction, "{n}/{d}");
+        }
+        let p = pct(5, 1_000_000);
+        assert_eq!(Percent.convert(p), p);
+        assert!((Ppm.convert(p) - 5.0).abs() < 1e-9);
+        assert!((Fraction.convert(p) - 5e-6).abs() < 1e-15);
+
+        assert_eq!(Percent.metric_name("nuclear_pct_numt"), "nuclear_pct_numt");
+        assert_eq!(Ppm.metric_name("nuclear_pct_numt"), "nuclear_numt_ppm");
+        assert_eq!(
+            Fraction.metric_name("mito_pct_covered_by_numt_homologs"),
+            "mito_covered_by_numt_homologs_frac"
+        );
+    }
+
+    #[test]
+    fn summary_files_in_each_unit() {
+        let mut b = SummaryBuilder::default();
+        let locus = PairedLocus {
+            pair_id: "P1".into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: 0,
+            nuc_end: 50,
+            mito_contig: "m1".into(),
+            mito_start: 0,
+            mito_end: 50,
+            aln_len: 50,
+            aln_ident: 0.9,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        b.add(&locus, "Likely_NUMT");
+        let s = b.finish(16_000, 10_000_000);
+        let td = tempfile::tempdir().unwrap();
+        let read = |units: SummaryUnits| {
+            let tsv = td.path().join("summary.tsv");
+            let json = td.path().join("summary.json");
+            write_summary_tsv(&tsv, &s, units).unwrap();
+            write_summary_json(&json, &s, units).unwrap();
+            let v: serde_json::Value =
+                serde_json::from_str(&fs_err::read_to_string(json).unwrap()).unwrap();
+            (fs_err::read_to_string(tsv).unwrap(), v)
+        };
+
+        let (tsv, json) = read(SummaryUnits::Percent);
+        assert!(tsv.contains("\nnuclear_pct_numt\t0.000500\n"), "{tsv}");
+        assert_eq!(json, serde_json::to_value(&s).unwrap());
+        assert!(json.get("units").is_none());
+
+        let (tsv, json) = read(SummaryUnits::Ppm);
+        assert!(tsv.contains("\nnuclear_numt_ppm\t5.00\n"), "{tsv}");
+        assert!(tsv.contains("\nmito_covered_by_numt_homologs_ppm\t3125.00\n"));
+        assert!(!tsv.contains("_pct_"), "{tsv}");
+        // the other rows, and their order, are those of percent
+        let names = |t: &str| {
+            t.lines()
+                .map(|l| l.split('\t').next().unwrap().replace("_pct", ""))
+                .collect::<Vec<_>>()
+        };
+        let (percent_tsv, _) = read(SummaryUnits::Percent);
+        assert_eq!(
+            names(&tsv)
+                .iter()
+                .map(|n| n.trim_end_matches("_ppm"))
+                .collect::<Vec<_>>(),
+            names(&percent_tsv)
+        );
+        assert_eq!(json["units"], "ppm");
+        assert!((json["nuclear_numt_ppm"].as_f64().unwrap() - 5.0).abs() < 1e-9);
+        assert!(json.get("nuclear_pct_numt").is_none());
+        assert_eq!(json["nuclear_bp_numt"], 50);
+
+        let (tsv, json) = read(SummaryUnits::Fraction);
+        assert!(
+            tsv.contains("\nnuclear_numt_net_frac\t0.00000500\n"),
+            "{tsv}"
+        );
+        assert_eq!(json["units"], "fraction");
+        assert_eq!(
+            json.as_object()
+                .unwrap()
+                .keys()
+                .filter(|k| k.ends_with("_frac"))
+                .count(),
+            8
+        );
+        // the Summary itself stays in percent
+        assert!((s.nuclear_pct_numt - 0.0005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn nimt_split_by_molecule_type() {
+        let meta = MitoContigMeta::parse("pl1\tplasmid\tno\n").unwrap();
+        let lens: BTreeMap<String, u64> =
+            [("mt".to_string(), 16_000), ("pl1".to_string(), 4_000)].into();
+        let nimt = |mito_contig: &str, start: u32| PairedLocus {
+            pair_id: format!("{mito_contig}{start}"),
+            nuc_contig: "chr1".into(),
+            nuc_start: 0,
+            nuc_end: 400,
+            mito_contig: mito_contig.into(),
+            mito_start: start,
+            mito_end: start + 400,
+            aln_len: 400,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let mut b = SummaryBuilder::default();
+        b.add(&nimt("mt", 0), "Likely_NIMT");
+        b.add(&nimt("pl1", 0), "Likely_NIMT");
+        b.add(&nimt("pl1", 200), "Likely_NIMT");
+        let by_type = b.nimt_by_molecule_type(&meta, &lens);
+        let s = b
+            .finish(20_000, 1_000_000)
+            .with_nimt_by_molecule_type(by_type);
+
+        let pl = &s.nimt_by_molecule_type["plasmid"];
+        assert_eq!(
+            (pl.n_nimt, pl.mito_bp_total, pl.mito_bp_nimt),
+            (2, 4_000, 600)
+        );
+        assert!((pl.mito_pct_nimt - 15.0).abs() < 1e-9);
+        let mt = &s.nimt_by_molecule_type["mito"];
+        assert_eq!(
+            (mt.n_nimt, mt.mito_bp_total, mt.mito_bp_nimt),
+            (1, 16_000, 400)
+        );
+        // the totals still cover both
+        assert_eq!((s.n_nimt, s.mito_bp_nimt), (3, 1_000));
+
+        let td = tempfile::tempdir().unwrap();
+        let (tsv, json) = (td.path().join("s.tsv"), td.path().join("s.json"));
+        write_summary_tsv(&tsv, &s, SummaryUnits::Percent).unwrap();
+        let tsv = fs_err::read_to_string(tsv).unwrap();
+        assert!(
+            tsv.contains("\nn_nimt_mito\t1\nmito_bp_total_mito\t16000\n"),
+            "{tsv}"
+        );
+        assert!(tsv.contains("\nmito_bp_nimt_plasmid\t600\nmito_pct_nimt_plasmid\t15.000000\n"));
+        write_summary_json(&json, &s, SummaryUnits::Ppm).unwrap();
+        let v: serde_json::Value =
+            serde_json::from_str(&fs_err::read_to_string(json).unwrap()).unwrap();
+        let pl = &v["nimt_by_molecule_type"]["plasmid"];
+        assert!((pl["mito_nimt_ppm"].as_f64().unwrap() - 150_000.0).abs() < 1e-6);
+        assert!(pl.get("mito_pct_nimt").is_none());
+
+        // without metadata nothing is split
+        let s = SummaryBuilder::default().finish(20_000, 1_000_000);
+        assert!(serde_json::to_value(&s)
+            .unwrap()
+            .get("nimt_by_molecule_type")
+            .is_none());
+    }
+
     #[test]
     fn add_and_union_per_contig() {
         let mut m: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
@@ -296,7 +1453,7 @@ mod tests {
     #[test]
     fn small_summary_counts() {
         // Three loci: one NUMT, one NIMT, one ambiguous
-        let pairs = vec![
+        let pairs = [
             PairedLocus {
                 pair_id: "P1".into(),
                 nuc_contig: "chr1".into(),
@@ -307,6 +1464,14 @@ mod tests {
                 mito_end: 300,
                 aln_len: 200,
                 aln_ident: 0.99,
+                strand: Strand::Forward,
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: true,
+                parent_id: None,
+                rbh: None,
             },
             PairedLocus {
                 pair_id: "P2".into(),
@@ -318,6 +1483,14 @@ mod tests {
                 mito_end: 450,
                 aln_len: 100,
                 aln_ident: 0.95,
+                strand: Strand::Forward,
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: true,
+                parent_id: None,
+                rbh: None,
             },
             PairedLocus {
                 pair_id: "P3".into(),
@@ -329,6 +1502,14 @@ mod tests {
                 mito_end: 900,
                 aln_len: 100,
                 aln_ident: 0.90,
+                strand: Strand::Forward,
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: true,
+                parent_id: None,
+                rbh: None,
             },
         ];
         let calls: HashMap<_, _> = [
@@ -340,8 +1521,53 @@ mod tests {
         .collect();
 
         // Fake assembly sizes: mito=1000, chr1=1000, chr2=1000
-        // We can’t read real FASTA here; instead exercise the interval logic.
-        // So we only test the union logic directly:
+        let mut mito_fa = tempfile::NamedTempFile::new().unwrap();
+        let mut nuc_fa = tempfile::NamedTempFile::new().unwrap();
+        writeln!(mito_fa, ">m1\n{}", "A".repeat(1000)).unwrap();
+        writeln!(
+            nuc_fa,
+            ">chr1\n{}\n>chr2\n{}",
+            "C".repeat(1000),
+            "G".repeat(1000)
+        )
+        .unwrap();
+        let s = compute_percentages(mito_fa.path(), nuc_fa.path(), &pairs, &calls).unwrap();
+        assert_eq!((s.n_pairs, s.n_numt, s.n_nimt), (3, 1, 1));
+        assert_eq!(s.nuclear_bp_total, 2000);
+        assert_eq!(s.nuclear_bp_numt, 100);
+        assert_eq!(s.mito_bp_nimt, 50);
+        assert_eq!(s.mito_bp_covered_by_numt_homologs, 200);
+        assert_eq!(s.nuc_bp_covered_by_nimt_homologs, 100);
+        assert_eq!(s.numt_identity.n, 1);
+        assert_eq!(s.numt_identity.hist_string(), "99:1");
+        assert_eq!(s.nimt_identity.aln_bp, 100);
+
+        // Known sizes give the same summary without the FASTAs, with or
+        // without the per-contig lengths
+        let sizes =
+            GenomeSizes::read(&LengthCache::default(), mito_fa.path(), nuc_fa.path()).unwrap();
+        assert_eq!((sizes.mito_bp_total, sizes.nuclear_bp_total), (1000, 2000));
+        assert_eq!(sizes.totals(&["chr2".to_string()]), (1000, 1000));
+        let json = |s: &Summary| serde_json::to_value(s).unwrap();
+        for lens in [Some(&sizes.contigs), None] {
+            let known = compute_percentages_with_sizes(1000, 2000, lens, &pairs, &calls).unwrap();
+            assert_eq!(json(&known), json(&s));
+        }
+        // …and the lengths catch sizes from another assembly
+        let mut other = sizes.contigs.clone();
+        other.nuclear.remove("chr2");
+        other.mito.insert("m1".into(), 250);
+        let e = compute_percentages_with_sizes(1000, 2000, Some(&other), &pairs, &calls)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(e, "P1: ends at 300, past the end of m1 (250 bp)");
+        other.mito.insert("m1".into(), 1000);
+        let e = compute_percentages_with_sizes(1000, 2000, Some(&other), &pairs, &calls)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(e, "P3: chr2 is not a contig of the nuclear assembly");
+
+        // And the union logic directly:
         let mut mito_from_numt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
         let mut nuc_from_nimt: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
 
@@ -353,4 +1579,478 @@ mod tests {
         assert_eq!(union_len_all(&mito_from_numt), 200);
         assert_eq!(union_len_all(&nuc_from_nimt), 100);
     }
+
+    #[test]
+    fn collapsed_repeat_numts_counted_separately() {
+        let locus = |pid: &str, start: u32, end: u32| PairedLocus {
+            pair_id: pid.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: start,
+            nuc_end: end,
+            mito_contig: "m1".into(),
+            mito_start: 0,
+            mito_end: 100,
+            aln_len: end - start,
+            aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let mut b = SummaryBuilder::default();
+        let collapsed = LocusFlags {
+            collapsed_repeat: true,
+            ..Default::default()
+        };
+        b.add(&locus("P1", 0, 100), "Likely_NUMT");
+        b.add_flagged(&locus("P2", 50, 250), "Likely_NUMT", collapsed);
+        b.add_flagged(&locus("P3", 400, 500), "Likely_NUMT", collapsed);
+        b.add_flagged(&locus("P4", 600, 700), "Likely_NIMT", collapsed);
+        let numt_intervals = b.numt_nuclear_intervals();
+        let s = b.finish(1000, 1000);
+        assert_eq!(s.n_numt, 3);
+        assert_eq!(s.n_numt_collapsed_repeat, 2);
+        // liberal: [0,250) + [400,500); conservative: [0,100) only
+        assert_eq!(s.nuclear_bp_numt, 350);
+        assert_eq!(s.nuclear_bp_numt_collapsed_repeat, 300);
+        assert_eq!(s.nuclear_bp_numt_conservative, 100);
+        assert!((s.nuclear_pct_numt_conservative - 10.0).abs() < 1e-9);
+
+        // Ns are counted once where NUMT loci overlap; gross stays as it was
+        let gaps = crate::io::fasta::GapIndex::from_runs(
+            [("chr1".to_string(), vec![(40, 60), (200, 260), (650, 700)])].into(),
+        );
+        let n_bp = gaps.n_bases_in(&numt_intervals);
+        assert_eq!(n_bp, 20 + 50);
+        let s = s.with_numt_gap_bp(n_bp);
+        assert_eq!((s.nuclear_bp_numt, s.nuclear_bp_numt_net), (350, 280));
+        assert_eq!(s.nuclear_bp_numt_n, 70);
+        assert!((s.nuclear_pct_numt_net - 28.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shared_support_reported_apart_unless_included() {
+        let locus = |pid: &str, start: u32, end: u32| PairedLocus {
+            pair_id: pid.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: start,
+            nuc_end: end,
+            mito_contig: "m1".into(),
+            mito_start: start / 10,
+            mito_end: end / 10,
+            aln_len: end - start,
+            aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let summary = |included: bool| {
+            let mut b = SummaryBuilder::default().with_shared_in_percentages(included);
+            b.add(&locus("P1", 0, 100), "Likely_NUMT");
+            b.add(&locus("P2", 200, 500), "Shared_Support");
+            b.add(&locus("P3", 600, 700), "Ambiguous");
+            b.finish(100, 1000)
+        };
+
+        let s = summary(false);
+        assert_eq!((s.n_numt, s.n_nimt, s.n_shared), (1, 0, 1));
+        assert_eq!((s.nuclear_bp_shared, s.mito_bp_shared), (300, 30));
+        assert!((s.nuclear_pct_shared - 30.0).abs() < 1e-9);
+        assert!((s.mito_pct_shared - 30.0).abs() < 1e-9);
+        assert_eq!((s.nuclear_bp_numt, s.mito_bp_nimt), (100, 0));
+        assert!(!s.shared_in_percentages);
+
+        // included: their bp count on both sides, the call counts stay apart
+        let s = summary(true);
+        assert_eq!((s.n_numt, s.n_nimt, s.n_shared), (1, 0, 1));
+        assert_eq!((s.nuclear_bp_numt, s.mito_bp_nimt), (400, 30));
+        assert_eq!(s.nuclear_bp_numt_conservative, 400);
+        assert_eq!(s.numt_identity.n, 1);
+        assert!(s.shared_in_percentages);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let p = dir.path().join("summary.tsv");
+        write_summary_tsv(&p, &s, SummaryUnits::Percent).unwrap();
+        let tsv = fs_err::read_to_string(&p).unwrap();
+        assert!(tsv.contains("\nn_shared\t1\n"), "{tsv}");
+        assert!(tsv.contains("\nnuclear_pct_shared\t30.000000\n"), "{tsv}");
+        assert!(tsv.contains("\nshared_in_percentages\ttrue\n"), "{tsv}");
+    }
+
+    #[test]
+    fn control_region_calls_counted() {
+        let locus = |pid: &str, start: u32, end: u32| PairedLocus {
+            pair_id: pid.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: start * 10,
+            nuc_end: end * 10,
+            mito_contig: "m1".into(),
+            mito_start: start,
+            mito_end: end,
+            aln_len: end - start,
+            aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        };
+        let mut b = SummaryBuilder::default();
+        for (p, call) in [
+            (locus("P1", 0, 100), "Likely_NUMT"),
+            (locus("P2", 50, 150), "Likely_NIMT"),
+            (locus("P3", 300, 400), "Ambiguous"),
+            (locus("P4", 500, 600), "Likely_NUMT"),
+        ] {
+            let flags = LocusFlags {
+                control_region: p.pair_id != "P4",
+                ..Default::default()
+            };
+            b.add_flagged(&p, call, flags);
+        }
+        let s = b.finish(1000, 10_000);
+        // the ambiguous call is not counted, the overlap only once
+        assert_eq!((s.n_control_region, s.mito_bp_control_region), (2, 150));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let p = dir.path().join("summary.tsv");
+        write_summary_tsv(&p, &s, SummaryUnits::Percent).unwrap();
+        let tsv = fs_err::read_to_string(&p).unwrap();
+        assert!(
+            tsv.contains("\nn_control_region\t2\nmito_bp_control_region\t150\n"),
+            "{tsv}"
+        );
+    }
+
+    #[test]
+    fn calls_counted_by_reciprocal_support() {
+        let locus = |pid: &str, start: u32, reciprocal: bool| PairedLocus {
+            pair_id: pid.into(),
+            nuc_contig: "chr1".into(),
+            nuc_start: start,
+            nuc_end: start + 100,
+            mito_contig: "m1".into(),
+            mito_start: start,
+            mito_end: start + 100,
+            aln_len: 100,
+            aln_ident: 0.98,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal,
+            parent_id: None,
+            rbh: None,
+        };
+        let mut b = SummaryBuilder::default();
+        for (p, call) in [
+            (locus("P1", 0, true), "Likely_NUMT"),
+            (locus("P2", 200, false), "Likely_NIMT"),
+            (locus("P3", 400, false), "Ambiguous"),
+            (locus("P4", 600, true), "Likely_NIMT"),
+        ] {
+            b.add(&p, call);
+        }
+        let s = b.finish(1000, 10_000);
+        // only NUMT and NIMT calls count
+        assert_eq!((s.n_calls_reciprocal, s.n_calls_one_sided), (2, 1));
+        let tsv = summary_tsv(&s, SummaryUnits::Percent).unwrap();
+        assert!(
+            tsv.contains("\nn_calls_reciprocal\t2\nn_calls_one_sided\t1\n"),
+            "{tsv}"
+        );
+    }
+
+    #[test]
+    fn identity_stats_hand_computed() {
+        // 1 kb at 90%, 3 kb at 98%, 1 kb at 99%
+        let st = IdentityStats::from_pairs(&[(0.90, 1000), (0.98, 3000), (0.99, 1000)]);
+        assert_eq!(st.n, 3);
+        assert_eq!(st.aln_bp, 5000);
+        // (0.90 + 3*0.98 + 0.99) / 5 = 0.966
+        assert!((st.wmean - 0.966).abs() < 1e-6);
+        assert!((st.median - 0.98).abs() < 1e-6);
+        assert_eq!(st.hist_string(), "90:1,98:1,99:1");
+
+        let empty = IdentityStats::from_pairs(&[]);
+        assert_eq!((empty.n, empty.wmean, empty.median), (0, 0.0, 0.0));
+        assert_eq!(empty.hist_string(), "");
+    }
+
+    #[test]
+    fn compaction_preserves_union() {
+        let mut b = IntervalBuckets::default();
+        let mut plain: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        // many overlapping intervals force several compactions
+        for i in 0..10_000u32 {
+            let (s, e) = ((i * 37) % 50_000, (i * 37) % 50_000 + 25);
+            b.add("chr1", s, e);
+            add_interval(&mut plain, "chr1", s, e);
+        }
+        assert!(b.by_contig["chr1"].len() < 10_000);
+        assert_eq!(b.union_len(), union_len_all(&plain));
+    }
+
+    /// A mix of every call, flag and side: 40 loci with their calls and flags.
+    fn golden_loci() -> impl Iterator<Item = (PairedLocus, &'static str, LocusFlags)> {
+        let calls = [
+            "Likely_NUMT",
+            "Likely_NIMT",
+            "Shared_Support",
+            "Ambiguous",
+            "Likely_NUMT",
+        ];
+        (0..40u32).map(move |i| {
+            let p = PairedLocus {
+                pair_id: format!("P{i}"),
+                nuc_contig: if i % 2 == 0 { "chr1" } else { "chr2" }.into(),
+                nuc_start: i * 70,
+                nuc_end: i * 70 + 100 + 13 * (i % 3),
+                mito_contig: "m1".into(),
+                mito_start: (i * 53) % 900,
+                mito_end: (i * 53) % 900 + 60 + 7 * (i % 4),
+                aln_len: 100 + i,
+                aln_ident: 0.85 + 0.01 * (i % 14) as f32,
+                strand: Strand::Forward,
+                n_support_alignments: 1,
+                aln_ident_kind: IdentityKind::Blast,
+                nuc_start_ci: 0,
+                nuc_end_ci: 0,
+                reciprocal: i % 5 != 1,
+                parent_id: None,
+                rbh: None,
+            };
+            let flags = LocusFlags {
+                collapsed_repeat: i % 3 == 0,
+                high_confidence: i % 4 == 0,
+                control_region: i % 7 == 0,
+            };
+            (p, calls[(i % 5) as usize], flags)
+        })
+    }
+
+    /// `golden_loci`, shared with `shared`.
+    fn golden_builder(shared: bool) -> SummaryBuilder {
+        let mut b = SummaryBuilder::default().with_shared_in_percentages(shared);
+        for (p, call, flags) in golden_loci() {
+            b.add_flagged(&p, call, flags);
+        }
+        b
+    }
+
+    /// summary.tsv (in ppm) and summary.json of `golden_builder` before the
+    /// figures were declared as `MetricSpec`s.
+    const GOLDEN_TSV: &str = "\
+metric\tvalue\n\
+n_pairs\t40\n\
+n_numt\t16\n\
+n_nimt\t8\n\
+n_shared\t8\n\
+n_numt_regions\t0\n\
+n_regions\t0\n\
+nuclear_bp_total\t10000\n\
+nuclear_bp_numt\t1795\n\
+nuclear_numt_ppm\t179500.00\n\
+nuclear_bp_numt_n\t0\n\
+nuclear_bp_numt_net\t1795\n\
+nuclear_numt_net_ppm\t179500.00\n\
+nuclear_bp_numt_softmasked\t0\n\
+n_numt_collapsed_repeat\t6\n\
+nuclear_bp_numt_collapsed_repeat\t600\n\
+nuclear_bp_numt_conservative\t1195\n\
+nuclear_numt_conservative_ppm\t119500.00\n\
+mito_bp_total\t1000\n\
+mito_bp_nimt\t552\n\
+mito_nimt_ppm\t552000.00\n\
+mito_bp_covered_by_numt_homologs\t819\n\
+mito_covered_by_numt_homologs_ppm\t819000.00\n\
+nuc_bp_covered_by_nimt_homologs\t891\n\
+nuc_covered_by_nimt_homologs_ppm\t89100.00\n\
+nuclear_bp_shared\t917\n\
+nuclear_shared_ppm\t91700.00\n\
+mito_bp_shared\t545\n\
+mito_shared_ppm\t545000.00\n\
+shared_in_percentages\tfalse\n\
+n_control_region\t4\n\
+mito_bp_control_region\t277\n\
+n_calls_reciprocal\t16\n\
+n_calls_one_sided\t8\n\
+numt_ident_wmean\t0.9061\n\
+numt_ident_median\t0.9050\n\
+numt_ident_hist\t85:2,86:2,87:1,89:1,90:2,91:2,92:1,94:1,95:2,96:2\n\
+nimt_ident_wmean\t0.9136\n\
+nimt_ident_median\t0.9150\n\
+nimt_ident_hist\t86:1,87:1,88:1,91:1,92:1,93:1,96:1,97:1\n";
+    /// The rows that differ with `--shared-in-percentages`.
+    const GOLDEN_SHARED_ROWS: [(&str, &str); 10] = [
+        ("nuclear_bp_numt\t1795", "nuclear_bp_numt\t2712"),
+        ("nuclear_numt_ppm\t179500.00", "nuclear_numt_ppm\t271200.00"),
+        ("nuclear_bp_numt_net\t1795", "nuclear_bp_numt_net\t2712"),
+        (
+            "nuclear_numt_net_ppm\t179500.00",
+            "nuclear_numt_net_ppm\t271200.00",
+        ),
+        (
+            "nuclear_bp_numt_collapsed_repeat\t600",
+            "nuclear_bp_numt_collapsed_repeat\t800",
+        ),
+        (
+            "nuclear_bp_numt_conservative\t1195",
+            "nuclear_bp_numt_conservative\t1912",
+        ),
+        (
+            "nuclear_numt_conservative_ppm\t119500.00",
+            "nuclear_numt_conservative_ppm\t191200.00",
+        ),
+        ("mito_bp_nimt\t552", "mito_bp_nimt\t834"),
+        ("mito_nimt_ppm\t552000.00", "mito_nimt_ppm\t834000.00"),
+        (
+            "shared_in_percentages\tfalse",
+            "shared_in_percentages\ttrue",
+        ),
+    ];
+    const GOLDEN_JSON: [&str; 2] = [
+        r#"{"mito_bp_control_region":277,"mito_bp_covered_by_numt_homologs":819,"mito_bp_nimt":552,"mito_bp_shared":545,"mito_bp_total":1000,"mito_pct_covered_by_numt_homologs":81.9,"mito_pct_nimt":55.2,"mito_pct_shared":54.5,"n_calls_one_sided":8,"n_calls_reciprocal":16,"n_control_region":4,"n_nimt":8,"n_numt":16,"n_numt_collapsed_repeat":6,"n_numt_regions":0,"n_pairs":40,"n_regions":0,"n_shared":8,"nimt_identity":{"aln_bp":948,"hist":{"86":1,"87":1,"88":1,"91":1,"92":1,"93":1,"96":1,"97":1},"median":0.9150000214576721,"n":8,"wmean":0.913607610743257},"nuc_bp_covered_by_nimt_homologs":891,"nuc_pct_covered_by_nimt_homologs":8.91,"nuclear_bp_numt":1795,"nuclear_bp_numt_collapsed_repeat":600,"nuclear_bp_numt_conservative":1195,"nuclear_bp_numt_n":0,"nuclear_bp_numt_net":1795,"nuclear_bp_numt_softmasked":0,"nuclear_bp_shared":917,"nuclear_bp_total":10000,"nuclear_pct_numt":17.95,"nuclear_pct_numt_conservative":11.95,"nuclear_pct_numt_net":17.95,"nuclear_pct_shared":9.17,"numt_identity":{"aln_bp":1912,"hist":{"85":2,"86":2,"87":1,"89":1,"90":2,"91":2,"92":1,"94":1,"95":2,"96":2},"median":0.9050000309944153,"n":16,"wmean":0.9061401944654257},"shared_in_percentages":false}"#,
+        r#"{"mito_bp_control_region":277,"mito_bp_covered_by_numt_homologs":819,"mito_bp_nimt":834,"mito_bp_shared":545,"mito_bp_total":1000,"mito_pct_covered_by_numt_homologs":81.9,"mito_pct_nimt":83.4,"mito_pct_shared":54.5,"n_calls_one_sided":8,"n_calls_reciprocal":16,"n_control_region":4,"n_nimt":8,"n_numt":16,"n_numt_collapsed_repeat":6,"n_numt_regions":0,"n_pairs":40,"n_regions":0,"n_shared":8,"nimt_identity":{"aln_bp":948,"hist":{"86":1,"87":1,"88":1,"91":1,"92":1,"93":1,"96":1,"97":1},"median":0.9150000214576721,"n":8,"wmean":0.913607610743257},"nuc_bp_covered_by_nimt_homologs":891,"nuc_pct_covered_by_nimt_homologs":8.91,"nuclear_bp_numt":2712,"nuclear_bp_numt_collapsed_repeat":800,"nuclear_bp_numt_conservative":1912,"nuclear_bp_numt_n":0,"nuclear_bp_numt_net":2712,"nuclear_bp_numt_softmasked":0,"nuclear_bp_shared":917,"nuclear_bp_total":10000,"nuclear_pct_numt":27.12,"nuclear_pct_numt_conservative":19.12,"nuclear_pct_numt_net":27.12,"nuclear_pct_shared":9.17,"numt_identity":{"aln_bp":1912,"hist":{"85":2,"86":2,"87":1,"89":1,"90":2,"91":2,"92":1,"94":1,"95":2,"96":2},"median":0.9050000309944153,"n":16,"wmean":0.9061401944654257},"shared_in_percentages":true}"#,
+    ];
+
+    #[test]
+    fn declared_metrics_reproduce_the_golden_summary() {
+        for (shared, golden_json) in [false, true].into_iter().zip(GOLDEN_JSON) {
+            let s = golden_builder(shared).finish(1000, 10_000);
+            let tsv = summary_tsv(&s, SummaryUnits::Ppm).unwrap();
+            let mut json = summary_json(&s, SummaryUnits::Percent);
+            assert!(json.as_object_mut().unwrap().remove("strata").is_some());
+            assert!(json.as_object_mut().unwrap().remove("tiers").is_some());
+            assert_eq!(json.to_string(), golden_json);
+            // the strata and tier rows are new, the rest is as it was
+            let old: String = tsv
+                .lines()
+                .filter(|l| !l.contains("_high_confidence"))
+                .filter(|l| !l.starts_with("all_") && !l.starts_with("highconf_"))
+                .map(|l| format!("{l}\n"))
+                .collect();
+            let golden = if shared {
+                GOLDEN_SHARED_ROWS
+                    .iter()
+                    .fold(GOLDEN_TSV.to_string(), |t, (from, to)| {
+                        t.replace(&format!("\n{from}\n"), &format!("\n{to}\n"))
+                    })
+            } else {
+                GOLDEN_TSV.to_string()
+            };
+            assert_eq!(old, golden);
+        }
+    }
+
+    #[test]
+    fn high_confidence_stratum_from_its_spec() {
+        let s = golden_builder(true).finish(1000, 10_000);
+        // NUMT calls are i % 5 ∈ {0, 4}, NIMT i % 5 = 1, high confidence i % 4 = 0
+        let high = |r: u32| (0..40u32).filter(move |i| i % 5 == r && i % 4 == 0);
+        let numt: Vec<u32> = high(0).chain(high(4)).collect();
+        let nimt: Vec<u32> = high(1).collect();
+        let numt_bp: u64 = numt.iter().map(|i| 100 + 13 * (i % 3) as u64).sum();
+        let count = |name: &str| match s.strata[name] {
+            MetricValue::Count(n) => n,
+            MetricValue::Percent(_) => panic!("{name}"),
+        };
+        assert_eq!(count("n_numt_high_confidence"), numt.len() as u64);
+        assert_eq!(count("n_nimt_high_confidence"), nimt.len() as u64);
+        // 70 bp apart, so only loci two apart could overlap: none here
+        assert_eq!(count("nuclear_bp_numt_high_confidence"), numt_bp);
+        assert_eq!(
+            s.strata["nuclear_pct_numt_high_confidence"],
+            MetricValue::Percent(pct(numt_bp, 10_000))
+        );
+        // Shared_Support loci stay out of the stratum
+        assert!(count("nuclear_bp_numt_high_confidence") < s.nuclear_bp_numt);
+
+        let tsv = summary_tsv(&s, SummaryUnits::Ppm).unwrap();
+        assert!(tsv.contains(&format!(
+            "\nn_calls_one_sided\t{}\nn_numt_high_confidence\t{}\n",
+            s.n_calls_one_sided,
+            numt.len()
+        )));
+        assert!(tsv.contains(&format!(
+            "\nnuclear_numt_high_confidence_ppm\t{}\n",
+            SummaryUnits::Ppm.format(pct(numt_bp, 10_000))
+        )));
+        let json = summary_json(&s, SummaryUnits::Ppm);
+        assert!(json["strata"]["nuclear_numt_high_confidence_ppm"].is_f64());
+        assert_eq!(json["strata"]["n_numt_high_confidence"], numt.len());
+        // every declared metric is named once
+        let mut names: Vec<&str> = all_metrics()
+            .flat_map(|m| [Some(m.name), m.pct])
+            .flatten()
+            .collect();
+        let n = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), n);
+    }
+
+    #[test]
+    fn tiers_bound_the_figures_and_match_filtered_calls() {
+        for shared in [false, true] {
+            let s = golden_builder(shared).finish(1000, 10_000);
+            // the high tier recomputed from the high-confidence calls alone
+            let mut high = SummaryBuilder::default().with_shared_in_percentages(shared);
+            for (p, call, flags) in golden_loci().filter(|(_, _, f)| f.high_confidence) {
+                high.add_flagged(&p, call, flags);
+            }
+            let high = high.finish(1000, 10_000);
+            let want = serde_json::to_value(&high).unwrap();
+            let all = serde_json::to_value(&s).unwrap();
+            for name in FIELD_METRICS.iter().flat_map(metric_names) {
+                let (a, h) = (
+                    &s.tiers[&format!("all_{name}")],
+                    &s.tiers[&format!("highconf_{name}")],
+                );
+                assert_eq!(serde_json::to_value(a).unwrap(), all[name], "{name}");
+                assert_eq!(serde_json::to_value(h).unwrap(), want[name], "{name}");
+                match (a, h) {
+                    (MetricValue::Count(a), MetricValue::Count(h)) => assert!(h <= a, "{name}"),
+                    (MetricValue::Percent(a), MetricValue::Percent(h)) => {
+                        assert!(h <= a, "{name}")
+                    }
+                    _ => panic!("{name}: a count and a percentage"),
+                }
+            }
+            assert!(high.n_numt > 0 && high.n_numt < s.n_numt);
+            assert_eq!(
+                s.tiers["highconf_n_numt"],
+                s.strata["n_numt_high_confidence"]
+            );
+        }
+
+        let s = golden_builder(false).finish(1000, 10_000);
+        let tsv = summary_tsv(&s, SummaryUnits::Ppm).unwrap();
+        assert!(tsv.contains(&format!(
+            "\nall_n_numt\t{}\nall_n_nimt\t{}\n",
+            s.n_numt, s.n_nimt
+        )));
+        assert!(tsv.contains("\nall_nuclear_numt_ppm\t179500.00\n"), "{tsv}");
+        assert!(tsv.contains("\nhighconf_nuclear_numt_ppm\t"), "{tsv}");
+        // the all_ block, then the highconf_ one
+        let at = |row: &str| tsv.find(row).unwrap();
+        assert!(at("\nall_mito_bp_control_region\t") < at("\nhighconf_n_numt\t"));
+        let json = summary_json(&s, SummaryUnits::Ppm);
+        assert!(json["tiers"]["highconf_nuclear_numt_ppm"].is_f64());
+        assert!(json["tiers"].get("highconf_nuclear_pct_numt").is_none());
+        assert_eq!(json["tiers"]["all_n_numt"], s.n_numt);
+    }
 }
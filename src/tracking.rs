@@ -0,0 +1,510 @@
+//! Stable locus IDs across runs (`--track-against RUN_DIR`).
+//!
+//! `pair_id`s are assigned in pairing order, so they change whenever the
+//! candidate set does, and a boundary a few bp off changes any ID derived from
+//! coordinates. Instead each pair inherits the `stable_id` of the reference
+//! run's pair it overlaps best on the nuclear side (reciprocal overlap: shared
+//! bp over the longer interval, at least `min_overlap`). Matching is one to
+//! one: candidate matches are taken best overlap first, ties going to the
+//! earliest reference locus, then the earliest current one. A pair left
+//! unmatched is a new locus and gets a freshly minted ID; a reference pair
+//! left unmatched is retired.
+//!
+//! `tracking.tsv` lists every pair of the run, then the retired IDs, including
+//! those the reference run had already retired, so IDs are never minted twice.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::model::PairedLocus;
+use crate::scoring;
+
+pub const TRACKING_FILE: &str = "tracking.tsv";
+pub const TRACKING_HEADER: &str = "pair_id\tstable_id\tstatus\tref_pair_id\toverlap";
+
+/// Default `--track-min-overlap`.
+pub const DEFAULT_TRACK_MIN_OVERLAP: f32 = 0.5;
+
+const STABLE_ID_PREFIX: &str = "L";
+
+/// (start, end, index) sorted by start, and the longest interval.
+type ContigIntervals = (Vec<(u32, u32, usize)>, u32);
+
+/// Intervals by contig, for overlap queries.
+#[derive(Debug, Default)]
+pub struct IntervalIndex {
+    by_contig: HashMap<String, ContigIntervals>,
+}
+
+impl IntervalIndex {
+    /// Index the nuclear intervals of `loci`; queries return indices into it.
+    pub fn of_loci(loci: &[PairedLocus]) -> Self {
+        Self::of_intervals(
+            loci.iter()
+                .map(|l| (l.nuc_contig.as_str(), l.nuc_start, l.nuc_end)),
+        )
+    }
+
+    /// Index `(contig, start, end)` intervals; queries return their positions
+    /// in `intervals`.
+    pub fn of_intervals<'a>(intervals: impl IntoIterator<Item = (&'a str, u32, u32)>) -> Self {
+        let mut by_contig: HashMap<String, ContigIntervals> = HashMap::new();
+        for (i, (contig, start, end)) in intervals.into_iter().enumerate() {
+            let e = by_contig.entry(contig.to_string()).or_default();
+            e.0.push((start, end, i));
+            e.1 = e.1.max(end - start);
+        }
+        for (v, _) in by_contig.values_mut() {
+            v.sort_unstable();
+        }
+        Self { by_contig }
+    }
+
+    /// Indices of the intervals sharing at least one bp with `[start, end)`.
+    pub fn overlapping(&self, contig: &str, start: u32, end: u32) -> Vec<usize> {
+        let Some((v, longest)) = self.by_contig.get(contig) else {
+            return Vec::new();
+        };
+        // nothing starting before start - longest can reach start
+        let first = v.partition_point(|&(s, _, _)| s < start.saturating_sub(*longest));
+        v[first..]
+            .iter()
+            .take_while(|&&(s, _, _)| s < end)
+            .filter(|&&(_, e, _)| e > start)
+            .map(|&(_, _, i)| i)
+            .collect()
+    }
+}
+
+/// Shared bp over the longer of the two intervals (0 on different contigs).
+pub fn reciprocal_overlap(a: &PairedLocus, b: &PairedLocus) -> f32 {
+    if a.nuc_contig != b.nuc_contig {
+        return 0.0;
+    }
+    let shared = a
+        .nuc_end
+        .min(b.nuc_end)
+        .saturating_sub(a.nuc_start.max(b.nuc_start));
+    let longer = (a.nuc_end - a.nuc_start).max(b.nuc_end - b.nuc_start);
+    if longer == 0 {
+        return 0.0;
+    }
+    shared as f32 / longer as f32
+}
+
+/// For each current locus, the reference locus it inherits from and their
+/// overlap. One to one; see the module docs for the order.
+pub fn match_loci(
+    reference: &[PairedLocus],
+    current: &[PairedLocus],
+    min_overlap: f32,
+) -> Vec<Option<(usize, f32)>> {
+    let index = IntervalIndex::of_loci(reference);
+    let mut candidates: Vec<(f32, usize, usize)> = Vec::new();
+    for (c, l) in current.iter().enumerate() {
+        for r in index.overlapping(&l.nuc_contig, l.nuc_start, l.nuc_end) {
+            let ov = reciprocal_overlap(&reference[r], l);
+            if ov >= min_overlap && ov > 0.0 {
+                candidates.push((ov, r, c));
+            }
+        }
+    }
+    let pos = |l: &PairedLocus| (l.nuc_contig.clone(), l.nuc_start, l.nuc_end);
+    candidates.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| pos(&reference[a.1]).cmp(&pos(&reference[b.1])))
+            .then(a.1.cmp(&b.1))
+            .then_with(|| pos(&current[a.2]).cmp(&pos(&current[b.2])))
+            .then(a.2.cmp(&b.2))
+    });
+    let mut out = vec![None; current.len()];
+    let mut taken = vec![false; reference.len()];
+    for (ov, r, c) in candidates {
+        if out[c].is_none() && !taken[r] {
+            out[c] = Some((r, ov));
+            taken[r] = true;
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackStatus {
+    /// Inherited from an overlapping reference pair.
+    Matched,
+    /// No reference pair overlaps enough: a new ID.
+    New,
+    /// A reference ID no pair of this run inherited.
+    Retired,
+}
+
+impl TrackStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrackStatus::Matched => "matched",
+            TrackStatus::New => "new",
+            TrackStatus::Retired => "retired",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "matched" => Some(TrackStatus::Matched),
+            "new" => Some(TrackStatus::New),
+            "retired" => Some(TrackStatus::Retired),
+            _ => None,
+        }
+    }
+}
+
+/// One tracking.tsv row. Retired rows have no `pair_id`; IDs the reference
+/// had already retired also have no `ref_pair_id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingRow {
+    pub pair_id: Option<String>,
+    pub stable_id: String,
+    pub status: TrackStatus,
+    pub ref_pair_id: Option<String>,
+    pub overlap: Option<f32>,
+}
+
+/// Stable IDs of a reference run: its tracking.tsv, or for an untracked run
+/// IDs numbered like its pair_ids.
+#[derive(Debug, Default)]
+pub struct ReferenceIds {
+    /// pair_id → stable_id
+    pub by_pair: HashMap<String, String>,
+    /// Retired earlier; carried forward so they are not minted again.
+    pub retired: Vec<String>,
+}
+
+impl ReferenceIds {
+    /// From tracking.tsv text.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut ids = Self::default();
+        for (i, line) in text.lines().enumerate().skip(1) {
+            let f: Vec<&str> = line.split('\t').collect();
+            let status = f.get(2).and_then(|s| TrackStatus::parse(s));
+            let (Some(&pair_id), Some(&stable_id), Some(status)) = (f.first(), f.get(1), status)
+            else {
+                return Err(anyhow!("{TRACKING_FILE} line {}: malformed row", i + 1));
+            };
+            if status == TrackStatus::Retired {
+                ids.retired.push(stable_id.to_string());
+            } else {
+                ids.by_pair
+                    .insert(pair_id.to_string(), stable_id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// tracking.tsv of `run_dir` if there is one, else IDs from the pair_ids.
+    pub fn load(run_dir: &Path, reference: &[PairedLocus]) -> Result<Self> {
+        let p = run_dir.join(TRACKING_FILE);
+        if p.exists() {
+            let ids = Self::parse(&fs_err::read_to_string(&p)?)?;
+            if let Some(l) = reference
+                .iter()
+                .find(|l| !ids.by_pair.contains_key(&l.pair_id))
+            {
+                return Err(anyhow!(
+                    "{} does not list {} of that run's pairs.tsv (files from different runs?)",
+                    p.display(),
+                    l.pair_id
+                ));
+            }
+            return Ok(ids);
+        }
+        let by_pair = reference
+            .iter()
+            .map(|l| {
+                let n = id_number(&l.pair_id).unwrap_or(0);
+                (l.pair_id.clone(), stable_id(n))
+            })
+            .collect();
+        Ok(Self {
+            by_pair,
+            retired: Vec::new(),
+        })
+    }
+}
+
+fn stable_id(n: u64) -> String {
+    format!("{STABLE_ID_PREFIX}{n:06}")
+}
+
+/// The number in `P000012` / `L000012`.
+fn id_number(id: &str) -> Option<u64> {
+    id.trim_start_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()
+}
+
+/// Tracking rows for `current` against `reference` (whose stable IDs are `ids`).
+pub fn track(
+    reference: &[PairedLocus],
+    ids: &ReferenceIds,
+    current: &[PairedLocus],
+    min_overlap: f32,
+) -> Vec<TrackingRow> {
+    let matches = match_loci(reference, current, min_overlap);
+    let mut next = ids
+        .by_pair
+        .values()
+        .chain(&ids.retired)
+        .filter_map(|s| id_number(s))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut inherited = vec![false; reference.len()];
+    let mut rows = Vec::with_capacity(current.len());
+    for (l, m) in current.iter().zip(&matches) {
+        let row = match *m {
+            Some((r, ov)) => {
+                inherited[r] = true;
+                TrackingRow {
+                    pair_id: Some(l.pair_id.clone()),
+                    stable_id: ids.by_pair[&reference[r].pair_id].clone(),
+                    status: TrackStatus::Matched,
+                    ref_pair_id: Some(reference[r].pair_id.clone()),
+                    overlap: Some(ov),
+                }
+            }
+            None => {
+                next += 1;
+                TrackingRow {
+                    pair_id: Some(l.pair_id.clone()),
+                    stable_id: stable_id(next - 1),
+                    status: TrackStatus::New,
+                    ref_pair_id: None,
+                    overlap: None,
+                }
+            }
+        };
+        rows.push(row);
+    }
+    for (r, _) in inherited.iter().enumerate().filter(|(_, &i)| !i) {
+        rows.push(TrackingRow {
+            pair_id: None,
+            stable_id: ids.by_pair[&reference[r].pair_id].clone(),
+            status: TrackStatus::Retired,
+            ref_pair_id: Some(reference[r].pair_id.clone()),
+            overlap: None,
+        });
+    }
+    rows.extend(ids.retired.iter().map(|s| TrackingRow {
+        pair_id: None,
+        stable_id: s.clone(),
+        status: TrackStatus::Retired,
+        ref_pair_id: None,
+        overlap: None,
+    }));
+    rows
+}
+
+pub fn tracking_tsv(rows: &[TrackingRow]) -> String {
+    let mut s = format!("{TRACKING_HEADER}\n");
+    for r in rows {
+        let _ = writeln!(
+            s,
+            "{}\t{}\t{}\t{}\t{}",
+            r.pair_id.as_deref().unwrap_or("."),
+            r.stable_id,
+            r.status.as_str(),
+            r.ref_pair_id.as_deref().unwrap_or("."),
+            r.overlap
+                .map(|o| format!("{o:.4}"))
+                .unwrap_or_else(|| ".".to_string())
+        );
+    }
+    s
+}
+
+/// The run given to `--track-against`, read when the run starts so a bad
+/// directory fails before any work is done.
+#[derive(Debug)]
+pub struct TrackingReference {
+    pub dir: PathBuf,
+    pub loci: Vec<PairedLocus>,
+    pub ids: ReferenceIds,
+}
+
+impl TrackingReference {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let loci: Vec<PairedLocus> = scoring::read_run_pairs(dir)
+            .with_context(|| format!("--track-against {}", dir.display()))?
+            .into_iter()
+            .map(|r| r.locus)
+            .collect();
+        let ids = ReferenceIds::load(dir, &loci)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            loci,
+            ids,
+        })
+    }
+
+    /// Track the pairs of the finished run in `out_dir`; writes tracking.tsv.
+    pub fn track_run(&self, out_dir: &Path, min_overlap: f32) -> Result<()> {
+        let current: Vec<PairedLocus> = scoring::read_run_pairs(out_dir)?
+            .into_iter()
+            .map(|r| r.locus)
+            .collect();
+        let rows = track(&self.loci, &self.ids, &current, min_overlap);
+        let out = out_dir.join(TRACKING_FILE);
+        fs_err::write(&out, tracking_tsv(&rows))?;
+        let n = |s: TrackStatus| {
+            rows.iter()
+                .filter(|r| r.status == s && (s != TrackStatus::Retired || r.ref_pair_id.is_some()))
+                .count()
+        };
+        log::info!(
+            "tracking against {}: {} pairs matched, {} new, {} retired → {}",
+            self.dir.display(),
+            n(TrackStatus::Matched),
+            n(TrackStatus::New),
+            n(TrackStatus::Retired),
+            out.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::paf::IdentityKind;
+    use crate::model::orientation::Strand;
+
+    fn locus(id: &str, contig: &str, start: u32, end: u32) -> PairedLocus {
+        PairedLocus {
+            pair_id: id.to_string(),
+            nuc_contig: contig.to_string(),
+            nuc_start: start,
+            nuc_end: end,
+            mito_contig: "mt".to_string(),
+            mito_start: 0,
+            mito_end: end - start,
+            aln_len: end - start,
+            aln_ident: 0.95,
+            strand: Strand::Forward,
+            n_support_alignments: 1,
+            aln_ident_kind: IdentityKind::Blast,
+            nuc_start_ci: 0,
+            nuc_end_ci: 0,
+            reciprocal: true,
+            parent_id: None,
+            rbh: None,
+        }
+    }
+
+    fn summary(rows: &[TrackingRow]) -> Vec<String> {
+        rows.iter()
+            .map(|r| {
+                format!(
+                    "{}:{}:{}",
+                    r.pair_id.as_deref().unwrap_or("."),
+                    r.stable_id,
+                    r.status.as_str()
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_finds_every_overlap() {
+        let loci = vec![
+            locus("P1", "chr1", 0, 10_000),
+            locus("P2", "chr1", 5_000, 5_100),
+            locus("P3", "chr1", 9_999, 12_000),
+            locus("P4", "chr2", 5_000, 6_000),
+        ];
+        let index = IntervalIndex::of_loci(&loci);
+        let mut hits = index.overlapping("chr1", 5_050, 9_999);
+        hits.sort_unstable();
+        assert_eq!(hits, [0, 1]);
+        assert_eq!(index.overlapping("chr1", 10_000, 10_001), [2]);
+        // abutting intervals share no bp
+        assert!(index.overlapping("chr1", 12_000, 13_000).is_empty());
+        assert!(index.overlapping("chr3", 0, 100).is_empty());
+    }
+
+    #[test]
+    fn jittered_split_and_merged_loci() {
+        let reference = vec![
+            locus("P000001", "chr1", 1_000, 2_000),
+            // split in two in the current run
+            locus("P000002", "chr1", 10_000, 12_000),
+            // merged into one in the current run
+            locus("P000003", "chr2", 0, 1_000),
+            locus("P000004", "chr2", 1_000, 1_400),
+            // gone
+            locus("P000005", "chr3", 0, 500),
+        ];
+        let current = vec![
+            // jittered by a bp on each side
+            locus("P000001", "chr1", 999, 2_001),
+            locus("P000002", "chr1", 10_000, 10_800),
+            locus("P000003", "chr1", 10_900, 12_000),
+            locus("P000004", "chr2", 0, 1_400),
+            locus("P000005", "chr4", 0, 500),
+        ];
+        let ids = ReferenceIds::load(Path::new("/nonexistent"), &reference).unwrap();
+        let rows = track(&reference, &ids, &current, DEFAULT_TRACK_MIN_OVERLAP);
+        assert_eq!(
+            summary(&rows),
+            [
+                "P000001:L000001:matched",
+                // 1100 of 2000 bp: the larger piece keeps the ID
+                "P000002:L000006:new",
+                "P000003:L000002:matched",
+                // the larger of the merged loci passes its ID on
+                "P000004:L000003:matched",
+                "P000005:L000007:new",
+                ".:L000004:retired",
+                ".:L000005:retired",
+            ]
+        );
+        assert_eq!(rows[0].overlap, Some(1_000.0 / 1_002.0));
+        assert_eq!(rows[2].ref_pair_id.as_deref(), Some("P000002"));
+
+        // a third run against the second: its retired IDs stay retired
+        let text = tracking_tsv(&rows);
+        let ids2 = ReferenceIds::parse(&text).unwrap();
+        let rows2 = track(&current, &ids2, &current[..1], DEFAULT_TRACK_MIN_OVERLAP);
+        assert_eq!(rows2.len(), 1 + 4 + 2);
+        assert_eq!(rows2[0].stable_id, "L000001");
+        assert!(rows2[5..]
+            .iter()
+            .all(|r| r.status == TrackStatus::Retired && r.ref_pair_id.is_none()));
+        let rows3 = track(&current, &ids2, &[locus("P000001", "chr9", 0, 10)], 0.5);
+        assert_eq!(rows3[0].stable_id, "L000008");
+    }
+
+    #[test]
+    fn ties_resolve_by_earliest_coordinate() {
+        // two reference loci overlap the current one equally
+        let reference = vec![
+            locus("P000001", "chr1", 1_100, 2_100),
+            locus("P000002", "chr1", 900, 1_900),
+        ];
+        let current = vec![locus("P000001", "chr1", 1_000, 2_000)];
+        let ids = ReferenceIds::load(Path::new("/nonexistent"), &reference).unwrap();
+        for r in [reference.clone(), reference.iter().rev().cloned().collect()] {
+            let m = match_loci(&r, &current, 0.5);
+            let (i, ov) = m[0].unwrap();
+            assert_eq!((r[i].pair_id.as_str(), ov), ("P000002", 0.9));
+        }
+        let rows = track(&reference, &ids, &current, 0.5);
+        assert_eq!(
+            summary(&rows),
+            ["P000001:L000002:matched", ".:L000001:retired"]
+        );
+        // below the threshold nothing matches
+        let m = match_loci(&reference, &current, 0.95);
+        assert_eq!(m, [None]);
+    }
+}
@@ -0,0 +1,337 @@
+//! CIGAR strings (SAM column 6, PAF `cg:Z`): a validated parser and the
+//! lengths and blocks derived from it.
+//!
+//! Parsing rejects what a saturating digit loop would let through: an empty
+//! or `*` CIGAR, an op without a length (or a length without an op), unknown
+//! ops, and run lengths that do not fit in a `u32`. An overlong digit run
+//! fails at its eleventh digit, however long the input.
+
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `M`: aligned, match or mismatch.
+    Match,
+    /// `I`
+    Ins,
+    /// `D`
+    Del,
+    /// `N`: skipped reference (a splice).
+    Skip,
+    /// `S`
+    SoftClip,
+    /// `H`
+    HardClip,
+    /// `P`
+    Pad,
+    /// `=`
+    Equal,
+    /// `X`
+    Diff,
+}
+
+impl Op {
+    fn from_byte(b: u8) -> Option<Self> {
+        Some(match b {
+            b'M' => Op::Match,
+            b'I' => Op::Ins,
+            b'D' => Op::Del,
+            b'N' => Op::Skip,
+            b'S' => Op::SoftClip,
+            b'H' => Op::HardClip,
+            b'P' => Op::Pad,
+            b'=' => Op::Equal,
+            b'X' => Op::Diff,
+            _ => return None,
+        })
+    }
+
+    pub fn as_char(self) -> char {
+        match self {
+            Op::Match => 'M',
+            Op::Ins => 'I',
+            Op::Del => 'D',
+            Op::Skip => 'N',
+            Op::SoftClip => 'S',
+            Op::HardClip => 'H',
+            Op::Pad => 'P',
+            Op::Equal => '=',
+            Op::Diff => 'X',
+        }
+    }
+
+    pub fn consumes_ref(self) -> bool {
+        matches!(self, Op::Match | Op::Del | Op::Skip | Op::Equal | Op::Diff)
+    }
+
+    pub fn consumes_query(self) -> bool {
+        matches!(
+            self,
+            Op::Match | Op::Ins | Op::SoftClip | Op::Equal | Op::Diff
+        )
+    }
+
+    fn is_aligned(self) -> bool {
+        matches!(self, Op::Match | Op::Equal | Op::Diff)
+    }
+
+    fn is_clip(self) -> bool {
+        matches!(self, Op::SoftClip | Op::HardClip)
+    }
+}
+
+/// One M/=/X run: offsets from the alignment start on the reference and from
+/// the first query base the CIGAR covers (soft clips included).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefBlock {
+    pub ref_offset: u64,
+    pub query_offset: u64,
+    pub len: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cigar {
+    ops: Vec<(Op, u32)>,
+}
+
+impl Cigar {
+    pub fn parse(cigar: &[u8]) -> Result<Self> {
+        let show = || String::from_utf8_lossy(&cigar[..cigar.len().min(40)]).into_owned();
+        if cigar.is_empty() || cigar == b"*" {
+            return Err(anyhow!("empty CIGAR"));
+        }
+        let mut ops = Vec::new();
+        let mut len: Option<u32> = None;
+        for &b in cigar {
+            if b.is_ascii_digit() {
+                let n = len
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add((b - b'0') as u32))
+                    .ok_or_else(|| anyhow!("CIGAR length overflows in {}", show()))?;
+                len = Some(n);
+                continue;
+            }
+            let op = Op::from_byte(b).ok_or_else(|| {
+                anyhow!(
+                    "unsupported CIGAR operation '{}' in {}",
+                    b.escape_ascii(),
+                    show()
+                )
+            })?;
+            let n = len.take().ok_or_else(|| {
+                anyhow!(
+                    "CIGAR operation '{}' without a length in {}",
+                    op.as_char(),
+                    show()
+                )
+            })?;
+            ops.push((op, n));
+        }
+        if len.is_some() {
+            return Err(anyhow!("CIGAR ends in a bare length: {}", show()));
+        }
+        Ok(Self { ops })
+    }
+
+    pub fn ops(&self) -> &[(Op, u32)] {
+        &self.ops
+    }
+
+    /// Reference bases covered (M, =, X, D, N).
+    pub fn ref_consumed(&self) -> u64 {
+        self.sum(Op::consumes_ref)
+    }
+
+    /// Query bases covered (M, =, X, I, S).
+    pub fn query_consumed(&self) -> u64 {
+        self.sum(Op::consumes_query)
+    }
+
+    /// Aligned columns (M, =, X): matches plus mismatches.
+    pub fn aligned(&self) -> u64 {
+        self.sum(Op::is_aligned)
+    }
+
+    /// Gap openings: the I and D runs, however long.
+    pub fn gap_opens(&self) -> u64 {
+        self.ops
+            .iter()
+            .filter(|(op, _)| matches!(op, Op::Ins | Op::Del))
+            .count() as u64
+    }
+
+    /// Soft- plus hard-clipped bases before the first non-clip op.
+    pub fn leading_clip(&self) -> u64 {
+        Self::clip(self.ops.iter())
+    }
+
+    /// Soft- plus hard-clipped bases after the last non-clip op.
+    pub fn trailing_clip(&self) -> u64 {
+        Self::clip(self.ops.iter().rev())
+    }
+
+    /// The M/=/X runs in order.
+    pub fn iter_ref_blocks(&self) -> impl Iterator<Item = RefBlock> + '_ {
+        let (mut r, mut q) = (0u64, 0u64);
+        self.ops.iter().filter_map(move |&(op, n)| {
+            let block = op.is_aligned().then_some(RefBlock {
+                ref_offset: r,
+                query_offset: q,
+                len: n,
+            });
+            if op.consumes_ref() {
+                r += n as u64;
+            }
+            if op.consumes_query() {
+                q += n as u64;
+            }
+            block
+        })
+    }
+
+    fn sum(&self, f: impl Fn(Op) -> bool) -> u64 {
+        self.ops
+            .iter()
+            .filter(|(op, _)| f(*op))
+            .map(|&(_, n)| n as u64)
+            .sum()
+    }
+
+    fn clip<'a>(ops: impl Iterator<Item = &'a (Op, u32)>) -> u64 {
+        ops.take_while(|(op, _)| op.is_clip())
+            .map(|&(_, n)| n as u64)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Cigar> {
+        Cigar::parse(s.as_bytes())
+    }
+
+    #[test]
+    fn lengths_and_clips() {
+        let c = parse("5H10S50M10I40M5D45M100N5M3S").unwrap();
+        assert_eq!(c.ref_consumed(), 50 + 40 + 5 + 45 + 100 + 5);
+        assert_eq!(c.query_consumed(), 10 + 50 + 10 + 40 + 45 + 5 + 3);
+        assert_eq!((c.leading_clip(), c.trailing_clip()), (15, 3));
+        let blocks: Vec<_> = c
+            .iter_ref_blocks()
+            .map(|b| (b.ref_offset, b.query_offset, b.len))
+            .collect();
+        assert_eq!(
+            blocks,
+            [(0, 10, 50), (50, 70, 40), (95, 110, 45), (240, 155, 5)]
+        );
+        assert_eq!(parse("50=50X").unwrap().ref_consumed(), 100);
+        // N is a splice, not a gap
+        assert_eq!((c.aligned(), c.gap_opens()), (50 + 40 + 45 + 5, 2));
+    }
+
+    #[test]
+    fn malformed_cigars_rejected() {
+        for (bad, why) in [
+            ("", "empty"),
+            ("*", "empty"),
+            ("100", "bare length"),
+            ("M", "without a length"),
+            ("10M5", "bare length"),
+            ("50M5Q45M", "unsupported"),
+            ("10M\u{e9}", "unsupported"),
+            ("4294967296M", "overflows"),
+        ] {
+            let e = parse(bad).unwrap_err().to_string();
+            assert!(e.contains(why), "{bad:?}: {e}");
+        }
+        assert_eq!(parse("4294967295M").unwrap().ops(), [(Op::Match, u32::MAX)]);
+        // a huge digit run fails early, and the message stays short
+        let huge = "9".repeat(10_000_000) + "M";
+        let e = parse(&huge).unwrap_err().to_string();
+        assert!(e.contains("overflows") && e.len() < 100, "{}", e.len());
+    }
+
+    /// SplitMix64, for the random CIGARs below.
+    fn rng(mut state: u64) -> impl FnMut() -> u64 {
+        move || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+    }
+
+    #[test]
+    fn random_valid_cigars_are_consistent() {
+        const OPS: &[u8] = b"MIDNSHP=X";
+        let mut next = rng(7);
+        for _ in 0..2000 {
+            let n_ops = 1 + next() % 12;
+            let mut text = String::new();
+            let mut ops = Vec::new();
+            for _ in 0..n_ops {
+                let op = OPS[(next() % OPS.len() as u64) as usize];
+                let len = match next() % 4 {
+                    0 => next() % 10,
+                    1 => u32::MAX as u64 - next() % 3,
+                    _ => next() % 100_000,
+                } as u32;
+                text.push_str(&format!("{len}{}", op as char));
+                ops.push((Op::from_byte(op).unwrap(), len));
+            }
+            let c = parse(&text).unwrap();
+            assert_eq!(c.ops(), &ops[..], "{text}");
+
+            let blocks: Vec<RefBlock> = c.iter_ref_blocks().collect();
+            let aligned: u64 = blocks.iter().map(|b| b.len as u64).sum();
+            let (ref_gaps, query_gaps) = (
+                c.sum(|o| matches!(o, Op::Del | Op::Skip)),
+                c.sum(|o| matches!(o, Op::Ins | Op::SoftClip)),
+            );
+            assert_eq!(c.ref_consumed(), aligned + ref_gaps, "{text}");
+            assert_eq!(c.query_consumed(), aligned + query_gaps, "{text}");
+            assert!(c.leading_clip().max(c.trailing_clip()) <= c.sum(Op::is_clip));
+            for w in blocks.windows(2) {
+                assert!(
+                    w[1].ref_offset >= w[0].ref_offset + w[0].len as u64,
+                    "{text}"
+                );
+                assert!(
+                    w[1].query_offset >= w[0].query_offset + w[0].len as u64,
+                    "{text}"
+                );
+            }
+            if let Some(last) = blocks.last() {
+                assert!(last.ref_offset + last.len as u64 <= c.ref_consumed());
+                assert!(last.query_offset + last.len as u64 <= c.query_consumed());
+            }
+        }
+    }
+
+    #[test]
+    fn random_garbage_never_panics() {
+        let mut next = rng(11);
+        for _ in 0..5000 {
+            let n = (next() % 40) as usize;
+            let bytes: Vec<u8> = (0..n)
+                .map(|_| match next() % 3 {
+                    0 => b"0123456789"[(next() % 10) as usize],
+                    1 => b"MIDNSHP=X*"[(next() % 10) as usize],
+                    _ => next() as u8,
+                })
+                .collect();
+            if let Ok(c) = Cigar::parse(&bytes) {
+                assert!(!c.ops().is_empty());
+                let _ = (
+                    c.ref_consumed(),
+                    c.query_consumed(),
+                    c.iter_ref_blocks().count(),
+                );
+            }
+        }
+    }
+}
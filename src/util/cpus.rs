@@ -0,0 +1,252 @@
+//! The default `--threads`: logical CPUs, limited by the cgroup CPU quota.
+//!
+//! In a container (Kubernetes, Docker `--cpus`) the pod may be limited to a
+//! few CPUs on a much larger node. Threads beyond the quota only get the job
+//! throttled, so the quota (rounded up) caps the default. On Linux the quota
+//! is read from the cgroup the process is in (`/proc/self/cgroup`): `cpu.max`
+//! under cgroup v2, `cpu.cfs_quota_us` / `cpu.cfs_period_us` under v1.
+//! Elsewhere there is no quota and the default is the logical CPU count, as
+//! before.
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Upper bound of the default thread count.
+pub const MAX_DEFAULT_THREADS: usize = 16;
+/// When the number of CPUs cannot be determined at all.
+const FALLBACK_THREADS: usize = 4;
+
+/// A CPU quota found in the process's cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CgroupCpuLimit {
+    /// cgroup version, 1 or 2.
+    pub version: u8,
+    /// Quota / period: how many CPUs' worth of time the cgroup may use.
+    /// `None` when no quota is set.
+    pub cpus: Option<f64>,
+}
+
+/// What the default thread count was derived from (also in `onsm syscheck`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CpuLimits {
+    /// `std::thread::available_parallelism`, if it could tell.
+    pub available_parallelism: Option<usize>,
+    /// `None` when no cgroup CPU controller was found (or not on Linux).
+    pub cgroup: Option<CgroupCpuLimit>,
+    /// The thread count used when `--threads` is not given.
+    pub default_threads: usize,
+}
+
+impl CpuLimits {
+    pub fn detect() -> Self {
+        let available = std::thread::available_parallelism().map(|n| n.get()).ok();
+        Self::from_parts(available, detect_cgroup())
+    }
+
+    fn from_parts(available: Option<usize>, cgroup: Option<CgroupCpuLimit>) -> Self {
+        let mut threads = available.unwrap_or(FALLBACK_THREADS);
+        if let Some(cpus) = cgroup.and_then(|c| c.cpus) {
+            // a 1.5-CPU quota can keep two threads partly busy
+            threads = threads.min(cpus.ceil().max(1.0) as usize);
+        }
+        Self {
+            available_parallelism: available,
+            cgroup,
+            default_threads: threads.min(MAX_DEFAULT_THREADS),
+        }
+    }
+
+    /// One line on where the default came from.
+    pub fn describe(&self) -> String {
+        let cpus = match self.available_parallelism {
+            Some(n) => format!("{n} logical CPUs"),
+            None => format!("CPU count unknown (assuming {FALLBACK_THREADS})"),
+        };
+        let quota = match self.cgroup {
+            Some(CgroupCpuLimit {
+                version,
+                cpus: Some(q),
+            }) => format!("cgroup v{version} CPU quota {q:.2}"),
+            Some(CgroupCpuLimit {
+                version,
+                cpus: None,
+            }) => format!("no cgroup v{version} CPU quota"),
+            None => "no cgroup CPU limit found".to_string(),
+        };
+        format!("{cpus}, {quota}, cap {MAX_DEFAULT_THREADS}")
+    }
+}
+
+/// `--threads` if given, else the detected default; logs the decision.
+pub fn resolve_threads(requested: Option<usize>) -> usize {
+    let limits = CpuLimits::detect();
+    log::info!("CPUs: {}", limits.describe());
+    match requested {
+        Some(n) => {
+            log::info!("Threads: {n} (--threads)");
+            n
+        }
+        None => {
+            log::info!("Threads: {} (default)", limits.default_threads);
+            limits.default_threads
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect_cgroup() -> Option<CgroupCpuLimit> {
+    let self_cgroup = fs_err::read_to_string("/proc/self/cgroup").ok()?;
+    cgroup_limit_in(Path::new("/sys/fs/cgroup"), &self_cgroup)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_cgroup() -> Option<CgroupCpuLimit> {
+    None
+}
+
+/// The CPU limit of the cgroup named in `self_cgroup` (the text of
+/// `/proc/self/cgroup`), with the hierarchy mounted at `root`. The cgroup's
+/// own directory is tried first, then the hierarchy root, which is what a
+/// container with a private cgroup namespace sees.
+fn cgroup_limit_in(root: &Path, self_cgroup: &str) -> Option<CgroupCpuLimit> {
+    let read = |p: &Path| fs_err::read_to_string(p).ok();
+    for line in self_cgroup.lines() {
+        let mut f = line.splitn(3, ':');
+        let (Some(id), Some(controllers), Some(path)) = (f.next(), f.next(), f.next()) else {
+            continue;
+        };
+        let path = path.trim().trim_start_matches('/');
+        if id == "0" && controllers.is_empty() {
+            for dir in [root.join(path), root.to_path_buf()] {
+                if let Some(text) = read(&dir.join("cpu.max")) {
+                    return Some(CgroupCpuLimit {
+                        version: 2,
+                        cpus: parse_cpu_max(&text),
+                    });
+                }
+            }
+        } else if controllers.split(',').any(|c| c == "cpu") {
+            for mount in [controllers, "cpu", "cpu,cpuacct"] {
+                for dir in [root.join(mount).join(path), root.join(mount)] {
+                    let quota = read(&dir.join("cpu.cfs_quota_us"));
+                    let period = read(&dir.join("cpu.cfs_period_us"));
+                    if let (Some(quota), Some(period)) = (quota, period) {
+                        return Some(CgroupCpuLimit {
+                            version: 1,
+                            cpus: parse_cfs_quota(&quota, &period),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// cgroup v2 `cpu.max`: `<quota> <period>`, the quota being `max` when unset.
+fn parse_cpu_max(text: &str) -> Option<f64> {
+    let mut f = text.split_whitespace();
+    let quota = f.next()?;
+    let period = f.next().unwrap_or("100000");
+    cpus_of(quota, period)
+}
+
+/// cgroup v1 `cpu.cfs_quota_us` (-1 when unset) and `cpu.cfs_period_us`.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    cpus_of(quota.trim(), period.trim())
+}
+
+fn cpus_of(quota: &str, period: &str) -> Option<f64> {
+    let quota: u64 = quota.parse().ok()?;
+    let period: u64 = period.parse().ok().filter(|&p| p > 0)?;
+    Some(quota as f64 / period as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, rel: &str, text: &str) {
+        let p = root.join(rel);
+        fs_err::create_dir_all(p.parent().unwrap()).unwrap();
+        fs_err::write(p, text).unwrap();
+    }
+
+    #[test]
+    fn parses_quota_files() {
+        assert_eq!(parse_cpu_max("400000 100000\n"), Some(4.0));
+        assert_eq!(parse_cpu_max("150000 100000"), Some(1.5));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max(""), None);
+        assert_eq!(parse_cfs_quota("200000\n", "100000\n"), Some(2.0));
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(parse_cfs_quota("50000", "0"), None);
+    }
+
+    #[test]
+    fn finds_v2_and_v1_layouts() {
+        // v2, own cgroup directory visible
+        let td = tempfile::tempdir().unwrap();
+        write(td.path(), "kubepods/pod1/cpu.max", "400000 100000\n");
+        write(td.path(), "cpu.max", "max 100000\n");
+        let v2 = cgroup_limit_in(td.path(), "0::/kubepods/pod1\n").unwrap();
+        assert_eq!((v2.version, v2.cpus), (2, Some(4.0)));
+        // v2 with a cgroup namespace: only the root is there
+        let v2 = cgroup_limit_in(td.path(), "0::/elsewhere\n").unwrap();
+        assert_eq!((v2.version, v2.cpus), (2, None));
+
+        // v1, combined cpu,cpuacct mount
+        let td = tempfile::tempdir().unwrap();
+        write(
+            td.path(),
+            "cpu,cpuacct/docker/abc/cpu.cfs_quota_us",
+            "250000\n",
+        );
+        write(
+            td.path(),
+            "cpu,cpuacct/docker/abc/cpu.cfs_period_us",
+            "100000\n",
+        );
+        let self_cgroup = "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc\n1:name=systemd:/x\n";
+        let v1 = cgroup_limit_in(td.path(), self_cgroup).unwrap();
+        assert_eq!((v1.version, v1.cpus), (1, Some(2.5)));
+        // v1 quota unset
+        write(td.path(), "cpu,cpuacct/docker/abc/cpu.cfs_quota_us", "-1\n");
+        assert_eq!(cgroup_limit_in(td.path(), self_cgroup).unwrap().cpus, None);
+
+        // no cpu controller at all
+        assert_eq!(cgroup_limit_in(td.path(), "3:memory:/x\n"), None);
+        assert_eq!(cgroup_limit_in(td.path(), ""), None);
+    }
+
+    #[test]
+    fn quota_caps_the_default() {
+        let quota = |cpus| Some(CgroupCpuLimit { version: 2, cpus });
+        assert_eq!(
+            CpuLimits::from_parts(Some(128), quota(Some(4.0))).default_threads,
+            4
+        );
+        assert_eq!(
+            CpuLimits::from_parts(Some(128), quota(Some(1.5))).default_threads,
+            2
+        );
+        assert_eq!(
+            CpuLimits::from_parts(Some(8), quota(Some(0.1))).default_threads,
+            1
+        );
+        assert_eq!(
+            CpuLimits::from_parts(Some(2), quota(Some(4.0))).default_threads,
+            2
+        );
+        // no quota: logical CPUs up to the cap, as before
+        assert_eq!(
+            CpuLimits::from_parts(Some(128), quota(None)).default_threads,
+            16
+        );
+        assert_eq!(CpuLimits::from_parts(Some(8), None).default_threads, 8);
+        assert_eq!(CpuLimits::from_parts(None, None).default_threads, 4);
+
+        let d = CpuLimits::from_parts(Some(128), quota(Some(4.0))).describe();
+        assert_eq!(d, "128 logical CPUs, cgroup v2 CPU quota 4.00, cap 16");
+    }
+}
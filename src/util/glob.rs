@@ -0,0 +1,167 @@
+//! Shell-style wildcards in input paths, expanded by onsm itself so that a
+//! pattern still works where no shell sees it (quoted in a pipeline string).
+//!
+//! `*` matches any run of characters and `?` any one, within a path
+//! component; `[abc]`, `[a-z]` and `[!a]` match one character of (or not
+//! of) a set. As in the shell, a wildcard does not match a leading `.`, and
+//! wildcards may appear in directory components too (`runs/*/reads.fq.gz`).
+
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Whether `s` contains a wildcard.
+pub fn has_wildcards(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Whether the file name `name` matches `pattern`.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = name.chars().collect();
+    matches_from(&p, &s)
+}
+
+fn matches_from(p: &[char], s: &[char]) -> bool {
+    match p.split_first() {
+        None => s.is_empty(),
+        Some(('*', rest)) => (0..=s.len()).any(|i| matches_from(rest, &s[i..])),
+        Some(('?', rest)) => !s.is_empty() && matches_from(rest, &s[1..]),
+        Some(('[', rest)) => match class(rest) {
+            Some((class, after)) => {
+                s.first().is_some_and(|&c| class.contains(c)) && matches_from(after, &s[1..])
+            }
+            // no closing `]`: a literal `[`
+            None => s.first() == Some(&'[') && matches_from(rest, &s[1..]),
+        },
+        Some((c, rest)) => s.first() == Some(c) && matches_from(rest, &s[1..]),
+    }
+}
+
+/// A `[...]` set, without its brackets.
+struct Class<'a> {
+    negated: bool,
+    set: &'a [char],
+}
+
+impl Class<'_> {
+    fn contains(&self, c: char) -> bool {
+        let mut found = false;
+        let mut rest = self.set;
+        while let Some(&first) = rest.first() {
+            if let [lo, '-', hi, tail @ ..] = rest {
+                found |= (*lo..=*hi).contains(&c);
+                rest = tail;
+            } else {
+                found |= first == c;
+                rest = &rest[1..];
+            }
+        }
+        found != self.negated
+    }
+}
+
+/// The set after a `[`, and the pattern after its `]`; `None` without one.
+fn class(p: &[char]) -> Option<(Class<'_>, &[char])> {
+    let (negated, body) = match p.first() {
+        Some('!' | '^') => (true, &p[1..]),
+        _ => (false, p),
+    };
+    // a `]` right after the `[` (or `[!`) is a member, not the end
+    let end = body.iter().skip(1).position(|&c| c == ']')? + 1;
+    Some((
+        Class {
+            negated,
+            set: &body[..end],
+        },
+        &body[end + 1..],
+    ))
+}
+
+/// The existing paths `pattern` matches, sorted.
+pub fn expand(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = vec![PathBuf::new()];
+    for comp in pattern.components() {
+        let part = comp.as_os_str().to_string_lossy();
+        if !matches!(comp, Component::Normal(_)) || !has_wildcards(&part) {
+            for p in &mut found {
+                p.push(comp);
+            }
+            continue;
+        }
+        let mut next = Vec::new();
+        for dir in &found {
+            let listed = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir.as_path()
+            };
+            if !listed.is_dir() {
+                continue;
+            }
+            for entry in
+                fs_err::read_dir(listed).with_context(|| format!("expand {}", pattern.display()))?
+            {
+                let name = entry?.file_name();
+                if matches(&part, &name.to_string_lossy()) {
+                    next.push(dir.join(name));
+                }
+            }
+        }
+        next.sort();
+        found = next;
+    }
+    found.retain(|p| p.exists());
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.fastq*", "run1.fastq.gz"));
+        assert!(matches("*.fastq*", "run1.fastq"));
+        assert!(!matches("*.fastq*", "run1.fq"));
+        assert!(matches("r?n[0-9].fq", "run1.fq"));
+        assert!(!matches("r?n[!0-9].fq", "run1.fq"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[b", "a[b"));
+        assert!(matches("*", "reads"));
+        // hidden files only when asked for
+        assert!(!matches("*.fq", ".partial.fq"));
+        assert!(matches(".*.fq", ".partial.fq"));
+        assert!(!has_wildcards("reads/run1.fq"));
+        assert!(has_wildcards("reads/run[12].fq"));
+    }
+
+    #[test]
+    fn expands_in_every_component_sorted() {
+        let td = TempDir::new().unwrap();
+        for f in [
+            "fc2/a.fq",
+            "fc1/b.fq",
+            "fc1/a.fq",
+            "fc1/a.txt",
+            "other/a.fq",
+        ] {
+            let p = td.path().join(f);
+            fs_err::create_dir_all(p.parent().unwrap()).unwrap();
+            fs_err::write(p, "").unwrap();
+        }
+        let got: Vec<String> = expand(&td.path().join("fc*/*.fq"))
+            .unwrap()
+            .iter()
+            .map(|p| p.strip_prefix(td.path()).unwrap().display().to_string())
+            .collect();
+        assert_eq!(got, ["fc1/a.fq", "fc1/b.fq", "fc2/a.fq"]);
+        assert!(expand(&td.path().join("none*/*.fq")).unwrap().is_empty());
+        // no wildcard: the path itself, if it exists
+        assert_eq!(expand(&td.path().join("fc1/a.fq")).unwrap().len(), 1);
+    }
+}
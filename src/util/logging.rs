@@ -1,25 +1,216 @@
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
 
-pub fn init_logging(out_dir: &Path) -> anyhow::Result<()> {
-    fs::create_dir_all(out_dir)?;
-    let logfile = out_dir.join("onsm.log");
+use log::{Log, Metadata, Record};
+
+/// Log file written into every run directory.
+pub const LOG_FILE: &str = "onsm.log";
+
+/// Filter used when RUST_LOG is unset: onsm's own messages at info and above.
+pub const DEFAULT_FILTER: &str = "onsm=info";
+
+fn builder() -> env_logger::Builder {
+    // RUST_LOG is read, never written.
+    builder_from(env_logger::Env::default().default_filter_or(DEFAULT_FILTER))
+}
 
-    let mut builder = env_logger::Builder::from_default_env();
+fn builder_from(env: env_logger::Env) -> env_logger::Builder {
+    let mut builder = env_logger::Builder::from_env(env);
     builder
-        .filter_level(log::LevelFilter::Info)
         .format_timestamp_millis()
         .format_module_path(false)
         .format_level(true);
+    builder
+}
+
+/// Stderr plus the current run's `onsm.log`. Installed once per process; each
+/// `init_logging` swaps the file side to the new run directory.
+struct Tee {
+    stderr: env_logger::Logger,
+    file: Mutex<Option<(u64, env_logger::Logger)>>,
+}
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.stderr.log(record);
+        if let Some((_, file)) = self.file.lock().unwrap().as_ref() {
+            file.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some((_, file)) = self.file.lock().unwrap().as_ref() {
+            file.flush();
+        }
+    }
+}
+
+static TEE: OnceLock<&'static Tee> = OnceLock::new();
+static NEXT_RUN: AtomicU64 = AtomicU64::new(1);
+
+/// The process-wide logger, installed on first use with no file target.
+fn tee() -> &'static Tee {
+    TEE.get_or_init(|| {
+        let stderr = builder().target(env_logger::Target::Stderr).build();
+        let tee: &'static Tee = Box::leak(Box::new(Tee {
+            stderr,
+            file: Mutex::new(None),
+        }));
+        if log::set_logger(tee).is_ok() {
+            log::set_max_level(tee.stderr.filter());
+        }
+        tee
+    })
+}
+
+/// Log to stderr only, with the same filter as `init_logging`. `main` calls it
+/// before dispatch, so that subcommands without a run directory log too; a
+/// later `init_logging` adds the run's file to the same logger.
+pub fn init_stderr() {
+    tee();
+}
 
-    // Also duplicate logs to a file
-    let file = std::fs::File::create(&logfile)?;
-    let _ = std::sync::Mutex::new(file);
-    builder.target(env_logger::Target::Stderr);
+/// Keeps the run's `onsm.log` attached; dropping it detaches the file (stderr
+/// logging stays). A later `init_logging` takes over the file target even while
+/// an older guard is alive, and the older guard's drop then leaves it alone.
+#[must_use = "the run's log file is detached when the guard is dropped"]
+pub struct LogGuard {
+    run: u64,
+}
 
-    // Install a simple tee: stderr via env_logger; file via log::set_boxed_logger is overkill.
-    // Keep it minimal: advise users to tail the file created by mapping/scoring steps.
-    builder.init();
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some(tee) = TEE.get() {
+            let mut file = tee.file.lock().unwrap();
+            if file.as_ref().is_some_and(|(run, _)| *run == self.run) {
+                if let Some((_, f)) = file.take() {
+                    f.flush();
+                }
+            }
+        }
+    }
+}
+
+/// Log to stderr and to `out_dir/onsm.log` (truncated) until the guard drops.
+///
+/// Safe to call repeatedly in one process (pipeline API, tests): the logger is
+/// installed on the first call and later calls point its file target at the new
+/// run directory. If another logger was installed first, only it is used.
+pub fn init_logging(out_dir: &Path) -> anyhow::Result<LogGuard> {
+    fs::create_dir_all(out_dir)?;
+    let logfile = out_dir.join(LOG_FILE);
+    let file = fs::File::create(&logfile)?;
+    let file_logger = builder()
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .build();
+    let run = NEXT_RUN.fetch_add(1, Ordering::Relaxed);
+
+    if let Some((_, old)) = tee().file.lock().unwrap().replace((run, file_logger)) {
+        old.flush();
+    }
     log::info!("Logging initialized. Log file: {}", logfile.display());
-    Ok(())
+    Ok(LogGuard { run })
+}
+
+/// Serializes tests that start runs, since they share the process-wide file target.
+#[cfg(test)]
+pub(crate) static TEST_RUNS: Mutex<()> = Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn read(dir: &Path) -> String {
+        fs::read_to_string(dir.join(LOG_FILE)).unwrap()
+    }
+
+    #[test]
+    fn sequential_runs_log_to_their_own_file() {
+        let _runs = TEST_RUNS.lock().unwrap_or_else(|e| e.into_inner());
+        let a = TempDir::new().unwrap();
+        let b = TempDir::new().unwrap();
+
+        let guard = init_logging(a.path()).unwrap();
+        log::info!("first run message");
+        drop(guard);
+        log::info!("between runs");
+
+        let guard = init_logging(b.path()).unwrap();
+        std::thread::spawn(|| log::warn!("second run, from a worker thread"))
+            .join()
+            .unwrap();
+        // a re-init while the guard is alive switches the target too
+        let c = TempDir::new().unwrap();
+        let newer = init_logging(c.path()).unwrap();
+        drop(guard);
+        log::info!("third run message");
+        drop(newer);
+
+        let (a, b, c) = (read(a.path()), read(b.path()), read(c.path()));
+        assert!(a.contains("first run message"), "{a}");
+        assert!(b.contains("second run, from a worker thread"), "{b}");
+        assert!(c.contains("third run message"), "{c}");
+        for other in [&b, &c] {
+            assert!(!other.contains("first run message"), "{other}");
+        }
+        assert!(!a.contains("second run") && !c.contains("second run"));
+        assert!(!a.contains("between runs") && !b.contains("between runs"));
+        assert!(!b.contains("third run message"));
+    }
+
+    #[test]
+    fn rust_log_is_respected_and_never_written() {
+        let enabled = |env: env_logger::Env, target: &str, level| {
+            let logger = builder_from(env).build();
+            logger.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+        let unset = "ONSM_TEST_LOG_UNSET";
+        let default = || env_logger::Env::new().filter_or(unset, DEFAULT_FILTER);
+        assert!(enabled(default(), "onsm::pipeline", log::Level::Info));
+        assert!(!enabled(default(), "onsm::pipeline", log::Level::Debug));
+        assert!(!enabled(default(), "paf", log::Level::Info));
+        // a RUST_LOG-style setting replaces the default, level included
+        let debug = env_logger::Env::new().filter_or(unset, "debug");
+        assert!(enabled(debug, "onsm::pipeline", log::Level::Debug));
+
+        let _runs = TEST_RUNS.lock().unwrap_or_else(|e| e.into_inner());
+        let before = std::env::var_os("RUST_LOG");
+        let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let first = init_logging(a.path()).unwrap();
+        let second = init_logging(b.path()).unwrap();
+        drop((first, second));
+        assert_eq!(std::env::var_os("RUST_LOG"), before);
+        assert!(read(b.path()).contains("Logging initialized"));
+    }
+
+    #[test]
+    fn stderr_logging_comes_before_any_run() {
+        let _runs = TEST_RUNS.lock().unwrap_or_else(|e| e.into_inner());
+        init_stderr();
+        init_stderr();
+        let info = Metadata::builder()
+            .target("onsm::subcommands::pair")
+            .level(log::Level::Info)
+            .build();
+        assert!(log::logger().enabled(&info));
+        assert!(log::max_level() >= log::LevelFilter::Info);
+        // with nothing attached, a message goes to stderr alone…
+        log::info!("before any run");
+        // …and a run started afterwards still gets its file
+        let dir = TempDir::new().unwrap();
+        let guard = init_logging(dir.path()).unwrap();
+        log::info!("during the run");
+        drop(guard);
+        let log = read(dir.path());
+        assert!(log.contains("during the run"), "{log}");
+        assert!(!log.contains("before any run"), "{log}");
+    }
 }
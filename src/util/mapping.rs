@@ -1,18 +1,46 @@
 use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::util::shutdown::{self, CancelToken};
+use crate::util::{runlock, warnings};
+
+/// minimap2 `--secondary=yes|no`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Mm2Secondary {
+    Yes,
+    No,
+}
+
+/// Aligner settings for `map_asm_to_asm`. `None` leaves minimap2's own default
+/// (secondaries on, `-N 5`), which is what onsm has always used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AsmMapOptions {
+    pub secondary: Option<Mm2Secondary>,
+    pub max_secondary: Option<u32>,
+}
+
 pub fn resolve_bins(
     minimap2: Option<&Path>,
     samtools: Option<&Path>,
 ) -> Result<(PathBuf, PathBuf)> {
     let mm2 = match minimap2 {
-        Some(p) => p.to_path_buf(),
+        Some(p) => {
+            which::which(p).with_context(|| format!("minimap2 not found at {}", p.display()))?
+        }
         None => which::which("minimap2")
             .context("minimap2 not found in PATH. Install or pass --minimap2")?,
     };
     let sam = match samtools {
-        Some(p) => p.to_path_buf(),
+        Some(p) => {
+            which::which(p).with_context(|| format!("samtools not found at {}", p.display()))?
+        }
         None => which::which("samtools")
             .context("samtools not found in PATH. Install or pass --samtools")?,
     };
@@ -28,13 +56,176 @@ pub fn get_version(bin: &Path) -> Result<String> {
     Ok(s.lines().next().unwrap_or_default().to_string())
 }
 
-/// Run minimap2 assembly→assembly mapping with preset `-x asm10` to PAF.
+/* ------------------------- samtools capabilities ------------------------- */
+
+/// Parsed `samtools --version` (major.minor.patch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SamtoolsVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl std::fmt::Display for SamtoolsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        } else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+/// Parse the version out of a `samtools --version` line. Handles the usual shapes:
+/// `samtools 1.9`, `samtools 1.16.1`, `samtools 1.17-11-g1a2b3c4` (git),
+/// `samtools 1.15.1+htslib-1.15.1`, `Version: 1.9 (using htslib 1.9)`.
+pub fn parse_samtools_version(line: &str) -> Option<SamtoolsVersion> {
+    let tok = line
+        .split_whitespace()
+        .find(|t| t.starts_with(|c: char| c.is_ascii_digit()))?;
+    let core: &str = tok
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()?;
+    let mut it = core.split('.').filter(|s| !s.is_empty());
+    Some(SamtoolsVersion {
+        major: it.next()?.parse().ok()?,
+        minor: it.next()?.parse().ok()?,
+        patch: it.next().and_then(|s| s.parse().ok()).unwrap_or(0),
+    })
+}
+
+/// samtools invocations onsm issues, each with the first release supporting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamtoolsFeature {
+    /// `samtools depth -r REGION` (local depth windows).
+    DepthRegion,
+    /// `samtools depth -G FLAGS` (depth without supplementary alignments).
+    DepthExclFlags,
+    /// `samtools view BAM REGION` (spanning reads).
+    ViewRegion,
+    /// `samtools sort -o OUT` (older releases took an output prefix).
+    SortOutput,
+    /// `samtools sort --write-index` (saves a separate `samtools index` pass).
+    SortWriteIndex,
+}
+
+impl SamtoolsFeature {
+    pub fn min_version(self) -> SamtoolsVersion {
+        let (major, minor) = match self {
+            SamtoolsFeature::DepthRegion | SamtoolsFeature::ViewRegion => (1, 0),
+            SamtoolsFeature::SortOutput => (1, 3),
+            SamtoolsFeature::SortWriteIndex => (1, 10),
+            SamtoolsFeature::DepthExclFlags => (1, 13),
+        };
+        SamtoolsVersion {
+            major,
+            minor,
+            patch: 0,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            SamtoolsFeature::DepthRegion => "`samtools depth -r` (coverage windows)",
+            SamtoolsFeature::ViewRegion => "`samtools view` region queries (spanning reads)",
+            SamtoolsFeature::SortOutput => "`samtools sort -o` (reads→assembly BAMs)",
+            SamtoolsFeature::SortWriteIndex => "`samtools sort --write-index`",
+            SamtoolsFeature::DepthExclFlags => {
+                "`samtools depth -G` (depth without supplementary alignments)"
+            }
+        }
+    }
+}
+
+/// Needed to map reads (`classify`).
+pub const MAPPING_FEATURES: &[SamtoolsFeature] = &[SamtoolsFeature::SortOutput];
+
+/// What the detected samtools can do. An unknown version is treated as capable
+/// (we only refuse up-front when we *know* a release is too old).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SamtoolsCaps {
+    pub version: Option<SamtoolsVersion>,
+}
+
+impl SamtoolsCaps {
+    pub fn from_version_line(line: &str) -> Self {
+        Self {
+            version: parse_samtools_version(line),
+        }
+    }
+
+    pub fn supports(&self, f: SamtoolsFeature) -> bool {
+        self.version.is_none_or(|v| v >= f.min_version())
+    }
+
+    /// Error with a "samtools ≥ X required for Y" message if any feature is unsupported.
+    pub fn require(&self, features: &[SamtoolsFeature]) -> Result<()> {
+        for &f in features {
+            if !self.supports(f) {
+                return Err(anyhow!(
+                    "samtools ≥ {} required for {} (found {}); please upgrade samtools",
+                    f.min_version(),
+                    f.describe(),
+                    self.version.map(|v| v.to_string()).unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Detect the samtools version (`--version`) once, at resolve time.
+pub fn probe_samtools(sam: &Path) -> SamtoolsCaps {
+    let line = get_version(sam).unwrap_or_default();
+    let caps = SamtoolsCaps::from_version_line(&line);
+    if caps.version.is_none() {
+        warnings::warn(
+            warnings::TOOL_VERSION,
+            format!("could not parse a samtools version from {line:?}; assuming a recent release"),
+        );
+    }
+    caps
+}
+
+/// argv (after the binary) for assembly→assembly mapping with preset `-x asm10` to PAF.
+pub fn asm_to_asm_args(
+    query_fa: &Path,
+    target_fa: &Path,
+    out_paf: &Path,
+    threads: usize,
+    opts: &AsmMapOptions,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = ["-x", "asm10", "-c", "-t"]
+        .iter()
+        .map(OsString::from)
+        .collect();
+    args.push(threads.to_string().into());
+    match opts.secondary {
+        Some(Mm2Secondary::Yes) => args.push("--secondary=yes".into()),
+        Some(Mm2Secondary::No) => args.push("--secondary=no".into()),
+        None => {}
+    }
+    if let Some(n) = opts.max_secondary {
+        args.push("-N".into());
+        args.push(n.to_string().into());
+    }
+    args.push(target_fa.into());
+    args.push(query_fa.into());
+    args.push("-o".into());
+    args.push(out_paf.into());
+    args
+}
+
+/// Run minimap2 assembly→assembly mapping with preset `-x asm10` to PAF;
+/// cancelling kills it.
 pub fn map_asm_to_asm(
     mm2: &Path,
     query_fa: &Path,
     target_fa: &Path,
     out_paf: &Path,
     threads: usize,
+    opts: &AsmMapOptions,
+    cancel: &CancelToken,
 ) -> Result<()> {
     log::info!(
         "minimap2 asm-asm: {} → {} → {}",
@@ -42,23 +233,64 @@ pub fn map_asm_to_asm(
         target_fa.display(),
         out_paf.display()
     );
-    let status = Command::new(mm2)
-        .args(["-x", "asm10", "-c", "-t"])
-        .arg(threads.to_string())
-        .arg(target_fa)
-        .arg(query_fa)
-        .arg("-o")
-        .arg(out_paf)
-        .status()
+    let mut child = Command::new(mm2)
+        .args(asm_to_asm_args(query_fa, target_fa, out_paf, threads, opts))
+        .spawn()
         .context("failed to spawn minimap2 for asm-asm")?;
+    let status = shutdown::wait_child(&mut child, cancel)?;
     if !status.success() {
         return Err(anyhow!("minimap2 (asm-asm) failed with status {}", status));
     }
     Ok(())
 }
 
-/// Map reads→reference, convert to sorted BAM + index.
-/// Presets: `map-hifi` or `map-ont`.
+/// Build a minimap2 index of `fasta` as an `-x asm10` target at `out_mmi`;
+/// cancelling kills it.
+pub fn build_asm_index(
+    mm2: &Path,
+    fasta: &Path,
+    out_mmi: &Path,
+    threads: usize,
+    cancel: &CancelToken,
+) -> Result<()> {
+    log::info!(
+        "minimap2 index: {} → {}",
+        fasta.display(),
+        out_mmi.display()
+    );
+    let mut child = Command::new(mm2)
+        .args(["-x", "asm10", "-t"])
+        .arg(threads.to_string())
+        .arg("-d")
+        .arg(out_mmi)
+        .arg(fasta)
+        .spawn()
+        .context("failed to spawn minimap2 to build an index")?;
+    let status = shutdown::wait_child(&mut child, cancel)?;
+    if !status.success() {
+        return Err(anyhow!("minimap2 (index) failed with status {}", status));
+    }
+    Ok(())
+}
+
+/// `samtools faidx` `fasta`, writing the index to `out_fai`.
+pub fn faidx(sam: &Path, fasta: &Path, out_fai: &Path) -> Result<()> {
+    let status = Command::new(sam)
+        .arg("faidx")
+        .arg(fasta)
+        .arg("--fai-idx")
+        .arg(out_fai)
+        .status()
+        .context("spawn samtools faidx")?;
+    if !status.success() {
+        return Err(anyhow!("samtools faidx failed with {status}"));
+    }
+    Ok(())
+}
+
+/// Map reads→reference, convert to sorted BAM + index; cancelling kills the
+/// pipeline. Presets: `map-hifi` or `map-ont`.
+#[allow(clippy::too_many_arguments)]
 pub fn map_reads_to_ref(
     mm2: &Path,
     sam: &Path,
@@ -67,7 +299,10 @@ pub fn map_reads_to_ref(
     reference: &Path,
     out_bam: &Path,
     threads: usize,
+    caps: &SamtoolsCaps,
+    cancel: &CancelToken,
 ) -> Result<()> {
+    caps.require(MAPPING_FEATURES)?;
     let preset = match platform {
         "hifi" => "map-hifi",
         "ont" => "map-ont",
@@ -90,9 +325,10 @@ pub fn map_reads_to_ref(
         mm2_cmd.arg(r);
     }
 
-    // Pipe to samtools sort
+    // Pipe to samtools sort (indexing on the fly where supported)
+    let write_index = caps.supports(SamtoolsFeature::SortWriteIndex);
     let mut sort_cmd = Command::new(sam);
-    sort_cmd.args(["sort", "-o"]).arg(out_bam);
+    sort_cmd.args(sort_args(out_bam, write_index));
 
     // Spawn with pipe
     let mut mm2_child = mm2_cmd
@@ -100,11 +336,20 @@ pub fn map_reads_to_ref(
         .spawn()
         .context("spawn minimap2 for reads→ref")?;
     let mm2_out = mm2_child.stdout.take().unwrap();
-    let sort_status = sort_cmd
-        .stdin(mm2_out)
-        .status()
-        .context("spawn samtools sort")?;
+    let mut sort_child = match sort_cmd.stdin(mm2_out).spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            let _ = mm2_child.kill();
+            mm2_child.wait().context("wait minimap2")?;
+            return Err(e).context("spawn samtools sort");
+        }
+    };
+    let sort_status = shutdown::wait_child(&mut sort_child, cancel);
+    if sort_status.is_err() {
+        let _ = mm2_child.kill();
+    }
     let mm2_status = mm2_child.wait().context("wait minimap2")?;
+    let sort_status = sort_status?;
 
     if !mm2_status.success() || !sort_status.success() {
         return Err(anyhow!(
@@ -112,17 +357,44 @@ pub fn map_reads_to_ref(
         ));
     }
 
-    // Index
-    let status = Command::new(sam)
+    if write_index {
+        return Ok(());
+    }
+
+    // Index (older samtools: separate pass)
+    let mut child = Command::new(sam)
         .args(["index", out_bam.to_str().unwrap()])
-        .status()
+        .spawn()
         .context("samtools index")?;
+    let status = shutdown::wait_child(&mut child, cancel)?;
     if !status.success() {
         return Err(anyhow!("samtools index failed with {status}"));
     }
     Ok(())
 }
 
+/// `samtools sort` argv; with `write_index` the .bai is written alongside.
+/// Chunk files are prefixed with the run token: samtools' default prefix is
+/// the output name, which two runs given the same `--out` would share.
+fn sort_args(out_bam: &Path, write_index: bool) -> Vec<OsString> {
+    let mut prefix = out_bam.as_os_str().to_owned();
+    prefix.push(format!(".{}", runlock::run_token()));
+    let mut args: Vec<OsString> = vec!["sort".into(), "-T".into(), prefix];
+    if write_index {
+        let mut o = out_bam.as_os_str().to_owned();
+        o.push("##idx##");
+        o.push(out_bam.as_os_str());
+        o.push(".bai");
+        args.push("--write-index".into());
+        args.push("-o".into());
+        args.push(o);
+    } else {
+        args.push("-o".into());
+        args.push(out_bam.into());
+    }
+    args
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,8 +421,234 @@ mod tests {
             Path::new("ref.fa"),
             Path::new("out.bam"),
             1,
+            &SamtoolsCaps::default(),
+            &CancelToken::default(),
         )
         .unwrap_err();
         assert!(e.to_string().contains("unknown --platform"));
     }
+
+    #[test]
+    fn samtools_version_strings() {
+        let v = |s| parse_samtools_version(s).map(|v| v.to_string());
+        assert_eq!(v("samtools 1.9").as_deref(), Some("1.9"));
+        assert_eq!(v("samtools 1.16.1").as_deref(), Some("1.16.1"));
+        assert_eq!(v("samtools 1.17-11-g1a2b3c4").as_deref(), Some("1.17"));
+        assert_eq!(
+            v("samtools 1.15.1+htslib-1.15.1").as_deref(),
+            Some("1.15.1")
+        );
+        assert_eq!(v("Version: 1.9 (using htslib 1.9)").as_deref(), Some("1.9"));
+        assert_eq!(v("samtools 1.21 (conda-forge)").as_deref(), Some("1.21"));
+        assert_eq!(v("samtools 0.1.19-44428cd").as_deref(), Some("0.1.19"));
+        assert_eq!(v(""), None);
+        assert_eq!(v("samtools: command not found"), None);
+        // ordering is numeric, not lexical
+        assert!(
+            parse_samtools_version("samtools 1.10").unwrap()
+                > parse_samtools_version("samtools 1.9").unwrap()
+        );
+    }
+
+    #[test]
+    fn capabilities_by_version() {
+        let caps = |s| SamtoolsCaps::from_version_line(s);
+        assert!(!caps("samtools 1.9").supports(SamtoolsFeature::SortWriteIndex));
+        assert!(caps("samtools 1.10").supports(SamtoolsFeature::SortWriteIndex));
+        assert!(caps("samtools 1.9").require(MAPPING_FEATURES).is_ok());
+        // unknown version: assume capable
+        assert!(caps("???").supports(SamtoolsFeature::SortWriteIndex));
+
+        let e = caps("samtools 1.2").require(MAPPING_FEATURES).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "samtools ≥ 1.3 required for `samtools sort -o` (reads→assembly BAMs) (found 1.2); please upgrade samtools"
+        );
+        assert!(caps("samtools 0.1.19")
+            .require(crate::io::bam::EVIDENCE_FEATURES)
+            .is_err());
+        // depth without supplementary alignments needs `samtools depth -G`
+        let evidence = crate::io::bam::evidence_features;
+        assert!(caps("samtools 1.12").require(evidence(true)).is_ok());
+        assert!(caps("samtools 1.12").require(evidence(false)).is_err());
+        assert!(caps("samtools 1.13").require(evidence(false)).is_ok());
+    }
+
+    #[test]
+    fn sort_argv_by_capability() {
+        let a: Vec<String> = sort_args(Path::new("x.bam"), true)
+            .into_iter()
+            .map(|a| a.into_string().unwrap())
+            .collect();
+        let prefix = format!("x.bam.{}", runlock::run_token());
+        assert_eq!(
+            a,
+            [
+                "sort",
+                "-T",
+                &prefix,
+                "--write-index",
+                "-o",
+                "x.bam##idx##x.bam.bai"
+            ]
+        );
+        let a: Vec<String> = sort_args(Path::new("x.bam"), false)
+            .into_iter()
+            .map(|a| a.into_string().unwrap())
+            .collect();
+        assert_eq!(a, ["sort", "-T", &prefix, "-o", "x.bam"]);
+    }
+
+    fn argv(opts: &AsmMapOptions) -> Vec<String> {
+        asm_to_asm_args(
+            Path::new("q.fa"),
+            Path::new("t.fa"),
+            Path::new("o.paf"),
+            4,
+            opts,
+        )
+        .into_iter()
+        .map(|a| a.into_string().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn asm_args_default_unchanged() {
+        assert_eq!(
+            argv(&AsmMapOptions::default()),
+            ["-x", "asm10", "-c", "-t", "4", "t.fa", "q.fa", "-o", "o.paf"]
+        );
+    }
+
+    #[test]
+    fn asm_args_secondary_controls() {
+        let a = argv(&AsmMapOptions {
+            secondary: Some(Mm2Secondary::No),
+            max_secondary: None,
+        });
+        assert_eq!(a[5], "--secondary=no");
+        let a = argv(&AsmMapOptions {
+            secondary: Some(Mm2Secondary::Yes),
+            max_secondary: Some(2),
+        });
+        assert_eq!(&a[5..8], ["--secondary=yes", "-N", "2"]);
+        assert_eq!(&a[8..], ["t.fa", "q.fa", "-o", "o.paf"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn map_asm_to_asm_passes_options_to_runner() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        // Mock minimap2: record argv, write an empty PAF to the -o target.
+        let mock = dir.path().join("minimap2");
+        let log = dir.path().join("argv.txt");
+        std::fs::write(
+            &mock,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\nwhile [ $# -gt 1 ]; do [ \"$1\" = -o ] && : > \"$2\"; shift; done\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&mock, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let out = dir.path().join("o.paf");
+        let opts = AsmMapOptions {
+            secondary: Some(Mm2Secondary::No),
+            max_secondary: Some(0),
+        };
+        map_asm_to_asm(
+            &mock,
+            Path::new("q.fa"),
+            Path::new("t.fa"),
+            &out,
+            2,
+            &opts,
+            &CancelToken::default(),
+        )
+        .unwrap();
+        let seen = std::fs::read_to_string(&log).unwrap();
+        assert!(seen.contains("--secondary=no\n-N\n0\n"), "{seen}");
+        assert!(out.exists());
+    }
+
+    /// Miniature genomes: a mito segment inserted twice in the nuclear genome, so
+    /// minimap2 reports one primary + one secondary hit unless told not to.
+    /// Needs a real minimap2 in PATH; skipped otherwise.
+    #[test]
+    fn secondary_setting_changes_pair_count() {
+        use crate::io::paf::read_paf;
+        use crate::model::PairingParams;
+        use crate::pairing::{PairingStrategy, ReciprocalBest};
+
+        let Ok(mm2) = which::which("minimap2") else {
+            eprintln!("minimap2 not in PATH; skipping");
+            return;
+        };
+        let dir = tempfile::TempDir::new().unwrap();
+        let seq = pseudo_random_dna(40_000, 7);
+        let mito = &seq[..6_000];
+        let insert = &mito[1_000..3_000];
+        let nuc = format!(
+            "{}{insert}{}{insert}{}",
+            &seq[6_000..16_000],
+            &seq[16_000..26_000],
+            &seq[26_000..]
+        );
+        let mito_fa = dir.path().join("mito.fa");
+        let nuc_fa = dir.path().join("nuc.fa");
+        std::fs::write(&mito_fa, format!(">mt\n{mito}\n")).unwrap();
+        std::fs::write(&nuc_fa, format!(">chr1\n{nuc}\n")).unwrap();
+
+        let n_pairs = |secondary| {
+            let opts = AsmMapOptions {
+                secondary: Some(secondary),
+                max_secondary: None,
+            };
+            let m2n = dir.path().join("m2n.paf");
+            let n2m = dir.path().join("n2m.paf");
+            map_asm_to_asm(
+                &mm2,
+                &mito_fa,
+                &nuc_fa,
+                &m2n,
+                1,
+                &opts,
+                &CancelToken::default(),
+            )
+            .unwrap();
+            map_asm_to_asm(
+                &mm2,
+                &nuc_fa,
+                &mito_fa,
+                &n2m,
+                1,
+                &opts,
+                &CancelToken::default(),
+            )
+            .unwrap();
+            let (m2n, n2m) = (
+                read_paf(&m2n, 0.9, 100, 0).unwrap(),
+                read_paf(&n2m, 0.9, 100, 0).unwrap(),
+            );
+            ReciprocalBest
+                .pair(&m2n, &n2m, &PairingParams::default())
+                .unwrap()
+                .len()
+        };
+        let (with, without) = (n_pairs(Mm2Secondary::Yes), n_pairs(Mm2Secondary::No));
+        assert!(with > without, "secondary=yes {with} vs no {without}");
+    }
+
+    fn pseudo_random_dna(n: usize, mut seed: u64) -> String {
+        (0..n)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"ACGT"[(seed >> 62) as usize] as char
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,317 @@
+//! `progress.json` in a `classify` output directory, for workflow dashboards
+//! that want "onsm: evidence 1,420/2,000 pairs" without parsing the log.
+//!
+//! The file is rewritten whole (`runfiles::write_atomic`, so a reader never
+//! sees half of it) at every stage transition and, while evidence is
+//! collected, every `EVERY_PAIRS` pairs but at most once per `MIN_INTERVAL`.
+//! When the run ends it stays behind with `state` finished, interrupted or
+//! failed. `--resume` reads the interrupted run's file: the run keeps its
+//! original start time, and the log says where it had got to.
+//!
+//! Updates are best-effort: a failed write is logged and the run carries on.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::io::runfiles;
+
+pub const PROGRESS_FILE: &str = "progress.json";
+/// Pairs between two updates during evidence collection…
+pub const EVERY_PAIRS: u64 = 100;
+/// …and the least time between them.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Where a run is, in the order `classify` goes through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Stage {
+    /// Preflight: inputs, tool versions, the manifest.
+    Starting,
+    /// minimap2: assemblies against each other, reads against both.
+    Mapping,
+    /// PAF filtering and pairing.
+    Pairing,
+    /// Depth and spanning evidence, pair by pair (counted).
+    Evidence,
+    /// Scoring and writing the outputs (`--low-memory` does it during
+    /// `evidence`).
+    Scoring,
+    /// Everything written.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ProgressState {
+    Running,
+    Finished,
+    /// `--max-runtime` or a signal; `classify --resume` continues it.
+    Interrupted,
+    Failed,
+}
+
+/// Contents of `progress.json`. Times are seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ProgressReport {
+    pub state: ProgressState,
+    pub stage: Stage,
+    /// Items of the stage done, out of `total` (pairs for `evidence`; 0 and
+    /// `null` for stages that are not counted).
+    pub done: u64,
+    pub total: Option<u64>,
+    pub run_started: u64,
+    pub stage_started: u64,
+    pub updated: u64,
+    /// Seconds until the stage is done, at its rate so far (pairs a resumed
+    /// run found already done don't count); `null` until there is a rate.
+    pub eta_secs: Option<u64>,
+}
+
+impl ProgressReport {
+    pub fn load_from(dir: &Path) -> Result<Self> {
+        let path = dir.join(PROGRESS_FILE);
+        let text = fs_err::read_to_string(&path)?;
+        serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+struct Tracker {
+    report: ProgressReport,
+    stage_clock: Instant,
+    /// `done` when the stage started.
+    stage_base: u64,
+    /// `done` and time of the last write within the stage.
+    written: (u64, Instant),
+}
+
+/// Keeps `progress.json` up to date; a run dropped while still running (an
+/// error, a panic) leaves it `failed`.
+pub struct Progress {
+    path: PathBuf,
+    every: u64,
+    min_interval: Duration,
+    tracker: RefCell<Tracker>,
+}
+
+impl Progress {
+    /// Start the file in `out_dir` at `Stage::Starting`. With `resume`, the
+    /// previous run's start time carries over.
+    pub fn start(out_dir: &Path, resume: bool) -> Result<Self> {
+        let mut run_started = unix_now();
+        if resume {
+            match ProgressReport::load_from(out_dir) {
+                Ok(prev) => {
+                    log::info!(
+                        "--resume: the previous run was {:?} in {:?} at {}/{}",
+                        prev.state,
+                        prev.stage,
+                        prev.done,
+                        prev.total.map_or("?".to_string(), |t| t.to_string())
+                    );
+                    run_started = prev.run_started;
+                }
+                Err(e) => log::debug!("--resume: no usable {PROGRESS_FILE}: {e:#}"),
+            }
+        }
+        let now = Instant::now();
+        let p = Self {
+            path: out_dir.join(PROGRESS_FILE),
+            every: EVERY_PAIRS,
+            min_interval: MIN_INTERVAL,
+            tracker: RefCell::new(Tracker {
+                report: ProgressReport {
+                    state: ProgressState::Running,
+                    stage: Stage::Starting,
+                    done: 0,
+                    total: None,
+                    run_started,
+                    stage_started: unix_now(),
+                    updated: unix_now(),
+                    eta_secs: None,
+                },
+                stage_clock: now,
+                stage_base: 0,
+                written: (0, now),
+            }),
+        };
+        p.write(&mut p.tracker.borrow_mut())?;
+        Ok(p)
+    }
+
+    /// Update every `every` items, at most once per `min_interval`.
+    pub fn with_cadence(mut self, every: u64, min_interval: Duration) -> Self {
+        self.every = every.max(1);
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Enter `stage` with `done` of `total` items already done; always written.
+    pub fn stage(&self, stage: Stage, done: u64, total: Option<u64>) {
+        let mut t = self.tracker.borrow_mut();
+        t.report.stage = stage;
+        t.report.stage_started = unix_now();
+        t.report.done = done;
+        t.report.total = total;
+        t.stage_clock = Instant::now();
+        t.stage_base = done;
+        self.update(&mut t);
+    }
+
+    /// `done` items of the stage are done; written if the cadence allows.
+    pub fn advance(&self, done: u64) {
+        let mut t = self.tracker.borrow_mut();
+        t.report.done = done;
+        if done.saturating_sub(t.written.0) >= self.every
+            && t.written.1.elapsed() >= self.min_interval
+        {
+            self.update(&mut t);
+        }
+    }
+
+    /// The run ended; `Finished` moves to `Stage::Done`.
+    pub fn finish(&self, state: ProgressState) {
+        let mut t = self.tracker.borrow_mut();
+        t.report.state = state;
+        if state == ProgressState::Finished {
+            t.report.stage = Stage::Done;
+            t.report.stage_started = unix_now();
+            (t.report.done, t.report.total) = (0, None);
+        }
+        self.update(&mut t);
+    }
+
+    fn update(&self, t: &mut Tracker) {
+        t.report.eta_secs = eta(t);
+        if let Err(e) = self.write(t) {
+            log::warn!("could not update {}: {e:#}", self.path.display());
+        }
+    }
+
+    fn write(&self, t: &mut Tracker) -> Result<()> {
+        t.report.updated = unix_now();
+        t.written = (t.report.done, Instant::now());
+        runfiles::write_atomic(&self.path, &serde_json::to_vec_pretty(&t.report)?)
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.tracker.get_mut().report.state == ProgressState::Running {
+            self.finish(ProgressState::Failed);
+        }
+    }
+}
+
+/// Remaining items over the stage's rate so far.
+fn eta(t: &Tracker) -> Option<u64> {
+    let r = &t.report;
+    let total = r.total?;
+    if r.state != ProgressState::Running || r.done >= total {
+        return (r.done >= total).then_some(0);
+    }
+    let done_here = r.done.checked_sub(t.stage_base).filter(|&n| n > 0)?;
+    let per_item = t.stage_clock.elapsed().as_secs_f64() / done_here as f64;
+    Some((per_item * (total - r.done) as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn stages_then_a_final_state() {
+        let td = TempDir::new().unwrap();
+        let read = || ProgressReport::load_from(td.path()).unwrap();
+        let p = Progress::start(td.path(), false).unwrap();
+        assert_eq!(
+            (read().state, read().stage),
+            (ProgressState::Running, Stage::Starting)
+        );
+        p.stage(Stage::Pairing, 0, None);
+        assert_eq!(
+            (read().stage, read().total, read().eta_secs),
+            (Stage::Pairing, None, None)
+        );
+        p.finish(ProgressState::Finished);
+        let r = read();
+        assert_eq!((r.state, r.stage), (ProgressState::Finished, Stage::Done));
+        assert!(r.run_started <= r.updated);
+        drop(p);
+        // finished stays finished; a run dropped midway failed
+        assert_eq!(read().state, ProgressState::Finished);
+        let p = Progress::start(td.path(), false).unwrap();
+        p.stage(Stage::Evidence, 0, Some(10));
+        drop(p);
+        assert_eq!(
+            (read().state, read().stage),
+            (ProgressState::Failed, Stage::Evidence)
+        );
+    }
+
+    #[test]
+    fn resume_keeps_the_start_time() {
+        let td = TempDir::new().unwrap();
+        let p = Progress::start(td.path(), false).unwrap();
+        p.finish(ProgressState::Interrupted);
+        let mut prev = ProgressReport::load_from(td.path()).unwrap();
+        prev.run_started -= 3600;
+        fs_err::write(
+            td.path().join(PROGRESS_FILE),
+            serde_json::to_vec(&prev).unwrap(),
+        )
+        .unwrap();
+
+        let p = Progress::start(td.path(), true).unwrap();
+        let r = ProgressReport::load_from(td.path()).unwrap();
+        assert_eq!(
+            (r.state, r.run_started),
+            (ProgressState::Running, prev.run_started)
+        );
+        drop(p);
+        // a fresh run starts its own clock, and so does a resume without a file
+        let p = Progress::start(td.path(), false).unwrap();
+        assert!(ProgressReport::load_from(td.path()).unwrap().run_started > prev.run_started);
+        drop(p);
+        let empty = TempDir::new().unwrap();
+        assert!(Progress::start(empty.path(), true).is_ok());
+    }
+
+    #[test]
+    fn updates_are_rate_limited() {
+        let td = TempDir::new().unwrap();
+        let done = || ProgressReport::load_from(td.path()).unwrap().done;
+        let p = Progress::start(td.path(), false)
+            .unwrap()
+            .with_cadence(10, Duration::ZERO);
+        p.stage(Stage::Evidence, 0, Some(100));
+        for i in 1..=25 {
+            p.advance(i);
+        }
+        assert_eq!(done(), 20);
+        // however many pairs, not before the interval is up
+        let p = p.with_cadence(1, Duration::from_secs(3600));
+        for i in 26..=99 {
+            p.advance(i);
+        }
+        assert_eq!(done(), 20);
+        // a stage transition is always written
+        p.stage(Stage::Scoring, 0, None);
+        assert_eq!(
+            ProgressReport::load_from(td.path()).unwrap().stage,
+            Stage::Scoring
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! `contig:start-end` region strings, as samtools and seqkit take them.
+//!
+//! Numbers may contain commas (`chr3:1,200,000-1,240,000`). By default they
+//! are 1-based and inclusive, as genome browsers show them; `--coords 0based`
+//! reads them as 0-based half-open like onsm's own files. Either way a
+//! resolved `Region` is 0-based half-open.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+
+/// How to read the numbers of a region string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Coords {
+    /// 1-based, both ends included (`chr1:1-100` is the first 100 bp).
+    #[default]
+    #[value(name = "1based")]
+    OneBased,
+    /// 0-based, end excluded (`chr1:0-100` is the first 100 bp).
+    #[value(name = "0based")]
+    ZeroBased,
+}
+
+/// A region string as given, before the coordinates are interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionString {
+    pub contig: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// `contig:start-end`; the contig name may itself contain `:`. Only the
+/// syntax is checked here, see `RegionString::resolve`.
+pub fn parse(s: &str) -> Result<RegionString, String> {
+    let bad = || format!("expected CONTIG:START-END, got {s:?}");
+    let (contig, range) = s.trim().rsplit_once(':').ok_or_else(bad)?;
+    let (start, end) = range.split_once('-').ok_or_else(bad)?;
+    let num = |v: &str| v.replace(',', "").trim().parse::<u32>().map_err(|_| bad());
+    let (start, end) = (num(start)?, num(end)?);
+    if contig.is_empty() {
+        return Err(bad());
+    }
+    Ok(RegionString {
+        contig: contig.to_string(),
+        start,
+        end,
+    })
+}
+
+/// A checked region, 0-based half-open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Region {
+    pub contig: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl RegionString {
+    /// Read the numbers as `coords` and check the region lies on a contig of
+    /// `contig_len` bp.
+    pub fn resolve(&self, coords: Coords, contig_len: u64) -> Result<Region, String> {
+        let (start, end) = match coords {
+            Coords::OneBased if self.start == 0 => {
+                return Err(format!(
+                    "{self}: positions are 1-based (--coords 0based reads them as 0-based)"
+                ))
+            }
+            Coords::OneBased => (self.start - 1, self.end),
+            Coords::ZeroBased => (self.start, self.end),
+        };
+        if start >= end {
+            return Err(format!("{self}: start is not before end"));
+        }
+        if end as u64 > contig_len {
+            return Err(format!(
+                "{self}: past the end of {} ({contig_len} bp)",
+                self.contig
+            ));
+        }
+        Ok(Region {
+            contig: self.contig.clone(),
+            start,
+            end,
+        })
+    }
+}
+
+impl fmt::Display for RegionString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.contig, self.start, self.end)
+    }
+}
+
+impl Region {
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// 1-based inclusive, as samtools names extracted regions.
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.contig, self.start + 1, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_string_syntax() {
+        assert_eq!(
+            parse("chr3:1,200,000-1,240,000"),
+            Ok(RegionString {
+                contig: "chr3".into(),
+                start: 1_200_000,
+                end: 1_240_000
+            })
+        );
+        assert_eq!(
+            parse(" HiC_scaffold:12:5-10 ").unwrap().contig,
+            "HiC_scaffold:12"
+        );
+        for bad in [
+            "chr3",
+            "chr3:100",
+            "chr3:a-b",
+            ":1-2",
+            "chr3:-5-10",
+            "chr3:1-2-3",
+        ] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn one_and_zero_based_resolve_alike() {
+        let one = parse("chr1:1-100").unwrap().resolve(Coords::OneBased, 1000);
+        let zero = parse("chr1:0-100")
+            .unwrap()
+            .resolve(Coords::ZeroBased, 1000);
+        assert_eq!(one, zero);
+        let r = one.unwrap();
+        assert_eq!((r.start, r.end, r.len()), (0, 100, 100));
+        assert_eq!(r.to_string(), "chr1:1-100");
+        // a single base
+        let r = parse("chr1:7-7")
+            .unwrap()
+            .resolve(Coords::OneBased, 1000)
+            .unwrap();
+        assert_eq!((r.start, r.end), (6, 7));
+    }
+
+    #[test]
+    fn resolve_checks_the_coordinates() {
+        let err = |s: &str, coords| parse(s).unwrap().resolve(coords, 1000).unwrap_err();
+        assert!(err("chr1:0-10", Coords::OneBased).contains("1-based"));
+        assert!(err("chr1:7-7", Coords::ZeroBased).contains("not before"));
+        assert!(err("chr1:50-10", Coords::OneBased).contains("not before"));
+        assert!(err("chr1:900-1001", Coords::OneBased).contains("past the end of chr1 (1000 bp)"));
+        assert!(parse("chr1:900-1000")
+            .unwrap()
+            .resolve(Coords::OneBased, 1000)
+            .is_ok());
+    }
+}
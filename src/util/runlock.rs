@@ -0,0 +1,360 @@
+//! One run per output directory at a time.
+//!
+//! `classify` and `reuse` take `onsm.lock` in their output directory before
+//! anything else is written there (`onsm.log` included) and remove it when the
+//! run ends, on error or panic too (`RunLock` is a drop guard). A second run
+//! given the same `--out` while the first is alive refuses to start instead of
+//! truncating its log and interleaving its outputs. A lock left behind by a
+//! killed run is taken over when its PID is dead on this host; a lock from
+//! another host, or one that cannot be read, needs `--force-lock`.
+//!
+//! Temporary files the run asks other tools to create (samtools sort's chunk
+//! files) embed `run_token()`, so even two runs forced into one directory
+//! never share a temporary name.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::util::warnings;
+
+/// In the run directory while a run is using it.
+pub const LOCK_FILE: &str = "onsm.lock";
+
+static NEXT_LOCK: AtomicU64 = AtomicU64::new(1);
+
+/// Unique to this process (PID plus start time); part of every temporary
+/// file name the run creates.
+pub fn run_token() -> &'static str {
+    static TOKEN: OnceLock<String> = OnceLock::new();
+    TOKEN.get_or_init(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        format!("{}-{nanos:x}", std::process::id())
+    })
+}
+
+/// Contents of `onsm.lock`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub host: String,
+    /// Unix seconds.
+    pub started: u64,
+    /// Tells this lock apart from a later one in the same directory.
+    pub token: String,
+}
+
+impl LockInfo {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            host: hostname(),
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            token: format!(
+                "{}.{}",
+                run_token(),
+                NEXT_LOCK.fetch_add(1, Ordering::Relaxed)
+            ),
+        }
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Left behind by a run that is gone: same host, PID no longer alive.
+    pub fn is_stale(&self) -> bool {
+        self.host == hostname() && !pid_alive(self.pid)
+    }
+}
+
+/// Why an existing lock was replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Takeover {
+    Stale(LockInfo),
+    Forced(Option<LockInfo>),
+}
+
+/// Holds `onsm.lock`; dropping it removes the file unless another run has
+/// since taken it over.
+#[must_use = "the run directory is unlocked when the guard is dropped"]
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    token: String,
+    takeover: Option<Takeover>,
+}
+
+impl RunLock {
+    /// Lock `out_dir` (which must exist) for this run. A live lock is an
+    /// error unless `force`; a stale one is replaced.
+    pub fn acquire(out_dir: &Path, force: bool) -> Result<Self> {
+        let path = out_dir.join(LOCK_FILE);
+        let info = LockInfo::current();
+        let mut takeover = None;
+        // Two tries: the second after removing a stale or forced lock
+        for _ in 0..2 {
+            match create_new(&path, &info) {
+                Ok(()) => {
+                    return Ok(Self {
+                        path,
+                        token: info.token,
+                        takeover,
+                    })
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("create {}", path.display()));
+                }
+            }
+            let held = LockInfo::read(&path);
+            takeover = match held {
+                Some(h) if h.is_stale() => Some(Takeover::Stale(h)),
+                h if force => Some(Takeover::Forced(h)),
+                Some(h) => return Err(held_error(&path, &h)),
+                None => return Err(unreadable_error(&path)),
+            };
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("remove {}", path.display()));
+                }
+            }
+        }
+        Err(anyhow!(
+            "{} was taken by another run while replacing it; is a second run starting in this directory?",
+            path.display()
+        ))
+    }
+
+    /// Record a replaced lock as a warning (once warnings are being collected).
+    pub fn report_takeover(&self) {
+        match &self.takeover {
+            None => {}
+            Some(Takeover::Stale(h)) => warnings::warn(
+                warnings::RUN_LOCK,
+                format!(
+                    "replaced the stale {LOCK_FILE} of PID {} (no longer running), started at {}",
+                    h.pid, h.started
+                ),
+            ),
+            Some(Takeover::Forced(h)) => warnings::warn(
+                warnings::RUN_LOCK,
+                match h {
+                    Some(h) => format!(
+                        "--force-lock: replaced the {LOCK_FILE} of PID {} on {}, started at {}; \
+                         outputs may be mixed if that run is still going",
+                        h.pid, h.host, h.started
+                    ),
+                    None => format!("--force-lock: replaced an unreadable {LOCK_FILE}"),
+                },
+            ),
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        if LockInfo::read(&self.path).is_some_and(|h| h.token == self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn unreadable_error(path: &Path) -> anyhow::Error {
+    anyhow!(
+        "{} exists but cannot be read (a run starting right now, or one that crashed); \
+         rerun with --force-lock if no other run uses this directory",
+        path.display()
+    )
+}
+
+fn held_error(path: &Path, h: &LockInfo) -> anyhow::Error {
+    let where_ = if h.host == hostname() {
+        "this host".to_string()
+    } else {
+        h.host.clone()
+    };
+    anyhow!(
+        "{} is held by a running onsm (PID {} on {where_}, started at {}); \
+         give each run its own --out, or rerun with --force-lock if that run is gone",
+        path.display(),
+        h.pid,
+        h.started
+    )
+}
+
+/// Create `path` only if absent, with `info` in it.
+fn create_new(path: &Path, info: &LockInfo) -> std::io::Result<()> {
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    f.write_all(serde_json::to_string_pretty(info)?.as_bytes())?;
+    f.sync_all()
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // gethostname NUL-terminates unless the name was truncated
+    let r = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len() - 1) };
+    if r == 0 {
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        if end > 0 {
+            return String::from_utf8_lossy(&buf[..end]).into_owned();
+        }
+    }
+    std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "localhost".to_string())
+}
+
+#[cfg(unix)]
+fn pid_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // Signal 0 only checks; EPERM means it exists under another user
+    let r = unsafe { libc::kill(pid, 0) };
+    r == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a way to check, every PID counts as alive.
+#[cfg(not(unix))]
+fn pid_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn held(dir: &Path) -> LockInfo {
+        LockInfo::read(&dir.join(LOCK_FILE)).unwrap()
+    }
+
+    #[test]
+    fn lock_is_held_until_dropped() {
+        let td = TempDir::new().unwrap();
+        let lock = RunLock::acquire(td.path(), false).unwrap();
+        let h = held(td.path());
+        assert_eq!((h.pid, h.host.as_str()), (std::process::id(), &*hostname()));
+        assert!(h.token.starts_with(run_token()));
+        assert!(!h.is_stale());
+        drop(lock);
+        assert!(!td.path().join(LOCK_FILE).exists());
+        // and can be taken again
+        let _again = RunLock::acquire(td.path(), false).unwrap();
+    }
+
+    #[test]
+    fn live_lock_refuses_a_second_run() {
+        let td = TempDir::new().unwrap();
+        let first = RunLock::acquire(td.path(), false).unwrap();
+        let e = RunLock::acquire(td.path(), false).unwrap_err().to_string();
+        assert!(e.contains("held by a running onsm"), "{e}");
+        assert!(e.contains(&format!("PID {}", std::process::id())), "{e}");
+        assert!(e.contains("--force-lock"), "{e}");
+        // the refused run left the first one's lock alone
+        assert_eq!(held(td.path()).token, first.token);
+    }
+
+    #[test]
+    fn force_takes_over_and_the_old_guard_leaves_it() {
+        let td = TempDir::new().unwrap();
+        let first = RunLock::acquire(td.path(), false).unwrap();
+        let second = RunLock::acquire(td.path(), true).unwrap();
+        assert!(matches!(second.takeover, Some(Takeover::Forced(Some(_)))));
+        drop(first);
+        assert_eq!(held(td.path()).token, second.token);
+        drop(second);
+        assert!(!td.path().join(LOCK_FILE).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dead_pid_on_this_host_is_stale() {
+        let td = TempDir::new().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let dead = LockInfo {
+            pid,
+            host: hostname(),
+            started: 1,
+            token: "gone".into(),
+        };
+        assert!(dead.is_stale());
+        fs::write(
+            td.path().join(LOCK_FILE),
+            serde_json::to_string(&dead).unwrap(),
+        )
+        .unwrap();
+        let lock = RunLock::acquire(td.path(), false).unwrap();
+        assert_eq!(lock.takeover, Some(Takeover::Stale(dead)));
+        assert_eq!(held(td.path()).token, lock.token);
+    }
+
+    #[test]
+    fn other_hosts_and_unreadable_locks_need_force() {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join(LOCK_FILE);
+        let elsewhere = LockInfo {
+            pid: 1,
+            host: format!("not-{}", hostname()),
+            started: 1,
+            token: "remote".into(),
+        };
+        assert!(!elsewhere.is_stale());
+        fs::write(&path, serde_json::to_string(&elsewhere).unwrap()).unwrap();
+        let e = RunLock::acquire(td.path(), false).unwrap_err().to_string();
+        assert!(e.contains(&elsewhere.host), "{e}");
+
+        fs::write(&path, "").unwrap();
+        let e = RunLock::acquire(td.path(), false).unwrap_err().to_string();
+        assert!(e.contains("cannot be read"), "{e}");
+        let lock = RunLock::acquire(td.path(), true).unwrap();
+        assert_eq!(lock.takeover, Some(Takeover::Forced(None)));
+    }
+
+    #[test]
+    fn concurrent_runs_get_one_lock() {
+        let td = TempDir::new().unwrap();
+        let dir = td.path().to_path_buf();
+        let barrier = std::sync::Barrier::new(8);
+        let won: Vec<RunLock> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        RunLock::acquire(&dir, false).ok()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().unwrap())
+                .collect()
+        });
+        assert_eq!(won.len(), 1);
+        assert_eq!(held(&dir).token, won[0].token);
+    }
+}
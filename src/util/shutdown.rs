@@ -0,0 +1,333 @@
+//! Cooperative shutdown of the evidence stage: `--max-runtime` and
+//! SIGTERM/SIGINT.
+//!
+//! Schedulers kill a job at its walltime; a killed evidence loop leaves
+//! nothing to resume from. Instead the loop checks `Shutdown` between pairs:
+//! once the deadline is near or a signal arrived, it finishes the current pair
+//! and returns `Interrupted`. By then every pair's evidence is already on disk
+//! (see `pipeline::CheckpointedEvidence`), `resume_hint.json` says how far the
+//! run got, and the process exits with `EXIT_INTERRUPTED` so a wrapper can
+//! resubmit with `--resume`. A second signal kills as usual.
+//!
+//! An application embedding the pipeline cancels it from another thread
+//! through a `CancelToken` (`CmdClassify::cancel`). Besides the evidence loop,
+//! a cancelled run stops between stages and kills the aligner or samtools
+//! process it waits on, and ends like an interrupted one with reason
+//! `Cancelled`.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Exit code of an interrupted run (EX_TEMPFAIL: try again).
+pub const EXIT_INTERRUPTED: i32 = 75;
+/// In the run directory.
+pub const RESUME_HINT_FILE: &str = "resume_hint.json";
+/// Longest time kept in hand before the deadline; shorter runtimes keep a tenth.
+pub const MAX_RUNTIME_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// How often `wait_child` looks at the token while a child process runs.
+const CHILD_POLL: Duration = Duration::from_millis(50);
+
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/// `HH:MM` (or `MM`) → duration.
+pub fn parse_runtime(s: &str) -> Result<Duration, String> {
+    let bad = || format!("expected HH:MM, got {s:?}");
+    let (h, m) = match s.split_once(':') {
+        Some((h, m)) => (h, m),
+        None => ("0", s),
+    };
+    let h: u64 = h.trim().parse().map_err(|_| bad())?;
+    let m: u64 = m.trim().parse().map_err(|_| bad())?;
+    if s.contains(':') && m >= 60 {
+        return Err(bad());
+    }
+    let d = Duration::from_secs(60 * (60 * h + m));
+    if d.is_zero() {
+        return Err("--max-runtime must be positive".to_string());
+    }
+    Ok(d)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// `--max-runtime` is about to run out.
+    Deadline,
+    /// SIGTERM or SIGINT.
+    Signal,
+    /// Cancelled in-process through a `CancelToken`.
+    #[serde(alias = "requested")]
+    Cancelled,
+}
+
+/// A flag shared with the thread that may cancel the run; clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// When the run should stop early.
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    stop_at: Option<Instant>,
+    cancel: CancelToken,
+}
+
+impl Shutdown {
+    /// Stop a margin before `max_runtime` from `started`, and on a signal once
+    /// `install_signal_handlers` was called.
+    pub fn new(started: Instant, max_runtime: Option<Duration>) -> Self {
+        Self {
+            stop_at: max_runtime.map(|d| started + d - (d / 10).min(MAX_RUNTIME_MARGIN)),
+            cancel: CancelToken::default(),
+        }
+    }
+
+    /// Also stop once `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// The token that cancels this run.
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    pub fn request(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Why to stop now, if at all.
+    pub fn check(&self) -> Option<StopReason> {
+        if self.cancel.is_cancelled() {
+            Some(StopReason::Cancelled)
+        } else if SIGNALLED.load(Ordering::SeqCst) {
+            Some(StopReason::Signal)
+        } else if self.stop_at.is_some_and(|t| Instant::now() >= t) {
+            Some(StopReason::Deadline)
+        } else {
+            None
+        }
+    }
+
+    /// Between stages before or after the evidence loop: `Interrupted` if the
+    /// run should stop, with `pairs_done` of `pairs_total` pairs' evidence
+    /// (0/0 before pairing).
+    pub fn stop_point(&self, pairs_done: usize, pairs_total: usize) -> Result<()> {
+        match self.check() {
+            Some(reason) => Err(Interrupted {
+                reason,
+                pairs_done,
+                pairs_total,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Wait for `child`, killing it if `cancel` is set first; a killed child gives
+/// `Interrupted` with reason `Cancelled`.
+pub fn wait_child(child: &mut Child, cancel: &CancelToken) -> Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if cancel.is_cancelled() {
+            // it may have exited in between; either way it is reaped
+            let _ = child.kill();
+            child.wait()?;
+            return Err(Interrupted {
+                reason: StopReason::Cancelled,
+                pairs_done: 0,
+                pairs_total: 0,
+            }
+            .into());
+        }
+        std::thread::sleep(CHILD_POLL);
+    }
+}
+
+/// Turn SIGTERM/SIGINT into a stop request for `Shutdown::check`; a second
+/// one terminates the process.
+#[cfg(unix)]
+pub fn install_signal_handlers() {
+    extern "C" fn on_signal(sig: libc::c_int) {
+        if SIGNALLED.swap(true, Ordering::SeqCst) {
+            // signal() and raise() are async-signal-safe
+            unsafe {
+                libc::signal(sig, libc::SIG_DFL);
+                libc::raise(sig);
+            }
+        }
+    }
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_signal_handlers() {}
+
+/// The evidence loop stopped early; what was collected is on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interrupted {
+    pub reason: StopReason,
+    pub pairs_done: usize,
+    pub pairs_total: usize,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let why = match self.reason {
+            StopReason::Deadline => "--max-runtime is nearly up",
+            StopReason::Signal => "received a termination signal",
+            StopReason::Cancelled => "cancelled",
+        };
+        if self.pairs_total == 0 {
+            return write!(
+                f,
+                "interrupted ({why}) before the evidence stage; rerun with --resume to continue"
+            );
+        }
+        write!(
+            f,
+            "interrupted ({why}) after evidence for {}/{} pairs; rerun with --resume to continue",
+            self.pairs_done, self.pairs_total
+        )
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Contents of `resume_hint.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResumeHint {
+    pub reason: StopReason,
+    pub pairs_done: usize,
+    pub pairs_total: usize,
+    pub elapsed_secs: u64,
+    /// The per-pair evidence collected so far.
+    pub partial_evidence: PathBuf,
+    /// The command to rerun, with `--resume`.
+    pub command: Vec<String>,
+}
+
+impl ResumeHint {
+    pub fn save_to(&self, out_dir: &Path) -> Result<()> {
+        let f = fs_err::File::create(out_dir.join(RESUME_HINT_FILE))?;
+        serde_json::to_writer_pretty(f, self)?;
+        Ok(())
+    }
+
+    pub fn load_from(out_dir: &Path) -> Result<Self> {
+        let p = out_dir.join(RESUME_HINT_FILE);
+        if !p.exists() {
+            return Err(anyhow!(
+                "--resume: no {RESUME_HINT_FILE} in {} (the run was not interrupted)",
+                out_dir.display()
+            ));
+        }
+        Ok(serde_json::from_str(&fs_err::read_to_string(&p)?)?)
+    }
+}
+
+/// This process's command line with `--resume` added.
+pub fn resume_command() -> Vec<String> {
+    let mut argv: Vec<String> = std::env::args().collect();
+    if !argv.iter().any(|a| a == "--resume") {
+        argv.push("--resume".to_string());
+    }
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_parsing() {
+        assert_eq!(parse_runtime("02:30"), Ok(Duration::from_secs(150 * 60)));
+        assert_eq!(parse_runtime("45"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_runtime("48:00"), Ok(Duration::from_secs(48 * 3600)));
+        for bad in ["1:60", "1h", "", "00:00", "-1:00"] {
+            assert!(parse_runtime(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn deadline_keeps_a_margin() {
+        let t0 = Instant::now();
+        let s = Shutdown::new(t0, Some(Duration::from_secs(3600)));
+        assert_eq!(s.stop_at, Some(t0 + Duration::from_secs(3300)));
+        // a tenth of short runtimes
+        let s = Shutdown::new(t0, Some(Duration::from_secs(600)));
+        assert_eq!(s.stop_at, Some(t0 + Duration::from_secs(540)));
+        assert_eq!(s.check(), None);
+        s.request();
+        assert_eq!(s.check(), Some(StopReason::Cancelled));
+
+        let past = Shutdown::new(t0 - Duration::from_secs(120), Some(Duration::from_secs(60)));
+        assert_eq!(past.check(), Some(StopReason::Deadline));
+        assert_eq!(Shutdown::default().check(), None);
+    }
+
+    #[test]
+    fn a_token_cancels_from_another_thread() {
+        let token = CancelToken::default();
+        let s = Shutdown::default().with_cancel(token.clone());
+        s.stop_point(0, 0).unwrap();
+        std::thread::spawn(move || token.cancel()).join().unwrap();
+        assert_eq!(s.check(), Some(StopReason::Cancelled));
+        let e = s.stop_point(0, 0).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<Interrupted>().map(|i| i.reason),
+            Some(StopReason::Cancelled)
+        );
+        assert!(e.to_string().contains("before the evidence stage"), "{e}");
+        // hints written before the rename still load
+        let old: StopReason = serde_json::from_str("\"requested\"").unwrap();
+        assert_eq!(old, StopReason::Cancelled);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancelling_kills_the_child_in_flight() {
+        let token = CancelToken::default();
+        let mut done = std::process::Command::new("true").spawn().unwrap();
+        assert!(wait_child(&mut done, &token).unwrap().success());
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let t0 = Instant::now();
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+        let e = wait_child(&mut child, &token).unwrap_err();
+        assert!(e.downcast_ref::<Interrupted>().is_some(), "{e}");
+        assert!(t0.elapsed() < Duration::from_secs(10));
+        // reaped
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
@@ -0,0 +1,137 @@
+//! Small descriptive-statistics helpers shared by summaries and reports.
+
+use std::collections::BTreeMap;
+
+/// Median of a slice (NaNs sort last). Returns 0.0 for empty input.
+pub fn median(v: &[f64]) -> f64 {
+    if v.is_empty() {
+        return 0.0;
+    }
+    let mut v = v.to_vec();
+    v.sort_by(|a, b| a.total_cmp(b));
+    let n = v.len();
+    if n % 2 == 1 {
+        v[n / 2]
+    } else {
+        0.5 * (v[n / 2 - 1] + v[n / 2])
+    }
+}
+
+/// Weighted mean of `(value, weight)` pairs. Returns 0.0 when total weight is 0.
+pub fn weighted_mean(v: &[(f64, f64)]) -> f64 {
+    let wsum: f64 = v.iter().map(|(_, w)| *w).sum();
+    if wsum <= 0.0 {
+        return 0.0;
+    }
+    v.iter().map(|(x, w)| x * w).sum::<f64>() / wsum
+}
+
+/// Histogram of fractions in [0,1] into 1%-wide bins keyed by the bin's lower
+/// percent (0..=100; exactly 1.0 lands in bin 100). Only non-empty bins are present.
+pub fn percent_histogram(v: &[f64]) -> BTreeMap<u32, u64> {
+    let mut h = BTreeMap::new();
+    for x in v {
+        // small epsilon so 0.93 (stored as 0.92999… in f32) lands in the 93% bin
+        let bin = ((x.clamp(0.0, 1.0) * 100.0) + 1e-4).floor() as u32;
+        *h.entry(bin).or_insert(0) += 1;
+    }
+    h
+}
+
+/// Linear-interpolated quantile (R type 7) for q in [0,1]. Returns 0.0 for empty input.
+pub fn quantile(v: &[f64], q: f64) -> f64 {
+    if v.is_empty() {
+        return 0.0;
+    }
+    let mut v = v.to_vec();
+    v.sort_by(|a, b| a.total_cmp(b));
+    quantile_sorted(&v, q)
+}
+
+fn quantile_sorted(v: &[f64], q: f64) -> f64 {
+    let h = (v.len() - 1) as f64 * q.clamp(0.0, 1.0);
+    let (lo, hi) = (h.floor() as usize, h.ceil() as usize);
+    v[lo] + (h - lo as f64) * (v[hi] - v[lo])
+}
+
+/// Five-number summary (min, quartiles, max).
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Quartiles {
+    pub min: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub max: f64,
+}
+
+impl Quartiles {
+    /// All zeros for empty input.
+    pub fn of(v: &[f64]) -> Self {
+        if v.is_empty() {
+            return Self::default();
+        }
+        let mut v = v.to_vec();
+        v.sort_by(|a, b| a.total_cmp(b));
+        Self {
+            min: v[0],
+            q1: quantile_sorted(&v, 0.25),
+            median: quantile_sorted(&v, 0.5),
+            q3: quantile_sorted(&v, 0.75),
+            max: v[v.len() - 1],
+        }
+    }
+}
+
+/// N50: the length L such that pieces ≥ L hold at least half the total. 0 for empty input.
+pub fn n50(lens: &[u64]) -> u64 {
+    let mut v = lens.to_vec();
+    v.sort_unstable_by(|a, b| b.cmp(a));
+    let total: u64 = v.iter().sum();
+    let mut acc = 0u64;
+    for l in v {
+        acc += l;
+        if 2 * acc >= total {
+            return l;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_and_weighted_mean() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[1.0, 2.0, 3.0, 4.0]), 2.5);
+        assert_eq!(weighted_mean(&[]), 0.0);
+        // (0.9*100 + 1.0*300) / 400 = 0.975
+        assert!((weighted_mean(&[(0.9, 100.0), (1.0, 300.0)]) - 0.975).abs() < 1e-12);
+    }
+
+    #[test]
+    fn percent_bins() {
+        let h = percent_histogram(&[0.93, 0.935, 0.999, 1.0, 0.5]);
+        assert_eq!(h.get(&93), Some(&2));
+        assert_eq!(h.get(&99), Some(&1));
+        assert_eq!(h.get(&100), Some(&1));
+        assert_eq!(h.get(&50), Some(&1));
+        assert_eq!(h.len(), 4);
+    }
+
+    #[test]
+    fn quartiles_and_n50() {
+        let q = Quartiles::of(&[1.0, 2.0, 3.0, 4.0, 10.0]);
+        assert_eq!(
+            (q.min, q.q1, q.median, q.q3, q.max),
+            (1.0, 2.0, 3.0, 4.0, 10.0)
+        );
+        assert_eq!(quantile(&[1.0, 2.0], 0.25), 1.25);
+        assert_eq!(Quartiles::of(&[]), Quartiles::default());
+        // total 100; 40+30 ≥ 50
+        assert_eq!(n50(&[10, 30, 20, 40]), 30);
+        assert_eq!(n50(&[]), 0);
+    }
+}
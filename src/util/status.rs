@@ -0,0 +1,149 @@
+//! End-of-run status for `classify` / `reuse`: a last stdout line
+//! `ONSM_STATUS: OK|OK_WITH_WARNINGS|INTERRUPTED|FAILED` for wrappers to grep,
+//! and with `--warnings-as-exit-code` a distinct exit code for a run that
+//! completed but whose warnings exceed the strict policy (see `util::strict`).
+//! An interrupted run (`--max-runtime`, SIGTERM; see `util::shutdown`) always
+//! exits with `shutdown::EXIT_INTERRUPTED`.
+//!
+//! The status is settled after every output, `warnings.json` included, has
+//! been written, so a nonzero exit never leaves a run half-written.
+
+use anyhow::Result;
+use std::io::Write;
+
+use crate::util::shutdown::{Interrupted, EXIT_INTERRUPTED};
+use crate::util::strict::StrictPolicy;
+use crate::util::warnings::WarningsReport;
+
+/// Exit code of an `OK_WITH_WARNINGS` run under `--warnings-as-exit-code`.
+pub const EXIT_WITH_WARNINGS: i32 = 10;
+/// Prefix of the status line.
+pub const STATUS_PREFIX: &str = "ONSM_STATUS: ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    Ok,
+    /// Completed, but some warning category exceeds the strict policy.
+    OkWithWarnings,
+    /// Stopped early with its evidence checkpointed; rerun with `--resume`.
+    Interrupted,
+    Failed,
+}
+
+impl RunStatus {
+    /// A completed run's status: the strict policy (defaults, or
+    /// `--strict-policy`) decides which warnings are serious.
+    pub fn of_run(policy: &StrictPolicy, report: &WarningsReport, n_pairs: usize) -> Self {
+        if policy.evaluate(report, n_pairs).is_empty() {
+            RunStatus::Ok
+        } else {
+            RunStatus::OkWithWarnings
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Ok => "OK",
+            RunStatus::OkWithWarnings => "OK_WITH_WARNINGS",
+            RunStatus::Interrupted => "INTERRUPTED",
+            RunStatus::Failed => "FAILED",
+        }
+    }
+
+    /// Process exit code for a completed run (a failed one returns its error).
+    pub fn exit_code(self, warnings_as_exit_code: bool) -> i32 {
+        match self {
+            RunStatus::OkWithWarnings if warnings_as_exit_code => EXIT_WITH_WARNINGS,
+            RunStatus::Interrupted => EXIT_INTERRUPTED,
+            RunStatus::Failed => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Write the status line of `result` to `out`; the exit code if the run
+/// completed or was interrupted, else its error.
+pub fn conclude(
+    result: Result<RunStatus>,
+    warnings_as_exit_code: bool,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let result = match result {
+        Err(e) if e.downcast_ref::<Interrupted>().is_some() => Ok(RunStatus::Interrupted),
+        r => r,
+    };
+    let status = result.as_ref().map_or(RunStatus::Failed, |s| *s);
+    writeln!(out, "{STATUS_PREFIX}{}", status.as_str())?;
+    out.flush()?;
+    Ok(result?.exit_code(warnings_as_exit_code))
+}
+
+/// `conclude` on stdout, exiting with the code when it is not 0.
+pub fn finish(result: Result<RunStatus>, warnings_as_exit_code: bool) -> Result<()> {
+    let code = conclude(result, warnings_as_exit_code, &mut std::io::stdout())?;
+    if code != 0 {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::shutdown::StopReason;
+    use crate::util::warnings::{self, Warnings};
+    use anyhow::anyhow;
+
+    fn run(result: Result<RunStatus>, flag: bool) -> (String, Result<i32>) {
+        let mut out = Vec::new();
+        let code = conclude(result, flag, &mut out);
+        (String::from_utf8(out).unwrap(), code)
+    }
+
+    #[test]
+    fn status_from_the_strict_policy() {
+        let policy = StrictPolicy::default();
+        let w = Warnings::default();
+        w.record(warnings::WINDOW_CLAMPED, "P1: clamped");
+        assert_eq!(RunStatus::of_run(&policy, &w.report(), 10), RunStatus::Ok);
+        // over the default 20% of pairs
+        for i in 0..3 {
+            w.record(warnings::SPARSE_EVIDENCE, &format!("P{i}: sparse"));
+        }
+        assert_eq!(
+            RunStatus::of_run(&policy, &w.report(), 10),
+            RunStatus::OkWithWarnings
+        );
+        assert_eq!(RunStatus::of_run(&policy, &w.report(), 100), RunStatus::Ok);
+    }
+
+    #[test]
+    fn each_status_line_and_exit_code() {
+        let (line, code) = run(Ok(RunStatus::Ok), true);
+        assert_eq!((line.as_str(), code.unwrap()), ("ONSM_STATUS: OK\n", 0));
+
+        // the exit code is opt-in
+        let (line, code) = run(Ok(RunStatus::OkWithWarnings), false);
+        assert_eq!(
+            (line.as_str(), code.unwrap()),
+            ("ONSM_STATUS: OK_WITH_WARNINGS\n", 0)
+        );
+        let (_, code) = run(Ok(RunStatus::OkWithWarnings), true);
+        assert_eq!(code.unwrap(), EXIT_WITH_WARNINGS);
+
+        let (line, code) = run(Err(anyhow!("samtools not found")), true);
+        assert_eq!(line, "ONSM_STATUS: FAILED\n");
+        assert_eq!(code.unwrap_err().to_string(), "samtools not found");
+
+        let stopped = Interrupted {
+            reason: StopReason::Signal,
+            pairs_done: 3,
+            pairs_total: 10,
+        };
+        let (line, code) = run(Err(anyhow::Error::new(stopped).context("evidence")), false);
+        assert_eq!(
+            (line.as_str(), code.unwrap()),
+            ("ONSM_STATUS: INTERRUPTED\n", EXIT_INTERRUPTED)
+        );
+    }
+}
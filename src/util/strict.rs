@@ -0,0 +1,273 @@
+//! `--strict`: fail a run whose warnings (see `util::warnings`) exceed a
+//! per-category threshold.
+//!
+//! The policy is checked after every output has been written, so a failed
+//! strict run can still be inspected; the run then exits nonzero and
+//! `error.json` lists the policies that fired. Defaults cover the categories
+//! that usually mean compromised results; `--strict-policy FILE` (JSON,
+//! category → `{"max_count": N}` and/or `{"max_pair_frac": F}`) overrides them
+//! per category, and `{}` disables one.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::util::warnings::{self, WarningsReport};
+
+pub const ERROR_FILE: &str = "error.json";
+
+/// When one category escalates to an error; unset limits never fire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    /// Fail when the category fired more often than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u64>,
+    /// Fail when it fired for more than this fraction of the run's pairs
+    /// (categories warned about once per pair).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pair_frac: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrictPolicy {
+    pub thresholds: BTreeMap<String, Threshold>,
+}
+
+impl Default for StrictPolicy {
+    fn default() -> Self {
+        let count = |n| Threshold {
+            max_count: Some(n),
+            max_pair_frac: None,
+        };
+        let thresholds = [
+            (
+                warnings::SPARSE_EVIDENCE,
+                Threshold {
+                    max_count: None,
+                    max_pair_frac: Some(0.20),
+                },
+            ),
+            (warnings::MALFORMED_PAF_LINE, count(5)),
+            (warnings::MALFORMED_BED_LINE, count(5)),
+            (warnings::FASTA_CHANGED, count(0)),
+            (warnings::INVALID_WINDOW, count(0)),
+            (warnings::LOW_MAPPING_RATE, count(0)),
+        ];
+        Self {
+            thresholds: thresholds
+                .into_iter()
+                .map(|(c, t)| (c.to_string(), t))
+                .collect(),
+        }
+    }
+}
+
+/// One threshold exceeded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Violation {
+    pub category: String,
+    pub count: u64,
+    pub n_pairs: usize,
+    pub threshold: Threshold,
+    pub examples: Vec<String>,
+}
+
+impl Violation {
+    fn describe(&self) -> String {
+        let mut limits = Vec::new();
+        if let Some(n) = self.threshold.max_count {
+            limits.push(format!("max_count {n}"));
+        }
+        if let Some(f) = self.threshold.max_pair_frac {
+            limits.push(format!("max_pair_frac {f}"));
+        }
+        format!(
+            "{} fired {} time(s) over {} pair(s) (limit: {})",
+            self.category,
+            self.count,
+            self.n_pairs,
+            limits.join(", ")
+        )
+    }
+}
+
+/// Contents of `error.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StrictError {
+    pub error: String,
+    pub violations: Vec<Violation>,
+}
+
+impl StrictPolicy {
+    /// The defaults with the categories in `path` replaced.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs_err::read_to_string(path)?;
+        let overrides: BTreeMap<String, Threshold> = serde_json::from_str(&text)
+            .with_context(|| format!("{}: not a strict policy", path.display()))?;
+        let mut policy = Self::default();
+        policy.thresholds.extend(overrides);
+        Ok(policy)
+    }
+
+    /// Thresholds exceeded by `report` for a run of `n_pairs` pairs.
+    pub fn evaluate(&self, report: &WarningsReport, n_pairs: usize) -> Vec<Violation> {
+        report
+            .warnings
+            .iter()
+            .filter_map(|w| {
+                let t = *self.thresholds.get(&w.category)?;
+                let over_count = t.max_count.is_some_and(|n| w.count > n);
+                let over_frac = t
+                    .max_pair_frac
+                    .is_some_and(|f| n_pairs > 0 && w.count as f64 / n_pairs as f64 > f);
+                (over_count || over_frac).then(|| Violation {
+                    category: w.category.clone(),
+                    count: w.count,
+                    n_pairs,
+                    threshold: t,
+                    examples: w.examples.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// End-of-run check: writes `error.json` and errors if any threshold was
+    /// exceeded, else removes a stale `error.json`.
+    pub fn enforce(&self, out_dir: &Path, report: &WarningsReport, n_pairs: usize) -> Result<()> {
+        let p = out_dir.join(ERROR_FILE);
+        let violations = self.evaluate(report, n_pairs);
+        if violations.is_empty() {
+            if p.exists() {
+                fs_err::remove_file(&p)?;
+            }
+            return Ok(());
+        }
+        let lines: Vec<String> = violations.iter().map(Violation::describe).collect();
+        let err = StrictError {
+            error: format!("--strict: {}", lines.join("; ")),
+            violations,
+        };
+        serde_json::to_writer_pretty(fs_err::File::create(&p)?, &err)?;
+        Err(anyhow!(
+            "{} (outputs were written; details in {})",
+            err.error,
+            p.display()
+        ))
+    }
+}
+
+/// The policy for a run: `None` without `--strict`.
+pub fn policy(strict: bool, file: Option<&Path>) -> Result<Option<StrictPolicy>> {
+    if !strict {
+        return Ok(None);
+    }
+    let p = match file {
+        Some(f) => StrictPolicy::load(f)?,
+        None => StrictPolicy::default(),
+    };
+    Ok(Some(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::warnings::Warnings;
+    use tempfile::TempDir;
+
+    fn profile(counts: &[(&str, u64)]) -> WarningsReport {
+        let w = Warnings::default();
+        for &(cat, n) in counts {
+            for i in 0..n {
+                w.record(cat, &format!("{cat} #{i}"));
+            }
+        }
+        w.report()
+    }
+
+    #[test]
+    fn default_policy_over_warning_profiles() {
+        let p = StrictPolicy::default();
+        // a clean-ish run: a few sparse pairs, a handful of bad PAF lines
+        let ok = profile(&[
+            (warnings::SPARSE_EVIDENCE, 20),
+            (warnings::MALFORMED_PAF_LINE, 5),
+            (warnings::WINDOW_CLAMPED, 500),
+        ]);
+        assert!(p.evaluate(&ok, 100).is_empty());
+
+        let bad = profile(&[
+            (warnings::SPARSE_EVIDENCE, 21),
+            (warnings::MALFORMED_PAF_LINE, 6),
+            (warnings::FASTA_CHANGED, 1),
+        ]);
+        let v = p.evaluate(&bad, 100);
+        let cats: Vec<&str> = v.iter().map(|v| v.category.as_str()).collect();
+        assert_eq!(
+            cats,
+            [
+                warnings::SPARSE_EVIDENCE,
+                warnings::MALFORMED_PAF_LINE,
+                warnings::FASTA_CHANGED
+            ]
+        );
+        assert!(v[0].describe().contains("max_pair_frac 0.2"), "{:?}", v[0]);
+        // fractions need pairs to divide by
+        assert!(p
+            .evaluate(&profile(&[(warnings::SPARSE_EVIDENCE, 3)]), 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn policy_file_overrides_and_disables_categories() {
+        let td = TempDir::new().unwrap();
+        let f = td.path().join("policy.json");
+        fs_err::write(
+            &f,
+            r#"{"malformed_paf_line": {"max_count": 100},
+                "sparse_evidence": {},
+                "window_clamped": {"max_pair_frac": 0.5}}"#,
+        )
+        .unwrap();
+        let p = StrictPolicy::load(&f).unwrap();
+        let r = profile(&[
+            (warnings::SPARSE_EVIDENCE, 90),
+            (warnings::MALFORMED_PAF_LINE, 50),
+            (warnings::WINDOW_CLAMPED, 60),
+            (warnings::FASTA_CHANGED, 1),
+        ]);
+        let cats: Vec<String> = p
+            .evaluate(&r, 100)
+            .into_iter()
+            .map(|v| v.category)
+            .collect();
+        assert_eq!(cats, [warnings::WINDOW_CLAMPED, warnings::FASTA_CHANGED]);
+
+        fs_err::write(&f, r#"{"malformed_paf_line": {"max": 1}}"#).unwrap();
+        let e = format!("{:#}", StrictPolicy::load(&f).unwrap_err());
+        assert!(e.contains("not a strict policy"), "{e}");
+    }
+
+    #[test]
+    fn enforce_writes_error_json_and_fails() {
+        let td = TempDir::new().unwrap();
+        let p = StrictPolicy::default();
+        let bad = profile(&[(warnings::MALFORMED_BED_LINE, 7)]);
+        let e = p.enforce(td.path(), &bad, 10).unwrap_err().to_string();
+        assert!(e.contains("malformed_bed_line fired 7 time(s)"), "{e}");
+        let err: StrictError =
+            serde_json::from_reader(fs_err::File::open(td.path().join(ERROR_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(err.violations.len(), 1);
+        assert_eq!(err.violations[0].threshold.max_count, Some(5));
+        assert_eq!(err.violations[0].examples.len(), warnings::MAX_EXAMPLES);
+
+        // a passing rerun into the same directory clears it
+        p.enforce(td.path(), &profile(&[]), 10).unwrap();
+        assert!(!td.path().join(ERROR_FILE).exists());
+    }
+}
@@ -0,0 +1,170 @@
+//! Structured build and tool provenance: `onsm --version-json`, and the
+//! `build_info` recorded in `run_manifest.json` and `results.json`.
+//!
+//! The git commit, build time, rustc version and cargo features are embedded
+//! at compile time by `build.rs`; anything it could not determine (e.g. a
+//! build from a source tarball) is `"unknown"`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::util::mapping;
+
+/// Value of any field that could not be determined.
+pub const UNKNOWN: &str = "unknown";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    /// Full commit hash, or `"unknown"` for builds outside a git checkout.
+    pub git_commit: String,
+    /// `git describe --tags --always --dirty`, or `"unknown"`.
+    pub git_describe: String,
+    /// UTC, RFC 3339 (`SOURCE_DATE_EPOCH` when set).
+    pub build_timestamp: String,
+    pub rustc: String,
+    pub features: Vec<String>,
+    /// External tools by name; only those that could be resolved.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, ToolVersion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ToolVersion {
+    pub path: PathBuf,
+    /// First line of `--version`, or `"unknown"` if it gave none.
+    pub version: String,
+}
+
+impl VersionInfo {
+    /// What this binary was built from (no tools).
+    pub fn build() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("ONSM_GIT_COMMIT").to_string(),
+            git_describe: env!("ONSM_GIT_DESCRIBE").to_string(),
+            build_timestamp: env!("ONSM_BUILD_TIMESTAMP").to_string(),
+            rustc: env!("ONSM_RUSTC_VERSION").to_string(),
+            features: env!("ONSM_FEATURES")
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .map(str::to_string)
+                .collect(),
+            tools: BTreeMap::new(),
+        }
+    }
+
+    /// Add a resolved tool and the version it reports.
+    pub fn with_tool(mut self, name: &str, bin: &Path) -> Self {
+        let version = mapping::get_version(bin)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| UNKNOWN.to_string());
+        self.tools.insert(
+            name.to_string(),
+            ToolVersion {
+                path: bin.to_path_buf(),
+                version,
+            },
+        );
+        self
+    }
+
+    /// Add minimap2 and samtools if they are on PATH.
+    pub fn with_path_tools(self) -> Self {
+        ["minimap2", "samtools"]
+            .into_iter()
+            .fold(self, |info, name| match which::which(name) {
+                Ok(bin) => info.with_tool(name, &bin),
+                Err(_) => info,
+            })
+    }
+}
+
+/// `onsm --version-json`.
+pub fn print_json() -> Result<()> {
+    let info = VersionInfo::build().with_path_tools();
+    println!("{}", serde_json::to_string_pretty(&info)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{self, RunManifest};
+    use tempfile::TempDir;
+
+    fn is_commit(s: &str) -> bool {
+        s == UNKNOWN || (s.len() == 40 && s.bytes().all(|b| b.is_ascii_hexdigit()))
+    }
+
+    #[test]
+    fn build_info_json_shape() {
+        let info = VersionInfo::build();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(is_commit(&info.git_commit), "{}", info.git_commit);
+        assert!(!info.git_describe.is_empty());
+        // YYYY-MM-DDTHH:MM:SSZ
+        let ts = info.build_timestamp.as_bytes();
+        assert_eq!((ts.len(), ts[4], ts[10], ts[19]), (20, b'-', b'T', b'Z'));
+
+        let v: serde_json::Value = serde_json::to_value(&info).unwrap();
+        let keys: Vec<&str> = v.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            [
+                "build_timestamp",
+                "features",
+                "git_commit",
+                "git_describe",
+                "name",
+                "rustc",
+                "version"
+            ]
+        );
+
+        let with = info.clone().with_tool("sh", Path::new("/no/such/tool"));
+        let v = serde_json::to_value(&with).unwrap();
+        assert_eq!(v["tools"]["sh"]["version"], UNKNOWN);
+        let back: VersionInfo = serde_json::from_value(v).unwrap();
+        assert_eq!(back, with);
+    }
+
+    #[test]
+    fn manifest_records_the_build() {
+        let td = TempDir::new().unwrap();
+        let m = RunManifest::new(
+            Path::new("mito.fa"),
+            Path::new("nuc.fa"),
+            &[],
+            "hifi",
+            1,
+            model::MIN_ID,
+            model::MIN_LEN,
+            model::MERGE_GAP,
+            model::FLANK_BP,
+            model::WIN_BP,
+        );
+        RunManifest::save_to(td.path(), &m).unwrap();
+        let path = td.path().join("run_manifest.json");
+        let mut v: serde_json::Value =
+            serde_json::from_str(&fs_err::read_to_string(&path).unwrap()).unwrap();
+        let commit = v["build_info"]["git_commit"].as_str().unwrap();
+        assert!(is_commit(commit), "{commit}");
+        assert_eq!(
+            RunManifest::load_from(td.path()).unwrap().build_info,
+            Some(VersionInfo::build())
+        );
+
+        // manifests from before build_info still load
+        v.as_object_mut().unwrap().remove("build_info");
+        fs_err::write(&path, v.to_string()).unwrap();
+        assert_eq!(RunManifest::load_from(td.path()).unwrap().build_info, None);
+    }
+}
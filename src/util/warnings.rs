@@ -0,0 +1,257 @@
+//! Structured warnings collected over a run and summarized at its end.
+//!
+//! Warning sites call `warn(category, message)` instead of `log::warn!`: the
+//! first `MAX_EXAMPLES` occurrences of a category are logged at WARN and kept
+//! as examples, later ones only counted (and logged at DEBUG). `classify` and
+//! `reuse` hold a `RunWarnings` guard that prints the de-duplicated summary and
+//! writes `warnings.json` when the run ends, failed runs included, so `doctor`
+//! can show it.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+pub const WARNINGS_FILE: &str = "warnings.json";
+/// Examples kept (and logged at WARN) per category.
+pub const MAX_EXAMPLES: usize = 5;
+
+// Categories used across the pipeline.
+pub const MALFORMED_PAF_LINE: &str = "malformed_paf_line";
+pub const MALFORMED_PAF_FILE: &str = "malformed_paf_file";
+pub const MALFORMED_BED_LINE: &str = "malformed_bed_line";
+pub const MALFORMED_BED_FILE: &str = "malformed_bed_file";
+pub const FASTA_CHANGED: &str = "fasta_changed_during_run";
+pub const SELF_EXCLUDED_CONTIG: &str = "self_excluded_contig";
+pub const IGNORED_OPTION: &str = "ignored_option";
+pub const TOOL_VERSION: &str = "tool_version";
+pub const WINDOW_CLAMPED: &str = "window_clamped";
+pub const INVALID_WINDOW: &str = "invalid_window";
+pub const SPARSE_EVIDENCE: &str = "sparse_evidence";
+pub const INTERVAL_CLIPPED: &str = "interval_clipped";
+pub const INTERPOLATED_LIFT: &str = "interpolated_lift";
+pub const EVIDENCE_PARAMS: &str = "evidence_params";
+pub const PAIR_COUNT: &str = "pair_count";
+pub const POST_SUMMARY: &str = "post_summary_failed";
+pub const ASSEMBLY_MISSING: &str = "assembly_missing";
+pub const STRAND_MISMATCH: &str = "reciprocal_strand_mismatch";
+pub const SELF_ASSEMBLY: &str = "mito_self_assembly";
+pub const IDENTITY_FALLBACK: &str = "identity_fallback";
+pub const RUN_LOCK: &str = "run_lock_replaced";
+pub const LOW_MAPPING_RATE: &str = "low_mapping_rate";
+pub const DEPTH_BASELINE: &str = "depth_baseline_fallback";
+
+/// One category: how often it fired and its first few messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WarningEntry {
+    pub category: String,
+    pub count: u64,
+    pub examples: Vec<String>,
+}
+
+/// All categories, most frequent first (serialized as `warnings.json`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WarningsReport {
+    pub warnings: Vec<WarningEntry>,
+}
+
+impl WarningsReport {
+    pub fn total(&self) -> u64 {
+        self.warnings.iter().map(|w| w.count).sum()
+    }
+
+    /// One line per category plus its examples; "" when there are none.
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        for w in &self.warnings {
+            let _ = writeln!(s, "  {} ×{}", w.category, w.count);
+            for e in &w.examples {
+                let _ = writeln!(s, "    - {e}");
+            }
+            let more = w.count.saturating_sub(w.examples.len() as u64);
+            if more > 0 {
+                let _ = writeln!(s, "    … and {more} more");
+            }
+        }
+        s
+    }
+
+    pub fn save_to(&self, dir: &Path) -> Result<()> {
+        let f = fs_err::File::create(dir.join(WARNINGS_FILE))?;
+        serde_json::to_writer_pretty(f, self)?;
+        Ok(())
+    }
+
+    /// `None` if the run directory has no `warnings.json`.
+    pub fn load_from(dir: &Path) -> Result<Option<Self>> {
+        let p = dir.join(WARNINGS_FILE);
+        if !p.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_reader(fs_err::File::open(p)?)?))
+    }
+}
+
+/// Aggregates warnings by category.
+#[derive(Debug)]
+pub struct Warnings {
+    entries: Mutex<BTreeMap<String, WarningEntry>>,
+    max_examples: usize,
+}
+
+impl Default for Warnings {
+    fn default() -> Self {
+        Self::with_max_examples(MAX_EXAMPLES)
+    }
+}
+
+impl Warnings {
+    pub fn with_max_examples(max_examples: usize) -> Self {
+        Self {
+            entries: Mutex::new(BTreeMap::new()),
+            max_examples,
+        }
+    }
+
+    /// Count one warning; true if its message was kept as an example.
+    pub fn record(&self, category: &str, message: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let e = entries
+            .entry(category.to_string())
+            .or_insert_with(|| WarningEntry {
+                category: category.to_string(),
+                count: 0,
+                examples: Vec::new(),
+            });
+        e.count += 1;
+        let keep = e.examples.len() < self.max_examples;
+        if keep {
+            e.examples.push(message.to_string());
+        }
+        keep
+    }
+
+    pub fn report(&self) -> WarningsReport {
+        let mut warnings: Vec<WarningEntry> =
+            self.entries.lock().unwrap().values().cloned().collect();
+        // stable: ties stay in category order
+        warnings.sort_by_key(|w| std::cmp::Reverse(w.count));
+        WarningsReport { warnings }
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+static GLOBAL: LazyLock<Warnings> = LazyLock::new(Warnings::default);
+
+/// Record a warning for the current run, logging it at WARN while the category
+/// still has room for examples.
+pub fn warn(category: &str, message: impl AsRef<str>) {
+    let message = message.as_ref();
+    if GLOBAL.record(category, message) {
+        log::warn!("{message}");
+    } else {
+        log::debug!("{message}");
+    }
+}
+
+/// Warnings recorded so far in the current run.
+pub fn current_report() -> WarningsReport {
+    GLOBAL.report()
+}
+
+/// Collects the warnings of one run; on drop, logs the summary and writes
+/// `warnings.json` into the run directory.
+#[must_use = "the summary is written when the guard is dropped"]
+pub struct RunWarnings {
+    out_dir: PathBuf,
+}
+
+impl RunWarnings {
+    /// Start a run: warnings recorded so far (an earlier run in this process)
+    /// are discarded.
+    pub fn begin(out_dir: &Path) -> Self {
+        GLOBAL.clear();
+        Self {
+            out_dir: out_dir.to_path_buf(),
+        }
+    }
+}
+
+impl Drop for RunWarnings {
+    fn drop(&mut self) {
+        let report = GLOBAL.report();
+        if report.warnings.is_empty() {
+            log::info!("no warnings");
+        } else {
+            log::warn!(
+                "{} warning(s) in {} categories (details in {}):\n{}",
+                report.total(),
+                report.warnings.len(),
+                self.out_dir.join(WARNINGS_FILE).display(),
+                report.to_text().trim_end()
+            );
+        }
+        if let Err(e) = report.save_to(&self.out_dir) {
+            log::error!("could not write {WARNINGS_FILE}: {e:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn aggregates_by_category_and_caps_examples() {
+        let w = Warnings::with_max_examples(3);
+        for i in 0..10 {
+            let kept = w.record(MALFORMED_PAF_LINE, &format!("x.paf:{i}: bad line"));
+            assert_eq!(kept, i < 3, "line {i}");
+        }
+        w.record(WINDOW_CLAMPED, "P000001: nuclear window clamped");
+        w.record(WINDOW_CLAMPED, "P000002: nuclear window clamped");
+
+        let r = w.report();
+        assert_eq!(r.total(), 12);
+        assert_eq!(r.warnings.len(), 2);
+        // most frequent first
+        let paf = &r.warnings[0];
+        assert_eq!((paf.category.as_str(), paf.count), (MALFORMED_PAF_LINE, 10));
+        assert_eq!(
+            paf.examples,
+            [
+                "x.paf:0: bad line",
+                "x.paf:1: bad line",
+                "x.paf:2: bad line"
+            ]
+        );
+        assert_eq!(r.warnings[1].count, 2);
+
+        let txt = r.to_text();
+        assert!(txt.contains("malformed_paf_line ×10"), "{txt}");
+        assert!(txt.contains("… and 7 more"), "{txt}");
+        assert!(!txt.contains("x.paf:3"), "{txt}");
+
+        w.clear();
+        assert!(w.report().warnings.is_empty());
+    }
+
+    #[test]
+    fn report_round_trips_through_run_dir() {
+        let td = TempDir::new().unwrap();
+        assert_eq!(WarningsReport::load_from(td.path()).unwrap(), None);
+        let w = Warnings::default();
+        w.record(SPARSE_EVIDENCE, "P000003: no reads");
+        let r = w.report();
+        r.save_to(td.path()).unwrap();
+        assert_eq!(WarningsReport::load_from(td.path()).unwrap(), Some(r));
+    }
+}
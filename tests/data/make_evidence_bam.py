@@ -0,0 +1,281 @@
+#!/usr/bin/env python3
+"""Write evidence.bam, evidence.bam.bai and evidence.sam, the fixture the
+native BAM backend (`--bam-backend native`) is checked against, and
+depth_nuc.bam and depth_mito.bam (with their .bai), the depth baselines'.
+
+The BAM is written by hand, block by block (BGZF) with its BAI, so the
+fixture needs only Python: reads on chr1 and chrM of every kind the evidence
+stage filters (secondary, supplementary, duplicate, QC-fail, low MAPQ,
+unmapped, placed and unplaced), CIGARs with clips, indels, splices and =/X
+runs, NM as C, S and i tags among others, and one read whose CIGAR is in a
+CG tag. evidence.sam holds the same records as the SAM lines `io::sam`
+renders them, for the tests to compare with. The depth BAMs tile their
+references evenly (10x nuclear, 100x mito) with three times that depth over
+a few candidate loci (DEPTH_HOT). Deterministic: rerunning it rewrites the
+same bytes.
+
+    python3 tests/data/make_evidence_bam.py
+"""
+
+import os
+import random
+import struct
+import zlib
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+REFS = [("chr1", 60_000), ("chrM", 16_569), ("chr2", 5_000)]
+DEPTH_NUC = [("chr1", 120_000), ("chr2", 40_000)]
+DEPTH_MITO = [("chrM", 16_569)]
+# [start, end) at three times the depth of the rest of the reference
+DEPTH_HOT = {"chr1": [(20_000, 24_000), (70_000, 74_000)], "chr2": [(10_000, 14_000)],
+             "chrM": [(3_000, 7_000)]}
+DEPTH_READ = 1_000
+BLOCK = 0xFF00
+OPS = "MIDNSHP=X"
+
+
+def reg2bin(beg, end):
+    end -= 1
+    for shift, off in ((14, 4681), (17, 585), (20, 73), (23, 9), (26, 1)):
+        if beg >> shift == end >> shift:
+            return off + (beg >> shift)
+    return 0
+
+
+def random_cigar(rng, ref_len):
+    """Ops covering about `ref_len` reference bases."""
+    ops = []
+    if rng.random() < 0.4:
+        ops.append((rng.randint(1, 2000), rng.choice("SH")))
+    left = ref_len
+    eqx = rng.random() < 0.15
+    while left > 0:
+        n = min(left, rng.randint(20, 3000))
+        if eqx:
+            ops.append((n, "="))
+            if left - n > 0:
+                ops.append((1, "X"))
+                left -= 1
+        else:
+            ops.append((n, "M"))
+        left -= n
+        if left <= 0:
+            break
+        r = rng.random()
+        if r < 0.3:
+            ops.append((rng.randint(1, 50), "I"))
+        elif r < 0.55:
+            d = min(left, rng.randint(1, 50))
+            ops.append((d, "D"))
+            left -= d
+        elif r < 0.6:
+            d = min(left, rng.randint(100, 1500))
+            ops.append((d, "N"))
+            left -= d
+    # never end on D/N: keep the alignment's last op a match
+    if ops[-1][1] in "DN":
+        ops.append((1, "M"))
+    if rng.random() < 0.4:
+        ops.append((rng.randint(1, 2000), rng.choice("SH")))
+    return ops
+
+
+def lens(ops):
+    ref = sum(n for n, o in ops if o in "MDN=X")
+    query = sum(n for n, o in ops if o in "MIS=X")
+    return ref, query
+
+
+def cigar_str(ops):
+    return "".join(f"{n}{o}" for n, o in ops) or "*"
+
+
+def make_reads(rng):
+    reads = []
+    for tid, (name, length) in enumerate(REFS[:2]):
+        n = 1200 if name == "chr1" else 800
+        for i in range(n):
+            span = rng.randint(300, 9000)
+            pos = rng.randint(0, length - 1)
+            span = min(span, length - pos)
+            ops = random_cigar(rng, span)
+            flag = rng.choice([0, 16])
+            r = rng.random()
+            if r < 0.06:
+                flag |= 0x100
+            elif r < 0.14:
+                flag |= 0x800
+            elif r < 0.18:
+                flag |= 0x400
+            elif r < 0.20:
+                flag |= 0x200
+            elif r < 0.23:
+                flag = 0x4
+                ops = []
+            mapq = rng.choice([0, 3, 19, 20, 30, 60, 60, 60])
+            r = rng.random()
+            nm = None if r < 0.1 else rng.randint(0, 400 if r < 0.7 else 70_000)
+            reads.append(dict(qname=f"{name}_r{i}", flag=flag, tid=tid, pos=pos,
+                              mapq=mapq, ops=ops, nm=nm, cg=False))
+    # a read with its CIGAR in a CG tag (SAM spec 4.2.2)
+    ops = []
+    for _ in range(150):
+        ops += [(rng.randint(5, 40), "M"), (rng.randint(1, 3), rng.choice("ID"))]
+    ops.append((30, "M"))
+    reads.append(dict(qname="chr1_cg", flag=0, tid=0, pos=15_500, mapq=60,
+                      ops=ops, nm=321, cg=True))
+    # unmapped and unplaced: after every placed read
+    for i in range(5):
+        reads.append(dict(qname=f"unplaced{i}", flag=0x4, tid=-1, pos=-1,
+                          mapq=0, ops=[], nm=None, cg=False))
+    reads.sort(key=lambda r: (r["tid"] < 0, r["tid"], r["pos"]))
+    return reads
+
+
+def nm_tag(rng, nm):
+    if nm is None:
+        return b""
+    kinds = [t for t, hi in (("C", 255), ("S", 65535), ("i", 2**31 - 1)) if nm <= hi]
+    kind = rng.choice(kinds)
+    return b"NM" + kind.encode() + struct.pack("<" + {"C": "B", "S": "H", "i": "i"}[kind], nm)
+
+
+def encode(rng, r):
+    ref_len, l_seq = lens(r["ops"])
+    bam_ops = r["ops"]
+    extra = b""
+    if r["cg"]:
+        extra = b"CGBI" + struct.pack("<I", len(r["ops"])) + b"".join(
+            struct.pack("<I", n << 4 | OPS.index(o)) for n, o in r["ops"])
+        bam_ops = [(l_seq, "S"), (ref_len, "N")]
+    end = r["pos"] + max(ref_len, 1)
+    bin_ = reg2bin(r["pos"], end) if r["pos"] >= 0 else 4680
+    name = r["qname"].encode() + b"\0"
+    body = struct.pack("<iiBBHHHIiii", r["tid"], r["pos"], len(name), r["mapq"], bin_,
+                       len(bam_ops), r["flag"], l_seq, -1, -1, 0)
+    body += name
+    body += b"".join(struct.pack("<I", n << 4 | OPS.index(o)) for n, o in bam_ops)
+    body += bytes([0x11] * ((l_seq + 1) // 2)) + b"\xff" * l_seq
+    # NM among other tags, before or after them
+    tags = [b"tpAP", b"deZ0.01\0", nm_tag(rng, r["nm"]), extra]
+    rng.shuffle(tags)
+    body += b"".join(tags)
+    return struct.pack("<i", len(body)) + body, end
+
+
+def bgzf_block(data):
+    c = zlib.compressobj(6, zlib.DEFLATED, -15)
+    cdata = c.compress(data) + c.flush()
+    header = struct.pack("<BBBBIBBHBBHH", 31, 139, 8, 4, 0, 0, 255, 6,
+                         ord("B"), ord("C"), 2, len(cdata) + 25)
+    return header + cdata + struct.pack("<II", zlib.crc32(data), len(data))
+
+
+def depth_reads(refs, step):
+    """Reads of DEPTH_READ bp every `step` bp of `refs`, clipped at their
+    ends, so every base is covered DEPTH_READ / step times, and twice more
+    over DEPTH_HOT."""
+    reads = []
+    for tid, (name, length) in enumerate(refs):
+        tilings = [(0, length)] + 2 * DEPTH_HOT.get(name, [])
+        for beg, end in tilings:
+            for s in range(beg - DEPTH_READ + step, end, step):
+                pos, fin = max(s, beg), min(s + DEPTH_READ, end)
+                reads.append(dict(qname=f"{name}_d{len(reads)}", flag=0, tid=tid, pos=pos,
+                                  mapq=60, ops=[(fin - pos, "M")], nm=None, cg=False))
+    reads.sort(key=lambda r: (r["tid"], r["pos"]))
+    return reads
+
+
+def write_bam(stem, refs, reads, rng):
+    """stem.bam and stem.bam.bai of the sorted `reads`; the header text."""
+    text = b"@HD\tVN:1.6\tSO:coordinate\n" + b"".join(
+        f"@SQ\tSN:{n}\tLN:{l}\n".encode() for n, l in refs)
+    header = b"BAM\1" + struct.pack("<i", len(text)) + text + struct.pack("<i", len(refs))
+    for n, l in refs:
+        header += struct.pack("<i", len(n) + 1) + n.encode() + b"\0" + struct.pack("<i", l)
+
+    stream = b""
+    spans = []  # (uncompressed start, end, read, alignment end)
+    for r in reads:
+        rec, end = encode(rng, r)
+        spans.append((len(stream), len(stream) + len(rec), r, end))
+        stream += rec
+
+    blocks = [bgzf_block(header)]
+    starts = []
+    coff = len(blocks[0])
+    for i in range(0, len(stream), BLOCK):
+        starts.append(coff)
+        blocks.append(bgzf_block(stream[i:i + BLOCK]))
+        coff += len(blocks[-1])
+    starts.append(coff)
+    blocks.append(bgzf_block(b""))  # EOF marker
+
+    def voff(u):
+        return starts[u // BLOCK] << 16 | u % BLOCK
+
+    with open(os.path.join(HERE, f"{stem}.bam"), "wb") as f:
+        f.write(b"".join(blocks))
+
+    # BAI: bins of chunks, merged when adjacent, a 16 kb linear index, and
+    # htslib's pseudo-bin 37450 with the mapped / unmapped counts
+    idx = [dict(bins={}, linear={}, beg=None, end=0, mapped=0, unmapped=0)
+           for _ in refs]
+    no_coor = 0
+    for ubeg, uend, r, end in spans:
+        if r["tid"] < 0:
+            no_coor += 1
+            continue
+        x = idx[r["tid"]]
+        beg, fin = voff(ubeg), voff(uend)
+        chunks = x["bins"].setdefault(reg2bin(r["pos"], end), [])
+        if chunks and chunks[-1][1] == beg:
+            chunks[-1][1] = fin
+        else:
+            chunks.append([beg, fin])
+        for w in range(r["pos"] >> 14, ((end - 1) >> 14) + 1):
+            x["linear"].setdefault(w, beg)
+        x["beg"] = beg if x["beg"] is None else x["beg"]
+        x["end"] = fin
+        x["mapped" if not r["flag"] & 0x4 else "unmapped"] += 1
+    bai = b"BAI\1" + struct.pack("<i", len(refs))
+    for x in idx:
+        bins = sorted(x["bins"].items())
+        if x["beg"] is not None:
+            bins.append((37450, [[x["beg"], x["end"]], [x["mapped"], x["unmapped"]]]))
+        bai += struct.pack("<i", len(bins))
+        for b, chunks in bins:
+            bai += struct.pack("<Ii", b, len(chunks))
+            bai += b"".join(struct.pack("<QQ", *c) for c in chunks)
+        n_intv = max(x["linear"], default=-1) + 1
+        bai += struct.pack("<i", n_intv)
+        last = 0
+        for w in range(n_intv):
+            last = x["linear"].get(w, last)
+            bai += struct.pack("<Q", last)
+    bai += struct.pack("<Q", no_coor)
+    with open(os.path.join(HERE, f"{stem}.bam.bai"), "wb") as f:
+        f.write(bai)
+    return text
+
+
+def main():
+    rng = random.Random(20261017)
+    reads = make_reads(rng)
+    text = write_bam("evidence", REFS, reads, rng)
+    with open(os.path.join(HERE, "evidence.sam"), "w") as f:
+        f.write(text.decode())
+        for r in reads:
+            rname = REFS[r["tid"]][0] if r["tid"] >= 0 else "*"
+            nm = "" if r["nm"] is None else f"\tNM:i:{r['nm']}"
+            f.write(f"{r['qname']}\t{r['flag']}\t{rname}\t{r['pos'] + 1}\t{r['mapq']}\t"
+                    f"{cigar_str(r['ops'])}\t*\t0\t0\t*\t*{nm}\n")
+
+    rng = random.Random(7742)
+    write_bam("depth_nuc", DEPTH_NUC, depth_reads(DEPTH_NUC, 100), rng)
+    write_bam("depth_mito", DEPTH_MITO, depth_reads(DEPTH_MITO, 10), rng)
+
+
+if __name__ == "__main__":
+    main()